@@ -68,6 +68,9 @@ pub enum BgpCapability {
     /// [RFC8950](https://datatracker.ietf.org/doc/html/rfc8950)
     ExtendedNextHopEncoding(ExtendedNextHopEncodingCapability),
 
+    /// [draft-ietf-idr-bgp-multisession](https://datatracker.ietf.org/doc/html/draft-ietf-idr-bgp-multisession)
+    Multisession(MultisessionCapability),
+
     Experimental(ExperimentalCapability),
 
     Unrecognized(UnrecognizedCapability),
@@ -87,6 +90,7 @@ impl BgpCapability {
             Self::BgpRole(_) => Ok(BgpCapabilityCode::BgpRole),
             Self::FourOctetAs(_) => Ok(BgpCapabilityCode::FourOctetAs),
             Self::ExtendedNextHopEncoding(_) => Ok(BgpCapabilityCode::ExtendedNextHopEncoding),
+            Self::Multisession(_) => Ok(BgpCapabilityCode::MultiSessionBgpCapability),
             Self::Experimental(value) => match value.code() {
                 ExperimentalCapabilityCode::Experimental239 => {
                     Ok(BgpCapabilityCode::Experimental239)
@@ -507,3 +511,38 @@ impl BgpRoleCapability {
         self.role
     }
 }
+
+/// Multiple BGP sessions between the same pair of speakers
+/// defined by: [draft-ietf-idr-bgp-multisession](https://datatracker.ietf.org/doc/html/draft-ietf-idr-bgp-multisession)
+/// ```text
+/// +--------------------------------+
+/// | Flags (1 octet)                |
+/// +--------------------------------+
+/// | Group IDs (1 octet each)       |
+/// +--------------------------------+
+/// ```
+/// Only the most significant bit of the flags, the Grouping bit, is defined.
+/// The other bits are ignored on receipt and sent as zero.
+#[derive(Debug, Hash, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct MultisessionCapability {
+    grouping: bool,
+    group_ids: Vec<u8>,
+}
+
+impl MultisessionCapability {
+    pub const fn new(grouping: bool, group_ids: Vec<u8>) -> Self {
+        Self {
+            grouping,
+            group_ids,
+        }
+    }
+
+    pub const fn grouping(&self) -> bool {
+        self.grouping
+    }
+
+    pub const fn group_ids(&self) -> &Vec<u8> {
+        &self.group_ids
+    }
+}
@@ -17,7 +17,10 @@
 
 #[cfg(feature = "fuzz")]
 use crate::{arbitrary_ipv4, arbitrary_ipv6};
-use crate::{iana::WellKnownCommunity, nlri::MacAddress};
+use crate::{
+    iana::{OriginValidationState, WellKnownCommunity},
+    nlri::MacAddress,
+};
 use serde::{Deserialize, Serialize};
 use std::net::{Ipv4Addr, Ipv6Addr};
 
@@ -571,7 +574,19 @@ impl ExtendedCommunityProperties for TransitiveOpaqueExtendedCommunity {
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 pub enum NonTransitiveOpaqueExtendedCommunity {
-    Unassigned { sub_type: u8, value: [u8; 6] },
+    /// BGP Prefix Origin Validation State Extended Community, it's a
+    /// non-transitive community carrying the route origin validation state
+    /// within an AS. The first five octets of the Value field are reserved
+    /// (set to 0 by the senders, ignored by the receivers).
+    /// [RFC8097](https://datatracker.ietf.org/doc/html/rfc8097)
+    BgpOriginValidationState {
+        validation_state: OriginValidationState,
+    },
+
+    Unassigned {
+        sub_type: u8,
+        value: [u8; 6],
+    },
 }
 
 impl ExtendedCommunityProperties for NonTransitiveOpaqueExtendedCommunity {
@@ -1279,6 +1279,63 @@ impl TryFrom<u8> for TransitiveOpaqueExtendedCommunitySubType {
     }
 }
 
+/// Non-Transitive Opaque Extended Community Sub-Types [IANA](https://www.iana.org/assignments/bgp-extended-communities/bgp-extended-communities.xhtml#non-trans-opaque)
+#[repr(u8)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub enum NonTransitiveOpaqueExtendedCommunitySubType {
+    /// [RFC8097](https://datatracker.ietf.org/doc/html/rfc8097)
+    BgpOriginValidationState = 0x00,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct UndefinedNonTransitiveOpaqueExtendedCommunitySubType(pub u8);
+
+impl TryFrom<u8> for NonTransitiveOpaqueExtendedCommunitySubType {
+    type Error = UndefinedNonTransitiveOpaqueExtendedCommunitySubType;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedNonTransitiveOpaqueExtendedCommunitySubType(value)),
+        }
+    }
+}
+
+/// Route origin validation states of the prefix origination in the BGP Prefix
+/// Origin Validation State Extended Community
+/// [RFC8097](https://datatracker.ietf.org/doc/html/rfc8097)
+#[repr(u8)]
+#[derive(Display, FromRepr, Hash, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub enum OriginValidationState {
+    /// At least one VRP matches the route
+    Valid = 0,
+
+    /// No VRP covers the route prefix
+    NotFound = 1,
+
+    /// The route prefix is covered by at least one VRP, but none matches the
+    /// route
+    Invalid = 2,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct UndefinedOriginValidationState(pub u8);
+
+impl TryFrom<u8> for OriginValidationState {
+    type Error = UndefinedOriginValidationState;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedOriginValidationState(value)),
+        }
+    }
+}
+
 /// BGP Role Values used in the route leak prevention and detection procedures
 /// [RFC9234](https://datatracker.ietf.org/doc/html/rfc9234)
 #[repr(u8)]
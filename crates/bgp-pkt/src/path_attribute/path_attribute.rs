@@ -365,6 +365,96 @@ pub enum AsPath {
     As4PathSegments(Vec<As4PathSegment>),
 }
 
+impl AsPath {
+    /// AS path length used in the route selection, see
+    /// [AsPathSegmentType::is_confed]
+    pub fn path_length(&self) -> usize {
+        match self {
+            Self::As2PathSegments(segments) => segments
+                .iter()
+                .map(|segment| {
+                    segment
+                        .segment_type()
+                        .path_length(segment.as_numbers().len())
+                })
+                .sum(),
+            Self::As4PathSegments(segments) => segments
+                .iter()
+                .map(|segment| {
+                    segment
+                        .segment_type()
+                        .path_length(segment.as_numbers().len())
+                })
+                .sum(),
+        }
+    }
+
+    /// Four-octet AS path of the route. For a two-octet `AS_PATH`, the AS
+    /// numbers are reconstructed with the `AS4_PATH` attribute as described in
+    /// [RFC6793](https://datatracker.ietf.org/doc/html/RFC6793#section-4.2.3):
+    /// the leading AS numbers of the `AS_PATH` that are not covered by the
+    /// `AS4_PATH` are prepended to it. The `AS4_PATH` is ignored when it's
+    /// longer than the `AS_PATH`.
+    pub fn effective_as4_path(&self, as4_path: Option<&As4Path>) -> Vec<As4PathSegment> {
+        let segments: Vec<As4PathSegment> = match self {
+            Self::As4PathSegments(segments) => return segments.clone(),
+            Self::As2PathSegments(segments) => segments
+                .iter()
+                .map(|segment| {
+                    As4PathSegment::new(
+                        segment.segment_type(),
+                        segment.as_numbers().iter().map(|asn| *asn as u32).collect(),
+                    )
+                })
+                .collect(),
+        };
+        let as4_segments: Vec<As4PathSegment> = match as4_path {
+            Some(as4_path) => as4_path
+                .segments()
+                .iter()
+                .filter(|segment| !segment.segment_type().is_confed())
+                .cloned()
+                .collect(),
+            None => return segments,
+        };
+        let as4_path_length: usize = as4_segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .segment_type()
+                    .path_length(segment.as_numbers().len())
+            })
+            .sum();
+        let mut leading = match self.path_length().checked_sub(as4_path_length) {
+            Some(leading) => leading,
+            None => return segments,
+        };
+        let mut effective = vec![];
+        for segment in segments {
+            match segment.segment_type() {
+                AsPathSegmentType::AsConfedSequence | AsPathSegmentType::AsConfedSet => {
+                    effective.push(segment)
+                }
+                _ if leading == 0 => break,
+                AsPathSegmentType::AsSet => {
+                    leading -= 1;
+                    effective.push(segment);
+                }
+                AsPathSegmentType::AsSequence => {
+                    let count = leading.min(segment.as_numbers().len());
+                    leading -= count;
+                    effective.push(As4PathSegment::new(
+                        AsPathSegmentType::AsSequence,
+                        segment.as_numbers()[..count].to_vec(),
+                    ));
+                }
+            }
+        }
+        effective.extend(as4_segments);
+        effective
+    }
+}
+
 impl PathAttributeValueProperties for AsPath {
     fn can_be_optional() -> Option<bool> {
         Some(false)
@@ -394,6 +484,34 @@ impl PathAttributeValueProperties for AsPath {
 pub enum AsPathSegmentType {
     AsSet = 1,
     AsSequence = 2,
+
+    /// Ordered set of Member AS Numbers in the local confederation that the
+    /// UPDATE message has traversed
+    /// [RFC5065](https://datatracker.ietf.org/doc/html/rfc5065)
+    AsConfedSequence = 3,
+
+    /// Unordered set of Member AS Numbers in the local confederation that the
+    /// UPDATE message has traversed
+    /// [RFC5065](https://datatracker.ietf.org/doc/html/rfc5065)
+    AsConfedSet = 4,
+}
+
+impl AsPathSegmentType {
+    /// The segment is only meaningful within a confederation
+    pub const fn is_confed(&self) -> bool {
+        matches!(self, Self::AsConfedSequence | Self::AsConfedSet)
+    }
+
+    /// Number of AS numbers counted in the AS path length used for route
+    /// selection: RFC4271 counts an `AS_SET` as 1 regardless of its size, and
+    /// RFC5065 excludes the confederation segments.
+    const fn path_length(&self, as_numbers: usize) -> usize {
+        match self {
+            Self::AsSequence => as_numbers,
+            Self::AsSet => 1,
+            Self::AsConfedSequence | Self::AsConfedSet => 0,
+        }
+    }
 }
 
 impl From<AsPathSegmentType> for u8 {
@@ -1417,6 +1535,76 @@ mod tests {
         assert_eq!(defined_u8, defined_code);
     }
 
+    #[test]
+    fn test_as_path_length() {
+        let as_path = AsPath::As4PathSegments(vec![
+            As4PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![65001, 65002]),
+            As4PathSegment::new(AsPathSegmentType::AsSequence, vec![100, 200]),
+            As4PathSegment::new(AsPathSegmentType::AsSet, vec![300, 400]),
+        ]);
+        let as2_path = AsPath::As2PathSegments(vec![
+            As2PathSegment::new(AsPathSegmentType::AsConfedSet, vec![65001]),
+            As2PathSegment::new(AsPathSegmentType::AsSequence, vec![100, 200, 300]),
+        ]);
+        assert_eq!(as_path.path_length(), 3);
+        assert_eq!(as2_path.path_length(), 3);
+        assert_eq!(AsPath::As4PathSegments(vec![]).path_length(), 0);
+    }
+
+    #[test]
+    fn test_effective_as4_path() {
+        let as2_path = AsPath::As2PathSegments(vec![
+            As2PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![65001]),
+            As2PathSegment::new(AsPathSegmentType::AsSequence, vec![100, 23456, 23456]),
+            As2PathSegment::new(AsPathSegmentType::AsSet, vec![300, 23456]),
+        ]);
+        let as4_path = As4Path::new(vec![
+            As4PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![65001]),
+            As4PathSegment::new(AsPathSegmentType::AsSequence, vec![200000, 200001]),
+            As4PathSegment::new(AsPathSegmentType::AsSet, vec![300, 200002]),
+        ]);
+        assert_eq!(
+            as2_path.effective_as4_path(Some(&as4_path)),
+            vec![
+                As4PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![65001]),
+                As4PathSegment::new(AsPathSegmentType::AsSequence, vec![100]),
+                As4PathSegment::new(AsPathSegmentType::AsSequence, vec![200000, 200001]),
+                As4PathSegment::new(AsPathSegmentType::AsSet, vec![300, 200002]),
+            ]
+        );
+        assert_eq!(
+            as2_path.effective_as4_path(None),
+            vec![
+                As4PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![65001]),
+                As4PathSegment::new(AsPathSegmentType::AsSequence, vec![100, 23456, 23456]),
+                As4PathSegment::new(AsPathSegmentType::AsSet, vec![300, 23456]),
+            ]
+        );
+
+        // AS4_PATH longer than the AS_PATH is ignored
+        let short_as2_path = AsPath::As2PathSegments(vec![As2PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![23456],
+        )]);
+        assert_eq!(
+            short_as2_path.effective_as4_path(Some(&as4_path)),
+            vec![As4PathSegment::new(
+                AsPathSegmentType::AsSequence,
+                vec![23456]
+            )]
+        );
+
+        // AS4_PATH is ignored with a four-octet AS_PATH
+        let as4_segments = vec![As4PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![100, 200],
+        )];
+        assert_eq!(
+            AsPath::As4PathSegments(as4_segments.clone()).effective_as4_path(Some(&as4_path)),
+            as4_segments
+        );
+    }
+
     #[test]
     fn test_path_attributes_well_known_mandatory() {
         assert!(!Origin::can_be_optional().unwrap_or(false));
@@ -38,7 +38,8 @@ use crate::{
         BGP_ROLE_CAPABILITY_LENGTH, ENHANCED_ROUTE_REFRESH_CAPABILITY_LENGTH,
         EXTENDED_MESSAGE_CAPABILITY_LENGTH, EXTENDED_NEXT_HOP_ENCODING_LENGTH,
         FOUR_OCTET_AS_CAPABILITY_LENGTH, GRACEFUL_RESTART_ADDRESS_FAMILY_LENGTH,
-        MULTI_PROTOCOL_EXTENSIONS_CAPABILITY_LENGTH, ROUTE_REFRESH_CAPABILITY_LENGTH,
+        MULTISESSION_CAPABILITY_MIN_LENGTH, MULTI_PROTOCOL_EXTENSIONS_CAPABILITY_LENGTH,
+        ROUTE_REFRESH_CAPABILITY_LENGTH,
     },
 };
 use netgauze_serde_macros::LocatedError;
@@ -67,6 +68,9 @@ pub enum BgpCapabilityParsingError {
     ),
     MultipleLabelError(#[from_located(module = "self")] MultipleLabelParsingError),
     BgpRoleCapabilityError(#[from_located(module = "self")] BgpRoleCapabilityParsingError),
+    MultisessionCapabilityError(
+        #[from_located(module = "self")] MultisessionCapabilityParsingError,
+    ),
 }
 
 fn parse_experimental_capability(
@@ -181,7 +185,8 @@ impl<'a> ReadablePdu<'a, LocatedBgpCapabilityParsingError<'a>> for BgpCapability
                     parse_unrecognized_capability(code.into(), buf)
                 }
                 BgpCapabilityCode::MultiSessionBgpCapability => {
-                    parse_unrecognized_capability(code.into(), buf)
+                    let (buf, cap) = parse_into_located(buf)?;
+                    Ok((buf, BgpCapability::Multisession(cap)))
                 }
                 BgpCapabilityCode::AddPathCapability => {
                     let (buf, cap) = parse_into_located(buf)?;
@@ -547,3 +552,33 @@ impl<'a> ReadablePdu<'a, LocatedBgpRoleCapabilityParsingError<'a>> for BgpRoleCa
         Ok((buf, BgpRoleCapability::new(role)))
     }
 }
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum MultisessionCapabilityParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    InvalidLength(u8),
+}
+
+impl<'a> ReadablePdu<'a, LocatedMultisessionCapabilityParsingError<'a>> for MultisessionCapability {
+    fn from_wire(
+        buf: Span<'a>,
+    ) -> IResult<Span<'a>, Self, LocatedMultisessionCapabilityParsingError<'a>> {
+        let (buf, length) = nom::combinator::map_res(be_u8, |length| {
+            if length < MULTISESSION_CAPABILITY_MIN_LENGTH {
+                Err(MultisessionCapabilityParsingError::InvalidLength(length))
+            } else {
+                Ok(length)
+            }
+        })(buf)?;
+        let (buf, flags) = be_u8(buf)?;
+        let (buf, group_ids) = nom::multi::count(
+            be_u8,
+            (length - MULTISESSION_CAPABILITY_MIN_LENGTH) as usize,
+        )(buf)?;
+        Ok((
+            buf,
+            MultisessionCapability::new(flags & 0x80 == 0x80, group_ids),
+        ))
+    }
+}
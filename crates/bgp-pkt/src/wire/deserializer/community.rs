@@ -696,10 +696,20 @@ impl<'a> ReadablePdu<'a, LocatedNonTransitiveOpaqueExtendedCommunityParsingError
                 ),
             )
         })?;
-        Ok((
-            buf,
-            NonTransitiveOpaqueExtendedCommunity::Unassigned { sub_type, value },
-        ))
+        let validation_state = match NonTransitiveOpaqueExtendedCommunitySubType::try_from(sub_type)
+        {
+            Ok(NonTransitiveOpaqueExtendedCommunitySubType::BgpOriginValidationState) => {
+                OriginValidationState::try_from(value[5]).ok()
+            }
+            Err(_) => None,
+        };
+        let community = match validation_state {
+            Some(validation_state) => {
+                NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState { validation_state }
+            }
+            None => NonTransitiveOpaqueExtendedCommunity::Unassigned { sub_type, value },
+        };
+        Ok((buf, community))
     }
 }
 
@@ -43,6 +43,9 @@ pub(crate) const GRACEFUL_RESTART_ADDRESS_FAMILY_LENGTH: u8 = 4;
 /// 1-octet length as defined by RFC9234
 pub(crate) const BGP_ROLE_CAPABILITY_LENGTH: u8 = 1;
 
+/// Multisession capability carries at least the flags octet
+pub(crate) const MULTISESSION_CAPABILITY_MIN_LENGTH: u8 = 1;
+
 /// Accumulated IGP Metric Length as defined in RFC7311
 pub(crate) const ACCUMULATED_IGP_METRIC: u16 = 11;
 
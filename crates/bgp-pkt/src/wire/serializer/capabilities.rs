@@ -18,8 +18,8 @@ use crate::{
     wire::{
         BGP_ROLE_CAPABILITY_LENGTH, ENHANCED_ROUTE_REFRESH_CAPABILITY_LENGTH,
         EXTENDED_MESSAGE_CAPABILITY_LENGTH, EXTENDED_NEXT_HOP_ENCODING_LENGTH,
-        FOUR_OCTET_AS_CAPABILITY_LENGTH, MULTI_PROTOCOL_EXTENSIONS_CAPABILITY_LENGTH,
-        ROUTE_REFRESH_CAPABILITY_LENGTH,
+        FOUR_OCTET_AS_CAPABILITY_LENGTH, MULTISESSION_CAPABILITY_MIN_LENGTH,
+        MULTI_PROTOCOL_EXTENSIONS_CAPABILITY_LENGTH, ROUTE_REFRESH_CAPABILITY_LENGTH,
     },
 };
 use byteorder::{NetworkEndian, WriteBytesExt};
@@ -37,6 +37,7 @@ pub enum BGPCapabilityWritingError {
     ExtendedNextHopEncodingCapabilityError(#[from] ExtendedNextHopEncodingCapabilityWritingError),
     MultipleLabelError(#[from] MultipleLabelWritingError),
     BgpRoleCapabilityError(#[from] BgpRoleCapabilityWritingError),
+    MultisessionCapabilityError(#[from] MultisessionCapabilityWritingError),
 }
 
 impl WritablePdu<BGPCapabilityWritingError> for BgpCapability {
@@ -60,6 +61,7 @@ impl WritablePdu<BGPCapabilityWritingError> for BgpCapability {
             Self::ExtendedMessage => EXTENDED_MESSAGE_CAPABILITY_LENGTH as usize,
             Self::MultipleLabels(value) => value.iter().map(|x| x.len()).sum(),
             Self::BgpRole(value) => value.len(),
+            Self::Multisession(value) => value.len(),
             Self::Experimental(value) => value.value().len(),
             Self::Unrecognized(value) => value.value().len(),
         };
@@ -101,6 +103,11 @@ impl WritablePdu<BGPCapabilityWritingError> for BgpCapability {
                 writer.write_u8(len)?;
                 value.write(writer)?;
             }
+            Self::Multisession(value) => {
+                writer.write_u8(self.code().unwrap().into())?;
+                writer.write_u8(len)?;
+                value.write(writer)?;
+            }
             Self::GracefulRestartCapability(value) => {
                 writer.write_u8(self.code().unwrap().into())?;
                 writer.write_u8(len)?;
@@ -344,3 +351,22 @@ impl WritablePdu<BgpRoleCapabilityWritingError> for BgpRoleCapability {
         Ok(())
     }
 }
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum MultisessionCapabilityWritingError {
+    StdIOError(#[from_std_io_error] String),
+}
+
+impl WritablePdu<MultisessionCapabilityWritingError> for MultisessionCapability {
+    // 1 octet flags followed by the group IDs
+    const BASE_LENGTH: usize = MULTISESSION_CAPABILITY_MIN_LENGTH as usize;
+    fn len(&self) -> usize {
+        Self::BASE_LENGTH + self.group_ids().len()
+    }
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), MultisessionCapabilityWritingError> {
+        let flags = if self.grouping() { 0x80 } else { 0x00 };
+        writer.write_u8(flags)?;
+        writer.write_all(self.group_ids())?;
+        Ok(())
+    }
+}
@@ -17,9 +17,10 @@ use crate::{
     community::*,
     iana::{
         BgpExtendedCommunityIpv6Type, BgpExtendedCommunityType, EvpnExtendedCommunitySubType,
-        NonTransitiveTwoOctetExtendedCommunitySubType, TransitiveFourOctetExtendedCommunitySubType,
-        TransitiveIpv4ExtendedCommunitySubType, TransitiveIpv6ExtendedCommunitySubType,
-        TransitiveOpaqueExtendedCommunitySubType, TransitiveTwoOctetExtendedCommunitySubType,
+        NonTransitiveOpaqueExtendedCommunitySubType, NonTransitiveTwoOctetExtendedCommunitySubType,
+        TransitiveFourOctetExtendedCommunitySubType, TransitiveIpv4ExtendedCommunitySubType,
+        TransitiveIpv6ExtendedCommunitySubType, TransitiveOpaqueExtendedCommunitySubType,
+        TransitiveTwoOctetExtendedCommunitySubType,
     },
     wire::serializer::nlri::MacAddressWritingError,
 };
@@ -740,6 +741,13 @@ impl WritablePdu<NonTransitiveOpaqueExtendedCommunityWritingError>
         writer: &mut T,
     ) -> Result<(), NonTransitiveOpaqueExtendedCommunityWritingError> {
         match self {
+            Self::BgpOriginValidationState { validation_state } => {
+                writer.write_u8(
+                    NonTransitiveOpaqueExtendedCommunitySubType::BgpOriginValidationState as u8,
+                )?;
+                writer.write_all(&[0; 5])?;
+                writer.write_u8(*validation_state as u8)?;
+            }
             Self::Unassigned { sub_type, value } => {
                 writer.write_u8(*sub_type)?;
                 writer.write_all(value)?;
@@ -380,6 +380,39 @@ fn test_bgp_role_capability() -> Result<(), BGPCapabilityWritingError> {
     test_write(&good, &good_wire)?;
     Ok(())
 }
+#[test]
+fn test_multisession_capability() -> Result<(), BGPCapabilityWritingError> {
+    let good_wire = [68, 1, 0x00];
+    let good_grouping_wire = [68, 3, 0x80, 1, 2];
+    let reserved_flags_wire = [68, 2, 0x7f, 3];
+    let invalid_length_wire = [68, 0];
+
+    let good = BgpCapability::Multisession(MultisessionCapability::new(false, vec![]));
+    let good_grouping = BgpCapability::Multisession(MultisessionCapability::new(true, vec![1, 2]));
+    let reserved_flags = BgpCapability::Multisession(MultisessionCapability::new(false, vec![3]));
+
+    let invalid_length = LocatedBgpCapabilityParsingError::new(
+        unsafe { Span::new_from_raw_offset(1, &invalid_length_wire[1..]) },
+        BgpCapabilityParsingError::MultisessionCapabilityError(
+            MultisessionCapabilityParsingError::InvalidLength(0),
+        ),
+    );
+
+    test_parsed_completely(&good_wire, &good);
+    test_parsed_completely(&good_grouping_wire, &good_grouping);
+    // Reserved flags are ignored
+    test_parsed_completely(&reserved_flags_wire, &reserved_flags);
+    test_parse_error::<BgpCapability, LocatedBgpCapabilityParsingError<'_>>(
+        &invalid_length_wire,
+        &invalid_length,
+    );
+
+    test_write(&good, &good_wire)?;
+    test_write(&good_grouping, &good_grouping_wire)?;
+    test_write(&reserved_flags, &[68, 2, 0x00, 3])?;
+    Ok(())
+}
+
 #[test]
 fn test_experimental_capabilities() -> Result<(), BGPCapabilityWritingError> {
     // IANA defines the codes 239-254 as reserved for Experimental Use
@@ -15,6 +15,7 @@
 
 use crate::{
     community::*,
+    iana::OriginValidationState,
     nlri::MacAddress,
     wire::{
         deserializer::community::{CommunityParsingError, LocatedCommunityParsingError},
@@ -106,6 +107,35 @@ fn test_transitive_ipv4_extended_community(
     Ok(())
 }
 
+#[test]
+fn test_non_transitive_opaque_extended_community(
+) -> Result<(), NonTransitiveOpaqueExtendedCommunityWritingError> {
+    let good_invalid_wire = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02];
+    let good_unassigned_wire = [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02];
+    // Undefined validation states are kept as is
+    let good_undefined_state_wire = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03];
+
+    let good_invalid = NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState {
+        validation_state: OriginValidationState::Invalid,
+    };
+    let good_unassigned = NonTransitiveOpaqueExtendedCommunity::Unassigned {
+        sub_type: 0x01,
+        value: [0x00, 0x00, 0x00, 0x00, 0x00, 0x02],
+    };
+    let good_undefined_state = NonTransitiveOpaqueExtendedCommunity::Unassigned {
+        sub_type: 0x00,
+        value: [0x00, 0x00, 0x00, 0x00, 0x00, 0x03],
+    };
+
+    test_parsed_completely(&good_invalid_wire, &good_invalid);
+    test_parsed_completely(&good_unassigned_wire, &good_unassigned);
+    test_parsed_completely(&good_undefined_state_wire, &good_undefined_state);
+    test_write(&good_invalid, &good_invalid_wire)?;
+    test_write(&good_unassigned, &good_unassigned_wire)?;
+    test_write(&good_undefined_state, &good_undefined_state_wire)?;
+    Ok(())
+}
+
 #[test]
 fn test_evpn_extended_community() -> Result<(), EvpnExtendedCommunityWritingError> {
     let mac_mobility_wire = [0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00];
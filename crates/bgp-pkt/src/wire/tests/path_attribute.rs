@@ -180,12 +180,16 @@ fn test_path_attribute_origin() -> Result<(), PathAttributeWritingError> {
 fn test_as2_path_segment() -> Result<(), AsPathWritingError> {
     let good_set_wire = [0x01, 0x01, 0x00, 0x01];
     let good_seq_wire = [0x02, 0x01, 0x00, 0x01];
+    let good_confed_seq_wire = [0x03, 0x01, 0x00, 0x01];
+    let good_confed_set_wire = [0x04, 0x01, 0x00, 0x01];
     let bad_empty_wire = [0x01, 0x00];
     let bad_undefined_segment_type_wire = [0x00, 0x01, 0x00, 0x01];
     let bad_incomplete_wire = [0x01, 0x01, 0x00];
 
     let set = As2PathSegment::new(AsPathSegmentType::AsSet, vec![1]);
     let seq = As2PathSegment::new(AsPathSegmentType::AsSequence, vec![1]);
+    let confed_seq = As2PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![1]);
+    let confed_set = As2PathSegment::new(AsPathSegmentType::AsConfedSet, vec![1]);
 
     let bad_empty = LocatedAsPathParsingError::new(
         unsafe { Span::new_from_raw_offset(1, &bad_empty_wire[1..]) },
@@ -203,6 +207,8 @@ fn test_as2_path_segment() -> Result<(), AsPathWritingError> {
 
     test_parsed_completely(&good_set_wire, &set);
     test_parsed_completely(&good_seq_wire, &seq);
+    test_parsed_completely(&good_confed_seq_wire, &confed_seq);
+    test_parsed_completely(&good_confed_set_wire, &confed_set);
     test_parse_error::<As2PathSegment, LocatedAsPathParsingError<'_>>(&bad_empty_wire, &bad_empty);
     test_parse_error::<As2PathSegment, LocatedAsPathParsingError<'_>>(
         &bad_undefined_segment_type_wire,
@@ -215,6 +221,8 @@ fn test_as2_path_segment() -> Result<(), AsPathWritingError> {
 
     test_write(&set, &good_set_wire)?;
     test_write(&seq, &good_seq_wire)?;
+    test_write(&confed_seq, &good_confed_seq_wire)?;
+    test_write(&confed_set, &good_confed_set_wire)?;
     Ok(())
 }
 
@@ -222,11 +230,13 @@ fn test_as2_path_segment() -> Result<(), AsPathWritingError> {
 fn test_as4_path_segment() -> Result<(), AsPathWritingError> {
     let good_set_wire = [0x01, 0x01, 0x00, 0x00, 0x00, 0x01];
     let good_seq_wire = [0x02, 0x01, 0x00, 0x00, 0x00, 0x01];
+    let good_confed_seq_wire = [0x03, 0x01, 0x00, 0x00, 0x00, 0x01];
     let bad_empty_wire = [0x01, 0x00];
     let undefined_segment_type_wire = [0x00, 0x01, 0x00, 0x00, 0x00, 0x01];
 
     let set = As4PathSegment::new(AsPathSegmentType::AsSet, vec![1]);
     let seq = As4PathSegment::new(AsPathSegmentType::AsSequence, vec![1]);
+    let confed_seq = As4PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![1]);
 
     let bad_empty = LocatedAsPathParsingError::new(
         unsafe { Span::new_from_raw_offset(1, &bad_empty_wire[1..]) },
@@ -240,6 +250,7 @@ fn test_as4_path_segment() -> Result<(), AsPathWritingError> {
 
     test_parsed_completely(&good_set_wire, &set);
     test_parsed_completely(&good_seq_wire, &seq);
+    test_parsed_completely(&good_confed_seq_wire, &confed_seq);
 
     test_parse_error::<As4PathSegment, LocatedAsPathParsingError<'_>>(&bad_empty_wire, &bad_empty);
     test_parse_error::<As4PathSegment, LocatedAsPathParsingError<'_>>(
@@ -249,6 +260,7 @@ fn test_as4_path_segment() -> Result<(), AsPathWritingError> {
 
     test_write(&set, &good_set_wire)?;
     test_write(&seq, &good_seq_wire)?;
+    test_write(&confed_seq, &good_confed_seq_wire)?;
     Ok(())
 }
 
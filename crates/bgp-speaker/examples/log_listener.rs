@@ -15,14 +15,14 @@
 
 use clap::Parser;
 use std::{
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     vec,
 };
 use tokio::net::TcpStream;
 
 use netgauze_bgp_speaker::{
     connection::TcpActiveConnect,
-    listener::BgpListener,
+    listener::{BgpListener, SessionKey},
     peer::{EchoCapabilitiesPolicy, PeerConfigBuilder, PeerProperties},
     peer_controller::PeerHandle,
     supervisor::PeersSupervisor,
@@ -40,7 +40,7 @@ fn create_peer(
     peer_asn: u32,
     my_bgp_id: Ipv4Addr,
     peer_addr: SocketAddr,
-    supervisor: &mut PeersSupervisor<IpAddr, SocketAddr, TcpStream>,
+    supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
 ) -> PeerHandle<SocketAddr, TcpStream> {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(1)
@@ -57,7 +57,13 @@ fn create_peer(
     let properties = PeerProperties::new(my_asn, peer_asn, my_bgp_id, peer_addr, true);
 
     let (mut received_rx, peer_handle) = supervisor
-        .create_peer(peer_addr.ip(), properties, config, TcpActiveConnect, policy)
+        .create_peer(
+            SessionKey::new(peer_addr.ip()),
+            properties,
+            config,
+            TcpActiveConnect::new(),
+            policy,
+        )
         .unwrap();
     peer_handle.start().unwrap();
     tokio::spawn(async move {
@@ -94,7 +100,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
     let peer_asn = 100;
     let peer_addr: SocketAddr = "192.168.56.10:179".parse().unwrap();
     let peer_handle = create_peer(my_asn, peer_asn, my_bgp_id, peer_addr, &mut supervisor);
-    listener.reg_peer(peer_addr.ip(), peer_handle.clone());
+    listener.reg_peer(SessionKey::new(peer_addr.ip()), peer_handle.clone());
 
     listener.run(&mut supervisor).await?;
     Ok(())
@@ -0,0 +1,537 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authentication of the TCP sessions carrying BGP using either TCP MD5
+//! Signature [RFC2385](https://datatracker.ietf.org/doc/html/rfc2385) or the
+//! TCP Authentication Option (TCP-AO)
+//! [RFC5925](https://datatracker.ietf.org/doc/html/rfc5925).
+//!
+//! Keys are installed on the sockets using the Linux specific socket options,
+//! on other platforms an [io::ErrorKind::Unsupported] error is returned.
+//! TCP-AO requires Linux 6.7 or newer.
+
+use std::{collections::HashMap, io, net::IpAddr, sync::Arc};
+
+use tokio::sync::watch;
+
+use crate::listener::SessionKey;
+
+/// Max key length allowed by the Linux kernel for both TCP MD5 and TCP-AO
+pub const TCP_AUTH_MAX_KEY_LEN: usize = 80;
+
+/// Authentication used for the TCP session with a given peer
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TcpAuth {
+    /// TCP MD5 Signature option with the given password
+    Md5(Vec<u8>),
+
+    /// TCP Authentication Option with the given key chain
+    Ao(TcpAoKeyChain),
+}
+
+/// MAC algorithms for TCP-AO defined in
+/// [RFC5926](https://datatracker.ietf.org/doc/html/rfc5926)
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, strum_macros::Display)]
+pub enum TcpAoAlgorithm {
+    HmacSha1,
+    AesCmac128,
+}
+
+impl TcpAoAlgorithm {
+    /// Algorithm name as expected by the Linux kernel crypto API
+    pub const fn kernel_name(&self) -> &'static str {
+        match self {
+            Self::HmacSha1 => "hmac(sha1)",
+            Self::AesCmac128 => "cmac(aes128)",
+        }
+    }
+}
+
+/// A single Master Key Tuple (MKT) of TCP-AO
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TcpAoKey {
+    send_id: u8,
+    recv_id: u8,
+    algorithm: TcpAoAlgorithm,
+    key: Vec<u8>,
+}
+
+impl TcpAoKey {
+    pub const fn new(send_id: u8, recv_id: u8, algorithm: TcpAoAlgorithm, key: Vec<u8>) -> Self {
+        Self {
+            send_id,
+            recv_id,
+            algorithm,
+            key,
+        }
+    }
+
+    pub const fn send_id(&self) -> u8 {
+        self.send_id
+    }
+
+    pub const fn recv_id(&self) -> u8 {
+        self.recv_id
+    }
+
+    pub const fn algorithm(&self) -> TcpAoAlgorithm {
+        self.algorithm
+    }
+
+    pub const fn key(&self) -> &Vec<u8> {
+        &self.key
+    }
+}
+
+/// TCP-AO key chain, the key identified by `current_send_id` is used to sign
+/// outgoing segments, while all the keys in the chain are accepted for the
+/// incoming segments. Keys are rotated by adding the new key to the chain,
+/// moving `current_send_id` to it and later removing the old key.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TcpAoKeyChain {
+    keys: Vec<TcpAoKey>,
+    current_send_id: u8,
+}
+
+impl TcpAoKeyChain {
+    pub const fn new(keys: Vec<TcpAoKey>, current_send_id: u8) -> Self {
+        Self {
+            keys,
+            current_send_id,
+        }
+    }
+
+    pub const fn keys(&self) -> &Vec<TcpAoKey> {
+        &self.keys
+    }
+
+    pub const fn current_send_id(&self) -> u8 {
+        self.current_send_id
+    }
+}
+
+/// Registry of the TCP authentication keys indexed by the [SessionKey] of the
+/// peer, so sessions to the same peer address from different local addresses
+/// or instances can use different keys. The registry is cheap to clone and
+/// changes are published to all the subscribers, such as
+/// [crate::listener::BgpListener], to update their sockets.
+#[derive(Debug, Clone)]
+pub struct TcpAuthKeys {
+    tx: Arc<watch::Sender<HashMap<SessionKey, TcpAuth>>>,
+}
+
+impl Default for TcpAuthKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcpAuthKeys {
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(HashMap::new());
+        Self { tx: Arc::new(tx) }
+    }
+
+    pub fn get(&self, session_key: &SessionKey) -> Option<TcpAuth> {
+        self.tx.borrow().get(session_key).cloned()
+    }
+
+    /// Find the key for a session from `local_ip` to `peer_ip`, a key
+    /// registered with the exact local address is preferred over a key
+    /// registered without one.
+    pub fn lookup(
+        &self,
+        instance: u32,
+        local_ip: Option<IpAddr>,
+        peer_ip: IpAddr,
+    ) -> Option<TcpAuth> {
+        session_tcp_auth(&self.tx.borrow(), instance, local_ip, peer_ip).cloned()
+    }
+
+    /// Set or replace the key for a given session, returns the previous key if
+    /// any
+    pub fn insert(&self, session_key: SessionKey, auth: TcpAuth) -> Option<TcpAuth> {
+        let mut previous = None;
+        self.tx.send_if_modified(|keys| {
+            previous = keys.insert(session_key, auth.clone());
+            previous.as_ref() != Some(&auth)
+        });
+        previous
+    }
+
+    pub fn remove(&self, session_key: &SessionKey) -> Option<TcpAuth> {
+        let mut previous = None;
+        self.tx.send_if_modified(|keys| {
+            previous = keys.remove(session_key);
+            previous.is_some()
+        });
+        previous
+    }
+
+    pub fn snapshot(&self) -> HashMap<SessionKey, TcpAuth> {
+        self.tx.borrow().clone()
+    }
+
+    /// Subscribe to changes in the keys
+    pub fn subscribe(&self) -> watch::Receiver<HashMap<SessionKey, TcpAuth>> {
+        self.tx.subscribe()
+    }
+}
+
+/// See [TcpAuthKeys::lookup]
+fn session_tcp_auth(
+    keys: &HashMap<SessionKey, TcpAuth>,
+    instance: u32,
+    local_ip: Option<IpAddr>,
+    peer_ip: IpAddr,
+) -> Option<&TcpAuth> {
+    let key = SessionKey::new(peer_ip.to_canonical()).with_instance(instance);
+    local_ip
+        .and_then(|local_ip| keys.get(&key.with_local_ip(Some(local_ip.to_canonical()))))
+        .or_else(|| keys.get(&key))
+}
+
+/// The keys to install on a listening socket bound to `local_ip` in the given
+/// instance, indexed by the peer address. The kernel matches the keys only by
+/// the peer address, hence a listening socket bound to the unspecified address
+/// uses the keys registered without a local address, or else the key of the
+/// lowest local address registered for the peer.
+pub fn listener_tcp_auth(
+    keys: &HashMap<SessionKey, TcpAuth>,
+    instance: u32,
+    local_ip: IpAddr,
+) -> HashMap<IpAddr, TcpAuth> {
+    let local_ip = local_ip.to_canonical();
+    let mut sessions: Vec<&SessionKey> = keys
+        .keys()
+        .filter(|key| key.instance() == instance)
+        .filter(|key| {
+            local_ip.is_unspecified()
+                || key.local_ip().is_none()
+                || key.local_ip() == Some(local_ip)
+        })
+        .collect();
+    sessions.sort();
+    let mut socket_keys = HashMap::new();
+    for session_key in sessions {
+        // Keys without a local address are sorted first
+        let auth = if local_ip.is_unspecified() {
+            keys.get(session_key)
+        } else {
+            session_tcp_auth(keys, instance, Some(local_ip), session_key.peer_ip())
+        };
+        if let Some(auth) = auth {
+            socket_keys
+                .entry(session_key.peer_ip())
+                .or_insert_with(|| auth.clone());
+        }
+    }
+    socket_keys
+}
+
+/// Replace the authentication keys installed on a socket for the given peer.
+/// `previous` is the authentication currently installed on the socket (if
+/// any) and `auth` is the new one to install (or `None` to remove the keys).
+///
+/// For TCP-AO key chains, new keys are added first, then the current key is
+/// switched, and finally the keys that are not in the chain anymore are
+/// removed. Thus, established sessions keep working during the rotation.
+#[cfg(target_os = "linux")]
+pub fn update_tcp_auth<S: std::os::fd::AsRawFd>(
+    socket: &S,
+    peer_ip: IpAddr,
+    previous: Option<&TcpAuth>,
+    auth: Option<&TcpAuth>,
+) -> io::Result<()> {
+    let fd = socket.as_raw_fd();
+    match (previous, auth) {
+        (Some(TcpAuth::Ao(previous)), Some(TcpAuth::Ao(chain))) => {
+            linux::update_tcp_ao_chain(fd, peer_ip, previous, chain)
+        }
+        (previous, auth) => {
+            match previous {
+                Some(TcpAuth::Md5(_)) => linux::set_tcp_md5sig(fd, peer_ip, &[])?,
+                Some(TcpAuth::Ao(previous)) => {
+                    for key in previous.keys() {
+                        linux::del_tcp_ao_key(fd, peer_ip, key)?;
+                    }
+                }
+                None => {}
+            }
+            match auth {
+                Some(TcpAuth::Md5(password)) => linux::set_tcp_md5sig(fd, peer_ip, password),
+                Some(TcpAuth::Ao(chain)) => linux::update_tcp_ao_chain(
+                    fd,
+                    peer_ip,
+                    &TcpAoKeyChain::new(vec![], chain.current_send_id()),
+                    chain,
+                ),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn update_tcp_auth<S>(
+    _socket: &S,
+    _peer_ip: IpAddr,
+    _previous: Option<&TcpAuth>,
+    auth: Option<&TcpAuth>,
+) -> io::Result<()> {
+    match auth {
+        None => Ok(()),
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TCP authentication is only supported on Linux",
+        )),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{io, mem, net::IpAddr, os::fd::RawFd};
+
+    use super::{TcpAoKey, TcpAoKeyChain, TCP_AUTH_MAX_KEY_LEN};
+
+    const TCP_AO_ADD_KEY: libc::c_int = 38;
+    const TCP_AO_DEL_KEY: libc::c_int = 39;
+    const TCP_AO_INFO: libc::c_int = 40;
+
+    /// `struct tcp_md5sig` from `linux/tcp.h`
+    #[repr(C)]
+    struct TcpMd5Sig {
+        addr: libc::sockaddr_storage,
+        flags: u8,
+        prefix_len: u8,
+        key_len: u16,
+        ifindex: libc::c_int,
+        key: [u8; TCP_AUTH_MAX_KEY_LEN],
+    }
+
+    /// `struct tcp_ao_add` from `linux/tcp.h`
+    #[repr(C, align(8))]
+    struct TcpAoAdd {
+        addr: libc::sockaddr_storage,
+        alg_name: [u8; 64],
+        ifindex: i32,
+        /// bit 0: set_current, bit 1: set_rnext
+        flags: u32,
+        reserved2: u16,
+        prefix: u8,
+        send_id: u8,
+        recv_id: u8,
+        mac_len: u8,
+        key_flags: u8,
+        key_len: u8,
+        key: [u8; TCP_AUTH_MAX_KEY_LEN],
+    }
+
+    /// `struct tcp_ao_del` from `linux/tcp.h`
+    #[repr(C, align(8))]
+    struct TcpAoDel {
+        addr: libc::sockaddr_storage,
+        ifindex: i32,
+        /// bit 0: set_current, bit 1: set_rnext, bit 2: del_async
+        flags: u32,
+        reserved2: u16,
+        prefix: u8,
+        send_id: u8,
+        recv_id: u8,
+        current_key: u8,
+        rnext: u8,
+        key_flags: u8,
+    }
+
+    /// Prefix of `struct tcp_ao_info_opt` from `linux/tcp.h`, the packet
+    /// counters are not used.
+    #[repr(C, align(8))]
+    struct TcpAoInfoOpt {
+        /// bit 0: set_current, bit 1: set_rnext
+        flags: u32,
+        reserved2: u16,
+        current_key: u8,
+        rnext: u8,
+        pkt_good: u64,
+        pkt_bad: u64,
+        pkt_key_not_found: u64,
+        pkt_ao_required: u64,
+        pkt_dropped_icmp: u64,
+    }
+
+    const SET_CURRENT: u32 = 1;
+    const SET_RNEXT: u32 = 1 << 1;
+
+    fn sockaddr_storage(ip: IpAddr) -> libc::sockaddr_storage {
+        // SAFETY: all zeros is a valid value for `sockaddr_storage`
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        match ip {
+            IpAddr::V4(ip) => {
+                // SAFETY: `sockaddr_storage` is large enough and aligned for `sockaddr_in`
+                let addr = unsafe {
+                    &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in>()
+                };
+                addr.sin_family = libc::AF_INET as libc::sa_family_t;
+                addr.sin_addr.s_addr = u32::from_ne_bytes(ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                // SAFETY: `sockaddr_storage` is large enough and aligned for `sockaddr_in6`
+                let addr = unsafe {
+                    &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+                };
+                addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                addr.sin6_addr.s6_addr = ip.octets();
+            }
+        }
+        storage
+    }
+
+    fn prefix_len(ip: IpAddr) -> u8 {
+        match ip {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    fn check_key_len(key: &[u8]) -> io::Result<()> {
+        if key.len() > TCP_AUTH_MAX_KEY_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "TCP authentication key length {} exceeds max length {TCP_AUTH_MAX_KEY_LEN}",
+                    key.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn setsockopt<T>(fd: RawFd, name: libc::c_int, value: &T) -> io::Result<()> {
+        // SAFETY: value is a valid reference of a `repr(C)` struct with the given size
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::IPPROTO_TCP,
+                name,
+                (value as *const T).cast(),
+                mem::size_of::<T>() as libc::socklen_t,
+            )
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Set the TCP MD5 password for a given peer, empty key removes it
+    pub(super) fn set_tcp_md5sig(fd: RawFd, peer_ip: IpAddr, key: &[u8]) -> io::Result<()> {
+        check_key_len(key)?;
+        let mut md5sig = TcpMd5Sig {
+            addr: sockaddr_storage(peer_ip),
+            flags: 0,
+            prefix_len: 0,
+            key_len: key.len() as u16,
+            ifindex: 0,
+            key: [0; TCP_AUTH_MAX_KEY_LEN],
+        };
+        md5sig.key[..key.len()].copy_from_slice(key);
+        setsockopt(fd, libc::TCP_MD5SIG, &md5sig)
+    }
+
+    fn add_tcp_ao_key(
+        fd: RawFd,
+        peer_ip: IpAddr,
+        key: &TcpAoKey,
+        set_current: bool,
+    ) -> io::Result<()> {
+        check_key_len(key.key())?;
+        let mut add = TcpAoAdd {
+            addr: sockaddr_storage(peer_ip),
+            alg_name: [0; 64],
+            ifindex: 0,
+            flags: if set_current {
+                SET_CURRENT | SET_RNEXT
+            } else {
+                0
+            },
+            reserved2: 0,
+            prefix: prefix_len(peer_ip),
+            send_id: key.send_id(),
+            recv_id: key.recv_id(),
+            mac_len: 0,
+            key_flags: 0,
+            key_len: key.key().len() as u8,
+            key: [0; TCP_AUTH_MAX_KEY_LEN],
+        };
+        let alg_name = key.algorithm().kernel_name().as_bytes();
+        add.alg_name[..alg_name.len()].copy_from_slice(alg_name);
+        add.key[..key.key().len()].copy_from_slice(key.key());
+        setsockopt(fd, TCP_AO_ADD_KEY, &add)
+    }
+
+    pub(super) fn del_tcp_ao_key(fd: RawFd, peer_ip: IpAddr, key: &TcpAoKey) -> io::Result<()> {
+        let del = TcpAoDel {
+            addr: sockaddr_storage(peer_ip),
+            ifindex: 0,
+            flags: 0,
+            reserved2: 0,
+            prefix: prefix_len(peer_ip),
+            send_id: key.send_id(),
+            recv_id: key.recv_id(),
+            current_key: 0,
+            rnext: 0,
+            key_flags: 0,
+        };
+        setsockopt(fd, TCP_AO_DEL_KEY, &del)
+    }
+
+    fn set_tcp_ao_current(fd: RawFd, key: &TcpAoKey) -> io::Result<()> {
+        let info = TcpAoInfoOpt {
+            flags: SET_CURRENT | SET_RNEXT,
+            reserved2: 0,
+            current_key: key.send_id(),
+            rnext: key.recv_id(),
+            pkt_good: 0,
+            pkt_bad: 0,
+            pkt_key_not_found: 0,
+            pkt_ao_required: 0,
+            pkt_dropped_icmp: 0,
+        };
+        setsockopt(fd, TCP_AO_INFO, &info)
+    }
+
+    pub(super) fn update_tcp_ao_chain(
+        fd: RawFd,
+        peer_ip: IpAddr,
+        previous: &TcpAoKeyChain,
+        chain: &TcpAoKeyChain,
+    ) -> io::Result<()> {
+        let is_current = |key: &TcpAoKey| key.send_id() == chain.current_send_id();
+        for key in chain.keys() {
+            if !previous.keys().contains(key) {
+                add_tcp_ao_key(fd, peer_ip, key, is_current(key))?;
+            } else if is_current(key) && previous.current_send_id() != chain.current_send_id() {
+                set_tcp_ao_current(fd, key)?;
+            }
+        }
+        for key in previous.keys() {
+            if !chain.keys().contains(key) {
+                del_tcp_ao_key(fd, peer_ip, key)?;
+            }
+        }
+        Ok(())
+    }
+}
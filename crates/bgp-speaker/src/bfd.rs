@@ -0,0 +1,785 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bidirectional Forwarding Detection (BFD) in asynchronous mode
+//! [RFC5880](https://datatracker.ietf.org/doc/html/rfc5880) for single-hop
+//! sessions over UDP [RFC5881](https://datatracker.ietf.org/doc/html/rfc5881).
+//!
+//! [Bfd] runs the BFD sessions in a background task and implements
+//! [LivenessDetector], a session is created for each subscribed peer address
+//! and removed once all its subscribers are dropped. Authentication, demand
+//! mode, and the echo function are not supported.
+//!
+//! Control packets are sent with TTL 255 and the received packets with a
+//! lower TTL are discarded, which requires Linux specific socket options.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
+    time::Duration,
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use tokio::{
+    net::UdpSocket,
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+use crate::{
+    liveness::{LivenessDetector, LivenessState},
+    socket::{set_udp_single_hop, GTSM_TTL},
+};
+
+/// Destination UDP port of single-hop BFD control packets
+pub const BFD_PORT: u16 = 3784;
+
+/// Source UDP ports of BFD control packets
+pub const BFD_SOURCE_PORTS: RangeInclusive<u16> = 49152..=65535;
+
+pub const BFD_VERSION: u8 = 1;
+
+/// Length of a control packet without the authentication section
+pub const BFD_CONTROL_PACKET_LEN: usize = 24;
+
+/// Min transmit interval advertised while the session is not up
+const SLOW_TX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Attempts to find a free source port
+const SOURCE_PORT_ATTEMPTS: usize = 16;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, strum_macros::Display)]
+pub enum BfdState {
+    AdminDown = 0,
+    Down = 1,
+    Init = 2,
+    Up = 3,
+}
+
+impl From<u8> for BfdState {
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => Self::AdminDown,
+            1 => Self::Down,
+            2 => Self::Init,
+            _ => Self::Up,
+        }
+    }
+}
+
+/// Reason of the last change of the local session state
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BfdDiagnostic {
+    NoDiagnostic,
+    ControlDetectionTimeExpired,
+    EchoFunctionFailed,
+    NeighborSignaledSessionDown,
+    ForwardingPlaneReset,
+    PathDown,
+    ConcatenatedPathDown,
+    AdministrativelyDown,
+    ReverseConcatenatedPathDown,
+    Unassigned(u8),
+}
+
+impl From<u8> for BfdDiagnostic {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::NoDiagnostic,
+            1 => Self::ControlDetectionTimeExpired,
+            2 => Self::EchoFunctionFailed,
+            3 => Self::NeighborSignaledSessionDown,
+            4 => Self::ForwardingPlaneReset,
+            5 => Self::PathDown,
+            6 => Self::ConcatenatedPathDown,
+            7 => Self::AdministrativelyDown,
+            8 => Self::ReverseConcatenatedPathDown,
+            value => Self::Unassigned(value),
+        }
+    }
+}
+
+impl From<BfdDiagnostic> for u8 {
+    fn from(value: BfdDiagnostic) -> Self {
+        match value {
+            BfdDiagnostic::NoDiagnostic => 0,
+            BfdDiagnostic::ControlDetectionTimeExpired => 1,
+            BfdDiagnostic::EchoFunctionFailed => 2,
+            BfdDiagnostic::NeighborSignaledSessionDown => 3,
+            BfdDiagnostic::ForwardingPlaneReset => 4,
+            BfdDiagnostic::PathDown => 5,
+            BfdDiagnostic::ConcatenatedPathDown => 6,
+            BfdDiagnostic::AdministrativelyDown => 7,
+            BfdDiagnostic::ReverseConcatenatedPathDown => 8,
+            BfdDiagnostic::Unassigned(value) => value,
+        }
+    }
+}
+
+/// Reasons a received control packet is discarded before being matched to a
+/// session
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BfdPacketError {
+    TooShort(usize),
+    InvalidVersion(u8),
+    InvalidLength(u8),
+    AuthenticationNotSupported,
+    MultipointNotSupported,
+    ZeroDetectMult,
+    ZeroMyDiscriminator,
+}
+
+impl Display for BfdPacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort(len) => write!(f, "packet of {len} bytes is too short"),
+            Self::InvalidVersion(version) => write!(f, "invalid version {version}"),
+            Self::InvalidLength(len) => write!(f, "invalid length {len}"),
+            Self::AuthenticationNotSupported => write!(f, "authentication is not supported"),
+            Self::MultipointNotSupported => write!(f, "multipoint is not supported"),
+            Self::ZeroDetectMult => write!(f, "detect multiplier is zero"),
+            Self::ZeroMyDiscriminator => write!(f, "my discriminator is zero"),
+        }
+    }
+}
+
+impl std::error::Error for BfdPacketError {}
+
+/// BFD control packet without authentication section, the intervals are in
+/// microseconds
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BfdControlPacket {
+    pub diagnostic: BfdDiagnostic,
+    pub state: BfdState,
+    pub poll: bool,
+    pub final_: bool,
+    pub control_plane_independent: bool,
+    pub demand: bool,
+    pub detect_mult: u8,
+    pub my_discriminator: u32,
+    pub your_discriminator: u32,
+    pub desired_min_tx_interval: u32,
+    pub required_min_rx_interval: u32,
+    pub required_min_echo_rx_interval: u32,
+}
+
+impl BfdControlPacket {
+    pub fn encode(&self) -> [u8; BFD_CONTROL_PACKET_LEN] {
+        let mut buf = [0u8; BFD_CONTROL_PACKET_LEN];
+        buf[0] = (BFD_VERSION << 5) | (u8::from(self.diagnostic) & 0x1f);
+        buf[1] = ((self.state as u8) << 6)
+            | (u8::from(self.poll) << 5)
+            | (u8::from(self.final_) << 4)
+            | (u8::from(self.control_plane_independent) << 3)
+            | (u8::from(self.demand) << 1);
+        buf[2] = self.detect_mult;
+        buf[3] = BFD_CONTROL_PACKET_LEN as u8;
+        buf[4..8].copy_from_slice(&self.my_discriminator.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.your_discriminator.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.desired_min_tx_interval.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.required_min_rx_interval.to_be_bytes());
+        buf[20..24].copy_from_slice(&self.required_min_echo_rx_interval.to_be_bytes());
+        buf
+    }
+
+    /// Decode and validate a received packet, see the reception checks of
+    /// RFC5880 section 6.8.6 that don't depend on the session
+    pub fn decode(buf: &[u8]) -> Result<Self, BfdPacketError> {
+        if buf.len() < BFD_CONTROL_PACKET_LEN {
+            return Err(BfdPacketError::TooShort(buf.len()));
+        }
+        let version = buf[0] >> 5;
+        if version != BFD_VERSION {
+            return Err(BfdPacketError::InvalidVersion(version));
+        }
+        let len = buf[3];
+        if (len as usize) < BFD_CONTROL_PACKET_LEN || len as usize > buf.len() {
+            return Err(BfdPacketError::InvalidLength(len));
+        }
+        let flags = buf[1];
+        if flags & 0x04 != 0 {
+            return Err(BfdPacketError::AuthenticationNotSupported);
+        }
+        if flags & 0x01 != 0 {
+            return Err(BfdPacketError::MultipointNotSupported);
+        }
+        let u32_at = |index: usize| {
+            u32::from_be_bytes([buf[index], buf[index + 1], buf[index + 2], buf[index + 3]])
+        };
+        let packet = Self {
+            diagnostic: BfdDiagnostic::from(buf[0] & 0x1f),
+            state: BfdState::from(flags >> 6),
+            poll: flags & 0x20 != 0,
+            final_: flags & 0x10 != 0,
+            control_plane_independent: flags & 0x08 != 0,
+            demand: flags & 0x02 != 0,
+            detect_mult: buf[2],
+            my_discriminator: u32_at(4),
+            your_discriminator: u32_at(8),
+            desired_min_tx_interval: u32_at(12),
+            required_min_rx_interval: u32_at(16),
+            required_min_echo_rx_interval: u32_at(20),
+        };
+        if packet.detect_mult == 0 {
+            return Err(BfdPacketError::ZeroDetectMult);
+        }
+        if packet.my_discriminator == 0 {
+            return Err(BfdPacketError::ZeroMyDiscriminator);
+        }
+        Ok(packet)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BfdConfig {
+    local_addr: SocketAddr,
+    peer_port: u16,
+    desired_min_tx_interval: Duration,
+    required_min_rx_interval: Duration,
+    detect_mult: u8,
+}
+
+impl BfdConfig {
+    /// Receive the control packets on `local_addr`, usually port [BFD_PORT]
+    /// of the unspecified address
+    pub const fn new(local_addr: SocketAddr) -> Self {
+        Self {
+            local_addr,
+            peer_port: BFD_PORT,
+            desired_min_tx_interval: Duration::from_millis(300),
+            required_min_rx_interval: Duration::from_millis(300),
+            detect_mult: 3,
+        }
+    }
+
+    /// Destination port of the sent control packets, default [BFD_PORT]
+    pub const fn with_peer_port(mut self, peer_port: u16) -> Self {
+        self.peer_port = peer_port;
+        self
+    }
+
+    pub const fn with_desired_min_tx_interval(mut self, interval: Duration) -> Self {
+        self.desired_min_tx_interval = interval;
+        self
+    }
+
+    pub const fn with_required_min_rx_interval(mut self, interval: Duration) -> Self {
+        self.required_min_rx_interval = interval;
+        self
+    }
+
+    pub const fn with_detect_mult(mut self, detect_mult: u8) -> Self {
+        self.detect_mult = detect_mult;
+        self
+    }
+
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub const fn peer_port(&self) -> u16 {
+        self.peer_port
+    }
+
+    pub const fn desired_min_tx_interval(&self) -> Duration {
+        self.desired_min_tx_interval
+    }
+
+    pub const fn required_min_rx_interval(&self) -> Duration {
+        self.required_min_rx_interval
+    }
+
+    pub const fn detect_mult(&self) -> u8 {
+        self.detect_mult
+    }
+}
+
+fn micros(duration: Duration) -> u32 {
+    duration.as_micros().try_into().unwrap_or(u32::MAX)
+}
+
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// Handle to the BFD sessions, the sessions are stopped once all the clones
+/// are dropped
+#[derive(Debug, Clone)]
+pub struct Bfd {
+    tx: mpsc::UnboundedSender<(IpAddr, watch::Sender<LivenessState>)>,
+}
+
+impl Bfd {
+    /// Bind to the configured local address and run the sessions in the
+    /// background, must be called within a tokio runtime
+    pub fn bind(config: BfdConfig) -> io::Result<Self> {
+        let socket = std::net::UdpSocket::bind(config.local_addr)?;
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket)?;
+        set_udp_single_hop(&socket, config.local_addr.is_ipv6())?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        log::info!("BFD listening on {}", config.local_addr);
+        tokio::spawn(BfdSessions::new(config, socket).run(rx));
+        Ok(Self { tx })
+    }
+}
+
+impl LivenessDetector<SocketAddr> for Bfd {
+    fn subscribe(&self, peer_addr: SocketAddr) -> watch::Receiver<LivenessState> {
+        let (tx, rx) = watch::channel(LivenessState::Down);
+        if self.tx.send((canonical_ip(peer_addr.ip()), tx)).is_err() {
+            log::error!("BFD is stopped, can't monitor {}", peer_addr.ip());
+        }
+        rx
+    }
+}
+
+/// State of a single BFD session, see RFC5880 section 6.8.1
+#[derive(Debug)]
+pub(crate) struct BfdSession {
+    peer_ip: IpAddr,
+    socket: Option<UdpSocket>,
+    state: BfdState,
+    remote_state: BfdState,
+    local_discriminator: u32,
+    remote_discriminator: u32,
+    local_diagnostic: BfdDiagnostic,
+    remote_min_rx_interval: Duration,
+    remote_desired_min_tx_interval: Duration,
+    remote_detect_mult: u8,
+    /// A Poll Sequence is in progress
+    poll: bool,
+    next_tx: Instant,
+    detection_deadline: Option<Instant>,
+    subscribers: Vec<watch::Sender<LivenessState>>,
+}
+
+impl BfdSession {
+    pub(crate) fn new(peer_ip: IpAddr, local_discriminator: u32, now: Instant) -> Self {
+        Self {
+            peer_ip,
+            socket: None,
+            state: BfdState::Down,
+            remote_state: BfdState::Down,
+            local_discriminator,
+            remote_discriminator: 0,
+            local_diagnostic: BfdDiagnostic::NoDiagnostic,
+            remote_min_rx_interval: Duration::from_micros(1),
+            remote_desired_min_tx_interval: Duration::ZERO,
+            remote_detect_mult: 0,
+            poll: false,
+            next_tx: now,
+            detection_deadline: None,
+            subscribers: vec![],
+        }
+    }
+
+    pub(crate) const fn state(&self) -> BfdState {
+        self.state
+    }
+
+    pub(crate) const fn local_diagnostic(&self) -> BfdDiagnostic {
+        self.local_diagnostic
+    }
+
+    /// A Down signaled by the remote system being administratively down is
+    /// not a failure and is not reported, see RFC5882 section 3.2
+    fn liveness(&self) -> Option<LivenessState> {
+        match self.state() {
+            BfdState::Up => Some(LivenessState::Up),
+            _ if self.remote_state == BfdState::AdminDown => None,
+            _ => Some(LivenessState::Down),
+        }
+    }
+
+    fn notify(&self) {
+        if let Some(liveness) = self.liveness() {
+            for tx in &self.subscribers {
+                tx.send_if_modified(|current| {
+                    let modified = *current != liveness;
+                    *current = liveness;
+                    modified
+                });
+            }
+        }
+    }
+
+    fn subscribe(&mut self, tx: watch::Sender<LivenessState>) {
+        if self.state == BfdState::Up {
+            tx.send_replace(LivenessState::Up);
+        }
+        self.subscribers.push(tx);
+    }
+
+    /// Drop the closed subscribers, returns false when none is left
+    fn retain_subscribers(&mut self) -> bool {
+        self.subscribers.retain(|tx| !tx.is_closed());
+        !self.subscribers.is_empty()
+    }
+
+    fn set_state(&mut self, state: BfdState, diagnostic: BfdDiagnostic, now: Instant) {
+        if self.state == state {
+            return;
+        }
+        log::info!(
+            "[{}] BFD session state transitions from {} to {state} ({diagnostic:?})",
+            self.peer_ip,
+            self.state
+        );
+        let slow_before = self.state != BfdState::Up;
+        self.state = state;
+        self.local_diagnostic = diagnostic;
+        if (state != BfdState::Up) != slow_before {
+            // The advertised transmit interval changed
+            self.poll = true;
+        }
+        self.next_tx = now;
+        if state == BfdState::Down {
+            self.detection_deadline = None;
+        }
+        self.notify();
+    }
+
+    fn advertised_min_tx_interval(&self, config: &BfdConfig) -> Duration {
+        if self.state == BfdState::Up {
+            config.desired_min_tx_interval
+        } else {
+            config.desired_min_tx_interval.max(SLOW_TX_INTERVAL)
+        }
+    }
+
+    fn detection_time(&self, config: &BfdConfig) -> Duration {
+        config
+            .required_min_rx_interval
+            .max(self.remote_desired_min_tx_interval)
+            * self.remote_detect_mult as u32
+    }
+
+    /// Interval to the next periodic packet with the jitter of RFC5880
+    /// section 6.8.7
+    fn tx_interval(&self, config: &BfdConfig, rng: &mut SmallRng) -> Duration {
+        let interval = self
+            .advertised_min_tx_interval(config)
+            .max(self.remote_min_rx_interval);
+        let max_percent = if config.detect_mult == 1 { 90 } else { 100 };
+        interval * rng.gen_range(75..=max_percent) / 100
+    }
+
+    pub(crate) fn control_packet(&self, config: &BfdConfig, final_: bool) -> BfdControlPacket {
+        BfdControlPacket {
+            diagnostic: self.local_diagnostic(),
+            state: self.state(),
+            poll: self.poll && !final_,
+            final_,
+            control_plane_independent: false,
+            demand: false,
+            detect_mult: config.detect_mult,
+            my_discriminator: self.local_discriminator,
+            your_discriminator: self.remote_discriminator,
+            desired_min_tx_interval: micros(self.advertised_min_tx_interval(config)),
+            required_min_rx_interval: micros(config.required_min_rx_interval),
+            required_min_echo_rx_interval: 0,
+        }
+    }
+
+    /// Apply a received packet that is matched to the session, returns true
+    /// when a packet with the Final bit must be sent in response
+    pub(crate) fn receive(
+        &mut self,
+        packet: &BfdControlPacket,
+        config: &BfdConfig,
+        now: Instant,
+    ) -> bool {
+        if packet.final_ {
+            self.poll = false;
+        }
+        self.remote_discriminator = packet.my_discriminator;
+        self.remote_state = packet.state;
+        self.remote_min_rx_interval = Duration::from_micros(packet.required_min_rx_interval as u64);
+        self.remote_desired_min_tx_interval =
+            Duration::from_micros(packet.desired_min_tx_interval as u64);
+        self.remote_detect_mult = packet.detect_mult;
+        if self.state == BfdState::AdminDown {
+            return false;
+        }
+        if packet.state == BfdState::AdminDown {
+            self.set_state(
+                BfdState::Down,
+                BfdDiagnostic::NeighborSignaledSessionDown,
+                now,
+            );
+        } else {
+            match (self.state, packet.state) {
+                (BfdState::Down, BfdState::Down) => {
+                    self.set_state(BfdState::Init, BfdDiagnostic::NoDiagnostic, now)
+                }
+                (BfdState::Down, BfdState::Init)
+                | (BfdState::Init, BfdState::Init | BfdState::Up) => {
+                    self.set_state(BfdState::Up, BfdDiagnostic::NoDiagnostic, now)
+                }
+                (BfdState::Up, BfdState::Down) => self.set_state(
+                    BfdState::Down,
+                    BfdDiagnostic::NeighborSignaledSessionDown,
+                    now,
+                ),
+                _ => {}
+            }
+        }
+        if matches!(self.state, BfdState::Init | BfdState::Up) {
+            self.detection_deadline = Some(now + self.detection_time(config));
+        }
+        packet.poll
+    }
+
+    /// Handle the expiry of the detection time, and returns true when a
+    /// periodic packet is due
+    pub(crate) fn poll_timers(
+        &mut self,
+        config: &BfdConfig,
+        rng: &mut SmallRng,
+        now: Instant,
+    ) -> bool {
+        if self
+            .detection_deadline
+            .is_some_and(|deadline| deadline <= now)
+        {
+            self.remote_discriminator = 0;
+            self.set_state(
+                BfdState::Down,
+                BfdDiagnostic::ControlDetectionTimeExpired,
+                now,
+            );
+        }
+        // Periodic packets are not sent when the remote system asks not to
+        // receive any
+        if self.next_tx <= now && !self.remote_min_rx_interval.is_zero() {
+            self.next_tx = now + self.tx_interval(config, rng);
+            return true;
+        }
+        false
+    }
+
+    fn next_deadline(&self) -> Instant {
+        match self.detection_deadline {
+            Some(deadline) => deadline.min(self.next_tx),
+            None => self.next_tx,
+        }
+    }
+
+    fn send(&self, config: &BfdConfig, final_: bool) {
+        let Some(socket) = self.socket.as_ref() else {
+            return;
+        };
+        let packet = self.control_packet(config, final_).encode();
+        let dest = SocketAddr::new(self.peer_ip, config.peer_port);
+        if let Err(err) = socket.try_send_to(&packet, dest) {
+            log::warn!("[{}] Couldn't send BFD control packet: {err}", self.peer_ip);
+        }
+    }
+}
+
+/// Bind the socket sending the control packets of a session to a random
+/// source port, see RFC5881 section 4
+fn bind_source_socket(
+    local_ip: IpAddr,
+    peer_ip: IpAddr,
+    rng: &mut SmallRng,
+) -> io::Result<UdpSocket> {
+    let local_ip = match (local_ip, peer_ip) {
+        (IpAddr::V4(local), IpAddr::V4(_)) => IpAddr::V4(local),
+        (IpAddr::V6(local), IpAddr::V6(_)) => IpAddr::V6(local),
+        (IpAddr::V6(local), IpAddr::V4(_)) if local.is_unspecified() => {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        }
+        (IpAddr::V4(local), IpAddr::V6(_)) if local.is_unspecified() => {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        }
+        (local, peer) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                "local address {local} and peer address {peer} are of different address families"
+            ),
+            ))
+        }
+    };
+    let mut last_err = None;
+    for _ in 0..SOURCE_PORT_ATTEMPTS {
+        let port = rng.gen_range(BFD_SOURCE_PORTS);
+        match std::net::UdpSocket::bind(SocketAddr::new(local_ip, port)) {
+            Ok(socket) => {
+                socket.set_nonblocking(true)?;
+                let socket = UdpSocket::from_std(socket)?;
+                set_udp_single_hop(&socket, local_ip.is_ipv6())?;
+                return Ok(socket);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse => last_err = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrInUse)))
+}
+
+#[cfg(target_os = "linux")]
+async fn recv_from(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+    socket
+        .async_io(tokio::io::Interest::READABLE, || {
+            crate::socket::recv_from_with_ttl(socket, buf)
+        })
+        .await
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn recv_from(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+    let (len, peer_addr) = socket.recv_from(buf).await?;
+    Ok((len, peer_addr, None))
+}
+
+/// The BFD sessions run by a [Bfd] background task
+#[derive(Debug)]
+struct BfdSessions {
+    config: BfdConfig,
+    socket: UdpSocket,
+    sessions: HashMap<IpAddr, BfdSession>,
+    /// Peer address of the sessions indexed by their local discriminator
+    discriminators: HashMap<u32, IpAddr>,
+    rng: SmallRng,
+}
+
+impl BfdSessions {
+    fn new(config: BfdConfig, socket: UdpSocket) -> Self {
+        Self {
+            config,
+            socket,
+            sessions: HashMap::new(),
+            discriminators: HashMap::new(),
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    fn subscribe(&mut self, peer_ip: IpAddr, tx: watch::Sender<LivenessState>) {
+        if let Some(session) = self.sessions.get_mut(&peer_ip) {
+            session.subscribe(tx);
+            return;
+        }
+        let discriminator = loop {
+            let discriminator = self.rng.gen_range(1..=u32::MAX);
+            if !self.discriminators.contains_key(&discriminator) {
+                break discriminator;
+            }
+        };
+        let mut session = BfdSession::new(peer_ip, discriminator, Instant::now());
+        match bind_source_socket(self.config.local_addr.ip(), peer_ip, &mut self.rng) {
+            Ok(socket) => session.socket = Some(socket),
+            Err(err) => log::error!("[{peer_ip}] Couldn't bind BFD source socket: {err}"),
+        }
+        log::info!("[{peer_ip}] BFD session created with discriminator {discriminator}");
+        session.subscribe(tx);
+        self.discriminators.insert(discriminator, peer_ip);
+        self.sessions.insert(peer_ip, session);
+    }
+
+    fn receive(&mut self, buf: &[u8], peer_addr: SocketAddr, ttl: Option<u8>) {
+        let peer_ip = canonical_ip(peer_addr.ip());
+        if ttl.is_some_and(|ttl| ttl != GTSM_TTL) {
+            log::debug!("[{peer_ip}] Discarding BFD packet received with TTL {ttl:?}");
+            return;
+        }
+        let packet = match BfdControlPacket::decode(buf) {
+            Ok(packet) => packet,
+            Err(err) => {
+                log::debug!("[{peer_ip}] Discarding BFD packet: {err}");
+                return;
+            }
+        };
+        let session_ip = if packet.your_discriminator != 0 {
+            self.discriminators.get(&packet.your_discriminator).copied()
+        } else if matches!(packet.state, BfdState::Down | BfdState::AdminDown) {
+            Some(peer_ip)
+        } else {
+            None
+        };
+        let Some(session) = session_ip.and_then(|ip| self.sessions.get_mut(&ip)) else {
+            log::debug!("[{peer_ip}] Discarding BFD packet not matching any session");
+            return;
+        };
+        if session.receive(&packet, &self.config, Instant::now()) {
+            session.send(&self.config, true);
+        }
+    }
+
+    fn poll_timers(&mut self) {
+        let now = Instant::now();
+        let config = &self.config;
+        let rng = &mut self.rng;
+        let discriminators = &mut self.discriminators;
+        self.sessions.retain(|peer_ip, session| {
+            if !session.retain_subscribers() {
+                log::info!("[{peer_ip}] BFD session removed");
+                session.state = BfdState::AdminDown;
+                session.local_diagnostic = BfdDiagnostic::AdministrativelyDown;
+                session.send(config, false);
+                discriminators.remove(&session.local_discriminator);
+                return false;
+            }
+            if session.poll_timers(config, rng, now) {
+                session.send(config, false);
+            }
+            true
+        });
+    }
+
+    async fn run(
+        mut self,
+        mut rx: mpsc::UnboundedReceiver<(IpAddr, watch::Sender<LivenessState>)>,
+    ) {
+        let mut buf = [0u8; 512];
+        loop {
+            let deadline = self
+                .sessions
+                .values()
+                .map(|session| session.next_deadline())
+                .min()
+                .unwrap_or_else(|| Instant::now() + SLOW_TX_INTERVAL);
+            // Check for a stopped handle first, so the sessions aren't signaled
+            // AdminDown when the subscribers are dropped along with the handle
+            tokio::select! {
+                biased;
+                subscription = rx.recv() => match subscription {
+                    Some((peer_ip, tx)) => self.subscribe(peer_ip, tx),
+                    None => {
+                        log::info!("BFD on {} stopped", self.config.local_addr);
+                        return;
+                    }
+                },
+                received = recv_from(&self.socket, &mut buf) => match received {
+                    Ok((len, peer_addr, ttl)) => self.receive(&buf[..len], peer_addr, ttl),
+                    Err(err) => log::warn!("Error receiving BFD packet: {err}"),
+                },
+                _ = tokio::time::sleep_until(deadline) => {}
+            }
+            self.poll_timers();
+        }
+    }
+}
@@ -0,0 +1,686 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BGP Monitoring Protocol ([RFC7854](https://datatracker.ietf.org/doc/html/rfc7854))
+//! exporter of the speaker's own sessions, enabled with the `bmp` feature.
+//!
+//! [BmpExporter::start] connects to one or more BMP collectors and keeps the
+//! connections up, reconnecting with an exponential backoff. The exporter is
+//! attached to the peers with
+//! [crate::supervisor::PeersSupervisor::set_bmp_exporter], the peers then
+//! report:
+//! * Peer Up notifications with the OPEN messages sent and received on the
+//!   session, and Peer Down notifications with the reason the session was
+//!   closed.
+//! * Route Monitoring messages for the pre-policy and post-policy Adj-RIB-In,
+//!   the Adj-RIB-Out ([RFC8671](https://datatracker.ietf.org/doc/html/rfc8671))
+//!   and the routes injected into the supervisor as Loc-RIB
+//!   ([RFC9069](https://datatracker.ietf.org/doc/html/rfc9069)).
+//!
+//! Each collector connection keeps a copy of the reported RIBs, which is sent
+//! after the Initiation message whenever the collector (re)connects, and from
+//! which the periodic Statistics Reports are built.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::SinkExt;
+use tokio::{
+    io::AsyncReadExt,
+    net::{tcp::OwnedWriteHalf, TcpStream},
+    sync::mpsc,
+};
+use tokio_util::codec::FramedWrite;
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, FourOctetAsCapability},
+    iana::AS_TRANS,
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_bmp_pkt::{
+    codec::BmpCodec, iana::PeerTerminationCode, BmpMessage, BmpMessageValue, BmpPeerType,
+    CounterU32, GaugeU64, InitiationInformation, InitiationMessage, PeerDownNotificationMessage,
+    PeerDownNotificationReason, PeerHeader, PeerUpNotificationMessage, RouteMonitoringMessage,
+    StatisticsCounter, StatisticsReportMessage, TerminationInformation, TerminationMessage,
+};
+
+use crate::{
+    export::ExportRoute,
+    rib::{withdraw_updates, AdjRib, RouteKey, UpdateRoutes, BGP_MAX_MESSAGE_LENGTH},
+};
+
+/// Table name of the Loc-RIB instance reported to the collectors
+pub const LOC_RIB_TABLE_NAME: &str = "global";
+
+/// RIB a Route Monitoring message is reported for
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum BmpRib {
+    /// Updates as received from the peer
+    AdjRibInPre,
+    /// Updates after the ingress treatment of the speaker
+    AdjRibInPost,
+    /// Updates sent to the peer
+    AdjRibOut,
+}
+
+/// Events reported to the [BmpExporter]
+#[derive(Debug, Clone, PartialEq)]
+pub enum BmpPeerEvent<A> {
+    PeerUp {
+        peer_addr: A,
+        sent_open: BgpOpenMessage,
+        received_open: BgpOpenMessage,
+    },
+    PeerDown {
+        peer_addr: A,
+        reason: PeerDownNotificationReason,
+    },
+    RouteMonitoring {
+        peer_addr: A,
+        rib: BmpRib,
+        update: BgpUpdateMessage,
+    },
+    /// An update received from the peer with `prefixes` NLRIs was treated as
+    /// withdraw
+    TreatAsWithdraw { peer_addr: A, prefixes: usize },
+    /// Changes of the routes injected into the supervisor
+    LocRib(BgpUpdateMessage),
+}
+
+/// Configuration of the [BmpExporter]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BmpExporterConfig {
+    collectors: Vec<SocketAddr>,
+    my_asn: u32,
+    my_bgp_id: Ipv4Addr,
+    sys_name: String,
+    sys_descr: String,
+    stats_interval: Duration,
+    min_reconnect_backoff: Duration,
+    max_reconnect_backoff: Duration,
+}
+
+impl BmpExporterConfig {
+    pub fn new(collectors: Vec<SocketAddr>, my_asn: u32, my_bgp_id: Ipv4Addr) -> Self {
+        Self {
+            collectors,
+            my_asn,
+            my_bgp_id,
+            sys_name: my_bgp_id.to_string(),
+            sys_descr: format!("NetGauze BGP Speaker {}", env!("CARGO_PKG_VERSION")),
+            stats_interval: Duration::from_secs(60),
+            min_reconnect_backoff: Duration::from_secs(1),
+            max_reconnect_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// sysName sent in the Initiation message, defaults to the BGP Identifier
+    pub fn with_sys_name(mut self, value: String) -> Self {
+        self.sys_name = value;
+        self
+    }
+
+    /// sysDescr sent in the Initiation message
+    pub fn with_sys_descr(mut self, value: String) -> Self {
+        self.sys_descr = value;
+        self
+    }
+
+    /// Interval between Statistics Reports, zero disables them
+    pub const fn with_stats_interval(mut self, value: Duration) -> Self {
+        self.stats_interval = value;
+        self
+    }
+
+    /// Delay before reconnecting to a collector, doubled after each failed
+    /// attempt up to `max`
+    pub const fn with_reconnect_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.min_reconnect_backoff = min;
+        self.max_reconnect_backoff = max;
+        self
+    }
+
+    pub const fn collectors(&self) -> &Vec<SocketAddr> {
+        &self.collectors
+    }
+    pub const fn my_asn(&self) -> u32 {
+        self.my_asn
+    }
+    pub const fn my_bgp_id(&self) -> Ipv4Addr {
+        self.my_bgp_id
+    }
+    pub fn sys_name(&self) -> &str {
+        &self.sys_name
+    }
+    pub fn sys_descr(&self) -> &str {
+        &self.sys_descr
+    }
+    pub const fn stats_interval(&self) -> Duration {
+        self.stats_interval
+    }
+    pub const fn min_reconnect_backoff(&self) -> Duration {
+        self.min_reconnect_backoff
+    }
+    pub const fn max_reconnect_backoff(&self) -> Duration {
+        self.max_reconnect_backoff
+    }
+}
+
+/// Handle to the collector connections, cloned into every peer. The
+/// connections are closed with a Termination message once all the handles
+/// are dropped.
+#[derive(Debug, Clone)]
+pub struct BmpExporter<A> {
+    collectors_tx: Vec<mpsc::UnboundedSender<BmpPeerEvent<A>>>,
+}
+
+impl<A: Clone> BmpExporter<A> {
+    /// Spawn a task per collector in [BmpExporterConfig::collectors]
+    pub fn start(config: BmpExporterConfig) -> Self
+    where
+        A: Into<SocketAddr> + Send + 'static,
+    {
+        let collectors_tx = config
+            .collectors()
+            .iter()
+            .map(|collector| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                tokio::spawn(run_collector(*collector, config.clone(), rx));
+                tx
+            })
+            .collect();
+        Self { collectors_tx }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn channel() -> (Self, mpsc::UnboundedReceiver<BmpPeerEvent<A>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                collectors_tx: vec![tx],
+            },
+            rx,
+        )
+    }
+
+    pub fn send(&self, event: BmpPeerEvent<A>) {
+        for tx in &self.collectors_tx {
+            if tx.send(event.clone()).is_err() {
+                log::debug!("BMP collector task terminated, dropping event");
+            }
+        }
+    }
+}
+
+/// Pack the changes of the routes injected into the supervisor into BGP
+/// updates reported for the Loc-RIB
+pub(crate) fn loc_rib_updates(
+    announced: &[ExportRoute],
+    withdrawn: &[RouteKey],
+) -> Vec<BgpUpdateMessage> {
+    let mut withdrawn_by_type: HashMap<_, Vec<RouteKey>> = HashMap::new();
+    for key in withdrawn {
+        withdrawn_by_type
+            .entry(key.address_type())
+            .or_default()
+            .push(*key);
+    }
+    let mut updates = vec![];
+    for (address_type, keys) in &withdrawn_by_type {
+        updates.extend(withdraw_updates(
+            *address_type,
+            keys,
+            BGP_MAX_MESSAGE_LENGTH,
+        ));
+    }
+    let mut rib = AdjRib::new();
+    for route in announced {
+        rib.insert(*route.key(), route.route().clone());
+    }
+    for address_type in rib.address_types() {
+        updates.extend(rib.updates(address_type, BGP_MAX_MESSAGE_LENGTH));
+    }
+    updates
+}
+
+fn update_prefixes(update: &BgpUpdateMessage) -> u64 {
+    let update_routes = UpdateRoutes::from(update);
+    (update_routes.announced().len() + update_routes.withdrawn().len()) as u64
+}
+
+fn is_asn4(open: &BgpOpenMessage) -> bool {
+    open.capabilities()
+        .iter()
+        .any(|cap| matches!(cap, BgpCapability::FourOctetAs(_)))
+}
+
+fn rib_len(rib: &AdjRib) -> u64 {
+    rib.address_types()
+        .into_iter()
+        .map(|address_type| rib.len(address_type) as u64)
+        .sum()
+}
+
+#[derive(Debug)]
+struct BmpPeerState {
+    address: IpAddr,
+    asn: u32,
+    bgp_id: Ipv4Addr,
+    asn4: bool,
+    remote_port: u16,
+    sent_open: BgpOpenMessage,
+    received_open: BgpOpenMessage,
+    ribs: HashMap<BmpRib, AdjRib>,
+    treat_as_withdraw_updates: u32,
+    treat_as_withdraw_prefixes: u32,
+}
+
+impl BmpPeerState {
+    fn peer_header(&self, rib: BmpRib) -> PeerHeader {
+        PeerHeader::new(
+            BmpPeerType::GlobalInstancePeer {
+                ipv6: self.address.is_ipv6(),
+                post_policy: rib != BmpRib::AdjRibInPre,
+                asn2: !self.asn4,
+                adj_rib_out: rib == BmpRib::AdjRibOut,
+            },
+            None,
+            Some(self.address),
+            self.asn,
+            self.bgp_id,
+            Some(Utc::now()),
+        )
+    }
+
+    fn peer_up(&self) -> Option<BmpMessage> {
+        let peer_up = PeerUpNotificationMessage::build(
+            self.peer_header(BmpRib::AdjRibInPre),
+            None,
+            None,
+            Some(self.remote_port),
+            BgpMessage::Open(self.sent_open.clone()),
+            BgpMessage::Open(self.received_open.clone()),
+            vec![],
+        )
+        .ok()?;
+        Some(BmpMessage::V3(BmpMessageValue::PeerUpNotification(peer_up)))
+    }
+
+    fn route_monitoring(&self, rib: BmpRib, update: BgpUpdateMessage) -> Option<BmpMessage> {
+        let msg = RouteMonitoringMessage::build(self.peer_header(rib), BgpMessage::Update(update))
+            .ok()?;
+        Some(BmpMessage::V3(BmpMessageValue::RouteMonitoring(msg)))
+    }
+
+    fn statistics(&self) -> BmpMessage {
+        let mut counters = vec![
+            StatisticsCounter::NumberOfUpdatesSubjectedToTreatAsWithdraw(CounterU32::new(
+                self.treat_as_withdraw_updates,
+            )),
+            StatisticsCounter::NumberOfPrefixesSubjectedToTreatAsWithdraw(CounterU32::new(
+                self.treat_as_withdraw_prefixes,
+            )),
+        ];
+        if let Some(rib) = self.ribs.get(&BmpRib::AdjRibInPre) {
+            counters.push(StatisticsCounter::NumberOfRoutesInAdjRibIn(GaugeU64::new(
+                rib_len(rib),
+            )));
+            for address_type in rib.address_types() {
+                counters.push(StatisticsCounter::NumberOfRoutesInPerAfiSafiAdjRibIn(
+                    address_type,
+                    GaugeU64::new(rib.len(address_type) as u64),
+                ));
+            }
+        }
+        if let Some(rib) = self.ribs.get(&BmpRib::AdjRibOut) {
+            counters.push(StatisticsCounter::NumberOfRoutesInPostPolicyAdjRibOut(
+                GaugeU64::new(rib_len(rib)),
+            ));
+            for address_type in rib.address_types() {
+                counters.push(
+                    StatisticsCounter::NumberOfRoutesInPerAfiSafiPostPolicyAdjRibOut(
+                        address_type,
+                        GaugeU64::new(rib.len(address_type) as u64),
+                    ),
+                );
+            }
+        }
+        BmpMessage::V3(BmpMessageValue::StatisticsReport(
+            StatisticsReportMessage::new(self.peer_header(BmpRib::AdjRibInPre), counters),
+        ))
+    }
+}
+
+/// State reported to a collector, replayed when the collector reconnects
+#[derive(Debug)]
+pub(crate) struct BmpState {
+    config: BmpExporterConfig,
+    peers: HashMap<IpAddr, BmpPeerState>,
+    loc_rib: AdjRib,
+}
+
+impl BmpState {
+    pub(crate) fn new(config: BmpExporterConfig) -> Self {
+        Self {
+            config,
+            peers: HashMap::new(),
+            loc_rib: AdjRib::new(),
+        }
+    }
+
+    fn loc_rib_header(&self) -> PeerHeader {
+        PeerHeader::new(
+            BmpPeerType::LocRibInstancePeer { filtered: false },
+            None,
+            None,
+            self.config.my_asn(),
+            self.config.my_bgp_id(),
+            Some(Utc::now()),
+        )
+    }
+
+    /// RFC9069: Peer Up of the Loc-RIB instance carries a fabricated OPEN
+    /// message and the table name
+    fn loc_rib_peer_up(&self) -> Option<BmpMessage> {
+        let asn = self.config.my_asn();
+        let open = BgpOpenMessage::new(
+            u16::try_from(asn).unwrap_or(AS_TRANS),
+            0,
+            self.config.my_bgp_id(),
+            vec![BgpOpenMessageParameter::Capabilities(vec![
+                BgpCapability::FourOctetAs(FourOctetAsCapability::new(asn)),
+            ])],
+        );
+        let peer_up = PeerUpNotificationMessage::build(
+            self.loc_rib_header(),
+            None,
+            None,
+            None,
+            BgpMessage::Open(open.clone()),
+            BgpMessage::Open(open),
+            vec![InitiationInformation::VrfTableName(
+                LOC_RIB_TABLE_NAME.to_string(),
+            )],
+        )
+        .ok()?;
+        Some(BmpMessage::V3(BmpMessageValue::PeerUpNotification(peer_up)))
+    }
+
+    fn loc_rib_route_monitoring(&self, update: BgpUpdateMessage) -> Option<BmpMessage> {
+        let msg = RouteMonitoringMessage::build(self.loc_rib_header(), BgpMessage::Update(update))
+            .ok()?;
+        Some(BmpMessage::V3(BmpMessageValue::RouteMonitoring(msg)))
+    }
+
+    pub(crate) fn initiation(&self) -> BmpMessage {
+        BmpMessage::V3(BmpMessageValue::Initiation(InitiationMessage::new(vec![
+            InitiationInformation::SystemDescription(self.config.sys_descr().to_string()),
+            InitiationInformation::SystemName(self.config.sys_name().to_string()),
+        ])))
+    }
+
+    pub(crate) fn termination() -> BmpMessage {
+        BmpMessage::V3(BmpMessageValue::Termination(TerminationMessage::new(vec![
+            TerminationInformation::Reason(PeerTerminationCode::AdministrativelyClosed),
+        ])))
+    }
+
+    /// Messages sent when the collector connects: the Initiation message, then
+    /// the Peer Up and the RIBs of the Loc-RIB instance and every established
+    /// peer.
+    pub(crate) fn snapshot(&self) -> Vec<BmpMessage> {
+        let mut messages = vec![self.initiation()];
+        messages.extend(self.loc_rib_peer_up());
+        for address_type in self.loc_rib.address_types() {
+            for update in self.loc_rib.updates(address_type, BGP_MAX_MESSAGE_LENGTH) {
+                messages.extend(self.loc_rib_route_monitoring(update));
+            }
+        }
+        let mut addresses = self.peers.keys().copied().collect::<Vec<_>>();
+        addresses.sort();
+        for address in addresses {
+            let peer = &self.peers[&address];
+            messages.extend(peer.peer_up());
+            let mut ribs = peer.ribs.iter().collect::<Vec<_>>();
+            ribs.sort_by_key(|(rib, _)| **rib);
+            for (rib, adj_rib) in ribs {
+                for address_type in adj_rib.address_types() {
+                    for update in adj_rib.updates(address_type, BGP_MAX_MESSAGE_LENGTH) {
+                        messages.extend(peer.route_monitoring(*rib, update));
+                    }
+                }
+            }
+        }
+        messages
+    }
+
+    /// Statistics Reports for the Loc-RIB instance and every established peer
+    pub(crate) fn statistics(&self) -> Vec<BmpMessage> {
+        let mut counters = vec![StatisticsCounter::NumberOfRoutesInLocRib(GaugeU64::new(
+            rib_len(&self.loc_rib),
+        ))];
+        for address_type in self.loc_rib.address_types() {
+            counters.push(StatisticsCounter::NumberOfRoutesInPerAfiSafiLocRib(
+                address_type,
+                GaugeU64::new(self.loc_rib.len(address_type) as u64),
+            ));
+        }
+        let mut messages = vec![BmpMessage::V3(BmpMessageValue::StatisticsReport(
+            StatisticsReportMessage::new(self.loc_rib_header(), counters),
+        ))];
+        let mut addresses = self.peers.keys().copied().collect::<Vec<_>>();
+        addresses.sort();
+        for address in addresses {
+            messages.push(self.peers[&address].statistics());
+        }
+        messages
+    }
+
+    /// Update the state with the event and return the messages reporting it
+    pub(crate) fn apply<A: Into<SocketAddr>>(&mut self, event: BmpPeerEvent<A>) -> Vec<BmpMessage> {
+        match event {
+            BmpPeerEvent::PeerUp {
+                peer_addr,
+                sent_open,
+                received_open,
+            } => {
+                let peer_addr = peer_addr.into();
+                let peer = BmpPeerState {
+                    address: peer_addr.ip(),
+                    asn: received_open.my_asn4(),
+                    bgp_id: received_open.bgp_id(),
+                    asn4: is_asn4(&sent_open) && is_asn4(&received_open),
+                    remote_port: peer_addr.port(),
+                    sent_open,
+                    received_open,
+                    ribs: HashMap::new(),
+                    treat_as_withdraw_updates: 0,
+                    treat_as_withdraw_prefixes: 0,
+                };
+                let msg = peer.peer_up();
+                self.peers.insert(peer.address, peer);
+                msg.into_iter().collect()
+            }
+            BmpPeerEvent::PeerDown { peer_addr, reason } => {
+                let peer = match self.peers.remove(&peer_addr.into().ip()) {
+                    Some(peer) => peer,
+                    None => return vec![],
+                };
+                PeerDownNotificationMessage::build(peer.peer_header(BmpRib::AdjRibInPre), reason)
+                    .map(|msg| BmpMessage::V3(BmpMessageValue::PeerDownNotification(msg)))
+                    .into_iter()
+                    .collect()
+            }
+            BmpPeerEvent::RouteMonitoring {
+                peer_addr,
+                rib,
+                update,
+            } => {
+                let peer = match self.peers.get_mut(&peer_addr.into().ip()) {
+                    Some(peer) => peer,
+                    None => return vec![],
+                };
+                peer.ribs
+                    .entry(rib)
+                    .or_default()
+                    .apply(UpdateRoutes::from(&update));
+                peer.route_monitoring(rib, update).into_iter().collect()
+            }
+            BmpPeerEvent::TreatAsWithdraw {
+                peer_addr,
+                prefixes,
+            } => {
+                if let Some(peer) = self.peers.get_mut(&peer_addr.into().ip()) {
+                    peer.treat_as_withdraw_updates = peer.treat_as_withdraw_updates.wrapping_add(1);
+                    peer.treat_as_withdraw_prefixes = peer
+                        .treat_as_withdraw_prefixes
+                        .wrapping_add(prefixes as u32);
+                }
+                vec![]
+            }
+            BmpPeerEvent::LocRib(update) => {
+                if update_prefixes(&update) == 0 {
+                    return vec![];
+                }
+                self.loc_rib.apply(UpdateRoutes::from(&update));
+                self.loc_rib_route_monitoring(update).into_iter().collect()
+            }
+        }
+    }
+}
+
+/// Receive events until `deadline`, returns `false` if all the
+/// [BmpExporter] handles are dropped
+async fn collect_until<A: Into<SocketAddr>>(
+    state: &mut BmpState,
+    rx: &mut mpsc::UnboundedReceiver<BmpPeerEvent<A>>,
+    deadline: tokio::time::Instant,
+) -> bool {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => return true,
+            event = rx.recv() => match event {
+                Some(event) => {
+                    state.apply(event);
+                }
+                None => return false,
+            }
+        }
+    }
+}
+
+async fn run_collector<A: Into<SocketAddr>>(
+    collector: SocketAddr,
+    config: BmpExporterConfig,
+    mut rx: mpsc::UnboundedReceiver<BmpPeerEvent<A>>,
+) {
+    let min_backoff = config.min_reconnect_backoff();
+    let max_backoff = config.max_reconnect_backoff();
+    let stats_interval = config.stats_interval();
+    let mut state = BmpState::new(config);
+    let mut backoff = min_backoff;
+    loop {
+        let connect = TcpStream::connect(collector);
+        tokio::pin!(connect);
+        let connected = loop {
+            tokio::select! {
+                result = &mut connect => break result,
+                event = rx.recv() => match event {
+                    Some(event) => {
+                        state.apply(event);
+                    }
+                    None => return,
+                }
+            }
+        };
+        match connected {
+            Ok(stream) => {
+                log::info!("[BMP][{collector}] Connected to collector");
+                // Collectors don't send any message, reading is used to detect the
+                // connection being closed
+                let (mut read_half, write_half) = stream.into_split();
+                let mut read_buf = [0u8; 64];
+                let mut framed = FramedWrite::new(write_half, BmpCodec::default());
+                let mut stats_timer = (!stats_interval.is_zero()).then(|| {
+                    let mut interval = tokio::time::interval(stats_interval);
+                    interval.reset();
+                    interval
+                });
+                let mut result = send_all(&mut framed, state.snapshot()).await;
+                if result.is_ok() {
+                    backoff = min_backoff;
+                }
+                while result.is_ok() {
+                    tokio::select! {
+                        event = rx.recv() => match event {
+                            Some(event) => {
+                                let messages = state.apply(event);
+                                result = send_all(&mut framed, messages).await;
+                            }
+                            None => {
+                                let _ = framed.send(BmpState::termination()).await;
+                                return;
+                            }
+                        },
+                        read = read_half.read(&mut read_buf) => {
+                            if matches!(read, Ok(0) | Err(_)) {
+                                log::warn!("[BMP][{collector}] Connection closed by collector");
+                                break;
+                            }
+                        }
+                        _ = async {
+                            match stats_timer.as_mut() {
+                                Some(interval) => {
+                                    interval.tick().await;
+                                }
+                                None => std::future::pending().await,
+                            }
+                        } => {
+                            result = send_all(&mut framed, state.statistics()).await;
+                        }
+                    }
+                }
+                if let Err(err) = result {
+                    log::warn!("[BMP][{collector}] Connection to collector failed: {err:?}");
+                }
+            }
+            Err(err) => {
+                log::warn!("[BMP][{collector}] Couldn't connect to collector: {err}");
+            }
+        }
+        log::debug!("[BMP][{collector}] Reconnecting in {backoff:?}");
+        let deadline = tokio::time::Instant::now() + backoff;
+        if !collect_until(&mut state, &mut rx, deadline).await {
+            return;
+        }
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+async fn send_all(
+    framed: &mut FramedWrite<OwnedWriteHalf, BmpCodec>,
+    messages: Vec<BmpMessage>,
+) -> Result<(), netgauze_bmp_pkt::wire::serializer::BmpMessageWritingError> {
+    for msg in messages {
+        framed.feed(msg).await?;
+    }
+    framed.flush().await
+}
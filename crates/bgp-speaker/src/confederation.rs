@@ -0,0 +1,96 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Autonomous System confederations for BGP
+//! ([RFC5065](https://datatracker.ietf.org/doc/html/rfc5065)).
+//!
+//! The confederation identifier is configured per peer with
+//! [crate::peer::PeerProperties::with_confederation_id], with `my_asn` being
+//! the member AS of the local speaker. Peers in other member ASes are marked
+//! with [crate::peer::PeerProperties::with_confederation_member]. The
+//! confederation identifier is advertised in the OPEN message sent to peers
+//! outside the confederation, and the AS_CONFED_SEQUENCE and AS_CONFED_SET
+//! segments are maintained by [crate::export::export_route].
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, FourOctetAsCapability},
+    iana::AS_TRANS,
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::{AsPath, PathAttribute, PathAttributeValue},
+};
+
+/// Replace the AS number advertised in an OPEN message, both in the My
+/// Autonomous System field and in the Four-octet AS capability
+pub(crate) fn set_open_asn(open: BgpOpenMessage, asn: u32) -> BgpOpenMessage {
+    let my_as = u16::try_from(asn).unwrap_or(AS_TRANS);
+    let mut has_asn4 = false;
+    let mut params = Vec::with_capacity(open.params().len() + 1);
+    for param in open.params() {
+        let BgpOpenMessageParameter::Capabilities(capabilities) = param;
+        let mut capabilities = capabilities.clone();
+        for capability in &mut capabilities {
+            if let BgpCapability::FourOctetAs(_) = capability {
+                has_asn4 = true;
+                *capability = BgpCapability::FourOctetAs(FourOctetAsCapability::new(asn));
+            }
+        }
+        params.push(BgpOpenMessageParameter::Capabilities(capabilities));
+    }
+    if !has_asn4 && my_as == AS_TRANS {
+        params.push(BgpOpenMessageParameter::Capabilities(vec![
+            BgpCapability::FourOctetAs(FourOctetAsCapability::new(asn)),
+        ]));
+    }
+    BgpOpenMessage::new(my_as, open.hold_time(), open.bgp_id(), params)
+}
+
+/// Remove the AS_CONFED_SEQUENCE and AS_CONFED_SET segments from an AS_PATH,
+/// as done before advertising a route outside the confederation
+pub fn strip_confed_segments(as_path: &AsPath) -> AsPath {
+    match as_path {
+        AsPath::As2PathSegments(segments) => AsPath::As2PathSegments(
+            segments
+                .iter()
+                .filter(|segment| !segment.segment_type().is_confed())
+                .cloned()
+                .collect(),
+        ),
+        AsPath::As4PathSegments(segments) => AsPath::As4PathSegments(
+            segments
+                .iter()
+                .filter(|segment| !segment.segment_type().is_confed())
+                .cloned()
+                .collect(),
+        ),
+    }
+}
+
+/// RFC7606 Section 7.2: an AS_PATH received from a peer outside the
+/// confederation that carries confederation segments is malformed
+pub fn has_confed_segments(attributes: &[PathAttribute]) -> bool {
+    attributes.iter().any(|attr| match attr.value() {
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(segments)) => segments
+            .iter()
+            .any(|segment| segment.segment_type().is_confed()),
+        PathAttributeValue::AsPath(AsPath::As4PathSegments(segments)) => segments
+            .iter()
+            .any(|segment| segment.segment_type().is_confed()),
+        PathAttributeValue::As4Path(as4_path) => as4_path
+            .segments()
+            .iter()
+            .any(|segment| segment.segment_type().is_confed()),
+        _ => false,
+    })
+}
@@ -19,6 +19,8 @@ use futures_util::{FutureExt, SinkExt};
 
 use async_trait::async_trait;
 use pin_project::pin_project;
+#[cfg(unix)]
+use std::{collections::HashMap, path::PathBuf};
 use std::{
     fmt::{Debug, Display},
     future::Future,
@@ -28,16 +30,18 @@ use std::{
     task::{Context, Poll},
     time::Duration,
 };
+#[cfg(unix)]
+use tokio::net::UnixStream;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::TcpStream,
+    net::{TcpSocket, TcpStream},
 };
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
 use netgauze_bgp_pkt::{
     capabilities::BgpCapability,
     codec::{BgpCodec, BgpCodecDecoderError},
-    iana::PathAttributeType,
+    iana::{BgpRoleValue, PathAttributeType},
     notification::{
         BgpNotificationMessage, FiniteStateMachineError, HoldTimerExpiredError, OpenMessageError,
         UpdateMessageError,
@@ -59,9 +63,14 @@ use netgauze_bgp_pkt::{
 use netgauze_iana::address_family::{AddressFamily, SubsequentAddressFamily};
 
 use crate::{
+    auth::{update_tcp_auth, TcpAuthKeys},
+    confederation::set_open_asn,
     events::{ConnectionEvent, UpdateTreatment},
     fsm::FsmStateError,
+    multisession::{add_multisession_capability, multisession_negotiated},
     peer::{PeerConfig, PeerPolicy, PeerProperties},
+    role::{add_role_capability, check_open_role},
+    socket::TcpSocketConfig,
 };
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -302,15 +311,24 @@ pub struct Connection<
     my_bgp_id: Ipv4Addr,
     #[pin]
     peer_bgp_id: Option<Ipv4Addr>,
+    bgp_role: Option<BgpRoleValue>,
+    strict_bgp_role: bool,
+    multisession: bool,
+    /// RFC5065: the confederation identifier advertised instead of `my_asn`
+    /// to peers outside the confederation
+    open_asn: Option<u32>,
     #[pin]
     sent_capabilities: Option<Vec<BgpCapability>>,
     received_capabilities: Option<Vec<BgpCapability>>,
+    sent_open: Option<BgpOpenMessage>,
+    received_open: Option<BgpOpenMessage>,
     peer_hold_time: Option<u16>,
     remote_bgp_id: Option<Ipv4Addr>,
     #[pin]
     inner: Framed<I, D>,
     #[pin]
     stats: ConnectionStats,
+    last_notification_sent: Option<BgpNotificationMessage>,
     #[pin]
     keepalive_timer: Option<tokio::time::Interval>,
     keepalive_timer_duration: Duration,
@@ -327,6 +345,10 @@ pub struct Connection<
     /// which is uniformly distributed in the range from 0.75 to 1.0.
     #[pin]
     jitter: f32,
+    /// Value of the `peer` label of the connection metrics, see
+    /// [Self::set_metrics_peer]
+    #[cfg(feature = "metrics")]
+    metrics_peer: String,
 }
 
 impl<
@@ -351,8 +373,11 @@ impl<
             Some(peer_properties.peer_asn())
         };
         let my_bgp_id = peer_properties.my_bgp_id();
+        let open_asn = Some(peer_properties.local_asn()).filter(|asn| *asn != my_asn);
 
         Self {
+            #[cfg(feature = "metrics")]
+            metrics_peer: peer_addr.to_string(),
             peer_addr,
             state: ConnectionState::Connected,
             connection_type,
@@ -361,12 +386,19 @@ impl<
             peer_asn,
             my_bgp_id,
             peer_bgp_id: None,
+            bgp_role: peer_properties.bgp_role(),
+            strict_bgp_role: peer_properties.strict_bgp_role(),
+            multisession: peer_properties.multisession(),
+            open_asn,
             sent_capabilities: None,
             received_capabilities: None,
+            sent_open: None,
+            received_open: None,
             peer_hold_time: None,
             remote_bgp_id: None,
             inner,
             stats: ConnectionStats::default(),
+            last_notification_sent: None,
             keepalive_timer: None,
             keepalive_timer_duration: config.keepalive_timer_duration(),
             open_delay_timer: None,
@@ -380,6 +412,13 @@ impl<
         &self.peer_addr
     }
 
+    /// Report the metrics of the connection under the key of the peer owning
+    /// it rather than the peer address, which changes with each connection
+    #[cfg(feature = "metrics")]
+    pub(crate) fn set_metrics_peer(&mut self, peer: String) {
+        self.metrics_peer = peer;
+    }
+
     pub const fn state(&self) -> ConnectionState {
         self.state
     }
@@ -388,6 +427,11 @@ impl<
         &self.stats
     }
 
+    /// Take the last notification message sent on this connection, if any
+    pub fn take_last_notification_sent(&mut self) -> Option<BgpNotificationMessage> {
+        self.last_notification_sent.take()
+    }
+
     pub const fn config(&self) -> &ConnectionConfig {
         &self.config
     }
@@ -416,6 +460,11 @@ impl<
         self.peer_bgp_id
     }
 
+    /// Configured peer ASN, or the one learned from the peer's OPEN message
+    pub const fn peer_asn(&self) -> Option<u32> {
+        self.peer_asn
+    }
+
     pub const fn connection_type(&self) -> ConnectionType {
         self.connection_type
     }
@@ -426,6 +475,41 @@ impl<
     pub const fn received_capabilities(&self) -> Option<&Vec<BgpCapability>> {
         self.received_capabilities.as_ref()
     }
+    pub const fn sent_open(&self) -> Option<&BgpOpenMessage> {
+        self.sent_open.as_ref()
+    }
+    pub const fn received_open(&self) -> Option<&BgpOpenMessage> {
+        self.received_open.as_ref()
+    }
+
+    /// Both ends advertised the Multisession capability, see
+    /// [crate::multisession]
+    pub fn multisession_negotiated(&self) -> bool {
+        match (&self.sent_capabilities, &self.received_capabilities) {
+            (Some(sent), Some(received)) => multisession_negotiated(sent, received),
+            _ => false,
+        }
+    }
+
+    /// RFC9234: the local BGP Role is advertised to external peers,
+    /// RFC5065: the confederation identifier is advertised to peers outside
+    /// the confederation, and the Multisession capability is advertised when
+    /// enabled
+    fn open_with_role(&self, open: BgpOpenMessage) -> BgpOpenMessage {
+        let open = match self.open_asn {
+            Some(asn) => set_open_asn(open, asn),
+            None => open,
+        };
+        let open = if self.multisession {
+            add_multisession_capability(open)
+        } else {
+            open
+        };
+        match self.bgp_role {
+            Some(role) => add_role_capability(open, role),
+            None => open,
+        }
+    }
 
     fn read_open_msg(&mut self, open: &BgpOpenMessage) {
         self.peer_asn = Some(open.my_asn4());
@@ -433,6 +517,7 @@ impl<
         self.received_capabilities =
             Some(open.capabilities().iter().map(|x| (*x).clone()).collect());
         self.peer_hold_time = Some(open.hold_time());
+        self.received_open = Some(open.clone());
     }
 
     fn set_negotiated_timers(&mut self) {
@@ -520,6 +605,7 @@ impl<
             ConnectionEvent::DelayOpenTimerExpires => {
                 self.start_hold_timer();
                 let open = policy.open_message().await;
+                let open = self.open_with_role(open);
                 self.send(BgpMessage::Open(open)).await?;
                 self.state = ConnectionState::OpenSent;
             }
@@ -528,6 +614,7 @@ impl<
                 if self.config.open_delay_timer_duration == 0 {
                     self.start_hold_timer();
                     let open = policy.open_message().await;
+                    let open = self.open_with_role(open);
                     self.send(BgpMessage::Open(open)).await?;
                     self.state = ConnectionState::OpenSent;
                 } else {
@@ -548,6 +635,7 @@ impl<
                 self.read_open_msg(open);
                 self.set_negotiated_timers();
                 let open = policy.open_message().await;
+                let open = self.open_with_role(open);
                 if !self.keepalive_timer_duration.is_zero() {
                     let duration = self.keepalive_timer_duration.mul_f32(self.jitter);
                     let mut interval = tokio::time::interval(duration);
@@ -1235,6 +1323,8 @@ fn update_treatment(errors: &BgpParsingIgnoredErrors) -> UpdateTreatment {
 fn handle_open_message<A>(
     open: BgpOpenMessage,
     peer_asn: Option<u32>,
+    bgp_role: Option<BgpRoleValue>,
+    strict_bgp_role: bool,
     delay_timer_running: bool,
 ) -> (Ipv4Addr, ConnectionEvent<A>) {
     // Check Peer ASN number
@@ -1248,6 +1338,11 @@ fn handle_open_message<A>(
             );
         }
     }
+    if let Some(bgp_role) = bgp_role {
+        if let Err(err) = check_open_role(bgp_role, strict_bgp_role, &open) {
+            return (open.bgp_id(), ConnectionEvent::BGPOpenMsgErr(err));
+        }
+    }
     // TODO: check BGP ID according to RFC4271: If the BGP Identifier field of the
     // OPEN message is syntactically incorrect, then the Error Subcode MUST be set
     // to Bad BGP Identifier. Syntactic correctness means that the BGP Identifier
@@ -1276,10 +1371,10 @@ fn handle_update_message<A>(
     let mut has_next_hop = false;
     let mut bgp_mp_reach_count = 0;
     let mut bgp_mp_unreach_count = 0;
+    // All the attributes are scanned, even after ORIGIN and AS_PATH are found:
+    // NEXT_HOP and the duplicate MP_REACH_NLRI or MP_UNREACH_NLRI may come
+    // after them.
     for attr in update.path_attributes() {
-        if has_origin && has_asn_path {
-            break;
-        }
         if let PathAttributeValue::Origin(_) = attr.value() {
             has_origin = true;
         } else if let PathAttributeValue::AsPath(_) = attr.value() {
@@ -1294,14 +1389,17 @@ fn handle_update_message<A>(
             bgp_mp_unreach_count += 1;
         }
     }
-    if end_of_rib.is_none() && !has_origin {
+    // RFC4271: ORIGIN and AS_PATH are mandatory only in UPDATE messages
+    // announcing reachable NLRI, withdraw only updates don't carry them.
+    let has_reachable_nlri = !update.nlri().is_empty() || bgp_mp_reach_count > 0;
+    if end_of_rib.is_none() && has_reachable_nlri && !has_origin {
         return Some(ConnectionEvent::UpdateMsgErr(
             UpdateMessageError::MissingWellKnownAttribute {
                 value: vec![PathAttributeType::Origin as u8],
             },
         ));
     }
-    if end_of_rib.is_none() && !has_asn_path {
+    if end_of_rib.is_none() && has_reachable_nlri && !has_asn_path {
         return Some(ConnectionEvent::UpdateMsgErr(
             UpdateMessageError::MissingWellKnownAttribute {
                 value: vec![PathAttributeType::AsPath as u8],
@@ -1380,10 +1478,14 @@ where
                     match msg {
                         None => Some(ConnectionEvent::TcpConnectionFails),
                         Some(Err(err)) => {
+                            #[cfg(feature = "metrics")]
+                            crate::metrics::metrics().decode_error(this.metrics_peer, &err);
                             Some(err.into())
                         },
                         Some(Ok((msg, parsing_errors))) => {
                             let current = Utc::now();
+                            #[cfg(feature = "metrics")]
+                            crate::metrics::metrics().message_received(this.metrics_peer, &msg);
                             this.stats.messages_received += 1;
                             this.stats.last_received = Some(current);
                             match msg {
@@ -1399,7 +1501,7 @@ where
                                             );
                                         }
                                     }
-                                    let (peer_bgp_id, event) = handle_open_message(open, *this.peer_asn, this.open_delay_timer.is_some());
+                                    let (peer_bgp_id, event) = handle_open_message(open, *this.peer_asn, *this.bgp_role, *this.strict_bgp_role, this.open_delay_timer.is_some());
                                     this.peer_bgp_id.replace(peer_bgp_id);
                                     Some(event)
                                 }
@@ -1458,6 +1560,8 @@ impl<
             );
         }
         let mut this = self.project();
+        #[cfg(feature = "metrics")]
+        crate::metrics::metrics().message_sent(this.metrics_peer, &message);
         this.stats.messages_sent += 1;
         this.stats.last_sent = Some(Utc::now());
         match &message {
@@ -1465,6 +1569,7 @@ impl<
                 this.stats.open_sent += 1;
                 this.sent_capabilities
                     .replace(open.capabilities().iter().map(|x| (*x).clone()).collect());
+                this.sent_open.replace(open.clone());
             }
             BgpMessage::Update(_) => {
                 match *this.keepalive_timer.as_mut() {
@@ -1473,8 +1578,9 @@ impl<
                 }
                 this.stats.update_sent += 1;
             }
-            BgpMessage::Notification(_) => {
+            BgpMessage::Notification(notif) => {
                 this.stats.notification_sent += 1;
+                this.last_notification_sent.replace(notif.clone());
             }
             BgpMessage::KeepAlive => {
                 match *this.keepalive_timer.as_mut() {
@@ -1513,12 +1619,112 @@ pub trait ActiveConnect<
     async fn connect(&mut self, peer_addr: P) -> io::Result<I>;
 }
 
-#[derive(Debug, Clone)]
-pub struct TcpActiveConnect;
+/// Initiate TCP connections to BGP peers, optionally installing the TCP
+/// authentication keys and applying the socket options configured for the
+/// peer before connecting.
+#[derive(Debug, Clone, Default)]
+pub struct TcpActiveConnect {
+    tcp_auth_keys: Option<TcpAuthKeys>,
+    instance: u32,
+    socket_config: TcpSocketConfig,
+}
+
+impl TcpActiveConnect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up the TCP MD5 or TCP-AO keys of the session in `tcp_auth_keys`,
+    /// see [TcpAuthKeys::lookup]
+    pub fn with_tcp_auth_keys(mut self, tcp_auth_keys: TcpAuthKeys) -> Self {
+        self.tcp_auth_keys.replace(tcp_auth_keys);
+        self
+    }
+
+    pub const fn tcp_auth_keys(&self) -> Option<&TcpAuthKeys> {
+        self.tcp_auth_keys.as_ref()
+    }
+
+    /// Routing instance used to look up the TCP authentication keys
+    pub const fn with_instance(mut self, instance: u32) -> Self {
+        self.instance = instance;
+        self
+    }
+
+    pub const fn instance(&self) -> u32 {
+        self.instance
+    }
+
+    /// Source address, TTL, GTSM, DSCP, and bind to device options
+    pub fn with_socket_config(mut self, socket_config: TcpSocketConfig) -> Self {
+        self.socket_config = socket_config;
+        self
+    }
+
+    pub const fn socket_config(&self) -> &TcpSocketConfig {
+        &self.socket_config
+    }
+}
 
 #[async_trait]
 impl ActiveConnect<SocketAddr, TcpStream, BgpCodec> for TcpActiveConnect {
     async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<TcpStream> {
-        TcpStream::connect(peer_addr).await
+        let socket = match peer_addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        self.socket_config.configure_connect(&socket, peer_addr)?;
+        let auth = self.tcp_auth_keys.as_ref().and_then(|keys| {
+            keys.lookup(
+                self.instance,
+                self.socket_config.local_addr(),
+                peer_addr.ip(),
+            )
+        });
+        if auth.is_some() {
+            update_tcp_auth(&socket, peer_addr.ip(), None, auth.as_ref())?;
+        }
+        socket.connect(peer_addr).await
+    }
+}
+
+/// Initiate BGP sessions over Unix domain sockets, e.g. with a speaker running
+/// in another process on the same host. The peers keep being identified by
+/// their [SocketAddr], which is mapped to the path of the socket the peer
+/// listens on.
+#[cfg(unix)]
+#[derive(Debug, Clone, Default)]
+pub struct UnixActiveConnect {
+    paths: HashMap<SocketAddr, PathBuf>,
+}
+
+#[cfg(unix)]
+impl UnixActiveConnect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect to `peer_addr` on the Unix socket at `path`
+    pub fn with_path(mut self, peer_addr: SocketAddr, path: PathBuf) -> Self {
+        self.paths.insert(peer_addr, path);
+        self
+    }
+
+    pub fn path(&self, peer_addr: &SocketAddr) -> Option<&PathBuf> {
+        self.paths.get(peer_addr)
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl ActiveConnect<SocketAddr, UnixStream, BgpCodec> for UnixActiveConnect {
+    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<UnixStream> {
+        match self.paths.get(&peer_addr) {
+            Some(path) => UnixStream::connect(path).await,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no Unix socket path configured for {peer_addr}"),
+            )),
+        }
     }
 }
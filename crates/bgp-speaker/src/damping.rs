@@ -0,0 +1,460 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BGP Route Flap Damping [RFC2439](https://datatracker.ietf.org/doc/html/rfc2439)
+//! with the updated default parameters of
+//! [RFC7196](https://datatracker.ietf.org/doc/html/rfc7196).
+//!
+//! [RouteFlapDamping] sits between the Adj-RIB-In of the peers and the
+//! Loc-RIB. Routes received from the peers are passed through
+//! [RouteFlapDamping::apply], which tracks a penalty per (peer, AFI/SAFI,
+//! prefix) and holds back the routes whose penalty exceeds the suppress
+//! threshold. [RouteFlapDamping::reuse] must be called periodically to
+//! release the suppressed routes once their penalty decays below the reuse
+//! threshold.
+//!
+//! Damping is enabled per peer with
+//! [crate::peer::PeerConfigBuilder::route_flap_damping]. The peer applies it
+//! to the routes it receives, schedules the reuse from
+//! [RouteFlapDamping::next_reuse], and reports the released routes as
+//! [crate::events::BgpEvent::DampedRoutesReused].
+
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::rib::{Route, RouteKey, UpdateRoutes};
+
+/// Route flap damping parameters, penalties and thresholds are unit-less
+/// figures of merit as defined in RFC2439.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct RouteFlapDampingConfig {
+    half_life: u16,
+    reuse_threshold: u32,
+    suppress_threshold: u32,
+    max_suppress_time: u16,
+    withdrawal_penalty: u32,
+    readvertisement_penalty: u32,
+    attribute_change_penalty: u32,
+}
+
+impl Default for RouteFlapDampingConfig {
+    fn default() -> Self {
+        Self {
+            half_life: 900,
+            reuse_threshold: 750,
+            // RFC 7196 recommends raising the suppress threshold to 6000
+            suppress_threshold: 6000,
+            max_suppress_time: 3600,
+            withdrawal_penalty: 1000,
+            readvertisement_penalty: 0,
+            attribute_change_penalty: 500,
+        }
+    }
+}
+
+impl RouteFlapDampingConfig {
+    pub const fn half_life(&self) -> Duration {
+        Duration::from_secs(self.half_life as u64)
+    }
+
+    pub const fn reuse_threshold(&self) -> u32 {
+        self.reuse_threshold
+    }
+
+    pub const fn suppress_threshold(&self) -> u32 {
+        self.suppress_threshold
+    }
+
+    pub const fn max_suppress_time(&self) -> Duration {
+        Duration::from_secs(self.max_suppress_time as u64)
+    }
+
+    pub const fn withdrawal_penalty(&self) -> u32 {
+        self.withdrawal_penalty
+    }
+
+    pub const fn readvertisement_penalty(&self) -> u32 {
+        self.readvertisement_penalty
+    }
+
+    pub const fn attribute_change_penalty(&self) -> u32 {
+        self.attribute_change_penalty
+    }
+
+    /// Max penalty a route can accumulate, ensures a suppressed route is
+    /// released after at most [RouteFlapDampingConfig::max_suppress_time]
+    /// once it stops flapping.
+    pub fn penalty_ceiling(&self) -> f64 {
+        if self.half_life == 0 {
+            return self.reuse_threshold as f64;
+        }
+        let exponent = self.max_suppress_time as f64 / self.half_life as f64;
+        self.reuse_threshold as f64 * exponent.exp2()
+    }
+
+    /// Decay a penalty over the elapsed time
+    fn decay(&self, penalty: f64, elapsed: Duration) -> f64 {
+        if self.half_life == 0 {
+            return 0.0;
+        }
+        penalty * (-elapsed.as_secs_f64() / self.half_life as f64).exp2()
+    }
+
+    /// Time needed for a penalty to decay below the reuse threshold
+    fn time_to_reuse(&self, penalty: f64) -> Duration {
+        let reuse = self.reuse_threshold as f64;
+        if penalty < reuse || reuse <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.half_life as f64 * (penalty / reuse).log2())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RouteFlapDampingConfigBuilder {
+    config: RouteFlapDampingConfig,
+}
+
+impl RouteFlapDampingConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub const fn half_life(mut self, value: u16) -> Self {
+        self.config.half_life = value;
+        self
+    }
+
+    pub const fn reuse_threshold(mut self, value: u32) -> Self {
+        self.config.reuse_threshold = value;
+        self
+    }
+
+    pub const fn suppress_threshold(mut self, value: u32) -> Self {
+        self.config.suppress_threshold = value;
+        self
+    }
+
+    pub const fn max_suppress_time(mut self, value: u16) -> Self {
+        self.config.max_suppress_time = value;
+        self
+    }
+
+    pub const fn withdrawal_penalty(mut self, value: u32) -> Self {
+        self.config.withdrawal_penalty = value;
+        self
+    }
+
+    pub const fn readvertisement_penalty(mut self, value: u32) -> Self {
+        self.config.readvertisement_penalty = value;
+        self
+    }
+
+    pub const fn attribute_change_penalty(mut self, value: u32) -> Self {
+        self.config.attribute_change_penalty = value;
+        self
+    }
+
+    pub const fn build(self) -> RouteFlapDampingConfig {
+        self.config
+    }
+}
+
+/// Damping history of a single route
+#[derive(Debug, Clone)]
+struct DampingEntry {
+    /// Penalty at the time of `last_update`
+    penalty: f64,
+    last_update: Instant,
+    flaps: u32,
+    suppressed: bool,
+    /// Last announcement of the route, `None` if the route is withdrawn
+    route: Option<Route>,
+}
+
+impl DampingEntry {
+    fn penalty(&self, config: &RouteFlapDampingConfig, now: Instant) -> f64 {
+        config.decay(
+            self.penalty,
+            now.saturating_duration_since(self.last_update),
+        )
+    }
+
+    fn add_penalty(&mut self, config: &RouteFlapDampingConfig, value: u32, now: Instant) {
+        let penalty = self.penalty(config, now) + value as f64;
+        self.penalty = penalty.min(config.penalty_ceiling());
+        self.last_update = now;
+    }
+}
+
+/// Damping state of a route as reported by the query API
+#[derive(Debug, Clone, PartialEq)]
+pub struct DampedRoute<K> {
+    peer: K,
+    key: RouteKey,
+    penalty: u32,
+    flaps: u32,
+    suppressed: bool,
+    reuse_in: Option<Duration>,
+}
+
+impl<K: Clone> DampedRoute<K> {
+    pub fn peer(&self) -> K {
+        self.peer.clone()
+    }
+
+    pub const fn key(&self) -> RouteKey {
+        self.key
+    }
+
+    /// Current penalty after decay
+    pub const fn penalty(&self) -> u32 {
+        self.penalty
+    }
+
+    /// Number of times the route has been withdrawn
+    pub const fn flaps(&self) -> u32 {
+        self.flaps
+    }
+
+    pub const fn suppressed(&self) -> bool {
+        self.suppressed
+    }
+
+    /// Time left till a suppressed route is released
+    pub const fn reuse_in(&self) -> Option<Duration> {
+        self.reuse_in
+    }
+}
+
+impl<K> DampedRoute<K> {
+    /// Replace the key of the peer the route is received from
+    pub(crate) fn with_peer<P>(self, peer: P) -> DampedRoute<P> {
+        DampedRoute {
+            peer,
+            key: self.key,
+            penalty: self.penalty,
+            flaps: self.flaps,
+            suppressed: self.suppressed,
+            reuse_in: self.reuse_in,
+        }
+    }
+}
+
+/// Track route flaps per (peer, AFI/SAFI, prefix) and suppress the routes
+/// that flap too often.
+#[derive(Debug, Clone)]
+pub struct RouteFlapDamping<K> {
+    config: RouteFlapDampingConfig,
+    entries: HashMap<(K, RouteKey), DampingEntry>,
+}
+
+impl<K: Hash + Eq + Clone> RouteFlapDamping<K> {
+    pub fn new(config: RouteFlapDampingConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub const fn config(&self) -> &RouteFlapDampingConfig {
+        &self.config
+    }
+
+    /// Update the damping history with the routes received from a peer and
+    /// return the routes to be passed to the Loc-RIB. Announcements of
+    /// suppressed routes are held back until they are reused, and routes
+    /// that became suppressed are withdrawn.
+    pub fn apply(&mut self, peer: K, update_routes: UpdateRoutes, now: Instant) -> UpdateRoutes {
+        let mut announced = vec![];
+        let mut withdrawn = vec![];
+        for key in update_routes.withdrawn() {
+            // The route is withdrawn from the Loc-RIB even if it was suppressed,
+            // since withdrawing an unknown route is harmless.
+            withdrawn.push(*key);
+            let entry_key = (peer.clone(), *key);
+            let entry = match self.entries.get_mut(&entry_key) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if entry.route.take().is_some() {
+                entry.flaps += 1;
+                entry.add_penalty(&self.config, self.config.withdrawal_penalty, now);
+            }
+            Self::update_suppressed(&self.config, entry, now);
+            if Self::is_obsolete(&self.config, entry, now) {
+                self.entries.remove(&entry_key);
+            }
+        }
+        for (key, route) in update_routes.announced() {
+            let entry = self
+                .entries
+                .entry((peer.clone(), *key))
+                .or_insert_with(|| DampingEntry {
+                    penalty: 0.0,
+                    last_update: now,
+                    flaps: 0,
+                    suppressed: false,
+                    route: None,
+                });
+            let penalty = match &entry.route {
+                None if entry.flaps > 0 => self.config.readvertisement_penalty,
+                None => 0,
+                Some(previous) if !previous.same_path(route) => {
+                    self.config.attribute_change_penalty
+                }
+                Some(_) => 0,
+            };
+            if penalty > 0 {
+                entry.add_penalty(&self.config, penalty, now);
+            }
+            let was_suppressed = entry.suppressed;
+            let was_announced = entry.route.is_some();
+            entry.route = Some(route.clone());
+            Self::update_suppressed(&self.config, entry, now);
+            if !entry.suppressed {
+                announced.push((*key, route.clone()));
+            } else if !was_suppressed && was_announced {
+                // Route is suppressed as a result of this change
+                withdrawn.push(*key);
+            }
+        }
+        UpdateRoutes::new(announced, withdrawn)
+    }
+
+    fn update_suppressed(config: &RouteFlapDampingConfig, entry: &mut DampingEntry, now: Instant) {
+        let penalty = entry.penalty(config, now);
+        if !entry.suppressed && penalty > config.suppress_threshold as f64 {
+            entry.suppressed = true;
+        } else if entry.suppressed && penalty <= config.reuse_threshold as f64 {
+            entry.suppressed = false;
+        }
+    }
+
+    /// The history of withdrawn routes that are not suppressed is forgotten
+    /// once their penalty decays below half of the reuse threshold.
+    fn is_obsolete(config: &RouteFlapDampingConfig, entry: &DampingEntry, now: Instant) -> bool {
+        entry.route.is_none()
+            && !entry.suppressed
+            && entry.penalty(config, now) < config.reuse_threshold as f64 / 2.0
+    }
+
+    /// Release the suppressed routes whose penalty decayed below the reuse
+    /// threshold. The routes that are still announced by the peers are
+    /// returned to be installed in the Loc-RIB.
+    pub fn reuse(&mut self, now: Instant) -> Vec<(K, UpdateRoutes)> {
+        let mut reused: HashMap<K, Vec<(RouteKey, Route)>> = HashMap::new();
+        for ((peer, key), entry) in &mut self.entries {
+            if !entry.suppressed {
+                continue;
+            }
+            Self::update_suppressed(&self.config, entry, now);
+            if entry.suppressed {
+                continue;
+            }
+            if let Some(route) = &entry.route {
+                reused
+                    .entry(peer.clone())
+                    .or_default()
+                    .push((*key, route.clone()));
+            }
+        }
+        let config = self.config;
+        self.entries
+            .retain(|_, entry| !Self::is_obsolete(&config, entry, now));
+        reused
+            .into_iter()
+            .map(|(peer, announced)| (peer, UpdateRoutes::new(announced, vec![])))
+            .collect()
+    }
+
+    /// Time at which the next suppressed route is due to be reused
+    pub fn next_reuse(&self, now: Instant) -> Option<Instant> {
+        self.entries
+            .values()
+            .filter(|entry| entry.suppressed)
+            .map(|entry| now + self.config.time_to_reuse(entry.penalty(&self.config, now)))
+            .min()
+    }
+
+    fn damped_route(
+        &self,
+        peer: &K,
+        key: &RouteKey,
+        entry: &DampingEntry,
+        now: Instant,
+    ) -> DampedRoute<K> {
+        let penalty = entry.penalty(&self.config, now);
+        DampedRoute {
+            peer: peer.clone(),
+            key: *key,
+            penalty: penalty.round() as u32,
+            flaps: entry.flaps,
+            suppressed: entry.suppressed,
+            reuse_in: if entry.suppressed {
+                Some(self.config.time_to_reuse(penalty))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Damping state of a given route, `None` if the route has no flap
+    /// history
+    pub fn get(&self, peer: &K, key: &RouteKey, now: Instant) -> Option<DampedRoute<K>> {
+        self.entries
+            .get(&(peer.clone(), *key))
+            .map(|entry| self.damped_route(peer, key, entry, now))
+    }
+
+    /// Routes that are currently suppressed
+    pub fn dampened_routes(&self, now: Instant) -> Vec<DampedRoute<K>> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.suppressed)
+            .map(|((peer, key), entry)| self.damped_route(peer, key, entry, now))
+            .collect()
+    }
+
+    /// All the routes with flap history, including the suppressed ones
+    pub fn flap_statistics(&self, now: Instant) -> Vec<DampedRoute<K>> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.penalty > 0.0)
+            .map(|((peer, key), entry)| self.damped_route(peer, key, entry, now))
+            .collect()
+    }
+
+    /// Clear the damping history of a peer, the suppressed routes that are
+    /// still announced by the peer are returned to be installed in the
+    /// Loc-RIB.
+    pub fn clear_peer(&mut self, peer: &K) -> UpdateRoutes {
+        let mut announced = vec![];
+        self.entries.retain(|(entry_peer, key), entry| {
+            if entry_peer != peer {
+                return true;
+            }
+            if entry.suppressed {
+                if let Some(route) = entry.route.take() {
+                    announced.push((*key, route));
+                }
+            }
+            false
+        });
+        UpdateRoutes::new(announced, vec![])
+    }
+}
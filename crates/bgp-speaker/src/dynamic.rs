@@ -0,0 +1,330 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dynamic peer ranges, accepting BGP sessions from peers that are not
+//! configured individually, similar to the BGP listen ranges of commercial
+//! routers.
+//!
+//! A [DynamicPeerRange] is matched against the source address of the incoming
+//! connections by [crate::listener::BgpListener], the most specific range
+//! wins. The peer is created from the range's [DynamicPeerTemplate] and is
+//! removed as soon as its session goes down, there is no idle timeout keeping
+//! it around waiting for the remote end to reconnect.
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    ops::RangeInclusive,
+};
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder};
+
+use netgauze_bgp_pkt::{
+    capabilities::BgpCapability,
+    codec::BgpCodecDecoderError,
+    notification::OpenMessageError,
+    open::BgpOpenMessage,
+    wire::{deserializer::BgpParsingIgnoredErrors, serializer::BgpMessageWritingError},
+    BgpMessage,
+};
+
+use crate::{
+    connection::Connection,
+    events::ConnectionEvent,
+    peer::{
+        EchoCapabilitiesPolicy, PeerConfig, PeerConfigBuilder, PeerEvent, PeerPolicy,
+        PeerProperties,
+    },
+    reflection::ClientRole,
+};
+
+/// ASNs a dynamic peer is allowed to use
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub enum RemoteAsns {
+    #[default]
+    Any,
+    List(Vec<u32>),
+    Range(RangeInclusive<u32>),
+}
+
+impl RemoteAsns {
+    pub fn contains(&self, asn: u32) -> bool {
+        match self {
+            Self::Any => true,
+            Self::List(asns) => asns.contains(&asn),
+            Self::Range(range) => range.contains(&asn),
+        }
+    }
+}
+
+/// Settings of the peers created from a [DynamicPeerRange]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DynamicPeerTemplate {
+    config: PeerConfig,
+    send_asn4_cap_by_default: bool,
+    capabilities: Vec<BgpCapability>,
+    reject_capabilities: Vec<BgpCapability>,
+    client_role: ClientRole,
+}
+
+impl Default for DynamicPeerTemplate {
+    fn default() -> Self {
+        Self::new(
+            PeerConfigBuilder::new()
+                // set open_delay_Timer to max, to allow the peer to communicate it's open
+                // message first
+                .open_delay_timer_duration(u16::MAX)
+                .passive_tcp_establishment(true)
+                .build(),
+        )
+    }
+}
+
+impl DynamicPeerTemplate {
+    /// The peer's capabilities are echoed back only when `config` has a large
+    /// enough OpenDelayTimer, see [EchoCapabilitiesPolicy]. Dynamic peers
+    /// are expected to use passive TCP establishment.
+    pub const fn new(config: PeerConfig) -> Self {
+        Self {
+            config,
+            send_asn4_cap_by_default: true,
+            capabilities: Vec::new(),
+            reject_capabilities: Vec::new(),
+            client_role: ClientRole::NonClient,
+        }
+    }
+
+    pub fn with_send_asn4_cap_by_default(mut self, value: bool) -> Self {
+        self.send_asn4_cap_by_default = value;
+        self
+    }
+
+    /// Capabilities always sent to the peer
+    pub fn with_capabilities(mut self, value: Vec<BgpCapability>) -> Self {
+        self.capabilities = value;
+        self
+    }
+
+    /// Capabilities of the peer that are not echoed back
+    pub fn with_reject_capabilities(mut self, value: Vec<BgpCapability>) -> Self {
+        self.reject_capabilities = value;
+        self
+    }
+
+    pub fn with_client_role(mut self, value: ClientRole) -> Self {
+        self.client_role = value;
+        self
+    }
+
+    pub const fn config(&self) -> &PeerConfig {
+        &self.config
+    }
+
+    pub const fn capabilities(&self) -> &Vec<BgpCapability> {
+        &self.capabilities
+    }
+
+    pub const fn reject_capabilities(&self) -> &Vec<BgpCapability> {
+        &self.reject_capabilities
+    }
+
+    pub const fn client_role(&self) -> ClientRole {
+        self.client_role
+    }
+
+    /// Properties of a peer created from the template, the peer ASN is
+    /// learned from its OPEN message
+    pub fn properties<A: Clone>(
+        &self,
+        my_asn: u32,
+        my_bgp_id: Ipv4Addr,
+        peer_addr: A,
+    ) -> PeerProperties<A> {
+        PeerProperties::new(my_asn, my_asn, my_bgp_id, peer_addr, true)
+            .with_client_role(self.client_role)
+    }
+
+    /// Policy of a peer created from the template
+    pub fn policy<A, I, D>(
+        &self,
+        my_asn: u32,
+        my_bgp_id: Ipv4Addr,
+        remote_asns: RemoteAsns,
+    ) -> DynamicPeerPolicy<A, I, D> {
+        let hold_timer_duration = self.config.hold_timer_duration().as_secs() as u16;
+        DynamicPeerPolicy::new(
+            EchoCapabilitiesPolicy::new(
+                my_asn,
+                self.send_asn4_cap_by_default,
+                my_bgp_id,
+                hold_timer_duration,
+                self.capabilities.clone(),
+                self.reject_capabilities.clone(),
+            ),
+            remote_asns,
+        )
+    }
+}
+
+/// Source prefix from which dynamic peers are accepted, along with the ASNs
+/// they can use and the template the peers are created from
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DynamicPeerRange {
+    prefix: IpNet,
+    remote_asns: RemoteAsns,
+    template: DynamicPeerTemplate,
+    max_peers: Option<usize>,
+}
+
+impl DynamicPeerRange {
+    pub fn new(prefix: IpNet, template: DynamicPeerTemplate) -> Self {
+        Self {
+            prefix: prefix.trunc(),
+            remote_asns: RemoteAsns::Any,
+            template,
+            max_peers: None,
+        }
+    }
+
+    pub fn with_remote_asns(mut self, value: RemoteAsns) -> Self {
+        self.remote_asns = value;
+        self
+    }
+
+    /// Maximum number of dynamic peers running at the same time from this
+    /// range, further connections are rejected. A peer frees its slot once
+    /// its session goes down.
+    pub fn with_max_peers(mut self, value: Option<usize>) -> Self {
+        self.max_peers = value;
+        self
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+
+    pub const fn remote_asns(&self) -> &RemoteAsns {
+        &self.remote_asns
+    }
+
+    pub const fn template(&self) -> &DynamicPeerTemplate {
+        &self.template
+    }
+
+    pub const fn max_peers(&self) -> Option<usize> {
+        self.max_peers
+    }
+
+    pub fn contains(&self, peer_ip: &IpAddr) -> bool {
+        self.prefix.contains(&peer_ip.to_canonical())
+    }
+}
+
+/// Find the most specific range containing `peer_ip`
+pub fn match_dynamic_peer_range<'a>(
+    ranges: &'a [DynamicPeerRange],
+    peer_ip: &IpAddr,
+) -> Option<&'a DynamicPeerRange> {
+    ranges
+        .iter()
+        .filter(|range| range.contains(peer_ip))
+        .max_by_key(|range| range.prefix.prefix_len())
+}
+
+/// [EchoCapabilitiesPolicy] that rejects the OPEN messages of peers using an
+/// ASN that is not allowed by the peer's [DynamicPeerRange]. The session is
+/// closed with a Bad Peer AS notification.
+#[derive(Debug, Clone)]
+pub struct DynamicPeerPolicy<A, I, D> {
+    inner: EchoCapabilitiesPolicy<A, I, D>,
+    remote_asns: RemoteAsns,
+}
+
+impl<A, I, D> DynamicPeerPolicy<A, I, D> {
+    pub const fn new(inner: EchoCapabilitiesPolicy<A, I, D>, remote_asns: RemoteAsns) -> Self {
+        Self { inner, remote_asns }
+    }
+
+    pub const fn remote_asns(&self) -> &RemoteAsns {
+        &self.remote_asns
+    }
+}
+
+#[async_trait]
+impl<
+        A: Send + Sync + 'static,
+        I: AsyncWrite + AsyncRead + Send + Sync + 'static,
+        D: Decoder<Item = (BgpMessage, BgpParsingIgnoredErrors), Error = BgpCodecDecoderError>
+            + Encoder<BgpMessage, Error = BgpMessageWritingError>
+            + Send
+            + Sync,
+    > PeerPolicy<A, I, D> for DynamicPeerPolicy<A, I, D>
+{
+    async fn open_message(&mut self) -> BgpOpenMessage {
+        self.inner.open_message().await
+    }
+
+    async fn pre_handle_connection_event_hook(
+        &mut self,
+        event: ConnectionEvent<A>,
+        connection: &Connection<A, I, D>,
+    ) -> ConnectionEvent<A> {
+        match &event {
+            ConnectionEvent::BGPOpen(open) | ConnectionEvent::BGPOpenWithDelayOpenTimer(open)
+                if !self.remote_asns.contains(open.my_asn4()) =>
+            {
+                log::info!(
+                    "Rejected dynamic peer with ASN {} not in {:?}",
+                    open.my_asn4(),
+                    self.remote_asns
+                );
+                ConnectionEvent::BGPOpenMsgErr(OpenMessageError::BadPeerAs {
+                    value: open.my_asn4().to_be_bytes().to_vec(),
+                })
+            }
+            _ => {
+                self.inner
+                    .pre_handle_connection_event_hook(event, connection)
+                    .await
+            }
+        }
+    }
+
+    async fn post_handle_connection_event_hook(
+        &self,
+        event: ConnectionEvent<A>,
+        connection: Option<&Connection<A, I, D>>,
+    ) -> ConnectionEvent<A> {
+        self.inner
+            .post_handle_connection_event_hook(event, connection)
+            .await
+    }
+
+    async fn pre_handle_peer_event_hook(
+        &self,
+        event: Option<PeerEvent<A, I>>,
+    ) -> Option<PeerEvent<A, I>> {
+        self.inner.pre_handle_peer_event_hook(event).await
+    }
+
+    async fn post_handle_peer_event_hook(
+        &self,
+        event: Option<PeerEvent<A, I>>,
+    ) -> Option<PeerEvent<A, I>> {
+        self.inner.post_handle_peer_event_hook(event).await
+    }
+}
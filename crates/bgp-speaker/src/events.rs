@@ -101,7 +101,7 @@ impl Ord for UpdateTreatment {
     }
 }
 
-#[derive(Debug, PartialEq, strum_macros::Display, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, strum_macros::Display, serde::Serialize, serde::Deserialize)]
 pub enum BgpEvent<A> {
     /// **Event 1:** Local system administrator manually starts the peer
     /// connection.
@@ -316,6 +316,73 @@ pub enum BgpEvent<A> {
     RouteRefresh(BgpRouteRefreshMessage),
 
     RouteRefreshErr(RouteRefreshError),
+
+    /// Routes that were marked as stale after receiving a Beginning of Route
+    /// Refresh (BoRR) message and were not re-advertised by the peer before
+    /// the End of Route Refresh (EoRR) message. The routes are purged from
+    /// the Adj-RIB-In and reported as a synthesized BGP Update message.
+    ///
+    /// This event is not defined in RFC4271, see
+    /// [RFC7313](https://datatracker.ietf.org/doc/html/rfc7313).
+    StaleRoutesWithdrawn(BgpUpdateMessage),
+
+    /// Routes received from the peer that were suppressed by the route flap
+    /// damping and are now released since their penalty decayed below the
+    /// reuse threshold. The routes are reported as a synthesized BGP Update
+    /// message.
+    ///
+    /// This event is not defined in RFC4271, see
+    /// [RFC2439](https://datatracker.ietf.org/doc/html/rfc2439).
+    DampedRoutesReused(BgpUpdateMessage),
+
+    /// The route flap damping reuse timer expired, the released routes are
+    /// reported in the subsequent [BgpEvent::DampedRoutesReused] events.
+    ///
+    /// This event is not defined in RFC4271, the reuse timer is described in
+    /// [RFC2439 Section 4.8.6](https://datatracker.ietf.org/doc/html/rfc2439#section-4.8.6).
+    RouteFlapDampingReuseTimerExpires,
+
+    /// Routes received from the peer affected by a change of the resolution
+    /// of their next hop, reported as a synthesized BGP Update message. The
+    /// routes whose next hop became unreachable are withdrawn, and the routes
+    /// whose next hop is reachable again or at a different IGP metric are
+    /// announced.
+    ///
+    /// This event is not defined in RFC4271, see [crate::nexthop].
+    NextHopRoutesChanged(BgpUpdateMessage),
+
+    /// The resolution of the next hops of the received routes changed, the
+    /// affected routes are reported in the subsequent
+    /// [BgpEvent::NextHopRoutesChanged] events.
+    ///
+    /// This event is not defined in RFC4271, see [crate::nexthop].
+    NextHopResolutionChanged,
+
+    /// The MinRouteAdvertisementIntervalTimer expired and the exported routes
+    /// held back by it are advertised.
+    ///
+    /// This event is not defined in the RFC4271 FSM, the timer is described in
+    /// [RFC4271 Section 9.2.1.1](https://datatracker.ietf.org/doc/html/rfc4271#section-9.2.1.1).
+    MinRouteAdvertisementIntervalTimerExpires,
+
+    /// The data used to validate the origin of the received routes changed,
+    /// and the routes received from the peer are validated again. The routes
+    /// whose validation changed are reported in the subsequent
+    /// [BgpEvent::RevalidatedRoutesChanged] events.
+    ///
+    /// This event is not defined in RFC4271, see
+    /// [RFC8481](https://datatracker.ietf.org/doc/html/rfc8481#section-4).
+    #[cfg(feature = "rpki")]
+    RouteOriginValidationDataChanged,
+
+    /// Routes received from the peer whose origin validation changed,
+    /// reported as a synthesized BGP Update message. The routes that are no
+    /// longer accepted are withdrawn, and the routes that are accepted again
+    /// or with a different validation state are announced.
+    ///
+    /// This event is not defined in RFC4271, see [crate::rpki].
+    #[cfg(feature = "rpki")]
+    RevalidatedRoutesChanged(BgpUpdateMessage),
 }
 
 /// Subset of BGP Events defined [RFC4271](https://datatracker.ietf.org/doc/html/rfc4271) that
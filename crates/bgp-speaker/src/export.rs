@@ -0,0 +1,393 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of the routes injected into the speaker to its peers.
+//!
+//! Routes are announced or withdrawn for all the peers at once using
+//! [crate::supervisor::PeersSupervisor::announce_routes] and
+//! [crate::supervisor::PeersSupervisor::withdraw_routes]. Each peer then
+//! applies its [ExportConfig] and the iBGP/eBGP rules in [export_route]
+//! before packing the routes into BGP Update messages, rate limited by the
+//! MinRouteAdvertisementIntervalTimer (MRAI).
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    iana::AS_TRANS,
+    path_attribute::{
+        As2PathSegment, As4Path, As4PathSegment, AsPath, AsPathSegmentType, LocalPreference,
+        NextHop, PathAttribute, PathAttributeValue,
+    },
+};
+
+use crate::{
+    confederation::strip_confed_segments,
+    peer::PeerProperties,
+    reflection::{reflect_attributes, strip_reflection_attributes, ClientRole},
+    rib::{insert_attribute, MpNextHop, Route, RouteKey},
+    role::otc_egress,
+};
+
+/// RFC4271 recommends LOCAL_PREF of routes sent to internal peers to be set,
+/// this value is used when the route doesn't carry one.
+pub const DEFAULT_LOCAL_PREF: u32 = 100;
+
+/// Max number of AS numbers in a single AS_PATH segment
+const MAX_AS_PATH_SEGMENT_LEN: usize = u8::MAX as usize;
+
+/// Where a route injected into the speaker is learned from, used to apply the
+/// iBGP advertisement rules
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RouteSource {
+    /// Originated by the speaker itself
+    Local,
+    /// Learned from an external peer
+    External,
+    /// Learned from the internal peer with the BGP Identifier `peer_bgp_id`,
+    /// `from_client` is set when the peer is a route reflector client
+    Internal {
+        peer_bgp_id: Ipv4Addr,
+        from_client: bool,
+    },
+}
+
+/// A route injected into the speaker to be exported to its peers
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportRoute {
+    key: RouteKey,
+    route: Route,
+    source: RouteSource,
+}
+
+impl ExportRoute {
+    pub const fn new(key: RouteKey, route: Route, source: RouteSource) -> Self {
+        Self { key, route, source }
+    }
+
+    /// A route originated by the speaker itself
+    pub const fn local(key: RouteKey, route: Route) -> Self {
+        Self::new(key, route, RouteSource::Local)
+    }
+
+    pub const fn key(&self) -> &RouteKey {
+        &self.key
+    }
+
+    pub const fn route(&self) -> &Route {
+        &self.route
+    }
+
+    pub const fn source(&self) -> RouteSource {
+        self.source
+    }
+}
+
+/// Per peer settings applied to the routes exported to the peer
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct ExportConfig {
+    next_hop_self_ipv4: Option<Ipv4Addr>,
+    next_hop_self_ipv6: Option<Ipv6Addr>,
+    as_prepend: u8,
+    mrai: u16,
+}
+
+impl ExportConfig {
+    /// Rewrite the next hop of the exported IPv4 routes to the given address
+    pub const fn with_next_hop_self_ipv4(mut self, value: Option<Ipv4Addr>) -> Self {
+        self.next_hop_self_ipv4 = value;
+        self
+    }
+
+    /// Rewrite the next hop of the exported IPv6 routes to the given address
+    pub const fn with_next_hop_self_ipv6(mut self, value: Option<Ipv6Addr>) -> Self {
+        self.next_hop_self_ipv6 = value;
+        self
+    }
+
+    /// Number of times the local ASN is prepended to the AS_PATH, in addition
+    /// to the one mandated for external peers
+    pub const fn with_as_prepend(mut self, value: u8) -> Self {
+        self.as_prepend = value;
+        self
+    }
+
+    /// MinRouteAdvertisementIntervalTimer in seconds, zero disables it
+    pub const fn with_mrai(mut self, value: u16) -> Self {
+        self.mrai = value;
+        self
+    }
+
+    pub const fn next_hop_self_ipv4(&self) -> Option<Ipv4Addr> {
+        self.next_hop_self_ipv4
+    }
+
+    pub const fn next_hop_self_ipv6(&self) -> Option<Ipv6Addr> {
+        self.next_hop_self_ipv6
+    }
+
+    pub const fn as_prepend(&self) -> u8 {
+        self.as_prepend
+    }
+
+    /// Min time between two advertisements of routes to the peer, withdrawals
+    /// are not delayed
+    pub const fn mrai(&self) -> Duration {
+        Duration::from_secs(self.mrai as u64)
+    }
+}
+
+/// Apply the export rules to a route before sending it to a peer, returns
+/// `None` if the route must not be advertised to the peer.
+///
+/// * External peers: the local ASN is prepended to the AS_PATH
+///   ([ExportConfig::as_prepend] + 1 times), LOCAL_PREF, ORIGINATOR_ID and
+///   CLUSTER_LIST are removed, and MED is only kept for locally originated
+///   routes.
+/// * Route server clients ([ClientRole::RouteServerClient]): same as external
+///   peers, except the AS_PATH, MED and next hop are left untouched.
+/// * Internal peers: routes learned from internal peers are only advertised
+///   when reflected, i.e. either the route is learned from a route reflector
+///   client or the peer is a client ([ClientRole::RouteReflectorClient]).
+///   Reflected routes carry the ORIGINATOR_ID and CLUSTER_LIST attributes.
+///   LOCAL_PREF is set to [DEFAULT_LOCAL_PREF] when missing.
+/// * External peers with a BGP Role: the OTC egress procedure of RFC9234 is
+///   applied, routes carrying the OTC attribute are not advertised to
+///   providers, peers and route servers.
+/// * The next hop is rewritten when next-hop-self is configured for the
+///   route's address family.
+pub fn export_route<A: Clone>(
+    properties: &PeerProperties<A>,
+    config: &ExportConfig,
+    key: &RouteKey,
+    route: &Route,
+    source: RouteSource,
+) -> Option<Route> {
+    let internal = properties.is_internal();
+    let confed_external = properties.is_confed_external();
+    let route_server =
+        !internal && !confed_external && properties.client_role() == ClientRole::RouteServerClient;
+    let mut attributes = route.attributes().clone();
+    let has_as_path = attributes
+        .iter()
+        .any(|attr| matches!(attr.value(), PathAttributeValue::AsPath(_)));
+    if !has_as_path {
+        // Locally originated routes start with an empty AS_PATH
+        let as_path = PathAttribute::from(
+            false,
+            true,
+            false,
+            false,
+            PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![])),
+        )
+        .ok()?;
+        insert_attribute(&mut attributes, as_path);
+    }
+    if internal {
+        if let RouteSource::Internal {
+            peer_bgp_id,
+            from_client,
+        } = source
+        {
+            if !from_client && properties.client_role() != ClientRole::RouteReflectorClient {
+                return None;
+            }
+            reflect_attributes(&mut attributes, peer_bgp_id, properties.cluster_id())?;
+        }
+        let has_local_pref = attributes
+            .iter()
+            .any(|attr| matches!(attr.value(), PathAttributeValue::LocalPreference(_)));
+        if !has_local_pref {
+            let local_pref = PathAttribute::from(
+                false,
+                true,
+                false,
+                false,
+                PathAttributeValue::LocalPreference(LocalPreference::new(DEFAULT_LOCAL_PREF)),
+            )
+            .ok()?;
+            insert_attribute(&mut attributes, local_pref);
+        }
+    } else if confed_external {
+        // RFC5065: NEXT_HOP, MED and LOCAL_PREF are kept within the confederation,
+        // while route reflection is local to the member AS
+        strip_reflection_attributes(&mut attributes);
+    } else {
+        strip_reflection_attributes(&mut attributes);
+        attributes.retain(|attr| match attr.value() {
+            PathAttributeValue::LocalPreference(_) => false,
+            // RFC4271: MED received from a neighboring AS MUST NOT be propagated to other
+            // neighboring ASes
+            PathAttributeValue::MultiExitDiscriminator(_) => {
+                route_server || source == RouteSource::Local
+            }
+            _ => true,
+        });
+        // RFC8097: the validation state community is not sent to other ASes
+        #[cfg(feature = "rpki")]
+        netgauze_rpki::rov::set_origin_validation_state(&mut attributes, None);
+    }
+    if let Some(role) = properties.bgp_role() {
+        if !otc_egress(role, properties.my_asn(), &mut attributes) {
+            return None;
+        }
+    }
+    if confed_external {
+        // RFC5065: the member AS is prepended to the AS_CONFED_SEQUENCE, which is only
+        // carried in the AS_PATH
+        for attr in &mut attributes {
+            let value = match attr.value() {
+                PathAttributeValue::AsPath(as_path) => PathAttributeValue::AsPath(prepend_as_path(
+                    as_path,
+                    AsPathSegmentType::AsConfedSequence,
+                    properties.my_asn(),
+                    1,
+                )),
+                _ => continue,
+            };
+            *attr = PathAttribute::from(
+                attr.optional(),
+                attr.transitive(),
+                attr.partial(),
+                attr.extended_length(),
+                value,
+            )
+            .ok()?;
+        }
+    } else if !internal && !route_server {
+        let count = config.as_prepend() as usize + 1;
+        let asn = properties.local_asn();
+        for attr in &mut attributes {
+            let value = match attr.value() {
+                PathAttributeValue::AsPath(as_path) => PathAttributeValue::AsPath(prepend_as_path(
+                    &strip_confed_segments(as_path),
+                    AsPathSegmentType::AsSequence,
+                    asn,
+                    count,
+                )),
+                PathAttributeValue::As4Path(as4_path) => {
+                    let segments = as4_path
+                        .segments()
+                        .iter()
+                        .filter(|segment| !segment.segment_type().is_confed())
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    PathAttributeValue::As4Path(As4Path::new(prepend_as4_segments(
+                        &segments,
+                        AsPathSegmentType::AsSequence,
+                        asn,
+                        count,
+                    )))
+                }
+                _ => continue,
+            };
+            *attr = PathAttribute::from(
+                attr.optional(),
+                attr.transitive(),
+                attr.partial(),
+                attr.extended_length(),
+                value,
+            )
+            .ok()?;
+        }
+    }
+
+    let mut mp_next_hop = route.mp_next_hop();
+    let next_hop_self = if route_server {
+        None
+    } else if key.prefix().addr().is_ipv4() {
+        config.next_hop_self_ipv4().map(IpAddr::V4)
+    } else {
+        config.next_hop_self_ipv6().map(IpAddr::V6)
+    };
+    if let Some(next_hop_self) = next_hop_self {
+        match (mp_next_hop, next_hop_self) {
+            (Some(_), _) => mp_next_hop = Some(MpNextHop::new(next_hop_self, None)),
+            (None, IpAddr::V4(next_hop_self)) => {
+                for attr in &mut attributes {
+                    if let PathAttributeValue::NextHop(_) = attr.value() {
+                        *attr = PathAttribute::from(
+                            attr.optional(),
+                            attr.transitive(),
+                            attr.partial(),
+                            attr.extended_length(),
+                            PathAttributeValue::NextHop(NextHop::new(next_hop_self)),
+                        )
+                        .ok()?;
+                    }
+                }
+            }
+            (None, IpAddr::V6(_)) => {}
+        }
+    }
+    Some(Route::new(attributes, mp_next_hop))
+}
+
+fn prepend_as_path(
+    as_path: &AsPath,
+    segment_type: AsPathSegmentType,
+    asn: u32,
+    count: usize,
+) -> AsPath {
+    match as_path {
+        AsPath::As2PathSegments(segments) => {
+            let asn = u16::try_from(asn).unwrap_or(AS_TRANS);
+            let mut segments = segments.clone();
+            for _ in 0..count {
+                match segments.first_mut() {
+                    Some(first)
+                        if first.segment_type() == segment_type
+                            && first.as_numbers().len() < MAX_AS_PATH_SEGMENT_LEN =>
+                    {
+                        let mut as_numbers = first.as_numbers().clone();
+                        as_numbers.insert(0, asn);
+                        *first = As2PathSegment::new(segment_type, as_numbers);
+                    }
+                    _ => segments.insert(0, As2PathSegment::new(segment_type, vec![asn])),
+                }
+            }
+            AsPath::As2PathSegments(segments)
+        }
+        AsPath::As4PathSegments(segments) => {
+            AsPath::As4PathSegments(prepend_as4_segments(segments, segment_type, asn, count))
+        }
+    }
+}
+
+fn prepend_as4_segments(
+    segments: &[As4PathSegment],
+    segment_type: AsPathSegmentType,
+    asn: u32,
+    count: usize,
+) -> Vec<As4PathSegment> {
+    let mut segments = segments.to_vec();
+    for _ in 0..count {
+        match segments.first_mut() {
+            Some(first)
+                if first.segment_type() == segment_type
+                    && first.as_numbers().len() < MAX_AS_PATH_SEGMENT_LEN =>
+            {
+                let mut as_numbers = first.as_numbers().clone();
+                as_numbers.insert(0, asn);
+                *first = As4PathSegment::new(segment_type, as_numbers);
+            }
+            _ => segments.insert(0, As4PathSegment::new(segment_type, vec![asn])),
+        }
+    }
+    segments
+}
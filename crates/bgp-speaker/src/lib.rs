@@ -20,12 +20,36 @@ use netgauze_bgp_pkt::codec::BgpCodec;
 
 pub type BgpFramed = Framed<TcpStream, BgpCodec>;
 
+pub mod auth;
+pub mod bfd;
+#[cfg(feature = "bmp")]
+pub mod bmp;
+pub mod confederation;
 pub mod connection;
+pub mod damping;
+pub mod dynamic;
 pub mod events;
+pub mod export;
 pub mod fsm;
 pub mod listener;
+pub mod liveness;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mrt")]
+pub mod mrt;
+pub mod multisession;
+pub mod nexthop;
 pub mod peer;
 pub mod peer_controller;
+pub mod reflection;
+pub mod rib;
+pub mod role;
+#[cfg(feature = "rpki")]
+pub mod rpki;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod socket;
+pub mod stats;
 pub mod supervisor;
 
 #[cfg(test)]
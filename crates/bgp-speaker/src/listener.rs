@@ -20,18 +20,165 @@ use std::{
     io,
     net::{IpAddr, SocketAddr},
     pin::Pin,
+    str::FromStr,
     task::{Context, Poll},
 };
 
-use crate::connection::TcpActiveConnect;
+use crate::{
+    auth::{listener_tcp_auth, update_tcp_auth, TcpAuth},
+    connection::TcpActiveConnect,
+    dynamic::{match_dynamic_peer_range, DynamicPeerRange, DynamicPeerTemplate},
+    socket::{enable_save_syn, TcpSocketConfig, GTSM_TTL},
+};
 use futures_util::stream::FuturesUnordered;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::{TcpListener, TcpStream},
+    net::{TcpListener, TcpSocket, TcpStream},
+    sync::mpsc,
 };
 use tokio_stream::StreamExt;
 
-use crate::{fsm::FsmState, peer_controller::PeerHandle, supervisor::PeersSupervisor};
+use crate::{
+    fsm::FsmState,
+    peer_controller::{PeerHandle, PeerStateResult},
+    supervisor::PeersSupervisor,
+};
+
+/// Event of a dynamic peer, tagged with the peer's session key
+pub type DynamicPeerEvent<A> = (SessionKey, PeerStateResult<A>);
+
+/// Progress of a dynamic peer, reported to the listener by the task waiting
+/// for its session
+#[derive(Debug)]
+enum DynamicPeerStatus<A: Display, I: AsyncWrite + AsyncRead> {
+    /// The peer reached OpenConfirm, its connections are now accepted by the
+    /// listener as for a registered peer
+    Up(SessionKey, PeerHandle<A, I>),
+    /// The session failed or went down, the peer is removed
+    Down(SessionKey),
+}
+
+/// Identifies a BGP session by the routing instance (i.e., VRF), the local
+/// address, and the ip address of the peer. Multiple sessions to the same peer
+/// address can be run from different local addresses or instances.
+///
+/// When the local address is not set, the key matches connections to any of
+/// the local addresses of the instance that are not claimed by a more
+/// specific key.
+///
+/// The string representation is `[vrf<instance>:]<peer>[@<local>]`, the
+/// instance is omitted when it's the default instance `0`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct SessionKey {
+    instance: u32,
+    local_ip: Option<IpAddr>,
+    peer_ip: IpAddr,
+}
+
+impl SessionKey {
+    pub const fn new(peer_ip: IpAddr) -> Self {
+        Self {
+            instance: 0,
+            local_ip: None,
+            peer_ip,
+        }
+    }
+
+    pub const fn with_local_ip(mut self, local_ip: Option<IpAddr>) -> Self {
+        self.local_ip = local_ip;
+        self
+    }
+
+    pub const fn with_instance(mut self, instance: u32) -> Self {
+        self.instance = instance;
+        self
+    }
+
+    pub const fn instance(&self) -> u32 {
+        self.instance
+    }
+
+    pub const fn local_ip(&self) -> Option<IpAddr> {
+        self.local_ip
+    }
+
+    pub const fn peer_ip(&self) -> IpAddr {
+        self.peer_ip
+    }
+}
+
+impl From<IpAddr> for SessionKey {
+    fn from(peer_ip: IpAddr) -> Self {
+        Self::new(peer_ip)
+    }
+}
+
+impl Display for SessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.instance != 0 {
+            write!(f, "vrf{}:", self.instance)?;
+        }
+        write!(f, "{}", self.peer_ip)?;
+        if let Some(local_ip) = self.local_ip {
+            write!(f, "@{local_ip}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidSessionKey(pub String);
+
+impl Display for InvalidSessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid session key: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidSessionKey {}
+
+impl FromStr for SessionKey {
+    type Err = InvalidSessionKey;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidSessionKey(value.to_string());
+        let (instance, addresses) = match value.strip_prefix("vrf") {
+            Some(rest) => {
+                let (instance, addresses) = rest.split_once(':').ok_or_else(invalid)?;
+                (instance.parse().map_err(|_| invalid())?, addresses)
+            }
+            None => (0, value),
+        };
+        let (peer_ip, local_ip) = match addresses.split_once('@') {
+            Some((peer_ip, local_ip)) => (peer_ip, Some(local_ip)),
+            None => (addresses, None),
+        };
+        let peer_ip = peer_ip.parse().map_err(|_| invalid())?;
+        let local_ip = local_ip
+            .map(|local_ip| local_ip.parse().map_err(|_| invalid()))
+            .transpose()?;
+        Ok(Self::new(peer_ip)
+            .with_local_ip(local_ip)
+            .with_instance(instance))
+    }
+}
+
+impl From<SessionKey> for String {
+    fn from(key: SessionKey) -> Self {
+        key.to_string()
+    }
+}
+
+impl TryFrom<String> for SessionKey {
+    type Error = InvalidSessionKey;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
 
 /// A modified version of Tokio's TcpListenerStream wrapper that returns the
 /// peer socket along the incoming stream
@@ -52,6 +199,12 @@ impl TcpListenerStream {
     }
 }
 
+impl AsRef<TcpListener> for TcpListenerStream {
+    fn as_ref(&self) -> &TcpListener {
+        &self.inner
+    }
+}
+
 impl Stream for TcpListenerStream {
     type Item = io::Result<(TcpStream, SocketAddr)>;
 
@@ -67,11 +220,39 @@ impl Stream for TcpListenerStream {
 #[derive(Debug)]
 pub struct BgpListener<A: Display, I: AsyncWrite + AsyncRead> {
     sockets: Vec<SocketAddr>,
-    /// Holding PeerHandle to control peers, and indexed by ip address of the
-    /// peer
-    peers: HashMap<IpAddr, PeerHandle<A, I>>,
+    /// Routing instance of the listening sockets, used to look up the
+    /// session keys of the accepted connections
+    instance: u32,
+    /// Holding PeerHandle to control peers, and indexed by the session key
+    peers: HashMap<SessionKey, PeerHandle<A, I>>,
     // TODO: change the flag to a policy trait
     allow_dynamic_peers: bool,
+    /// When not empty, only dynamic peers from these ranges are accepted
+    dynamic_peer_ranges: Vec<DynamicPeerRange>,
+    /// Running dynamic peers, mapped to the prefix of the range they were
+    /// accepted from
+    dynamic_peers: HashMap<SessionKey, IpNet>,
+    /// Notifies the listener when the session of a dynamic peer is up, or
+    /// goes down so the peer is removed
+    dynamic_peer_status_tx: mpsc::UnboundedSender<DynamicPeerStatus<A, I>>,
+    dynamic_peer_status_rx: mpsc::UnboundedReceiver<DynamicPeerStatus<A, I>>,
+    /// Where the events of the established dynamic peers are forwarded, when
+    /// not set they're only logged
+    dynamic_peer_events_tx: Option<mpsc::UnboundedSender<DynamicPeerEvent<A>>>,
+    /// Socket options applied to the listening sockets, and to the accepted
+    /// connections of peers without a specific config
+    socket_config: TcpSocketConfig,
+    /// Socket options for the accepted connections, indexed by the session
+    /// key
+    peer_socket_configs: HashMap<SessionKey, TcpSocketConfig>,
+    /// Sockets bound by the first call to [BgpListener::run], kept so the
+    /// listener can be run again without dropping pending connections
+    listening_sockets: Vec<TcpListenerStream>,
+    /// Socket options the listening sockets were bound with, and if they
+    /// save the received SYN packets
+    listening_socket_config: Option<(TcpSocketConfig, bool)>,
+    /// TCP authentication keys installed on the listening sockets
+    listening_tcp_auth: HashMap<SessionKey, TcpAuth>,
 }
 
 impl<
@@ -80,26 +261,195 @@ impl<
     > BgpListener<A, I>
 {
     pub fn new(sockets: Vec<SocketAddr>, allow_dynamic_peers: bool) -> Self {
+        let (dynamic_peer_status_tx, dynamic_peer_status_rx) = mpsc::unbounded_channel();
         Self {
             sockets,
+            instance: 0,
             peers: HashMap::new(),
             allow_dynamic_peers,
+            dynamic_peer_ranges: Vec::new(),
+            dynamic_peers: HashMap::new(),
+            dynamic_peer_status_tx,
+            dynamic_peer_status_rx,
+            dynamic_peer_events_tx: None,
+            socket_config: TcpSocketConfig::default(),
+            peer_socket_configs: HashMap::new(),
+            listening_sockets: Vec::new(),
+            listening_socket_config: None,
+            listening_tcp_auth: HashMap::new(),
+        }
+    }
+
+    /// Change the listening sockets, they're re-bound by the next call to
+    /// [BgpListener::run] only when they changed
+    pub fn set_sockets(&mut self, sockets: Vec<SocketAddr>) {
+        if sockets != self.sockets {
+            self.sockets = sockets;
+            self.listening_sockets.clear();
+        }
+    }
+
+    pub const fn sockets(&self) -> &Vec<SocketAddr> {
+        &self.sockets
+    }
+
+    pub fn set_allow_dynamic_peers(&mut self, allow_dynamic_peers: bool) {
+        self.allow_dynamic_peers = allow_dynamic_peers;
+    }
+
+    /// Set the routing instance of the listening sockets, e.g., when the
+    /// sockets are bound to a VRF device. Only the peers registered with the
+    /// same instance are matched.
+    pub fn set_instance(&mut self, instance: u32) {
+        self.instance = instance;
+    }
+
+    pub const fn instance(&self) -> u32 {
+        self.instance
+    }
+
+    /// Match the connections to a peer, a registered peer takes over the key
+    /// of a dynamic peer
+    pub fn reg_peer(&mut self, session_key: SessionKey, peer_handle: PeerHandle<A, I>) {
+        self.dynamic_peers.remove(&session_key);
+        self.peers.insert(session_key, peer_handle);
+    }
+
+    /// Stop matching the connections to a removed peer, along with its socket
+    /// options
+    pub fn unreg_peer(&mut self, session_key: &SessionKey) -> Option<PeerHandle<A, I>> {
+        self.peer_socket_configs.remove(session_key);
+        self.peers.remove(session_key)
+    }
+
+    /// Find the key of the registered peer for a connection accepted on
+    /// `local_ip` from `peer_ip`. A peer registered with the exact local
+    /// address is preferred over a peer registered without one.
+    pub fn session_key(&self, local_ip: IpAddr, peer_ip: IpAddr) -> Option<SessionKey> {
+        let key = SessionKey::new(peer_ip.to_canonical()).with_instance(self.instance);
+        let exact = key.with_local_ip(Some(local_ip.to_canonical()));
+        if self.peers.contains_key(&exact) {
+            Some(exact)
+        } else if self.peers.contains_key(&key) {
+            Some(key)
+        } else {
+            None
         }
     }
 
-    pub fn reg_peer(&mut self, peer_ip: IpAddr, peer_handle: PeerHandle<A, I>) {
-        self.peers.insert(peer_ip, peer_handle);
+    /// Restrict the dynamic peers to the given prefixes, the peers are created
+    /// from the default [DynamicPeerTemplate]
+    pub fn set_dynamic_peer_ranges(&mut self, ranges: Vec<IpNet>) {
+        self.dynamic_peer_ranges = ranges
+            .into_iter()
+            .map(|prefix| DynamicPeerRange::new(prefix, DynamicPeerTemplate::default()))
+            .collect();
+    }
+
+    /// Accept dynamic peers from the given range. Ranges can overlap, the
+    /// most specific range containing the peer address is used.
+    pub fn add_dynamic_peer_range(&mut self, range: DynamicPeerRange) {
+        self.dynamic_peer_ranges.push(range);
+    }
+
+    pub const fn dynamic_peer_ranges(&self) -> &Vec<DynamicPeerRange> {
+        &self.dynamic_peer_ranges
+    }
+
+    /// Range used for a connection from an unconfigured peer
+    pub fn dynamic_peer_range(&self, peer_ip: &IpAddr) -> Option<&DynamicPeerRange> {
+        match_dynamic_peer_range(&self.dynamic_peer_ranges, peer_ip)
+    }
+
+    /// Number of the running dynamic peers accepted from the range with the
+    /// given prefix
+    pub fn dynamic_peer_count(&self, prefix: &IpNet) -> usize {
+        self.dynamic_peers
+            .values()
+            .filter(|range_prefix| *range_prefix == prefix)
+            .count()
+    }
+
+    /// Track a dynamic peer as accepted from the range with the given prefix
+    #[cfg(test)]
+    pub(crate) fn insert_dynamic_peer(&mut self, session_key: SessionKey, prefix: IpNet) {
+        self.dynamic_peers.insert(session_key, prefix);
+    }
+
+    /// Forward the events of the established dynamic peers to `tx`
+    pub fn set_dynamic_peer_events_tx(&mut self, tx: mpsc::UnboundedSender<DynamicPeerEvent<A>>) {
+        self.dynamic_peer_events_tx = Some(tx);
+    }
+
+    /// Check if a connection from an unconfigured peer can be accepted
+    pub fn is_dynamic_peer_allowed(&self, peer_ip: &IpAddr) -> bool {
+        self.allow_dynamic_peers
+            && (self.dynamic_peer_ranges.is_empty() || self.dynamic_peer_range(peer_ip).is_some())
+    }
+
+    pub fn set_socket_config(&mut self, socket_config: TcpSocketConfig) {
+        self.socket_config = socket_config;
+    }
+
+    pub const fn socket_config(&self) -> &TcpSocketConfig {
+        &self.socket_config
+    }
+
+    pub fn reg_peer_socket_config(
+        &mut self,
+        session_key: SessionKey,
+        socket_config: TcpSocketConfig,
+    ) {
+        self.peer_socket_configs.insert(session_key, socket_config);
+    }
+
+    /// Socket options used for the connections of the given peer
+    pub fn peer_socket_config(&self, session_key: &SessionKey) -> &TcpSocketConfig {
+        self.peer_socket_configs
+            .get(session_key)
+            .unwrap_or(&self.socket_config)
     }
 }
 
 impl BgpListener<SocketAddr, TcpStream> {
-    async fn accept_peer_connection(
+    /// Replace the dynamic peer ranges. The running dynamic peers are kept
+    /// when they're still accepted from the same range with the same
+    /// settings, the others are removed.
+    pub fn update_dynamic_peer_ranges(
+        &mut self,
+        ranges: Vec<DynamicPeerRange>,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    ) {
+        let previous = std::mem::replace(&mut self.dynamic_peer_ranges, ranges);
+        let removed: Vec<SessionKey> = self
+            .dynamic_peers
+            .iter()
+            .filter(|(peer_key, prefix)| {
+                let previous = previous.iter().find(|range| range.prefix() == **prefix);
+                !self.is_dynamic_peer_allowed(&peer_key.peer_ip())
+                    || self.dynamic_peer_range(&peer_key.peer_ip()) != previous
+            })
+            .map(|(peer_key, _)| *peer_key)
+            .collect();
+        for peer_key in removed {
+            self.remove_dynamic_peer(&peer_key, peer_supervisor);
+        }
+    }
+
+    fn accept_peer_connection(
         &mut self,
-        peer_key: IpAddr,
+        peer_key: SessionKey,
         peer_addr: SocketAddr,
         stream: TcpStream,
-        peer_supervisor: &mut PeersSupervisor<IpAddr, SocketAddr, TcpStream>,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
     ) {
+        if let Err(err) = self
+            .peer_socket_config(&peer_key)
+            .configure_accepted(&stream)
+        {
+            log::warn!("Rejected connection from peer {peer_addr}: {err}");
+            return;
+        }
         match self.peers.get_mut(&peer_key) {
             Some(peer_handle) => {
                 log::info!("Accepted Connection for peer {peer_key}");
@@ -108,71 +458,318 @@ impl BgpListener<SocketAddr, TcpStream> {
                 }
             }
             None => {
-                if !self.allow_dynamic_peers {
-                    log::info!("No peer configured for: {peer_addr}");
+                if self.is_dynamic_peer_allowed(&peer_key.peer_ip()) {
+                    self.accept_dynamic_peer(peer_key, peer_addr, stream, peer_supervisor);
                 } else {
-                    // TODO: rewrite for more clear logic and dynamic peer handling factory
-                    if let Ok((mut rx, mut peer_handle)) =
-                        peer_supervisor.dynamic_peer(peer_key, peer_addr, TcpActiveConnect)
-                    {
-                        if let Err(err) = peer_handle.start() {
-                            log::error!("Error starting dynamic peer: {err:?}");
-                            return;
-                        }
-                        rx.recv().await;
-                        if let Err(err) = peer_handle.accept_connection(peer_addr, stream) {
-                            log::error!("[{peer_addr}] Dynamic connection error sending event to peer: {err:?}");
-                            return;
-                        }
-                        while let Some(Ok((state, event))) = rx.recv().await {
-                            log::info!("[{peer_addr}] Dynamic connection at state {state} GOT EVENT: {event:?}");
-                            if state == FsmState::Idle {
-                                log::warn!("[{peer_addr}] Dynamic Connection failed before reaching OpenConfirm state");
-                                return;
-                            }
-                            if state == FsmState::OpenConfirm {
-                                log::info!(
-                                    "[{peer_addr}] Accepted Dynamic Connection: {peer_addr}"
-                                );
-                                self.peers.insert(peer_key, peer_handle);
-                                tokio::spawn(async move {
-                                    while let Some(event) = rx.recv().await {
-                                        log::info!(
-                                            "[{peer_addr}] dynamic connection got event: {event:?}"
-                                        );
-                                    }
-                                });
-                                return;
-                            }
-                        }
-                    }
+                    log::info!("No peer configured for: {peer_addr}");
                 }
             }
         }
     }
 
+    /// Create a dynamic peer for the connection. The session is awaited in
+    /// its own task, so a peer that doesn't complete the OPEN exchange doesn't
+    /// hold up the other connections.
+    fn accept_dynamic_peer(
+        &mut self,
+        peer_key: SessionKey,
+        peer_addr: SocketAddr,
+        stream: TcpStream,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    ) {
+        let range = match self.dynamic_peer_range(&peer_key.peer_ip()) {
+            Some(range) => range.clone(),
+            // No ranges are configured, any peer is accepted with the default template
+            None => DynamicPeerRange::new(
+                IpNet::from(peer_key.peer_ip()),
+                DynamicPeerTemplate::default(),
+            ),
+        };
+        if let Some(max_peers) = range.max_peers() {
+            if self.dynamic_peer_count(&range.prefix()) >= max_peers {
+                log::warn!(
+                    "[{peer_addr}] Rejected dynamic connection, range {} reached the maximum of {max_peers} peers",
+                    range.prefix()
+                );
+                return;
+            }
+        }
+        let active_connect = TcpActiveConnect::new()
+            .with_tcp_auth_keys(peer_supervisor.tcp_auth_keys().clone())
+            .with_instance(self.instance)
+            .with_socket_config(self.peer_socket_config(&peer_key).clone());
+        let (rx, peer_handle) = match peer_supervisor.dynamic_peer_from_range(
+            peer_key,
+            peer_addr,
+            active_connect,
+            &range,
+        ) {
+            Ok(peer) => peer,
+            Err(err) => {
+                log::error!("[{peer_addr}] Error creating dynamic peer: {err:?}");
+                return;
+            }
+        };
+        self.dynamic_peers.insert(peer_key, range.prefix());
+        if let Err(err) = peer_handle.start() {
+            log::error!("Error starting dynamic peer: {err:?}");
+            self.remove_dynamic_peer(&peer_key, peer_supervisor);
+            return;
+        }
+        tokio::spawn(run_dynamic_peer(
+            peer_key,
+            peer_addr,
+            stream,
+            rx,
+            peer_handle,
+            self.dynamic_peer_status_tx.clone(),
+            self.dynamic_peer_events_tx.clone(),
+        ));
+    }
+
+    /// Apply the status reported by the task of a dynamic peer
+    fn dynamic_peer_status(
+        &mut self,
+        status: DynamicPeerStatus<SocketAddr, TcpStream>,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    ) {
+        match status {
+            // The peer may have been removed meanwhile, e.g., its range was removed
+            DynamicPeerStatus::Up(peer_key, peer_handle) => {
+                if self.dynamic_peers.contains_key(&peer_key) {
+                    self.peers.insert(peer_key, peer_handle);
+                }
+            }
+            DynamicPeerStatus::Down(peer_key) => {
+                self.remove_dynamic_peer(&peer_key, peer_supervisor)
+            }
+        }
+    }
+
+    /// Remove a dynamic peer after its session went down
+    fn remove_dynamic_peer(
+        &mut self,
+        peer_key: &SessionKey,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    ) {
+        if self.dynamic_peers.remove(peer_key).is_some() {
+            self.peers.remove(peer_key);
+            peer_supervisor.remove_peer(peer_key);
+            log::info!("Removed dynamic peer {peer_key}");
+        }
+    }
+
+    /// Apply the changes in TCP authentication keys to a listening socket
+    fn update_listener_tcp_auth(
+        listener: &TcpListener,
+        instance: u32,
+        previous: &HashMap<SessionKey, TcpAuth>,
+        current: &HashMap<SessionKey, TcpAuth>,
+    ) {
+        let local_addr = match listener.local_addr() {
+            Ok(local_addr) => local_addr,
+            Err(err) => {
+                log::error!("Couldn't get local address of listening socket: {err:?}");
+                return;
+            }
+        };
+        let previous = &listener_tcp_auth(previous, instance, local_addr.ip());
+        let current = &listener_tcp_auth(current, instance, local_addr.ip());
+        let peers = previous
+            .keys()
+            .chain(current.keys().filter(|ip| !previous.contains_key(ip)));
+        for peer_ip in peers {
+            let (previous, auth) = (previous.get(peer_ip), current.get(peer_ip));
+            if previous == auth {
+                continue;
+            }
+            // IPv4 peers connecting to dual-stack IPv6 sockets are seen as IPv4-mapped addresses
+            let key_addr = match (local_addr, peer_ip) {
+                (SocketAddr::V4(_), IpAddr::V6(_)) => continue,
+                (SocketAddr::V6(_), IpAddr::V4(ip)) => IpAddr::V6(ip.to_ipv6_mapped()),
+                (_, ip) => *ip,
+            };
+            if let Err(err) = update_tcp_auth(listener, key_addr, previous, auth) {
+                log::error!(
+                    "Couldn't update TCP authentication for peer {peer_ip} on listening socket {local_addr}: {err:?}"
+                );
+            }
+        }
+    }
+
+    /// Socket options of the listening sockets, and if the received SYN
+    /// packets are saved. When any of the peers uses GTSM, the SYN-ACK must
+    /// be sent with TTL 255 as well and the TTL of the SYN is checked when
+    /// the connection is accepted.
+    fn listen_socket_config(&self) -> (TcpSocketConfig, bool) {
+        if self.socket_config.ttl_security_hops().is_some() {
+            return (self.socket_config.clone(), true);
+        }
+        let gtsm = self
+            .peer_socket_configs
+            .values()
+            .any(|config| config.ttl_security_hops().is_some());
+        if gtsm {
+            (self.socket_config.clone().with_ttl(GTSM_TTL), true)
+        } else {
+            (self.socket_config.clone(), false)
+        }
+    }
+
+    fn bind(
+        socket: SocketAddr,
+        instance: u32,
+        socket_config: &TcpSocketConfig,
+        save_syn: bool,
+        tcp_auth: &HashMap<SessionKey, TcpAuth>,
+    ) -> Result<TcpListener, io::Error> {
+        let tcp_socket = match socket {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket_config.configure_listen(&tcp_socket, socket)?;
+        if save_syn {
+            enable_save_syn(&tcp_socket)?;
+        }
+        tcp_socket.set_reuseaddr(true)?;
+        tcp_socket.bind(socket)?;
+        let listener = tcp_socket.listen(1024)?;
+        Self::update_listener_tcp_auth(&listener, instance, &HashMap::new(), tcp_auth);
+        Ok(listener)
+    }
+
+    /// Accept the connections of the registered peers and the dynamic peers.
+    /// The sockets are bound on the first call and kept open when the
+    /// returned future is dropped, so calling `run` again resumes listening
+    /// on the same sockets. They're re-bound when their socket options
+    /// changed in between, e.g., a peer using GTSM was registered.
     pub async fn run(
         &mut self,
-        peer_supervisor: &mut PeersSupervisor<IpAddr, SocketAddr, TcpStream>,
+        peer_supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
     ) -> Result<(), io::Error> {
         log::info!("Configured listening socket: {:?}", self.sockets);
-        let mut listening_sockets = Vec::with_capacity(self.sockets.len());
-
-        for socket in &self.sockets {
-            let listener = TcpListener::bind(socket).await?;
-            let listener_stream = TcpListenerStream::new(listener);
-            listening_sockets.push(listener_stream);
+        let mut tcp_auth_rx = peer_supervisor.tcp_auth_keys().subscribe();
+        let tcp_auth = tcp_auth_rx.borrow_and_update().clone();
+        let listen_socket_config = self.listen_socket_config();
+        if self.listening_sockets.len() == self.sockets.len()
+            && self.listening_socket_config.as_ref() == Some(&listen_socket_config)
+        {
+            for listener in &self.listening_sockets {
+                Self::update_listener_tcp_auth(
+                    listener.as_ref(),
+                    self.instance,
+                    &self.listening_tcp_auth,
+                    &tcp_auth,
+                );
+            }
+        } else {
+            // The previous sockets are closed first to bind to the same addresses
+            self.listening_sockets.clear();
+            self.listening_socket_config = None;
+            let (socket_config, save_syn) = &listen_socket_config;
+            let mut listening_sockets = Vec::with_capacity(self.sockets.len());
+            for socket in &self.sockets {
+                let listener =
+                    Self::bind(*socket, self.instance, socket_config, *save_syn, &tcp_auth)?;
+                listening_sockets.push(TcpListenerStream::new(listener));
+            }
+            self.listening_sockets = listening_sockets;
+            self.listening_socket_config = Some(listen_socket_config);
         }
+        self.listening_tcp_auth = tcp_auth;
+        log::info!("BGP Listener listening on sockets: {:?}", self.sockets);
         loop {
-            let mut listen_futures = FuturesUnordered::new();
-            for incoming in &mut listening_sockets {
-                listen_futures.push(incoming.next());
+            let accepted = {
+                let mut listen_futures = FuturesUnordered::new();
+                for incoming in &mut self.listening_sockets {
+                    listen_futures.push(incoming.next());
+                }
+                tokio::select! {
+                    accepted = listen_futures.next() => accepted,
+                    Ok(_) = tcp_auth_rx.changed() => {
+                        let current = tcp_auth_rx.borrow_and_update().clone();
+                        drop(listen_futures);
+                        for listener in &self.listening_sockets {
+                            Self::update_listener_tcp_auth(listener.as_ref(), self.instance, &self.listening_tcp_auth, &current);
+                        }
+                        self.listening_tcp_auth = current;
+                        continue;
+                    }
+                    Some(status) = self.dynamic_peer_status_rx.recv() => {
+                        drop(listen_futures);
+                        self.dynamic_peer_status(status, peer_supervisor);
+                        continue;
+                    }
+                }
+            };
+            match accepted {
+                Some(Some(Ok((stream, peer_addr)))) => {
+                    let local_addr = match stream.local_addr() {
+                        Ok(local_addr) => local_addr,
+                        Err(err) => {
+                            log::error!("Couldn't get local address of connection from {peer_addr}: {err:?}");
+                            continue;
+                        }
+                    };
+                    // Dynamic peers are keyed by the local address they connected to
+                    let peer_key = self
+                        .session_key(local_addr.ip(), peer_addr.ip())
+                        .unwrap_or_else(|| {
+                            SessionKey::new(peer_addr.ip().to_canonical())
+                                .with_local_ip(Some(local_addr.ip().to_canonical()))
+                                .with_instance(self.instance)
+                        });
+                    self.accept_peer_connection(peer_key, peer_addr, stream, peer_supervisor);
+                }
+                Some(Some(Err(err))) => {
+                    log::error!("Error accepting connection: {err:?}");
+                }
+                _ => {}
             }
-            log::info!("BGP Listener listening on sockets: {:?}", self.sockets);
-            while let Some(Some(Ok((stream, peer_addr)))) = listen_futures.next().await {
-                self.accept_peer_connection(peer_addr.ip(), peer_addr, stream, peer_supervisor)
-                    .await;
+        }
+    }
+}
+
+/// Hand the accepted connection to a dynamic peer and wait for its session to
+/// reach OpenConfirm, then forward its events until the session goes down
+async fn run_dynamic_peer(
+    peer_key: SessionKey,
+    peer_addr: SocketAddr,
+    stream: TcpStream,
+    mut rx: mpsc::UnboundedReceiver<PeerStateResult<SocketAddr>>,
+    mut peer_handle: PeerHandle<SocketAddr, TcpStream>,
+    status_tx: mpsc::UnboundedSender<DynamicPeerStatus<SocketAddr, TcpStream>>,
+    mut events_tx: Option<mpsc::UnboundedSender<DynamicPeerEvent<SocketAddr>>>,
+) {
+    rx.recv().await;
+    if let Err(err) = peer_handle.accept_connection(peer_addr, stream) {
+        log::error!("[{peer_addr}] Dynamic connection error sending event to peer: {err:?}");
+        let _ = status_tx.send(DynamicPeerStatus::Down(peer_key));
+        return;
+    }
+    while let Some(Ok((state, event))) = rx.recv().await {
+        log::info!("[{peer_addr}] Dynamic connection at state {state} GOT EVENT: {event:?}");
+        if state == FsmState::Idle {
+            log::warn!("[{peer_addr}] Dynamic Connection failed before reaching OpenConfirm state");
+            break;
+        }
+        if state == FsmState::OpenConfirm {
+            log::info!("[{peer_addr}] Accepted Dynamic Connection: {peer_addr}");
+            let _ = status_tx.send(DynamicPeerStatus::Up(peer_key, peer_handle));
+            while let Some(event) = rx.recv().await {
+                log::info!("[{peer_addr}] dynamic connection got event: {event:?}");
+                let is_down = matches!(event, Ok((FsmState::Idle, _)));
+                if let Some(tx) = &events_tx {
+                    if tx.send((peer_key, event)).is_err() {
+                        events_tx = None;
+                    }
+                }
+                if is_down {
+                    break;
+                }
             }
+            break;
         }
     }
+    let _ = status_tx.send(DynamicPeerStatus::Down(peer_key));
 }
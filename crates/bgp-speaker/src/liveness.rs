@@ -0,0 +1,87 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hook to tie BGP sessions to an external liveness detector, such as BFD
+//! [RFC5882](https://datatracker.ietf.org/doc/html/rfc5882).
+//!
+//! A [crate::peer::Peer] subscribes to a [LivenessDetector] for its remote
+//! address. Once the detector reported the peer as up, a transition to down
+//! tears down the session with a Cease/BFD Down notification
+//! [RFC9384](https://datatracker.ietf.org/doc/html/rfc9384). The session is
+//! started again when the detector reports the peer as up.
+
+use std::fmt::Debug;
+
+use tokio::sync::watch;
+
+/// Liveness of a remote system as seen by a [LivenessDetector]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, strum_macros::Display)]
+pub enum LivenessState {
+    Up,
+    Down,
+}
+
+/// Detects the liveness of remote systems independently of the BGP session,
+/// e.g., [crate::bfd::Bfd].
+pub trait LivenessDetector<A>: Debug + Send + Sync {
+    /// Start monitoring `peer_addr`, the current state and its changes are
+    /// reported on the returned receiver. The monitoring stops once all the
+    /// receivers of the peer are dropped.
+    fn subscribe(&self, peer_addr: A) -> watch::Receiver<LivenessState>;
+}
+
+/// Tracks the liveness state reported to a peer and filters the transitions
+/// the peer should act on
+#[derive(Debug)]
+pub(crate) struct LivenessMonitor {
+    rx: watch::Receiver<LivenessState>,
+    /// Down is only acted on after the remote system has been seen up
+    was_up: bool,
+}
+
+impl LivenessMonitor {
+    pub(crate) fn new(mut rx: watch::Receiver<LivenessState>) -> Self {
+        let was_up = *rx.borrow_and_update() == LivenessState::Up;
+        Self { rx, was_up }
+    }
+
+    pub(crate) fn state(&self) -> LivenessState {
+        *self.rx.borrow()
+    }
+
+    /// Wait for the remote system to go down after being up while the session
+    /// is active, or to come up while the session could be restarted. Other
+    /// transitions are consumed without being reported. Cancel safe.
+    pub(crate) async fn next_transition(
+        &mut self,
+        session_active: bool,
+        restartable: bool,
+    ) -> LivenessState {
+        loop {
+            if self.rx.changed().await.is_err() {
+                // The detector is gone, the last reported state is kept
+                std::future::pending::<()>().await;
+            }
+            let state = *self.rx.borrow_and_update();
+            let was_up = self.was_up;
+            self.was_up = state == LivenessState::Up;
+            match state {
+                LivenessState::Down if was_up && session_active => return state,
+                LivenessState::Up if !was_up && restartable => return state,
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus metrics of the BGP speaker, registered in the process wide
+//! [netgauze_metrics::registry] when the `metrics` feature is enabled.
+
+use std::sync::{Arc, OnceLock};
+
+use netgauze_bgp_pkt::{codec::BgpCodecDecoderError, BgpMessage};
+use netgauze_metrics::{Counter, Family, Gauge};
+
+use crate::fsm::FsmState;
+
+/// Current FSM state of a peer, set to 1 for the state the peer is in
+pub const PEER_STATE: &str = "netgauze_bgp_peer_state";
+/// Number of times a peer transitioned to the Established state
+pub const PEER_ESTABLISHED_TRANSITIONS: &str = "netgauze_bgp_peer_established_transitions_total";
+/// BGP messages received from a peer by message type
+pub const MESSAGES_RECEIVED: &str = "netgauze_bgp_messages_received_total";
+/// BGP messages sent to a peer by message type
+pub const MESSAGES_SENT: &str = "netgauze_bgp_messages_sent_total";
+/// Errors decoding BGP messages by [BgpCodecDecoderError] variant
+pub const DECODE_ERRORS: &str = "netgauze_bgp_decode_errors_total";
+
+pub(crate) struct BgpSpeakerMetrics {
+    peer_state: Arc<Family<Gauge>>,
+    established_transitions: Arc<Family<Counter>>,
+    messages_received: Arc<Family<Counter>>,
+    messages_sent: Arc<Family<Counter>>,
+    decode_errors: Arc<Family<Counter>>,
+}
+
+impl BgpSpeakerMetrics {
+    fn new() -> Self {
+        let registry = netgauze_metrics::registry();
+        Self {
+            peer_state: registry
+                .gauge_family(PEER_STATE, "Current BGP FSM state", &["peer", "state"])
+                .expect("metric registered with a different type"),
+            established_transitions: registry
+                .counter_family(
+                    PEER_ESTABLISHED_TRANSITIONS,
+                    "Number of transitions to the Established state",
+                    &["peer"],
+                )
+                .expect("metric registered with a different type"),
+            messages_received: registry
+                .counter_family(
+                    MESSAGES_RECEIVED,
+                    "BGP messages received",
+                    &["peer", "type"],
+                )
+                .expect("metric registered with a different type"),
+            messages_sent: registry
+                .counter_family(MESSAGES_SENT, "BGP messages sent", &["peer", "type"])
+                .expect("metric registered with a different type"),
+            decode_errors: registry
+                .counter_family(
+                    DECODE_ERRORS,
+                    "Errors decoding BGP messages",
+                    &["peer", "error"],
+                )
+                .expect("metric registered with a different type"),
+        }
+    }
+
+    pub(crate) fn fsm_transition(&self, peer: &str, from: FsmState, to: FsmState) {
+        self.peer_state.remove(&[peer, &from.to_string()]);
+        self.peer_state.with_labels(&[peer, &to.to_string()]).set(1);
+        if to == FsmState::Established {
+            self.established_transitions.with_labels(&[peer]).inc();
+        }
+    }
+
+    pub(crate) fn message_received(&self, peer: &str, msg: &BgpMessage) {
+        let msg_type = format!("{:?}", msg.get_type());
+        self.messages_received.with_labels(&[peer, &msg_type]).inc();
+    }
+
+    pub(crate) fn message_sent(&self, peer: &str, msg: &BgpMessage) {
+        let msg_type = format!("{:?}", msg.get_type());
+        self.messages_sent.with_labels(&[peer, &msg_type]).inc();
+    }
+
+    pub(crate) fn decode_error(&self, peer: &str, error: &BgpCodecDecoderError) {
+        self.decode_errors
+            .with_labels(&[peer, decode_error_kind(error)])
+            .inc();
+    }
+
+    /// Stop reporting all the metrics of a peer
+    fn remove_peer(&self, peer: &str) {
+        for family in [
+            &self.established_transitions,
+            &self.messages_received,
+            &self.messages_sent,
+            &self.decode_errors,
+        ] {
+            family.remove_matching("peer", peer);
+        }
+        self.peer_state.remove_matching("peer", peer);
+    }
+}
+
+pub(crate) fn decode_error_kind(error: &BgpCodecDecoderError) -> &'static str {
+    match error {
+        BgpCodecDecoderError::IoError(_) => "IoError",
+        BgpCodecDecoderError::Incomplete(_) => "Incomplete",
+        BgpCodecDecoderError::BgpMessageParsingError(_) => "BgpMessageParsingError",
+    }
+}
+
+/// Metrics of the BGP speaker, registered on first use
+pub(crate) fn metrics() -> &'static BgpSpeakerMetrics {
+    static METRICS: OnceLock<BgpSpeakerMetrics> = OnceLock::new();
+    METRICS.get_or_init(BgpSpeakerMetrics::new)
+}
+
+/// Report the metrics of a peer for as long as the guard is alive, they're
+/// labeled with the peer key
+#[derive(Debug)]
+pub(crate) struct PeerMetricsGuard {
+    peer: String,
+}
+
+impl PeerMetricsGuard {
+    pub(crate) const fn new(peer: String) -> Self {
+        Self { peer }
+    }
+}
+
+impl Drop for PeerMetricsGuard {
+    fn drop(&mut self) {
+        metrics().remove_peer(&self.peer);
+    }
+}
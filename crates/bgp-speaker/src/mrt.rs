@@ -0,0 +1,617 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! MRT ([RFC6396](https://datatracker.ietf.org/doc/html/rfc6396)) recorder of
+//! the speaker's own sessions, enabled with the `mrt` feature.
+//!
+//! [MrtRecorder::start] spawns a task writing the events reported by the peers
+//! to files in [MrtRecorderConfig::directory]. The recorder is attached to the
+//! peers with [crate::supervisor::PeersSupervisor::set_mrt_recorder], then:
+//! * Every BGP message received from the peers and every FSM state transition
+//!   is written as a `BGP4MP_ET` record to the `updates.<timestamp>.mrt` files,
+//!   which are rotated by time and size.
+//! * A `TABLE_DUMP_V2` snapshot of the Adj-RIB-In of all the peers is
+//!   periodically written to a new `rib.<timestamp>.mrt` file.
+//!
+//! The snapshot is built from the recorded updates, i.e., it's the Adj-RIB-In
+//! before the ingress treatment of the speaker, so replaying the updates
+//! recorded after a snapshot gives the same RIB as the next snapshot.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+    sync::mpsc,
+    time::Interval,
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::BgpCapability,
+    iana::AS_TRANS,
+    open::BgpOpenMessage,
+    path_attribute::{
+        Aggregator, As4Aggregator, As4PathSegment, AsPath, MpReach, PathAttribute,
+        PathAttributeValue,
+    },
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+use netgauze_mrt_pkt::{
+    iana::Bgp4mpState, AfiSafiRib, Bgp4mp, Bgp4mpMessage, Bgp4mpPeer, Bgp4mpStateChange, MrtRecord,
+    MrtValue, PeerEntry, PeerIndexTable, RibEntry, TableDumpV2,
+};
+use netgauze_parse_utils::WritablePdu;
+
+use crate::{
+    fsm::FsmState,
+    rib::{insert_attribute, Route, RouteKey, UpdateRoutes},
+};
+
+/// Prefix of the files the BGP4MP records are written to
+pub const UPDATES_FILE_PREFIX: &str = "updates";
+
+/// Prefix of the files the TABLE_DUMP_V2 snapshots are written to
+pub const RIB_FILE_PREFIX: &str = "rib";
+
+/// Events reported to the [MrtRecorder]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MrtPeerEvent<A> {
+    /// A BGP message received from the peer, `asn4` is set when four-octet AS
+    /// numbers are negotiated on the session
+    Message {
+        timestamp: DateTime<Utc>,
+        peer_addr: A,
+        peer_asn: u32,
+        local_asn: u32,
+        asn4: bool,
+        message: BgpMessage,
+    },
+    StateChange {
+        timestamp: DateTime<Utc>,
+        peer_addr: A,
+        peer_asn: u32,
+        local_asn: u32,
+        old_state: FsmState,
+        new_state: FsmState,
+    },
+}
+
+/// Configuration of the [MrtRecorder]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MrtRecorderConfig {
+    directory: PathBuf,
+    collector_bgp_id: Ipv4Addr,
+    view_name: String,
+    rotate_interval: Duration,
+    rotate_size: u64,
+    rib_dump_interval: Duration,
+}
+
+impl MrtRecorderConfig {
+    pub fn new(directory: PathBuf, collector_bgp_id: Ipv4Addr) -> Self {
+        Self {
+            directory,
+            collector_bgp_id,
+            view_name: String::new(),
+            rotate_interval: Duration::from_secs(15 * 60),
+            rotate_size: 0,
+            rib_dump_interval: Duration::from_secs(2 * 60 * 60),
+        }
+    }
+
+    /// View name written in the PEER_INDEX_TABLE of the snapshots
+    pub fn with_view_name(mut self, value: String) -> Self {
+        self.view_name = value;
+        self
+    }
+
+    /// Start a new updates file after this interval, zero disables it
+    pub const fn with_rotate_interval(mut self, value: Duration) -> Self {
+        self.rotate_interval = value;
+        self
+    }
+
+    /// Start a new updates file once the current one reached this size in
+    /// bytes, zero disables it
+    pub const fn with_rotate_size(mut self, value: u64) -> Self {
+        self.rotate_size = value;
+        self
+    }
+
+    /// Interval between the Adj-RIB-In snapshots, zero disables them
+    pub const fn with_rib_dump_interval(mut self, value: Duration) -> Self {
+        self.rib_dump_interval = value;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    pub const fn collector_bgp_id(&self) -> Ipv4Addr {
+        self.collector_bgp_id
+    }
+    pub fn view_name(&self) -> &str {
+        &self.view_name
+    }
+    pub const fn rotate_interval(&self) -> Duration {
+        self.rotate_interval
+    }
+    pub const fn rotate_size(&self) -> u64 {
+        self.rotate_size
+    }
+    pub const fn rib_dump_interval(&self) -> Duration {
+        self.rib_dump_interval
+    }
+}
+
+/// Handle to the recorder task, cloned into every peer. The files are
+/// flushed and closed once all the handles are dropped.
+#[derive(Debug, Clone)]
+pub struct MrtRecorder<A> {
+    tx: mpsc::UnboundedSender<MrtPeerEvent<A>>,
+}
+
+impl<A> MrtRecorder<A> {
+    /// Spawn the task writing the MRT files
+    pub fn start(config: MrtRecorderConfig) -> Self
+    where
+        A: Into<SocketAddr> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_recorder(config, rx));
+        Self { tx }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn channel() -> (Self, mpsc::UnboundedReceiver<MrtPeerEvent<A>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    pub fn send(&self, event: MrtPeerEvent<A>) {
+        if self.tx.send(event).is_err() {
+            log::debug!("MRT recorder task terminated, dropping event");
+        }
+    }
+}
+
+/// Four-octet AS numbers are used on the session when both sides advertised
+/// the capability
+pub(crate) fn is_asn4(sent_open: &BgpOpenMessage, received_open: &BgpOpenMessage) -> bool {
+    [sent_open, received_open].iter().all(|open| {
+        open.capabilities()
+            .iter()
+            .any(|cap| matches!(cap, BgpCapability::FourOctetAs(_)))
+    })
+}
+
+const fn bgp4mp_state(state: FsmState) -> Bgp4mpState {
+    match state {
+        FsmState::Idle => Bgp4mpState::Idle,
+        FsmState::Connect => Bgp4mpState::Connect,
+        FsmState::Active => Bgp4mpState::Active,
+        FsmState::OpenSent => Bgp4mpState::OpenSent,
+        FsmState::OpenConfirm => Bgp4mpState::OpenConfirm,
+        FsmState::Established => Bgp4mpState::Established,
+    }
+}
+
+/// The local address isn't known to the peer, the unspecified address of the
+/// same family is recorded instead
+fn bgp4mp_peer(peer_addr: SocketAddr, peer_asn: u32, local_asn: u32) -> Bgp4mpPeer {
+    let local_address = match peer_addr.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    Bgp4mpPeer::new(peer_asn, local_asn, 0, peer_addr.ip(), local_address)
+}
+
+/// RFC6396: AS numbers in the TABLE_DUMP_V2 RIB entries are always encoded as
+/// four-octet AS numbers
+fn as4_attribute(attribute: &PathAttribute) -> PathAttribute {
+    let value = match attribute.value() {
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(segments)) => {
+            PathAttributeValue::AsPath(AsPath::As4PathSegments(
+                segments
+                    .iter()
+                    .map(|segment| {
+                        As4PathSegment::new(
+                            segment.segment_type(),
+                            segment.as_numbers().iter().map(|asn| *asn as u32).collect(),
+                        )
+                    })
+                    .collect(),
+            ))
+        }
+        PathAttributeValue::Aggregator(Aggregator::As2Aggregator(aggregator)) => {
+            PathAttributeValue::Aggregator(Aggregator::As4Aggregator(As4Aggregator::new(
+                *aggregator.asn() as u32,
+                aggregator.origin(),
+            )))
+        }
+        _ => return attribute.clone(),
+    };
+    PathAttribute::from(
+        attribute.optional(),
+        attribute.transitive(),
+        attribute.partial(),
+        attribute.extended_length(),
+        value,
+    )
+    .unwrap_or_else(|_| attribute.clone())
+}
+
+/// Path attributes of a RIB entry, the next hop of the multiprotocol routes is
+/// carried in an `MP_REACH_NLRI` attribute without NLRI
+fn rib_attributes(address_type: AddressType, route: &Route) -> Vec<PathAttribute> {
+    let mut attributes: Vec<PathAttribute> = route.attributes().iter().map(as4_attribute).collect();
+    let next_hop = match route.mp_next_hop() {
+        Some(next_hop) => next_hop,
+        None => return attributes,
+    };
+    let mp_reach = match (address_type, next_hop.next_hop()) {
+        (AddressType::Ipv4Unicast, _) => MpReach::Ipv4Unicast {
+            next_hop: next_hop.next_hop(),
+            next_hop_local: next_hop.next_hop_local(),
+            nlri: vec![],
+        },
+        (AddressType::Ipv4Multicast, _) => MpReach::Ipv4Multicast {
+            next_hop: next_hop.next_hop(),
+            next_hop_local: next_hop.next_hop_local(),
+            nlri: vec![],
+        },
+        (AddressType::Ipv6Unicast, IpAddr::V6(next_hop_global)) => MpReach::Ipv6Unicast {
+            next_hop_global,
+            next_hop_local: next_hop.next_hop_local(),
+            nlri: vec![],
+        },
+        (AddressType::Ipv6Multicast, IpAddr::V6(next_hop_global)) => MpReach::Ipv6Multicast {
+            next_hop_global,
+            next_hop_local: next_hop.next_hop_local(),
+            nlri: vec![],
+        },
+        _ => return attributes,
+    };
+    if let Ok(mp_reach) = PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MpReach(mp_reach),
+    ) {
+        insert_attribute(&mut attributes, mp_reach);
+    }
+    attributes
+}
+
+#[derive(Debug)]
+struct MrtPeerState {
+    asn: u32,
+    bgp_id: Ipv4Addr,
+    /// Routes received from the peer along with the time they were received
+    routes: HashMap<RouteKey, (DateTime<Utc>, Route)>,
+}
+
+/// Adj-RIB-In of the peers rebuilt from the recorded updates
+#[derive(Debug)]
+pub(crate) struct MrtState {
+    config: MrtRecorderConfig,
+    peers: HashMap<IpAddr, MrtPeerState>,
+}
+
+impl MrtState {
+    pub(crate) fn new(config: MrtRecorderConfig) -> Self {
+        Self {
+            config,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Update the state with the event and return the BGP4MP_ET record
+    /// reporting it
+    pub(crate) fn apply<A: Into<SocketAddr>>(&mut self, event: MrtPeerEvent<A>) -> MrtRecord {
+        match event {
+            MrtPeerEvent::Message {
+                timestamp,
+                peer_addr,
+                peer_asn,
+                local_asn,
+                asn4,
+                message,
+            } => {
+                let peer_addr = peer_addr.into();
+                match &message {
+                    BgpMessage::Open(open) => {
+                        self.peers.insert(
+                            peer_addr.ip(),
+                            MrtPeerState {
+                                asn: open.my_asn4(),
+                                bgp_id: open.bgp_id(),
+                                routes: HashMap::new(),
+                            },
+                        );
+                    }
+                    BgpMessage::Update(update) => {
+                        if let Some(peer) = self.peers.get_mut(&peer_addr.ip()) {
+                            let update_routes = UpdateRoutes::from(update);
+                            for key in update_routes.withdrawn() {
+                                peer.routes.remove(key);
+                            }
+                            for (key, route) in update_routes.announced() {
+                                peer.routes.insert(*key, (timestamp, route.clone()));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                let value = if asn4 {
+                    let peer = bgp4mp_peer(peer_addr, peer_asn, local_asn);
+                    Bgp4mp::MessageAs4(Bgp4mpMessage::new(peer, message))
+                } else {
+                    let peer = bgp4mp_peer(
+                        peer_addr,
+                        u16::try_from(peer_asn).unwrap_or(AS_TRANS) as u32,
+                        u16::try_from(local_asn).unwrap_or(AS_TRANS) as u32,
+                    );
+                    Bgp4mp::Message(Bgp4mpMessage::new(peer, message))
+                };
+                MrtRecord::new(timestamp, MrtValue::Bgp4mpEt(value))
+            }
+            MrtPeerEvent::StateChange {
+                timestamp,
+                peer_addr,
+                peer_asn,
+                local_asn,
+                old_state,
+                new_state,
+            } => {
+                let peer_addr = peer_addr.into();
+                if matches!(
+                    new_state,
+                    FsmState::Idle | FsmState::Connect | FsmState::Active
+                ) {
+                    self.peers.remove(&peer_addr.ip());
+                }
+                let value = Bgp4mp::StateChangeAs4(Bgp4mpStateChange::new(
+                    bgp4mp_peer(peer_addr, peer_asn, local_asn),
+                    bgp4mp_state(old_state),
+                    bgp4mp_state(new_state),
+                ));
+                MrtRecord::new(timestamp, MrtValue::Bgp4mpEt(value))
+            }
+        }
+    }
+
+    /// TABLE_DUMP_V2 records of the Adj-RIB-In of all the peers: the
+    /// PEER_INDEX_TABLE followed by a RIB record per prefix
+    pub(crate) fn rib_dump(&self, timestamp: DateTime<Utc>) -> Vec<MrtRecord> {
+        let mut addresses = self.peers.keys().copied().collect::<Vec<_>>();
+        addresses.sort();
+        let peer_entries = addresses
+            .iter()
+            .map(|address| {
+                let peer = &self.peers[address];
+                PeerEntry::new(peer.bgp_id, *address, peer.asn, true)
+            })
+            .collect();
+        let mut records = vec![MrtRecord::new(
+            timestamp,
+            MrtValue::TableDumpV2(TableDumpV2::PeerIndexTable(PeerIndexTable::new(
+                self.config.collector_bgp_id(),
+                self.config.view_name().to_string(),
+                peer_entries,
+            ))),
+        )];
+        let mut sequence_number: u32 = 0;
+        for address_type in [
+            AddressType::Ipv4Unicast,
+            AddressType::Ipv4Multicast,
+            AddressType::Ipv6Unicast,
+            AddressType::Ipv6Multicast,
+        ] {
+            let mut prefixes: BTreeMap<IpNet, Vec<RibEntry>> = BTreeMap::new();
+            for (peer_index, address) in addresses.iter().enumerate() {
+                for (key, (originated_time, route)) in &self.peers[address].routes {
+                    if key.address_type() != address_type {
+                        continue;
+                    }
+                    prefixes
+                        .entry(key.prefix())
+                        .or_default()
+                        .push(RibEntry::new(
+                            peer_index as u16,
+                            *originated_time,
+                            None,
+                            rib_attributes(address_type, route),
+                        ));
+                }
+            }
+            for (prefix, entries) in prefixes {
+                let rib = AfiSafiRib::new(sequence_number, prefix, entries);
+                let value = match address_type {
+                    AddressType::Ipv4Unicast => TableDumpV2::RibIpv4Unicast(rib),
+                    AddressType::Ipv4Multicast => TableDumpV2::RibIpv4Multicast(rib),
+                    AddressType::Ipv6Unicast => TableDumpV2::RibIpv6Unicast(rib),
+                    _ => TableDumpV2::RibIpv6Multicast(rib),
+                };
+                records.push(MrtRecord::new(timestamp, MrtValue::TableDumpV2(value)));
+                sequence_number = sequence_number.wrapping_add(1);
+            }
+        }
+        records
+    }
+}
+
+/// Create a new file named `<prefix>.<timestamp>.mrt` in the directory, a
+/// counter is appended when a file with the same name exists
+async fn create_file(directory: &Path, prefix: &str) -> io::Result<(PathBuf, File)> {
+    let name = format!("{prefix}.{}", Utc::now().format("%Y%m%d.%H%M%S"));
+    let mut path = directory.join(format!("{name}.mrt"));
+    let mut counter = 0;
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                counter += 1;
+                path = directory.join(format!("{name}.{counter}.mrt"));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn encode(record: &MrtRecord) -> Option<Vec<u8>> {
+    let mut buf = Vec::with_capacity(record.len());
+    match record.write(&mut buf) {
+        Ok(()) => Some(buf),
+        Err(err) => {
+            log::warn!("[MRT] Couldn't encode record {record:?}: {err:?}");
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UpdatesFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+/// Append the records to the current updates file, creating a new one when
+/// needed
+async fn write_updates(
+    config: &MrtRecorderConfig,
+    file: &mut Option<UpdatesFile>,
+    records: Vec<MrtRecord>,
+) -> io::Result<()> {
+    for record in records {
+        let buf = match encode(&record) {
+            Some(buf) => buf,
+            None => continue,
+        };
+        let current = match file {
+            Some(current) => current,
+            None => {
+                let (path, created) = create_file(config.directory(), UPDATES_FILE_PREFIX).await?;
+                log::info!("[MRT] Recording updates to {}", path.display());
+                file.insert(UpdatesFile {
+                    path,
+                    writer: BufWriter::new(created),
+                    size: 0,
+                })
+            }
+        };
+        current.writer.write_all(&buf).await?;
+        current.size += buf.len() as u64;
+        if config.rotate_size() != 0 && current.size >= config.rotate_size() {
+            close_updates(file).await?;
+        }
+    }
+    if let Some(current) = file.as_mut() {
+        current.writer.flush().await?;
+    }
+    Ok(())
+}
+
+async fn close_updates(file: &mut Option<UpdatesFile>) -> io::Result<()> {
+    if let Some(mut current) = file.take() {
+        log::debug!("[MRT] Closing {}", current.path.display());
+        current.writer.shutdown().await?;
+    }
+    Ok(())
+}
+
+async fn write_rib_dump(config: &MrtRecorderConfig, state: &MrtState) -> io::Result<()> {
+    let (path, file) = create_file(config.directory(), RIB_FILE_PREFIX).await?;
+    log::info!("[MRT] Writing RIB snapshot to {}", path.display());
+    let mut writer = BufWriter::new(file);
+    for record in state.rib_dump(Utc::now()) {
+        if let Some(buf) = encode(&record) {
+            writer.write_all(&buf).await?;
+        }
+    }
+    writer.shutdown().await
+}
+
+fn interval(period: Duration) -> Option<Interval> {
+    (!period.is_zero()).then(|| {
+        let mut interval = tokio::time::interval(period);
+        interval.reset();
+        interval
+    })
+}
+
+async fn tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn run_recorder<A: Into<SocketAddr>>(
+    config: MrtRecorderConfig,
+    mut rx: mpsc::UnboundedReceiver<MrtPeerEvent<A>>,
+) {
+    let mut state = MrtState::new(config.clone());
+    let mut updates = None;
+    let mut rotate_timer = interval(config.rotate_interval());
+    let mut rib_dump_timer = interval(config.rib_dump_interval());
+    loop {
+        let result = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => {
+                    let mut records = vec![state.apply(event)];
+                    while let Ok(event) = rx.try_recv() {
+                        records.push(state.apply(event));
+                    }
+                    write_updates(&config, &mut updates, records).await
+                }
+                None => {
+                    if let Err(err) = close_updates(&mut updates).await {
+                        log::error!("[MRT] Couldn't close the updates file: {err}");
+                    }
+                    return;
+                }
+            },
+            _ = tick(rotate_timer.as_mut()) => close_updates(&mut updates).await,
+            _ = tick(rib_dump_timer.as_mut()) => write_rib_dump(&config, &state).await,
+        };
+        if let Err(err) = result {
+            log::error!(
+                "[MRT] Couldn't write to {}: {err}",
+                config.directory().display()
+            );
+            // Records are written to a new file on the next event
+            updates.take();
+        }
+    }
+}
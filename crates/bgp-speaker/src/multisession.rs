@@ -0,0 +1,57 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BGP Multisession capability
+//! ([draft-ietf-idr-bgp-multisession](https://datatracker.ietf.org/doc/html/draft-ietf-idr-bgp-multisession)).
+//!
+//! Sessions to the same peer address are told apart by their
+//! [crate::listener::SessionKey], i.e., the local address and the routing
+//! instance. Multisession is enabled per peer with
+//! [crate::peer::PeerProperties::with_multisession], the capability is then
+//! added to the OPEN message sent to the peer, and it's negotiated when the
+//! peer advertises it as well, see [multisession_negotiated]. Peers that don't
+//! advertise it are still accepted as a single session.
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, MultisessionCapability},
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+};
+
+/// Add the Multisession capability to an OPEN message, unless the message
+/// already carries one
+pub(crate) fn add_multisession_capability(open: BgpOpenMessage) -> BgpOpenMessage {
+    let has_multisession = open
+        .capabilities()
+        .iter()
+        .any(|cap| matches!(cap, BgpCapability::Multisession(_)));
+    if has_multisession {
+        return open;
+    }
+    let mut params = open.params().clone();
+    params.push(BgpOpenMessageParameter::Capabilities(vec![
+        BgpCapability::Multisession(MultisessionCapability::new(false, vec![])),
+    ]));
+    BgpOpenMessage::new(open.my_as(), open.hold_time(), open.bgp_id(), params)
+}
+
+/// Multisession is negotiated when both the sent and the received OPEN
+/// messages carry the Multisession capability
+pub fn multisession_negotiated(sent: &[BgpCapability], received: &[BgpCapability]) -> bool {
+    let has_multisession = |caps: &[BgpCapability]| {
+        caps.iter()
+            .any(|cap| matches!(cap, BgpCapability::Multisession(_)))
+    };
+    has_multisession(sent) && has_multisession(received)
+}
@@ -0,0 +1,449 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Next-hop tracking: resolve the BGP next hops of the routes against the IGP
+//! (or any other routing source) and re-evaluate the routes when the
+//! resolution changes.
+//!
+//! A [NextHopResolver] reports if a next hop is reachable and at which IGP
+//! metric, [StaticNextHopResolver] is a resolver backed by a static table of
+//! prefixes. A [NextHopTracker] keeps track of the next hops of a set of
+//! routes and reports the routes affected by a change of the resolution, so
+//! the best path selection can be run again only for them.
+//!
+//! The peers track the next hops of the received routes once a resolver is
+//! set with [crate::supervisor::PeersSupervisor::set_next_hop_resolver], the
+//! routes with an unreachable next hop are reported as withdrawn until their
+//! next hop is reachable again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    future::Future,
+    hash::Hash,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use ipnet::IpNet;
+use tokio::{sync::watch, task::AbortHandle};
+
+use netgauze_bgp_pkt::{
+    nlri::LabeledNextHop,
+    path_attribute::{MpReach, PathAttributeValue},
+};
+
+use crate::rib::{MpNextHop, Route};
+
+/// Reachability of a BGP next hop as resolved by a [NextHopResolver]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum NextHopStatus {
+    Unreachable,
+    Reachable {
+        /// IGP metric to reach the next hop
+        metric: u32,
+    },
+}
+
+impl NextHopStatus {
+    pub const fn is_reachable(&self) -> bool {
+        matches!(self, Self::Reachable { .. })
+    }
+
+    pub const fn metric(&self) -> Option<u32> {
+        match self {
+            Self::Unreachable => None,
+            Self::Reachable { metric } => Some(*metric),
+        }
+    }
+}
+
+/// Resolves BGP next hops, e.g., against the IGP routing table
+pub trait NextHopResolver: Debug + Send + Sync {
+    /// Current reachability of `next_hop`
+    fn resolve(&self, next_hop: IpAddr) -> NextHopStatus;
+
+    /// Start tracking `next_hop`, the current status and its changes are
+    /// reported on the returned receiver. The tracking stops once all the
+    /// receivers of the next hop are dropped.
+    fn subscribe(&self, next_hop: IpAddr) -> watch::Receiver<NextHopStatus>;
+}
+
+/// [NextHopResolver] backed by a static table of prefixes and their metric. A
+/// next hop is resolved by the longest matching prefix, next hops not covered
+/// by any prefix are unreachable. The subscribers are notified when a change
+/// of the table changes the resolution of their next hop.
+#[derive(Debug, Default)]
+pub struct StaticNextHopResolver {
+    inner: Mutex<StaticNextHopResolverInner>,
+}
+
+#[derive(Debug, Default)]
+struct StaticNextHopResolverInner {
+    routes: HashMap<IpNet, u32>,
+    watchers: HashMap<IpAddr, watch::Sender<NextHopStatus>>,
+}
+
+impl StaticNextHopResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_route(self, prefix: IpNet, metric: u32) -> Self {
+        self.set_route(prefix, metric);
+        self
+    }
+
+    /// Add or replace the route to `prefix`, returns the previous metric
+    pub fn set_route(&self, prefix: IpNet, metric: u32) -> Option<u32> {
+        let mut inner = self.inner.lock().unwrap();
+        let old = inner.routes.insert(prefix.trunc(), metric);
+        inner.notify();
+        old
+    }
+
+    /// Remove the route to `prefix`, returns its metric
+    pub fn remove_route(&self, prefix: &IpNet) -> Option<u32> {
+        let mut inner = self.inner.lock().unwrap();
+        let old = inner.routes.remove(&prefix.trunc());
+        inner.notify();
+        old
+    }
+
+    /// Routes of the table ordered by prefix
+    pub fn routes(&self) -> Vec<(IpNet, u32)> {
+        let inner = self.inner.lock().unwrap();
+        let mut routes: Vec<_> = inner
+            .routes
+            .iter()
+            .map(|(prefix, metric)| (*prefix, *metric))
+            .collect();
+        routes.sort();
+        routes
+    }
+}
+
+impl StaticNextHopResolverInner {
+    fn lookup(routes: &HashMap<IpNet, u32>, next_hop: IpAddr) -> NextHopStatus {
+        routes
+            .iter()
+            .filter(|(prefix, _)| prefix.contains(&next_hop))
+            .max_by_key(|(prefix, _)| prefix.prefix_len())
+            .map_or(NextHopStatus::Unreachable, |(_, metric)| {
+                NextHopStatus::Reachable { metric: *metric }
+            })
+    }
+
+    /// Resolve again the subscribed next hops and notify the changes
+    fn notify(&mut self) {
+        self.watchers.retain(|_, tx| tx.receiver_count() > 0);
+        for (next_hop, tx) in &self.watchers {
+            let status = Self::lookup(&self.routes, *next_hop);
+            tx.send_if_modified(|current| {
+                if *current == status {
+                    return false;
+                }
+                *current = status;
+                true
+            });
+        }
+    }
+}
+
+impl NextHopResolver for StaticNextHopResolver {
+    fn resolve(&self, next_hop: IpAddr) -> NextHopStatus {
+        let inner = self.inner.lock().unwrap();
+        StaticNextHopResolverInner::lookup(&inner.routes, next_hop)
+    }
+
+    fn subscribe(&self, next_hop: IpAddr) -> watch::Receiver<NextHopStatus> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        let tx = inner.watchers.entry(next_hop).or_insert_with(|| {
+            watch::Sender::new(StaticNextHopResolverInner::lookup(&inner.routes, next_hop))
+        });
+        tx.subscribe()
+    }
+}
+
+/// Next hop carried in an `MP_REACH_NLRI` attribute of any address family.
+/// The Route Distinguisher of the labeled next hops is dropped, it's always
+/// zero [RFC4364](https://datatracker.ietf.org/doc/html/rfc4364#section-4.3.2).
+pub fn mp_reach_next_hop(mp_reach: &MpReach) -> Option<MpNextHop> {
+    match mp_reach {
+        MpReach::Ipv4Unicast {
+            next_hop,
+            next_hop_local,
+            ..
+        }
+        | MpReach::Ipv4Multicast {
+            next_hop,
+            next_hop_local,
+            ..
+        }
+        | MpReach::Ipv4NlriMplsLabels {
+            next_hop,
+            next_hop_local,
+            ..
+        }
+        | MpReach::Ipv6NlriMplsLabels {
+            next_hop,
+            next_hop_local,
+            ..
+        } => Some(MpNextHop::new(*next_hop, *next_hop_local)),
+        MpReach::Ipv6Unicast {
+            next_hop_global,
+            next_hop_local,
+            ..
+        }
+        | MpReach::Ipv6Multicast {
+            next_hop_global,
+            next_hop_local,
+            ..
+        } => Some(MpNextHop::new(
+            IpAddr::V6(*next_hop_global),
+            *next_hop_local,
+        )),
+        MpReach::Ipv4MplsVpnUnicast { next_hop, .. }
+        | MpReach::Ipv6MplsVpnUnicast { next_hop, .. }
+        | MpReach::BgpLsVpn { next_hop, .. } => Some(labeled_next_hop(next_hop)),
+        MpReach::L2Evpn { next_hop, .. }
+        | MpReach::RouteTargetMembership { next_hop, .. }
+        | MpReach::BgpLs { next_hop, .. } => Some(MpNextHop::new(*next_hop, None)),
+        MpReach::Unknown { .. } => None,
+    }
+}
+
+fn labeled_next_hop(next_hop: &LabeledNextHop) -> MpNextHop {
+    match next_hop {
+        LabeledNextHop::Ipv4(next_hop) => MpNextHop::new(IpAddr::V4(next_hop.next_hop()), None),
+        LabeledNextHop::Ipv6(next_hop) => {
+            MpNextHop::new(IpAddr::V6(next_hop.next_hop()), next_hop.next_hop_local())
+        }
+    }
+}
+
+/// Next hop of a route, either from the `MP_REACH_NLRI` or the `NEXT_HOP`
+/// attribute. `None` for the routes without next hop, such as the locally
+/// originated routes.
+pub fn route_next_hop(route: &Route) -> Option<MpNextHop> {
+    route.mp_next_hop().or_else(|| {
+        route
+            .attributes()
+            .iter()
+            .find_map(|attr| match attr.value() {
+                PathAttributeValue::NextHop(next_hop) => {
+                    Some(MpNextHop::new(IpAddr::V4(next_hop.next_hop()), None))
+                }
+                _ => None,
+            })
+    })
+}
+
+/// Address to resolve for a next hop. The global next hop is resolved, unless
+/// it is unspecified and only the link-local next hop is usable
+/// [RFC2545](https://datatracker.ietf.org/doc/html/rfc2545#section-3). An
+/// IPv4-mapped IPv6 next hop [RFC4798](https://datatracker.ietf.org/doc/html/rfc4798#section-2)
+/// is resolved as an IPv4 address.
+pub fn resolvable_address(next_hop: &MpNextHop) -> IpAddr {
+    match (next_hop.next_hop(), next_hop.next_hop_local()) {
+        (IpAddr::V6(global), Some(local)) if global.is_unspecified() => IpAddr::V6(local),
+        (IpAddr::V6(global), _) => global
+            .to_ipv4_mapped()
+            .map_or(IpAddr::V6(global), IpAddr::V4),
+        (IpAddr::V4(global), _) => IpAddr::V4(global),
+    }
+}
+
+#[derive(Debug)]
+struct TrackedNextHop<K> {
+    rx: watch::Receiver<NextHopStatus>,
+    /// Last status reported by [NextHopTracker::poll_changes]
+    status: NextHopStatus,
+    keys: HashSet<K>,
+    /// Task forwarding the changes of the next hop to the generation of the
+    /// tracker, see [forward_changes]
+    forwarder: AbortHandle,
+}
+
+impl<K> Drop for TrackedNextHop<K> {
+    fn drop(&mut self) {
+        self.forwarder.abort();
+    }
+}
+
+/// Bump the generation of a [NextHopTracker] each time the resolver reports
+/// a change of the next hop, until the resolver stops tracking it
+async fn forward_changes(
+    mut rx: watch::Receiver<NextHopStatus>,
+    generation: Arc<watch::Sender<u64>>,
+) {
+    while rx.changed().await.is_ok() {
+        generation.send_modify(|generation| *generation = generation.wrapping_add(1));
+    }
+}
+
+/// Next hop whose resolution changed and the routes using it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextHopChange<K> {
+    next_hop: IpAddr,
+    status: NextHopStatus,
+    keys: Vec<K>,
+}
+
+impl<K> NextHopChange<K> {
+    pub const fn next_hop(&self) -> IpAddr {
+        self.next_hop
+    }
+
+    pub const fn status(&self) -> NextHopStatus {
+        self.status
+    }
+
+    /// Routes using the next hop
+    pub const fn keys(&self) -> &Vec<K> {
+        &self.keys
+    }
+}
+
+/// Tracks the next hops of a set of routes identified by `K`. Each distinct
+/// next hop is subscribed once to the [NextHopResolver], the subscription is
+/// dropped when no route uses the next hop anymore.
+///
+/// The tracker is event driven: [NextHopTracker::changed] resolves when the
+/// resolution of a tracked next hop may have changed, then
+/// [NextHopTracker::poll_changes] returns the routes to re-evaluate. Tracking
+/// a next hop spawns a task on the tokio runtime to forward its changes.
+#[derive(Debug)]
+pub struct NextHopTracker<K> {
+    resolver: Arc<dyn NextHopResolver>,
+    next_hops: HashMap<IpAddr, TrackedNextHop<K>>,
+    routes: HashMap<K, IpAddr>,
+    /// Bumped when the status of a tracked next hop changes or a next hop is
+    /// subscribed
+    generation: Arc<watch::Sender<u64>>,
+    /// Generation as of the last [NextHopTracker::poll_changes]
+    polled_generation: watch::Receiver<u64>,
+}
+
+impl<K: Clone + Eq + Hash> NextHopTracker<K> {
+    pub fn new(resolver: Arc<dyn NextHopResolver>) -> Self {
+        let (generation, polled_generation) = watch::channel(0);
+        Self {
+            resolver,
+            next_hops: HashMap::new(),
+            routes: HashMap::new(),
+            generation: Arc::new(generation),
+            polled_generation,
+        }
+    }
+
+    pub fn resolver(&self) -> &Arc<dyn NextHopResolver> {
+        &self.resolver
+    }
+
+    /// Track the next hop of the route identified by `key`, replacing the one
+    /// previously tracked for it. Returns the current status of the next hop,
+    /// `None` if the route has no next hop.
+    pub fn track(&mut self, key: K, route: &Route) -> Option<NextHopStatus> {
+        let next_hop = route_next_hop(route).map(|next_hop| resolvable_address(&next_hop));
+        if self.routes.get(&key) != next_hop.as_ref() {
+            self.untrack(&key);
+        }
+        let next_hop = next_hop?;
+        self.routes.insert(key.clone(), next_hop);
+        let tracked = self.next_hops.entry(next_hop).or_insert_with(|| {
+            let mut rx = self.resolver.subscribe(next_hop);
+            let status = *rx.borrow_and_update();
+            let forwarder =
+                tokio::spawn(forward_changes(rx.clone(), self.generation.clone())).abort_handle();
+            self.generation
+                .send_modify(|generation| *generation = generation.wrapping_add(1));
+            TrackedNextHop {
+                rx,
+                status,
+                keys: HashSet::new(),
+                forwarder,
+            }
+        });
+        tracked.keys.insert(key);
+        Some(tracked.status)
+    }
+
+    /// Stop tracking the next hop of the route identified by `key`
+    pub fn untrack(&mut self, key: &K) {
+        let next_hop = match self.routes.remove(key) {
+            Some(next_hop) => next_hop,
+            None => return,
+        };
+        if let Some(tracked) = self.next_hops.get_mut(&next_hop) {
+            tracked.keys.remove(key);
+            if tracked.keys.is_empty() {
+                self.next_hops.remove(&next_hop);
+            }
+        }
+    }
+
+    /// Status of the next hop of the route identified by `key`, as of the
+    /// last [NextHopTracker::poll_changes]. `None` if the route isn't tracked
+    /// or has no next hop.
+    pub fn status(&self, key: &K) -> Option<NextHopStatus> {
+        self.routes
+            .get(key)
+            .and_then(|next_hop| self.next_hops.get(next_hop))
+            .map(|tracked| tracked.status)
+    }
+
+    /// Tracked next hops and their status
+    pub fn next_hops(&self) -> Vec<(IpAddr, NextHopStatus)> {
+        self.next_hops
+            .iter()
+            .map(|(next_hop, tracked)| (*next_hop, tracked.status))
+            .collect()
+    }
+
+    /// Returns a future that resolves once the resolution of the next hops
+    /// tracked at the time of the call or afterward may have changed since the
+    /// last [NextHopTracker::poll_changes]. The future doesn't borrow the
+    /// tracker, so it can be awaited while the tracker is updated.
+    pub fn changed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut generation = self.polled_generation.clone();
+        async move {
+            if generation.changed().await.is_err() {
+                // The tracker was dropped
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Collect the next hops whose status changed since the last call
+    pub fn poll_changes(&mut self) -> Vec<NextHopChange<K>> {
+        self.polled_generation.borrow_and_update();
+        let mut changes = vec![];
+        for (next_hop, tracked) in &mut self.next_hops {
+            let status = *tracked.rx.borrow_and_update();
+            if status == tracked.status {
+                continue;
+            }
+            tracked.status = status;
+            changes.push(NextHopChange {
+                next_hop: *next_hop,
+                status,
+                keys: tracked.keys.iter().cloned().collect(),
+            });
+        }
+        changes
+    }
+}
@@ -14,39 +14,70 @@
 // limitations under the License.
 
 use std::{
+    any::Any,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Display, Formatter},
+    future::Future,
     marker::PhantomData,
     net::Ipv4Addr,
     ops::Add,
+    sync::Arc,
     time::Duration,
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use futures_util::SinkExt;
 use rand::{rngs::SmallRng, thread_rng, Rng, RngCore, SeedableRng};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     sync::oneshot,
-    time::Interval,
+    time::{Instant, Interval},
 };
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
 use netgauze_bgp_pkt::{
     capabilities::{BgpCapability, FourOctetAsCapability},
     codec::{BgpCodecDecoderError, BgpCodecInitializer},
-    iana::{BgpCapabilityCode, AS_TRANS},
+    iana::{BgpCapabilityCode, BgpRoleValue, RouteRefreshSubcode, AS_TRANS},
     notification::{BgpNotificationMessage, CeaseError, OpenMessageError},
     open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::PathAttributeValue,
+    route_refresh::BgpRouteRefreshMessage,
+    update::BgpUpdateMessage,
     wire::{deserializer::BgpParsingIgnoredErrors, serializer::BgpMessageWritingError},
     BgpMessage,
 };
-
+use netgauze_iana::address_family::{AddressFamily, AddressType, SubsequentAddressFamily};
+
+#[cfg(feature = "bmp")]
+use crate::bmp::{BmpExporter, BmpPeerEvent, BmpRib};
+#[cfg(feature = "mrt")]
+use crate::mrt::{is_asn4, MrtPeerEvent, MrtRecorder};
+#[cfg(feature = "rpki")]
+use crate::rpki::RouteOriginValidator;
 use crate::{
+    confederation::has_confed_segments,
     connection::{ActiveConnect, Connection, ConnectionState, ConnectionStats, ConnectionType},
-    events::{BgpEvent, ConnectionEvent},
+    damping::{DampedRoute, RouteFlapDamping, RouteFlapDampingConfig},
+    events::{BgpEvent, ConnectionEvent, UpdateTreatment},
+    export::{export_route, ExportConfig, ExportRoute},
     fsm::{FsmState, FsmStateError},
+    liveness::{LivenessDetector, LivenessMonitor, LivenessState},
+    nexthop::{NextHopResolver, NextHopStatus, NextHopTracker},
+    reflection::{is_reflection_loop, ClientRole},
+    rib::{
+        route_updates, withdraw_updates, AdjRib, Route, RouteKey, UpdateRoutes,
+        BGP_EXTENDED_MAX_MESSAGE_LENGTH, BGP_MAX_MESSAGE_LENGTH,
+    },
+    role::otc_ingress,
+    stats::{FsmTransition, NotificationRecord, PrefixCounters, RateMeter, FSM_HISTORY_LEN},
 };
+#[cfg(feature = "bmp")]
+use netgauze_bmp_pkt::PeerDownNotificationReason;
+#[cfg(feature = "rpki")]
+use netgauze_rpki::aspa::AspaDirection;
 
 pub type PeerResult<A> = Result<BgpEvent<A>, FsmStateError<A>>;
 
@@ -87,7 +118,7 @@ pub trait PeerPolicy<
 /// capability and rejecting some capabilities. For this policy to be effective,
 /// OpenDelayTimer must be set to large enough value. Otherwise, only initially
 /// defined `capabilities` are sent to the peer.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct EchoCapabilitiesPolicy<A, I, D> {
     my_asn: u32,
     send_asn4_cap_by_default: bool,
@@ -135,6 +166,38 @@ impl<A, I, D> EchoCapabilitiesPolicy<A, I, D> {
     }
 }
 
+// Not derived to avoid requiring the marker types to be cloneable
+impl<A, I, D> Clone for EchoCapabilitiesPolicy<A, I, D> {
+    fn clone(&self) -> Self {
+        Self {
+            my_asn: self.my_asn,
+            send_asn4_cap_by_default: self.send_asn4_cap_by_default,
+            my_bgp_id: self.my_bgp_id,
+            remote_as: self.remote_as,
+            hold_timer_duration: self.hold_timer_duration,
+            capabilities: self.capabilities.clone(),
+            reject_capabilities: self.reject_capabilities.clone(),
+            peer_capabilities: self.peer_capabilities.clone(),
+            _address_marker: PhantomData,
+            _inner_marker: PhantomData,
+            _codec_marker: PhantomData,
+        }
+    }
+}
+
+/// Policies are equal when they're configured the same, the remote AS and
+/// the capabilities learned from the peer's OPEN message are ignored.
+impl<A, I, D> PartialEq for EchoCapabilitiesPolicy<A, I, D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.my_asn == other.my_asn
+            && self.send_asn4_cap_by_default == other.send_asn4_cap_by_default
+            && self.my_bgp_id == other.my_bgp_id
+            && self.hold_timer_duration == other.hold_timer_duration
+            && self.capabilities == other.capabilities
+            && self.reject_capabilities == other.reject_capabilities
+    }
+}
+
 #[async_trait]
 impl<
         A: Send + Sync + 'static,
@@ -258,13 +321,49 @@ impl<A: Display, I: AsyncWrite + AsyncRead> Display for PeerAdminEvents<A, I> {
 pub enum PeerEvent<A, I: AsyncWrite + AsyncRead> {
     Admin(PeerAdminEvents<A, I>),
     BgpMessage(BgpMessage),
-    GetPeerStats(oneshot::Sender<PeerStats>),
+    GetPeerStats(oneshot::Sender<PeerStats<A>>),
     GetConnectionStats(oneshot::Sender<Option<ConnectionStats>>),
     GetTrackedConnectionStats(oneshot::Sender<Option<ConnectionStats>>),
     ConnectionSentCapabilities(oneshot::Sender<Option<Vec<BgpCapability>>>),
     ConnectionReceivedCapabilities(oneshot::Sender<Option<Vec<BgpCapability>>>),
     TrackedConnectionSentCapabilities(oneshot::Sender<Option<Vec<BgpCapability>>>),
     TrackedConnectionReceivedCapabilities(oneshot::Sender<Option<Vec<BgpCapability>>>),
+    /// Ask the peer to re-advertise its Adj-RIB-Out for the given address type
+    RequestRouteRefresh(AddressType),
+    GetAdjRibIn(oneshot::Sender<AdjRib>),
+    GetAdjRibOut(oneshot::Sender<AdjRib>),
+    /// Damping state of a received route, see [Peer::damped_route]
+    GetDampedRoute(RouteKey, oneshot::Sender<Option<DampedRoute<()>>>),
+    /// Received routes suppressed by the route flap damping
+    GetDampenedRoutes(oneshot::Sender<Vec<DampedRoute<()>>>),
+    /// Received routes with flap history
+    GetFlapStatistics(oneshot::Sender<Vec<DampedRoute<()>>>),
+    /// Add or replace routes exported to the peer, see [Peer::export_routes]
+    AnnounceRoutes(Vec<ExportRoute>),
+    /// Stop exporting routes to the peer, see [Peer::export_routes]
+    WithdrawRoutes(Vec<RouteKey>),
+    /// Replace the [PeerConfig] of a running peer, see [Peer::update_config]
+    UpdateConfig(Box<PeerConfig>),
+    /// Replace the [PeerPolicy] of a running peer, the boxed value is of the
+    /// same type as the policy the peer was created with, see
+    /// [crate::peer_controller::PeerHandle::update_policy]
+    UpdatePolicy(Box<dyn Any + Send>),
+    /// Report the session to a BMP exporter, see [Peer::set_bmp_exporter]
+    #[cfg(feature = "bmp")]
+    SetBmpExporter(Option<BmpExporter<A>>),
+    /// Record the session to MRT files, see [Peer::set_mrt_recorder]
+    #[cfg(feature = "mrt")]
+    SetMrtRecorder(Option<MrtRecorder<A>>),
+    /// Validate the origin of the received routes, see
+    /// [Peer::set_route_origin_validator]
+    #[cfg(feature = "rpki")]
+    SetRouteOriginValidator(Option<RouteOriginValidator>),
+    /// Tie the session to a liveness detector, see
+    /// [Peer::set_liveness_detector]
+    SetLivenessDetector(Option<Arc<dyn LivenessDetector<A>>>),
+    /// Resolve the next hops of the received routes, see
+    /// [Peer::set_next_hop_resolver]
+    SetNextHopResolver(Option<Arc<dyn NextHopResolver>>),
 }
 
 impl<A: Display, I: AsyncWrite + AsyncRead> Display for PeerEvent<A, I> {
@@ -285,6 +384,26 @@ impl<A: Display, I: AsyncWrite + AsyncRead> Display for PeerEvent<A, I> {
             PeerEvent::TrackedConnectionReceivedCapabilities(_) => {
                 write!(f, "TrackedConnectionReceivedCapabilities")
             }
+            PeerEvent::RequestRouteRefresh(address_type) => {
+                write!(f, "RequestRouteRefresh({address_type:?})")
+            }
+            PeerEvent::GetAdjRibIn(_) => write!(f, "GetAdjRibIn"),
+            PeerEvent::GetAdjRibOut(_) => write!(f, "GetAdjRibOut"),
+            PeerEvent::GetDampedRoute(key, _) => write!(f, "GetDampedRoute({key:?})"),
+            PeerEvent::GetDampenedRoutes(_) => write!(f, "GetDampenedRoutes"),
+            PeerEvent::GetFlapStatistics(_) => write!(f, "GetFlapStatistics"),
+            PeerEvent::AnnounceRoutes(routes) => write!(f, "AnnounceRoutes({})", routes.len()),
+            PeerEvent::WithdrawRoutes(keys) => write!(f, "WithdrawRoutes({})", keys.len()),
+            PeerEvent::UpdateConfig(config) => write!(f, "UpdateConfig({config:?})"),
+            PeerEvent::UpdatePolicy(_) => write!(f, "UpdatePolicy"),
+            #[cfg(feature = "bmp")]
+            PeerEvent::SetBmpExporter(_) => write!(f, "SetBmpExporter"),
+            #[cfg(feature = "mrt")]
+            PeerEvent::SetMrtRecorder(_) => write!(f, "SetMrtRecorder"),
+            #[cfg(feature = "rpki")]
+            PeerEvent::SetRouteOriginValidator(_) => write!(f, "SetRouteOriginValidator"),
+            PeerEvent::SetLivenessDetector(_) => write!(f, "SetLivenessDetector"),
+            PeerEvent::SetNextHopResolver(_) => write!(f, "SetNextHopResolver"),
         }
     }
 }
@@ -318,30 +437,262 @@ enum CollisionCheckRet {
     InvalidTrackedBgpId(Ipv4Addr),
 }
 
+/// Internally used to track the number of prefixes received from a peer
+/// relative to the configured [MaxPrefixConfig], so warnings are logged only
+/// when the state changes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum MaxPrefixState {
+    Normal,
+    Warning,
+    Exceeded,
+}
+
+/// Internally used to signal that the routes received from the peer have to
+/// be validated again, see [crate::rpki]. Uninhabited without the `rpki`
+/// feature, since the routes are never validated.
+#[cfg(feature = "rpki")]
+struct OriginValidationChanged;
+
+#[cfg(not(feature = "rpki"))]
+type OriginValidationChanged = std::convert::Infallible;
+
 /// Internally used return type when polling main and tracked connection for
 /// next ConnectionEvent to handle.
 #[derive(Debug, Clone, PartialEq)]
 enum ConnectionNextEvent<A> {
     DropMain,
-    DropTracked,
+    /// Drop the tracked connection, then handle the main connection event that
+    /// triggered the collision detection (if any).
+    DropTracked(Option<ConnectionEvent<A>>),
     Event(ConnectionEvent<A>),
 }
 
-#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
-pub struct PeerStats {
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats<A> {
     connect_retry_counter: u32,
+    damp_penalty: u32,
+    established_transitions: u32,
+    last_up: Option<DateTime<Utc>>,
+    last_down: Option<DateTime<Utc>>,
+    uptime: Option<Duration>,
+    last_notification_sent: Option<NotificationRecord>,
+    last_notification_received: Option<NotificationRecord>,
+    prefixes: HashMap<AddressType, PrefixCounters>,
+    update_rate: f64,
+    withdraw_rate: f64,
+    /// Total number of FSM state transitions, including the ones that are no
+    /// longer in `fsm_history`
+    fsm_transitions: u64,
+    fsm_history: VecDeque<FsmTransition<A>>,
+    fsm_state: FsmState,
 }
 
-impl PeerStats {
+impl<A> Default for PeerStats<A> {
+    fn default() -> Self {
+        Self {
+            connect_retry_counter: 0,
+            damp_penalty: 0,
+            established_transitions: 0,
+            last_up: None,
+            last_down: None,
+            uptime: None,
+            last_notification_sent: None,
+            last_notification_received: None,
+            prefixes: HashMap::new(),
+            update_rate: 0.0,
+            withdraw_rate: 0.0,
+            fsm_transitions: 0,
+            fsm_history: VecDeque::with_capacity(FSM_HISTORY_LEN),
+            fsm_state: FsmState::Idle,
+        }
+    }
+}
+
+impl<A> PeerStats<A> {
     pub const fn connect_retry_counter(&self) -> u32 {
         self.connect_retry_counter
     }
+
+    /// Number of recent session failures used to compute the IdleHoldTimer
+    /// when DampPeerOscillations is enabled
+    pub const fn damp_penalty(&self) -> u32 {
+        self.damp_penalty
+    }
+
+    /// Number of times the FSM transitioned to Established state
+    pub const fn established_transitions(&self) -> u32 {
+        self.established_transitions
+    }
+
+    /// Last time the session was established
+    pub const fn last_up(&self) -> Option<DateTime<Utc>> {
+        self.last_up
+    }
+
+    /// Last time the session left the Established state
+    pub const fn last_down(&self) -> Option<DateTime<Utc>> {
+        self.last_down
+    }
+
+    /// Time since the session was established, `None` if the session is not
+    /// established
+    pub const fn uptime(&self) -> Option<Duration> {
+        self.uptime
+    }
+
+    pub const fn last_notification_sent(&self) -> Option<&NotificationRecord> {
+        self.last_notification_sent.as_ref()
+    }
+
+    pub const fn last_notification_received(&self) -> Option<&NotificationRecord> {
+        self.last_notification_received.as_ref()
+    }
+
+    /// Prefix counters per AFI/SAFI
+    pub const fn prefixes(&self) -> &HashMap<AddressType, PrefixCounters> {
+        &self.prefixes
+    }
+
+    pub fn prefix_counters(&self, address_type: AddressType) -> PrefixCounters {
+        self.prefixes
+            .get(&address_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Update messages received per second over the last [crate::stats::RATE_WINDOW]
+    pub const fn update_rate(&self) -> f64 {
+        self.update_rate
+    }
+
+    /// Prefixes withdrawn per second over the last [crate::stats::RATE_WINDOW]
+    pub const fn withdraw_rate(&self) -> f64 {
+        self.withdraw_rate
+    }
+
+    pub const fn fsm_transitions(&self) -> u64 {
+        self.fsm_transitions
+    }
+
+    /// The last [FSM_HISTORY_LEN] FSM state transitions, oldest first
+    pub const fn fsm_history(&self) -> &VecDeque<FsmTransition<A>> {
+        &self.fsm_history
+    }
+
+    /// FSM state of the peer when the stats were collected
+    pub const fn fsm_state(&self) -> FsmState {
+        self.fsm_state
+    }
 }
 
-/// Peer Configurations that are allowed to change without needing to restart
-/// the peer
+/// Invalid values given to [MaxPrefixConfig::new] or
+/// [PeerConfigBuilder::max_prefix]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MaxPrefixConfigError {
+    /// The warning threshold must be a percentage in the range `1..=100`
+    InvalidWarningThreshold(u8),
+    /// A session can't be restarted immediately after being torn down, use
+    /// `None` to wait for a manual start instead
+    ZeroRestartInterval,
+    /// The prefixes are only counted for the address types tracked by the
+    /// Adj-RIB-In, see [AdjRib::is_supported]
+    UnsupportedAddressType(AddressType),
+}
+
+impl Display for MaxPrefixConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidWarningThreshold(value) => write!(
+                f,
+                "invalid maximum-prefix warning threshold {value}%, expected 1 to 100"
+            ),
+            Self::ZeroRestartInterval => {
+                write!(
+                    f,
+                    "maximum-prefix restart interval must be at least 1 second"
+                )
+            }
+            Self::UnsupportedAddressType(address_type) => {
+                write!(f, "maximum-prefix is not supported for {address_type:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaxPrefixConfigError {}
+
+/// Maximum number of prefixes accepted from a peer for a given AFI/SAFI
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct MaxPrefixConfig {
+    limit: u32,
+    warning_threshold: u8,
+    warning_only: bool,
+    restart_interval: Option<u16>,
+}
+
+impl MaxPrefixConfig {
+    /// * `limit`: max number of prefixes accepted from the peer.
+    /// * `warning_threshold`: percentage of the limit at which a warning is
+    ///   logged, in the range `1..=100`.
+    /// * `warning_only`: only log a warning when the limit is exceeded rather
+    ///   than tearing down the session.
+    /// * `restart_interval`: seconds to wait before automatically restarting a
+    ///   session that was torn down, `None` means wait for a manual start.
+    pub const fn new(
+        limit: u32,
+        warning_threshold: u8,
+        warning_only: bool,
+        restart_interval: Option<u16>,
+    ) -> Result<Self, MaxPrefixConfigError> {
+        if warning_threshold == 0 || warning_threshold > 100 {
+            return Err(MaxPrefixConfigError::InvalidWarningThreshold(
+                warning_threshold,
+            ));
+        }
+        if matches!(restart_interval, Some(0)) {
+            return Err(MaxPrefixConfigError::ZeroRestartInterval);
+        }
+        Ok(Self {
+            limit,
+            warning_threshold,
+            warning_only,
+            restart_interval,
+        })
+    }
+
+    pub const fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub const fn warning_threshold(&self) -> u8 {
+        self.warning_threshold
+    }
+
+    pub const fn warning_only(&self) -> bool {
+        self.warning_only
+    }
+
+    pub const fn restart_interval(&self) -> Option<Duration> {
+        match self.restart_interval {
+            Some(value) => Some(Duration::from_secs(value as u64)),
+            None => None,
+        }
+    }
+
+    /// Number of prefixes at which a warning is logged
+    pub const fn warning_limit(&self) -> u64 {
+        self.limit as u64 * self.warning_threshold as u64 / 100
+    }
+}
+
+/// Peer Configurations that are allowed to change without needing to restart
+/// the peer, see [Peer::update_config]
+///
+/// Note: `PeerConfig` isn't `Copy` since it holds the per AFI/SAFI
+/// [MaxPrefixConfig] and the [ExportConfig], clone it where a copy was taken.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 pub struct PeerConfig {
     allow_auto_start: bool,
     allow_auto_stop: bool,
@@ -352,9 +703,15 @@ pub struct PeerConfig {
     pub(crate) hold_timer_duration_large_value: u16,
     pub(crate) keepalive_timer_duration: u16,
     pub(crate) idle_hold_duration: u16,
+    damp_peer_oscillations: bool,
+    idle_hold_max_duration: u16,
+    idle_hold_decay_duration: u16,
     passive_tcp_establishment: bool,
     collision_detect_established_state: bool,
     rng_seed: u64,
+    max_prefix: HashMap<AddressType, MaxPrefixConfig>,
+    export: ExportConfig,
+    route_flap_damping: Option<RouteFlapDampingConfig>,
 }
 
 impl Default for PeerConfig {
@@ -370,9 +727,15 @@ impl Default for PeerConfig {
             hold_timer_duration_large_value: 240,
             keepalive_timer_duration: 30,
             idle_hold_duration: 1,
+            damp_peer_oscillations: false,
+            idle_hold_max_duration: 300,
+            idle_hold_decay_duration: 60,
             passive_tcp_establishment: false,
             collision_detect_established_state: false,
             rng_seed: thread_rng().next_u64(),
+            max_prefix: HashMap::new(),
+            export: ExportConfig::default(),
+            route_flap_damping: None,
         }
     }
 }
@@ -418,9 +781,56 @@ impl PeerConfig {
         Duration::from_secs(self.idle_hold_duration as u64)
     }
 
+    /// RFC 4271 optional DampPeerOscillations attribute. When enabled, the
+    /// peer is kept in Idle state for an exponentially increasing
+    /// IdleHoldTime after each session failure, then automatically restarted.
+    pub const fn damp_peer_oscillations(&self) -> bool {
+        self.damp_peer_oscillations
+    }
+
+    /// Upper bound of the IdleHoldTime
+    pub const fn idle_hold_max_duration(&self) -> Duration {
+        Duration::from_secs(self.idle_hold_max_duration as u64)
+    }
+
+    /// Time a session has to stay established to decrease the damping penalty
+    /// by one
+    pub const fn idle_hold_decay_duration(&self) -> Duration {
+        Duration::from_secs(self.idle_hold_decay_duration as u64)
+    }
+
+    /// IdleHoldTime for a given damping penalty, starting at
+    /// [PeerConfig::idle_hold_duration] and doubling for each additional
+    /// penalty point up to [PeerConfig::idle_hold_max_duration].
+    pub fn idle_hold_time(&self, damp_penalty: u32) -> Duration {
+        let exponent = damp_penalty.saturating_sub(1).min(16);
+        let secs = (self.idle_hold_duration as u64) << exponent;
+        let secs = secs.min(self.idle_hold_max_duration as u64);
+        if secs == 0 {
+            Duration::from_millis(1)
+        } else {
+            Duration::from_secs(secs)
+        }
+    }
+
     pub const fn passive_tcp_establishment(&self) -> bool {
         self.passive_tcp_establishment
     }
+
+    pub fn max_prefix(&self, address_type: AddressType) -> Option<MaxPrefixConfig> {
+        self.max_prefix.get(&address_type).copied()
+    }
+
+    /// Settings applied to the routes exported to the peer
+    pub const fn export(&self) -> &ExportConfig {
+        &self.export
+    }
+
+    /// Route flap damping applied to the routes received from the peer,
+    /// disabled when `None`
+    pub const fn route_flap_damping(&self) -> Option<RouteFlapDampingConfig> {
+        self.route_flap_damping
+    }
 }
 
 #[derive(Debug, Default)]
@@ -478,6 +888,21 @@ impl PeerConfigBuilder {
         self
     }
 
+    pub const fn damp_peer_oscillations(mut self, value: bool) -> Self {
+        self.config.damp_peer_oscillations = value;
+        self
+    }
+
+    pub const fn idle_hold_max_duration(mut self, value: u16) -> Self {
+        self.config.idle_hold_max_duration = value;
+        self
+    }
+
+    pub const fn idle_hold_decay_duration(mut self, value: u16) -> Self {
+        self.config.idle_hold_decay_duration = value;
+        self
+    }
+
     pub const fn passive_tcp_establishment(mut self, value: bool) -> Self {
         self.config.passive_tcp_establishment = value;
         self
@@ -493,7 +918,31 @@ impl PeerConfigBuilder {
         self
     }
 
-    pub const fn build(self) -> PeerConfig {
+    /// Limit the prefixes received for an address type, only the address
+    /// types tracked by the Adj-RIB-In are supported.
+    pub fn max_prefix(
+        mut self,
+        address_type: AddressType,
+        value: MaxPrefixConfig,
+    ) -> Result<Self, MaxPrefixConfigError> {
+        if !AdjRib::is_supported(address_type) {
+            return Err(MaxPrefixConfigError::UnsupportedAddressType(address_type));
+        }
+        self.config.max_prefix.insert(address_type, value);
+        Ok(self)
+    }
+
+    pub fn export(mut self, value: ExportConfig) -> Self {
+        self.config.export = value;
+        self
+    }
+
+    pub const fn route_flap_damping(mut self, value: Option<RouteFlapDampingConfig>) -> Self {
+        self.config.route_flap_damping = value;
+        self
+    }
+
+    pub fn build(self) -> PeerConfig {
         self.config
     }
 }
@@ -507,6 +956,13 @@ pub struct PeerProperties<A> {
     my_bgp_id: Ipv4Addr,
     peer_addr: A,
     allow_dynamic_as: bool,
+    client_role: ClientRole,
+    cluster_id: Option<Ipv4Addr>,
+    bgp_role: Option<BgpRoleValue>,
+    strict_bgp_role: bool,
+    confederation_id: Option<u32>,
+    confederation_member: bool,
+    multisession: bool,
 }
 
 impl<A: Clone> PeerProperties<A> {
@@ -523,9 +979,60 @@ impl<A: Clone> PeerProperties<A> {
             my_bgp_id,
             peer_addr,
             allow_dynamic_as,
+            client_role: ClientRole::NonClient,
+            cluster_id: None,
+            bgp_role: None,
+            strict_bgp_role: false,
+            confederation_id: None,
+            confederation_member: false,
+            multisession: false,
         }
     }
 
+    /// Route reflector or route server role of the peer
+    pub const fn with_client_role(mut self, value: ClientRole) -> Self {
+        self.client_role = value;
+        self
+    }
+
+    /// Route reflector cluster ID, defaults to the local BGP Identifier
+    pub const fn with_cluster_id(mut self, value: Option<Ipv4Addr>) -> Self {
+        self.cluster_id = value;
+        self
+    }
+
+    /// RFC9234 local BGP Role, only applicable to external peers
+    pub const fn with_bgp_role(mut self, value: Option<BgpRoleValue>) -> Self {
+        self.bgp_role = value;
+        self
+    }
+
+    /// Reject the session when the peer doesn't advertise its BGP Role
+    pub const fn with_strict_bgp_role(mut self, value: bool) -> Self {
+        self.strict_bgp_role = value;
+        self
+    }
+
+    /// RFC5065 confederation identifier, `my_asn` is then the member AS of the
+    /// local speaker within the confederation
+    pub const fn with_confederation_id(mut self, value: Option<u32>) -> Self {
+        self.confederation_id = value;
+        self
+    }
+
+    /// The peer is in another member AS of the local confederation
+    pub const fn with_confederation_member(mut self, value: bool) -> Self {
+        self.confederation_member = value;
+        self
+    }
+
+    /// Advertise the Multisession capability to the peer, see
+    /// [crate::multisession]
+    pub const fn with_multisession(mut self, value: bool) -> Self {
+        self.multisession = value;
+        self
+    }
+
     pub const fn my_asn(&self) -> u32 {
         self.my_asn
     }
@@ -541,6 +1048,55 @@ impl<A: Clone> PeerProperties<A> {
     pub const fn allow_dynamic_as(&self) -> bool {
         self.allow_dynamic_as
     }
+    pub const fn client_role(&self) -> ClientRole {
+        self.client_role
+    }
+    pub const fn cluster_id(&self) -> Ipv4Addr {
+        match self.cluster_id {
+            Some(cluster_id) => cluster_id,
+            None => self.my_bgp_id,
+        }
+    }
+    /// Both ends of the session are in the same AS
+    pub const fn is_internal(&self) -> bool {
+        self.my_asn == self.peer_asn
+    }
+    /// The local BGP Role, always `None` for internal and confederation peers
+    /// since the BGP Role capability is not used within an AS
+    pub const fn bgp_role(&self) -> Option<BgpRoleValue> {
+        if self.is_internal() || self.is_confed_external() {
+            None
+        } else {
+            self.bgp_role
+        }
+    }
+    pub const fn strict_bgp_role(&self) -> bool {
+        self.strict_bgp_role
+    }
+    pub const fn confederation_id(&self) -> Option<u32> {
+        self.confederation_id
+    }
+    pub const fn confederation_member(&self) -> bool {
+        self.confederation_member
+    }
+    pub const fn multisession(&self) -> bool {
+        self.multisession
+    }
+    /// The peer is in another member AS of the local confederation
+    /// (confed-eBGP)
+    pub const fn is_confed_external(&self) -> bool {
+        !self.is_internal() && self.confederation_id.is_some() && self.confederation_member
+    }
+    /// The AS number the peer sees for the local speaker, the confederation
+    /// identifier for peers outside the confederation
+    pub const fn local_asn(&self) -> u32 {
+        match self.confederation_id {
+            Some(confederation_id) if !self.is_internal() && !self.confederation_member => {
+                confederation_id
+            }
+            _ => self.my_asn,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -562,11 +1118,54 @@ pub struct Peer<
     connection: Option<Connection<A, I, D>>,
     tracked_connection: Option<Connection<A, I, D>>,
     connect_retry_timer: Option<Interval>,
-    stats: PeerStats,
+    stats: PeerStats<A>,
+    update_rate: RateMeter,
+    withdraw_rate: RateMeter,
     active_connect: C,
     allowed_to_active_connect: bool,
     waiting_admin_events: Vec<PeerAdminEvents<A, I>>,
+    /// BGP events generated as side effect of handling another event and to
+    /// be reported in the subsequent calls to [Peer::run]
+    pending_events: VecDeque<BgpEvent<A>>,
+    adj_rib_in: AdjRib,
+    adj_rib_out: AdjRib,
+    /// Routes to be exported to the peer before applying [ExportConfig]
+    export_rib: HashMap<RouteKey, ExportRoute>,
+    /// Routes in `export_rib` changed since they were last advertised
+    export_pending: HashSet<RouteKey>,
+    /// Running while advertisements are delayed by the MRAI
+    mrai_timer: Option<Interval>,
+    last_advertisement: Option<Instant>,
+    max_prefix_state: HashMap<AddressType, MaxPrefixState>,
+    /// Automatically restart the peer after being torn down for exceeding the
+    /// max number of prefixes
+    max_prefix_restart_timer: Option<Interval>,
+    /// Running while the peer is held in Idle state to damp peer oscillations
+    idle_hold_timer: Option<Interval>,
+    /// Used to decay the damping penalty of sessions that stayed established
+    established_since: Option<Instant>,
     rng: SmallRng,
+    #[cfg(feature = "bmp")]
+    bmp_exporter: Option<BmpExporter<A>>,
+    /// The session is reported as up to the [BmpExporter]
+    #[cfg(feature = "bmp")]
+    bmp_peer_up: bool,
+    #[cfg(feature = "mrt")]
+    mrt_recorder: Option<MrtRecorder<A>>,
+    #[cfg(feature = "rpki")]
+    route_origin_validator: Option<RouteOriginValidator>,
+    /// Routes received from the peer before the route origin validation,
+    /// retained while a [RouteOriginValidator] is attached to validate them
+    /// again when the validated data changes
+    #[cfg(feature = "rpki")]
+    unvalidated_rib_in: Option<AdjRib>,
+    /// Route flap damping of the routes received from the peer, see
+    /// [PeerConfig::route_flap_damping]
+    damping: Option<RouteFlapDamping<()>>,
+    /// Next hops of the routes in the Adj-RIB-In, see
+    /// [Peer::set_next_hop_resolver]
+    next_hops: Option<NextHopTracker<RouteKey>>,
+    liveness: Option<LivenessMonitor>,
 }
 
 impl<
@@ -587,6 +1186,8 @@ impl<
         policy: P,
         active_connect: C,
     ) -> Self {
+        let rng = SmallRng::seed_from_u64(config.rng_seed);
+        let damping = config.route_flap_damping.map(RouteFlapDamping::new);
         Self {
             peer_key,
             properties,
@@ -598,13 +1199,43 @@ impl<
             tracked_connection: None,
             connect_retry_timer: None,
             stats: PeerStats::default(),
+            update_rate: RateMeter::default(),
+            withdraw_rate: RateMeter::default(),
             active_connect,
             allowed_to_active_connect: false,
             waiting_admin_events: vec![],
-            rng: SmallRng::seed_from_u64(config.rng_seed),
+            pending_events: VecDeque::new(),
+            adj_rib_in: AdjRib::new(),
+            adj_rib_out: AdjRib::new(),
+            export_rib: HashMap::new(),
+            export_pending: HashSet::new(),
+            mrai_timer: None,
+            last_advertisement: None,
+            max_prefix_state: HashMap::new(),
+            max_prefix_restart_timer: None,
+            idle_hold_timer: None,
+            established_since: None,
+            rng,
+            #[cfg(feature = "bmp")]
+            bmp_exporter: None,
+            #[cfg(feature = "bmp")]
+            bmp_peer_up: false,
+            #[cfg(feature = "mrt")]
+            mrt_recorder: None,
+            #[cfg(feature = "rpki")]
+            route_origin_validator: None,
+            #[cfg(feature = "rpki")]
+            unvalidated_rib_in: None,
+            damping,
+            next_hops: None,
+            liveness: None,
         }
     }
 
+    pub const fn peer_key(&self) -> K {
+        self.peer_key
+    }
+
     pub const fn fsm_state(&self) -> FsmState {
         self.fsm_state
     }
@@ -625,29 +1256,949 @@ impl<
         self.connect_retry_timer.as_ref()
     }
 
-    pub const fn stats(&self) -> PeerStats {
-        self.stats
+    pub const fn max_prefix_restart_timer(&self) -> Option<&Interval> {
+        self.max_prefix_restart_timer.as_ref()
+    }
+
+    pub const fn idle_hold_timer(&self) -> Option<&Interval> {
+        self.idle_hold_timer.as_ref()
+    }
+
+    pub fn stats(&self) -> PeerStats<A> {
+        self.peer_stats()
     }
 
     pub const fn config(&self) -> &PeerConfig {
         &self.config
     }
 
-    // Central method for transitioning to make it easier for consistent logging
-    #[inline]
-    fn fsm_transition(&mut self, new_state: FsmState) {
-        if new_state == self.fsm_state {
+    /// Replace the peer configuration without resetting the session.
+    /// Timers pick up the new durations on their next cycle, and connection
+    /// level settings (hold time, keepalive, open delay) are applied to the
+    /// next connection attempt.
+    pub fn update_config(&mut self, config: PeerConfig) {
+        if config == self.config {
+            return;
+        }
+        log::info!(
+            "[{}][{}] Updating peer config to: {config:?}",
+            self.peer_key,
+            self.fsm_state
+        );
+        if config.route_flap_damping != self.config.route_flap_damping {
+            self.update_route_flap_damping(config.route_flap_damping);
+        }
+        self.config = config;
+    }
+
+    pub const fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Replace the peer policy, the new policy is used starting from the next
+    /// connection attempt.
+    pub fn update_policy(&mut self, policy: P) {
+        log::info!(
+            "[{}][{}] Updating peer policy",
+            self.peer_key,
+            self.fsm_state
+        );
+        self.policy = policy;
+    }
+
+    /// Report the session to a BMP exporter. If the session is already
+    /// established, the Peer Up and the current Adj-RIB-In and Adj-RIB-Out
+    /// are reported right away.
+    #[cfg(feature = "bmp")]
+    pub fn set_bmp_exporter(&mut self, exporter: Option<BmpExporter<A>>) {
+        self.bmp_exporter = exporter;
+        self.bmp_peer_up = false;
+        self.report_bmp_session(None);
+        if !self.bmp_peer_up {
+            return;
+        }
+        for (rib, adj_rib) in [
+            (BmpRib::AdjRibInPost, &self.adj_rib_in),
+            (BmpRib::AdjRibOut, &self.adj_rib_out),
+        ] {
+            for address_type in adj_rib.address_types() {
+                for update in adj_rib.updates(address_type, BGP_MAX_MESSAGE_LENGTH) {
+                    self.report_bmp(BmpPeerEvent::RouteMonitoring {
+                        peer_addr: self.properties.peer_addr,
+                        rib,
+                        update,
+                    });
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "bmp")]
+    fn report_bmp(&self, event: BmpPeerEvent<A>) {
+        if let Some(exporter) = self.bmp_exporter.as_ref() {
+            exporter.send(event);
+        }
+    }
+
+    /// Report the session going up or down to the BMP exporter, `event` is
+    /// the BGP event that caused the transition.
+    #[cfg(feature = "bmp")]
+    fn report_bmp_session(&mut self, event: Option<&BgpEvent<A>>) {
+        let established = self.fsm_state == FsmState::Established;
+        if self.bmp_exporter.is_none() || established == self.bmp_peer_up {
+            return;
+        }
+        if established {
+            let opens = self.connection.as_ref().and_then(|connection| {
+                connection
+                    .sent_open()
+                    .cloned()
+                    .zip(connection.received_open().cloned())
+            });
+            if let Some((sent_open, received_open)) = opens {
+                self.bmp_peer_up = true;
+                self.report_bmp(BmpPeerEvent::PeerUp {
+                    peer_addr: self.properties.peer_addr,
+                    sent_open,
+                    received_open,
+                });
+            }
+            return;
+        }
+        self.bmp_peer_up = false;
+        let notification_sent = self
+            .stats
+            .last_notification_sent
+            .as_ref()
+            .filter(|record| {
+                self.stats
+                    .last_up
+                    .is_some_and(|last_up| record.timestamp() >= last_up)
+            })
+            .map(|record| record.notification().clone());
+        let reason = match (event, notification_sent) {
+            (Some(BgpEvent::NotifMsg(notif)), _) => {
+                PeerDownNotificationReason::RemoteSystemClosedNotificationPduFollows(
+                    BgpMessage::Notification(notif.clone()),
+                )
+            }
+            (_, Some(notif)) => {
+                PeerDownNotificationReason::LocalSystemClosedNotificationPduFollows(
+                    BgpMessage::Notification(notif),
+                )
+            }
+            (Some(BgpEvent::TcpConnectionFails), None) => {
+                PeerDownNotificationReason::RemoteSystemClosedNoData
+            }
+            // RFC4271 Section 8.1 event numbers
+            (Some(BgpEvent::ManualStop), None) => {
+                PeerDownNotificationReason::LocalSystemClosedFsmEventFollows(2)
+            }
+            (Some(BgpEvent::AutomaticStop), None) => {
+                PeerDownNotificationReason::LocalSystemClosedFsmEventFollows(8)
+            }
+            (Some(BgpEvent::HoldTimerExpires), None) => {
+                PeerDownNotificationReason::LocalSystemClosedFsmEventFollows(10)
+            }
+            _ => PeerDownNotificationReason::LocalSystemClosedFsmEventFollows(0),
+        };
+        self.report_bmp(BmpPeerEvent::PeerDown {
+            peer_addr: self.properties.peer_addr,
+            reason,
+        });
+    }
+
+    /// Report an update received on the established session, before and after
+    /// the ingress treatment
+    #[cfg(feature = "bmp")]
+    fn report_bmp_update(&self, pre_policy: BgpUpdateMessage, events: &[&ConnectionEvent<A>]) {
+        if !self.bmp_peer_up {
+            return;
+        }
+        let peer_addr = self.properties.peer_addr;
+        let post_policy = events.iter().flat_map(|event| match event {
+            ConnectionEvent::UpdateMsg(update, UpdateTreatment::Normal)
+            | ConnectionEvent::UpdateMsg(update, UpdateTreatment::AttributeDiscard) => {
+                vec![update.clone()]
+            }
+            ConnectionEvent::UpdateMsg(update, UpdateTreatment::TreatAsWithdraw) => {
+                let update_routes = UpdateRoutes::from(update);
+                self.report_bmp(BmpPeerEvent::TreatAsWithdraw {
+                    peer_addr,
+                    prefixes: update_routes.announced().len() + update_routes.withdrawn().len(),
+                });
+                let mut withdrawn: HashMap<AddressType, Vec<RouteKey>> = HashMap::new();
+                for key in update_routes.treat_as_withdraw().withdrawn() {
+                    withdrawn.entry(key.address_type()).or_default().push(*key);
+                }
+                withdrawn
+                    .iter()
+                    .flat_map(|(address_type, keys)| {
+                        withdraw_updates(*address_type, keys, BGP_MAX_MESSAGE_LENGTH)
+                    })
+                    .collect()
+            }
+            _ => vec![],
+        });
+        self.report_bmp(BmpPeerEvent::RouteMonitoring {
+            peer_addr,
+            rib: BmpRib::AdjRibInPre,
+            update: pre_policy,
+        });
+        for update in post_policy {
+            self.report_bmp(BmpPeerEvent::RouteMonitoring {
+                peer_addr,
+                rib: BmpRib::AdjRibInPost,
+                update,
+            });
+        }
+    }
+
+    /// Record the messages received from the peer and the FSM state
+    /// transitions to MRT files
+    #[cfg(feature = "mrt")]
+    pub fn set_mrt_recorder(&mut self, recorder: Option<MrtRecorder<A>>) {
+        self.mrt_recorder = recorder;
+    }
+
+    /// Record a BGP message carried by the connection event
+    #[cfg(feature = "mrt")]
+    fn record_mrt_message(&self, event: &ConnectionEvent<A>) {
+        let recorder = match self.mrt_recorder.as_ref() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        let message = match event {
+            ConnectionEvent::BGPOpen(open) | ConnectionEvent::BGPOpenWithDelayOpenTimer(open) => {
+                BgpMessage::Open(open.clone())
+            }
+            ConnectionEvent::NotifMsg(notif) => BgpMessage::Notification(notif.clone()),
+            ConnectionEvent::KeepAliveMsg => BgpMessage::KeepAlive,
+            ConnectionEvent::UpdateMsg(update, _) => BgpMessage::Update(update.clone()),
+            ConnectionEvent::RouteRefresh(route_refresh) => {
+                BgpMessage::RouteRefresh(route_refresh.clone())
+            }
+            _ => return,
+        };
+        let connection = self.connection.as_ref();
+        let received_open = match &message {
+            BgpMessage::Open(open) => Some(open),
+            _ => connection.and_then(|connection| connection.received_open()),
+        };
+        let sent_open = connection.and_then(|connection| connection.sent_open());
+        recorder.send(MrtPeerEvent::Message {
+            timestamp: Utc::now(),
+            peer_addr: self.properties.peer_addr,
+            peer_asn: received_open
+                .map(|open| open.my_asn4())
+                .unwrap_or(self.properties.peer_asn),
+            local_asn: self.properties.local_asn(),
+            asn4: sent_open
+                .zip(received_open)
+                .is_some_and(|(sent, received)| is_asn4(sent, received)),
+            message,
+        });
+    }
+
+    /// Validate the origin of the routes received from the peer. The routes
+    /// already received are validated again with the new validator, or
+    /// restored as received when the validator is removed, see [crate::rpki].
+    #[cfg(feature = "rpki")]
+    pub fn set_route_origin_validator(&mut self, validator: Option<RouteOriginValidator>) {
+        if validator.is_some() && self.unvalidated_rib_in.is_none() {
+            // Without a validator, the Adj-RIB-In holds the routes as received
+            self.unvalidated_rib_in = Some(self.adj_rib_in.clone());
+        }
+        self.route_origin_validator = validator.map(|mut validator| {
+            validator.mark_changed();
+            validator
+        });
+    }
+
+    #[cfg(feature = "rpki")]
+    pub const fn route_origin_validator(&self) -> Option<&RouteOriginValidator> {
+        self.route_origin_validator.as_ref()
+    }
+
+    /// Apply the route origin validation to an update received from the peer
+    #[cfg(feature = "rpki")]
+    fn origin_validation(&self, event: ConnectionEvent<A>) -> Vec<ConnectionEvent<A>> {
+        let (validator, update, treatment) = match (self.route_origin_validator.as_ref(), event) {
+            (Some(validator), ConnectionEvent::UpdateMsg(update, treatment))
+                if matches!(
+                    treatment,
+                    UpdateTreatment::Normal | UpdateTreatment::AttributeDiscard
+                ) =>
+            {
+                (validator, update, treatment)
+            }
+            (_, event) => return vec![event],
+        };
+        let local_asn = self
+            .properties
+            .confederation_id()
+            .unwrap_or(self.properties.my_asn());
+        let internal = self.properties.is_internal() || self.properties.is_confed_external();
+        let local_role = self.properties.bgp_role().filter(|_| !internal);
+        // A route server doesn't add its AS to the path
+        let neighbor_as = match local_role {
+            Some(BgpRoleValue::RsClient) => None,
+            _ => Some(
+                self.connection
+                    .as_ref()
+                    .and_then(|connection| connection.peer_asn())
+                    .unwrap_or(self.properties.peer_asn()),
+            ),
+        };
+        validator
+            .validate_update(
+                update,
+                local_asn,
+                internal,
+                local_role.map(AspaDirection::from),
+                neighbor_as,
+            )
+            .into_iter()
+            .map(|update| ConnectionEvent::UpdateMsg(update, treatment.clone()))
+            .collect()
+    }
+
+    #[cfg(not(feature = "rpki"))]
+    fn origin_validation(&self, event: ConnectionEvent<A>) -> Vec<ConnectionEvent<A>> {
+        vec![event]
+    }
+
+    /// Wait until the routes received from the peer have to be validated
+    /// again: the validated data changed or the validator was removed
+    #[cfg(feature = "rpki")]
+    fn origin_validation_changed(&self) -> impl Future<Output = OriginValidationChanged> {
+        let validator = self.route_origin_validator.clone();
+        let removed = validator.is_none() && self.unvalidated_rib_in.is_some();
+        async move {
+            match validator {
+                Some(mut validator) => validator.changed().await,
+                None if removed => {}
+                None => std::future::pending().await,
+            }
+            OriginValidationChanged
+        }
+    }
+
+    #[cfg(not(feature = "rpki"))]
+    fn origin_validation_changed(&self) -> impl Future<Output = OriginValidationChanged> {
+        std::future::pending()
+    }
+
+    /// Keep the routes received from the peer before they're validated, see
+    /// [Peer::revalidate_routes]
+    #[cfg(feature = "rpki")]
+    fn retain_unvalidated_routes(&mut self, event: &ConnectionEvent<A>) {
+        let rib = match self.unvalidated_rib_in.as_mut() {
+            Some(rib) => rib,
+            None => return,
+        };
+        match event {
+            ConnectionEvent::UpdateMsg(update, treatment) => match treatment {
+                UpdateTreatment::Normal | UpdateTreatment::AttributeDiscard => {
+                    rib.apply(UpdateRoutes::from(update));
+                }
+                UpdateTreatment::TreatAsWithdraw => {
+                    rib.apply(UpdateRoutes::from(update).treat_as_withdraw());
+                }
+                UpdateTreatment::ResetAddressFamily(afi, safi) => {
+                    if let Some(address_type) = reset_address_type(*afi, *safi) {
+                        rib.clear_address_type(address_type);
+                    }
+                }
+                UpdateTreatment::SessionReset => {}
+            },
+            ConnectionEvent::RouteRefresh(refresh) => match refresh.operation_type() {
+                RouteRefreshSubcode::BeginningOfRouteRefresh => {
+                    rib.mark_stale(refresh.address_type());
+                }
+                RouteRefreshSubcode::EndOfRouteRefresh => {
+                    rib.sweep_stale(refresh.address_type());
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Validate the routes received from the peer again, without asking the
+    /// peer to send them again, see
+    /// [RFC8481](https://datatracker.ietf.org/doc/html/rfc8481#section-4).
+    /// The Adj-RIB-In is updated and the routes whose validation changed are
+    /// reported as [BgpEvent::RevalidatedRoutesChanged].
+    #[cfg(feature = "rpki")]
+    async fn revalidate_routes(&mut self, _: OriginValidationChanged) -> PeerResult<A> {
+        let max_message_length = self.max_message_length();
+        let mut validated = AdjRib::new();
+        if let Some(unvalidated) = self.unvalidated_rib_in.as_ref() {
+            for address_type in unvalidated.address_types() {
+                for update in unvalidated.updates(address_type, max_message_length) {
+                    let event = ConnectionEvent::UpdateMsg(update, UpdateTreatment::Normal);
+                    for event in self.origin_validation(event) {
+                        if let ConnectionEvent::UpdateMsg(update, _) = event {
+                            validated.apply(UpdateRoutes::from(&update));
+                        }
+                    }
+                }
+            }
+        }
+        match self.route_origin_validator.as_mut() {
+            Some(validator) => validator.mark_unchanged(),
+            None => self.unvalidated_rib_in = None,
+        }
+
+        let mut announced = vec![];
+        for address_type in validated.address_types() {
+            for (key, route) in validated.routes(address_type) {
+                if !self
+                    .adj_rib_in
+                    .get(key)
+                    .is_some_and(|current| current.same_path(route))
+                {
+                    announced.push((*key, route.clone()));
+                }
+            }
+        }
+        let withdrawn: Vec<RouteKey> = self
+            .adj_rib_in_keys()
+            .into_iter()
+            .filter(|key| validated.get(key).is_none())
+            .collect();
+        if announced.is_empty() && withdrawn.is_empty() {
+            return Ok(BgpEvent::RouteOriginValidationDataChanged);
+        }
+        log::info!(
+            "[{}][{}] Route origin validation data changed, {} routes announced and {} withdrawn",
+            self.peer_key,
+            self.fsm_state,
+            announced.len(),
+            withdrawn.len()
+        );
+        let changes = UpdateRoutes::new(announced, withdrawn);
+        let address_types: HashSet<AddressType> = changes
+            .announced()
+            .iter()
+            .map(|(key, _)| key.address_type())
+            .collect();
+        self.track_next_hops(&changes);
+        self.adj_rib_in.apply(changes.clone());
+        #[cfg(feature = "bmp")]
+        if self.bmp_peer_up {
+            for update in route_updates(&changes, BGP_MAX_MESSAGE_LENGTH) {
+                self.report_bmp(BmpPeerEvent::RouteMonitoring {
+                    peer_addr: self.properties.peer_addr,
+                    rib: BmpRib::AdjRibInPost,
+                    update,
+                });
+            }
+        }
+        for address_type in address_types {
+            if self.check_max_prefix(address_type).await {
+                return Ok(BgpEvent::RouteOriginValidationDataChanged);
+            }
+        }
+
+        // The routes held back by the route flap damping or the next hop
+        // resolution are left to be reported when they're released
+        let now = Instant::now();
+        let changes = self.withdraw_unreachable_next_hops(changes);
+        let changes = UpdateRoutes::new(
+            changes
+                .announced()
+                .iter()
+                .filter(|(key, _)| !self.is_suppressed(key, now))
+                .cloned()
+                .collect(),
+            changes
+                .withdrawn()
+                .iter()
+                .filter(|key| !self.is_suppressed(key, now))
+                .copied()
+                .collect(),
+        );
+        self.pending_events.extend(
+            route_updates(&changes, max_message_length)
+                .into_iter()
+                .map(BgpEvent::RevalidatedRoutesChanged),
+        );
+        Ok(BgpEvent::RouteOriginValidationDataChanged)
+    }
+
+    #[cfg(not(feature = "rpki"))]
+    async fn revalidate_routes(&mut self, changed: OriginValidationChanged) -> PeerResult<A> {
+        match changed {}
+    }
+
+    /// Enable, disable or reconfigure the route flap damping. The flap history
+    /// is reset and the currently suppressed routes are released.
+    fn update_route_flap_damping(&mut self, config: Option<RouteFlapDampingConfig>) {
+        if let Some(mut damping) = self.damping.take() {
+            let released = self.withdraw_unreachable_next_hops(damping.clear_peer(&()));
+            self.pending_events.extend(
+                route_updates(&released, self.max_message_length())
+                    .into_iter()
+                    .map(BgpEvent::DampedRoutesReused),
+            );
+        }
+        self.damping = config.map(RouteFlapDamping::new);
+    }
+
+    /// Pass the routes received from the peer through the route flap damping.
+    /// Announcements of suppressed routes are held back until the routes are
+    /// reused, and routes that became suppressed are withdrawn.
+    fn route_flap_damping(&mut self, event: ConnectionEvent<A>) -> Vec<ConnectionEvent<A>> {
+        let damping = match self.damping.as_mut() {
+            Some(damping) => damping,
+            None => return vec![event],
+        };
+        let (update, treatment) = match event {
+            ConnectionEvent::UpdateMsg(update, treatment)
+                if matches!(
+                    treatment,
+                    UpdateTreatment::Normal
+                        | UpdateTreatment::AttributeDiscard
+                        | UpdateTreatment::TreatAsWithdraw
+                ) =>
+            {
+                (update, treatment)
+            }
+            event => return vec![event],
+        };
+        let update_routes = match treatment {
+            UpdateTreatment::TreatAsWithdraw => UpdateRoutes::from(&update).treat_as_withdraw(),
+            _ => UpdateRoutes::from(&update),
+        };
+        let damped = damping.apply((), update_routes.clone(), Instant::now());
+        if damped == update_routes {
+            return vec![ConnectionEvent::UpdateMsg(update, treatment)];
+        }
+        log::debug!(
+            "[{}][{}] Route flap damping suppressed {} routes",
+            self.peer_key,
+            self.fsm_state,
+            update_routes.announced().len() - damped.announced().len()
+        );
+        route_updates(&damped, self.max_message_length())
+            .into_iter()
+            .map(|update| ConnectionEvent::UpdateMsg(update, treatment.clone()))
+            .collect()
+    }
+
+    /// Record the withdrawal of the given routes in the flap history
+    fn route_flap_damping_withdraw(&mut self, keys: Vec<RouteKey>) {
+        if let Some(damping) = self.damping.as_mut() {
+            damping.apply((), UpdateRoutes::new(vec![], keys), Instant::now());
+        }
+    }
+
+    /// Release the suppressed routes whose penalty decayed below the reuse
+    /// threshold
+    fn reuse_damped_routes(&mut self) -> PeerResult<A> {
+        let reused = self
+            .damping
+            .as_mut()
+            .map(|damping| damping.reuse(Instant::now()))
+            .unwrap_or_default();
+        for (_, reused) in reused {
+            log::info!(
+                "[{}][{}] Route flap damping released {} routes",
+                self.peer_key,
+                self.fsm_state,
+                reused.announced().len()
+            );
+            let reused = self.withdraw_unreachable_next_hops(reused);
+            self.pending_events.extend(
+                route_updates(&reused, self.max_message_length())
+                    .into_iter()
+                    .map(BgpEvent::DampedRoutesReused),
+            );
+        }
+        Ok(BgpEvent::RouteFlapDampingReuseTimerExpires)
+    }
+
+    /// Damping state of a route received from the peer, `None` if the route
+    /// has no flap history or damping is disabled
+    pub fn damped_route(&self, key: &RouteKey) -> Option<DampedRoute<()>> {
+        self.damping
+            .as_ref()
+            .and_then(|damping| damping.get(&(), key, Instant::now()))
+    }
+
+    /// Routes received from the peer that are currently suppressed
+    pub fn dampened_routes(&self) -> Vec<DampedRoute<()>> {
+        self.damping
+            .as_ref()
+            .map(|damping| damping.dampened_routes(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Routes received from the peer with flap history
+    pub fn flap_statistics(&self) -> Vec<DampedRoute<()>> {
+        self.damping
+            .as_ref()
+            .map(|damping| damping.flap_statistics(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Resolve the next hops of the routes received from the peer. Routes
+    /// whose next hop is unreachable are kept in the Adj-RIB-In, but reported
+    /// as withdrawn until their next hop is reachable again, see
+    /// [crate::nexthop]. The routes already received are resolved with the
+    /// new resolver and the differences are reported as
+    /// [BgpEvent::NextHopRoutesChanged].
+    pub fn set_next_hop_resolver(&mut self, resolver: Option<Arc<dyn NextHopResolver>>) {
+        let unreachable_before = self.unreachable_routes();
+        self.next_hops = resolver.map(|resolver| {
+            let mut next_hops = NextHopTracker::new(resolver);
+            for address_type in self.adj_rib_in.address_types() {
+                for (key, route) in self.adj_rib_in.routes(address_type) {
+                    next_hops.track(*key, route);
+                }
+            }
+            next_hops
+        });
+        let unreachable_after = self.unreachable_routes();
+        let announced = unreachable_before
+            .difference(&unreachable_after)
+            .copied()
+            .collect();
+        let withdrawn = unreachable_after
+            .difference(&unreachable_before)
+            .copied()
+            .collect();
+        self.report_next_hop_changes(announced, withdrawn);
+    }
+
+    /// Status of the next hop of a route received from the peer, `None` if
+    /// there is no [NextHopResolver] or the route has no next hop
+    pub fn next_hop_status(&self, key: &RouteKey) -> Option<NextHopStatus> {
+        self.next_hops
+            .as_ref()
+            .and_then(|next_hops| next_hops.status(key))
+    }
+
+    fn is_next_hop_unreachable(&self, key: &RouteKey) -> bool {
+        self.next_hop_status(key) == Some(NextHopStatus::Unreachable)
+    }
+
+    /// Routes of the Adj-RIB-In whose next hop is unreachable
+    fn unreachable_routes(&self) -> HashSet<RouteKey> {
+        self.adj_rib_in_keys()
+            .into_iter()
+            .filter(|key| self.is_next_hop_unreachable(key))
+            .collect()
+    }
+
+    /// Track the next hops of the routes added to the Adj-RIB-In and stop
+    /// tracking the removed ones
+    fn track_next_hops(&mut self, update_routes: &UpdateRoutes) {
+        if let Some(next_hops) = self.next_hops.as_mut() {
+            for key in update_routes.withdrawn() {
+                next_hops.untrack(key);
+            }
+            for (key, route) in update_routes.announced() {
+                next_hops.track(*key, route);
+            }
+        }
+    }
+
+    fn untrack_next_hops(&mut self, keys: &[RouteKey]) {
+        if let Some(next_hops) = self.next_hops.as_mut() {
+            for key in keys {
+                next_hops.untrack(key);
+            }
+        }
+    }
+
+    /// Turn the announcements of routes with an unreachable next hop into
+    /// withdrawals, they are announced again once the next hop is reachable
+    fn withdraw_unreachable_next_hops(&self, update_routes: UpdateRoutes) -> UpdateRoutes {
+        if !update_routes
+            .announced()
+            .iter()
+            .any(|(key, _)| self.is_next_hop_unreachable(key))
+        {
+            return update_routes;
+        }
+        let mut withdrawn = update_routes.withdrawn().clone();
+        let mut announced = vec![];
+        for (key, route) in update_routes.announced() {
+            if self.is_next_hop_unreachable(key) {
+                withdrawn.push(*key);
+            } else {
+                announced.push((*key, route.clone()));
+            }
+        }
+        UpdateRoutes::new(announced, withdrawn)
+    }
+
+    /// Hold back the received routes whose next hop is unreachable
+    fn next_hop_reachability(&self, event: ConnectionEvent<A>) -> Vec<ConnectionEvent<A>> {
+        let (update, treatment) = match event {
+            ConnectionEvent::UpdateMsg(update, treatment)
+                if self.next_hops.is_some()
+                    && matches!(
+                        treatment,
+                        UpdateTreatment::Normal | UpdateTreatment::AttributeDiscard
+                    ) =>
+            {
+                (update, treatment)
+            }
+            event => return vec![event],
+        };
+        let update_routes = UpdateRoutes::from(&update);
+        let reachable = self.withdraw_unreachable_next_hops(update_routes.clone());
+        if reachable == update_routes {
+            return vec![ConnectionEvent::UpdateMsg(update, treatment)];
+        }
+        log::debug!(
+            "[{}][{}] Holding back {} routes with an unreachable next hop",
+            self.peer_key,
+            self.fsm_state,
+            update_routes.announced().len() - reachable.announced().len()
+        );
+        route_updates(&reachable, self.max_message_length())
+            .into_iter()
+            .map(|update| ConnectionEvent::UpdateMsg(update, treatment.clone()))
+            .collect()
+    }
+
+    /// Report the routes whose next hop resolution changed since the last
+    /// call
+    fn next_hops_changed(&mut self) -> PeerResult<A> {
+        let changes = self
+            .next_hops
+            .as_mut()
+            .map(NextHopTracker::poll_changes)
+            .unwrap_or_default();
+        let mut announced = vec![];
+        let mut withdrawn = vec![];
+        for change in changes {
+            log::info!(
+                "[{}][{}] Next hop {} resolution changed to {:?} for {} routes",
+                self.peer_key,
+                self.fsm_state,
+                change.next_hop(),
+                change.status(),
+                change.keys().len()
+            );
+            match change.status() {
+                NextHopStatus::Unreachable => withdrawn.extend(change.keys()),
+                NextHopStatus::Reachable { .. } => announced.extend(change.keys()),
+            }
+        }
+        self.report_next_hop_changes(announced, withdrawn);
+        Ok(BgpEvent::NextHopResolutionChanged)
+    }
+
+    /// Check if a route received from the peer is suppressed by the route flap
+    /// damping
+    fn is_suppressed(&self, key: &RouteKey, now: Instant) -> bool {
+        self.damping
+            .as_ref()
+            .and_then(|damping| damping.get(&(), key, now))
+            .is_some_and(|route| route.suppressed())
+    }
+
+    /// Report the routes of the Adj-RIB-In whose next hop changed, the routes
+    /// suppressed by the route flap damping are left to be reported when
+    /// they're reused
+    fn report_next_hop_changes(&mut self, announced: Vec<RouteKey>, withdrawn: Vec<RouteKey>) {
+        let now = Instant::now();
+        let announced: Vec<(RouteKey, Route)> = announced
+            .into_iter()
+            .filter(|key| !self.is_suppressed(key, now))
+            .filter_map(|key| self.adj_rib_in.get(&key).map(|route| (key, route.clone())))
+            .collect();
+        let withdrawn: Vec<RouteKey> = withdrawn
+            .into_iter()
+            .filter(|key| !self.is_suppressed(key, now) && self.adj_rib_in.get(key).is_some())
+            .collect();
+        let update_routes = UpdateRoutes::new(announced, withdrawn);
+        self.pending_events.extend(
+            route_updates(&update_routes, self.max_message_length())
+                .into_iter()
+                .map(BgpEvent::NextHopRoutesChanged),
+        );
+    }
+
+    /// Subscribe to a [LivenessDetector] for the peer address. The session is
+    /// torn down when the detector reports the peer as down after being up,
+    /// and automatically started when it reports the peer as up again.
+    pub fn set_liveness_detector(&mut self, detector: Option<Arc<dyn LivenessDetector<A>>>) {
+        self.liveness = detector
+            .map(|detector| LivenessMonitor::new(detector.subscribe(self.properties.peer_addr)));
+    }
+
+    /// Last state reported by the [LivenessDetector], if any
+    pub fn liveness_state(&self) -> Option<LivenessState> {
+        self.liveness.as_ref().map(|liveness| liveness.state())
+    }
+
+    async fn handle_liveness_transition(&mut self, state: LivenessState) -> PeerResult<A> {
+        if state == LivenessState::Up {
+            log::info!(
+                "[{}][{}] Liveness detector reported peer as up, restarting peer",
+                self.peer_key,
+                self.fsm_state
+            );
+            return Ok(self.automatic_start());
+        }
+        log::warn!(
+            "[{}][{}] Liveness detector reported peer as down, tearing down the session",
+            self.peer_key,
+            self.fsm_state
+        );
+        let notif =
+            BgpMessage::Notification(BgpNotificationMessage::CeaseError(CeaseError::BfdDown {
+                value: vec![],
+            }));
+        if let Some(conn) = self.connection.as_mut() {
+            let _ = conn.send(notif.clone()).await;
+        }
+        self.record_notification_sent();
+        if let Some(conn) = self.tracked_connection.as_mut() {
+            let _ = conn.send(notif).await;
+        }
+        self.connection.take();
+        self.tracked_connection.take();
+        self.connect_retry_timer.take();
+        self.stats.connect_retry_counter += 1;
+        self.fsm_transition(FsmState::Idle);
+        Ok(BgpEvent::AutomaticStop)
+    }
+
+    /// Routes received from the peer
+    pub const fn adj_rib_in(&self) -> &AdjRib {
+        &self.adj_rib_in
+    }
+
+    fn adj_rib_in_keys(&self) -> Vec<RouteKey> {
+        self.adj_rib_in
+            .address_types()
+            .into_iter()
+            .flat_map(|address_type| {
+                self.adj_rib_in
+                    .routes(address_type)
+                    .map(|(key, _)| *key)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Routes advertised to the peer
+    pub const fn adj_rib_out(&self) -> &AdjRib {
+        &self.adj_rib_out
+    }
+
+    pub const fn mrai_timer(&self) -> Option<&Interval> {
+        self.mrai_timer.as_ref()
+    }
+
+    // Central method for transitioning to make it easier for consistent logging
+    #[inline]
+    fn fsm_transition(&mut self, new_state: FsmState) {
+        if new_state == self.fsm_state {
+            return;
+        }
+        let before = self.fsm_state;
+        self.fsm_state = new_state;
+        log::info!(
+            "[{}][{}] FSM state transitions from {} to {}",
+            self.peer_key,
+            self.fsm_state,
+            before,
+            new_state
+        );
+        if self.stats.fsm_history.len() == FSM_HISTORY_LEN {
+            self.stats.fsm_history.pop_front();
+        }
+        self.stats
+            .fsm_history
+            .push_back(FsmTransition::new(before, new_state));
+        #[cfg(feature = "mrt")]
+        if let Some(recorder) = self.mrt_recorder.as_ref() {
+            recorder.send(MrtPeerEvent::StateChange {
+                timestamp: Utc::now(),
+                peer_addr: self.properties.peer_addr,
+                peer_asn: self.properties.peer_asn,
+                local_asn: self.properties.local_asn(),
+                old_state: before,
+                new_state,
+            });
+        }
+        self.stats.fsm_transitions += 1;
+        if before == FsmState::Established {
+            // Routes received from the peer are implicitly withdrawn
+            let received = self.adj_rib_in_keys();
+            self.untrack_next_hops(&received);
+            self.route_flap_damping_withdraw(received);
+            self.adj_rib_in.clear();
+            #[cfg(feature = "rpki")]
+            if let Some(rib) = self.unvalidated_rib_in.as_mut() {
+                rib.clear();
+            }
+            self.adj_rib_out.clear();
+            self.mrai_timer.take();
+            self.last_advertisement.take();
+            self.max_prefix_state.clear();
+            self.stats.last_down = Some(Utc::now());
+        }
+        if new_state == FsmState::Established {
+            self.stats.established_transitions += 1;
+            self.stats.last_up = Some(Utc::now());
+            // The exported routes are advertised after the transition, see [Peer::run]
+            self.export_pending.extend(self.export_rib.keys().copied());
+        }
+        #[cfg(feature = "metrics")]
+        crate::metrics::metrics().fsm_transition(&self.peer_key.to_string(), before, new_state);
+        if new_state == FsmState::Idle && self.peer_state == PeerState::AdminUp {
+            self.damp_peer_oscillation();
+        }
+        if new_state == FsmState::Established {
+            self.established_since = Some(Instant::now());
+        } else {
+            self.established_since = None;
+        }
+    }
+
+    /// Damping penalty after accounting for the time the current session
+    /// has been established
+    fn damp_penalty(&self) -> u32 {
+        let decay = self.config.idle_hold_decay_duration();
+        match self.established_since {
+            Some(since) if !decay.is_zero() => {
+                let decayed = since.elapsed().as_secs() / decay.as_secs();
+                self.stats
+                    .damp_penalty
+                    .saturating_sub(decayed.try_into().unwrap_or(u32::MAX))
+            }
+            Some(_) => 0,
+            None => self.stats.damp_penalty,
+        }
+    }
+
+    /// Hold the peer in Idle state for the IdleHoldTime after a session
+    /// failure when DampPeerOscillations is enabled
+    fn damp_peer_oscillation(&mut self) {
+        if !self.config.damp_peer_oscillations() {
             return;
         }
-        let before = self.fsm_state;
-        self.fsm_state = new_state;
+        self.stats.damp_penalty = self.damp_penalty().saturating_add(1);
+        let idle_hold_time = self.config.idle_hold_time(self.stats.damp_penalty);
         log::info!(
-            "[{}][{}] FSM state transitions from {} to {}",
+            "[{}][{}] Damping peer oscillation with penalty {}, holding in Idle state for {idle_hold_time:?}",
             self.peer_key,
             self.fsm_state,
-            before,
-            new_state
+            self.stats.damp_penalty
         );
+        let mut interval = tokio::time::interval(idle_hold_time);
+        interval.reset();
+        self.idle_hold_timer.replace(interval);
     }
     fn add_connection(&mut self, connection: Connection<A, I, D>) {
         if self.connection.is_some() {
@@ -688,7 +2239,8 @@ impl<
     ) -> Result<Connection<A, I, D>, FsmStateError<A>> {
         let codec = D::new(self);
         let framed = Framed::new(stream, codec);
-        let connection = Connection::new(
+        #[allow(unused_mut)]
+        let mut connection = Connection::new(
             &self.properties,
             peer_addr,
             connection_type,
@@ -696,6 +2248,8 @@ impl<
             framed,
             jitter,
         );
+        #[cfg(feature = "metrics")]
+        connection.set_metrics_peer(self.peer_key.to_string());
         Ok(connection)
     }
 
@@ -706,6 +2260,7 @@ impl<
         tcp_stream: I,
     ) -> Result<Option<BgpEvent<A>>, FsmStateError<A>> {
         if self.peer_state == PeerState::AdminDown
+            || self.idle_hold_timer.is_some()
             || (self.connection.is_some() && self.tracked_connection.is_some())
             || (self.fsm_state == FsmState::Established
                 && !self.config.collision_detect_established_state)
@@ -758,14 +2313,475 @@ impl<
                 self.tracked_connection.take();
             }
         }
+        if self.fsm_state == FsmState::Established {
+            if let BgpMessage::Update(update) = &msg {
+                self.adj_rib_out.apply(UpdateRoutes::from(update));
+                #[cfg(feature = "bmp")]
+                if self.bmp_peer_up {
+                    self.report_bmp(BmpPeerEvent::RouteMonitoring {
+                        peer_addr: self.properties.peer_addr,
+                        rib: BmpRib::AdjRibOut,
+                        update: update.clone(),
+                    });
+                }
+            }
+        }
         if let Some(connection) = self.connection.as_mut() {
             connection.send(msg).await?;
         }
         Ok(())
     }
 
-    pub fn peer_stats(&self) -> PeerStats {
-        self.stats
+    /// Update the routes exported to the peer. Withdrawals are sent right
+    /// away, while announcements are delayed by the MRAI configured in
+    /// [ExportConfig::mrai]. Routes are kept when the session goes down and
+    /// advertised again once it's established.
+    pub async fn export_routes(
+        &mut self,
+        announced: Vec<ExportRoute>,
+        withdrawn: Vec<RouteKey>,
+    ) -> Result<(), FsmStateError<A>> {
+        for key in withdrawn {
+            if self.export_rib.remove(&key).is_some() {
+                self.export_pending.insert(key);
+            }
+        }
+        for route in announced {
+            self.export_pending.insert(*route.key());
+            self.export_rib.insert(*route.key(), route);
+        }
+        self.advertise_exported_routes().await
+    }
+
+    /// Send the pending changes of the exported routes. Announcements are
+    /// held back while the MRAI is running.
+    async fn advertise_exported_routes(&mut self) -> Result<(), FsmStateError<A>> {
+        if self.fsm_state != FsmState::Established || self.export_pending.is_empty() {
+            return Ok(());
+        }
+        let mrai = self.config.export().mrai();
+        let next_advertisement = self
+            .last_advertisement
+            .map(|last| last + mrai)
+            .filter(|next| !mrai.is_zero() && *next > Instant::now());
+        let mut announced = AdjRib::new();
+        let mut withdrawn: HashMap<AddressType, Vec<RouteKey>> = HashMap::new();
+        let mut pending = HashSet::new();
+        for key in self.export_pending.drain() {
+            let exported = self.export_rib.get(&key).and_then(|export| {
+                export_route(
+                    &self.properties,
+                    self.config.export(),
+                    &key,
+                    export.route(),
+                    export.source(),
+                )
+            });
+            match exported {
+                None => {
+                    if self.adj_rib_out.get(&key).is_some() {
+                        withdrawn.entry(key.address_type()).or_default().push(key);
+                    }
+                }
+                Some(route) => {
+                    if next_advertisement.is_some() {
+                        pending.insert(key);
+                    } else if self.adj_rib_out.get(&key) != Some(&route) {
+                        announced.insert(key, route);
+                    }
+                }
+            }
+        }
+        self.export_pending = pending;
+        if let Some(next_advertisement) = next_advertisement {
+            if !self.export_pending.is_empty() && self.mrai_timer.is_none() {
+                self.mrai_timer
+                    .replace(tokio::time::interval_at(next_advertisement, mrai));
+            }
+        }
+
+        let max_message_length = self.max_message_length();
+        let mut updates = vec![];
+        for (address_type, keys) in &withdrawn {
+            updates.extend(withdraw_updates(*address_type, keys, max_message_length));
+        }
+        let advertised = !announced.is_empty();
+        for address_type in announced.address_types() {
+            updates.extend(announced.updates(address_type, max_message_length));
+        }
+        if updates.is_empty() {
+            return Ok(());
+        }
+        log::debug!(
+            "[{}][{}] Exporting {} updates",
+            self.peer_key,
+            self.fsm_state,
+            updates.len()
+        );
+        if advertised {
+            self.last_advertisement = Some(Instant::now());
+        }
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        for update in updates {
+            self.adj_rib_out.apply(UpdateRoutes::from(&update));
+            #[cfg(feature = "bmp")]
+            if self.bmp_peer_up {
+                if let Some(exporter) = self.bmp_exporter.as_ref() {
+                    exporter.send(BmpPeerEvent::RouteMonitoring {
+                        peer_addr: self.properties.peer_addr,
+                        rib: BmpRib::AdjRibOut,
+                        update: update.clone(),
+                    });
+                }
+            }
+            connection.send(BgpMessage::Update(update)).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a route refresh request to the peer for the given address type.
+    /// The request is ignored if the session is not established or the peer
+    /// didn't advertise the route refresh capability.
+    pub async fn request_route_refresh(
+        &mut self,
+        address_type: AddressType,
+    ) -> Result<(), FsmStateError<A>> {
+        if self.fsm_state != FsmState::Established {
+            log::warn!(
+                "[{}][{}] Ignoring route refresh request for {address_type:?}, session is not established",
+                self.peer_key,
+                self.fsm_state
+            );
+            return Ok(());
+        }
+        let supported = self
+            .main_connection_received_capabilities()
+            .map(|caps| {
+                caps.iter().any(|cap| {
+                    matches!(
+                        cap,
+                        BgpCapability::RouteRefresh | BgpCapability::EnhancedRouteRefresh
+                    )
+                })
+            })
+            .unwrap_or(false);
+        if !supported {
+            log::warn!(
+                "[{}][{}] Ignoring route refresh request for {address_type:?}, peer didn't advertise route refresh capability",
+                self.peer_key,
+                self.fsm_state
+            );
+            return Ok(());
+        }
+        if let Some(connection) = self.connection.as_mut() {
+            connection
+                .send(BgpMessage::RouteRefresh(BgpRouteRefreshMessage::new(
+                    address_type,
+                    RouteRefreshSubcode::NormalRequest,
+                )))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Check if Enhanced Route Refresh capability is advertised by both ends
+    /// of the main connection
+    fn is_enhanced_route_refresh(&self) -> bool {
+        let has_cap = |caps: Option<&Vec<BgpCapability>>| {
+            caps.map(|caps| caps.contains(&BgpCapability::EnhancedRouteRefresh))
+                .unwrap_or(false)
+        };
+        self.connection.as_ref().is_some_and(|conn| {
+            has_cap(conn.sent_capabilities()) && has_cap(conn.received_capabilities())
+        })
+    }
+
+    /// Max length of the BGP messages on the main connection,
+    /// [BGP_EXTENDED_MAX_MESSAGE_LENGTH] once the Extended Message capability
+    /// is advertised by both ends.
+    fn max_message_length(&self) -> u16 {
+        let has_cap = |caps: Option<&Vec<BgpCapability>>| {
+            caps.map(|caps| caps.contains(&BgpCapability::ExtendedMessage))
+                .unwrap_or(false)
+        };
+        let extended = self.connection.as_ref().is_some_and(|conn| {
+            has_cap(conn.sent_capabilities()) && has_cap(conn.received_capabilities())
+        });
+        if extended {
+            BGP_EXTENDED_MAX_MESSAGE_LENGTH
+        } else {
+            BGP_MAX_MESSAGE_LENGTH
+        }
+    }
+
+    /// Update the Adj-RIB-In and handle route refresh requests received on an
+    /// established session.
+    async fn handle_established_event(
+        &mut self,
+        event: &ConnectionEvent<A>,
+    ) -> Result<(), FsmStateError<A>> {
+        match event {
+            ConnectionEvent::UpdateMsg(update, treatment) => match treatment {
+                UpdateTreatment::Normal | UpdateTreatment::AttributeDiscard => {
+                    let update_routes = UpdateRoutes::from(update);
+                    self.count_update_routes(&update_routes);
+                    let address_types: HashSet<AddressType> = update_routes
+                        .announced()
+                        .iter()
+                        .map(|(key, _)| key.address_type())
+                        .collect();
+                    self.track_next_hops(&update_routes);
+                    self.adj_rib_in.apply(update_routes);
+                    for address_type in address_types {
+                        if self.check_max_prefix(address_type).await {
+                            break;
+                        }
+                    }
+                }
+                UpdateTreatment::TreatAsWithdraw => {
+                    let update_routes = UpdateRoutes::from(update).treat_as_withdraw();
+                    self.count_update_routes(&update_routes);
+                    self.track_next_hops(&update_routes);
+                    self.adj_rib_in.apply(update_routes);
+                }
+                UpdateTreatment::ResetAddressFamily(afi, safi) => {
+                    if let Some(address_type) = reset_address_type(*afi, *safi) {
+                        let keys: Vec<RouteKey> = self
+                            .adj_rib_in
+                            .routes(address_type)
+                            .map(|(key, _)| *key)
+                            .collect();
+                        self.untrack_next_hops(&keys);
+                        self.adj_rib_in.clear_address_type(address_type);
+                    }
+                }
+                UpdateTreatment::SessionReset => {}
+            },
+            ConnectionEvent::RouteRefresh(refresh) => {
+                let address_type = refresh.address_type();
+                match refresh.operation_type() {
+                    RouteRefreshSubcode::NormalRequest => {
+                        self.readvertise_adj_rib_out(address_type).await?;
+                    }
+                    RouteRefreshSubcode::BeginningOfRouteRefresh => {
+                        self.adj_rib_in.mark_stale(address_type);
+                    }
+                    RouteRefreshSubcode::EndOfRouteRefresh => {
+                        let swept = self.adj_rib_in.sweep_stale(address_type);
+                        if !swept.is_empty() {
+                            log::info!(
+                                "[{}][{}] Purged {} stale routes for {address_type:?} after End of Route Refresh",
+                                self.peer_key,
+                                self.fsm_state,
+                                swept.len()
+                            );
+                        }
+                        self.untrack_next_hops(&swept);
+                        self.route_flap_damping_withdraw(swept.clone());
+                        for update in
+                            withdraw_updates(address_type, &swept, self.max_message_length())
+                        {
+                            self.pending_events
+                                .push_back(BgpEvent::StaleRoutesWithdrawn(update));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Update the received prefix counters and rates
+    fn count_update_routes(&mut self, update_routes: &UpdateRoutes) {
+        let now = Instant::now();
+        self.update_rate.record(now, 1);
+        self.withdraw_rate
+            .record(now, update_routes.withdrawn().len() as u64);
+        for (key, _) in update_routes.announced() {
+            self.stats
+                .prefixes
+                .entry(key.address_type())
+                .or_default()
+                .add_received(1);
+        }
+        for key in update_routes.withdrawn() {
+            self.stats
+                .prefixes
+                .entry(key.address_type())
+                .or_default()
+                .add_withdrawn(1);
+        }
+    }
+
+    /// Enforce the [MaxPrefixConfig] configured for the given address type.
+    /// Returns `true` if the session has been torn down.
+    async fn check_max_prefix(&mut self, address_type: AddressType) -> bool {
+        let max_prefix = match self.config.max_prefix(address_type) {
+            Some(max_prefix) => max_prefix,
+            None => return false,
+        };
+        let count = self.adj_rib_in.len(address_type) as u64;
+        let state = if count > max_prefix.limit() as u64 {
+            MaxPrefixState::Exceeded
+        } else if count >= max_prefix.warning_limit() {
+            MaxPrefixState::Warning
+        } else {
+            MaxPrefixState::Normal
+        };
+        let before = self
+            .max_prefix_state
+            .insert(address_type, state)
+            .unwrap_or(MaxPrefixState::Normal);
+        if state == before {
+            return state == MaxPrefixState::Exceeded && !max_prefix.warning_only();
+        }
+        match state {
+            MaxPrefixState::Normal => {}
+            MaxPrefixState::Warning => {
+                log::warn!(
+                    "[{}][{}] Received {count} prefixes for {address_type:?}, reached {}% of the limit {}",
+                    self.peer_key,
+                    self.fsm_state,
+                    max_prefix.warning_threshold(),
+                    max_prefix.limit()
+                );
+            }
+            MaxPrefixState::Exceeded => {
+                log::warn!(
+                    "[{}][{}] Received {count} prefixes for {address_type:?}, exceeding the limit {}",
+                    self.peer_key,
+                    self.fsm_state,
+                    max_prefix.limit()
+                );
+            }
+        }
+        if state != MaxPrefixState::Exceeded || max_prefix.warning_only() {
+            return false;
+        }
+        // RFC4486: the data field contains AFI (2 octets), SAFI (1 octet) and the
+        // prefix upper bound (4 octets)
+        let mut value = Vec::with_capacity(7);
+        value.extend(u16::from(address_type.address_family()).to_be_bytes());
+        value.push(address_type.subsequent_address_family().into());
+        value.extend(max_prefix.limit().to_be_bytes());
+        let notif =
+            BgpNotificationMessage::CeaseError(CeaseError::MaximumNumberOfPrefixesReached {
+                value,
+            });
+        if let Some(conn) = self.connection.as_mut() {
+            let _ = conn.send(BgpMessage::Notification(notif)).await;
+        }
+        self.record_notification_sent();
+        self.connection.take();
+        self.tracked_connection.take();
+        self.connect_retry_timer.take();
+        self.stats.connect_retry_counter += 1;
+        self.fsm_transition(FsmState::Idle);
+        // Restarting is controlled by the max prefix config rather than damping
+        self.idle_hold_timer.take();
+        if let Some(restart_interval) = max_prefix.restart_interval() {
+            let mut interval = tokio::time::interval(restart_interval);
+            interval.reset();
+            self.max_prefix_restart_timer.replace(interval);
+        }
+        self.pending_events.push_back(BgpEvent::AutomaticStop);
+        true
+    }
+
+    /// Re-advertise the content of Adj-RIB-Out for the given address type as
+    /// a response to a route refresh request. When Enhanced Route Refresh is
+    /// negotiated, the updates are demarcated with BoRR and EoRR messages.
+    async fn readvertise_adj_rib_out(
+        &mut self,
+        address_type: AddressType,
+    ) -> Result<(), FsmStateError<A>> {
+        let enhanced = self.is_enhanced_route_refresh();
+        let updates = self
+            .adj_rib_out
+            .updates(address_type, self.max_message_length());
+        log::info!(
+            "[{}][{}] Re-advertising {} updates for {address_type:?} after route refresh request",
+            self.peer_key,
+            self.fsm_state,
+            updates.len()
+        );
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        if enhanced {
+            connection
+                .send(BgpMessage::RouteRefresh(BgpRouteRefreshMessage::new(
+                    address_type,
+                    RouteRefreshSubcode::BeginningOfRouteRefresh,
+                )))
+                .await?;
+        }
+        for update in updates {
+            connection.send(BgpMessage::Update(update)).await?;
+        }
+        if enhanced {
+            connection
+                .send(BgpMessage::RouteRefresh(BgpRouteRefreshMessage::new(
+                    address_type,
+                    RouteRefreshSubcode::EndOfRouteRefresh,
+                )))
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn peer_stats(&self) -> PeerStats<A> {
+        let mut stats = self.stats.clone();
+        stats.fsm_state = self.fsm_state;
+        stats.damp_penalty = self.damp_penalty();
+        if self.fsm_state == FsmState::Established {
+            stats.uptime = self
+                .stats
+                .last_up
+                .and_then(|last_up| (Utc::now() - last_up).to_std().ok());
+        }
+        for address_type in self.adj_rib_in.address_types() {
+            stats
+                .prefixes
+                .entry(address_type)
+                .or_default()
+                .set_accepted(self.adj_rib_in.len(address_type) as u64);
+        }
+        for address_type in self.adj_rib_out.address_types() {
+            stats
+                .prefixes
+                .entry(address_type)
+                .or_default()
+                .set_advertised(self.adj_rib_out.len(address_type) as u64);
+        }
+        let now = Instant::now();
+        stats.update_rate = self.update_rate.rate(now);
+        stats.withdraw_rate = self.withdraw_rate.rate(now);
+        stats
+    }
+
+    /// Keep track of the last notification sent on the main connection
+    fn record_notification_sent(&mut self) {
+        let notif = self
+            .connection
+            .as_mut()
+            .and_then(|conn| conn.take_last_notification_sent());
+        if let Some(notif) = notif {
+            self.stats.last_notification_sent = Some(NotificationRecord::new(notif));
+        }
+    }
+
+    /// Attach the BGP event to the FSM transitions it triggered
+    fn set_transitions_event(&mut self, transitions_before: u64, event: &BgpEvent<A>) {
+        let count = (self.stats.fsm_transitions - transitions_before) as usize;
+        for transition in self.stats.fsm_history.iter_mut().rev().take(count) {
+            transition.set_event(event.clone());
+        }
     }
 
     pub fn waiting_admin_events(&self) -> &Vec<PeerAdminEvents<A, I>> {
@@ -807,6 +2823,8 @@ impl<
     async fn shutdown(&mut self) {
         log::info!("[{}][{}] Shutting down peer", self.peer_key, self.fsm_state);
         self.connect_retry_timer.take();
+        self.max_prefix_restart_timer.take();
+        self.idle_hold_timer.take();
         self.peer_state = PeerState::AdminDown;
         self.fsm_transition(FsmState::Idle);
         // Dropping connections
@@ -819,6 +2837,7 @@ impl<
                 ))
                 .await;
         }
+        self.record_notification_sent();
         self.connection.take();
         if let Some(conn) = self.tracked_connection.as_mut() {
             let _ = conn
@@ -835,6 +2854,7 @@ impl<
     fn start(&mut self) {
         self.peer_state = PeerState::AdminUp;
         self.stats.connect_retry_counter = 0;
+        self.max_prefix_restart_timer.take();
         if self.fsm_state != FsmState::Idle {
             // Start events are ignored in already started peer
             return;
@@ -920,6 +2940,8 @@ impl<
         &mut self,
         event: ConnectionEvent<A>,
     ) -> Result<BgpEvent<A>, FsmStateError<A>> {
+        #[cfg(feature = "mrt")]
+        self.record_mrt_message(&event);
         let conn = match self.connection.as_mut() {
             Some(conn) => conn,
             None => {
@@ -937,6 +2959,12 @@ impl<
             .await;
         let conn_state_before = conn.state();
         let event = conn.handle_event(&mut self.policy, event).await?;
+        if let Some(notif) = conn.take_last_notification_sent() {
+            self.stats.last_notification_sent = Some(NotificationRecord::new(notif));
+        }
+        if let ConnectionEvent::NotifMsg(notif) = &event {
+            self.stats.last_notification_received = Some(NotificationRecord::new(notif.clone()));
+        }
         let event = self
             .policy
             .post_handle_connection_event_hook(event, Some(conn))
@@ -1314,8 +3342,133 @@ impl<
                 ));
             }
         }
+        let event = if conn_state_before == ConnectionState::Established
+            && conn_state_after == ConnectionState::Established
+        {
+            #[cfg(feature = "bmp")]
+            let pre_policy = match &event {
+                ConnectionEvent::UpdateMsg(update, _) if self.bmp_peer_up => Some(update.clone()),
+                _ => None,
+            };
+            let event = self.ingress_treatment(event);
+            #[cfg(feature = "rpki")]
+            self.retain_unvalidated_routes(&event);
+            let mut events = self.origin_validation(event).into_iter();
+            // Origin validation could split the update, the additional updates are
+            // reported in the next calls to [Peer::run]
+            let event = events.next().unwrap_or(ConnectionEvent::UpdateMsg(
+                BgpUpdateMessage::new(vec![], vec![], vec![]),
+                UpdateTreatment::Normal,
+            ));
+            let additional: Vec<ConnectionEvent<A>> = events.collect();
+            self.handle_established_event(&event).await?;
+            for event in &additional {
+                self.handle_established_event(event).await?;
+            }
+            #[cfg(feature = "bmp")]
+            if let Some(pre_policy) = pre_policy {
+                let post_policy: Vec<&ConnectionEvent<A>> =
+                    std::iter::once(&event).chain(additional.iter()).collect();
+                self.report_bmp_update(pre_policy, &post_policy);
+            }
+            // Damping and next hop resolution sit between the Adj-RIB-In and the
+            // consumers of the received routes, the routes held back by them are
+            // still kept in the Adj-RIB-In
+            let mut events = std::iter::once(event)
+                .chain(additional)
+                .flat_map(|event| self.route_flap_damping(event))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|event| self.next_hop_reachability(event))
+                .collect::<Vec<_>>()
+                .into_iter();
+            let event = events.next().unwrap_or(ConnectionEvent::UpdateMsg(
+                BgpUpdateMessage::new(vec![], vec![], vec![]),
+                UpdateTreatment::Normal,
+            ));
+            self.pending_events.extend(events.map(BgpEvent::from));
+            event
+        } else {
+            event
+        };
         Ok(event.into())
     }
+
+    /// Apply the ingress procedures to the routes received from the peer:
+    /// * RFC4456: routes received from an internal peer with the local BGP
+    ///   Identifier as `ORIGINATOR_ID` or the local cluster ID in the
+    ///   `CLUSTER_LIST` are ignored by treating them as withdrawn.
+    /// * RFC9234: route leaks detected by the OTC ingress procedure are treated
+    ///   as withdrawn, and the OTC attribute is added when required.
+    fn ingress_treatment(&self, event: ConnectionEvent<A>) -> ConnectionEvent<A> {
+        let (update, treatment) = match event {
+            ConnectionEvent::UpdateMsg(update, treatment)
+                if matches!(
+                    treatment,
+                    UpdateTreatment::Normal | UpdateTreatment::AttributeDiscard
+                ) =>
+            {
+                (update, treatment)
+            }
+            event => return event,
+        };
+        if self.properties.is_internal()
+            && is_reflection_loop(
+                update.path_attributes(),
+                self.properties.my_bgp_id(),
+                self.properties.cluster_id(),
+            )
+        {
+            log::debug!(
+                "[{}][{}] Route reflection loop detected, treating update as withdraw",
+                self.peer_key,
+                self.fsm_state
+            );
+            return ConnectionEvent::UpdateMsg(update, UpdateTreatment::TreatAsWithdraw);
+        }
+        if !self.properties.is_internal()
+            && !self.properties.is_confed_external()
+            && has_confed_segments(update.path_attributes())
+        {
+            log::debug!(
+                "[{}][{}] AS_PATH with confederation segments received from a peer outside the confederation, treating update as withdraw",
+                self.peer_key,
+                self.fsm_state
+            );
+            return ConnectionEvent::UpdateMsg(update, UpdateTreatment::TreatAsWithdraw);
+        }
+        let has_reachable_nlri = !update.nlri().is_empty()
+            || update
+                .path_attributes()
+                .iter()
+                .any(|attr| matches!(attr.value(), PathAttributeValue::MpReach(_)));
+        if let Some(role) = self.properties.bgp_role().filter(|_| has_reachable_nlri) {
+            let peer_asn = self
+                .connection
+                .as_ref()
+                .and_then(|connection| connection.peer_asn())
+                .unwrap_or(self.properties.peer_asn());
+            let mut attributes = update.path_attributes().clone();
+            if !otc_ingress(role, peer_asn, &mut attributes) {
+                log::debug!(
+                    "[{}][{}] Route leak detected by the OTC ingress procedure, treating update as withdraw",
+                    self.peer_key,
+                    self.fsm_state
+                );
+                return ConnectionEvent::UpdateMsg(update, UpdateTreatment::TreatAsWithdraw);
+            }
+            if attributes.len() != update.path_attributes().len() {
+                let update = BgpUpdateMessage::new(
+                    update.withdraw_routes().clone(),
+                    attributes,
+                    update.nlri().clone(),
+                );
+                return ConnectionEvent::UpdateMsg(update, treatment);
+            }
+        }
+        ConnectionEvent::UpdateMsg(update, treatment)
+    }
+
     async fn connect(
         peer_key: K,
         peer_addr: A,
@@ -1450,14 +3603,19 @@ impl<
         // collision detection event.
         loop {
             let event = tokio::select! {
-                event = Self::get_connection_event(&mut connection) => {
+                // The tracked connection is polled first, so a collision is detected as
+                // soon as its OPEN message is received rather than depending on which
+                // connection is ready first. It can't starve the main connection, since
+                // it's no longer polled once in OpenConfirm.
+                biased;
+                _ = Self::get_tracked_connection_event(fsm_state, policy, &mut tracked_connection) => {
                     let check = Self::check_connection_collision(
                         my_bgp_id,
                         &mut connection,
                         &mut tracked_connection);
                     match check {
                         Some(CollisionCheckRet::DropMain) => Some(ConnectionNextEvent::DropMain),
-                        Some(CollisionCheckRet::DropTracked) => Some(ConnectionNextEvent::DropTracked),
+                        Some(CollisionCheckRet::DropTracked) => Some(ConnectionNextEvent::DropTracked(None)),
                         Some(CollisionCheckRet::InvalidTrackedBgpId(peer_id)) => {
                             if let Some(tracked) = tracked_connection.take() {
                                 let _ = tracked.send(
@@ -1469,18 +3627,18 @@ impl<
                             None
                         },
                         None => {
-                             Some(ConnectionNextEvent::Event(event))
+                             None
                         }
                     }
                 }
-                _ = Self::get_tracked_connection_event(fsm_state, policy, &mut tracked_connection) => {
+                event = Self::get_connection_event(&mut connection) => {
                     let check = Self::check_connection_collision(
                         my_bgp_id,
                         &mut connection,
                         &mut tracked_connection);
                     match check {
                         Some(CollisionCheckRet::DropMain) => Some(ConnectionNextEvent::DropMain),
-                        Some(CollisionCheckRet::DropTracked) => Some(ConnectionNextEvent::DropTracked),
+                        Some(CollisionCheckRet::DropTracked) => Some(ConnectionNextEvent::DropTracked(Some(event))),
                         Some(CollisionCheckRet::InvalidTrackedBgpId(peer_id)) => {
                             if let Some(tracked) = tracked_connection.take() {
                                 let _ = tracked.send(
@@ -1492,7 +3650,7 @@ impl<
                             None
                         },
                         None => {
-                             None
+                             Some(ConnectionNextEvent::Event(event))
                         }
                     }
                 }
@@ -1527,7 +3685,7 @@ impl<
 
                 Ok(BgpEvent::OpenCollisionDump)
             }
-            ConnectionNextEvent::DropTracked => {
+            ConnectionNextEvent::DropTracked(event) => {
                 if let Some(mut tracked) = self.tracked_connection.take() {
                     log::info!(
                         "[{}][{}] BGP Collision detection dropping tracked connection: {}",
@@ -1543,20 +3701,61 @@ impl<
                         ))
                         .await;
                 }
-                Ok(BgpEvent::OpenCollisionDump)
+                match event {
+                    Some(event) => self.handle_connection_event(event).await,
+                    None => Ok(BgpEvent::OpenCollisionDump),
+                }
             }
             ConnectionNextEvent::Event(event) => self.handle_connection_event(event).await,
         }
     }
 
+    fn automatic_start(&mut self) -> BgpEvent<A> {
+        self.start();
+        let damp = self.config.damp_peer_oscillations;
+        if self.config.passive_tcp_establishment {
+            let mut interval = tokio::time::interval(self.config.connect_retry_duration());
+            interval.reset();
+            self.connect_retry_timer.replace(interval);
+            if damp {
+                BgpEvent::AutomaticStartWithDampPeerOscillationsPassiveTcp
+            } else {
+                BgpEvent::AutomaticStartWithPassiveTcp
+            }
+        } else if damp {
+            BgpEvent::AutomaticStartWithDampPeerOscillations
+        } else {
+            BgpEvent::AutomaticStart
+        }
+    }
+
     pub async fn run(&mut self) -> PeerResult<A> {
-        // First check if there's any pending admin event to handle
+        let transitions_before = self.stats.fsm_transitions;
+        let result = self.run_fsm().await;
+        #[cfg(feature = "bmp")]
+        self.report_bmp_session(result.as_ref().ok());
+        if let Ok(event) = &result {
+            self.set_transitions_event(transitions_before, event);
+            self.advertise_exported_routes().await?;
+        }
+        result
+    }
+
+    async fn run_fsm(&mut self) -> PeerResult<A> {
+        // Report events generated while handling previous events
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(event);
+        }
+        // Check if there's any pending admin event to handle
         if let Some(admin_event) = self.waiting_admin_events.pop() {
             let bgp_event = match admin_event {
                 PeerAdminEvents::ManualStart => {
                     if self.fsm_state != FsmState::Idle {
                         None
                     } else {
+                        // Operator intervention overrides peer oscillation damping
+                        self.idle_hold_timer.take();
+                        self.stats.damp_penalty = 0;
                         self.start();
                         if self.config.passive_tcp_establishment {
                             let mut interval =
@@ -1572,22 +3771,15 @@ impl<
                 PeerAdminEvents::ManualStop => {
                     self.shutdown().await;
                     self.stats.connect_retry_counter = 0;
+                    self.stats.damp_penalty = 0;
                     Some(BgpEvent::ManualStop)
                 }
                 PeerAdminEvents::AutomaticStart => {
-                    if self.fsm_state != FsmState::Idle {
+                    // Automatic start events are ignored while the IdleHoldTimer is running
+                    if self.fsm_state != FsmState::Idle || self.idle_hold_timer.is_some() {
                         None
                     } else {
-                        self.start();
-                        if self.config.passive_tcp_establishment {
-                            let mut interval =
-                                tokio::time::interval(self.config.connect_retry_duration());
-                            interval.reset();
-                            self.connect_retry_timer.replace(interval);
-                            Some(BgpEvent::AutomaticStartWithPassiveTcp)
-                        } else {
-                            Some(BgpEvent::AutomaticStart)
-                        }
+                        Some(self.automatic_start())
                     }
                 }
                 PeerAdminEvents::AutomaticStop => {
@@ -1603,6 +3795,18 @@ impl<
                 return Ok(event);
             }
         }
+        let session_active = self.fsm_state != FsmState::Idle;
+        let restartable = self.fsm_state == FsmState::Idle
+            && self.peer_state == PeerState::AdminUp
+            && self.config.allow_auto_start()
+            && self.idle_hold_timer.is_none()
+            && self.max_prefix_restart_timer.is_none();
+        let next_reuse = self
+            .damping
+            .as_ref()
+            .and_then(|damping| damping.next_reuse(Instant::now()));
+        let next_hops_changed = self.next_hops.as_ref().map(NextHopTracker::changed);
+        let origin_validation_changed = self.origin_validation_changed();
         tokio::select! {
             connect_result = Self::connect(
                 self.peer_key,
@@ -1624,6 +3828,15 @@ impl<
                     }
                 }
             => {
+                // Pick up changes to the ConnectRetryTimer duration
+                let connect_retry_duration = self.config.connect_retry_duration();
+                if let Some(interval) = self.connect_retry_timer.as_mut() {
+                    if interval.period() != connect_retry_duration {
+                        let mut interval = tokio::time::interval(connect_retry_duration);
+                        interval.reset();
+                        self.connect_retry_timer.replace(interval);
+                    }
+                }
                 if self.fsm_state == FsmState::Active {
                     self.fsm_transition(FsmState::Connect);
                 }
@@ -1631,6 +3844,84 @@ impl<
                 self.connection.take();
                 Ok(BgpEvent::ConnectRetryTimerExpires)
             }
+            _ = async {
+                    match self.max_prefix_restart_timer.as_mut() {
+                        Some(interval) => {
+                            interval.tick().await;
+                        },
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                log::info!(
+                    "[{}][{}] Restarting peer after being stopped for exceeding max prefixes",
+                    self.peer_key,
+                    self.fsm_state
+                );
+                Ok(self.automatic_start())
+            }
+            _ = async {
+                    match self.idle_hold_timer.as_mut() {
+                        Some(interval) => {
+                            interval.tick().await;
+                        },
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                self.idle_hold_timer.take();
+                log::info!(
+                    "[{}][{}] IdleHoldTimer expired, restarting peer",
+                    self.peer_key,
+                    self.fsm_state
+                );
+                let event = self.automatic_start();
+                self.pending_events.push_back(event);
+                Ok(BgpEvent::IdleHoldTimerExpires)
+            }
+            _ = async {
+                    match self.mrai_timer.as_mut() {
+                        Some(interval) => {
+                            interval.tick().await;
+                        },
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                // The delayed routes are advertised by [Peer::run]
+                self.mrai_timer.take();
+                Ok(BgpEvent::MinRouteAdvertisementIntervalTimerExpires)
+            }
+            _ = async {
+                    match next_reuse {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                self.reuse_damped_routes()
+            }
+            _ = async {
+                    match next_hops_changed {
+                        Some(changed) => changed.await,
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                self.next_hops_changed()
+            }
+            state = async {
+                    match self.liveness.as_mut() {
+                        Some(liveness) => liveness.next_transition(session_active, restartable).await,
+                        None => std::future::pending().await,
+                    }
+                }
+            => {
+                self.handle_liveness_transition(state).await
+            }
+            changed = origin_validation_changed => {
+                self.revalidate_routes(changed).await
+            }
             value = Self::next_connection_event(
                 self.properties.my_bgp_id,
                 self.fsm_state,
@@ -1643,3 +3934,11 @@ impl<
         }
     }
 }
+
+/// Address type of the routes to remove after a
+/// [UpdateTreatment::ResetAddressFamily], `None` if it's not known
+fn reset_address_type(afi: u16, safi: u8) -> Option<AddressType> {
+    AddressFamily::from_repr(afi)
+        .zip(SubsequentAddressFamily::from_repr(safi))
+        .and_then(|(afi, safi)| AddressType::from_afi_safi(afi, safi).ok())
+}
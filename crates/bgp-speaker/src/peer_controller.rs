@@ -15,9 +15,14 @@
 
 use crate::{
     connection::{ActiveConnect, ConnectionStats},
+    damping::DampedRoute,
     events::BgpEvent,
+    export::ExportRoute,
     fsm::{FsmState, FsmStateError},
+    liveness::LivenessDetector,
+    nexthop::NextHopResolver,
     peer::*,
+    rib::{AdjRib, RouteKey},
 };
 use netgauze_bgp_pkt::{
     capabilities::BgpCapability,
@@ -25,10 +30,13 @@ use netgauze_bgp_pkt::{
     wire::{deserializer::BgpParsingIgnoredErrors, serializer::BgpMessageWritingError},
     BgpMessage,
 };
+use netgauze_iana::address_family::AddressType;
 use std::{
+    any::TypeId,
     error::Error,
-    fmt::{Debug, Display},
+    fmt::{Debug, Display, Formatter},
     marker::PhantomData,
+    sync::Arc,
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite},
@@ -41,11 +49,42 @@ pub type PeerStateResult<A> = Result<(FsmState, BgpEvent<A>), FsmStateError<A>>;
 
 type PeerJoinHandle<A> = JoinHandle<Result<(), SendError<PeerStateResult<A>>>>;
 
+#[derive(Debug)]
+pub enum UpdatePolicyError<A, I: AsyncWrite + AsyncRead> {
+    /// The policy is not of the same type as the policy the peer was created
+    /// with
+    PolicyTypeMismatch,
+    Send(SendError<PeerEvent<A, I>>),
+}
+
+impl<A, I: AsyncWrite + AsyncRead> Display for UpdatePolicyError<A, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PolicyTypeMismatch => write!(
+                f,
+                "the policy type is different from the policy the peer was created with"
+            ),
+            Self::Send(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl<A, I: AsyncWrite + AsyncRead> From<SendError<PeerEvent<A, I>>> for UpdatePolicyError<A, I> {
+    fn from(err: SendError<PeerEvent<A, I>>) -> Self {
+        Self::Send(err)
+    }
+}
+
 #[derive(Debug)]
 pub struct PeerController<K, A, I: AsyncWrite + AsyncRead> {
     properties: PeerProperties<A>,
+    config: PeerConfig,
+    /// Type of the [PeerPolicy] the peer was created with
+    policy_type: TypeId,
     join_handle: PeerJoinHandle<A>,
     peer_events_tx: mpsc::UnboundedSender<PeerEvent<A, I>>,
+    #[cfg(feature = "metrics")]
+    _metrics: crate::metrics::PeerMetricsGuard,
     _marker: PhantomData<K>,
 }
 
@@ -74,15 +113,19 @@ impl<
         let (join_handle, peer_events_tx) = Self::start_peer(
             peer_key,
             properties,
-            config,
+            config.clone(),
             received_events_tx,
             policy,
             active_connect,
         );
         Self {
             properties,
+            config,
+            policy_type: TypeId::of::<P>(),
             join_handle,
             peer_events_tx,
+            #[cfg(feature = "metrics")]
+            _metrics: crate::metrics::PeerMetricsGuard::new(peer_key.to_string()),
             _marker: PhantomData,
         }
     }
@@ -92,7 +135,7 @@ impl<
             + Decoder<Item = (BgpMessage, BgpParsingIgnoredErrors), Error = BgpCodecDecoderError>
             + Encoder<BgpMessage, Error = BgpMessageWritingError>,
         C: ActiveConnect<A, I, D> + Send,
-        P: PeerPolicy<A, I, D>,
+        P: PeerPolicy<A, I, D> + 'static,
     >(
         peer: &mut Peer<K, A, I, D, C, P>,
         peer_event: Option<PeerEvent<A, I>>,
@@ -149,6 +192,71 @@ impl<
                         );
                     }
                 }
+                PeerEvent::RequestRouteRefresh(address_type) => {
+                    peer.request_route_refresh(address_type).await?;
+                }
+                PeerEvent::GetAdjRibIn(tx) => {
+                    if let Err(err) = tx.send(peer.adj_rib_in().clone()) {
+                        log::error!("Error sending Adj-RIB-In: {err:?}");
+                    }
+                }
+                PeerEvent::GetAdjRibOut(tx) => {
+                    if let Err(err) = tx.send(peer.adj_rib_out().clone()) {
+                        log::error!("Error sending Adj-RIB-Out: {err:?}");
+                    }
+                }
+                PeerEvent::GetDampedRoute(key, tx) => {
+                    if let Err(err) = tx.send(peer.damped_route(&key)) {
+                        log::error!("Error sending damped route: {err:?}");
+                    }
+                }
+                PeerEvent::GetDampenedRoutes(tx) => {
+                    if let Err(err) = tx.send(peer.dampened_routes()) {
+                        log::error!("Error sending dampened routes: {err:?}");
+                    }
+                }
+                PeerEvent::GetFlapStatistics(tx) => {
+                    if let Err(err) = tx.send(peer.flap_statistics()) {
+                        log::error!("Error sending flap statistics: {err:?}");
+                    }
+                }
+                PeerEvent::AnnounceRoutes(routes) => {
+                    peer.export_routes(routes, vec![]).await?;
+                }
+                PeerEvent::WithdrawRoutes(keys) => {
+                    peer.export_routes(vec![], keys).await?;
+                }
+                PeerEvent::UpdateConfig(config) => {
+                    peer.update_config(*config);
+                }
+                PeerEvent::UpdatePolicy(policy) => match policy.downcast::<P>() {
+                    Ok(policy) => peer.update_policy(*policy),
+                    Err(_) => {
+                        log::error!(
+                            "[{}][{}] Ignoring policy update with a different policy type",
+                            peer.peer_key(),
+                            peer.fsm_state()
+                        );
+                    }
+                },
+                #[cfg(feature = "bmp")]
+                PeerEvent::SetBmpExporter(exporter) => {
+                    peer.set_bmp_exporter(exporter);
+                }
+                #[cfg(feature = "mrt")]
+                PeerEvent::SetMrtRecorder(recorder) => {
+                    peer.set_mrt_recorder(recorder);
+                }
+                #[cfg(feature = "rpki")]
+                PeerEvent::SetRouteOriginValidator(validator) => {
+                    peer.set_route_origin_validator(validator);
+                }
+                PeerEvent::SetLivenessDetector(detector) => {
+                    peer.set_liveness_detector(detector);
+                }
+                PeerEvent::SetNextHopResolver(resolver) => {
+                    peer.set_next_hop_resolver(resolver);
+                }
             }
         }
         Ok(())
@@ -247,8 +355,30 @@ impl<
         self.properties.peer_addr()
     }
 
+    pub const fn properties(&self) -> &PeerProperties<A> {
+        &self.properties
+    }
+
+    /// Last configuration pushed to the peer
+    pub const fn config(&self) -> &PeerConfig {
+        &self.config
+    }
+
+    /// Push a new configuration to the running peer, see
+    /// [Peer::update_config]
+    pub fn update_config(&mut self, config: PeerConfig) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::UpdateConfig(Box::new(config.clone())))?;
+        self.config = config;
+        Ok(())
+    }
+
     pub fn get_new_handle(&self) -> PeerHandle<A, I> {
-        PeerHandle::new(self.peer_events_tx.clone(), self.properties.peer_addr())
+        PeerHandle::new(
+            self.peer_events_tx.clone(),
+            self.properties.peer_addr(),
+            self.policy_type,
+        )
     }
 }
 
@@ -262,6 +392,7 @@ impl<K, A, I: AsyncWrite + AsyncRead> Drop for PeerController<K, A, I> {
 pub struct PeerHandle<A, I: AsyncWrite + AsyncRead> {
     peer_events_tx: mpsc::UnboundedSender<PeerEvent<A, I>>,
     peer_addr: A,
+    policy_type: TypeId,
 }
 
 impl<A: Clone, I: AsyncWrite + AsyncRead> Clone for PeerHandle<A, I> {
@@ -269,15 +400,21 @@ impl<A: Clone, I: AsyncWrite + AsyncRead> Clone for PeerHandle<A, I> {
         Self {
             peer_events_tx: self.peer_events_tx.clone(),
             peer_addr: self.peer_addr.clone(),
+            policy_type: self.policy_type,
         }
     }
 }
 
 impl<A: Display + Debug + 'static, I: AsyncWrite + AsyncRead + 'static> PeerHandle<A, I> {
-    fn new(peer_events_tx: mpsc::UnboundedSender<PeerEvent<A, I>>, peer_addr: A) -> Self {
+    fn new(
+        peer_events_tx: mpsc::UnboundedSender<PeerEvent<A, I>>,
+        peer_addr: A,
+        policy_type: TypeId,
+    ) -> Self {
         Self {
             peer_events_tx,
             peer_addr,
+            policy_type,
         }
     }
 
@@ -306,7 +443,108 @@ impl<A: Display + Debug + 'static, I: AsyncWrite + AsyncRead + 'static> PeerHand
             ))))
     }
 
-    pub async fn peer_stats(&mut self) -> Result<PeerStats, Box<dyn Error>> {
+    /// Send a BGP message to the peer over its current connection
+    pub fn send_bgp_message(&self, msg: BgpMessage) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx.send(PeerEvent::BgpMessage(msg))
+    }
+
+    /// Ask the peer to re-advertise its routes for the given address type.
+    /// The request is ignored if the session is not established or the peer
+    /// doesn't support route refresh.
+    pub fn request_route_refresh(
+        &self,
+        address_type: AddressType,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::RequestRouteRefresh(address_type))
+    }
+
+    /// Add or replace routes exported to the peer, see [Peer::export_routes]
+    pub fn announce_routes(
+        &self,
+        routes: Vec<ExportRoute>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx.send(PeerEvent::AnnounceRoutes(routes))
+    }
+
+    /// Stop exporting routes to the peer, see [Peer::export_routes]
+    pub fn withdraw_routes(&self, keys: Vec<RouteKey>) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx.send(PeerEvent::WithdrawRoutes(keys))
+    }
+
+    /// Push a new configuration to the running peer without resetting the
+    /// session, see [Peer::update_config]
+    pub fn update_config(&self, config: PeerConfig) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::UpdateConfig(Box::new(config)))
+    }
+
+    /// Replace the policy of the running peer, it's used starting from the
+    /// next connection attempt. The policy must be of the same type the peer
+    /// was created with, otherwise [UpdatePolicyError::PolicyTypeMismatch] is
+    /// returned.
+    pub fn update_policy<P: Send + 'static>(
+        &self,
+        policy: P,
+    ) -> Result<(), UpdatePolicyError<A, I>> {
+        if TypeId::of::<P>() != self.policy_type {
+            return Err(UpdatePolicyError::PolicyTypeMismatch);
+        }
+        self.peer_events_tx
+            .send(PeerEvent::UpdatePolicy(Box::new(policy)))?;
+        Ok(())
+    }
+
+    /// Report the session of the running peer to a BMP exporter
+    #[cfg(feature = "bmp")]
+    pub fn set_bmp_exporter(
+        &self,
+        exporter: Option<crate::bmp::BmpExporter<A>>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::SetBmpExporter(exporter))
+    }
+
+    /// Record the session of the running peer to MRT files
+    #[cfg(feature = "mrt")]
+    pub fn set_mrt_recorder(
+        &self,
+        recorder: Option<crate::mrt::MrtRecorder<A>>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::SetMrtRecorder(recorder))
+    }
+
+    /// Validate the origin of the routes received by the running peer
+    #[cfg(feature = "rpki")]
+    pub fn set_route_origin_validator(
+        &self,
+        validator: Option<crate::rpki::RouteOriginValidator>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::SetRouteOriginValidator(validator))
+    }
+
+    /// Tie the session of the running peer to a liveness detector, such as
+    /// [crate::bfd::Bfd]
+    pub fn set_liveness_detector(
+        &self,
+        detector: Option<Arc<dyn LivenessDetector<A>>>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::SetLivenessDetector(detector))
+    }
+
+    /// Resolve the next hops of the routes received by the running peer
+    pub fn set_next_hop_resolver(
+        &self,
+        resolver: Option<Arc<dyn NextHopResolver>>,
+    ) -> Result<(), SendError<PeerEvent<A, I>>> {
+        self.peer_events_tx
+            .send(PeerEvent::SetNextHopResolver(resolver))
+    }
+
+    pub async fn peer_stats(&mut self) -> Result<PeerStats<A>, Box<dyn Error>> {
         let (tx, rx) = oneshot::channel();
         self.peer_events_tx.send(PeerEvent::GetPeerStats(tx))?;
         Ok(rx.await?)
@@ -363,4 +601,44 @@ impl<A: Display + Debug + 'static, I: AsyncWrite + AsyncRead + 'static> PeerHand
             .send(PeerEvent::TrackedConnectionReceivedCapabilities(tx))?;
         Ok(rx.await?)
     }
+
+    /// Copy of the routes received from the peer
+    pub async fn adj_rib_in(&mut self) -> Result<AdjRib, Box<dyn Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.peer_events_tx.send(PeerEvent::GetAdjRibIn(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Copy of the routes advertised to the peer
+    pub async fn adj_rib_out(&mut self) -> Result<AdjRib, Box<dyn Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.peer_events_tx.send(PeerEvent::GetAdjRibOut(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Route flap damping state of a route received from the peer
+    pub async fn damped_route(
+        &mut self,
+        key: RouteKey,
+    ) -> Result<Option<DampedRoute<()>>, Box<dyn Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.peer_events_tx
+            .send(PeerEvent::GetDampedRoute(key, tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Routes received from the peer that are suppressed by the route flap
+    /// damping
+    pub async fn dampened_routes(&mut self) -> Result<Vec<DampedRoute<()>>, Box<dyn Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.peer_events_tx.send(PeerEvent::GetDampenedRoutes(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Routes received from the peer with route flap history
+    pub async fn flap_statistics(&mut self) -> Result<Vec<DampedRoute<()>>, Box<dyn Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.peer_events_tx.send(PeerEvent::GetFlapStatistics(tx))?;
+        Ok(rx.await?)
+    }
 }
@@ -0,0 +1,125 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Route reflection ([RFC4456](https://datatracker.ietf.org/doc/html/rfc4456))
+//! and route server ([RFC7947](https://datatracker.ietf.org/doc/html/rfc7947))
+//! path attribute handling.
+//!
+//! The role of each peer is configured with
+//! [crate::peer::PeerProperties::with_client_role], and is applied by
+//! [crate::export::export_route] when exporting routes to the peer.
+
+use std::net::Ipv4Addr;
+
+use netgauze_bgp_pkt::path_attribute::{
+    ClusterId, ClusterList, Originator, PathAttribute, PathAttributeValue,
+};
+
+use crate::rib::insert_attribute;
+
+/// Role of a peer with respect to the speaker acting as route reflector or
+/// route server
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub enum ClientRole {
+    /// Regular peer, routes learned from internal non-client peers are not
+    /// advertised to it if it's an internal peer
+    #[default]
+    NonClient,
+
+    /// RFC4456 route reflector client, only applicable to internal peers.
+    /// Routes learned from any internal peer are reflected to the client.
+    RouteReflectorClient,
+
+    /// RFC7947 route server client, only applicable to external peers. The
+    /// route server is transparent to the client: the local ASN is not
+    /// prepended to the AS_PATH and the next hop and MED are not changed.
+    RouteServerClient,
+}
+
+/// Check the route reflection attributes of a route received from an internal
+/// peer. RFC4456 requires the route to be ignored when the `ORIGINATOR_ID` is
+/// the local BGP Identifier, or the `CLUSTER_LIST` contains the local cluster
+/// ID.
+pub fn is_reflection_loop(
+    attributes: &[PathAttribute],
+    my_bgp_id: Ipv4Addr,
+    cluster_id: Ipv4Addr,
+) -> bool {
+    attributes.iter().any(|attr| match attr.value() {
+        PathAttributeValue::Originator(originator) => originator.id() == my_bgp_id,
+        PathAttributeValue::ClusterList(cluster_list) => cluster_list
+            .cluster_list()
+            .iter()
+            .any(|id| id.id() == cluster_id),
+        _ => false,
+    })
+}
+
+/// Add the route reflection attributes when reflecting a route learned from
+/// the internal peer `peer_bgp_id`: `ORIGINATOR_ID` is set if it doesn't
+/// exist already, and `cluster_id` is prepended to the `CLUSTER_LIST`.
+pub(crate) fn reflect_attributes(
+    attributes: &mut Vec<PathAttribute>,
+    peer_bgp_id: Ipv4Addr,
+    cluster_id: Ipv4Addr,
+) -> Option<()> {
+    let has_originator = attributes
+        .iter()
+        .any(|attr| matches!(attr.value(), PathAttributeValue::Originator(_)));
+    if !has_originator {
+        let originator = PathAttribute::from(
+            true,
+            false,
+            false,
+            false,
+            PathAttributeValue::Originator(Originator::new(peer_bgp_id)),
+        )
+        .ok()?;
+        insert_attribute(attributes, originator);
+    }
+    let mut cluster_list = vec![ClusterId::new(cluster_id)];
+    attributes.retain(|attr| match attr.value() {
+        PathAttributeValue::ClusterList(existing) => {
+            cluster_list.extend(existing.cluster_list().iter().cloned());
+            false
+        }
+        _ => true,
+    });
+    // Four bytes per cluster id, the extended length flag is needed if the
+    // attribute is longer than 255 bytes
+    let extended_length = cluster_list.len() * 4 > u8::MAX as usize;
+    let cluster_list = PathAttribute::from(
+        true,
+        false,
+        false,
+        extended_length,
+        PathAttributeValue::ClusterList(ClusterList::new(cluster_list)),
+    )
+    .ok()?;
+    insert_attribute(attributes, cluster_list);
+    Some(())
+}
+
+/// `ORIGINATOR_ID` and `CLUSTER_LIST` are only meaningful within the AS,
+/// they're removed from the routes advertised to external peers
+pub(crate) fn strip_reflection_attributes(attributes: &mut Vec<PathAttribute>) {
+    attributes.retain(|attr| {
+        !matches!(
+            attr.value(),
+            PathAttributeValue::Originator(_) | PathAttributeValue::ClusterList(_)
+        )
+    });
+}
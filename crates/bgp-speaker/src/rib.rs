@@ -0,0 +1,766 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routing Information Bases (RIBs) maintained per peer.
+//!
+//! At the moment only prefix based address families are tracked:
+//! IPv4/IPv6 Unicast and Multicast. Routes of other address families are
+//! ignored by the RIB.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    net::{IpAddr, Ipv6Addr},
+};
+
+use ipnet::IpNet;
+
+use netgauze_bgp_pkt::{
+    nlri::{
+        Ipv4Multicast, Ipv4MulticastAddress, Ipv4Unicast, Ipv4UnicastAddress, Ipv6Multicast,
+        Ipv6MulticastAddress, Ipv6Unicast, Ipv6UnicastAddress,
+    },
+    path_attribute::{MpReach, MpUnreach, PathAttribute, PathAttributeValue},
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+use netgauze_parse_utils::WritablePdu;
+
+pub use netgauze_bgp_pkt::wire::deserializer::BGP_MAX_MESSAGE_LENGTH;
+
+/// Max length of a BGP message once the
+/// [RFC8654](https://datatracker.ietf.org/doc/html/rfc8654) Extended Message
+/// capability is negotiated. Without it, messages are limited to
+/// [BGP_MAX_MESSAGE_LENGTH] octets.
+pub const BGP_EXTENDED_MAX_MESSAGE_LENGTH: u16 = u16::MAX;
+
+/// Uniquely identify a route within a RIB
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RouteKey {
+    address_type: AddressType,
+    path_id: Option<u32>,
+    prefix: IpNet,
+}
+
+impl RouteKey {
+    pub const fn new(address_type: AddressType, path_id: Option<u32>, prefix: IpNet) -> Self {
+        Self {
+            address_type,
+            path_id,
+            prefix,
+        }
+    }
+
+    pub const fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub const fn path_id(&self) -> Option<u32> {
+        self.path_id
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+}
+
+/// Next hop carried in `MP_REACH_NLRI` attribute. Routes carried in the NLRI
+/// field of the BGP Update message have their next hop in the `NEXT_HOP` path
+/// attribute instead.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MpNextHop {
+    next_hop: IpAddr,
+    next_hop_local: Option<Ipv6Addr>,
+}
+
+impl MpNextHop {
+    pub const fn new(next_hop: IpAddr, next_hop_local: Option<Ipv6Addr>) -> Self {
+        Self {
+            next_hop,
+            next_hop_local,
+        }
+    }
+
+    pub const fn next_hop(&self) -> IpAddr {
+        self.next_hop
+    }
+
+    pub const fn next_hop_local(&self) -> Option<Ipv6Addr> {
+        self.next_hop_local
+    }
+}
+
+/// A route stored in a RIB
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    /// Path attributes of the route, excluding `MP_REACH_NLRI` and
+    /// `MP_UNREACH_NLRI`
+    attributes: Vec<PathAttribute>,
+    mp_next_hop: Option<MpNextHop>,
+    /// [RFC7313](https://datatracker.ietf.org/doc/html/rfc7313) routes are
+    /// marked as stale when a Beginning of Route Refresh (BoRR) is received
+    /// and purged if not re-advertised before the End of Route Refresh.
+    stale: bool,
+}
+
+impl Route {
+    pub fn new(attributes: Vec<PathAttribute>, mp_next_hop: Option<MpNextHop>) -> Self {
+        Self {
+            attributes: attributes
+                .into_iter()
+                .filter(|attr| {
+                    !matches!(
+                        attr.value(),
+                        PathAttributeValue::MpReach(_) | PathAttributeValue::MpUnreach(_)
+                    )
+                })
+                .collect(),
+            mp_next_hop,
+            stale: false,
+        }
+    }
+
+    pub const fn attributes(&self) -> &Vec<PathAttribute> {
+        &self.attributes
+    }
+
+    pub const fn mp_next_hop(&self) -> Option<MpNextHop> {
+        self.mp_next_hop
+    }
+
+    pub const fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Check if two routes can be packed in the same BGP Update message
+    pub(crate) fn same_path(&self, other: &Route) -> bool {
+        self.mp_next_hop == other.mp_next_hop && self.attributes == other.attributes
+    }
+
+    /// Hashable key of the path, routes with the same key can be packed in
+    /// the same BGP Update message. The path attributes don't implement
+    /// [Hash], so their wire encoding is used instead. `None` if the
+    /// attributes can't be encoded.
+    fn path_key(&self) -> Option<(Option<MpNextHop>, Vec<u8>)> {
+        let mut encoded = Vec::with_capacity(self.attributes.iter().map(|attr| attr.len()).sum());
+        for attr in &self.attributes {
+            attr.write(&mut encoded).ok()?;
+        }
+        Some((self.mp_next_hop, encoded))
+    }
+}
+
+/// Routes announced and withdrawn by a single BGP Update message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateRoutes {
+    announced: Vec<(RouteKey, Route)>,
+    withdrawn: Vec<RouteKey>,
+}
+
+impl UpdateRoutes {
+    pub const fn new(announced: Vec<(RouteKey, Route)>, withdrawn: Vec<RouteKey>) -> Self {
+        Self {
+            announced,
+            withdrawn,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.announced.is_empty() && self.withdrawn.is_empty()
+    }
+
+    pub const fn announced(&self) -> &Vec<(RouteKey, Route)> {
+        &self.announced
+    }
+
+    pub const fn withdrawn(&self) -> &Vec<RouteKey> {
+        &self.withdrawn
+    }
+
+    /// Treat all the announced routes as withdrawn, used for RFC7606
+    /// treat-as-withdraw error handling.
+    pub fn treat_as_withdraw(self) -> Self {
+        let mut withdrawn = self.withdrawn;
+        withdrawn.extend(self.announced.into_iter().map(|(key, _)| key));
+        Self {
+            announced: vec![],
+            withdrawn,
+        }
+    }
+}
+
+impl From<&BgpUpdateMessage> for UpdateRoutes {
+    fn from(update: &BgpUpdateMessage) -> Self {
+        let mut announced = vec![];
+        let mut withdrawn: Vec<RouteKey> = update
+            .withdraw_routes()
+            .iter()
+            .map(|addr| {
+                RouteKey::new(
+                    AddressType::Ipv4Unicast,
+                    addr.path_id(),
+                    IpNet::V4(addr.network().address()),
+                )
+            })
+            .collect();
+        if !update.nlri().is_empty() {
+            let route = Route::new(update.path_attributes().clone(), None);
+            for addr in update.nlri() {
+                let key = RouteKey::new(
+                    AddressType::Ipv4Unicast,
+                    addr.path_id(),
+                    IpNet::V4(addr.network().address()),
+                );
+                announced.push((key, route.clone()));
+            }
+        }
+        for attr in update.path_attributes() {
+            match attr.value() {
+                PathAttributeValue::MpReach(mp_reach) => {
+                    announced.extend(mp_reach_routes(mp_reach, update.path_attributes()));
+                }
+                PathAttributeValue::MpUnreach(mp_unreach) => {
+                    withdrawn.extend(mp_unreach_keys(mp_unreach));
+                }
+                _ => {}
+            }
+        }
+        Self {
+            announced,
+            withdrawn,
+        }
+    }
+}
+
+fn mp_reach_routes(mp_reach: &MpReach, attributes: &[PathAttribute]) -> Vec<(RouteKey, Route)> {
+    let (address_type, next_hop, prefixes): (_, _, Vec<(Option<u32>, IpNet)>) = match mp_reach {
+        MpReach::Ipv4Unicast {
+            next_hop,
+            next_hop_local,
+            nlri,
+        } => (
+            AddressType::Ipv4Unicast,
+            MpNextHop::new(*next_hop, *next_hop_local),
+            nlri.iter()
+                .map(|x| (x.path_id(), IpNet::V4(x.network().address())))
+                .collect(),
+        ),
+        MpReach::Ipv4Multicast {
+            next_hop,
+            next_hop_local,
+            nlri,
+        } => (
+            AddressType::Ipv4Multicast,
+            MpNextHop::new(*next_hop, *next_hop_local),
+            nlri.iter()
+                .map(|x| (x.path_id(), IpNet::V4(x.network().address())))
+                .collect(),
+        ),
+        MpReach::Ipv6Unicast {
+            next_hop_global,
+            next_hop_local,
+            nlri,
+        } => (
+            AddressType::Ipv6Unicast,
+            MpNextHop::new(IpAddr::V6(*next_hop_global), *next_hop_local),
+            nlri.iter()
+                .map(|x| (x.path_id(), IpNet::V6(x.network().address())))
+                .collect(),
+        ),
+        MpReach::Ipv6Multicast {
+            next_hop_global,
+            next_hop_local,
+            nlri,
+        } => (
+            AddressType::Ipv6Multicast,
+            MpNextHop::new(IpAddr::V6(*next_hop_global), *next_hop_local),
+            nlri.iter()
+                .map(|x| (x.path_id(), IpNet::V6(x.network().address())))
+                .collect(),
+        ),
+        _ => return vec![],
+    };
+    let route = Route::new(attributes.to_vec(), Some(next_hop));
+    prefixes
+        .into_iter()
+        .map(|(path_id, prefix)| (RouteKey::new(address_type, path_id, prefix), route.clone()))
+        .collect()
+}
+
+fn mp_unreach_keys(mp_unreach: &MpUnreach) -> Vec<RouteKey> {
+    match mp_unreach {
+        MpUnreach::Ipv4Unicast { nlri } => nlri
+            .iter()
+            .map(|x| {
+                RouteKey::new(
+                    AddressType::Ipv4Unicast,
+                    x.path_id(),
+                    IpNet::V4(x.network().address()),
+                )
+            })
+            .collect(),
+        MpUnreach::Ipv4Multicast { nlri } => nlri
+            .iter()
+            .map(|x| {
+                RouteKey::new(
+                    AddressType::Ipv4Multicast,
+                    x.path_id(),
+                    IpNet::V4(x.network().address()),
+                )
+            })
+            .collect(),
+        MpUnreach::Ipv6Unicast { nlri } => nlri
+            .iter()
+            .map(|x| {
+                RouteKey::new(
+                    AddressType::Ipv6Unicast,
+                    x.path_id(),
+                    IpNet::V6(x.network().address()),
+                )
+            })
+            .collect(),
+        MpUnreach::Ipv6Multicast { nlri } => nlri
+            .iter()
+            .map(|x| {
+                RouteKey::new(
+                    AddressType::Ipv6Multicast,
+                    x.path_id(),
+                    IpNet::V6(x.network().address()),
+                )
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Adj-RIB-In or Adj-RIB-Out of a single peer
+#[derive(Debug, Clone, Default)]
+pub struct AdjRib {
+    routes: HashMap<AddressType, HashMap<RouteKey, Route>>,
+}
+
+impl AdjRib {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if the routes of an address type are tracked by the RIB
+    pub const fn is_supported(address_type: AddressType) -> bool {
+        matches!(
+            address_type,
+            AddressType::Ipv4Unicast
+                | AddressType::Ipv4Multicast
+                | AddressType::Ipv6Unicast
+                | AddressType::Ipv6Multicast
+        )
+    }
+
+    pub fn get(&self, key: &RouteKey) -> Option<&Route> {
+        self.routes
+            .get(&key.address_type())
+            .and_then(|routes| routes.get(key))
+    }
+
+    /// Number of routes stored for a given address type
+    pub fn len(&self, address_type: AddressType) -> usize {
+        self.routes
+            .get(&address_type)
+            .map(|routes| routes.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.values().all(|routes| routes.is_empty())
+    }
+
+    pub fn address_types(&self) -> Vec<AddressType> {
+        self.routes
+            .iter()
+            .filter(|(_, routes)| !routes.is_empty())
+            .map(|(address_type, _)| *address_type)
+            .collect()
+    }
+
+    pub fn routes(
+        &self,
+        address_type: AddressType,
+    ) -> impl Iterator<Item = (&RouteKey, &Route)> + '_ {
+        self.routes.get(&address_type).into_iter().flatten()
+    }
+
+    /// Add or replace a route, returns the previous route if any
+    pub fn insert(&mut self, key: RouteKey, route: Route) -> Option<Route> {
+        self.routes
+            .entry(key.address_type())
+            .or_default()
+            .insert(key, route)
+    }
+
+    pub fn remove(&mut self, key: &RouteKey) -> Option<Route> {
+        self.routes
+            .get_mut(&key.address_type())
+            .and_then(|routes| routes.remove(key))
+    }
+
+    /// Apply routes from a BGP update message to the RIB.
+    pub fn apply(&mut self, update_routes: UpdateRoutes) {
+        for key in &update_routes.withdrawn {
+            self.remove(key);
+        }
+        for (key, route) in update_routes.announced {
+            self.insert(key, route);
+        }
+    }
+
+    /// Remove all routes of a given address type
+    pub fn clear_address_type(&mut self, address_type: AddressType) {
+        self.routes.remove(&address_type);
+    }
+
+    pub fn clear(&mut self) {
+        self.routes.clear();
+    }
+
+    /// Mark all routes of a given address type as stale
+    pub fn mark_stale(&mut self, address_type: AddressType) {
+        if let Some(routes) = self.routes.get_mut(&address_type) {
+            for route in routes.values_mut() {
+                route.stale = true;
+            }
+        }
+    }
+
+    /// Remove all the routes of a given address type that are marked as stale
+    /// and return their keys.
+    pub fn sweep_stale(&mut self, address_type: AddressType) -> Vec<RouteKey> {
+        let mut swept = vec![];
+        if let Some(routes) = self.routes.get_mut(&address_type) {
+            routes.retain(|key, route| {
+                if route.stale {
+                    swept.push(*key);
+                }
+                !route.stale
+            });
+        }
+        swept
+    }
+
+    /// Build the BGP Update messages required to advertise all the routes of
+    /// a given address type. Routes sharing the same path attributes are
+    /// packed together in messages of at most `max_message_length` octets.
+    pub fn updates(
+        &self,
+        address_type: AddressType,
+        max_message_length: u16,
+    ) -> Vec<BgpUpdateMessage> {
+        let mut groups: Vec<(&Route, Vec<&RouteKey>)> = vec![];
+        let mut group_index: HashMap<_, usize> = HashMap::new();
+        for (key, route) in self.routes(address_type) {
+            match route.path_key() {
+                Some(path_key) => match group_index.entry(path_key) {
+                    Entry::Occupied(entry) => groups[*entry.get()].1.push(key),
+                    Entry::Vacant(entry) => {
+                        entry.insert(groups.len());
+                        groups.push((route, vec![key]));
+                    }
+                },
+                None => groups.push((route, vec![key])),
+            }
+        }
+        let mut updates = vec![];
+        for (route, mut keys) in groups {
+            keys.sort_by_key(|key| (key.path_id(), key.prefix()));
+            updates.extend(announce_updates(
+                address_type,
+                route,
+                &keys,
+                max_message_length,
+            ));
+        }
+        updates
+    }
+}
+
+/// Build the BGP Update messages announcing the given routes sharing the same
+/// path, each message is at most `max_message_length` octets long.
+pub(crate) fn announce_updates(
+    address_type: AddressType,
+    route: &Route,
+    keys: &[&RouteKey],
+    max_message_length: u16,
+) -> Vec<BgpUpdateMessage> {
+    let base_length = match announce_update(address_type, route, &[]) {
+        Some(update) => BgpMessage::Update(update).len(),
+        None => return vec![],
+    };
+    pack_keys(keys, base_length, max_message_length)
+        .into_iter()
+        .filter_map(|chunk| announce_update(address_type, route, chunk))
+        .collect()
+}
+
+pub(crate) fn announce_update(
+    address_type: AddressType,
+    route: &Route,
+    keys: &[&RouteKey],
+) -> Option<BgpUpdateMessage> {
+    let next_hop = match route.mp_next_hop {
+        None => {
+            if address_type != AddressType::Ipv4Unicast {
+                return None;
+            }
+            let nlri = keys
+                .iter()
+                .filter_map(|key| ipv4_unicast_address(key))
+                .collect();
+            return Some(BgpUpdateMessage::new(
+                vec![],
+                route.attributes.clone(),
+                nlri,
+            ));
+        }
+        Some(next_hop) => next_hop,
+    };
+    let mp_reach = match (address_type, next_hop.next_hop) {
+        (AddressType::Ipv4Unicast, _) => MpReach::Ipv4Unicast {
+            next_hop: next_hop.next_hop,
+            next_hop_local: next_hop.next_hop_local,
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv4_unicast_address(key))
+                .collect(),
+        },
+        (AddressType::Ipv4Multicast, _) => MpReach::Ipv4Multicast {
+            next_hop: next_hop.next_hop,
+            next_hop_local: next_hop.next_hop_local,
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv4_multicast_address(key))
+                .collect(),
+        },
+        (AddressType::Ipv6Unicast, IpAddr::V6(next_hop_global)) => MpReach::Ipv6Unicast {
+            next_hop_global,
+            next_hop_local: next_hop.next_hop_local,
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv6_unicast_address(key))
+                .collect(),
+        },
+        (AddressType::Ipv6Multicast, IpAddr::V6(next_hop_global)) => MpReach::Ipv6Multicast {
+            next_hop_global,
+            next_hop_local: next_hop.next_hop_local,
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv6_multicast_address(key))
+                .collect(),
+        },
+        _ => return None,
+    };
+    let mp_reach = PathAttribute::from(
+        true,
+        false,
+        false,
+        true,
+        PathAttributeValue::MpReach(mp_reach),
+    )
+    .ok()?;
+    let mut attributes = route.attributes.clone();
+    insert_attribute(&mut attributes, mp_reach);
+    Some(BgpUpdateMessage::new(vec![], attributes, vec![]))
+}
+
+/// Build the BGP update messages withdrawing the given routes of a single
+/// address type, each message is at most `max_message_length` octets long.
+pub fn withdraw_updates(
+    address_type: AddressType,
+    keys: &[RouteKey],
+    max_message_length: u16,
+) -> Vec<BgpUpdateMessage> {
+    let keys: Vec<&RouteKey> = keys
+        .iter()
+        .filter(|key| key.address_type() == address_type)
+        .collect();
+    if keys.is_empty() {
+        return vec![];
+    }
+    let base_length = match withdraw_update(address_type, &[]) {
+        Some(update) => BgpMessage::Update(update).len(),
+        None => return vec![],
+    };
+    pack_keys(&keys, base_length, max_message_length)
+        .into_iter()
+        .filter_map(|chunk| withdraw_update(address_type, chunk))
+        .collect()
+}
+
+fn withdraw_update(address_type: AddressType, keys: &[&RouteKey]) -> Option<BgpUpdateMessage> {
+    let mp_unreach = match address_type {
+        AddressType::Ipv4Unicast => {
+            let withdrawn = keys
+                .iter()
+                .filter_map(|key| ipv4_unicast_address(key))
+                .collect();
+            return Some(BgpUpdateMessage::new(withdrawn, vec![], vec![]));
+        }
+        AddressType::Ipv4Multicast => MpUnreach::Ipv4Multicast {
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv4_multicast_address(key))
+                .collect(),
+        },
+        AddressType::Ipv6Unicast => MpUnreach::Ipv6Unicast {
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv6_unicast_address(key))
+                .collect(),
+        },
+        AddressType::Ipv6Multicast => MpUnreach::Ipv6Multicast {
+            nlri: keys
+                .iter()
+                .filter_map(|key| ipv6_multicast_address(key))
+                .collect(),
+        },
+        _ => return None,
+    };
+    let mp_unreach = PathAttribute::from(
+        true,
+        false,
+        false,
+        true,
+        PathAttributeValue::MpUnreach(mp_unreach),
+    )
+    .ok()?;
+    Some(BgpUpdateMessage::new(vec![], vec![mp_unreach], vec![]))
+}
+
+/// Split the keys in chunks whose NLRI fit in a message of
+/// `max_message_length` octets next to the `base_length` octets taken by the
+/// header and the path attributes. A chunk holds at least one key, even if it
+/// doesn't fit.
+fn pack_keys<'a, 'b>(
+    keys: &'b [&'a RouteKey],
+    base_length: usize,
+    max_message_length: u16,
+) -> Vec<&'b [&'a RouteKey]> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut length = base_length;
+    for (index, key) in keys.iter().enumerate() {
+        let nlri_length = nlri_length(key);
+        if index > start && length + nlri_length > max_message_length as usize {
+            chunks.push(&keys[start..index]);
+            start = index;
+            length = base_length;
+        }
+        length += nlri_length;
+    }
+    if start < keys.len() {
+        chunks.push(&keys[start..]);
+    }
+    chunks
+}
+
+/// Encoded length of the NLRI of a route: the optional
+/// [RFC7911](https://datatracker.ietf.org/doc/html/rfc7911) path id, the
+/// prefix length octet and the significant octets of the prefix.
+fn nlri_length(key: &RouteKey) -> usize {
+    let path_id_length = if key.path_id.is_some() { 4 } else { 0 };
+    path_id_length + 1 + usize::from(key.prefix.prefix_len()).div_ceil(8)
+}
+
+/// Build the BGP update messages carrying the given routes, withdrawals are
+/// placed before the announcements.
+pub(crate) fn route_updates(
+    update_routes: &UpdateRoutes,
+    max_message_length: u16,
+) -> Vec<BgpUpdateMessage> {
+    let mut address_types: Vec<AddressType> = vec![];
+    for address_type in update_routes
+        .withdrawn()
+        .iter()
+        .map(|key| key.address_type())
+    {
+        if !address_types.contains(&address_type) {
+            address_types.push(address_type);
+        }
+    }
+    let mut updates: Vec<BgpUpdateMessage> = address_types
+        .into_iter()
+        .flat_map(|address_type| {
+            withdraw_updates(address_type, update_routes.withdrawn(), max_message_length)
+        })
+        .collect();
+    let mut rib = AdjRib::new();
+    for (key, route) in update_routes.announced() {
+        rib.insert(*key, route.clone());
+    }
+    for address_type in rib.address_types() {
+        updates.extend(rib.updates(address_type, max_message_length));
+    }
+    updates
+}
+
+/// Insert a path attribute keeping the attributes ordered by ascending
+/// attribute type code, as RFC4271 recommends
+pub(crate) fn insert_attribute(attributes: &mut Vec<PathAttribute>, attr: PathAttribute) {
+    let pos = attributes
+        .iter()
+        .position(|other| attribute_code(other) > attribute_code(&attr))
+        .unwrap_or(attributes.len());
+    attributes.insert(pos, attr);
+}
+
+fn attribute_code(attr: &PathAttribute) -> u8 {
+    match attr.path_attribute_type() {
+        Ok(code) => code as u8,
+        Err(code) => code,
+    }
+}
+
+fn ipv4_unicast_address(key: &RouteKey) -> Option<Ipv4UnicastAddress> {
+    match key.prefix {
+        IpNet::V4(net) => Ipv4Unicast::from_net(net)
+            .ok()
+            .map(|network| Ipv4UnicastAddress::new(key.path_id, network)),
+        IpNet::V6(_) => None,
+    }
+}
+
+fn ipv4_multicast_address(key: &RouteKey) -> Option<Ipv4MulticastAddress> {
+    match key.prefix {
+        IpNet::V4(net) => Ipv4Multicast::from_net(net)
+            .ok()
+            .map(|network| Ipv4MulticastAddress::new(key.path_id, network)),
+        IpNet::V6(_) => None,
+    }
+}
+
+fn ipv6_unicast_address(key: &RouteKey) -> Option<Ipv6UnicastAddress> {
+    match key.prefix {
+        IpNet::V6(net) => Ipv6Unicast::from_net(net)
+            .ok()
+            .map(|network| Ipv6UnicastAddress::new(key.path_id, network)),
+        IpNet::V4(_) => None,
+    }
+}
+
+fn ipv6_multicast_address(key: &RouteKey) -> Option<Ipv6MulticastAddress> {
+    match key.prefix {
+        IpNet::V6(net) => Ipv6Multicast::from_net(net)
+            .ok()
+            .map(|network| Ipv6MulticastAddress::new(key.path_id, network)),
+        IpNet::V4(_) => None,
+    }
+}
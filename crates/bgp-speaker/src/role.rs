@@ -0,0 +1,141 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BGP Role capability and Only to Customer (OTC) route leak prevention
+//! ([RFC9234](https://datatracker.ietf.org/doc/html/rfc9234)).
+//!
+//! The local role is configured per external peer with
+//! [crate::peer::PeerProperties::with_bgp_role]. The BGP Role capability is
+//! then added to the OPEN message sent to the peer, the role advertised by the
+//! peer is checked with [check_open_role], and the OTC ingress and egress
+//! procedures are applied to the routes received from and advertised to the
+//! peer.
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, BgpRoleCapability},
+    iana::BgpRoleValue,
+    notification::OpenMessageError,
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::{OnlyToCustomer, PathAttribute, PathAttributeValue},
+};
+
+use crate::rib::insert_attribute;
+
+/// The only role the peer is allowed to advertise given the local role
+pub const fn expected_peer_role(role: BgpRoleValue) -> BgpRoleValue {
+    match role {
+        BgpRoleValue::Provider => BgpRoleValue::Customer,
+        BgpRoleValue::Customer => BgpRoleValue::Provider,
+        BgpRoleValue::RS => BgpRoleValue::RsClient,
+        BgpRoleValue::RsClient => BgpRoleValue::RS,
+        BgpRoleValue::Peer => BgpRoleValue::Peer,
+    }
+}
+
+/// Add the BGP Role capability to an OPEN message, unless the message already
+/// carries one
+pub(crate) fn add_role_capability(open: BgpOpenMessage, role: BgpRoleValue) -> BgpOpenMessage {
+    let has_role = open
+        .capabilities()
+        .iter()
+        .any(|cap| matches!(cap, BgpCapability::BgpRole(_)));
+    if has_role {
+        return open;
+    }
+    let mut params = open.params().clone();
+    params.push(BgpOpenMessageParameter::Capabilities(vec![
+        BgpCapability::BgpRole(BgpRoleCapability::new(role)),
+    ]));
+    BgpOpenMessage::new(open.my_as(), open.hold_time(), open.bgp_id(), params)
+}
+
+/// Check the BGP Role capability received from the peer against the local
+/// role, according to RFC9234 Section 4.2: the session is rejected with a
+/// Role Mismatch notification when the roles don't correspond, when the peer
+/// advertises multiple different roles, or in `strict` mode when the peer
+/// doesn't advertise its role.
+pub fn check_open_role(
+    role: BgpRoleValue,
+    strict: bool,
+    open: &BgpOpenMessage,
+) -> Result<(), OpenMessageError> {
+    let mut peer_roles = open.capabilities().into_iter().filter_map(|cap| match cap {
+        BgpCapability::BgpRole(role) => Some(role.role()),
+        _ => None,
+    });
+    let peer_role = match peer_roles.next() {
+        Some(peer_role) => peer_role,
+        None if strict => return Err(OpenMessageError::RoleMismatch { value: vec![] }),
+        None => return Ok(()),
+    };
+    if peer_roles.any(|other| other != peer_role) || peer_role != expected_peer_role(role) {
+        return Err(OpenMessageError::RoleMismatch {
+            value: vec![peer_role.into()],
+        });
+    }
+    Ok(())
+}
+
+fn only_to_customer(attributes: &[PathAttribute]) -> Option<u32> {
+    attributes.iter().find_map(|attr| match attr.value() {
+        PathAttributeValue::OnlyToCustomer(otc) => Some(otc.asn()),
+        _ => None,
+    })
+}
+
+fn add_only_to_customer(attributes: &mut Vec<PathAttribute>, asn: u32) {
+    if let Ok(otc) = PathAttribute::from(
+        true,
+        true,
+        false,
+        false,
+        PathAttributeValue::OnlyToCustomer(OnlyToCustomer::new(asn)),
+    ) {
+        insert_attribute(attributes, otc);
+    }
+}
+
+/// OTC ingress procedure (RFC9234 Section 5) for a route received from the
+/// peer `peer_asn`. Returns `false` if the route is a leak and must be
+/// considered ineligible, otherwise the OTC attribute is added if required.
+pub fn otc_ingress(role: BgpRoleValue, peer_asn: u32, attributes: &mut Vec<PathAttribute>) -> bool {
+    match (role, only_to_customer(attributes)) {
+        // Received from a Customer or an RS-Client
+        (BgpRoleValue::Provider | BgpRoleValue::RS, Some(_)) => false,
+        (BgpRoleValue::Peer, Some(asn)) => asn == peer_asn,
+        // Received from a Provider, a Peer or an RS
+        (BgpRoleValue::Customer | BgpRoleValue::Peer | BgpRoleValue::RsClient, None) => {
+            add_only_to_customer(attributes, peer_asn);
+            true
+        }
+        _ => true,
+    }
+}
+
+/// OTC egress procedure (RFC9234 Section 5) for a route advertised to the
+/// peer. Returns `false` if the route must not be advertised, otherwise the
+/// OTC attribute is added with `my_asn` if required.
+pub fn otc_egress(role: BgpRoleValue, my_asn: u32, attributes: &mut Vec<PathAttribute>) -> bool {
+    match (role, only_to_customer(attributes)) {
+        // Sent to a Provider, a Peer or an RS
+        (BgpRoleValue::Customer | BgpRoleValue::Peer | BgpRoleValue::RsClient, Some(_)) => false,
+        // Sent to a Customer, a Peer or an RS-Client
+        (BgpRoleValue::Provider | BgpRoleValue::Peer | BgpRoleValue::RS, None) => {
+            add_only_to_customer(attributes, my_asn);
+            true
+        }
+        _ => true,
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Route origin validation ([RFC6811](https://datatracker.ietf.org/doc/html/rfc6811))
+//! of the routes received from the peers, enabled with the `rpki` feature.
+//!
+//! A [RouteOriginValidator] is attached to the peers with
+//! [crate::supervisor::PeersSupervisor::set_route_origin_validator] and is
+//! applied to the updates received on established sessions after the other
+//! ingress procedures:
+//! * Invalid routes are treated as withdrawn when
+//!   [RouteOriginValidator::reject_invalid] is set.
+//! * The validation state is signaled to the internal peers with the BGP
+//!   Prefix Origin Validation State Extended Community
+//!   ([RFC8097](https://datatracker.ietf.org/doc/html/rfc8097)) when
+//!   [RouteOriginValidator::validation_state_community] is set. The state
+//!   signaled by internal peers is trusted, while the community received from
+//!   external peers is removed.
+//!
+//! * Routes with an AS path that is `Invalid` according to the ASPA records
+//!   ([draft-ietf-sidrops-aspa-verification](https://datatracker.ietf.org/doc/html/draft-ietf-sidrops-aspa-verification))
+//!   are treated as withdrawn when [RouteOriginValidator::aspa_verification]
+//!   is set. Only the routes of external peers with a configured BGP role are
+//!   verified, the role tells if the path is verified as upstream or
+//!   downstream.
+//!
+//! Updates carrying routes with different validation states are split, one
+//! update per state. The routes received from the peers are retained before
+//! validation, so when the validated data changes they're validated again
+//! locally, without requesting a route refresh from the peers
+//! ([RFC8481](https://datatracker.ietf.org/doc/html/rfc8481#section-4)).
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use netgauze_bgp_pkt::{
+    iana::OriginValidationState,
+    path_attribute::{MpReach, MpUnreach, PathAttribute, PathAttributeValue},
+    update::BgpUpdateMessage,
+};
+use netgauze_iana::address_family::AddressType;
+use netgauze_rpki::{
+    aspa::{verify_route, AspaDirection, AspaVerificationState},
+    client::{RpkiData, RtrClient},
+    rov::{origin_validation_state, route_origin, set_origin_validation_state},
+    vrp::VrpTable,
+};
+
+use crate::rib::{
+    announce_updates, withdraw_updates, Route, RouteKey, UpdateRoutes, BGP_MAX_MESSAGE_LENGTH,
+};
+
+/// Validates the origin of the received routes against the data of an RTR
+/// client, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct RouteOriginValidator {
+    data: watch::Receiver<Arc<RpkiData>>,
+    reject_invalid: bool,
+    validation_state_community: bool,
+    aspa_verification: bool,
+}
+
+impl RouteOriginValidator {
+    pub fn new(data: watch::Receiver<Arc<RpkiData>>) -> Self {
+        Self {
+            data,
+            reject_invalid: false,
+            validation_state_community: false,
+            aspa_verification: false,
+        }
+    }
+
+    pub fn from_client(client: &RtrClient) -> Self {
+        Self::new(client.subscribe())
+    }
+
+    /// Validator with a fixed set of VRPs
+    pub fn from_table(table: VrpTable) -> Self {
+        let (_, data) = watch::channel(Arc::new(RpkiData::from(table)));
+        Self::new(data)
+    }
+
+    pub const fn reject_invalid(&self) -> bool {
+        self.reject_invalid
+    }
+
+    pub const fn validation_state_community(&self) -> bool {
+        self.validation_state_community
+    }
+
+    pub const fn aspa_verification(&self) -> bool {
+        self.aspa_verification
+    }
+
+    /// Treat the invalid routes as withdrawn
+    pub fn with_reject_invalid(mut self, value: bool) -> Self {
+        self.reject_invalid = value;
+        self
+    }
+
+    /// Mark the accepted routes with the BGP Prefix Origin Validation State
+    /// Extended Community
+    pub fn with_validation_state_community(mut self, value: bool) -> Self {
+        self.validation_state_community = value;
+        self
+    }
+
+    /// Treat the routes with an `Invalid` AS path according to the ASPA
+    /// records as withdrawn
+    pub fn with_aspa_verification(mut self, value: bool) -> Self {
+        self.aspa_verification = value;
+        self
+    }
+
+    pub fn data(&self) -> Arc<RpkiData> {
+        self.data.borrow().clone()
+    }
+
+    /// Make [RouteOriginValidator::changed] return immediately, used to
+    /// validate the routes already received when the validator is attached
+    pub(crate) fn mark_changed(&mut self) {
+        self.data.mark_changed();
+    }
+
+    /// Mark the current data as seen, it was used to validate the routes
+    pub(crate) fn mark_unchanged(&mut self) {
+        self.data.mark_unchanged();
+    }
+
+    /// Wait until the validated data changes, never returns if the data can't
+    /// change anymore
+    pub(crate) async fn changed(&mut self) {
+        if self.data.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Apply the validation to an update received from a peer. `local_as` is
+    /// the origin of the routes with an empty AS path, and `internal` is set
+    /// for the peers within the local AS or confederation. The AS path is
+    /// verified with the ASPA records when `aspa_direction` is given, see
+    /// [netgauze_rpki::aspa::verify_route] for `neighbor_as`.
+    pub(crate) fn validate_update(
+        &self,
+        update: BgpUpdateMessage,
+        local_as: u32,
+        internal: bool,
+        aspa_direction: Option<AspaDirection>,
+        neighbor_as: Option<u32>,
+    ) -> Vec<BgpUpdateMessage> {
+        let update_routes = UpdateRoutes::from(&update);
+        if update_routes.announced().is_empty() {
+            if internal || origin_validation_state(update.path_attributes()).is_none() {
+                return vec![update];
+            }
+            let mut attributes = update.path_attributes().clone();
+            set_origin_validation_state(&mut attributes, None);
+            return vec![BgpUpdateMessage::new(
+                update.withdraw_routes().clone(),
+                attributes,
+                update.nlri().clone(),
+            )];
+        }
+        let data = self.data();
+        let signaled = if internal {
+            origin_validation_state(update.path_attributes())
+        } else {
+            None
+        };
+        let origin = route_origin(update.path_attributes(), local_as);
+        let states: Vec<OriginValidationState> = update_routes
+            .announced()
+            .iter()
+            .map(|(key, _)| signaled.unwrap_or_else(|| data.vrps().validate(&key.prefix(), origin)))
+            .collect();
+        let aspa_invalid = self.aspa_verification
+            && aspa_direction.is_some_and(|direction| {
+                verify_route(
+                    data.aspas(),
+                    update.path_attributes(),
+                    direction,
+                    neighbor_as,
+                ) == AspaVerificationState::Invalid
+            });
+        let rejected = |state: &OriginValidationState| {
+            aspa_invalid || (self.reject_invalid && *state == OriginValidationState::Invalid)
+        };
+        let first_state = states[0];
+        if states.iter().all(|state| *state == first_state) && !rejected(&first_state) {
+            let (withdrawn_routes, mut attributes, nlri) = (
+                update.withdraw_routes().clone(),
+                update.path_attributes().clone(),
+                update.nlri().clone(),
+            );
+            self.mark(&mut attributes, first_state, internal);
+            return vec![BgpUpdateMessage::new(withdrawn_routes, attributes, nlri)];
+        }
+
+        let mut updates = vec![];
+        // Routes of the address families that are not validated are kept as is
+        if let Some(mut update) = not_validated_remainder(&update) {
+            if !internal {
+                let mut attributes = update.path_attributes().clone();
+                set_origin_validation_state(&mut attributes, None);
+                update = BgpUpdateMessage::new(vec![], attributes, vec![]);
+            }
+            updates.push(update);
+        }
+        let mut withdrawn: Vec<RouteKey> = update_routes.withdrawn().clone();
+        // Announced routes grouped by address type and validation state
+        let mut announced: Vec<(AddressType, OriginValidationState, &Route, Vec<&RouteKey>)> =
+            vec![];
+        for ((key, route), state) in update_routes.announced().iter().zip(states) {
+            if rejected(&state) {
+                withdrawn.push(*key);
+                continue;
+            }
+            match announced
+                .iter_mut()
+                .find(|(address_type, group_state, _, _)| {
+                    *address_type == key.address_type() && *group_state == state
+                }) {
+                Some((_, _, _, keys)) => keys.push(key),
+                None => announced.push((key.address_type(), state, route, vec![key])),
+            }
+        }
+        let mut address_types: Vec<AddressType> = vec![];
+        for key in &withdrawn {
+            if !address_types.contains(&key.address_type()) {
+                address_types.push(key.address_type());
+            }
+        }
+        updates.extend(address_types.into_iter().flat_map(|address_type| {
+            withdraw_updates(address_type, &withdrawn, BGP_MAX_MESSAGE_LENGTH)
+        }));
+        for (address_type, state, route, keys) in announced {
+            let mut attributes = route.attributes().clone();
+            self.mark(&mut attributes, state, internal);
+            let route = Route::new(attributes, route.mp_next_hop());
+            updates.extend(announce_updates(
+                address_type,
+                &route,
+                &keys,
+                BGP_MAX_MESSAGE_LENGTH,
+            ));
+        }
+        updates
+    }
+
+    fn mark(
+        &self,
+        attributes: &mut Vec<PathAttribute>,
+        state: OriginValidationState,
+        internal: bool,
+    ) {
+        if self.validation_state_community {
+            set_origin_validation_state(attributes, Some(state));
+        } else if !internal {
+            set_origin_validation_state(attributes, None);
+        }
+    }
+}
+
+/// The parts of an update that are not covered by [UpdateRoutes], i.e., the
+/// `MP_REACH_NLRI` and `MP_UNREACH_NLRI` of the other address families
+fn not_validated_remainder(update: &BgpUpdateMessage) -> Option<BgpUpdateMessage> {
+    let validated = |value: &PathAttributeValue| match value {
+        PathAttributeValue::MpReach(mp_reach) => matches!(
+            mp_reach,
+            MpReach::Ipv4Unicast { .. }
+                | MpReach::Ipv4Multicast { .. }
+                | MpReach::Ipv6Unicast { .. }
+                | MpReach::Ipv6Multicast { .. }
+        ),
+        PathAttributeValue::MpUnreach(mp_unreach) => matches!(
+            mp_unreach,
+            MpUnreach::Ipv4Unicast { .. }
+                | MpUnreach::Ipv4Multicast { .. }
+                | MpUnreach::Ipv6Unicast { .. }
+                | MpUnreach::Ipv6Multicast { .. }
+        ),
+        _ => false,
+    };
+    let has_other = update.path_attributes().iter().any(|attr| {
+        matches!(
+            attr.value(),
+            PathAttributeValue::MpReach(_) | PathAttributeValue::MpUnreach(_)
+        ) && !validated(attr.value())
+    });
+    if !has_other {
+        return None;
+    }
+    let attributes = update
+        .path_attributes()
+        .iter()
+        .filter(|attr| !validated(attr.value()))
+        .cloned()
+        .collect();
+    Some(BgpUpdateMessage::new(vec![], attributes, vec![]))
+}
@@ -0,0 +1,1021 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Toolkit to run multiple BGP speakers within a single process without real
+//! sockets, so multi-speaker scenarios can be tested deterministically.
+//!
+//! * [SimNetwork] carries the sessions over in-memory duplex streams
+//!   ([SimStream]). A link between two addresses can be brought down, the
+//!   traffic over it is then silently dropped.
+//! * [SimClock] drives the FSM timers from the paused tokio clock, the time
+//!   only advances when all the tasks are idle, so the hold timers and the
+//!   connect retries take no wall-clock time.
+//! * [SimTopologyBuilder] creates one [SimSpeaker] per address, each with its
+//!   own [PeersSupervisor], and the sessions between them. A speaker selects
+//!   a best route per prefix among the locally originated routes and the
+//!   routes received from its peers (shortest AS_PATH, then lowest IGP metric
+//!   to the next hop, then lowest peer address) and advertises it to all its
+//!   peers. When the speaker has a [NextHopResolver], the received routes
+//!   with an unreachable next hop aren't selected and the selection is run
+//!   again when the resolution of a next hop changes.
+//!
+//! The toolkit must be used within a current thread tokio runtime.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Formatter},
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf},
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::BgpCapability,
+    codec::BgpCodec,
+    path_attribute::{AsPath, PathAttributeValue},
+};
+
+use crate::{
+    connection::ActiveConnect,
+    events::{BgpEvent, UpdateTreatment},
+    export::{ExportRoute, RouteSource},
+    fsm::FsmState,
+    nexthop::{NextHopResolver, NextHopTracker},
+    peer::{EchoCapabilitiesPolicy, PeerConfig, PeerProperties},
+    peer_controller::{PeerHandle, PeerStateResult},
+    rib::{Route, RouteKey, UpdateRoutes},
+    supervisor::PeersSupervisor,
+};
+
+/// Port the simulated speakers listen on
+pub const SIM_BGP_PORT: u16 = 179;
+
+/// Capacity in bytes of each direction of a [SimStream]
+pub const DEFAULT_SIM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// First source port assigned to the connections
+const SIM_EPHEMERAL_PORT: u16 = 49152;
+
+const SIM_HOLD_TIME: u16 = 180;
+
+/// In-memory network connecting [SimListener]s and [SimActiveConnect]s
+#[derive(Debug, Clone)]
+pub struct SimNetwork {
+    inner: Arc<Mutex<SimNetworkInner>>,
+}
+
+#[derive(Debug)]
+struct SimNetworkInner {
+    buffer_size: usize,
+    listeners: HashMap<SocketAddr, mpsc::UnboundedSender<(SimStream, SocketAddr)>>,
+    /// Whether the link between two addresses is up, indexed by the ordered
+    /// pair of addresses
+    links: HashMap<(IpAddr, IpAddr), Arc<AtomicBool>>,
+    next_port: u16,
+}
+
+impl Default for SimNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimNetwork {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(SimNetworkInner {
+                buffer_size: DEFAULT_SIM_BUFFER_SIZE,
+                listeners: HashMap::new(),
+                links: HashMap::new(),
+                next_port: SIM_EPHEMERAL_PORT,
+            })),
+        }
+    }
+
+    /// Capacity in bytes of each direction of the new connections
+    pub fn with_buffer_size(self, buffer_size: usize) -> Self {
+        self.inner.lock().unwrap().buffer_size = buffer_size;
+        self
+    }
+
+    /// Accept the connections to `addr`
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<SimListener> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner
+            .listeners
+            .get(&addr)
+            .is_some_and(|listener| !listener.is_closed())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{addr} is already bound"),
+            ));
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        inner.listeners.insert(addr, tx);
+        Ok(SimListener {
+            local_addr: addr,
+            rx,
+        })
+    }
+
+    /// Open a connection from `local_ip` to the listener bound to `peer_addr`
+    pub fn connect(&self, local_ip: IpAddr, peer_addr: SocketAddr) -> io::Result<SimStream> {
+        let mut inner = self.inner.lock().unwrap();
+        let link = inner.link(local_ip, peer_addr.ip());
+        if !link.load(Ordering::Relaxed) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("link between {local_ip} and {} is down", peer_addr.ip()),
+            ));
+        }
+        let Some(listener) = inner.listeners.get(&peer_addr).cloned() else {
+            return Err(io::ErrorKind::ConnectionRefused.into());
+        };
+        let local_addr = SocketAddr::new(local_ip, inner.next_port);
+        inner.next_port = inner.next_port.checked_add(1).unwrap_or(SIM_EPHEMERAL_PORT);
+        let (client, server) = tokio::io::duplex(inner.buffer_size);
+        let server = SimStream {
+            inner: server,
+            link: link.clone(),
+            local_addr: peer_addr,
+            peer_addr: local_addr,
+        };
+        if listener.send((server, local_addr)).is_err() {
+            inner.listeners.remove(&peer_addr);
+            return Err(io::ErrorKind::ConnectionRefused.into());
+        }
+        Ok(SimStream {
+            inner: client,
+            link,
+            local_addr,
+            peer_addr,
+        })
+    }
+
+    /// Bring the link between two addresses up or down. While down, the
+    /// established connections silently drop the traffic and the new
+    /// connections fail.
+    pub fn set_link_up(&self, a: IpAddr, b: IpAddr, up: bool) {
+        let link = self.inner.lock().unwrap().link(a, b);
+        log::info!(
+            "Simulated link between {a} and {b} is {}",
+            if up { "up" } else { "down" }
+        );
+        link.store(up, Ordering::Relaxed);
+    }
+
+    pub fn is_link_up(&self, a: IpAddr, b: IpAddr) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .link(a, b)
+            .load(Ordering::Relaxed)
+    }
+}
+
+impl SimNetworkInner {
+    fn link(&mut self, a: IpAddr, b: IpAddr) -> Arc<AtomicBool> {
+        let key = if a <= b { (a, b) } else { (b, a) };
+        self.links
+            .entry(key)
+            .or_insert_with(|| Arc::new(AtomicBool::new(true)))
+            .clone()
+    }
+}
+
+/// Receives the connections opened to an address of a [SimNetwork]
+#[derive(Debug)]
+pub struct SimListener {
+    local_addr: SocketAddr,
+    rx: mpsc::UnboundedReceiver<(SimStream, SocketAddr)>,
+}
+
+impl SimListener {
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Wait for the next connection, returns the stream and the remote address
+    pub async fn accept(&mut self) -> io::Result<(SimStream, SocketAddr)> {
+        self.rx
+            .recv()
+            .await
+            .ok_or_else(|| io::ErrorKind::NotConnected.into())
+    }
+}
+
+/// One end of an in-memory connection over a [SimNetwork]
+#[derive(Debug)]
+pub struct SimStream {
+    inner: DuplexStream,
+    link: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+}
+
+impl SimStream {
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub const fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    fn is_link_up(&self) -> bool {
+        self.link.load(Ordering::Relaxed)
+    }
+}
+
+impl AsyncRead for SimStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.is_link_up() {
+            // Nothing is received while the link is down, not even the remote
+            // end closing the connection
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for SimStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if !self.is_link_up() {
+            return Poll::Ready(Ok(buf.len()));
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.is_link_up() {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.is_link_up() {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Initiate BGP sessions over a [SimNetwork] from a given local address
+#[derive(Debug, Clone)]
+pub struct SimActiveConnect {
+    network: SimNetwork,
+    local_ip: IpAddr,
+}
+
+impl SimActiveConnect {
+    pub const fn new(network: SimNetwork, local_ip: IpAddr) -> Self {
+        Self { network, local_ip }
+    }
+}
+
+#[async_trait]
+impl ActiveConnect<SocketAddr, SimStream, BgpCodec> for SimActiveConnect {
+    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<SimStream> {
+        self.network.connect(self.local_ip, peer_addr)
+    }
+}
+
+/// Virtual clock of the simulation, backed by the paused tokio clock
+#[derive(Debug, Clone, Copy)]
+pub struct SimClock {
+    start: Instant,
+}
+
+impl SimClock {
+    /// Pause the tokio clock, panics if it's not called from a current thread
+    /// runtime or if the clock is already paused
+    pub fn start() -> Self {
+        tokio::time::pause();
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// Virtual time elapsed since the clock started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Let the speakers run for `duration` of virtual time, the timers that
+    /// expire meanwhile fire in order
+    pub async fn run_for(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    /// Move the clock forward at once, the timers that expire meanwhile fire
+    /// together when the tasks are polled next
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::advance(duration).await;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SimTopologyError {
+    DuplicateSpeaker(IpAddr),
+    UnknownSpeaker(IpAddr),
+    DuplicateLink(IpAddr, IpAddr),
+    /// The address of the speaker is already bound on the network
+    AddressInUse(IpAddr),
+}
+
+impl Display for SimTopologyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateSpeaker(ip) => write!(f, "speaker {ip} is defined more than once"),
+            Self::UnknownSpeaker(ip) => write!(f, "link to the undefined speaker {ip}"),
+            Self::DuplicateLink(a, b) => {
+                write!(f, "link between {a} and {b} is defined more than once")
+            }
+            Self::AddressInUse(ip) => write!(f, "speaker address {ip} is already in use"),
+        }
+    }
+}
+
+impl std::error::Error for SimTopologyError {}
+
+/// BGP session between the speakers of two addresses
+#[derive(Debug, Clone)]
+pub struct SimLink {
+    a: IpAddr,
+    b: IpAddr,
+    a_config: PeerConfig,
+    b_config: PeerConfig,
+    capabilities: Vec<BgpCapability>,
+}
+
+impl SimLink {
+    pub fn new(a: IpAddr, b: IpAddr) -> Self {
+        Self {
+            a,
+            b,
+            a_config: PeerConfig::default(),
+            b_config: PeerConfig::default(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Config of the peer on the speaker `a`
+    pub fn with_a_config(mut self, config: PeerConfig) -> Self {
+        self.a_config = config;
+        self
+    }
+
+    /// Config of the peer on the speaker `b`
+    pub fn with_b_config(mut self, config: PeerConfig) -> Self {
+        self.b_config = config;
+        self
+    }
+
+    /// Capabilities advertised by both ends, in addition to the four-octet AS
+    /// number capability
+    pub fn with_capabilities(mut self, capabilities: Vec<BgpCapability>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub const fn a(&self) -> IpAddr {
+        self.a
+    }
+
+    pub const fn b(&self) -> IpAddr {
+        self.b
+    }
+}
+
+/// Define the speakers and the sessions between them
+#[derive(Debug, Default)]
+pub struct SimTopologyBuilder {
+    network: SimNetwork,
+    speakers: Vec<(IpAddr, u32, Ipv4Addr)>,
+    links: Vec<SimLink>,
+    resolvers: HashMap<IpAddr, Arc<dyn NextHopResolver>>,
+}
+
+impl SimTopologyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the speakers on the given network rather than a new one
+    pub fn with_network(mut self, network: SimNetwork) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Add a speaker listening on `ip`
+    pub fn speaker(mut self, ip: IpAddr, asn: u32, bgp_id: Ipv4Addr) -> Self {
+        self.speakers.push((ip, asn, bgp_id));
+        self
+    }
+
+    pub fn link(mut self, link: SimLink) -> Self {
+        self.links.push(link);
+        self
+    }
+
+    /// Resolve the next hops of the routes received by the speaker `ip`
+    pub fn next_hop_resolver(mut self, ip: IpAddr, resolver: Arc<dyn NextHopResolver>) -> Self {
+        self.resolvers.insert(ip, resolver);
+        self
+    }
+
+    /// Create the speakers and their peers, the peers are not started. Must
+    /// be called within a tokio runtime.
+    pub fn build(self) -> Result<SimTopology, SimTopologyError> {
+        let mut definitions = BTreeMap::new();
+        for (ip, asn, bgp_id) in &self.speakers {
+            if definitions.insert(*ip, (*asn, *bgp_id)).is_some() {
+                return Err(SimTopologyError::DuplicateSpeaker(*ip));
+            }
+        }
+        if let Some(ip) = self
+            .resolvers
+            .keys()
+            .find(|ip| !definitions.contains_key(ip))
+        {
+            return Err(SimTopologyError::UnknownSpeaker(*ip));
+        }
+        let mut peers: BTreeMap<IpAddr, Vec<(IpAddr, PeerConfig, Vec<BgpCapability>)>> =
+            BTreeMap::new();
+        for link in &self.links {
+            for ip in [link.a, link.b] {
+                if !definitions.contains_key(&ip) {
+                    return Err(SimTopologyError::UnknownSpeaker(ip));
+                }
+            }
+            let a_peers = peers.entry(link.a).or_default();
+            if link.a == link.b || a_peers.iter().any(|(ip, _, _)| *ip == link.b) {
+                return Err(SimTopologyError::DuplicateLink(link.a, link.b));
+            }
+            a_peers.push((link.b, link.a_config.clone(), link.capabilities.clone()));
+            peers.entry(link.b).or_default().push((
+                link.a,
+                link.b_config.clone(),
+                link.capabilities.clone(),
+            ));
+        }
+        let mut speakers = BTreeMap::new();
+        for (ip, (asn, bgp_id)) in &definitions {
+            let speaker_peers = peers
+                .remove(ip)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(peer_ip, config, capabilities)| {
+                    let (peer_asn, peer_bgp_id) = definitions[&peer_ip];
+                    SimPeer {
+                        ip: peer_ip,
+                        asn: peer_asn,
+                        bgp_id: peer_bgp_id,
+                        config,
+                        capabilities,
+                    }
+                })
+                .collect();
+            let speaker = SimSpeaker::new(
+                &self.network,
+                *ip,
+                *asn,
+                *bgp_id,
+                speaker_peers,
+                self.resolvers.get(ip).cloned(),
+            )
+            .map_err(|_| SimTopologyError::AddressInUse(*ip))?;
+            speakers.insert(*ip, speaker);
+        }
+        Ok(SimTopology {
+            network: self.network,
+            speakers,
+        })
+    }
+}
+
+/// Speakers created by a [SimTopologyBuilder]
+#[derive(Debug)]
+pub struct SimTopology {
+    network: SimNetwork,
+    speakers: BTreeMap<IpAddr, SimSpeaker>,
+}
+
+impl SimTopology {
+    pub const fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    pub fn speaker(&self, ip: IpAddr) -> Option<&SimSpeaker> {
+        self.speakers.get(&ip)
+    }
+
+    pub fn speakers(&self) -> impl Iterator<Item = &SimSpeaker> {
+        self.speakers.values()
+    }
+
+    /// Start the peers of all the speakers
+    pub fn start(&self) {
+        for speaker in self.speakers.values() {
+            speaker.start();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SimPeer {
+    ip: IpAddr,
+    asn: u32,
+    bgp_id: Ipv4Addr,
+    config: PeerConfig,
+    capabilities: Vec<BgpCapability>,
+}
+
+/// Route selected by a [SimSpeaker] for a prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimBestRoute {
+    /// `None` for the locally originated routes
+    peer_ip: Option<IpAddr>,
+    route: Route,
+    igp_metric: Option<u32>,
+}
+
+impl SimBestRoute {
+    pub const fn peer_ip(&self) -> Option<IpAddr> {
+        self.peer_ip
+    }
+
+    pub const fn route(&self) -> &Route {
+        &self.route
+    }
+
+    /// IGP metric to the next hop, `None` for the locally originated routes
+    /// and when the speaker has no [NextHopResolver]
+    pub const fn igp_metric(&self) -> Option<u32> {
+        self.igp_metric
+    }
+
+    /// Number of ASes in the AS_PATH of the route
+    pub fn as_path_len(&self) -> usize {
+        as_path(&self.route).map_or(0, AsPath::path_length)
+    }
+}
+
+/// Sessions and routes of a [SimSpeaker]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimSpeakerState {
+    sessions: HashMap<IpAddr, FsmState>,
+    local_routes: HashMap<RouteKey, Route>,
+    received_routes: HashMap<IpAddr, HashMap<RouteKey, Route>>,
+    best_routes: HashMap<RouteKey, SimBestRoute>,
+}
+
+impl SimSpeakerState {
+    /// FSM state of the session with the peer, [FsmState::Idle] until the
+    /// peer reports its first event
+    pub fn session_state(&self, peer_ip: IpAddr) -> FsmState {
+        self.sessions
+            .get(&peer_ip)
+            .copied()
+            .unwrap_or(FsmState::Idle)
+    }
+
+    pub fn is_established(&self, peer_ip: IpAddr) -> bool {
+        self.session_state(peer_ip) == FsmState::Established
+    }
+
+    /// Routes received from the peer over the current session
+    pub fn received_routes(&self, peer_ip: IpAddr) -> Option<&HashMap<RouteKey, Route>> {
+        self.received_routes.get(&peer_ip)
+    }
+
+    pub fn best_route(&self, key: &RouteKey) -> Option<&SimBestRoute> {
+        self.best_routes.get(key)
+    }
+
+    pub const fn best_routes(&self) -> &HashMap<RouteKey, SimBestRoute> {
+        &self.best_routes
+    }
+
+    /// Select the best route for the prefix, returns true if it changed
+    fn select(
+        &mut self,
+        key: &RouteKey,
+        my_asn: u32,
+        next_hops: Option<&NextHopTracker<(IpAddr, RouteKey)>>,
+    ) -> bool {
+        let best = match self.local_routes.get(key) {
+            Some(route) => Some(SimBestRoute {
+                peer_ip: None,
+                route: route.clone(),
+                igp_metric: None,
+            }),
+            None => self
+                .received_routes
+                .iter()
+                .filter_map(|(peer_ip, routes)| routes.get(key).map(|route| (*peer_ip, route)))
+                .filter(|(_, route)| !as_path(route).is_some_and(|path| contains_asn(path, my_asn)))
+                .filter_map(|(peer_ip, route)| {
+                    let igp_metric = match next_hops {
+                        None => None,
+                        // Routes without next hop are resolved at no cost
+                        Some(next_hops) => match next_hops.status(&(peer_ip, *key)) {
+                            None => Some(0),
+                            Some(status) => Some(status.metric()?),
+                        },
+                    };
+                    Some((peer_ip, route, igp_metric))
+                })
+                .min_by_key(|(peer_ip, route, igp_metric)| {
+                    (
+                        as_path(route).map_or(0, AsPath::path_length),
+                        *igp_metric,
+                        *peer_ip,
+                    )
+                })
+                .map(|(peer_ip, route, igp_metric)| SimBestRoute {
+                    peer_ip: Some(peer_ip),
+                    route: route.clone(),
+                    igp_metric,
+                }),
+        };
+        if self.best_routes.get(key) == best.as_ref() {
+            return false;
+        }
+        match best {
+            Some(best) => self.best_routes.insert(*key, best),
+            None => self.best_routes.remove(key),
+        };
+        true
+    }
+}
+
+fn as_path(route: &Route) -> Option<&AsPath> {
+    route
+        .attributes()
+        .iter()
+        .find_map(|attr| match attr.value() {
+            PathAttributeValue::AsPath(as_path) => Some(as_path),
+            _ => None,
+        })
+}
+
+fn contains_asn(as_path: &AsPath, asn: u32) -> bool {
+    match as_path {
+        AsPath::As2PathSegments(segments) => segments
+            .iter()
+            .any(|segment| segment.as_numbers().iter().any(|n| *n as u32 == asn)),
+        AsPath::As4PathSegments(segments) => segments
+            .iter()
+            .any(|segment| segment.as_numbers().contains(&asn)),
+    }
+}
+
+type SimSupervisor = PeersSupervisor<IpAddr, SocketAddr, SimStream>;
+
+#[derive(Debug)]
+struct SimSpeakerInner {
+    ip: IpAddr,
+    asn: u32,
+    bgp_id: Ipv4Addr,
+    /// ASN and BGP ID of the peers
+    peers: HashMap<IpAddr, (u32, Ipv4Addr)>,
+    supervisor: Mutex<SimSupervisor>,
+    state: watch::Sender<SimSpeakerState>,
+    /// Next hops of the received routes, keyed by peer address and prefix,
+    /// their IGP metric is used by the route selection
+    next_hops: Option<Mutex<NextHopTracker<(IpAddr, RouteKey)>>>,
+}
+
+/// A BGP speaker of a [SimTopology]
+#[derive(Debug, Clone)]
+pub struct SimSpeaker {
+    inner: Arc<SimSpeakerInner>,
+}
+
+impl SimSpeaker {
+    fn new(
+        network: &SimNetwork,
+        ip: IpAddr,
+        asn: u32,
+        bgp_id: Ipv4Addr,
+        peers: Vec<SimPeer>,
+        resolver: Option<Arc<dyn NextHopResolver>>,
+    ) -> io::Result<Self> {
+        let listener = network.bind(SocketAddr::new(ip, SIM_BGP_PORT))?;
+        let peer_ids = peers
+            .iter()
+            .map(|peer| (peer.ip, (peer.asn, peer.bgp_id)))
+            .collect();
+        let mut supervisor = SimSupervisor::new(asn, bgp_id);
+        // The peers withdraw the routes with an unreachable next hop
+        supervisor.set_next_hop_resolver(resolver.clone());
+        let mut handles = HashMap::new();
+        let mut receivers = vec![];
+        for peer in peers {
+            let properties = PeerProperties::new(
+                asn,
+                peer.asn,
+                bgp_id,
+                SocketAddr::new(peer.ip, SIM_BGP_PORT),
+                false,
+            );
+            let policy: EchoCapabilitiesPolicy<SocketAddr, SimStream, BgpCodec> =
+                EchoCapabilitiesPolicy::new(
+                    asn,
+                    true,
+                    bgp_id,
+                    SIM_HOLD_TIME,
+                    peer.capabilities,
+                    Vec::new(),
+                );
+            let (rx, handle) = supervisor
+                .create_peer(
+                    peer.ip,
+                    properties,
+                    peer.config,
+                    SimActiveConnect::new(network.clone(), ip),
+                    policy,
+                )
+                .map_err(|_| io::Error::from(io::ErrorKind::AlreadyExists))?;
+            handles.insert(peer.ip, handle);
+            receivers.push((peer.ip, rx));
+        }
+        let (state, _) = watch::channel(SimSpeakerState::default());
+        let inner = Arc::new(SimSpeakerInner {
+            ip,
+            asn,
+            bgp_id,
+            peers: peer_ids,
+            supervisor: Mutex::new(supervisor),
+            state,
+            next_hops: resolver.map(|resolver| Mutex::new(NextHopTracker::new(resolver))),
+        });
+        for (peer_ip, mut rx) in receivers {
+            let inner = inner.clone();
+            tokio::spawn(async move {
+                while let Some(event) = rx.recv().await {
+                    inner.handle_peer_event(peer_ip, event);
+                }
+            });
+        }
+        tokio::spawn(accept_connections(ip, listener, handles));
+        if inner.next_hops.is_some() {
+            tokio::spawn(track_next_hops(inner.clone()));
+        }
+        Ok(Self { inner })
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.inner.ip
+    }
+
+    pub fn asn(&self) -> u32 {
+        self.inner.asn
+    }
+
+    pub fn bgp_id(&self) -> Ipv4Addr {
+        self.inner.bgp_id
+    }
+
+    /// Start all the peers of the speaker, in the order of their addresses so
+    /// the simulation runs the same way every time
+    pub fn start(&self) {
+        let mut supervisor = self.inner.supervisor.lock().unwrap();
+        let mut peer_ips = supervisor.peer_keys();
+        peer_ips.sort();
+        for peer_ip in peer_ips {
+            if let Some(handle) = supervisor.peer_handler(&peer_ip) {
+                if let Err(err) = handle.start() {
+                    log::error!("[{}][{peer_ip}] Couldn't start peer: {err}", self.ip());
+                }
+            }
+        }
+    }
+
+    pub fn peer_handle(&self, peer_ip: IpAddr) -> Option<PeerHandle<SocketAddr, SimStream>> {
+        self.inner.supervisor.lock().unwrap().peer_handler(&peer_ip)
+    }
+
+    /// Originate routes from the speaker, they take precedence over the
+    /// received routes for the same prefix
+    pub fn announce_routes(&self, routes: Vec<(RouteKey, Route)>) {
+        let keys: Vec<RouteKey> = routes.iter().map(|(key, _)| *key).collect();
+        self.inner.state.send_modify(|state| {
+            state.local_routes.extend(routes);
+        });
+        self.inner.select(&keys);
+    }
+
+    /// Withdraw routes originated with [Self::announce_routes]
+    pub fn withdraw_routes(&self, keys: Vec<RouteKey>) {
+        self.inner.state.send_modify(|state| {
+            for key in &keys {
+                state.local_routes.remove(key);
+            }
+        });
+        self.inner.select(&keys);
+    }
+
+    /// Current sessions and routes of the speaker
+    pub fn state(&self) -> SimSpeakerState {
+        self.inner.state.borrow().clone()
+    }
+
+    /// Wait until the state of the speaker satisfies `f`, the virtual clock
+    /// advances meanwhile
+    pub async fn wait_until(&self, f: impl FnMut(&SimSpeakerState) -> bool) -> SimSpeakerState {
+        let mut rx = self.inner.state.subscribe();
+        let state = rx
+            .wait_for(f)
+            .await
+            .expect("the speaker holds the state sender");
+        state.clone()
+    }
+}
+
+impl SimSpeakerInner {
+    fn handle_peer_event(&self, peer_ip: IpAddr, event: PeerStateResult<SocketAddr>) {
+        let (fsm_state, event) = match event {
+            Ok(event) => event,
+            Err(err) => {
+                log::error!("[{}][{peer_ip}] Peer error: {err:?}", self.ip);
+                return;
+            }
+        };
+        let mut keys = vec![];
+        let mut next_hops = self
+            .next_hops
+            .as_ref()
+            .map(|next_hops| next_hops.lock().unwrap());
+        self.state.send_modify(|state| {
+            state.sessions.insert(peer_ip, fsm_state);
+            if fsm_state != FsmState::Established {
+                if let Some(routes) = state.received_routes.remove(&peer_ip) {
+                    keys.extend(routes.into_keys());
+                }
+                if let Some(next_hops) = next_hops.as_mut() {
+                    for key in &keys {
+                        next_hops.untrack(&(peer_ip, *key));
+                    }
+                }
+                return;
+            }
+            let update = match event {
+                BgpEvent::UpdateMsg(update, treatment) => Some((update, treatment)),
+                // Routes released by the route flap damping are installed as received
+                BgpEvent::DampedRoutesReused(update) => Some((update, UpdateTreatment::Normal)),
+                // Routes whose next hop became reachable or unreachable
+                BgpEvent::NextHopRoutesChanged(update) => Some((update, UpdateTreatment::Normal)),
+                // Routes whose origin validation changed
+                #[cfg(feature = "rpki")]
+                BgpEvent::RevalidatedRoutesChanged(update) => {
+                    Some((update, UpdateTreatment::Normal))
+                }
+                _ => None,
+            };
+            if let Some((update, treatment)) = update {
+                let update_routes = UpdateRoutes::from(&update);
+                let update_routes = match treatment {
+                    UpdateTreatment::TreatAsWithdraw => update_routes.treat_as_withdraw(),
+                    _ => update_routes,
+                };
+                let routes = state.received_routes.entry(peer_ip).or_default();
+                for key in update_routes.withdrawn() {
+                    routes.remove(key);
+                    keys.push(*key);
+                    if let Some(next_hops) = next_hops.as_mut() {
+                        next_hops.untrack(&(peer_ip, *key));
+                    }
+                }
+                for (key, route) in update_routes.announced() {
+                    routes.insert(*key, route.clone());
+                    keys.push(*key);
+                    if let Some(next_hops) = next_hops.as_mut() {
+                        next_hops.track((peer_ip, *key), route);
+                    }
+                }
+            }
+        });
+        drop(next_hops);
+        self.select(&keys);
+    }
+
+    /// Run the route selection for the prefixes and advertise the changes to
+    /// the peers
+    fn select(&self, keys: &[RouteKey]) {
+        let mut announced = vec![];
+        let mut withdrawn = vec![];
+        let next_hops = self
+            .next_hops
+            .as_ref()
+            .map(|next_hops| next_hops.lock().unwrap());
+        self.state.send_if_modified(|state| {
+            let mut modified = false;
+            for key in keys {
+                if !state.select(key, self.asn, next_hops.as_deref()) {
+                    continue;
+                }
+                modified = true;
+                match state.best_routes.get(key) {
+                    Some(best) => announced.push(ExportRoute::new(
+                        *key,
+                        best.route.clone(),
+                        self.route_source(best.peer_ip),
+                    )),
+                    None => withdrawn.push(*key),
+                }
+            }
+            modified
+        });
+        drop(next_hops);
+        let mut supervisor = self.supervisor.lock().unwrap();
+        supervisor.announce_routes(announced);
+        supervisor.withdraw_routes(withdrawn);
+    }
+
+    fn route_source(&self, peer_ip: Option<IpAddr>) -> RouteSource {
+        match peer_ip.and_then(|peer_ip| self.peers.get(&peer_ip)) {
+            None => RouteSource::Local,
+            Some((peer_asn, _)) if *peer_asn != self.asn => RouteSource::External,
+            Some((_, peer_bgp_id)) => RouteSource::Internal {
+                peer_bgp_id: *peer_bgp_id,
+                from_client: false,
+            },
+        }
+    }
+}
+
+/// Run the route selection again for the routes whose next hop resolution
+/// changed
+async fn track_next_hops(inner: Arc<SimSpeakerInner>) {
+    let next_hops = match inner.next_hops.as_ref() {
+        Some(next_hops) => next_hops,
+        None => return,
+    };
+    loop {
+        let changed = next_hops.lock().unwrap().changed();
+        changed.await;
+        let keys: HashSet<RouteKey> = next_hops
+            .lock()
+            .unwrap()
+            .poll_changes()
+            .iter()
+            .flat_map(|change| change.keys().iter().map(|(_, key)| *key))
+            .collect();
+        inner.select(&keys.into_iter().collect::<Vec<_>>());
+    }
+}
+
+/// Hand the connections received by a speaker over to its peers
+async fn accept_connections(
+    ip: IpAddr,
+    mut listener: SimListener,
+    mut handles: HashMap<IpAddr, PeerHandle<SocketAddr, SimStream>>,
+) {
+    while let Ok((stream, peer_addr)) = listener.accept().await {
+        match handles.get_mut(&peer_addr.ip()) {
+            Some(handle) => {
+                if let Err(err) = handle.accept_connection(peer_addr, stream) {
+                    log::error!("[{ip}][{peer_addr}] Error sending event to peer: {err:?}");
+                }
+            }
+            None => log::info!("[{ip}] No peer configured for: {peer_addr}"),
+        }
+    }
+}
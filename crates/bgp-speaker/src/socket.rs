@@ -0,0 +1,494 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-session socket options: source address, IP TTL, Generalized TTL
+//! Security Mechanism (GTSM) [RFC5082](https://datatracker.ietf.org/doc/html/rfc5082),
+//! DSCP marking, and binding to a network device (i.e., VRF).
+//!
+//! Except for the source address, the options are set using Linux specific
+//! socket options, on other platforms an [io::ErrorKind::Unsupported] error
+//! is returned.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use tokio::net::{TcpSocket, TcpStream};
+
+/// TTL used for outgoing packets when GTSM is enabled
+pub const GTSM_TTL: u8 = 255;
+
+/// DSCP Class Selector 6 used for network control traffic such as BGP
+/// [RFC4594](https://datatracker.ietf.org/doc/html/rfc4594)
+pub const DSCP_CS6: u8 = 48;
+
+/// Max DSCP value, DSCP is a 6-bit field
+pub const DSCP_MAX: u8 = 63;
+
+/// Socket options applied to the TCP connections of a BGP session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TcpSocketConfig {
+    local_addr: Option<IpAddr>,
+    ttl: Option<u8>,
+    ttl_security_hops: Option<u8>,
+    dscp: Option<u8>,
+    bind_device: Option<String>,
+}
+
+impl TcpSocketConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Source address for outgoing connections. For incoming connections,
+    /// connections received on any other local address are rejected.
+    pub const fn with_local_addr(mut self, local_addr: IpAddr) -> Self {
+        self.local_addr = Some(local_addr);
+        self
+    }
+
+    /// IP TTL (IPv4) or hop limit (IPv6) for the outgoing packets, ignored
+    /// when GTSM is enabled.
+    pub const fn with_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Enable GTSM, the peer is expected to be within `hops` hops. Outgoing
+    /// packets are sent with TTL 255 and incoming packets with TTL less than
+    /// `256 - hops` are dropped.
+    pub const fn with_ttl_security(mut self, hops: u8) -> Self {
+        self.ttl_security_hops = Some(if hops == 0 { 1 } else { hops });
+        self
+    }
+
+    /// DSCP marking of the outgoing packets, usually [DSCP_CS6]
+    pub const fn with_dscp(mut self, dscp: u8) -> Self {
+        self.dscp = Some(dscp);
+        self
+    }
+
+    /// Bind the socket to a network device, e.g., a VRF device
+    pub fn with_bind_device(mut self, device: String) -> Self {
+        self.bind_device = Some(device);
+        self
+    }
+
+    pub const fn local_addr(&self) -> Option<IpAddr> {
+        self.local_addr
+    }
+
+    pub const fn ttl(&self) -> Option<u8> {
+        self.ttl
+    }
+
+    pub const fn ttl_security_hops(&self) -> Option<u8> {
+        self.ttl_security_hops
+    }
+
+    pub const fn dscp(&self) -> Option<u8> {
+        self.dscp
+    }
+
+    pub const fn bind_device(&self) -> Option<&String> {
+        self.bind_device.as_ref()
+    }
+
+    /// Min TTL accepted for incoming packets when GTSM is enabled
+    pub const fn min_ttl(&self) -> Option<u8> {
+        match self.ttl_security_hops {
+            Some(hops) => Some(GTSM_TTL - (hops - 1)),
+            None => None,
+        }
+    }
+
+    /// TTL set on the outgoing packets
+    pub const fn effective_ttl(&self) -> Option<u8> {
+        match self.ttl_security_hops {
+            Some(_) => Some(GTSM_TTL),
+            None => self.ttl,
+        }
+    }
+
+    const fn has_ip_options(&self) -> bool {
+        self.ttl.is_some() || self.ttl_security_hops.is_some() || self.dscp.is_some()
+    }
+
+    fn check_dscp(&self) -> io::Result<()> {
+        match self.dscp {
+            Some(dscp) if dscp > DSCP_MAX => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("DSCP value {dscp} is larger than {DSCP_MAX}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the options to a socket before connecting to `peer_addr`
+    pub fn configure_connect(&self, socket: &TcpSocket, peer_addr: SocketAddr) -> io::Result<()> {
+        self.check_dscp()?;
+        if let Some(device) = &self.bind_device {
+            bind_device(socket, device)?;
+        }
+        if self.has_ip_options() {
+            set_ip_options(socket, peer_addr.is_ipv6(), self)?;
+        }
+        if let Some(local_addr) = self.local_addr {
+            if local_addr.is_ipv4() != peer_addr.is_ipv4() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("source address {local_addr} and peer address {peer_addr} are of different address families"),
+                ));
+            }
+            socket.bind(SocketAddr::new(local_addr, 0))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the options to a socket before listening on `local_addr`.
+    /// The source address is not used, since the socket is bound to the
+    /// listening address.
+    pub fn configure_listen(&self, socket: &TcpSocket, local_addr: SocketAddr) -> io::Result<()> {
+        self.check_dscp()?;
+        if let Some(device) = &self.bind_device {
+            bind_device(socket, device)?;
+        }
+        if self.has_ip_options() {
+            set_ip_options(socket, local_addr.is_ipv6(), self)?;
+        }
+        Ok(())
+    }
+
+    /// Check an accepted connection against the config then apply the
+    /// options to it. When GTSM is enabled, the TTL of the received SYN is
+    /// checked, which requires [enable_save_syn] to be called on the
+    /// listening socket.
+    pub fn configure_accepted(&self, stream: &TcpStream) -> io::Result<()> {
+        self.check_dscp()?;
+        let local_addr = stream.local_addr()?;
+        if let Some(expected) = self.local_addr {
+            let local_ip = canonical_ip(local_addr.ip());
+            if local_ip != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("connection received on {local_ip} instead of {expected}"),
+                ));
+            }
+        }
+        if let Some(min_ttl) = self.min_ttl() {
+            let ttl = saved_syn_ttl(stream)?;
+            if ttl < min_ttl {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("received SYN with TTL {ttl} less than the GTSM min TTL {min_ttl}"),
+                ));
+            }
+        }
+        if self.has_ip_options() {
+            set_ip_options(stream, local_addr.is_ipv6(), self)?;
+        }
+        Ok(())
+    }
+}
+
+/// IPv4 peers connecting to dual-stack IPv6 sockets are seen as IPv4-mapped
+/// addresses
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ipv6) => ipv6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(ipv6)),
+        ip => ip,
+    }
+}
+
+#[cfg(target_os = "linux")]
+use linux::{bind_device, saved_syn_ttl, set_ip_options};
+#[cfg(target_os = "linux")]
+pub(crate) use linux::{recv_from_with_ttl, set_udp_single_hop};
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        io, mem,
+        net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+        os::fd::{AsRawFd, RawFd},
+    };
+
+    use super::{TcpSocketConfig, GTSM_TTL};
+
+    /// Max size of the saved IP and TCP headers of a SYN packet
+    const SAVED_SYN_MAX_LEN: usize = 512;
+
+    fn setsockopt(
+        fd: RawFd,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: value is a valid c_int reference
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                level,
+                name,
+                (&value as *const libc::c_int).cast(),
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) fn bind_device<S: AsRawFd>(socket: &S, device: &str) -> io::Result<()> {
+        // SAFETY: the device name is valid for the given length
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                device.as_ptr().cast(),
+                device.len() as libc::socklen_t,
+            )
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Set TTL, min TTL, and DSCP. On IPv6 sockets, the IPv4 options are set
+    /// as well to cover IPv4-mapped connections.
+    pub(super) fn set_ip_options<S: AsRawFd>(
+        socket: &S,
+        is_ipv6: bool,
+        config: &TcpSocketConfig,
+    ) -> io::Result<()> {
+        let fd = socket.as_raw_fd();
+        if let Some(ttl) = config.effective_ttl() {
+            setsockopt(fd, libc::IPPROTO_IP, libc::IP_TTL, ttl as libc::c_int)?;
+            if is_ipv6 {
+                setsockopt(
+                    fd,
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_UNICAST_HOPS,
+                    ttl as libc::c_int,
+                )?;
+            }
+        }
+        if let Some(min_ttl) = config.min_ttl() {
+            setsockopt(
+                fd,
+                libc::IPPROTO_IP,
+                libc::IP_MINTTL,
+                min_ttl as libc::c_int,
+            )?;
+            if is_ipv6 {
+                setsockopt(
+                    fd,
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_MINHOPCOUNT,
+                    min_ttl as libc::c_int,
+                )?;
+            }
+        }
+        if let Some(dscp) = config.dscp() {
+            let tos = (dscp as libc::c_int) << 2;
+            setsockopt(fd, libc::IPPROTO_IP, libc::IP_TOS, tos)?;
+            if is_ipv6 {
+                setsockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send UDP datagrams with TTL 255 and report the TTL of the received
+    /// datagrams, see [recv_from_with_ttl]
+    pub(crate) fn set_udp_single_hop<S: AsRawFd>(socket: &S, is_ipv6: bool) -> io::Result<()> {
+        let fd = socket.as_raw_fd();
+        setsockopt(fd, libc::IPPROTO_IP, libc::IP_TTL, GTSM_TTL as libc::c_int)?;
+        setsockopt(fd, libc::IPPROTO_IP, libc::IP_RECVTTL, 1)?;
+        if is_ipv6 {
+            setsockopt(
+                fd,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_UNICAST_HOPS,
+                GTSM_TTL as libc::c_int,
+            )?;
+            setsockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Receive a datagram along with its TTL (IPv4) or hop limit (IPv6), the
+    /// socket must be configured with [set_udp_single_hop]
+    pub(crate) fn recv_from_with_ttl<S: AsRawFd>(
+        socket: &S,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        // SAFETY: all zeros is a valid sockaddr_storage
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        // u64 to align the control messages
+        let mut control = [0u64; 16];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+        // SAFETY: all zeros is a valid msghdr
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = (&mut addr as *mut libc::sockaddr_storage).cast();
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+        // SAFETY: msg points to valid buffers for the given lengths
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut ttl = None;
+        // SAFETY: the control messages are filled by the kernel within the
+        // control buffer
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let header = &*cmsg;
+                if (header.cmsg_level == libc::IPPROTO_IP && header.cmsg_type == libc::IP_TTL)
+                    || (header.cmsg_level == libc::IPPROTO_IPV6
+                        && header.cmsg_type == libc::IPV6_HOPLIMIT)
+                {
+                    let value =
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<libc::c_int>());
+                    ttl = u8::try_from(value).ok();
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        let peer_addr = match addr.ss_family as libc::c_int {
+            libc::AF_INET => {
+                // SAFETY: the address family is checked
+                let addr = unsafe {
+                    &*(&addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>()
+                };
+                SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                    u16::from_be(addr.sin_port),
+                ))
+            }
+            libc::AF_INET6 => {
+                // SAFETY: the address family is checked
+                let addr = unsafe {
+                    &*(&addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+                };
+                SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                ))
+            }
+            family => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected address family {family} of received datagram"),
+                ))
+            }
+        };
+        Ok((len as usize, peer_addr, ttl))
+    }
+
+    pub(super) fn save_syn(fd: RawFd) -> io::Result<()> {
+        setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_SAVE_SYN, 1)
+    }
+
+    /// Get the TTL (IPv4) or hop limit (IPv6) of the SYN packet that opened
+    /// the connection. The kernel frees the saved SYN once it's read.
+    pub(super) fn saved_syn_ttl<S: AsRawFd>(socket: &S) -> io::Result<u8> {
+        let mut buf = [0u8; SAVED_SYN_MAX_LEN];
+        let mut len = buf.len() as libc::socklen_t;
+        // SAFETY: buf is valid for len bytes
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_SAVED_SYN,
+                buf.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let header = &buf[..len as usize];
+        let ttl = match header.first().map(|first| first >> 4) {
+            Some(4) => header.get(8),
+            Some(6) => header.get(7),
+            _ => None,
+        };
+        ttl.copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "couldn't parse the IP header of the saved SYN",
+            )
+        })
+    }
+}
+
+/// Save the SYN packets received on a listening socket, required to check
+/// the TTL of accepted connections when GTSM is enabled
+#[cfg(target_os = "linux")]
+pub fn enable_save_syn<S: std::os::fd::AsRawFd>(socket: &S) -> io::Result<()> {
+    linux::save_syn(socket.as_raw_fd())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enable_save_syn<S>(_socket: &S) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TTL, GTSM, DSCP, and bind to device socket options are only supported on Linux",
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_device<S>(_socket: &S, _device: &str) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_udp_single_hop<S>(_socket: &S, _is_ipv6: bool) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_ip_options<S>(_socket: &S, _is_ipv6: bool, _config: &TcpSocketConfig) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn saved_syn_ttl<S>(_socket: &S) -> io::Result<u8> {
+    Err(unsupported())
+}
@@ -0,0 +1,214 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Building blocks of the per-peer statistics reported in
+//! [crate::peer::PeerStats].
+
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+use netgauze_bgp_pkt::notification::BgpNotificationMessage;
+
+use crate::{events::BgpEvent, fsm::FsmState};
+
+/// Max number of FSM state transitions kept in the history of a peer
+pub const FSM_HISTORY_LEN: usize = 64;
+
+/// Window over which the update and withdraw rates are computed
+pub const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Prefix counters of a single AFI/SAFI
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct PrefixCounters {
+    received: u64,
+    withdrawn: u64,
+    accepted: u64,
+    advertised: u64,
+}
+
+impl PrefixCounters {
+    pub const fn new(received: u64, withdrawn: u64, accepted: u64, advertised: u64) -> Self {
+        Self {
+            received,
+            withdrawn,
+            accepted,
+            advertised,
+        }
+    }
+
+    /// Total number of prefix announcements received from the peer
+    pub const fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Total number of prefix withdrawals received from the peer
+    pub const fn withdrawn(&self) -> u64 {
+        self.withdrawn
+    }
+
+    /// Number of prefixes currently in the Adj-RIB-In
+    pub const fn accepted(&self) -> u64 {
+        self.accepted
+    }
+
+    /// Number of prefixes currently in the Adj-RIB-Out
+    pub const fn advertised(&self) -> u64 {
+        self.advertised
+    }
+
+    pub(crate) fn add_received(&mut self, count: u64) {
+        self.received += count;
+    }
+
+    pub(crate) fn add_withdrawn(&mut self, count: u64) {
+        self.withdrawn += count;
+    }
+
+    pub(crate) fn set_accepted(&mut self, count: u64) {
+        self.accepted = count;
+    }
+
+    pub(crate) fn set_advertised(&mut self, count: u64) {
+        self.advertised = count;
+    }
+}
+
+/// A BGP notification message along with the time it was sent or received
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationRecord {
+    timestamp: DateTime<Utc>,
+    notification: BgpNotificationMessage,
+}
+
+impl NotificationRecord {
+    pub fn new(notification: BgpNotificationMessage) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            notification,
+        }
+    }
+
+    pub const fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub const fn notification(&self) -> &BgpNotificationMessage {
+        &self.notification
+    }
+}
+
+/// A single transition of the BGP FSM
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsmTransition<A> {
+    timestamp: DateTime<Utc>,
+    from: FsmState,
+    to: FsmState,
+    event: Option<BgpEvent<A>>,
+}
+
+impl<A> FsmTransition<A> {
+    pub fn new(from: FsmState, to: FsmState) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            from,
+            to,
+            event: None,
+        }
+    }
+
+    pub const fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub const fn from(&self) -> FsmState {
+        self.from
+    }
+
+    pub const fn to(&self) -> FsmState {
+        self.to
+    }
+
+    /// The BGP event that triggered the transition
+    pub const fn event(&self) -> Option<&BgpEvent<A>> {
+        self.event.as_ref()
+    }
+
+    pub(crate) fn set_event(&mut self, event: BgpEvent<A>) {
+        self.event = Some(event);
+    }
+}
+
+/// Count events per second over the last [RATE_WINDOW]
+#[derive(Debug, Clone)]
+pub struct RateMeter {
+    start: Instant,
+    /// Number of events per second since `start`
+    buckets: VecDeque<(u64, u64)>,
+}
+
+impl Default for RateMeter {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl RateMeter {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs()
+    }
+
+    fn expire(&mut self, second: u64) {
+        while let Some((bucket, _)) = self.buckets.front() {
+            if bucket + RATE_WINDOW.as_secs() <= second {
+                self.buckets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn record(&mut self, now: Instant, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let second = self.second(now);
+        self.expire(second);
+        match self.buckets.back_mut() {
+            Some((bucket, value)) if *bucket == second => *value += count,
+            _ => self.buckets.push_back((second, count)),
+        }
+    }
+
+    /// Average number of events per second over the last [RATE_WINDOW]
+    pub fn rate(&self, now: Instant) -> f64 {
+        let second = self.second(now);
+        let total: u64 = self
+            .buckets
+            .iter()
+            .filter(|(bucket, _)| bucket + RATE_WINDOW.as_secs() > second)
+            .map(|(_, value)| value)
+            .sum();
+        total as f64 / RATE_WINDOW.as_secs_f64()
+    }
+}
@@ -13,17 +13,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{connection::ActiveConnect, peer::*, peer_controller::*};
+#[cfg(feature = "bmp")]
+use crate::bmp::{loc_rib_updates, BmpExporter, BmpPeerEvent};
+#[cfg(feature = "mrt")]
+use crate::mrt::MrtRecorder;
+#[cfg(feature = "rpki")]
+use crate::rpki::RouteOriginValidator;
+use crate::{
+    auth::{TcpAuth, TcpAuthKeys},
+    connection::ActiveConnect,
+    damping::DampedRoute,
+    dynamic::{DynamicPeerPolicy, DynamicPeerRange},
+    export::ExportRoute,
+    listener::SessionKey,
+    nexthop::NextHopResolver,
+    peer::*,
+    peer_controller::*,
+    rib::RouteKey,
+};
 use netgauze_bgp_pkt::{
     codec::{BgpCodecDecoderError, BgpCodecInitializer},
     wire::{deserializer::BgpParsingIgnoredErrors, serializer::BgpMessageWritingError},
     BgpMessage,
 };
+#[cfg(feature = "bmp")]
+use netgauze_bmp_pkt::PeerDownNotificationReason;
 use std::{
-    collections::HashMap,
+    any::Any,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
     hash::Hash,
     net::Ipv4Addr,
+    sync::Arc,
 };
 
 use tokio::{
@@ -37,6 +58,89 @@ pub enum PeersSupervisorError {
     PeerExists,
 }
 
+/// Desired state of a peer, used by [PeersSupervisor::update_peers] to
+/// reconcile the running peers with a new peer set.
+#[derive(Debug, Clone)]
+pub struct PeerDefinition<A, C, P> {
+    properties: PeerProperties<A>,
+    config: PeerConfig,
+    tcp_auth: Option<(SessionKey, TcpAuth)>,
+    active_connect: C,
+    policy: P,
+}
+
+impl<A, C, P> PeerDefinition<A, C, P> {
+    pub const fn new(
+        properties: PeerProperties<A>,
+        config: PeerConfig,
+        active_connect: C,
+        policy: P,
+    ) -> Self {
+        Self {
+            properties,
+            config,
+            tcp_auth: None,
+            active_connect,
+            policy,
+        }
+    }
+
+    /// TCP MD5/TCP-AO keys used for the peer's session
+    pub fn with_tcp_auth(mut self, session_key: SessionKey, auth: TcpAuth) -> Self {
+        self.tcp_auth = Some((session_key, auth));
+        self
+    }
+
+    pub const fn properties(&self) -> &PeerProperties<A> {
+        &self.properties
+    }
+
+    pub const fn config(&self) -> &PeerConfig {
+        &self.config
+    }
+
+    pub const fn tcp_auth(&self) -> Option<&(SessionKey, TcpAuth)> {
+        self.tcp_auth.as_ref()
+    }
+}
+
+/// A peer created by [PeersSupervisor::update_peers] along with the receiver
+/// of its events and its handle
+pub type AddedPeer<K, A, I> = (K, UnboundedReceiver<PeerStateResult<A>>, PeerHandle<A, I>);
+
+/// Changes applied by [PeersSupervisor::update_peers]. A peer whose
+/// [PeerProperties] changed is recreated, hence reported as both removed and
+/// added.
+#[derive(Debug)]
+pub struct PeersUpdate<K, A, I: AsyncWrite + AsyncRead> {
+    added: Vec<AddedPeer<K, A, I>>,
+    removed: Vec<K>,
+    updated: Vec<K>,
+}
+
+impl<K, A, I: AsyncWrite + AsyncRead> PeersUpdate<K, A, I> {
+    /// Peers that were created, they need to be started using their
+    /// [PeerHandle]
+    pub const fn added(&self) -> &Vec<AddedPeer<K, A, I>> {
+        &self.added
+    }
+
+    /// Peers that were shutdown and removed
+    pub const fn removed(&self) -> &Vec<K> {
+        &self.removed
+    }
+
+    /// Peers that are kept running with a new configuration, policy, or TCP
+    /// authentication keys
+    pub const fn updated(&self) -> &Vec<K> {
+        &self.updated
+    }
+
+    pub fn into_added(self) -> Vec<AddedPeer<K, A, I>> {
+        self.added
+    }
+}
+
 /// Peer lifetime management
 /// At the moment this is a simple implementation and need more work
 #[derive(Debug)]
@@ -44,6 +148,28 @@ pub struct PeersSupervisor<K: Hash + Eq + PartialEq, A, I: AsyncWrite + AsyncRea
     my_asn: u32,
     my_bgp_id: Ipv4Addr,
     peers: HashMap<K, PeerController<K, A, I>>,
+    /// Peers created by [Self::dynamic_peer] or [Self::dynamic_peer_from_range],
+    /// they're left alone by [Self::update_peers]
+    dynamic_peers: HashSet<K>,
+    tcp_auth_keys: TcpAuthKeys,
+    /// Sessions of the TCP authentication keys set by [Self::update_peers],
+    /// to clean them up when the peer is removed
+    peers_tcp_auth: HashMap<K, SessionKey>,
+    /// Policies set by [Self::update_peers], the policy of a running peer is
+    /// replaced only when it changed
+    peers_policy: HashMap<K, Box<dyn Any + Send + Sync>>,
+    /// Routes injected with [Self::announce_routes], exported to all the peers
+    loc_rib: HashMap<RouteKey, ExportRoute>,
+    /// Per peer Loc-RIBs injected with [Self::announce_client_routes], they
+    /// take precedence over `loc_rib` for the peer
+    client_ribs: HashMap<K, HashMap<RouteKey, ExportRoute>>,
+    next_hop_resolver: Option<Arc<dyn NextHopResolver>>,
+    #[cfg(feature = "bmp")]
+    bmp_exporter: Option<BmpExporter<A>>,
+    #[cfg(feature = "mrt")]
+    mrt_recorder: Option<MrtRecorder<A>>,
+    #[cfg(feature = "rpki")]
+    route_origin_validator: Option<RouteOriginValidator>,
 }
 
 impl<
@@ -57,7 +183,86 @@ impl<
             my_asn,
             my_bgp_id,
             peers: HashMap::new(),
+            dynamic_peers: HashSet::new(),
+            tcp_auth_keys: TcpAuthKeys::new(),
+            peers_tcp_auth: HashMap::new(),
+            peers_policy: HashMap::new(),
+            loc_rib: HashMap::new(),
+            client_ribs: HashMap::new(),
+            next_hop_resolver: None,
+            #[cfg(feature = "bmp")]
+            bmp_exporter: None,
+            #[cfg(feature = "mrt")]
+            mrt_recorder: None,
+            #[cfg(feature = "rpki")]
+            route_origin_validator: None,
+        }
+    }
+
+    /// Report the sessions of all the current and future peers to a BMP
+    /// exporter, along with the routes announced with [Self::announce_routes]
+    /// as the Loc-RIB.
+    #[cfg(feature = "bmp")]
+    pub fn set_bmp_exporter(&mut self, exporter: Option<BmpExporter<A>>) {
+        if let Some(exporter) = exporter.as_ref() {
+            let routes = self.exported_routes();
+            for update in loc_rib_updates(&routes, &[]) {
+                exporter.send(BmpPeerEvent::LocRib(update));
+            }
+        }
+        for (peer_key, controller) in &self.peers {
+            if let Err(err) = controller
+                .get_new_handle()
+                .set_bmp_exporter(exporter.clone())
+            {
+                log::error!("[{peer_key}] Couldn't set BMP exporter: {err}");
+            }
+        }
+        self.bmp_exporter = exporter;
+    }
+
+    /// Record the sessions of all the current and future peers to MRT files
+    #[cfg(feature = "mrt")]
+    pub fn set_mrt_recorder(&mut self, recorder: Option<MrtRecorder<A>>) {
+        for (peer_key, controller) in &self.peers {
+            if let Err(err) = controller
+                .get_new_handle()
+                .set_mrt_recorder(recorder.clone())
+            {
+                log::error!("[{peer_key}] Couldn't set MRT recorder: {err}");
+            }
         }
+        self.mrt_recorder = recorder;
+    }
+
+    /// Validate the origin of the routes received by all the current and
+    /// future peers
+    #[cfg(feature = "rpki")]
+    pub fn set_route_origin_validator(&mut self, validator: Option<RouteOriginValidator>) {
+        for (peer_key, controller) in &self.peers {
+            if let Err(err) = controller
+                .get_new_handle()
+                .set_route_origin_validator(validator.clone())
+            {
+                log::error!("[{peer_key}] Couldn't set route origin validator: {err}");
+            }
+        }
+        self.route_origin_validator = validator;
+    }
+
+    /// Resolve the next hops of the routes received by all the current and
+    /// future peers, the routes with an unreachable next hop are withdrawn
+    /// until it's reachable again
+    pub fn set_next_hop_resolver(&mut self, resolver: Option<Arc<dyn NextHopResolver>>) {
+        for (peer_key, controller) in &self.peers {
+            if let Err(err) = controller
+                .get_new_handle()
+                .set_next_hop_resolver(resolver.clone())
+            {
+                log::error!("[{peer_key}] Couldn't set next hop resolver: {err}");
+            }
+        }
+        self.next_hop_resolver = resolver;
     }
 
     #[allow(clippy::type_complexity)]
@@ -91,12 +296,53 @@ impl<
             active_connect,
         );
         let peer_handle = peer_controller.get_new_handle();
+        #[cfg(feature = "bmp")]
+        if let Some(exporter) = self.bmp_exporter.as_ref() {
+            if let Err(err) = peer_handle.set_bmp_exporter(Some(exporter.clone())) {
+                log::error!("[{peer_key}] Couldn't set BMP exporter: {err}");
+            }
+        }
+        #[cfg(feature = "mrt")]
+        if let Some(recorder) = self.mrt_recorder.as_ref() {
+            if let Err(err) = peer_handle.set_mrt_recorder(Some(recorder.clone())) {
+                log::error!("[{peer_key}] Couldn't set MRT recorder: {err}");
+            }
+        }
+        #[cfg(feature = "rpki")]
+        if let Some(validator) = self.route_origin_validator.as_ref() {
+            if let Err(err) = peer_handle.set_route_origin_validator(Some(validator.clone())) {
+                log::error!("[{peer_key}] Couldn't set route origin validator: {err}");
+            }
+        }
+        if let Some(resolver) = self.next_hop_resolver.as_ref() {
+            if let Err(err) = peer_handle.set_next_hop_resolver(Some(resolver.clone())) {
+                log::error!("[{peer_key}] Couldn't set next hop resolver: {err}");
+            }
+        }
+        let routes = self.peer_routes(&peer_key);
+        if !routes.is_empty() {
+            if let Err(err) = peer_handle.announce_routes(routes) {
+                log::error!("[{peer_key}] Couldn't export routes to the new peer: {err}");
+            }
+        }
         self.peers.insert(peer_key, peer_controller);
         Ok((rx, peer_handle))
     }
 
     pub fn remove_peer(&mut self, peer_key: &K) -> Option<PeerController<K, A, I>> {
+        self.dynamic_peers.remove(peer_key);
+        if let Some(session_key) = self.peers_tcp_auth.remove(peer_key) {
+            self.tcp_auth_keys.remove(&session_key);
+        }
+        self.peers_policy.remove(peer_key);
         self.peers.remove(peer_key).inspect(|controller| {
+            #[cfg(feature = "bmp")]
+            if let Some(exporter) = self.bmp_exporter.as_ref() {
+                exporter.send(BmpPeerEvent::PeerDown {
+                    peer_addr: controller.peer_addr(),
+                    reason: PeerDownNotificationReason::PeerDeConfigured,
+                });
+            }
             let handler = controller.get_new_handle();
             let _ = handler.shutdown();
         })
@@ -106,10 +352,393 @@ impl<
         self.peers.get(peer_key).map(|ctrl| ctrl.get_new_handle())
     }
 
+    pub fn is_dynamic_peer(&self, peer_key: &K) -> bool {
+        self.dynamic_peers.contains(peer_key)
+    }
+
     pub fn peer_keys(&self) -> Vec<K> {
         self.peers.keys().cloned().collect()
     }
 
+    /// Route flap damping state of a route received from the given peer,
+    /// `None` if the peer doesn't exist or the route has no flap history
+    pub async fn damped_route(&self, peer_key: &K, key: RouteKey) -> Option<DampedRoute<K>> {
+        let mut handle = self.peers.get(peer_key)?.get_new_handle();
+        match handle.damped_route(key).await {
+            Ok(route) => route.map(|route| route.with_peer(*peer_key)),
+            Err(err) => {
+                log::error!("[{peer_key}] Couldn't get damped route: {err}");
+                None
+            }
+        }
+    }
+
+    /// Received routes suppressed by the route flap damping of all the peers
+    pub async fn dampened_routes(&self) -> Vec<DampedRoute<K>> {
+        let mut routes = vec![];
+        for (peer_key, mut handle) in self.peer_handles() {
+            match handle.dampened_routes().await {
+                Ok(peer_routes) => routes.extend(
+                    peer_routes
+                        .into_iter()
+                        .map(|route| route.with_peer(peer_key)),
+                ),
+                Err(err) => log::error!("[{peer_key}] Couldn't get dampened routes: {err}"),
+            }
+        }
+        routes
+    }
+
+    /// Received routes with route flap history of all the peers
+    pub async fn flap_statistics(&self) -> Vec<DampedRoute<K>> {
+        let mut routes = vec![];
+        for (peer_key, mut handle) in self.peer_handles() {
+            match handle.flap_statistics().await {
+                Ok(peer_routes) => routes.extend(
+                    peer_routes
+                        .into_iter()
+                        .map(|route| route.with_peer(peer_key)),
+                ),
+                Err(err) => log::error!("[{peer_key}] Couldn't get flap statistics: {err}"),
+            }
+        }
+        routes
+    }
+
+    fn peer_handles(&self) -> Vec<(K, PeerHandle<A, I>)> {
+        self.peers
+            .iter()
+            .map(|(peer_key, controller)| (*peer_key, controller.get_new_handle()))
+            .collect()
+    }
+
+    /// Announce routes to all the current and future peers, replacing the
+    /// routes previously announced with the same [RouteKey]. Each peer applies
+    /// its [crate::export::ExportConfig] before advertising them.
+    pub fn announce_routes(&mut self, routes: Vec<ExportRoute>) {
+        if routes.is_empty() {
+            return;
+        }
+        for route in &routes {
+            self.loc_rib.insert(*route.key(), route.clone());
+        }
+        #[cfg(feature = "bmp")]
+        if let Some(exporter) = self.bmp_exporter.as_ref() {
+            for update in loc_rib_updates(&routes, &[]) {
+                exporter.send(BmpPeerEvent::LocRib(update));
+            }
+        }
+        for (peer_key, controller) in &self.peers {
+            let routes: Vec<ExportRoute> = routes
+                .iter()
+                .filter(|route| !self.has_client_route(peer_key, route.key()))
+                .cloned()
+                .collect();
+            if routes.is_empty() {
+                continue;
+            }
+            if let Err(err) = controller.get_new_handle().announce_routes(routes) {
+                log::error!("[{peer_key}] Couldn't export routes: {err}");
+            }
+        }
+    }
+
+    /// Withdraw routes previously announced with [Self::announce_routes] from
+    /// all the peers
+    pub fn withdraw_routes(&mut self, keys: Vec<RouteKey>) {
+        let keys: Vec<RouteKey> = keys
+            .into_iter()
+            .filter(|key| self.loc_rib.remove(key).is_some())
+            .collect();
+        if keys.is_empty() {
+            return;
+        }
+        #[cfg(feature = "bmp")]
+        if let Some(exporter) = self.bmp_exporter.as_ref() {
+            for update in loc_rib_updates(&[], &keys) {
+                exporter.send(BmpPeerEvent::LocRib(update));
+            }
+        }
+        for (peer_key, controller) in &self.peers {
+            let keys: Vec<RouteKey> = keys
+                .iter()
+                .filter(|key| !self.has_client_route(peer_key, key))
+                .copied()
+                .collect();
+            if keys.is_empty() {
+                continue;
+            }
+            if let Err(err) = controller.get_new_handle().withdraw_routes(keys) {
+                log::error!("[{peer_key}] Couldn't withdraw exported routes: {err}");
+            }
+        }
+    }
+
+    /// Routes announced with [Self::announce_routes] and not withdrawn
+    pub fn exported_routes(&self) -> Vec<ExportRoute> {
+        self.loc_rib.values().cloned().collect()
+    }
+
+    /// Announce routes only to the given peer, e.g. the per client Loc-RIB of
+    /// a route server. The routes take precedence over the ones announced with
+    /// [Self::announce_routes] for the same [RouteKey], and are kept when the
+    /// peer is removed and created again.
+    pub fn announce_client_routes(&mut self, peer_key: K, routes: Vec<ExportRoute>) {
+        if routes.is_empty() {
+            return;
+        }
+        let client_rib = self.client_ribs.entry(peer_key).or_default();
+        for route in &routes {
+            client_rib.insert(*route.key(), route.clone());
+        }
+        if let Some(controller) = self.peers.get(&peer_key) {
+            if let Err(err) = controller.get_new_handle().announce_routes(routes) {
+                log::error!("[{peer_key}] Couldn't export client routes: {err}");
+            }
+        }
+    }
+
+    /// Withdraw routes previously announced with
+    /// [Self::announce_client_routes] from the given peer. The peer falls back
+    /// to the routes announced with [Self::announce_routes] if any.
+    pub fn withdraw_client_routes(&mut self, peer_key: K, keys: Vec<RouteKey>) {
+        let client_rib = match self.client_ribs.get_mut(&peer_key) {
+            Some(client_rib) => client_rib,
+            None => return,
+        };
+        let keys: Vec<RouteKey> = keys
+            .into_iter()
+            .filter(|key| client_rib.remove(key).is_some())
+            .collect();
+        if client_rib.is_empty() {
+            self.client_ribs.remove(&peer_key);
+        }
+        let controller = match self.peers.get(&peer_key) {
+            Some(controller) => controller,
+            None => return,
+        };
+        let (fallback, withdrawn): (Vec<RouteKey>, Vec<RouteKey>) = keys
+            .into_iter()
+            .partition(|key| self.loc_rib.contains_key(key));
+        let handle = controller.get_new_handle();
+        if !withdrawn.is_empty() {
+            if let Err(err) = handle.withdraw_routes(withdrawn) {
+                log::error!("[{peer_key}] Couldn't withdraw client routes: {err}");
+            }
+        }
+        if !fallback.is_empty() {
+            let routes = fallback
+                .iter()
+                .filter_map(|key| self.loc_rib.get(key).cloned())
+                .collect();
+            if let Err(err) = handle.announce_routes(routes) {
+                log::error!("[{peer_key}] Couldn't export routes: {err}");
+            }
+        }
+    }
+
+    /// Routes announced with [Self::announce_client_routes] to the given peer
+    /// and not withdrawn
+    pub fn client_routes(&self, peer_key: &K) -> Vec<ExportRoute> {
+        self.client_ribs
+            .get(peer_key)
+            .map(|client_rib| client_rib.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn has_client_route(&self, peer_key: &K, key: &RouteKey) -> bool {
+        self.client_ribs
+            .get(peer_key)
+            .is_some_and(|client_rib| client_rib.contains_key(key))
+    }
+
+    /// Routes exported to the peer, the shared Loc-RIB overridden by the
+    /// peer's own Loc-RIB
+    fn peer_routes(&self, peer_key: &K) -> Vec<ExportRoute> {
+        self.loc_rib
+            .values()
+            .filter(|route| !self.has_client_route(peer_key, route.key()))
+            .chain(
+                self.client_ribs
+                    .get(peer_key)
+                    .into_iter()
+                    .flat_map(|rib| rib.values()),
+            )
+            .cloned()
+            .collect()
+    }
+
+    /// TCP MD5/TCP-AO keys shared with the active connectors and listeners
+    pub const fn tcp_auth_keys(&self) -> &TcpAuthKeys {
+        &self.tcp_auth_keys
+    }
+
+    /// Set, rotate, or remove (when `auth` is `None`) the TCP authentication
+    /// keys used for a peer's session. Listening sockets are updated only for
+    /// the peer address of the session, so sessions with other peers are not
+    /// affected. The new keys are used by the subsequent connections.
+    pub fn set_tcp_auth(
+        &mut self,
+        session_key: SessionKey,
+        auth: Option<TcpAuth>,
+    ) -> Option<TcpAuth> {
+        match auth {
+            Some(auth) => self.tcp_auth_keys.insert(session_key, auth),
+            None => self.tcp_auth_keys.remove(&session_key),
+        }
+    }
+
+    /// Reconcile the running peers with a new peer set. Peers not in `peers`
+    /// are removed, except the dynamic peers, new peers are created (but not
+    /// started), and peers whose [PeerProperties] changed are recreated. A
+    /// dynamic peer in `peers` is replaced by the configured peer. The
+    /// remaining peers keep their
+    /// sessions: configuration and TCP authentication changes are pushed to
+    /// them, and a changed policy is replaced to be used from the next
+    /// connection attempt.
+    pub fn update_peers<
+        D: BgpCodecInitializer<Peer<K, A, I, D, C, P>>
+            + Decoder<Item = (BgpMessage, BgpParsingIgnoredErrors), Error = BgpCodecDecoderError>
+            + Encoder<BgpMessage, Error = BgpMessageWritingError>
+            + Send
+            + Sync,
+        C: ActiveConnect<A, I, D> + Send + Sync + 'static,
+        P: PeerPolicy<A, I, D> + PartialEq + Clone + Send + Sync + 'static,
+    >(
+        &mut self,
+        peers: HashMap<K, PeerDefinition<A, C, P>>,
+    ) -> PeersUpdate<K, A, I>
+    where
+        A: PartialEq,
+    {
+        let mut update = PeersUpdate {
+            added: vec![],
+            removed: vec![],
+            updated: vec![],
+        };
+        let stale: Vec<K> = self
+            .peers
+            .iter()
+            .filter(|(peer_key, controller)| match peers.get(peer_key) {
+                None => !self.dynamic_peers.contains(peer_key),
+                Some(definition) => controller.properties() != definition.properties(),
+            })
+            .map(|(peer_key, _)| *peer_key)
+            .collect();
+        for peer_key in stale {
+            self.remove_peer(&peer_key);
+            update.removed.push(peer_key);
+        }
+        for (peer_key, definition) in peers {
+            self.dynamic_peers.remove(&peer_key);
+            let auth_updated = self.update_peer_tcp_auth(peer_key, definition.tcp_auth.clone());
+            match self.peers.get_mut(&peer_key) {
+                Some(controller) => {
+                    let mut updated = auth_updated;
+                    if controller.config() != definition.config() {
+                        if let Err(err) = controller.update_config(definition.config) {
+                            log::error!("[{peer_key}] Couldn't update peer config: {err}");
+                        }
+                        updated = true;
+                    }
+                    let policy_changed = self
+                        .peers_policy
+                        .get(&peer_key)
+                        .and_then(|policy| policy.downcast_ref::<P>())
+                        != Some(&definition.policy);
+                    if policy_changed {
+                        match controller
+                            .get_new_handle()
+                            .update_policy(definition.policy.clone())
+                        {
+                            Ok(()) => {
+                                self.peers_policy
+                                    .insert(peer_key, Box::new(definition.policy));
+                                updated = true;
+                            }
+                            Err(err) => {
+                                log::error!("[{peer_key}] Couldn't update peer policy: {err}");
+                            }
+                        }
+                    }
+                    if updated {
+                        update.updated.push(peer_key);
+                    }
+                }
+                None => {
+                    self.peers_policy
+                        .insert(peer_key, Box::new(definition.policy.clone()));
+                    let (rx, handle) = self
+                        .create_peer(
+                            peer_key,
+                            definition.properties,
+                            definition.config,
+                            definition.active_connect,
+                            definition.policy,
+                        )
+                        .expect("stale peers are removed before creating new ones");
+                    update.added.push((peer_key, rx, handle));
+                }
+            }
+        }
+        update
+    }
+
+    /// Create a single peer from its definition (but don't start it), along
+    /// with its TCP authentication keys. The other peers are not affected.
+    #[allow(clippy::type_complexity)]
+    pub fn add_peer<
+        D: BgpCodecInitializer<Peer<K, A, I, D, C, P>>
+            + Decoder<Item = (BgpMessage, BgpParsingIgnoredErrors), Error = BgpCodecDecoderError>
+            + Encoder<BgpMessage, Error = BgpMessageWritingError>
+            + Send
+            + Sync,
+        C: ActiveConnect<A, I, D> + Send + Sync + 'static,
+        P: PeerPolicy<A, I, D> + Send + Sync + 'static,
+    >(
+        &mut self,
+        peer_key: K,
+        definition: PeerDefinition<A, C, P>,
+    ) -> Result<(UnboundedReceiver<PeerStateResult<A>>, PeerHandle<A, I>), PeersSupervisorError>
+    {
+        if self.peers.contains_key(&peer_key) {
+            return Err(PeersSupervisorError::PeerExists);
+        }
+        self.update_peer_tcp_auth(peer_key, definition.tcp_auth);
+        self.create_peer(
+            peer_key,
+            definition.properties,
+            definition.config,
+            definition.active_connect,
+            definition.policy,
+        )
+    }
+
+    /// Returns true if the TCP authentication keys of the peer are changed
+    fn update_peer_tcp_auth(
+        &mut self,
+        peer_key: K,
+        tcp_auth: Option<(SessionKey, TcpAuth)>,
+    ) -> bool {
+        let mut changed = false;
+        let old_session = self.peers_tcp_auth.get(&peer_key).copied();
+        if let Some(old_session) = old_session {
+            if tcp_auth.as_ref().map(|(session_key, _)| *session_key) != Some(old_session) {
+                self.peers_tcp_auth.remove(&peer_key);
+                self.tcp_auth_keys.remove(&old_session);
+                changed = true;
+            }
+        }
+        if let Some((session_key, auth)) = tcp_auth {
+            self.peers_tcp_auth.insert(peer_key, session_key);
+            if self.tcp_auth_keys.get(&session_key).as_ref() != Some(&auth) {
+                self.tcp_auth_keys.insert(session_key, auth);
+                changed = true;
+            }
+        }
+        changed
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn dynamic_peer<
         D: BgpCodecInitializer<Peer<K, A, I, D, C, EchoCapabilitiesPolicy<A, I, D>>>
@@ -148,6 +777,40 @@ impl<
             active_connect,
             policy,
         )?;
+        self.dynamic_peers.insert(peer_key);
         Ok((rx, peer_handle))
     }
+
+    /// Create a dynamic peer from the template of a [DynamicPeerRange]. The
+    /// session is rejected when the peer's ASN isn't allowed by the range.
+    #[allow(clippy::type_complexity)]
+    pub fn dynamic_peer_from_range<
+        D: BgpCodecInitializer<Peer<K, A, I, D, C, DynamicPeerPolicy<A, I, D>>>
+            + Decoder<Item = (BgpMessage, BgpParsingIgnoredErrors), Error = BgpCodecDecoderError>
+            + Encoder<BgpMessage, Error = BgpMessageWritingError>
+            + Send
+            + Sync
+            + 'static,
+        C: ActiveConnect<A, I, D> + Send + Sync + 'static,
+    >(
+        &mut self,
+        peer_key: K,
+        peer_addr: A,
+        active_connect: C,
+        range: &DynamicPeerRange,
+    ) -> Result<(UnboundedReceiver<PeerStateResult<A>>, PeerHandle<A, I>), PeersSupervisorError>
+    {
+        let template = range.template();
+        let peer_properties = template.properties(self.my_asn, self.my_bgp_id, peer_addr);
+        let policy = template.policy(self.my_asn, self.my_bgp_id, range.remote_asns().clone());
+        let peer = self.create_peer(
+            peer_key,
+            peer_properties,
+            template.config().clone(),
+            active_connect,
+            policy,
+        )?;
+        self.dynamic_peers.insert(peer_key);
+        Ok(peer)
+    }
 }
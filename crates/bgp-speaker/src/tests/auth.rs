@@ -0,0 +1,187 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::net::{TcpListener, TcpSocket};
+
+use crate::{
+    auth::*,
+    connection::{ActiveConnect, TcpActiveConnect},
+    listener::SessionKey,
+};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const LOCALHOST_SESSION: SessionKey = SessionKey::new(LOCALHOST);
+
+fn listen(auth: Option<&TcpAuth>) -> io::Result<TcpListener> {
+    let socket = TcpSocket::new_v4()?;
+    socket.bind(SocketAddr::new(LOCALHOST, 0))?;
+    update_tcp_auth(&socket, LOCALHOST, None, auth)?;
+    socket.listen(16)
+}
+
+async fn connect(listener: &TcpListener, keys: TcpAuthKeys) -> io::Result<()> {
+    let mut active_connect = TcpActiveConnect::new().with_tcp_auth_keys(keys);
+    let peer_addr = listener.local_addr()?;
+    match tokio::time::timeout(Duration::from_secs(2), active_connect.connect(peer_addr)).await {
+        Ok(stream) => {
+            stream?;
+            listener.accept().await?;
+            Ok(())
+        }
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "connect timeout")),
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tcp_md5_loopback() -> io::Result<()> {
+    let auth = TcpAuth::Md5(b"secret".to_vec());
+    let listener = listen(Some(&auth))?;
+    let keys = TcpAuthKeys::new();
+    keys.insert(LOCALHOST_SESSION, auth);
+    connect(&listener, keys).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tcp_md5_key_mismatch() -> io::Result<()> {
+    let listener = listen(Some(&TcpAuth::Md5(b"secret".to_vec())))?;
+    let keys = TcpAuthKeys::new();
+    keys.insert(LOCALHOST_SESSION, TcpAuth::Md5(b"wrong".to_vec()));
+    // Segments with invalid signatures are silently dropped by the kernel
+    let ret = connect(&listener, keys).await;
+    assert_eq!(ret.map_err(|err| err.kind()), Err(io::ErrorKind::TimedOut));
+
+    let ret = connect(&listener, TcpAuthKeys::new()).await;
+    assert_eq!(ret.map_err(|err| err.kind()), Err(io::ErrorKind::TimedOut));
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tcp_md5_rotation() -> io::Result<()> {
+    let old = TcpAuth::Md5(b"old-secret".to_vec());
+    let new = TcpAuth::Md5(b"new-secret".to_vec());
+    let listener = listen(Some(&old))?;
+    update_tcp_auth(&listener, LOCALHOST, Some(&old), Some(&new))?;
+
+    let keys = TcpAuthKeys::new();
+    keys.insert(LOCALHOST_SESSION, new);
+    connect(&listener, keys).await?;
+
+    update_tcp_auth(&listener, LOCALHOST, Some(&TcpAuth::Md5(vec![])), None)?;
+    connect(&listener, TcpAuthKeys::new()).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tcp_md5_key_too_long() {
+    let ret = listen(Some(&TcpAuth::Md5(vec![0; TCP_AUTH_MAX_KEY_LEN + 1])));
+    assert_eq!(
+        ret.map_err(|err| err.kind()).err(),
+        Some(io::ErrorKind::InvalidInput)
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tcp_ao_loopback() -> io::Result<()> {
+    let key = TcpAoKey::new(1, 1, TcpAoAlgorithm::HmacSha1, b"secret".to_vec());
+    let auth = TcpAuth::Ao(TcpAoKeyChain::new(vec![key], 1));
+    let listener = match listen(Some(&auth)) {
+        Ok(listener) => listener,
+        Err(err) if err.raw_os_error() == Some(libc::ENOPROTOOPT) => {
+            log::warn!("TCP-AO is not supported by the kernel, skipping test");
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    let keys = TcpAuthKeys::new();
+    keys.insert(LOCALHOST_SESSION, auth);
+    connect(&listener, keys).await
+}
+
+#[test]
+fn test_tcp_auth_keys_notify() {
+    let keys = TcpAuthKeys::new();
+    let mut rx = keys.subscribe();
+    let auth = TcpAuth::Md5(b"secret".to_vec());
+
+    assert_eq!(keys.insert(LOCALHOST_SESSION, auth.clone()), None);
+    assert!(rx.has_changed().unwrap());
+    rx.mark_unchanged();
+
+    // Setting the same key again is not a change
+    assert_eq!(
+        keys.insert(LOCALHOST_SESSION, auth.clone()),
+        Some(auth.clone())
+    );
+    assert!(!rx.has_changed().unwrap());
+
+    assert_eq!(keys.get(&LOCALHOST_SESSION), Some(auth.clone()));
+    assert_eq!(keys.remove(&LOCALHOST_SESSION), Some(auth));
+    assert!(rx.has_changed().unwrap());
+    assert!(keys.snapshot().is_empty());
+}
+
+#[test]
+fn test_tcp_auth_keys_by_session() {
+    let keys = TcpAuthKeys::new();
+    let peer_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let local_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+    let other_local_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 20));
+    let any_local = TcpAuth::Md5(b"any-local".to_vec());
+    let exact_local = TcpAuth::Md5(b"exact-local".to_vec());
+    let other_instance = TcpAuth::Md5(b"other-instance".to_vec());
+    let session_key = SessionKey::new(peer_ip);
+    keys.insert(session_key, any_local.clone());
+    keys.insert(
+        session_key.with_local_ip(Some(local_ip)),
+        exact_local.clone(),
+    );
+    keys.insert(session_key.with_instance(1), other_instance.clone());
+
+    // A key registered with the exact local address is preferred
+    assert_eq!(
+        keys.lookup(0, Some(local_ip), peer_ip),
+        Some(exact_local.clone())
+    );
+    assert_eq!(
+        keys.lookup(0, Some(other_local_ip), peer_ip),
+        Some(any_local.clone())
+    );
+    assert_eq!(keys.lookup(0, None, peer_ip), Some(any_local.clone()));
+    assert_eq!(keys.lookup(1, None, peer_ip), Some(other_instance));
+    assert_eq!(keys.lookup(2, None, peer_ip), None);
+
+    let snapshot = keys.snapshot();
+    assert_eq!(
+        listener_tcp_auth(&snapshot, 0, local_ip),
+        HashMap::from([(peer_ip, exact_local.clone())])
+    );
+    assert_eq!(
+        listener_tcp_auth(&snapshot, 0, other_local_ip),
+        HashMap::from([(peer_ip, any_local)])
+    );
+    // Wildcard sockets fall back to the keys of the lowest local address
+    keys.remove(&session_key);
+    assert_eq!(
+        listener_tcp_auth(&keys.snapshot(), 0, IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        HashMap::from([(peer_ip, exact_local)])
+    );
+    assert!(listener_tcp_auth(&keys.snapshot(), 0, other_local_ip).is_empty());
+}
@@ -0,0 +1,278 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    notification::{BgpNotificationMessage, CeaseError},
+    open::BgpOpenMessage,
+    BgpMessage,
+};
+use rand::{rngs::SmallRng, SeedableRng};
+use tokio::{sync::watch, time::Instant};
+
+use crate::{
+    bfd::*,
+    events::BgpEvent,
+    fsm::{FsmState, FsmStateError},
+    liveness::{LivenessDetector, LivenessState},
+    peer::*,
+    tests::*,
+};
+
+fn packet() -> BfdControlPacket {
+    BfdControlPacket {
+        diagnostic: BfdDiagnostic::ControlDetectionTimeExpired,
+        state: BfdState::Up,
+        poll: true,
+        final_: false,
+        control_plane_independent: false,
+        demand: false,
+        detect_mult: 3,
+        my_discriminator: 0x01020304,
+        your_discriminator: 0x05060708,
+        desired_min_tx_interval: 300_000,
+        required_min_rx_interval: 1_000_000,
+        required_min_echo_rx_interval: 0,
+    }
+}
+
+#[test]
+fn test_bfd_control_packet() {
+    let good_wire = [
+        0x21, 0xe0, 0x03, 0x18, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x04, 0x93,
+        0xe0, 0x00, 0x0f, 0x42, 0x40, 0x00, 0x00, 0x00, 0x00,
+    ];
+    assert_eq!(packet().encode(), good_wire);
+    assert_eq!(BfdControlPacket::decode(&good_wire), Ok(packet()));
+
+    let mut wire = good_wire;
+    wire[0] = 0x41;
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::InvalidVersion(2))
+    );
+    let mut wire = good_wire;
+    wire[3] = 0x30;
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::InvalidLength(0x30))
+    );
+    let mut wire = good_wire;
+    wire[1] |= 0x04;
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::AuthenticationNotSupported)
+    );
+    let mut wire = good_wire;
+    wire[1] |= 0x01;
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::MultipointNotSupported)
+    );
+    let mut wire = good_wire;
+    wire[2] = 0;
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::ZeroDetectMult)
+    );
+    let mut wire = good_wire;
+    wire[4..8].copy_from_slice(&[0, 0, 0, 0]);
+    assert_eq!(
+        BfdControlPacket::decode(&wire),
+        Err(BfdPacketError::ZeroMyDiscriminator)
+    );
+    assert_eq!(
+        BfdControlPacket::decode(&good_wire[..20]),
+        Err(BfdPacketError::TooShort(20))
+    );
+}
+
+#[test]
+fn test_bfd_session_state() {
+    let config = BfdConfig::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), BFD_PORT));
+    let now = Instant::now();
+    let mut session = BfdSession::new(PEER_ADDR.ip(), 1, now);
+    let remote = |state| BfdControlPacket {
+        diagnostic: BfdDiagnostic::NoDiagnostic,
+        state,
+        poll: false,
+        your_discriminator: 1,
+        ..packet()
+    };
+
+    assert!(!session.receive(&remote(BfdState::Down), &config, now));
+    assert_eq!(session.state(), BfdState::Init);
+    let sent = session.control_packet(&config, false);
+    assert_eq!(sent.state, BfdState::Init);
+    assert_eq!(sent.my_discriminator, 1);
+    assert_eq!(sent.your_discriminator, 0x01020304);
+    // Slow transmit interval until the session is up
+    assert_eq!(sent.desired_min_tx_interval, 1_000_000);
+
+    assert!(!session.receive(&remote(BfdState::Up), &config, now));
+    assert_eq!(session.state(), BfdState::Up);
+    // The faster transmit interval is advertised with a poll sequence
+    let sent = session.control_packet(&config, false);
+    assert!(sent.poll);
+    assert_eq!(sent.desired_min_tx_interval, 300_000);
+    let final_ = BfdControlPacket {
+        final_: true,
+        ..remote(BfdState::Up)
+    };
+    session.receive(&final_, &config, now);
+    assert!(!session.control_packet(&config, false).poll);
+
+    // Reply to a poll with the final bit
+    let poll = BfdControlPacket {
+        poll: true,
+        ..remote(BfdState::Up)
+    };
+    assert!(session.receive(&poll, &config, now));
+    let sent = session.control_packet(&config, true);
+    assert!(sent.final_);
+    assert!(!sent.poll);
+
+    // Detection time is the remote multiplier times the slower of the remote
+    // transmit interval and the local receive interval
+    let mut rng = SmallRng::seed_from_u64(0);
+    session.poll_timers(&config, &mut rng, now + Duration::from_millis(850));
+    assert_eq!(session.state(), BfdState::Up);
+    session.poll_timers(&config, &mut rng, now + Duration::from_millis(900));
+    assert_eq!(session.state(), BfdState::Down);
+    assert_eq!(
+        session.local_diagnostic(),
+        BfdDiagnostic::ControlDetectionTimeExpired
+    );
+    assert_eq!(session.control_packet(&config, false).your_discriminator, 0);
+
+    session.receive(&remote(BfdState::Init), &config, now);
+    assert_eq!(session.state(), BfdState::Up);
+    session.receive(&remote(BfdState::AdminDown), &config, now);
+    assert_eq!(session.state(), BfdState::Down);
+    assert_eq!(
+        session.local_diagnostic(),
+        BfdDiagnostic::NeighborSignaledSessionDown
+    );
+}
+
+async fn wait_for(rx: &mut watch::Receiver<LivenessState>, state: LivenessState) {
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        rx.wait_for(|current| *current == state),
+    )
+    .await
+    .expect("timed out waiting for BFD state")
+    .expect("BFD stopped");
+}
+
+#[test_log::test(tokio::test)]
+async fn test_bfd_loopback() {
+    let addr_a = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 43784);
+    let addr_b = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 43785);
+    let config = |local_addr: SocketAddr, peer_port| {
+        BfdConfig::new(local_addr)
+            .with_peer_port(peer_port)
+            .with_desired_min_tx_interval(Duration::from_millis(50))
+            .with_required_min_rx_interval(Duration::from_millis(50))
+    };
+    let bfd_a = Bfd::bind(config(addr_a, addr_b.port())).unwrap();
+    let bfd_b = Bfd::bind(config(addr_b, addr_a.port())).unwrap();
+
+    let mut rx_a = bfd_a.subscribe(addr_b);
+    let mut rx_b = bfd_b.subscribe(addr_a);
+    assert_eq!(*rx_a.borrow(), LivenessState::Down);
+    wait_for(&mut rx_a, LivenessState::Up).await;
+    wait_for(&mut rx_b, LivenessState::Up).await;
+
+    // A second subscriber to the same peer sees the current state
+    let rx_a2 = bfd_a.subscribe(addr_b);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*rx_a2.borrow(), LivenessState::Up);
+
+    // Stopping B is detected by A after the detection time
+    drop(bfd_b);
+    drop(rx_b);
+    wait_for(&mut rx_a, LivenessState::Down).await;
+    assert_eq!(*rx_a2.borrow(), LivenessState::Down);
+}
+
+#[derive(Debug)]
+struct MockLivenessDetector(watch::Sender<LivenessState>);
+
+impl LivenessDetector<SocketAddr> for MockLivenessDetector {
+    fn subscribe(&self, peer_addr: SocketAddr) -> watch::Receiver<LivenessState> {
+        assert_eq!(peer_addr, PEER_ADDR);
+        self.0.subscribe()
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_peer_liveness_down() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .write(BgpMessage::Notification(
+            BgpNotificationMessage::CeaseError(CeaseError::BfdDown { value: vec![] }),
+        ));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    let (tx, _rx) = watch::channel(LivenessState::Up);
+    peer.set_liveness_detector(Some(Arc::new(MockLivenessDetector(tx.clone()))));
+    assert_eq!(peer.liveness_state(), Some(LivenessState::Up));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    tx.send_replace(LivenessState::Down);
+    assert_eq!(peer.run().await?, BgpEvent::AutomaticStop);
+    assert_eq!(peer.fsm_state(), FsmState::Idle);
+    assert_eq!(peer.liveness_state(), Some(LivenessState::Down));
+
+    // The session is started again once the peer is reported up
+    tx.send_replace(LivenessState::Up);
+    assert_eq!(peer.run().await?, BgpEvent::AutomaticStart);
+    assert_eq!(peer.fsm_state(), FsmState::Connect);
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::FramedRead;
+
+use netgauze_bgp_pkt::{
+    notification::{BgpNotificationMessage, CeaseError},
+    open::BgpOpenMessage,
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_bmp_pkt::{
+    codec::BmpCodec, iana::BmpMessageType, BmpMessage, BmpMessageValue, CounterU32, GaugeU64,
+    PeerDownNotificationReason, StatisticsCounter,
+};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    bmp::*,
+    events::BgpEvent,
+    export::{ExportRoute, RouteSource},
+    fsm::{FsmState, FsmStateError},
+    peer::*,
+    rib::Route,
+    tests::{
+        rib::{as_path_attr, ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+fn config() -> BmpExporterConfig {
+    BmpExporterConfig::new(vec![], MY_AS, MY_BGP_ID)
+}
+
+fn sent_open() -> BgpOpenMessage {
+    BgpOpenMessage::new(MY_AS as u16, HOLD_TIME, MY_BGP_ID, vec![])
+}
+
+fn received_open() -> BgpOpenMessage {
+    BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![])
+}
+
+fn update(prefix: &str) -> BgpUpdateMessage {
+    BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+        ],
+        vec![ipv4_unicast(prefix)],
+    )
+}
+
+fn types(messages: &[BmpMessage]) -> Vec<BmpMessageType> {
+    messages.iter().map(|msg| msg.get_type()).collect()
+}
+
+#[test]
+fn test_bmp_state() {
+    let mut state = BmpState::new(config());
+    // Events of peers that are not up are ignored
+    assert!(state
+        .apply(BmpPeerEvent::RouteMonitoring {
+            peer_addr: PEER_ADDR,
+            rib: BmpRib::AdjRibInPre,
+            update: update("10.0.0.0/24"),
+        })
+        .is_empty());
+
+    let messages = state.apply(BmpPeerEvent::PeerUp {
+        peer_addr: PEER_ADDR,
+        sent_open: sent_open(),
+        received_open: received_open(),
+    });
+    assert_eq!(types(&messages), vec![BmpMessageType::PeerUpNotification]);
+    match &messages[0] {
+        BmpMessage::V3(BmpMessageValue::PeerUpNotification(peer_up)) => {
+            assert_eq!(peer_up.peer_header().address(), Some(PEER_ADDR.ip()));
+            assert_eq!(peer_up.peer_header().peer_as(), PEER_AS);
+            assert_eq!(peer_up.peer_header().bgp_id(), PEER_BGP_ID);
+            assert_eq!(peer_up.remote_port(), Some(PEER_ADDR.port()));
+            assert_eq!(peer_up.sent_message(), &BgpMessage::Open(sent_open()));
+            assert_eq!(
+                peer_up.received_message(),
+                &BgpMessage::Open(received_open())
+            );
+        }
+        msg => panic!("unexpected message {msg:?}"),
+    }
+
+    for rib in [BmpRib::AdjRibInPre, BmpRib::AdjRibInPost, BmpRib::AdjRibOut] {
+        let messages = state.apply(BmpPeerEvent::RouteMonitoring {
+            peer_addr: PEER_ADDR,
+            rib,
+            update: update("10.0.0.0/24"),
+        });
+        assert_eq!(types(&messages), vec![BmpMessageType::RouteMonitoring]);
+    }
+    assert!(state
+        .apply(BmpPeerEvent::TreatAsWithdraw {
+            peer_addr: PEER_ADDR,
+            prefixes: 2,
+        })
+        .is_empty());
+    let route = ExportRoute::new(
+        ipv4_key("192.0.2.0/24"),
+        Route::new(vec![origin_attr()], None),
+        RouteSource::Local,
+    );
+    for update in loc_rib_updates(&[route], &[]) {
+        assert_eq!(
+            types(&state.apply::<SocketAddr>(BmpPeerEvent::LocRib(update))),
+            vec![BmpMessageType::RouteMonitoring]
+        );
+    }
+
+    // The state is replayed when the collector connects
+    assert_eq!(
+        types(&state.snapshot()),
+        vec![
+            BmpMessageType::Initiation,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::RouteMonitoring,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::RouteMonitoring,
+            BmpMessageType::RouteMonitoring,
+            BmpMessageType::RouteMonitoring,
+        ]
+    );
+
+    let statistics = state.statistics();
+    assert_eq!(statistics.len(), 2);
+    match &statistics[1] {
+        BmpMessage::V3(BmpMessageValue::StatisticsReport(report)) => {
+            assert_eq!(
+                report.counters(),
+                &vec![
+                    StatisticsCounter::NumberOfUpdatesSubjectedToTreatAsWithdraw(CounterU32::new(
+                        1
+                    )),
+                    StatisticsCounter::NumberOfPrefixesSubjectedToTreatAsWithdraw(CounterU32::new(
+                        2
+                    )),
+                    StatisticsCounter::NumberOfRoutesInAdjRibIn(GaugeU64::new(1)),
+                    StatisticsCounter::NumberOfRoutesInPerAfiSafiAdjRibIn(
+                        AddressType::Ipv4Unicast,
+                        GaugeU64::new(1)
+                    ),
+                    StatisticsCounter::NumberOfRoutesInPostPolicyAdjRibOut(GaugeU64::new(1)),
+                    StatisticsCounter::NumberOfRoutesInPerAfiSafiPostPolicyAdjRibOut(
+                        AddressType::Ipv4Unicast,
+                        GaugeU64::new(1)
+                    ),
+                ]
+            );
+        }
+        msg => panic!("unexpected message {msg:?}"),
+    }
+
+    let messages = state.apply(BmpPeerEvent::PeerDown {
+        peer_addr: PEER_ADDR,
+        reason: PeerDownNotificationReason::PeerDeConfigured,
+    });
+    assert_eq!(types(&messages), vec![BmpMessageType::PeerDownNotification]);
+    assert!(state
+        .apply(BmpPeerEvent::PeerDown {
+            peer_addr: PEER_ADDR,
+            reason: PeerDownNotificationReason::PeerDeConfigured,
+        })
+        .is_empty());
+    assert_eq!(
+        types(&state.snapshot()),
+        vec![
+            BmpMessageType::Initiation,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::RouteMonitoring,
+        ]
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_peer_bmp_events() -> Result<(), FsmStateError<SocketAddr>> {
+    let cease =
+        BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(sent_open()))
+        .read(BgpMessage::Open(received_open()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update("10.0.0.0/24")))
+        .read(BgpMessage::Notification(cease.clone()));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    let (exporter, mut rx) = BmpExporter::channel();
+    peer.set_bmp_exporter(Some(exporter));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    assert_eq!(
+        rx.try_recv().ok(),
+        Some(BmpPeerEvent::PeerUp {
+            peer_addr: PEER_ADDR,
+            sent_open: sent_open(),
+            received_open: received_open(),
+        })
+    );
+
+    peer.run().await?;
+    for rib in [BmpRib::AdjRibInPre, BmpRib::AdjRibInPost] {
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(BmpPeerEvent::RouteMonitoring {
+                peer_addr: PEER_ADDR,
+                rib,
+                update: update("10.0.0.0/24"),
+            })
+        );
+    }
+
+    assert_eq!(peer.run().await?, BgpEvent::NotifMsg(cease.clone()));
+    assert_eq!(
+        rx.try_recv().ok(),
+        Some(BmpPeerEvent::PeerDown {
+            peer_addr: PEER_ADDR,
+            reason: PeerDownNotificationReason::RemoteSystemClosedNotificationPduFollows(
+                BgpMessage::Notification(cease)
+            ),
+        })
+    );
+    assert!(rx.try_recv().is_err());
+    Ok(())
+}
+
+async fn read_types(
+    framed: &mut FramedRead<TcpStream, BmpCodec>,
+    count: usize,
+) -> Vec<BmpMessageType> {
+    let mut received = vec![];
+    for _ in 0..count {
+        let msg = tokio::time::timeout(Duration::from_secs(5), framed.next())
+            .await
+            .expect("BMP message not received")
+            .unwrap()
+            .unwrap();
+        received.push(msg.get_type());
+    }
+    received
+}
+
+#[test_log::test(tokio::test)]
+async fn test_bmp_exporter_reconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let collector = listener.local_addr().unwrap();
+    let config = BmpExporterConfig::new(vec![collector], MY_AS, MY_BGP_ID)
+        .with_stats_interval(Duration::ZERO)
+        .with_reconnect_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    let exporter = BmpExporter::<SocketAddr>::start(config);
+    exporter.send(BmpPeerEvent::PeerUp {
+        peer_addr: PEER_ADDR,
+        sent_open: sent_open(),
+        received_open: received_open(),
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = FramedRead::new(stream, BmpCodec::default());
+    assert_eq!(
+        read_types(&mut framed, 3).await,
+        vec![
+            BmpMessageType::Initiation,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::PeerUpNotification,
+        ]
+    );
+    exporter.send(BmpPeerEvent::RouteMonitoring {
+        peer_addr: PEER_ADDR,
+        rib: BmpRib::AdjRibInPre,
+        update: update("10.0.0.0/24"),
+    });
+    assert_eq!(
+        read_types(&mut framed, 1).await,
+        vec![BmpMessageType::RouteMonitoring]
+    );
+
+    // The state is sent again after reconnecting
+    drop(framed);
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = FramedRead::new(stream, BmpCodec::default());
+    assert_eq!(
+        read_types(&mut framed, 4).await,
+        vec![
+            BmpMessageType::Initiation,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::PeerUpNotification,
+            BmpMessageType::RouteMonitoring,
+        ]
+    );
+
+    // Termination is sent once the exporter is dropped
+    drop(exporter);
+    assert_eq!(
+        read_types(&mut framed, 1).await,
+        vec![BmpMessageType::Termination]
+    );
+}
@@ -0,0 +1,300 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, FourOctetAsCapability},
+    iana::AS_TRANS,
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::{
+        As2PathSegment, AsPath, AsPathSegmentType, LocalPreference, MultiExitDiscriminator,
+        PathAttribute, PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+
+use crate::{
+    confederation::*,
+    events::{BgpEvent, UpdateTreatment},
+    export::*,
+    fsm::{FsmState, FsmStateError},
+    peer::*,
+    rib::Route,
+    tests::{
+        rib::{ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+const CONFED_ID: u32 = 65000;
+
+fn segments_attr(segments: Vec<As2PathSegment>) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(segments)),
+    )
+    .unwrap()
+}
+
+fn med_attr(med: u32) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MultiExitDiscriminator(MultiExitDiscriminator::new(med)),
+    )
+    .unwrap()
+}
+
+fn local_pref_attr(local_pref: u32) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::LocalPreference(LocalPreference::new(local_pref)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_set_open_asn() {
+    let open = BgpOpenMessage::new(
+        MY_AS as u16,
+        HOLD_TIME,
+        MY_BGP_ID,
+        vec![BgpOpenMessageParameter::Capabilities(vec![
+            BgpCapability::FourOctetAs(FourOctetAsCapability::new(MY_AS)),
+        ])],
+    );
+    assert_eq!(
+        set_open_asn(open.clone(), CONFED_ID),
+        BgpOpenMessage::new(
+            CONFED_ID as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![BgpOpenMessageParameter::Capabilities(vec![
+                BgpCapability::FourOctetAs(FourOctetAsCapability::new(CONFED_ID)),
+            ])],
+        )
+    );
+
+    // Four-octet AS capability is added for confederation identifiers that don't
+    // fit in two octets
+    let open = BgpOpenMessage::new(MY_AS as u16, HOLD_TIME, MY_BGP_ID, vec![]);
+    assert_eq!(
+        set_open_asn(open, 4_200_000_000),
+        BgpOpenMessage::new(
+            AS_TRANS,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![BgpOpenMessageParameter::Capabilities(vec![
+                BgpCapability::FourOctetAs(FourOctetAsCapability::new(4_200_000_000)),
+            ])],
+        )
+    );
+}
+
+#[test]
+fn test_confed_properties() {
+    let external = PROPERTIES.with_confederation_id(Some(CONFED_ID));
+    assert!(!external.is_confed_external());
+    assert_eq!(external.local_asn(), CONFED_ID);
+
+    let member = external.with_confederation_member(true);
+    assert!(member.is_confed_external());
+    assert_eq!(member.local_asn(), MY_AS);
+
+    let internal = PeerProperties::new(MY_AS, MY_AS, MY_BGP_ID, PEER_ADDR, false)
+        .with_confederation_id(Some(CONFED_ID));
+    assert!(!internal.is_confed_external());
+    assert_eq!(internal.local_asn(), MY_AS);
+
+    assert_eq!(PROPERTIES.local_asn(), MY_AS);
+}
+
+#[test]
+fn test_export_confed_external() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default()
+        .with_as_prepend(2)
+        .with_next_hop_self_ipv4(Some(Ipv4Addr::new(192, 0, 2, 254)));
+    let next_hop = next_hop_attr(Ipv4Addr::new(192, 0, 2, 1));
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            segments_attr(vec![As2PathSegment::new(
+                AsPathSegmentType::AsSequence,
+                vec![400],
+            )]),
+            next_hop.clone(),
+            med_attr(10),
+            local_pref_attr(200),
+        ],
+        None,
+    );
+    let properties = PeerProperties::new(MY_AS, 300, MY_BGP_ID, PEER_ADDR, false)
+        .with_confederation_id(Some(CONFED_ID))
+        .with_confederation_member(true);
+
+    // The member AS is prepended once in an AS_CONFED_SEQUENCE, while MED and
+    // LOCAL_PREF are kept
+    let exported = export_route(&properties, &config, &key, &route, RouteSource::External).unwrap();
+    assert_eq!(
+        exported.attributes(),
+        &vec![
+            origin_attr(),
+            segments_attr(vec![
+                As2PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![MY_AS as u16]),
+                As2PathSegment::new(AsPathSegmentType::AsSequence, vec![400]),
+            ]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 254)),
+            med_attr(10),
+            local_pref_attr(200),
+        ]
+    );
+
+    // Existing AS_CONFED_SEQUENCE is extended
+    let exported = export_route(
+        &properties,
+        &ExportConfig::default(),
+        &key,
+        &Route::new(exported.attributes().clone(), None),
+        RouteSource::External,
+    )
+    .unwrap();
+    assert_eq!(
+        exported.attributes()[1],
+        segments_attr(vec![
+            As2PathSegment::new(
+                AsPathSegmentType::AsConfedSequence,
+                vec![MY_AS as u16, MY_AS as u16]
+            ),
+            As2PathSegment::new(AsPathSegmentType::AsSequence, vec![400]),
+        ])
+    );
+}
+
+#[test]
+fn test_export_outside_confed() {
+    let key = ipv4_key("10.0.0.0/24");
+    let next_hop = next_hop_attr(Ipv4Addr::new(192, 0, 2, 1));
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            segments_attr(vec![
+                As2PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![300]),
+                As2PathSegment::new(AsPathSegmentType::AsConfedSet, vec![301, 302]),
+                As2PathSegment::new(AsPathSegmentType::AsSequence, vec![400]),
+            ]),
+            next_hop.clone(),
+            med_attr(10),
+            local_pref_attr(200),
+        ],
+        None,
+    );
+    let properties = PROPERTIES.with_confederation_id(Some(CONFED_ID));
+
+    // Confederation segments are removed and the confederation identifier is
+    // prepended instead of the member AS
+    let exported = export_route(
+        &properties,
+        &ExportConfig::default().with_as_prepend(1),
+        &key,
+        &route,
+        RouteSource::External,
+    )
+    .unwrap();
+    assert_eq!(
+        exported.attributes(),
+        &vec![
+            origin_attr(),
+            segments_attr(vec![As2PathSegment::new(
+                AsPathSegmentType::AsSequence,
+                vec![CONFED_ID as u16, CONFED_ID as u16, 400]
+            )]),
+            next_hop,
+        ]
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_open_confed_id() -> Result<(), FsmStateError<SocketAddr>> {
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            segments_attr(vec![
+                As2PathSegment::new(AsPathSegmentType::AsConfedSequence, vec![300]),
+                As2PathSegment::new(AsPathSegmentType::AsSequence, vec![PEER_AS as u16]),
+            ]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            CONFED_ID as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update.clone()));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let properties = PROPERTIES.with_confederation_id(Some(CONFED_ID));
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    // Confederation segments received from outside the confederation are
+    // malformed
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::UpdateMsg(update, UpdateTreatment::TreatAsWithdraw)
+    );
+    assert_eq!(peer.adj_rib_in().get(&ipv4_key("10.0.0.0/24")), None);
+    Ok(())
+}
@@ -21,8 +21,24 @@ use netgauze_bgp_pkt::notification::{
 use tokio_test::io::Mock;
 use tokio_util::codec::Framed;
 
-use crate::{connection::ConnectionConfig, events::ConnectionEvent, fsm::FsmStateError, tests::*};
-use netgauze_bgp_pkt::open::BgpOpenMessage;
+use crate::{
+    connection::ConnectionConfig,
+    events::{ConnectionEvent, UpdateTreatment},
+    fsm::FsmStateError,
+    tests::{
+        rib::{as_path_attr, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+use ipnet::Ipv6Net;
+use netgauze_bgp_pkt::{
+    nlri::{Ipv6Unicast, Ipv6UnicastAddress},
+    notification::UpdateMessageError,
+    open::BgpOpenMessage,
+    path_attribute::{MpReach, MpUnreach, PathAttribute, PathAttributeValue},
+    update::BgpUpdateMessage,
+};
+use std::{net::Ipv6Addr, str::FromStr};
 
 async fn get_connection(
     io: Mock,
@@ -419,3 +435,181 @@ async fn test_open_confirm_hold_timer_expires() -> io::Result<()> {
     assert!(connection.stats().last_received().is_some());
     Ok(())
 }
+
+#[cfg(unix)]
+#[test_log::test(tokio::test)]
+async fn test_unix_active_connect() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        connection::UnixActiveConnect,
+        fsm::FsmState,
+        peer::{PeerConfig, PeerConfigBuilder, PeerProperties},
+        supervisor::PeersSupervisor,
+    };
+    use tokio::net::{UnixListener, UnixStream};
+
+    let dir = std::env::temp_dir().join(format!("netgauze-unix-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("peer.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    // Unix sockets carry no addresses, the configured peer address is used as-is
+    let my_addr = SocketAddr::new(IpAddr::V4(MY_BGP_ID), 179);
+    let mut active: PeersSupervisor<IpAddr, SocketAddr, UnixStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut passive: PeersSupervisor<IpAddr, SocketAddr, UnixStream> =
+        PeersSupervisor::new(PEER_AS, PEER_BGP_ID);
+    let (mut active_rx, active_handle) = active
+        .create_peer(
+            PEER_ADDR.ip(),
+            PeerProperties::new(MY_AS, PEER_AS, MY_BGP_ID, PEER_ADDR, false),
+            PeerConfig::default(),
+            UnixActiveConnect::new().with_path(PEER_ADDR, path.clone()),
+            EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, vec![], vec![]),
+        )
+        .unwrap();
+    let (mut passive_rx, mut passive_handle) = passive
+        .create_peer(
+            my_addr.ip(),
+            PeerProperties::new(PEER_AS, MY_AS, PEER_BGP_ID, my_addr, false),
+            PeerConfigBuilder::new()
+                .passive_tcp_establishment(true)
+                .build(),
+            UnixActiveConnect::new(),
+            EchoCapabilitiesPolicy::new(PEER_AS, false, PEER_BGP_ID, HOLD_TIME, vec![], vec![]),
+        )
+        .unwrap();
+    passive_handle.start()?;
+    active_handle.start()?;
+    let (stream, _) = listener.accept().await?;
+    passive_handle.accept_connection(my_addr, stream)?;
+
+    for rx in [&mut active_rx, &mut passive_rx] {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = rx.recv().await {
+                if matches!(event, Ok((FsmState::Established, _))) {
+                    return;
+                }
+            }
+            panic!("peer stopped before being established");
+        })
+        .await?;
+    }
+    std::fs::remove_dir_all(&dir)?;
+
+    assert!(UnixActiveConnect::new().path(&PEER_ADDR).is_none());
+    Ok(())
+}
+
+/// Open a connection and receive `update` while in OpenSent state
+async fn receive_update(update: BgpUpdateMessage) -> Option<ConnectionEvent<SocketAddr>> {
+    let mut policy =
+        EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, Vec::new(), Vec::new());
+    let io = BgpIoMockBuilder::new()
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Update(update))
+        .build();
+    let config = ConnectionConfigBuilder::new()
+        .open_delay_timer_duration(0)
+        .build();
+    let mut connection = get_connection(io, &mut policy, config).await.unwrap();
+    connection.next().await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_next_hop_after_mandatory_attributes() {
+    // NEXT_HOP comes after ORIGIN and AS_PATH, it must still be accounted for
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 168, 0, 2)),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let event = receive_update(update.clone()).await;
+    assert_eq!(
+        event,
+        Some(ConnectionEvent::UpdateMsg(update, UpdateTreatment::Normal))
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_duplicate_mp_reach_after_mandatory_attributes() {
+    // The MP_REACH_NLRI attributes come after ORIGIN and AS_PATH, the duplicate
+    // must still be detected
+    let mp_reach = PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MpReach(MpReach::Ipv6Unicast {
+            next_hop_global: Ipv6Addr::from_str("2001:db8::1").unwrap(),
+            next_hop_local: None,
+            nlri: vec![Ipv6UnicastAddress::new(
+                None,
+                Ipv6Unicast::from_net(Ipv6Net::from_str("2001:db8:1::/48").unwrap()).unwrap(),
+            )],
+        }),
+    )
+    .unwrap();
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            mp_reach.clone(),
+            mp_reach,
+        ],
+        vec![],
+    );
+    let event = receive_update(update).await;
+    assert_eq!(
+        event,
+        Some(ConnectionEvent::UpdateMsgErr(
+            UpdateMessageError::MalformedAttributeList { value: vec![] }
+        ))
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_duplicate_mp_unreach_after_mandatory_attributes() {
+    // The MP_UNREACH_NLRI attributes come after ORIGIN and AS_PATH, the
+    // duplicate must still be detected
+    let mp_unreach = PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MpUnreach(MpUnreach::Ipv6Unicast {
+            nlri: vec![Ipv6UnicastAddress::new(
+                None,
+                Ipv6Unicast::from_net(Ipv6Net::from_str("2001:db8:1::/48").unwrap()).unwrap(),
+            )],
+        }),
+    )
+    .unwrap();
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            mp_unreach.clone(),
+            mp_unreach,
+        ],
+        vec![],
+    );
+    let event = receive_update(update).await;
+    assert_eq!(
+        event,
+        Some(ConnectionEvent::UpdateMsgErr(
+            UpdateMessageError::MalformedAttributeList { value: vec![] }
+        ))
+    );
+}
@@ -0,0 +1,373 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use tokio::time::Instant;
+
+use netgauze_bgp_pkt::{open::BgpOpenMessage, update::BgpUpdateMessage, BgpMessage};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    damping::*,
+    events::{BgpEvent, UpdateTreatment},
+    fsm::{FsmState, FsmStateError},
+    peer::{Peer, PeerAdminEvents, PeerConfigBuilder},
+    rib::{RouteKey, UpdateRoutes},
+    supervisor::PeersSupervisor,
+    tests::{
+        rib::{as_path_attr, ipv4_unicast, next_hop_attr, origin_attr},
+        BgpIoMockBuilder, MockActiveConnect, HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS,
+        PEER_BGP_ID, PEER_KEY, POLICY, PROPERTIES,
+    },
+};
+
+const PREFIX: &str = "10.0.0.0/24";
+
+fn config() -> RouteFlapDampingConfig {
+    RouteFlapDampingConfigBuilder::new()
+        .half_life(60)
+        .reuse_threshold(750)
+        .suppress_threshold(2000)
+        .max_suppress_time(240)
+        .build()
+}
+
+fn key() -> RouteKey {
+    RouteKey::new(
+        AddressType::Ipv4Unicast,
+        None,
+        IpNet::from_str(PREFIX).unwrap(),
+    )
+}
+
+fn announce(as_path: Vec<u16>) -> UpdateRoutes {
+    UpdateRoutes::from(&BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(as_path),
+            next_hop_attr(Ipv4Addr::new(192, 168, 0, 2)),
+        ],
+        vec![ipv4_unicast(PREFIX)],
+    ))
+}
+
+fn withdraw() -> UpdateRoutes {
+    UpdateRoutes::from(&BgpUpdateMessage::new(
+        vec![ipv4_unicast(PREFIX)],
+        vec![],
+        vec![],
+    ))
+}
+
+#[test]
+fn test_damping_config() {
+    let config = RouteFlapDampingConfig::default();
+    assert_eq!(config.suppress_threshold(), 6000);
+    assert_eq!(config.half_life(), Duration::from_secs(900));
+    assert_eq!(config.penalty_ceiling(), 750.0 * 16.0);
+    assert_eq!(self::config().penalty_ceiling(), 750.0 * 16.0);
+}
+
+#[test]
+fn test_damping_suppress_and_reuse() {
+    let mut damping = RouteFlapDamping::new(config());
+    let now = Instant::now();
+
+    for flap in 1..=2 {
+        let passed = damping.apply(PEER_KEY, announce(vec![200]), now);
+        assert_eq!(passed, announce(vec![200]));
+        let passed = damping.apply(PEER_KEY, withdraw(), now);
+        assert_eq!(passed, withdraw());
+        let state = damping.get(&PEER_KEY, &key(), now).unwrap();
+        assert_eq!(state.flaps(), flap);
+        assert_eq!(state.penalty(), flap * 1000);
+        assert!(!state.suppressed());
+    }
+    assert!(damping.dampened_routes(now).is_empty());
+
+    // Third flap crosses the suppress threshold
+    damping.apply(PEER_KEY, announce(vec![200]), now);
+    damping.apply(PEER_KEY, withdraw(), now);
+    let passed = damping.apply(PEER_KEY, announce(vec![200]), now);
+    assert!(passed.is_empty());
+
+    let dampened = damping.dampened_routes(now);
+    assert_eq!(dampened.len(), 1);
+    assert_eq!(dampened[0].peer(), PEER_KEY);
+    assert_eq!(dampened[0].key(), key());
+    assert_eq!(dampened[0].penalty(), 3000);
+    assert!(dampened[0].suppressed());
+    // 3000 decays to 750 after two half-lives
+    assert_eq!(dampened[0].reuse_in(), Some(Duration::from_secs(120)));
+    assert_eq!(
+        damping.next_reuse(now),
+        Some(now + Duration::from_secs(120))
+    );
+
+    let later = now + Duration::from_secs(60);
+    assert!(damping.reuse(later).is_empty());
+    assert_eq!(
+        damping.get(&PEER_KEY, &key(), later).unwrap().penalty(),
+        1500
+    );
+
+    let later = now + Duration::from_secs(121);
+    let reused = damping.reuse(later);
+    assert_eq!(reused, vec![(PEER_KEY, announce(vec![200]))]);
+    assert!(damping.dampened_routes(later).is_empty());
+    assert_eq!(damping.flap_statistics(later).len(), 1);
+}
+
+#[test]
+fn test_damping_attribute_change() {
+    let mut damping = RouteFlapDamping::new(
+        RouteFlapDampingConfigBuilder::new()
+            .half_life(60)
+            .suppress_threshold(1000)
+            .build(),
+    );
+    let now = Instant::now();
+    damping.apply(PEER_KEY, announce(vec![200]), now);
+    // Same path doesn't add a penalty
+    damping.apply(PEER_KEY, announce(vec![200]), now);
+    assert!(damping.flap_statistics(now).is_empty());
+
+    damping.apply(PEER_KEY, announce(vec![200, 300]), now);
+    let passed = damping.apply(PEER_KEY, announce(vec![200]), now);
+    assert_eq!(passed, announce(vec![200]));
+    assert_eq!(damping.get(&PEER_KEY, &key(), now).unwrap().penalty(), 1000);
+
+    // The route is withdrawn from the Loc-RIB when it gets suppressed
+    let passed = damping.apply(PEER_KEY, announce(vec![200, 300]), now);
+    assert_eq!(passed, UpdateRoutes::new(vec![], vec![key()]));
+    let state = damping.get(&PEER_KEY, &key(), now).unwrap();
+    assert!(state.suppressed());
+    assert_eq!(state.flaps(), 0);
+
+    // Latest announcement is used when the route is reused
+    damping.apply(PEER_KEY, announce(vec![200]), now);
+    assert_eq!(
+        damping.clear_peer(&PEER_KEY),
+        UpdateRoutes::new(announce(vec![200]).announced().clone(), vec![])
+    );
+    assert!(damping.get(&PEER_KEY, &key(), now).is_none());
+}
+
+#[test]
+fn test_damping_penalty_ceiling() {
+    let mut damping = RouteFlapDamping::new(config());
+    let now = Instant::now();
+    for _ in 0..20 {
+        damping.apply(PEER_KEY, announce(vec![200]), now);
+        damping.apply(PEER_KEY, withdraw(), now);
+    }
+    let state = damping.get(&PEER_KEY, &key(), now).unwrap();
+    assert_eq!(state.flaps(), 20);
+    assert_eq!(state.penalty(), 12000);
+    assert_eq!(state.reuse_in(), Some(config().max_suppress_time()));
+
+    // History of withdrawn routes is forgotten once the penalty decays
+    let later = now + Duration::from_secs(600);
+    assert!(damping.reuse(later).is_empty());
+    assert!(damping.get(&PEER_KEY, &key(), later).is_none());
+}
+
+fn announce_update() -> BgpUpdateMessage {
+    BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 168, 0, 2)),
+        ],
+        vec![ipv4_unicast(PREFIX)],
+    )
+}
+
+fn withdraw_update() -> BgpUpdateMessage {
+    BgpUpdateMessage::new(vec![ipv4_unicast(PREFIX)], vec![], vec![])
+}
+
+/// Peer receiving a route that flaps twice then is announced again, the
+/// session stays up long enough for the suppressed route to be reused
+fn flapping_route_connect() -> MockActiveConnect {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    for _ in 0..2 {
+        io_builder
+            .read(BgpMessage::Update(announce_update()))
+            .read(BgpMessage::Update(withdraw_update()));
+    }
+    io_builder
+        .read(BgpMessage::Update(announce_update()))
+        .wait(Duration::from_secs(30));
+    MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    }
+}
+
+fn peer_damping_config() -> RouteFlapDampingConfig {
+    RouteFlapDampingConfigBuilder::new()
+        .half_life(10)
+        .reuse_threshold(750)
+        .suppress_threshold(1500)
+        .max_suppress_time(40)
+        .build()
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_route_flap_damping() -> Result<(), FsmStateError<SocketAddr>> {
+    let config = PeerConfigBuilder::new()
+        .route_flap_damping(Some(peer_damping_config()))
+        .build();
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        config,
+        POLICY,
+        flapping_route_connect(),
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+
+    for _ in 0..2 {
+        let event = peer.run().await?;
+        assert_eq!(
+            event,
+            BgpEvent::UpdateMsg(announce_update(), UpdateTreatment::Normal)
+        );
+        let event = peer.run().await?;
+        assert_eq!(
+            event,
+            BgpEvent::UpdateMsg(withdraw_update(), UpdateTreatment::Normal)
+        );
+    }
+    let state = peer.damped_route(&key()).unwrap();
+    assert_eq!(state.flaps(), 2);
+    assert!(state.suppressed());
+
+    // The announcement is held back but kept in the Adj-RIB-In
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(
+            BgpUpdateMessage::new(vec![], vec![], vec![]),
+            UpdateTreatment::Normal
+        )
+    );
+    assert!(peer.adj_rib_in().get(&key()).is_some());
+    assert_eq!(peer.dampened_routes().len(), 1);
+
+    // 2000 decays to the reuse threshold of 750 in about 14 seconds
+    let before = Instant::now();
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::RouteFlapDampingReuseTimerExpires);
+    assert_eq!(before.elapsed().as_secs(), 14);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::DampedRoutesReused(announce_update()));
+    assert!(peer.dampened_routes().is_empty());
+    assert_eq!(peer.flap_statistics().len(), 1);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_route_flap_damping_disabled() -> Result<(), FsmStateError<SocketAddr>> {
+    let config = PeerConfigBuilder::new()
+        .route_flap_damping(Some(peer_damping_config()))
+        .build();
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        config.clone(),
+        POLICY,
+        flapping_route_connect(),
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.dampened_routes().is_empty() || peer.adj_rib_in().get(&key()).is_none() {
+        peer.run().await?;
+    }
+
+    // Disabling damping releases the suppressed routes
+    peer.update_config(PeerConfigBuilder::new().build());
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::DampedRoutesReused(announce_update()));
+    assert!(peer.damped_route(&key()).is_none());
+    assert!(peer.flap_statistics().is_empty());
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_supervisor_route_flap_damping_queries() {
+    let mut supervisor = PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let config = PeerConfigBuilder::new()
+        .route_flap_damping(Some(peer_damping_config()))
+        .build();
+    let (mut rx, handle) = supervisor
+        .create_peer(
+            PEER_KEY,
+            PROPERTIES,
+            config,
+            flapping_route_connect(),
+            POLICY,
+        )
+        .unwrap();
+    handle.start().unwrap();
+
+    // Two flaps and the suppressed announcement
+    let mut updates = 0;
+    while updates < 5 {
+        if let Ok((_, BgpEvent::UpdateMsg(_, _))) = rx.recv().await.unwrap() {
+            updates += 1;
+        }
+    }
+    let dampened = supervisor.dampened_routes().await;
+    assert_eq!(dampened.len(), 1);
+    assert_eq!(dampened[0].peer(), PEER_KEY);
+    assert_eq!(dampened[0].key(), key());
+    assert!(dampened[0].suppressed());
+    let state = supervisor.damped_route(&PEER_KEY, key()).await.unwrap();
+    assert_eq!(state.flaps(), 2);
+    let unknown_peer = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3));
+    assert!(supervisor
+        .damped_route(&unknown_peer, key())
+        .await
+        .is_none());
+    assert_eq!(supervisor.flap_statistics().await.len(), 1);
+}
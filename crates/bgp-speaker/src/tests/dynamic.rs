@@ -0,0 +1,184 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    connection::{Connection, ConnectionConfigBuilder, ConnectionType, TcpActiveConnect},
+    dynamic::{
+        match_dynamic_peer_range, DynamicPeerPolicy, DynamicPeerRange, DynamicPeerTemplate,
+        RemoteAsns,
+    },
+    events::ConnectionEvent,
+    listener::{BgpListener, SessionKey},
+    peer::{PeerConfigBuilder, PeerPolicy},
+    reflection::ClientRole,
+    supervisor::{PeersSupervisor, PeersSupervisorError},
+    tests::{HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS, PEER_BGP_ID},
+};
+use ipnet::IpNet;
+use netgauze_bgp_pkt::{codec::BgpCodec, notification::OpenMessageError, open::BgpOpenMessage};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::TcpStream;
+use tokio_test::io::Mock;
+use tokio_util::codec::Framed;
+
+#[test]
+fn test_remote_asns() {
+    assert!(RemoteAsns::Any.contains(PEER_AS));
+    let list = RemoteAsns::List(vec![PEER_AS, 65000]);
+    assert!(list.contains(PEER_AS));
+    assert!(list.contains(65000));
+    assert!(!list.contains(MY_AS));
+    let range = RemoteAsns::Range(64512..=65534);
+    assert!(range.contains(64512));
+    assert!(range.contains(65534));
+    assert!(!range.contains(65535));
+    assert!(!range.contains(PEER_AS));
+}
+
+#[test]
+fn test_match_dynamic_peer_range() {
+    let wide = DynamicPeerRange::new(
+        "10.0.0.0/8".parse().unwrap(),
+        DynamicPeerTemplate::default(),
+    );
+    let narrow = DynamicPeerRange::new(
+        "10.1.0.0/16".parse().unwrap(),
+        DynamicPeerTemplate::default().with_client_role(ClientRole::RouteServerClient),
+    )
+    .with_max_peers(Some(2));
+    let v6 = DynamicPeerRange::new(
+        "2001:db8::1/32".parse().unwrap(),
+        DynamicPeerTemplate::default(),
+    );
+    assert_eq!(v6.prefix(), "2001:db8::/32".parse::<IpNet>().unwrap());
+    let ranges = vec![narrow.clone(), wide.clone(), v6.clone()];
+
+    let in_narrow = Ipv4Addr::new(10, 1, 0, 1);
+    assert_eq!(
+        match_dynamic_peer_range(&ranges, &IpAddr::V4(in_narrow)),
+        Some(&narrow)
+    );
+    // IPv4 peers on dual-stack sockets are seen as IPv4-mapped addresses
+    assert_eq!(
+        match_dynamic_peer_range(&ranges, &IpAddr::V6(in_narrow.to_ipv6_mapped())),
+        Some(&narrow)
+    );
+    assert_eq!(
+        match_dynamic_peer_range(&ranges, &IpAddr::V4(Ipv4Addr::new(10, 2, 0, 1))),
+        Some(&wide)
+    );
+    assert_eq!(
+        match_dynamic_peer_range(
+            &ranges,
+            &IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2))
+        ),
+        Some(&v6)
+    );
+    assert_eq!(
+        match_dynamic_peer_range(&ranges, &IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))),
+        None
+    );
+
+    let mut listener: BgpListener<SocketAddr, TcpStream> = BgpListener::new(vec![], true);
+    listener.add_dynamic_peer_range(wide);
+    listener.add_dynamic_peer_range(narrow.clone());
+    assert!(listener.is_dynamic_peer_allowed(&IpAddr::V4(in_narrow)));
+    assert!(!listener.is_dynamic_peer_allowed(&IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))));
+    assert_eq!(
+        listener.dynamic_peer_range(&IpAddr::V4(in_narrow)),
+        Some(&narrow)
+    );
+    assert_eq!(listener.dynamic_peer_count(&narrow.prefix()), 0);
+}
+
+#[test]
+fn test_dynamic_peer_template() {
+    let config = PeerConfigBuilder::new()
+        .hold_timer_duration(HOLD_TIME)
+        .passive_tcp_establishment(true)
+        .build();
+    let template =
+        DynamicPeerTemplate::new(config.clone()).with_client_role(ClientRole::RouteReflectorClient);
+    let properties = template.properties(MY_AS, MY_BGP_ID, PEER_ADDR);
+    assert_eq!(properties.my_asn(), MY_AS);
+    assert!(properties.allow_dynamic_as());
+    assert_eq!(properties.client_role(), ClientRole::RouteReflectorClient);
+    assert_eq!(template.config(), &config);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dynamic_peer_policy_rejects_asn() {
+    let mut policy: DynamicPeerPolicy<SocketAddr, Mock, BgpCodec> =
+        DynamicPeerTemplate::default().policy(MY_AS, MY_BGP_ID, RemoteAsns::List(vec![PEER_AS]));
+    let properties = DynamicPeerTemplate::default().properties(MY_AS, MY_BGP_ID, PEER_ADDR);
+    let framed = Framed::new(tokio_test::io::Builder::new().build(), BgpCodec::new(true));
+    let connection = Connection::new(
+        &properties,
+        PEER_ADDR,
+        ConnectionType::Passive,
+        ConnectionConfigBuilder::new().build(),
+        framed,
+        0.95,
+    );
+
+    let allowed = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let event = policy
+        .pre_handle_connection_event_hook(ConnectionEvent::BGPOpen(allowed.clone()), &connection)
+        .await;
+    assert_eq!(event, ConnectionEvent::BGPOpen(allowed));
+
+    let rejected = BgpOpenMessage::new(65000, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let event = policy
+        .pre_handle_connection_event_hook(
+            ConnectionEvent::BGPOpenWithDelayOpenTimer(rejected),
+            &connection,
+        )
+        .await;
+    assert_eq!(
+        event,
+        ConnectionEvent::BGPOpenMsgErr(OpenMessageError::BadPeerAs {
+            value: 65000u32.to_be_bytes().to_vec()
+        })
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dynamic_peer_from_range() -> Result<(), PeersSupervisorError> {
+    let range = DynamicPeerRange::new(
+        "192.168.0.0/24".parse().unwrap(),
+        DynamicPeerTemplate::default(),
+    )
+    .with_remote_asns(RemoteAsns::Range(PEER_AS..=PEER_AS + 10));
+    let peer_key = SessionKey::new(PEER_ADDR.ip());
+    let mut supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let (_rx, _handle) = supervisor.dynamic_peer_from_range::<BgpCodec, _>(
+        peer_key,
+        PEER_ADDR,
+        TcpActiveConnect::new(),
+        &range,
+    )?;
+    assert_eq!(supervisor.peer_keys(), vec![peer_key]);
+    let duplicate = supervisor.dynamic_peer_from_range::<BgpCodec, _>(
+        peer_key,
+        PEER_ADDR,
+        TcpActiveConnect::new(),
+        &range,
+    );
+    assert!(matches!(duplicate, Err(PeersSupervisorError::PeerExists)));
+    assert!(supervisor.remove_peer(&peer_key).is_some());
+    assert!(supervisor.peer_keys().is_empty());
+    Ok(())
+}
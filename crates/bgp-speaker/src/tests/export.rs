@@ -0,0 +1,401 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+
+use netgauze_bgp_pkt::{
+    open::BgpOpenMessage,
+    path_attribute::{
+        As2PathSegment, AsPath, AsPathSegmentType, LocalPreference, MultiExitDiscriminator,
+        PathAttribute, PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    events::BgpEvent,
+    export::*,
+    fsm::{FsmState, FsmStateError},
+    peer::*,
+    rib::{MpNextHop, Route, RouteKey},
+    tests::{
+        rib::{as_path_attr, ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+const IBGP_PROPERTIES: PeerProperties<SocketAddr> =
+    PeerProperties::new(MY_AS, MY_AS, MY_BGP_ID, PEER_ADDR, false);
+
+fn med_attr(metric: u32) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MultiExitDiscriminator(MultiExitDiscriminator::new(metric)),
+    )
+    .unwrap()
+}
+
+fn local_pref_attr(value: u32) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::LocalPreference(LocalPreference::new(value)),
+    )
+    .unwrap()
+}
+
+fn as_path_segments(segments: Vec<Vec<u16>>) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(
+            segments
+                .into_iter()
+                .map(|as_numbers| As2PathSegment::new(AsPathSegmentType::AsSequence, as_numbers))
+                .collect(),
+        )),
+    )
+    .unwrap()
+}
+
+fn route() -> Route {
+    Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            med_attr(10),
+            local_pref_attr(200),
+        ],
+        None,
+    )
+}
+
+#[test]
+fn test_export_external() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default();
+    let exported = export_route(&PROPERTIES, &config, &key, &route(), RouteSource::Local);
+    assert_eq!(
+        exported,
+        Some(Route::new(
+            vec![
+                origin_attr(),
+                as_path_attr(vec![MY_AS as u16]),
+                next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+                med_attr(10),
+            ],
+            None,
+        ))
+    );
+
+    // MED learned from another AS is not propagated
+    let config = ExportConfig::default().with_as_prepend(2);
+    let exported = export_route(&PROPERTIES, &config, &key, &route(), RouteSource::External);
+    assert_eq!(
+        exported,
+        Some(Route::new(
+            vec![
+                origin_attr(),
+                as_path_attr(vec![MY_AS as u16, MY_AS as u16, MY_AS as u16]),
+                next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            ],
+            None,
+        ))
+    );
+
+    // Routes learned from internal peers are advertised to external peers
+    let source = RouteSource::Internal {
+        peer_bgp_id: PEER_BGP_ID,
+        from_client: false,
+    };
+    assert!(export_route(&PROPERTIES, &config, &key, &route(), source).is_some());
+}
+
+#[test]
+fn test_export_as_path_segment_full() {
+    let key = ipv4_key("10.0.0.0/24");
+    let full: Vec<u16> = (1..=255).collect();
+    let route = Route::new(
+        vec![origin_attr(), as_path_segments(vec![full.clone()])],
+        None,
+    );
+    let exported = export_route(
+        &PROPERTIES,
+        &ExportConfig::default(),
+        &key,
+        &route,
+        RouteSource::External,
+    );
+    assert_eq!(
+        exported,
+        Some(Route::new(
+            vec![
+                origin_attr(),
+                as_path_segments(vec![vec![MY_AS as u16], full])
+            ],
+            None
+        ))
+    );
+}
+
+#[test]
+fn test_export_internal() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default().with_as_prepend(2);
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            med_attr(10),
+        ],
+        None,
+    );
+    // An empty AS_PATH is added and LOCAL_PREF is set
+    let expected = Route::new(
+        vec![
+            origin_attr(),
+            PathAttribute::from(
+                false,
+                true,
+                false,
+                false,
+                PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![])),
+            )
+            .unwrap(),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            med_attr(10),
+            local_pref_attr(DEFAULT_LOCAL_PREF),
+        ],
+        None,
+    );
+    let exported = export_route(&IBGP_PROPERTIES, &config, &key, &route, RouteSource::Local);
+    assert_eq!(exported, Some(expected.clone()));
+    let exported = export_route(
+        &IBGP_PROPERTIES,
+        &config,
+        &key,
+        &route,
+        RouteSource::External,
+    );
+    assert_eq!(exported, Some(expected));
+
+    // Routes learned from internal peers are only reflected from or to clients
+    let source = RouteSource::Internal {
+        peer_bgp_id: PEER_BGP_ID,
+        from_client: false,
+    };
+    assert_eq!(
+        export_route(&IBGP_PROPERTIES, &config, &key, &route, source),
+        None
+    );
+}
+
+#[test]
+fn test_export_next_hop_self() {
+    let config = ExportConfig::default()
+        .with_next_hop_self_ipv4(Some(Ipv4Addr::new(192, 0, 2, 100)))
+        .with_next_hop_self_ipv6(Some(Ipv6Addr::from_str("2001:db8::100").unwrap()));
+    let key = ipv4_key("10.0.0.0/24");
+    let exported = export_route(
+        &IBGP_PROPERTIES,
+        &config,
+        &key,
+        &route(),
+        RouteSource::Local,
+    )
+    .unwrap();
+    assert!(exported
+        .attributes()
+        .contains(&next_hop_attr(Ipv4Addr::new(192, 0, 2, 100))));
+    assert_eq!(exported.mp_next_hop(), None);
+
+    let key = RouteKey::new(
+        AddressType::Ipv6Unicast,
+        None,
+        IpNet::from_str("2001:db8:1::/48").unwrap(),
+    );
+    let route = Route::new(
+        vec![origin_attr()],
+        Some(MpNextHop::new(
+            IpAddr::from_str("2001:db8::1").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::1").unwrap()),
+        )),
+    );
+    let exported = export_route(&IBGP_PROPERTIES, &config, &key, &route, RouteSource::Local);
+    assert_eq!(
+        exported.unwrap().mp_next_hop(),
+        Some(MpNextHop::new(
+            IpAddr::from_str("2001:db8::100").unwrap(),
+            None
+        ))
+    );
+
+    // Next hop is kept when next-hop-self is not configured for the family
+    let config = ExportConfig::default();
+    let exported = export_route(&IBGP_PROPERTIES, &config, &key, &route, RouteSource::Local);
+    assert_eq!(exported.unwrap().mp_next_hop(), route.mp_next_hop());
+}
+
+fn exported_update(prefixes: &[&str]) -> BgpUpdateMessage {
+    BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![MY_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            med_attr(10),
+        ],
+        prefixes.iter().map(|prefix| ipv4_unicast(prefix)).collect(),
+    )
+}
+
+fn established_io_builder() -> BgpIoMockBuilder {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    io_builder
+}
+
+#[test_log::test(tokio::test)]
+async fn test_peer_export_routes() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut io_builder = established_io_builder();
+    io_builder
+        // Routes exported before the session is established are packed together
+        .write(BgpMessage::Update(exported_update(&[
+            "10.0.0.0/24",
+            "10.0.1.0/24",
+        ])))
+        .write(BgpMessage::Update(BgpUpdateMessage::new(
+            vec![ipv4_unicast("10.0.0.0/24")],
+            vec![],
+            vec![],
+        )));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    let routes = vec![
+        ExportRoute::local(ipv4_key("10.0.0.0/24"), route()),
+        ExportRoute::local(ipv4_key("10.0.1.0/24"), route()),
+    ];
+    peer.export_routes(routes.clone(), vec![]).await?;
+    assert!(peer.adj_rib_out().is_empty());
+
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    assert_eq!(peer.adj_rib_out().len(AddressType::Ipv4Unicast), 2);
+
+    // Announcing the same routes again doesn't send any updates
+    peer.export_routes(routes, vec![]).await?;
+    peer.export_routes(vec![], vec![ipv4_key("10.0.0.0/24")])
+        .await?;
+    assert_eq!(peer.adj_rib_out().len(AddressType::Ipv4Unicast), 1);
+    assert!(peer.adj_rib_out().get(&ipv4_key("10.0.0.0/24")).is_none());
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_export_routes_mrai() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut io_builder = established_io_builder();
+    io_builder
+        .write(BgpMessage::Update(exported_update(&["10.0.0.0/24"])))
+        // Withdrawals are not delayed by the MRAI
+        .write(BgpMessage::Update(BgpUpdateMessage::new(
+            vec![ipv4_unicast("10.0.0.0/24")],
+            vec![],
+            vec![],
+        )))
+        .write(BgpMessage::Update(exported_update(&[
+            "10.0.1.0/24",
+            "10.0.2.0/24",
+        ])));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let config = PeerConfigBuilder::new()
+        .export(ExportConfig::default().with_mrai(5))
+        .build();
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    peer.export_routes(
+        vec![ExportRoute::local(ipv4_key("10.0.0.0/24"), route())],
+        vec![],
+    )
+    .await?;
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    assert_eq!(peer.adj_rib_out().len(AddressType::Ipv4Unicast), 1);
+
+    peer.export_routes(
+        vec![
+            ExportRoute::local(ipv4_key("10.0.1.0/24"), route()),
+            ExportRoute::local(ipv4_key("10.0.2.0/24"), route()),
+        ],
+        vec![ipv4_key("10.0.0.0/24")],
+    )
+    .await?;
+    assert!(peer.mrai_timer().is_some());
+    assert!(peer.adj_rib_out().is_empty());
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::MinRouteAdvertisementIntervalTimerExpires);
+    assert!(peer.mrai_timer().is_none());
+    assert_eq!(peer.adj_rib_out().len(AddressType::Ipv4Unicast), 2);
+    Ok(())
+}
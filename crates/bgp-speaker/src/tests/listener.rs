@@ -0,0 +1,328 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    connection::TcpActiveConnect,
+    dynamic::{DynamicPeerRange, DynamicPeerTemplate},
+    listener::{BgpListener, SessionKey},
+    peer::{EchoCapabilitiesPolicy, PeerConfig, PeerConfigBuilder, PeerProperties},
+    socket::{TcpSocketConfig, GTSM_TTL},
+    supervisor::{PeersSupervisor, PeersSupervisorError},
+    tests::{HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS, PROPERTIES},
+};
+use netgauze_bgp_pkt::codec::BgpCodec;
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpSocket, TcpStream},
+};
+
+#[test]
+fn test_dynamic_peer_ranges() {
+    let inside = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let outside = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+
+    let listener: BgpListener<SocketAddr, TcpStream> = BgpListener::new(vec![], false);
+    assert!(!listener.is_dynamic_peer_allowed(&inside));
+
+    let mut listener: BgpListener<SocketAddr, TcpStream> = BgpListener::new(vec![], true);
+    assert!(listener.is_dynamic_peer_allowed(&inside));
+    assert!(listener.is_dynamic_peer_allowed(&outside));
+
+    listener.set_dynamic_peer_ranges(vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "2001:db8::/32".parse().unwrap(),
+    ]);
+    assert!(listener.is_dynamic_peer_allowed(&inside));
+    assert!(!listener.is_dynamic_peer_allowed(&outside));
+    assert!(listener
+        .is_dynamic_peer_allowed(&IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))));
+}
+
+#[test]
+fn test_session_key_string() {
+    let peer_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let local_ip = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+    let keys = [
+        (SessionKey::new(peer_ip), "192.0.2.1"),
+        (
+            SessionKey::new(peer_ip).with_local_ip(Some(local_ip)),
+            "192.0.2.1@2001:db8::1",
+        ),
+        (
+            SessionKey::new(peer_ip).with_instance(10),
+            "vrf10:192.0.2.1",
+        ),
+        (
+            SessionKey::new(local_ip)
+                .with_local_ip(Some(peer_ip))
+                .with_instance(1),
+            "vrf1:2001:db8::1@192.0.2.1",
+        ),
+    ];
+    for (key, value) in keys {
+        assert_eq!(key.to_string(), value);
+        assert_eq!(value.parse::<SessionKey>(), Ok(key));
+    }
+    for invalid in ["", "vrf:192.0.2.1", "vrfx:192.0.2.1", "192.0.2.1@", "peer"] {
+        assert!(invalid.parse::<SessionKey>().is_err(), "{invalid}");
+    }
+}
+
+const fn policy() -> EchoCapabilitiesPolicy<SocketAddr, TcpStream, BgpCodec> {
+    EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, Vec::new(), Vec::new())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_session_key_lookup() -> Result<(), PeersSupervisorError> {
+    let local_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+    let other_local_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 10));
+    let any_local = SessionKey::new(PEER_ADDR.ip());
+    let exact_local = any_local.with_local_ip(Some(local_ip));
+    let other_instance = exact_local.with_instance(1);
+
+    let mut supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut listener: BgpListener<SocketAddr, TcpStream> = BgpListener::new(vec![], false);
+    assert_eq!(listener.session_key(local_ip, PEER_ADDR.ip()), None);
+
+    // Multiple sessions to the same peer address are allowed
+    for key in [exact_local, other_instance] {
+        let (_rx, handle) = supervisor.create_peer(
+            key,
+            PROPERTIES,
+            PeerConfig::default(),
+            TcpActiveConnect::new(),
+            policy(),
+        )?;
+        listener.reg_peer(key, handle);
+    }
+    assert_eq!(
+        listener.session_key(local_ip, PEER_ADDR.ip()),
+        Some(exact_local)
+    );
+    assert_eq!(listener.session_key(other_local_ip, PEER_ADDR.ip()), None);
+
+    let (_rx, handle) = supervisor.create_peer(
+        any_local,
+        PROPERTIES,
+        PeerConfig::default(),
+        TcpActiveConnect::new(),
+        policy(),
+    )?;
+    listener.reg_peer(any_local, handle);
+    assert_eq!(
+        listener.session_key(local_ip, PEER_ADDR.ip()),
+        Some(exact_local)
+    );
+    assert_eq!(
+        listener.session_key(other_local_ip, PEER_ADDR.ip()),
+        Some(any_local)
+    );
+
+    // IPv4 connections accepted on dual-stack sockets are seen as IPv4-mapped
+    let IpAddr::V4(peer_ip) = PEER_ADDR.ip() else {
+        unreachable!()
+    };
+    let IpAddr::V4(mapped_local_ip) = local_ip else {
+        unreachable!()
+    };
+    assert_eq!(
+        listener.session_key(
+            IpAddr::V6(mapped_local_ip.to_ipv6_mapped()),
+            IpAddr::V6(peer_ip.to_ipv6_mapped())
+        ),
+        Some(exact_local)
+    );
+
+    listener.set_instance(1);
+    assert_eq!(
+        listener.session_key(local_ip, PEER_ADDR.ip()),
+        Some(other_instance)
+    );
+    assert_eq!(listener.session_key(other_local_ip, PEER_ADDR.ip()), None);
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_dynamic_peer_ranges() -> Result<(), PeersSupervisorError> {
+    let template = DynamicPeerTemplate::new(PeerConfig::default());
+    let first = DynamicPeerRange::new("10.0.0.0/8".parse().unwrap(), template.clone());
+    let second = DynamicPeerRange::new("172.16.0.0/12".parse().unwrap(), template.clone());
+    let first_addr: SocketAddr = "10.0.0.1:179".parse().unwrap();
+    let second_addr: SocketAddr = "172.16.0.1:179".parse().unwrap();
+    let first_key = SessionKey::new(first_addr.ip());
+    let second_key = SessionKey::new(second_addr.ip());
+
+    let mut supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut listener: BgpListener<SocketAddr, TcpStream> = BgpListener::new(vec![], true);
+    listener.update_dynamic_peer_ranges(vec![first.clone(), second.clone()], &mut supervisor);
+    for (peer_key, peer_addr, range) in [
+        (first_key, first_addr, &first),
+        (second_key, second_addr, &second),
+    ] {
+        supervisor.dynamic_peer_from_range::<BgpCodec, _>(
+            peer_key,
+            peer_addr,
+            TcpActiveConnect::new(),
+            range,
+        )?;
+        listener.insert_dynamic_peer(peer_key, range.prefix());
+    }
+
+    // Peers of unchanged ranges are kept, the others are removed
+    listener.update_dynamic_peer_ranges(
+        vec![first.clone(), second.with_max_peers(Some(10))],
+        &mut supervisor,
+    );
+    assert_eq!(supervisor.peer_keys(), vec![first_key]);
+    assert_eq!(listener.dynamic_peer_count(&first.prefix()), 1);
+
+    listener.set_allow_dynamic_peers(false);
+    listener.update_dynamic_peer_ranges(vec![first.clone()], &mut supervisor);
+    assert!(supervisor.peer_keys().is_empty());
+    assert_eq!(listener.dynamic_peer_count(&first.prefix()), 0);
+    Ok(())
+}
+
+/// Connect to the listener from the given loopback address
+async fn connect_from(local_ip: Ipv4Addr, listen_addr: SocketAddr) -> io::Result<TcpStream> {
+    let socket = TcpSocket::new_v4()?;
+    socket.bind(SocketAddr::new(IpAddr::V4(local_ip), 0))?;
+    socket.connect(listen_addr).await
+}
+
+/// A dynamic peer that never sends its OPEN message doesn't hold up the
+/// connections of the other peers
+#[cfg(target_os = "linux")]
+#[test_log::test(tokio::test)]
+async fn test_silent_dynamic_peer() {
+    let dynamic_ip = Ipv4Addr::new(127, 0, 0, 2);
+    let peer_ip = Ipv4Addr::new(127, 0, 0, 3);
+    let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let peer_key = SessionKey::new(IpAddr::V4(peer_ip));
+    let mut supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut listener: BgpListener<SocketAddr, TcpStream> =
+        BgpListener::new(vec![listen_addr], true);
+    listener.set_dynamic_peer_ranges(vec![IpAddr::V4(dynamic_ip).into()]);
+    let (_rx, handle) = supervisor
+        .create_peer(
+            peer_key,
+            PeerProperties::new(
+                MY_AS,
+                PEER_AS,
+                MY_BGP_ID,
+                SocketAddr::new(IpAddr::V4(peer_ip), 179),
+                false,
+            ),
+            PeerConfigBuilder::new()
+                .passive_tcp_establishment(true)
+                .build(),
+            TcpActiveConnect::new(),
+            policy(),
+        )
+        .unwrap();
+    handle.start().unwrap();
+    listener.reg_peer(peer_key, handle);
+    let listener = tokio::spawn(async move { listener.run(&mut supervisor).await });
+
+    // Retried until the listener is bound
+    let _silent = loop {
+        match connect_from(dynamic_ip, listen_addr).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    // The configured peer sends its OPEN message once it gets the connection
+    let mut stream = connect_from(peer_ip, listen_addr).await.unwrap();
+    let mut buf = [0u8; 19];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf))
+        .await
+        .expect("the connection of the configured peer is not accepted")
+        .unwrap();
+    assert_eq!(buf[..16], [0xff; 16]);
+    listener.abort();
+}
+
+/// The listening sockets are re-bound to check the TTL of the SYN when a
+/// peer using GTSM is registered after the listener started
+#[cfg(target_os = "linux")]
+#[test_log::test(tokio::test)]
+async fn test_gtsm_peer_registered_after_run() {
+    let peer_ip = Ipv4Addr::new(127, 0, 0, 3);
+    let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let peer_key = SessionKey::new(IpAddr::V4(peer_ip));
+    let mut supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut listener: BgpListener<SocketAddr, TcpStream> =
+        BgpListener::new(vec![listen_addr], false);
+    let (_rx, handle) = supervisor
+        .create_peer(
+            peer_key,
+            PeerProperties::new(
+                MY_AS,
+                PEER_AS,
+                MY_BGP_ID,
+                SocketAddr::new(IpAddr::V4(peer_ip), 179),
+                false,
+            ),
+            PeerConfigBuilder::new()
+                .passive_tcp_establishment(true)
+                .build(),
+            TcpActiveConnect::new(),
+            policy(),
+        )
+        .unwrap();
+    handle.start().unwrap();
+    listener.reg_peer(peer_key, handle);
+    // Bind the sockets without GTSM
+    let _ = tokio::time::timeout(Duration::from_millis(10), listener.run(&mut supervisor)).await;
+
+    listener.reg_peer_socket_config(peer_key, TcpSocketConfig::new().with_ttl_security(1));
+    let listener = tokio::spawn(async move { listener.run(&mut supervisor).await });
+    let client_config = TcpSocketConfig::new().with_ttl(GTSM_TTL);
+    let mut stream = loop {
+        let socket = TcpSocket::new_v4().unwrap();
+        client_config
+            .configure_connect(&socket, listen_addr)
+            .unwrap();
+        socket
+            .bind(SocketAddr::new(IpAddr::V4(peer_ip), 0))
+            .unwrap();
+        match socket.connect(listen_addr).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    let mut buf = [0u8; 19];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf))
+        .await
+        .expect("the connection of the GTSM peer is not accepted")
+        .unwrap();
+    assert_eq!(buf[..16], [0xff; 16]);
+    listener.abort();
+}
@@ -0,0 +1,110 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{open::BgpOpenMessage, BgpMessage};
+
+use tokio::sync::mpsc;
+
+use crate::{
+    events::BgpEvent,
+    fsm::{FsmState, FsmStateError},
+    peer::{Peer, PeerAdminEvents, PeerConfig, PeerConfigBuilder},
+    peer_controller::PeerController,
+    tests::{
+        BgpIoMockBuilder, MockActiveConnect, HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS,
+        PEER_BGP_ID, POLICY, PROPERTIES,
+    },
+};
+
+#[test_log::test(tokio::test)]
+async fn test_peer_state_metrics() -> Result<(), FsmStateError<SocketAddr>> {
+    // Metrics are process wide, use a peer key that is not shared with other tests
+    let peer_key = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 233));
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        peer_key,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+
+    let encoded = netgauze_metrics::registry().encode();
+    assert!(encoded
+        .contains("netgauze_bgp_peer_state{peer=\"192.168.0.233\",state=\"Established\"} 1\n"));
+    assert!(!encoded.contains("netgauze_bgp_peer_state{peer=\"192.168.0.233\",state=\"Idle\"}"));
+    assert!(encoded
+        .contains("netgauze_bgp_peer_established_transitions_total{peer=\"192.168.0.233\"} 1\n"));
+    assert!(
+        encoded.contains("netgauze_bgp_messages_sent_total{peer=\"192.168.0.233\",type=\"Open\"}")
+    );
+    assert!(encoded.contains(
+        "netgauze_bgp_messages_received_total{peer=\"192.168.0.233\",type=\"KeepAlive\"}"
+    ));
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_removed_peer_metrics() {
+    // Metrics are process wide, use a peer key that is not shared with other tests
+    let peer_key = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 234));
+    let config = PeerConfigBuilder::new()
+        .passive_tcp_establishment(true)
+        .build();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder: BgpIoMockBuilder::new(),
+        connect_delay: Duration::from_secs(0),
+    };
+    let controller = PeerController::new(peer_key, PROPERTIES, config, tx, POLICY, active_connect);
+    controller.get_new_handle().start().unwrap();
+    assert_eq!(
+        rx.recv().await,
+        Some(Ok((FsmState::Active, BgpEvent::ManualStartWithPassiveTcp)))
+    );
+    let series = "netgauze_bgp_peer_state{peer=\"192.168.0.234\",state=\"Active\"} 1\n";
+    assert!(netgauze_metrics::registry().encode().contains(series));
+
+    drop(controller);
+    assert!(!netgauze_metrics::registry()
+        .encode()
+        .contains("peer=\"192.168.0.234\""));
+}
@@ -17,15 +17,51 @@ use crate::peer::{EchoCapabilitiesPolicy, PeerProperties};
 use std::net::{IpAddr, Ipv4Addr};
 
 use async_trait::async_trait;
-use std::{io, io::Cursor, net::SocketAddr, time::Duration};
+use futures::FutureExt;
+use std::{
+    io,
+    io::Cursor,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::connection::ActiveConnect;
+use crate::{
+    connection::ActiveConnect,
+    sim::{SimNetwork, SimStream},
+};
 use netgauze_bgp_pkt::{codec::BgpCodec, BgpMessage};
 use netgauze_parse_utils::WritablePdu;
 
+#[cfg(target_os = "linux")]
+mod auth;
+mod bfd;
+#[cfg(feature = "bmp")]
+mod bmp;
+mod confederation;
 mod connection;
+mod damping;
+mod dynamic;
+mod export;
+mod listener;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mrt")]
+mod mrt;
+mod multisession;
+mod nexthop;
 mod peer;
 mod peer_controller;
+mod reflection;
+mod rib;
+mod role;
+#[cfg(feature = "rpki")]
+mod rpki;
+mod sim;
+#[cfg(target_os = "linux")]
+mod socket;
+mod stats;
 mod supervisor;
 
 pub(crate) const MY_AS: u32 = 100;
@@ -44,6 +80,22 @@ pub(crate) const PROPERTIES: PeerProperties<SocketAddr> =
 pub(crate) const POLICY: EchoCapabilitiesPolicy<SocketAddr, tokio_test::io::Mock, BgpCodec> =
     EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, Vec::new(), Vec::new());
 
+pub(crate) const SIM_POLICY: EchoCapabilitiesPolicy<SocketAddr, SimStream, BgpCodec> =
+    EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, Vec::new(), Vec::new());
+
+/// Scenario played by the remote end of a mocked connection
+pub trait MockIoBuilder {
+    type Io: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    fn build(&mut self) -> Self::Io;
+}
+
+fn bgp_message_bytes(msg: BgpMessage) -> Vec<u8> {
+    let mut cursor = Cursor::new(vec![]);
+    msg.write(&mut cursor).unwrap();
+    cursor.into_inner()
+}
+
 /// Wrap [Builder] allowing it to accept BgpMessages for read and write
 /// mocks rather than `&[u8]`.
 #[derive(Default, Debug)]
@@ -101,15 +153,142 @@ impl BgpIoMockBuilder {
     }
 }
 
-pub struct MockActiveConnect {
+impl MockIoBuilder for BgpIoMockBuilder {
+    type Io = tokio_test::io::Mock;
+
+    fn build(&mut self) -> Self::Io {
+        self.io_builder.build()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SimScriptStep {
+    Read(Vec<u8>),
+    Write(Vec<u8>),
+    Wait(Duration),
+}
+
+/// Same scenario as [BgpIoMockBuilder] played over the in-memory transport of
+/// [crate::sim]: the `read` messages are sent to the local peer, and the
+/// `write` messages are expected from it. Each [MockIoBuilder::build] plays
+/// the scenario in a new task on the remote end of a new [SimStream], the
+/// connection is closed at the end of the scenario or at the first unexpected
+/// message. Unexpected messages are reported when the builder is dropped.
+#[derive(Default, Debug)]
+pub struct BgpSimScriptBuilder {
+    steps: Vec<SimScriptStep>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl BgpSimScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `msg` to the local peer
+    pub fn read(&mut self, msg: BgpMessage) -> &mut Self {
+        self.read_u8(&bgp_message_bytes(msg))
+    }
+
+    pub fn read_u8(&mut self, buf: &[u8]) -> &mut Self {
+        self.steps.push(SimScriptStep::Read(buf.to_vec()));
+        self
+    }
+
+    /// Expect `msg` from the local peer
+    pub fn write(&mut self, msg: BgpMessage) -> &mut Self {
+        self.write_u8(&bgp_message_bytes(msg))
+    }
+
+    pub fn write_u8(&mut self, buf: &[u8]) -> &mut Self {
+        self.steps.push(SimScriptStep::Write(buf.to_vec()));
+        self
+    }
+
+    /// Pause the scenario, the time is virtual when the tokio clock is paused
+    pub fn wait(&mut self, duration: Duration) -> &mut Self {
+        self.steps.push(SimScriptStep::Wait(duration));
+        self
+    }
+
+    async fn play(steps: Vec<SimScriptStep>, mut remote: SimStream) -> Result<(), String> {
+        for step in steps {
+            match step {
+                SimScriptStep::Read(buf) => remote
+                    .write_all(&buf)
+                    .await
+                    .map_err(|err| format!("couldn't send {buf:?}: {err}"))?,
+                SimScriptStep::Write(expected) => {
+                    // Compare the bytes as they're received, so a shorter
+                    // unexpected message doesn't block the scenario
+                    let mut buf = vec![0; expected.len()];
+                    let mut received = 0;
+                    while received < expected.len() {
+                        let n = remote
+                            .read(&mut buf[received..])
+                            .await
+                            .map_err(|err| format!("expected write {expected:?}: {err}"))?;
+                        if n == 0 {
+                            return Err(format!("connection closed, expected write {expected:?}"));
+                        }
+                        received += n;
+                        if buf[..received] != expected[..received] {
+                            return Err(format!(
+                                "unexpected write {:?}, expected {expected:?}",
+                                &buf[..received]
+                            ));
+                        }
+                    }
+                }
+                SimScriptStep::Wait(duration) => tokio::time::sleep(duration).await,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MockIoBuilder for BgpSimScriptBuilder {
+    type Io = SimStream;
+
+    fn build(&mut self) -> Self::Io {
+        let network = SimNetwork::new();
+        let mut listener = network.bind(PEER_ADDR).unwrap();
+        let stream = network.connect(IpAddr::V4(MY_BGP_ID), PEER_ADDR).unwrap();
+        let (remote, _) = listener
+            .accept()
+            .now_or_never()
+            .expect("the connection is queued to the listener")
+            .unwrap();
+        let steps = self.steps.clone();
+        let errors = self.errors.clone();
+        tokio::spawn(async move {
+            if let Err(err) = Self::play(steps, remote).await {
+                errors.lock().unwrap().push(err);
+            }
+        });
+        stream
+    }
+}
+
+impl Drop for BgpSimScriptBuilder {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let errors = self.errors.lock().unwrap();
+        assert!(errors.is_empty(), "scenario failed: {errors:?}");
+    }
+}
+
+pub struct MockActiveConnect<B = BgpIoMockBuilder> {
     pub peer_addr: SocketAddr,
-    pub io_builder: BgpIoMockBuilder,
+    pub io_builder: B,
     pub connect_delay: Duration,
 }
 
 #[async_trait]
-impl ActiveConnect<SocketAddr, tokio_test::io::Mock, BgpCodec> for MockActiveConnect {
-    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<tokio_test::io::Mock> {
+impl<B: MockIoBuilder + Send> ActiveConnect<SocketAddr, B::Io, BgpCodec> for MockActiveConnect<B> {
+    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<B::Io> {
         assert_eq!(self.peer_addr, peer_addr);
         if !self.connect_delay.is_zero() {
             tokio::time::sleep(self.connect_delay).await;
@@ -125,8 +304,10 @@ pub struct MockFailedActiveConnect {
 }
 
 #[async_trait]
-impl ActiveConnect<SocketAddr, tokio_test::io::Mock, BgpCodec> for MockFailedActiveConnect {
-    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<tokio_test::io::Mock> {
+impl<I: AsyncRead + AsyncWrite + Send + 'static> ActiveConnect<SocketAddr, I, BgpCodec>
+    for MockFailedActiveConnect
+{
+    async fn connect(&mut self, peer_addr: SocketAddr) -> io::Result<I> {
         assert_eq!(self.peer_addr, peer_addr);
         if !self.connect_delay.is_zero() {
             tokio::time::sleep(self.connect_delay).await;
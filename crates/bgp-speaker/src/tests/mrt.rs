@@ -0,0 +1,322 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{TimeZone, Utc};
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, FourOctetAsCapability},
+    notification::{BgpNotificationMessage, CeaseError},
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::{As4PathSegment, AsPath, AsPathSegmentType, PathAttributeValue},
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_mrt_pkt::{
+    iana::Bgp4mpState, reader::MrtReader, Bgp4mp, Bgp4mpMessage, Bgp4mpPeer, MrtRecord, MrtValue,
+    TableDumpV2,
+};
+
+use crate::{
+    fsm::{FsmState, FsmStateError},
+    mrt::*,
+    peer::*,
+    tests::{
+        rib::{as_path_attr, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+fn config() -> MrtRecorderConfig {
+    MrtRecorderConfig::new(PathBuf::new(), MY_BGP_ID)
+}
+
+fn sent_open() -> BgpOpenMessage {
+    BgpOpenMessage::new(MY_AS as u16, HOLD_TIME, MY_BGP_ID, vec![])
+}
+
+fn received_open() -> BgpOpenMessage {
+    BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![])
+}
+
+fn update(prefix: &str) -> BgpUpdateMessage {
+    BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+        ],
+        vec![ipv4_unicast(prefix)],
+    )
+}
+
+fn message(message: BgpMessage, asn4: bool) -> MrtPeerEvent<SocketAddr> {
+    MrtPeerEvent::Message {
+        timestamp: Utc.timestamp_opt(1704067200, 0).unwrap(),
+        peer_addr: PEER_ADDR,
+        peer_asn: PEER_AS,
+        local_asn: MY_AS,
+        asn4,
+        message,
+    }
+}
+
+fn state_change(old_state: FsmState, new_state: FsmState) -> MrtPeerEvent<SocketAddr> {
+    MrtPeerEvent::StateChange {
+        timestamp: Utc.timestamp_opt(1704067200, 0).unwrap(),
+        peer_addr: PEER_ADDR,
+        peer_asn: PEER_AS,
+        local_asn: MY_AS,
+        old_state,
+        new_state,
+    }
+}
+
+#[test]
+fn test_mrt_state() {
+    let mut state = MrtState::new(config());
+
+    let record = state.apply(state_change(FsmState::Connect, FsmState::OpenSent));
+    match record.value() {
+        MrtValue::Bgp4mpEt(Bgp4mp::StateChangeAs4(change)) => {
+            assert_eq!(change.peer().peer_address(), PEER_ADDR.ip());
+            assert_eq!(change.peer().peer_asn(), PEER_AS);
+            assert_eq!(change.peer().local_asn(), MY_AS);
+            assert_eq!(change.old_state(), Bgp4mpState::Connect);
+            assert_eq!(change.new_state(), Bgp4mpState::OpenSent);
+        }
+        value => panic!("unexpected record {value:?}"),
+    }
+
+    let record = state.apply(message(BgpMessage::Open(received_open()), false));
+    assert!(matches!(
+        record.value(),
+        MrtValue::Bgp4mpEt(Bgp4mp::Message(_))
+    ));
+    let record = state.apply(message(BgpMessage::Update(update("10.0.0.0/24")), true));
+    match record.value() {
+        MrtValue::Bgp4mpEt(Bgp4mp::MessageAs4(msg)) => {
+            assert_eq!(msg.message(), &BgpMessage::Update(update("10.0.0.0/24")));
+        }
+        value => panic!("unexpected record {value:?}"),
+    }
+    state.apply(message(BgpMessage::Update(update("10.0.1.0/24")), true));
+
+    let timestamp = Utc.timestamp_opt(1704070800, 0).unwrap();
+    let records = state.rib_dump(timestamp);
+    assert_eq!(records.len(), 3);
+    match records[0].value() {
+        MrtValue::TableDumpV2(TableDumpV2::PeerIndexTable(table)) => {
+            assert_eq!(table.collector_bgp_id(), MY_BGP_ID);
+            assert_eq!(table.peers().len(), 1);
+            assert_eq!(table.peers()[0].bgp_id(), PEER_BGP_ID);
+            assert_eq!(table.peers()[0].address(), PEER_ADDR.ip());
+            assert_eq!(table.peers()[0].asn(), PEER_AS);
+        }
+        value => panic!("unexpected record {value:?}"),
+    }
+    for (index, prefix) in ["10.0.0.0/24", "10.0.1.0/24"].iter().enumerate() {
+        match records[index + 1].value() {
+            MrtValue::TableDumpV2(TableDumpV2::RibIpv4Unicast(rib)) => {
+                assert_eq!(rib.sequence_number(), index as u32);
+                assert_eq!(rib.prefix(), prefix.parse().unwrap());
+                assert_eq!(rib.entries().len(), 1);
+                let entry = &rib.entries()[0];
+                assert_eq!(entry.peer_index(), 0);
+                assert_eq!(
+                    entry.originated_time(),
+                    &Utc.timestamp_opt(1704067200, 0).unwrap()
+                );
+                // AS numbers are always encoded as four-octet in RIB entries
+                assert!(entry.path_attributes().iter().any(|attr| attr.value()
+                    == &PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![
+                        As4PathSegment::new(AsPathSegmentType::AsSequence, vec![PEER_AS])
+                    ]))));
+            }
+            value => panic!("unexpected record {value:?}"),
+        }
+    }
+
+    // The Adj-RIB-In is cleared when the session goes down
+    state.apply(state_change(FsmState::Established, FsmState::Idle));
+    let records = state.rib_dump(timestamp);
+    assert_eq!(records.len(), 1);
+    match records[0].value() {
+        MrtValue::TableDumpV2(TableDumpV2::PeerIndexTable(table)) => {
+            assert!(table.peers().is_empty());
+        }
+        value => panic!("unexpected record {value:?}"),
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_peer_mrt_events() -> Result<(), FsmStateError<SocketAddr>> {
+    let cease =
+        BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(sent_open()))
+        .read(BgpMessage::Open(received_open()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update("10.0.0.0/24")))
+        .read(BgpMessage::Notification(cease.clone()));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    let (recorder, mut rx) = MrtRecorder::channel();
+    peer.set_mrt_recorder(Some(recorder));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..6 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Idle);
+
+    let mut messages = vec![];
+    let mut transitions = vec![];
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            MrtPeerEvent::Message {
+                peer_addr,
+                peer_asn,
+                local_asn,
+                asn4,
+                message,
+                ..
+            } => {
+                assert_eq!(peer_addr, PEER_ADDR);
+                assert_eq!(peer_asn, PEER_AS);
+                assert_eq!(local_asn, MY_AS);
+                assert!(!asn4);
+                messages.push(message);
+            }
+            MrtPeerEvent::StateChange {
+                old_state,
+                new_state,
+                ..
+            } => transitions.push((old_state, new_state)),
+        }
+    }
+    assert_eq!(
+        messages,
+        vec![
+            BgpMessage::Open(received_open()),
+            BgpMessage::KeepAlive,
+            BgpMessage::Update(update("10.0.0.0/24")),
+            BgpMessage::Notification(cease),
+        ]
+    );
+    assert_eq!(
+        transitions,
+        vec![
+            (FsmState::Idle, FsmState::Connect),
+            (FsmState::Connect, FsmState::OpenSent),
+            (FsmState::OpenSent, FsmState::OpenConfirm),
+            (FsmState::OpenConfirm, FsmState::Established),
+            (FsmState::Established, FsmState::Idle),
+        ]
+    );
+    Ok(())
+}
+
+fn read_records(path: &PathBuf) -> Vec<MrtRecord> {
+    MrtReader::open(path)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_mrt_recorder_files() {
+    let directory = std::env::temp_dir().join(format!("netgauze-mrt-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let config = MrtRecorderConfig::new(directory.clone(), MY_BGP_ID)
+        .with_rotate_interval(Duration::ZERO)
+        .with_rotate_size(1)
+        .with_rib_dump_interval(Duration::from_millis(200));
+    let recorder = MrtRecorder::<SocketAddr>::start(config);
+    let open = BgpOpenMessage::new(
+        PEER_AS as u16,
+        HOLD_TIME,
+        PEER_BGP_ID,
+        vec![BgpOpenMessageParameter::Capabilities(vec![
+            BgpCapability::FourOctetAs(FourOctetAsCapability::new(PEER_AS)),
+        ])],
+    );
+    recorder.send(message(BgpMessage::Open(open), false));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    recorder.send(message(BgpMessage::Update(update("10.0.0.0/24")), false));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    drop(recorder);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut paths = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    let names = paths
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names.len(), 3, "unexpected files {names:?}");
+    assert!(names[0].starts_with(RIB_FILE_PREFIX));
+    assert!(names[1].starts_with(UPDATES_FILE_PREFIX));
+    assert!(names[2].starts_with(UPDATES_FILE_PREFIX));
+
+    // The files are rotated after every record
+    let mut update_records = vec![];
+    for path in &paths[1..] {
+        let records = read_records(path);
+        assert_eq!(records.len(), 1);
+        update_records.extend(records);
+    }
+    assert!(update_records.contains(&MrtRecord::new(
+        Utc.timestamp_opt(1704067200, 0).unwrap(),
+        MrtValue::Bgp4mpEt(Bgp4mp::Message(Bgp4mpMessage::new(
+            Bgp4mpPeer::new(
+                PEER_AS,
+                MY_AS,
+                0,
+                PEER_ADDR.ip(),
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            ),
+            BgpMessage::Update(update("10.0.0.0/24")),
+        )))
+    )));
+
+    let rib_records = read_records(&paths[0]);
+    assert_eq!(rib_records.len(), 2);
+    assert!(matches!(
+        rib_records[1].value(),
+        MrtValue::TableDumpV2(TableDumpV2::RibIpv4Unicast(_))
+    ));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
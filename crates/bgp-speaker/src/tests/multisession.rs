@@ -0,0 +1,111 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, MultisessionCapability},
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    BgpMessage,
+};
+
+use crate::{
+    fsm::{FsmState, FsmStateError},
+    multisession::*,
+    peer::*,
+    tests::*,
+};
+
+fn multisession_cap() -> BgpCapability {
+    BgpCapability::Multisession(MultisessionCapability::new(false, vec![]))
+}
+
+fn multisession_open(my_as: u16, bgp_id: Ipv4Addr, multisession: bool) -> BgpOpenMessage {
+    let params = if multisession {
+        vec![BgpOpenMessageParameter::Capabilities(vec![
+            multisession_cap(),
+        ])]
+    } else {
+        vec![]
+    };
+    BgpOpenMessage::new(my_as, HOLD_TIME, bgp_id, params)
+}
+
+#[test]
+fn test_multisession_negotiated() {
+    let cap = multisession_cap();
+    let caps = vec![BgpCapability::RouteRefresh, cap];
+    assert!(multisession_negotiated(&caps, &caps[1..]));
+    assert!(!multisession_negotiated(&caps[1..], &[]));
+    assert!(!multisession_negotiated(&[], &caps));
+}
+
+async fn test_multisession_open(peer_multisession: bool) -> Result<(), FsmStateError<SocketAddr>> {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(multisession_open(
+            MY_AS as u16,
+            MY_BGP_ID,
+            true,
+        )))
+        .read(BgpMessage::Open(multisession_open(
+            PEER_AS as u16,
+            PEER_BGP_ID,
+            peer_multisession,
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES.with_multisession(true),
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    assert_eq!(
+        peer.main_connection_sent_capabilities(),
+        Some(vec![multisession_cap()])
+    );
+    assert_eq!(
+        peer.connection()
+            .map(|connection| connection.multisession_negotiated()),
+        Some(peer_multisession)
+    );
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_multisession_open_negotiated() -> Result<(), FsmStateError<SocketAddr>> {
+    test_multisession_open(true).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_multisession_open_single_session() -> Result<(), FsmStateError<SocketAddr>> {
+    // Peers without Multisession support are still accepted
+    test_multisession_open(false).await
+}
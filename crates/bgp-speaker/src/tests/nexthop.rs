@@ -0,0 +1,359 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+
+use netgauze_bgp_pkt::{
+    nlri::{LabeledIpv4NextHop, LabeledIpv6NextHop, LabeledNextHop, RouteDistinguisher},
+    open::BgpOpenMessage,
+    path_attribute::MpReach,
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    events::{BgpEvent, UpdateTreatment},
+    fsm::{FsmState, FsmStateError},
+    nexthop::*,
+    peer::{Peer, PeerAdminEvents, PeerConfig},
+    rib::{MpNextHop, Route, RouteKey},
+    tests::{
+        rib::{as_path_attr, ipv4_unicast, next_hop_attr, origin_attr},
+        BgpIoMockBuilder, MockActiveConnect, HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS,
+        PEER_BGP_ID, PEER_KEY, POLICY, PROPERTIES,
+    },
+};
+
+const LINK_LOCAL: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+
+fn net(prefix: &str) -> IpNet {
+    IpNet::from_str(prefix).unwrap()
+}
+
+fn reachable(metric: u32) -> NextHopStatus {
+    NextHopStatus::Reachable { metric }
+}
+
+#[test]
+fn test_static_next_hop_resolver() {
+    let resolver = StaticNextHopResolver::new()
+        .with_route(net("10.0.0.0/8"), 20)
+        .with_route(net("10.1.2.3/16"), 10)
+        .with_route(net("2001:db8::/32"), 5);
+    assert_eq!(
+        resolver.routes(),
+        vec![
+            (net("10.0.0.0/8"), 20),
+            (net("10.1.0.0/16"), 10),
+            (net("2001:db8::/32"), 5),
+        ]
+    );
+    let next_hop = IpAddr::V4(Ipv4Addr::new(10, 1, 0, 1));
+    assert_eq!(resolver.resolve(next_hop), reachable(10));
+    assert_eq!(
+        resolver.resolve(IpAddr::V4(Ipv4Addr::new(10, 2, 0, 1))),
+        reachable(20)
+    );
+    assert_eq!(
+        resolver.resolve(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))),
+        reachable(5)
+    );
+    assert_eq!(
+        resolver.resolve(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+        NextHopStatus::Unreachable
+    );
+
+    let mut rx = resolver.subscribe(next_hop);
+    assert_eq!(*rx.borrow_and_update(), reachable(10));
+    // Changes that don't affect the resolution aren't notified
+    assert_eq!(resolver.set_route(net("10.0.0.0/8"), 30), Some(20));
+    assert!(!rx.has_changed().unwrap());
+
+    assert_eq!(resolver.remove_route(&net("10.1.0.0/16")), Some(10));
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), reachable(30));
+    assert_eq!(resolver.remove_route(&net("10.0.0.0/8")), Some(30));
+    assert_eq!(*rx.borrow_and_update(), NextHopStatus::Unreachable);
+    assert_eq!(resolver.remove_route(&net("10.0.0.0/8")), None);
+}
+
+#[test]
+fn test_mp_reach_next_hop() {
+    let rd = RouteDistinguisher::As2Administrator { asn2: 0, number: 0 };
+    let global = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let mp_reach = MpReach::Ipv6Unicast {
+        next_hop_global: global,
+        next_hop_local: Some(LINK_LOCAL),
+        nlri: vec![],
+    };
+    let next_hop = mp_reach_next_hop(&mp_reach).unwrap();
+    assert_eq!(
+        next_hop,
+        MpNextHop::new(IpAddr::V6(global), Some(LINK_LOCAL))
+    );
+    assert_eq!(resolvable_address(&next_hop), IpAddr::V6(global));
+
+    let mp_reach = MpReach::Ipv4MplsVpnUnicast {
+        next_hop: LabeledNextHop::Ipv4(LabeledIpv4NextHop::new(rd, Ipv4Addr::new(10, 0, 0, 1))),
+        nlri: vec![],
+    };
+    assert_eq!(
+        mp_reach_next_hop(&mp_reach),
+        Some(MpNextHop::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), None))
+    );
+
+    let mp_reach = MpReach::Ipv6MplsVpnUnicast {
+        next_hop: LabeledNextHop::Ipv6(LabeledIpv6NextHop::new(
+            rd,
+            Ipv6Addr::UNSPECIFIED,
+            Some(LINK_LOCAL),
+        )),
+        nlri: vec![],
+    };
+    let next_hop = mp_reach_next_hop(&mp_reach).unwrap();
+    assert_eq!(
+        next_hop,
+        MpNextHop::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), Some(LINK_LOCAL))
+    );
+    // Only the link-local next hop is usable
+    assert_eq!(resolvable_address(&next_hop), IpAddr::V6(LINK_LOCAL));
+
+    // IPv4-mapped IPv6 next hop
+    let mapped = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+    let next_hop = MpNextHop::new(IpAddr::V6(mapped), None);
+    assert_eq!(
+        resolvable_address(&next_hop),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))
+    );
+
+    let route = Route::new(
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(10, 0, 0, 2))],
+        None,
+    );
+    assert_eq!(
+        route_next_hop(&route),
+        Some(MpNextHop::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), None))
+    );
+    assert_eq!(route_next_hop(&Route::new(vec![origin_attr()], None)), None);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_next_hop_tracker() {
+    let resolver = Arc::new(StaticNextHopResolver::new().with_route(net("10.0.0.0/24"), 10));
+    let mut tracker = NextHopTracker::new(resolver.clone());
+    let route = |next_hop| Route::new(vec![origin_attr(), next_hop_attr(next_hop)], None);
+    let next_hop_1 = Ipv4Addr::new(10, 0, 0, 1);
+    let next_hop_2 = Ipv4Addr::new(10, 0, 1, 1);
+
+    assert_eq!(tracker.track(1, &route(next_hop_1)), Some(reachable(10)));
+    assert_eq!(tracker.track(2, &route(next_hop_1)), Some(reachable(10)));
+    assert_eq!(
+        tracker.track(3, &Route::new(vec![origin_attr()], None)),
+        None
+    );
+    assert_eq!(tracker.status(&3), None);
+    assert_eq!(tracker.next_hops().len(), 1);
+
+    // A next hop tracked after the future is created is covered by it
+    let changed = tracker.changed();
+    assert_eq!(
+        tracker.track(3, &route(next_hop_2)),
+        Some(NextHopStatus::Unreachable)
+    );
+    tokio::time::timeout(Duration::from_secs(1), changed)
+        .await
+        .unwrap();
+    assert!(tracker.poll_changes().is_empty());
+
+    let changed = tracker.changed();
+    resolver.set_route(net("10.0.0.0/16"), 20);
+    tokio::time::timeout(Duration::from_secs(1), changed)
+        .await
+        .unwrap();
+    let changes = tracker.poll_changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].next_hop(), IpAddr::V4(next_hop_2));
+    assert_eq!(changes[0].status(), reachable(20));
+    assert_eq!(changes[0].keys(), &vec![3]);
+    assert_eq!(tracker.status(&3), Some(reachable(20)));
+
+    let changed = tracker.changed();
+    resolver.remove_route(&net("10.0.0.0/24"));
+    tokio::time::timeout(Duration::from_secs(1), changed)
+        .await
+        .unwrap();
+    let mut changes = tracker.poll_changes();
+    assert_eq!(changes.len(), 1);
+    let change = changes.pop().unwrap();
+    assert_eq!(change.next_hop(), IpAddr::V4(next_hop_1));
+    assert_eq!(change.status(), reachable(20));
+    let mut keys = change.keys().clone();
+    keys.sort();
+    assert_eq!(keys, vec![1, 2]);
+
+    // Moving a route to another next hop and dropping the last route of a next
+    // hop stop tracking it
+    assert_eq!(tracker.track(1, &route(next_hop_2)), Some(reachable(20)));
+    tracker.untrack(&2);
+    assert_eq!(
+        tracker.next_hops(),
+        vec![(IpAddr::V4(next_hop_2), reachable(20))]
+    );
+    tracker.untrack(&1);
+    tracker.untrack(&3);
+    assert!(tracker.next_hops().is_empty());
+    assert!(
+        tokio::time::timeout(Duration::from_millis(10), tracker.changed())
+            .await
+            .is_err()
+    );
+}
+
+const PREFIX: &str = "10.0.0.0/24";
+
+fn key() -> RouteKey {
+    RouteKey::new(AddressType::Ipv4Unicast, None, net(PREFIX))
+}
+
+fn announce_update() -> BgpUpdateMessage {
+    BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 168, 0, 2)),
+        ],
+        vec![ipv4_unicast(PREFIX)],
+    )
+}
+
+fn withdraw_update() -> BgpUpdateMessage {
+    BgpUpdateMessage::new(vec![ipv4_unicast(PREFIX)], vec![], vec![])
+}
+
+/// Peer receiving a single route via 192.168.0.2, the session stays up long
+/// enough to change the resolution of the next hop
+fn next_hop_connect() -> MockActiveConnect {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(announce_update()))
+        .wait(Duration::from_secs(30));
+    MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    }
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_next_hop_unreachable() -> Result<(), FsmStateError<SocketAddr>> {
+    let resolver = Arc::new(StaticNextHopResolver::new().with_route(net("192.168.0.0/24"), 10));
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        next_hop_connect(),
+    );
+    peer.set_next_hop_resolver(Some(resolver.clone()));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(announce_update(), UpdateTreatment::Normal)
+    );
+    assert_eq!(peer.next_hop_status(&key()), Some(reachable(10)));
+
+    // The route is withdrawn when its next hop becomes unreachable, but kept in
+    // the Adj-RIB-In
+    resolver.remove_route(&net("192.168.0.0/24"));
+    assert_eq!(peer.run().await?, BgpEvent::NextHopResolutionChanged);
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::NextHopRoutesChanged(withdraw_update())
+    );
+    assert_eq!(
+        peer.next_hop_status(&key()),
+        Some(NextHopStatus::Unreachable)
+    );
+    assert!(peer.adj_rib_in().get(&key()).is_some());
+
+    // And announced again once the next hop is reachable
+    resolver.set_route(net("192.168.0.0/16"), 20);
+    assert_eq!(peer.run().await?, BgpEvent::NextHopResolutionChanged);
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::NextHopRoutesChanged(announce_update())
+    );
+    assert_eq!(peer.next_hop_status(&key()), Some(reachable(20)));
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_next_hop_unreachable_on_receipt() -> Result<(), FsmStateError<SocketAddr>> {
+    let resolver = Arc::new(StaticNextHopResolver::new());
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        next_hop_connect(),
+    );
+    peer.set_next_hop_resolver(Some(resolver.clone()));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(withdraw_update(), UpdateTreatment::Normal)
+    );
+    assert!(peer.adj_rib_in().get(&key()).is_some());
+
+    // Removing the resolver releases the route
+    peer.set_next_hop_resolver(None);
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::NextHopRoutesChanged(announce_update())
+    );
+    assert_eq!(peer.next_hop_status(&key()), None);
+    Ok(())
+}
@@ -10,18 +10,26 @@ use netgauze_bgp_pkt::{
 };
 use netgauze_iana::address_family::AddressType;
 
-use crate::{events::*, fsm::*, peer::*, tests::*};
+use crate::{
+    events::*,
+    fsm::*,
+    peer::*,
+    tests::{
+        rib::{as_path_attr, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_idle_manual_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
         connect_delay: Duration::from_secs(0),
     };
     let config = PeerConfigBuilder::new().build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -34,9 +42,9 @@ async fn test_idle_manual_start() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_idle_manual_start_with_passive_tcp() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -51,7 +59,7 @@ async fn test_idle_manual_start_with_passive_tcp() {
     let config = PeerConfigBuilder::new()
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -64,16 +72,16 @@ async fn test_idle_manual_start_with_passive_tcp() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_idle_automatic_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
         connect_delay: Duration::from_secs(0),
     };
     let config = PeerConfigBuilder::new().build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::AutomaticStart);
@@ -87,9 +95,9 @@ async fn test_idle_automatic_start() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_idle_automatic_start_with_passive() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -104,7 +112,7 @@ async fn test_idle_automatic_start_with_passive() {
     let config = PeerConfigBuilder::new()
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
 
     assert_eq!(peer.fsm_state(), FsmState::Idle);
     peer.add_admin_event(PeerAdminEvents::AutomaticStart);
@@ -117,9 +125,9 @@ async fn test_idle_automatic_start_with_passive() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_manual_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
@@ -128,7 +136,7 @@ async fn test_connect_manual_start() {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(1)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -143,9 +151,9 @@ async fn test_connect_manual_start() {
     assert_eq!(peer.fsm_state(), FsmState::Connect);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_automatic_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
@@ -154,7 +162,7 @@ async fn test_connect_automatic_start() {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(1)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::AutomaticStart);
@@ -169,9 +177,9 @@ async fn test_connect_automatic_start() {
     assert_eq!(peer.fsm_state(), FsmState::Connect);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_manual_stop() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Notification(
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] }),
     ));
@@ -184,7 +192,7 @@ async fn test_connect_manual_stop() {
         .open_delay_timer_duration(1)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     // Check start is correct
@@ -218,9 +226,9 @@ async fn test_connect_manual_stop() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_retry_timer_expires() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Notification(
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] }),
     ));
@@ -234,7 +242,7 @@ async fn test_connect_retry_timer_expires() {
         .open_delay_timer_duration(3)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     // Check start is correct
@@ -256,10 +264,10 @@ async fn test_connect_retry_timer_expires() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_delay_open_timer_expires() {
     let delay_open_duration = 1;
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .wait(Duration::from_secs(1))
         .write(BgpMessage::Open(BgpOpenMessage::new(
@@ -276,7 +284,7 @@ async fn test_connect_delay_open_timer_expires() {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(delay_open_duration)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -305,10 +313,10 @@ async fn test_connect_delay_open_timer_expires() {
     );
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_tcp_connection_confirmed() {
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -324,7 +332,7 @@ async fn test_connect_tcp_connection_confirmed() {
         .open_delay_timer_duration(0)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     // Check start is correct
@@ -359,10 +367,10 @@ async fn test_connect_tcp_connection_confirmed() {
     );
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_tcp_connection_confirmed_with_open_delay() {
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder: active_io_builder,
@@ -372,7 +380,7 @@ async fn test_connect_tcp_connection_confirmed_with_open_delay() {
         .open_delay_timer_duration(1)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     // Check start is correct
@@ -402,7 +410,7 @@ async fn test_connect_tcp_connection_confirmed_with_open_delay() {
     assert!(conn.open_delay_timer().is_some())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_tcp_connection_fails() {
     let active_connect = MockFailedActiveConnect {
         peer_addr: PEER_ADDR,
@@ -412,7 +420,7 @@ async fn test_connect_tcp_connection_fails() {
         .open_delay_timer_duration(0)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     // Check start is correct
@@ -434,17 +442,17 @@ async fn test_connect_tcp_connection_fails() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_tcp_connection_fails_with_open_delay_timer() {
     // TODO
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_bgp_open_with_delay() {
     let delay_open_duration = 1;
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
 
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .wait(Duration::from_millis(10))
         .read(BgpMessage::Open(peer_open.clone()))
@@ -463,7 +471,7 @@ async fn test_connect_bgp_open_with_delay() {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(delay_open_duration)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -493,9 +501,9 @@ async fn test_connect_bgp_open_with_delay() {
     assert!(conn.hold_timer().is_some())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_tcp_cr_acked() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -511,7 +519,7 @@ async fn test_connect_tcp_cr_acked() {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -526,9 +534,9 @@ async fn test_connect_tcp_cr_acked() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_bgp_header_err() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let bad_header = [0xee; 16];
     io_builder
         .read_u8(&bad_header) // Malformed header
@@ -548,7 +556,7 @@ async fn test_connect_bgp_header_err() {
     let config = PeerConfigBuilder::new()
         .open_delay_timer_duration(1)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -571,9 +579,9 @@ async fn test_connect_bgp_header_err() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_bgp_open_err_unsupported_version() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let bgp_version = 0x03;
     io_builder
         .read_u8(&[0xff; 16]) // BGP Standard header
@@ -594,7 +602,7 @@ async fn test_connect_bgp_open_err_unsupported_version() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -617,9 +625,9 @@ async fn test_connect_bgp_open_err_unsupported_version() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_bgp_open_err_unacceptable_hold_time() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let hold_time = 1;
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
 
@@ -639,7 +647,7 @@ async fn test_connect_bgp_open_err_unacceptable_hold_time() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -663,15 +671,15 @@ async fn test_connect_bgp_open_err_unacceptable_hold_time() {
 }
 
 #[ignore]
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_notif_version_err() {
     // TODO: hard to do since without open delay peer will immediately send
     // a BGP Open and transition to OpenSent state
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_notif_version_err_with_open_delay() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.read(BgpMessage::Notification(
         BgpNotificationMessage::OpenMessageError(OpenMessageError::UnsupportedVersionNumber {
             value: vec![3],
@@ -686,7 +694,7 @@ async fn test_connect_notif_version_err_with_open_delay() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await.unwrap();
     assert_eq!(event, BgpEvent::ManualStart);
@@ -703,9 +711,9 @@ async fn test_connect_notif_version_err_with_open_delay() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_automatic_stop() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.write(BgpMessage::Notification(
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] }),
     ));
@@ -718,7 +726,7 @@ async fn test_connect_automatic_stop() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -738,15 +746,15 @@ async fn test_connect_automatic_stop() {
 }
 
 #[ignore]
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_hold_timer_expires() {
     // TODO: this sound like impossible to test since a BGP message is sent
     // and state is transitioned to OpenSent
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_notif_msg() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let notif =
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
     io_builder.read(BgpMessage::Notification(notif.clone()));
@@ -760,7 +768,7 @@ async fn test_connect_notif_msg() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -777,9 +785,9 @@ async fn test_connect_notif_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_keepalive_msg() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder.read(BgpMessage::KeepAlive);
 
     let active_connect = MockActiveConnect {
@@ -791,7 +799,7 @@ async fn test_connect_keepalive_msg() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -808,9 +816,9 @@ async fn test_connect_keepalive_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_update_msg() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let update = BgpUpdateMessage::new(vec![], vec![], vec![]);
     io_builder.read(BgpMessage::Update(update.clone()));
 
@@ -823,7 +831,7 @@ async fn test_connect_update_msg() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -843,9 +851,9 @@ async fn test_connect_update_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_update_err_msg() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let update = [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0x00, 0x1b, 0x02, 0x00, 0x04, 0x19, 0xac, 0x10, 0x01, 0x00, 0x00,
@@ -861,7 +869,7 @@ async fn test_connect_update_err_msg() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -883,9 +891,9 @@ async fn test_connect_update_err_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_route_refresh_msg() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     let route_refresh = BgpRouteRefreshMessage::new(
         AddressType::Ipv4Unicast,
         RouteRefreshSubcode::BeginningOfRouteRefresh,
@@ -901,7 +909,7 @@ async fn test_connect_route_refresh_msg() {
         .open_delay_timer_duration(1)
         .send_notif_without_open(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await;
     assert_eq!(event, Ok(BgpEvent::ManualStart));
@@ -918,9 +926,9 @@ async fn test_connect_route_refresh_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_manual_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
@@ -930,7 +938,7 @@ async fn test_active_manual_start() {
         .open_delay_timer_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -948,9 +956,9 @@ async fn test_active_manual_start() {
     assert!(peer.connect_retry_timer().is_some());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_automatic_start() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
         io_builder,
@@ -960,7 +968,7 @@ async fn test_active_automatic_start() {
         .open_delay_timer_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -978,9 +986,9 @@ async fn test_active_automatic_start() {
     assert!(peer.connect_retry_timer().is_some());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_connect_retry_timer_expires() {
-    let io_builder = BgpIoMockBuilder::new();
+    let io_builder = BgpSimScriptBuilder::new();
 
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
@@ -992,7 +1000,7 @@ async fn test_active_connect_retry_timer_expires() {
         .connect_retry_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1016,11 +1024,11 @@ async fn test_active_connect_retry_timer_expires() {
     assert_eq!(peer.peer_stats().connect_retry_counter(), 0);
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_delay_open_timer_expires() {
     let open_delay = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -1036,7 +1044,7 @@ async fn test_active_delay_open_timer_expires() {
         .open_delay_timer_duration(open_delay)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1071,10 +1079,10 @@ async fn test_active_delay_open_timer_expires() {
     );
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_tcp_connection_confirmed() {
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -1092,7 +1100,7 @@ async fn test_active_tcp_connection_confirmed() {
         .connect_retry_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1119,10 +1127,10 @@ async fn test_active_tcp_connection_confirmed() {
     );
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_tcp_connection_confirmed_with_open_delay() {
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
 
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
@@ -1134,7 +1142,7 @@ async fn test_active_tcp_connection_confirmed_with_open_delay() {
         .connect_retry_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1157,12 +1165,12 @@ async fn test_active_tcp_connection_confirmed_with_open_delay() {
     assert!(conn.open_delay_timer().is_some());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_tcp_connection_fails() -> Result<(), FsmStateError<SocketAddr>> {
-    let active_io_builder = BgpIoMockBuilder::new();
-    let passive_io = tokio_test::io::Builder::new()
-        .read_error(io::Error::from(io::ErrorKind::ConnectionAborted))
-        .build();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    // The remote end closes the connection right away
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
+    let passive_io = passive_io_builder.build();
 
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
@@ -1174,7 +1182,7 @@ async fn test_active_tcp_connection_fails() -> Result<(), FsmStateError<SocketAd
         .connect_retry_duration(1)
         .passive_tcp_establishment(true)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1205,13 +1213,13 @@ async fn test_active_tcp_connection_fails() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_bgp_open_with_open_delay() {
     let delay_open_duration = 1;
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
 
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder
         .wait(Duration::from_millis(10))
         .read(BgpMessage::Open(peer_open.clone()))
@@ -1234,7 +1242,7 @@ async fn test_active_bgp_open_with_open_delay() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1276,11 +1284,11 @@ async fn test_active_bgp_open_with_open_delay() {
     assert!(conn.hold_timer().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_bgp_header_err() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     let bad_header = [0xee; 16];
     passive_io_builder
         .read_u8(&bad_header) // Malformed header
@@ -1304,7 +1312,7 @@ async fn test_active_bgp_header_err() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1335,12 +1343,12 @@ async fn test_active_bgp_header_err() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_bgp_open_err() {
     let delay_open_duration = 1;
 
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     let bgp_version = 0x03;
     passive_io_builder
         .read_u8(&[0xff; 16]) // BGP Standard header
@@ -1364,7 +1372,7 @@ async fn test_active_bgp_open_err() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1396,18 +1404,18 @@ async fn test_active_bgp_open_err() {
 }
 
 #[ignore]
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_notif_version_err() {
     // TODO: hard to do since without open delay peer will immediately send
     // a BGP Open and transition to OpenSent state
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_notif_version_err_with_open_delay() {
     let delay_open_duration = 1;
 
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read(BgpMessage::Notification(
         BgpNotificationMessage::OpenMessageError(OpenMessageError::UnsupportedVersionNumber {
             value: vec![0x00, 0x04],
@@ -1425,7 +1433,7 @@ async fn test_active_notif_version_err_with_open_delay() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1449,11 +1457,11 @@ async fn test_active_notif_version_err_with_open_delay() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_automatic_stop() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Notification(
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] }),
     ));
@@ -1469,7 +1477,7 @@ async fn test_active_automatic_stop() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1495,13 +1503,13 @@ async fn test_active_automatic_stop() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_notif_msg() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
     let notif =
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read(BgpMessage::Notification(notif.clone()));
 
     let active_connect = MockActiveConnect {
@@ -1515,7 +1523,7 @@ async fn test_active_notif_msg() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1541,11 +1549,11 @@ async fn test_active_notif_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_keepalive_msg() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read(BgpMessage::KeepAlive);
 
     let active_connect = MockActiveConnect {
@@ -1559,7 +1567,7 @@ async fn test_active_keepalive_msg() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1584,12 +1592,12 @@ async fn test_active_keepalive_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_update_msg() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
     let update = BgpUpdateMessage::new(vec![], vec![], vec![]);
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read(BgpMessage::Update(update.clone()));
 
     let active_connect = MockActiveConnect {
@@ -1603,7 +1611,7 @@ async fn test_active_update_msg() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1630,15 +1638,15 @@ async fn test_active_update_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_update_err_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
     let update = [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0x00, 0x1b, 0x02, 0x00, 0x04, 0x19, 0xac, 0x10, 0x01, 0x00, 0x00,
     ];
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read_u8(&update);
 
     let active_connect = MockActiveConnect {
@@ -1652,7 +1660,7 @@ async fn test_active_update_err_msg() -> Result<(), FsmStateError<SocketAddr>> {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1682,15 +1690,15 @@ async fn test_active_update_err_msg() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_active_route_refresh_msg() {
     let delay_open_duration = 1;
-    let active_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
     let route_refresh = BgpRouteRefreshMessage::new(
         AddressType::Ipv4Unicast,
         RouteRefreshSubcode::BeginningOfRouteRefresh,
     );
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.read(BgpMessage::RouteRefresh(route_refresh.clone()));
 
     let active_connect = MockActiveConnect {
@@ -1704,7 +1712,7 @@ async fn test_active_route_refresh_msg() {
         .passive_tcp_establishment(true)
         .hold_timer_duration(0)
         .build();
-    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
     assert_eq!(peer.fsm_state(), FsmState::Idle);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1729,9 +1737,9 @@ async fn test_active_route_refresh_msg() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_manual_stop() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -1753,7 +1761,7 @@ async fn test_open_sent_manual_stop() {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1775,9 +1783,9 @@ async fn test_open_sent_manual_stop() {
     assert!(peer.connect_retry_timer().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_automatic_stop() {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -1799,7 +1807,7 @@ async fn test_open_sent_automatic_stop() {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1821,12 +1829,12 @@ async fn test_open_sent_automatic_stop() {
     assert!(peer.connect_retry_timer().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_hold_timer_expires() {
     let hold_time = 1;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -1868,11 +1876,11 @@ async fn test_open_sent_hold_timer_expires() {
     assert!(peer.tracked_connection().is_none());
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_tcp_connection_confirmed() -> Result<(), FsmStateError<SocketAddr>> {
     let mut passive_addr = PEER_ADDR;
     passive_addr.set_port(5000);
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -1884,7 +1892,7 @@ async fn test_open_sent_tcp_connection_confirmed() -> Result<(), FsmStateError<S
         io_builder: active_io_builder,
         connect_delay: Duration::from_secs(1),
     };
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
         HOLD_TIME,
@@ -1896,7 +1904,7 @@ async fn test_open_sent_tcp_connection_confirmed() -> Result<(), FsmStateError<S
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1923,15 +1931,15 @@ async fn test_open_sent_tcp_connection_confirmed() -> Result<(), FsmStateError<S
 }
 
 #[ignore]
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_tcp_cr_acked() {
     // TODO: this implementation doesn't initiate connections in OpenSent
     // state, hence TCP CR acked is impossible
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_tcp_connections_fails() -> Result<(), FsmStateError<SocketAddr>> {
-    let active_io_builder = BgpIoMockBuilder::new();
+    let active_io_builder = BgpSimScriptBuilder::new();
 
     let msg = BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
@@ -1939,14 +1947,9 @@ async fn test_open_sent_tcp_connections_fails() -> Result<(), FsmStateError<Sock
         MY_BGP_ID,
         vec![],
     ));
-    let buf = vec![];
-    let mut cursor = Cursor::new(buf);
-    msg.write(&mut cursor).unwrap();
-
-    let passive_io = tokio_test::io::Builder::new()
-        .write(&cursor.into_inner())
-        .read_error(io::Error::from(io::ErrorKind::ConnectionAborted))
-        .build();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
+    passive_io_builder.write(msg);
+    let passive_io = passive_io_builder.build();
 
     let active_connect = MockActiveConnect {
         peer_addr: PEER_ADDR,
@@ -1957,7 +1960,7 @@ async fn test_open_sent_tcp_connections_fails() -> Result<(), FsmStateError<Sock
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -1984,10 +1987,10 @@ async fn test_open_sent_tcp_connections_fails() -> Result<(), FsmStateError<Sock
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_bgp_open() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2007,7 +2010,7 @@ async fn test_open_sent_bgp_open() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2033,10 +2036,10 @@ async fn test_open_sent_bgp_open() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_bgp_header_err() -> Result<(), FsmStateError<SocketAddr>> {
     let bad_header = [0xee; 16];
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2063,7 +2066,7 @@ async fn test_open_sent_bgp_header_err() -> Result<(), FsmStateError<SocketAddr>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
 
@@ -2094,10 +2097,10 @@ async fn test_open_sent_bgp_header_err() -> Result<(), FsmStateError<SocketAddr>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_bgp_open_err() -> Result<(), FsmStateError<SocketAddr>> {
     let bgp_version = 0x03;
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2124,7 +2127,7 @@ async fn test_open_sent_bgp_open_err() -> Result<(), FsmStateError<SocketAddr>>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2154,9 +2157,9 @@ async fn test_open_sent_bgp_open_err() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_notif_version_err() -> Result<(), FsmStateError<SocketAddr>> {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2179,7 +2182,7 @@ async fn test_open_sent_notif_version_err() -> Result<(), FsmStateError<SocketAd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2204,11 +2207,11 @@ async fn test_open_sent_notif_version_err() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let notif =
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2232,7 +2235,7 @@ async fn test_open_sent_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2257,9 +2260,9 @@ async fn test_open_sent_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_keep_alive_msg() -> Result<(), FsmStateError<SocketAddr>> {
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2283,7 +2286,7 @@ async fn test_open_sent_keep_alive_msg() -> Result<(), FsmStateError<SocketAddr>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2308,10 +2311,10 @@ async fn test_open_sent_keep_alive_msg() -> Result<(), FsmStateError<SocketAddr>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let update = BgpUpdateMessage::new(vec![], vec![], vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2335,7 +2338,7 @@ async fn test_open_sent_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2360,13 +2363,13 @@ async fn test_open_sent_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_update_err() -> Result<(), FsmStateError<SocketAddr>> {
     let update = [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0x00, 0x1b, 0x02, 0x00, 0x04, 0x19, 0xac, 0x10, 0x01, 0x00, 0x00,
     ];
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2390,7 +2393,7 @@ async fn test_open_sent_update_err() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2418,13 +2421,13 @@ async fn test_open_sent_update_err() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_sent_route_refresh_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let route_refresh = BgpRouteRefreshMessage::new(
         AddressType::Ipv4Unicast,
         RouteRefreshSubcode::BeginningOfRouteRefresh,
     );
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2448,7 +2451,7 @@ async fn test_open_sent_route_refresh_msg() -> Result<(), FsmStateError<SocketAd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2473,10 +2476,179 @@ async fn test_open_sent_route_refresh_msg() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_open_sent_collision_main_open_dump_tracked_connection(
+) -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) - 1);
+    let properties = PeerProperties::new(MY_AS, PEER_AS, MY_BGP_ID, PEER_ADDR, false);
+    let mut passive_addr = PEER_ADDR;
+    passive_addr.set_port(5000);
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
+
+    // The OPEN on the main connection arrives after the one on the tracked
+    // connection, so it's the main connection event that detects the collision
+    let mut active_io_builder = BgpSimScriptBuilder::new();
+    active_io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .wait(Duration::from_millis(100))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder: active_io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
+    passive_io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .write(BgpMessage::Notification(
+            BgpNotificationMessage::CeaseError(CeaseError::ConnectionCollisionResolution {
+                value: vec![],
+            }),
+        ))
+        .wait(Duration::from_secs(1));
+
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+    assert_eq!(peer.fsm_state(), FsmState::OpenSent);
+
+    peer.add_admin_event(PeerAdminEvents::TcpConnectionConfirmed((
+        passive_addr,
+        passive_io_builder.build(),
+    )));
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionConfirmed(passive_addr));
+    assert_eq!(peer.fsm_state(), FsmState::OpenSent);
+    assert!(peer.tracked_connection().is_some());
+
+    // The tracked connection is dropped and the OPEN received on the main
+    // connection is still handled by the FSM
+    let event = tokio::time::timeout(Duration::from_secs(3), peer.run()).await;
+    assert_eq!(event, Ok(Ok(BgpEvent::BGPOpen(peer_open))));
+    assert_eq!(peer.fsm_state(), FsmState::OpenConfirm);
+    assert!(peer.tracked_connection().is_none());
+    assert_eq!(*peer.connection().as_ref().unwrap().peer_addr(), PEER_ADDR);
+
+    let event = tokio::time::timeout(Duration::from_secs(3), peer.run()).await;
+    assert_eq!(event, Ok(Ok(BgpEvent::KeepAliveMsg)));
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_open_sent_collision_main_open_dump_main_connection(
+) -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) + 1);
+    let properties = PeerProperties::new(MY_AS, PEER_AS, MY_BGP_ID, PEER_ADDR, false);
+    let mut passive_addr = PEER_ADDR;
+    passive_addr.set_port(5000);
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
+
+    let mut active_io_builder = BgpSimScriptBuilder::new();
+    active_io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .wait(Duration::from_millis(100))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::Notification(
+            BgpNotificationMessage::CeaseError(CeaseError::ConnectionCollisionResolution {
+                value: vec![],
+            }),
+        ))
+        .wait(Duration::from_secs(1));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder: active_io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
+    passive_io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+    assert_eq!(peer.fsm_state(), FsmState::OpenSent);
+
+    peer.add_admin_event(PeerAdminEvents::TcpConnectionConfirmed((
+        passive_addr,
+        passive_io_builder.build(),
+    )));
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionConfirmed(passive_addr));
+    assert!(peer.tracked_connection().is_some());
+
+    // The main connection is replaced by the tracked one, the OPEN received on
+    // the main connection is discarded with it
+    let event = tokio::time::timeout(Duration::from_secs(3), peer.run()).await;
+    assert_eq!(event, Ok(Ok(BgpEvent::OpenCollisionDump)));
+    assert_eq!(peer.fsm_state(), FsmState::OpenConfirm);
+    assert!(peer.tracked_connection().is_none());
+    assert_eq!(
+        *peer.connection().as_ref().unwrap().peer_addr(),
+        passive_addr
+    );
+
+    let event = tokio::time::timeout(Duration::from_secs(3), peer.run()).await;
+    assert_eq!(event, Ok(Ok(BgpEvent::KeepAliveMsg)));
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_starts() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2497,7 +2669,7 @@ async fn test_open_confirm_starts() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2532,10 +2704,10 @@ async fn test_open_confirm_starts() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_manual_stop() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2560,7 +2732,7 @@ async fn test_open_confirm_manual_stop() -> Result<(), FsmStateError<SocketAddr>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2590,10 +2762,10 @@ async fn test_open_confirm_manual_stop() -> Result<(), FsmStateError<SocketAddr>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_automatic_stop() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2618,7 +2790,7 @@ async fn test_open_confirm_automatic_stop() -> Result<(), FsmStateError<SocketAd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -2648,13 +2820,13 @@ async fn test_open_confirm_automatic_stop() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_hold_timer_expires() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2726,13 +2898,13 @@ async fn test_open_confirm_hold_timer_expires() -> Result<(), FsmStateError<Sock
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_keep_alive_timer_expires() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2780,7 +2952,7 @@ async fn test_open_confirm_keep_alive_timer_expires() -> Result<(), FsmStateErro
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -2788,7 +2960,7 @@ async fn test_open_confirm_notif_msg() -> Result<(), FsmStateError<SocketAddr>>
     let notif =
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2839,13 +3011,13 @@ async fn test_open_confirm_notif_msg() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_notif_version_err() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2900,7 +3072,7 @@ async fn test_open_confirm_notif_version_err() -> Result<(), FsmStateError<Socke
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_collision_dump_main_connection() -> Result<(), FsmStateError<SocketAddr>>
 {
     let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) + 1);
@@ -2909,7 +3081,7 @@ async fn test_open_confirm_collision_dump_main_connection() -> Result<(), FsmSta
     passive_addr.set_port(5000);
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
 
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2931,7 +3103,7 @@ async fn test_open_confirm_collision_dump_main_connection() -> Result<(), FsmSta
         connect_delay: Duration::from_secs(0),
     };
 
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -2947,7 +3119,7 @@ async fn test_open_confirm_collision_dump_main_connection() -> Result<(), FsmSta
         PEER_KEY,
         properties,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
 
@@ -2992,7 +3164,7 @@ async fn test_open_confirm_collision_dump_main_connection() -> Result<(), FsmSta
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_collision_dump_tracked_connection(
 ) -> Result<(), FsmStateError<SocketAddr>> {
     let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) - 1);
@@ -3001,7 +3173,7 @@ async fn test_open_confirm_collision_dump_tracked_connection(
     passive_addr.set_port(5000);
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
 
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3020,7 +3192,7 @@ async fn test_open_confirm_collision_dump_tracked_connection(
         connect_delay: Duration::from_secs(0),
     };
 
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3041,7 +3213,7 @@ async fn test_open_confirm_collision_dump_tracked_connection(
         PEER_KEY,
         properties,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
 
@@ -3084,7 +3256,7 @@ async fn test_open_confirm_collision_dump_tracked_connection(
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_open_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -3093,7 +3265,7 @@ async fn test_open_confirm_open_msg() -> Result<(), FsmStateError<SocketAddr>> {
         FiniteStateMachineError::ReceiveUnexpectedMessageInOpenConfirmState { value: vec![] },
     );
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3145,7 +3317,7 @@ async fn test_open_confirm_open_msg() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_bgp_open_err() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -3156,7 +3328,7 @@ async fn test_open_confirm_bgp_open_err() -> Result<(), FsmStateError<SocketAddr
             value: vec![0x00, 0x04],
         });
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3216,7 +3388,7 @@ async fn test_open_confirm_bgp_open_err() -> Result<(), FsmStateError<SocketAddr
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_bgp_header_err() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -3227,7 +3399,7 @@ async fn test_open_confirm_bgp_header_err() -> Result<(), FsmStateError<SocketAd
             value: Vec::from(&bad_header),
         });
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3285,10 +3457,10 @@ async fn test_open_confirm_bgp_header_err() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_keep_alive_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3310,7 +3482,7 @@ async fn test_open_confirm_keep_alive_msg() -> Result<(), FsmStateError<SocketAd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3336,11 +3508,11 @@ async fn test_open_confirm_keep_alive_msg() -> Result<(), FsmStateError<SocketAd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
     let update = BgpUpdateMessage::new(vec![], vec![], vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3368,7 +3540,7 @@ async fn test_open_confirm_update_msg() -> Result<(), FsmStateError<SocketAddr>>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3397,14 +3569,14 @@ async fn test_open_confirm_update_msg() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_update_err() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
     let update = [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0x00, 0x1b, 0x02, 0x00, 0x04, 0x19, 0xac, 0x10, 0x01, 0x00, 0x00,
     ];
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3432,7 +3604,7 @@ async fn test_open_confirm_update_err() -> Result<(), FsmStateError<SocketAddr>>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3464,14 +3636,14 @@ async fn test_open_confirm_update_err() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_open_confirm_route_refresh_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
     let route_refresh = BgpRouteRefreshMessage::new(
         AddressType::Ipv4Unicast,
         RouteRefreshSubcode::BeginningOfRouteRefresh,
     );
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3499,7 +3671,7 @@ async fn test_open_confirm_route_refresh_msg() -> Result<(), FsmStateError<Socke
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3528,10 +3700,10 @@ async fn test_open_confirm_route_refresh_msg() -> Result<(), FsmStateError<Socke
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_starts() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3553,7 +3725,7 @@ async fn test_established_starts() -> Result<(), FsmStateError<SocketAddr>> {
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3592,10 +3764,10 @@ async fn test_established_starts() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_manual_stop() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3621,7 +3793,7 @@ async fn test_established_manual_stop() -> Result<(), FsmStateError<SocketAddr>>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3655,10 +3827,10 @@ async fn test_established_manual_stop() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_automatic_stop() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3684,7 +3856,7 @@ async fn test_established_automatic_stop() -> Result<(), FsmStateError<SocketAdd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -3718,13 +3890,13 @@ async fn test_established_automatic_stop() -> Result<(), FsmStateError<SocketAdd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_hold_timer_expires() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3799,13 +3971,13 @@ async fn test_established_hold_timer_expires() -> Result<(), FsmStateError<Socke
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_keep_alive_timer_expires() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
         EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, hold_time, Vec::new(), Vec::new());
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3863,7 +4035,7 @@ async fn test_established_keep_alive_timer_expires() -> Result<(), FsmStateError
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_collision_dump_main_connection() -> Result<(), FsmStateError<SocketAddr>>
 {
     let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) + 1);
@@ -3872,7 +4044,7 @@ async fn test_established_collision_dump_main_connection() -> Result<(), FsmStat
     passive_addr.set_port(5000);
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
 
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3895,7 +4067,7 @@ async fn test_established_collision_dump_main_connection() -> Result<(), FsmStat
         connect_delay: Duration::from_secs(0),
     };
 
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3911,7 +4083,7 @@ async fn test_established_collision_dump_main_connection() -> Result<(), FsmStat
         .collision_detect_established_state(true)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, properties, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, properties, config, SIM_POLICY, active_connect);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await?;
@@ -3961,7 +4133,7 @@ async fn test_established_collision_dump_main_connection() -> Result<(), FsmStat
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_collision_dump_tracked_connection(
 ) -> Result<(), FsmStateError<SocketAddr>> {
     let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) - 1);
@@ -3970,7 +4142,7 @@ async fn test_established_collision_dump_tracked_connection(
     passive_addr.set_port(5000);
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
 
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -3990,7 +4162,7 @@ async fn test_established_collision_dump_tracked_connection(
         connect_delay: Duration::from_secs(0),
     };
 
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4011,7 +4183,7 @@ async fn test_established_collision_dump_tracked_connection(
         .collision_detect_established_state(true)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, properties, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, properties, config, SIM_POLICY, active_connect);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await?;
@@ -4058,7 +4230,7 @@ async fn test_established_collision_dump_tracked_connection(
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_reject_connection_tracking_disabled(
 ) -> Result<(), FsmStateError<SocketAddr>> {
     let peer_bgp_id = Ipv4Addr::from(u32::from(MY_BGP_ID) - 1);
@@ -4067,7 +4239,7 @@ async fn test_established_reject_connection_tracking_disabled(
     passive_addr.set_port(5000);
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, peer_bgp_id, vec![]);
 
-    let mut active_io_builder = BgpIoMockBuilder::new();
+    let mut active_io_builder = BgpSimScriptBuilder::new();
     active_io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4087,7 +4259,7 @@ async fn test_established_reject_connection_tracking_disabled(
         connect_delay: Duration::from_secs(0),
     };
 
-    let mut passive_io_builder = BgpIoMockBuilder::new();
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
     passive_io_builder.write(BgpMessage::Notification(
         BgpNotificationMessage::CeaseError(CeaseError::ConnectionRejected { value: vec![] }),
     ));
@@ -4096,7 +4268,7 @@ async fn test_established_reject_connection_tracking_disabled(
         .collision_detect_established_state(false)
         .build();
 
-    let mut peer = Peer::new(PEER_KEY, properties, config, POLICY, active_connect);
+    let mut peer = Peer::new(PEER_KEY, properties, config, SIM_POLICY, active_connect);
 
     peer.add_admin_event(PeerAdminEvents::ManualStart);
     let event = peer.run().await?;
@@ -4130,7 +4302,7 @@ async fn test_established_reject_connection_tracking_disabled(
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -4138,7 +4310,7 @@ async fn test_established_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, hold_time, PEER_BGP_ID, vec![]);
     let notif =
         BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4193,7 +4365,7 @@ async fn test_established_notif_msg() -> Result<(), FsmStateError<SocketAddr>> {
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_notif_version_error() -> Result<(), FsmStateError<SocketAddr>> {
     let hold_time = 3;
     let policy =
@@ -4203,7 +4375,7 @@ async fn test_established_notif_version_error() -> Result<(), FsmStateError<Sock
         BgpNotificationMessage::OpenMessageError(OpenMessageError::UnsupportedVersionNumber {
             value: vec![0x00, 0x04],
         });
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4258,7 +4430,7 @@ async fn test_established_notif_version_error() -> Result<(), FsmStateError<Sock
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_tcp_connection_fails() -> Result<(), FsmStateError<SocketAddr>> {
     let my_open = BgpMessage::Open(BgpOpenMessage::new(
         MY_AS as u16,
@@ -4266,34 +4438,20 @@ async fn test_established_tcp_connection_fails() -> Result<(), FsmStateError<Soc
         MY_BGP_ID,
         vec![],
     ));
-    let buf = vec![];
-    let mut cursor = Cursor::new(buf);
-    my_open.write(&mut cursor).unwrap();
-    let my_open_buf = cursor.into_inner();
-
     let peer_open = BgpMessage::Open(BgpOpenMessage::new(
         PEER_AS as u16,
         HOLD_TIME,
         PEER_BGP_ID,
         vec![],
     ));
-    let buf = vec![];
-    let mut cursor = Cursor::new(buf);
-    peer_open.write(&mut cursor).unwrap();
-    let peer_open_buf = cursor.into_inner();
-
-    let buf = vec![];
-    let mut cursor = Cursor::new(buf);
-    BgpMessage::KeepAlive.write(&mut cursor).unwrap();
-    let keepalive_buf = cursor.into_inner();
-
-    let passive_io = tokio_test::io::Builder::new()
-        .write(&my_open_buf)
-        .read(&peer_open_buf)
-        .write(&keepalive_buf)
-        .read(&keepalive_buf)
-        .read_error(io::Error::from(io::ErrorKind::ConnectionAborted))
-        .build();
+    // The remote end closes the connection once established
+    let mut passive_io_builder = BgpSimScriptBuilder::new();
+    passive_io_builder
+        .write(my_open)
+        .read(peer_open)
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive);
+    let passive_io = passive_io_builder.build();
 
     let active_connect = MockFailedActiveConnect {
         peer_addr: PEER_ADDR,
@@ -4303,7 +4461,7 @@ async fn test_established_tcp_connection_fails() -> Result<(), FsmStateError<Soc
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -4337,10 +4495,10 @@ async fn test_established_tcp_connection_fails() -> Result<(), FsmStateError<Soc
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_keep_alive_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4362,7 +4520,7 @@ async fn test_established_keep_alive_msg() -> Result<(), FsmStateError<SocketAdd
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -4391,11 +4549,11 @@ async fn test_established_keep_alive_msg() -> Result<(), FsmStateError<SocketAdd
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_established_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
     let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
     let update = BgpUpdateMessage::new(vec![], vec![], vec![]);
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .write(BgpMessage::Open(BgpOpenMessage::new(
             MY_AS as u16,
@@ -4417,7 +4575,7 @@ async fn test_established_update_msg() -> Result<(), FsmStateError<SocketAddr>>
         PEER_KEY,
         PROPERTIES,
         PeerConfig::default(),
-        POLICY,
+        SIM_POLICY,
         active_connect,
     );
     peer.add_admin_event(PeerAdminEvents::ManualStart);
@@ -4446,7 +4604,52 @@ async fn test_established_update_msg() -> Result<(), FsmStateError<SocketAddr>>
     Ok(())
 }
 
-#[test_log::test(tokio::test)]
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_withdraw_only_update_msg() -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    // Withdraw only updates don't carry the mandatory path attributes
+    let withdraw = BgpUpdateMessage::new(vec![ipv4_unicast("10.0.0.0/24")], vec![], vec![]);
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(withdraw.clone()));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(withdraw, UpdateTreatment::Normal)
+    );
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
 async fn test_connect_echo_policy() -> Result<(), FsmStateError<SocketAddr>> {
     let my_asn = 66_000; // must be encoded as ASN4
     let extended_msg_cap = BgpCapability::ExtendedMessage;
@@ -4516,7 +4719,7 @@ async fn test_connect_echo_policy() -> Result<(), FsmStateError<SocketAddr>> {
         ])],
     );
 
-    let mut io_builder = BgpIoMockBuilder::new();
+    let mut io_builder = BgpSimScriptBuilder::new();
     io_builder
         .read(BgpMessage::Open(peer_open.clone()))
         .write(BgpMessage::Open(my_open))
@@ -4547,3 +4750,699 @@ async fn test_connect_echo_policy() -> Result<(), FsmStateError<SocketAddr>> {
 
     Ok(())
 }
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_route_refresh_readvertise() -> Result<(), FsmStateError<SocketAddr>> {
+    let caps = vec![
+        BgpCapability::RouteRefresh,
+        BgpCapability::EnhancedRouteRefresh,
+    ];
+    let policy =
+        EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, caps.clone(), Vec::new());
+    let peer_open = BgpOpenMessage::new(
+        PEER_AS as u16,
+        HOLD_TIME,
+        PEER_BGP_ID,
+        vec![Capabilities(caps.clone())],
+    );
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![MY_AS as u16]),
+            next_hop_attr(MY_BGP_ID),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let request =
+        BgpRouteRefreshMessage::new(AddressType::Ipv4Unicast, RouteRefreshSubcode::NormalRequest);
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![Capabilities(caps)],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .write(BgpMessage::Update(update.clone()))
+        .read(BgpMessage::RouteRefresh(request.clone()))
+        .write(BgpMessage::RouteRefresh(BgpRouteRefreshMessage::new(
+            AddressType::Ipv4Unicast,
+            RouteRefreshSubcode::BeginningOfRouteRefresh,
+        )))
+        .write(BgpMessage::Update(update.clone()))
+        .write(BgpMessage::RouteRefresh(BgpRouteRefreshMessage::new(
+            AddressType::Ipv4Unicast,
+            RouteRefreshSubcode::EndOfRouteRefresh,
+        )));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        policy,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::BGPOpen(peer_open));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::KeepAliveMsg);
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    peer.send_bgp_message(BgpMessage::Update(update)).await?;
+    assert_eq!(peer.adj_rib_out().len(AddressType::Ipv4Unicast), 1);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::RouteRefresh(request));
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_enhanced_route_refresh_stale_sweep(
+) -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(PEER_BGP_ID),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    let refreshed = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(PEER_BGP_ID),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let borr = BgpRouteRefreshMessage::new(
+        AddressType::Ipv4Unicast,
+        RouteRefreshSubcode::BeginningOfRouteRefresh,
+    );
+    let eorr = BgpRouteRefreshMessage::new(
+        AddressType::Ipv4Unicast,
+        RouteRefreshSubcode::EndOfRouteRefresh,
+    );
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update.clone()))
+        .read(BgpMessage::RouteRefresh(borr.clone()))
+        .read(BgpMessage::Update(refreshed.clone()))
+        .read(BgpMessage::RouteRefresh(eorr.clone()));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::BGPOpen(peer_open));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::KeepAliveMsg);
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::UpdateMsg(update, UpdateTreatment::Normal));
+    assert_eq!(peer.adj_rib_in().len(AddressType::Ipv4Unicast), 2);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::RouteRefresh(borr));
+
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(refreshed, UpdateTreatment::Normal)
+    );
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::RouteRefresh(eorr));
+
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::StaleRoutesWithdrawn(BgpUpdateMessage::new(
+            vec![ipv4_unicast("10.0.1.0/24")],
+            vec![],
+            vec![]
+        ))
+    );
+    assert_eq!(peer.adj_rib_in().len(AddressType::Ipv4Unicast), 1);
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_request_route_refresh() -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(
+        PEER_AS as u16,
+        HOLD_TIME,
+        PEER_BGP_ID,
+        vec![Capabilities(vec![BgpCapability::RouteRefresh])],
+    );
+    let request =
+        BgpRouteRefreshMessage::new(AddressType::Ipv6Unicast, RouteRefreshSubcode::NormalRequest);
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .write(BgpMessage::RouteRefresh(request));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+    // Ignored since the session is not established yet
+    peer.request_route_refresh(AddressType::Ipv6Unicast).await?;
+
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::BGPOpen(peer_open));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::KeepAliveMsg);
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    peer.request_route_refresh(AddressType::Ipv6Unicast).await?;
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_max_prefix_exceeded() -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(PEER_BGP_ID),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update.clone()))
+        .write(BgpMessage::Notification(
+            BgpNotificationMessage::CeaseError(CeaseError::MaximumNumberOfPrefixesReached {
+                value: vec![0, 1, 1, 0, 0, 0, 1],
+            }),
+        ));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let config = PeerConfigBuilder::new()
+        .max_prefix(
+            AddressType::Ipv4Unicast,
+            MaxPrefixConfig::new(1, 75, false, Some(1)).unwrap(),
+        )
+        .unwrap()
+        .build();
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::BGPOpen(peer_open));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::KeepAliveMsg);
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::UpdateMsg(update, UpdateTreatment::Normal));
+    assert_eq!(peer.fsm_state(), FsmState::Idle);
+    assert!(peer.connection().is_none());
+    assert!(peer.adj_rib_in().is_empty());
+    assert!(peer.max_prefix_restart_timer().is_some());
+    assert_eq!(peer.stats().connect_retry_counter(), 1);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::AutomaticStop);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::AutomaticStart);
+    assert_eq!(peer.fsm_state(), FsmState::Connect);
+    assert!(peer.max_prefix_restart_timer().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_max_prefix_config_validation() {
+    assert!(MaxPrefixConfig::new(10, 1, false, Some(1)).is_ok());
+    assert!(MaxPrefixConfig::new(10, 100, false, None).is_ok());
+    assert_eq!(
+        MaxPrefixConfig::new(10, 0, false, None),
+        Err(MaxPrefixConfigError::InvalidWarningThreshold(0))
+    );
+    assert_eq!(
+        MaxPrefixConfig::new(10, 101, true, None),
+        Err(MaxPrefixConfigError::InvalidWarningThreshold(101))
+    );
+    assert_eq!(
+        MaxPrefixConfig::new(10, 75, false, Some(0)),
+        Err(MaxPrefixConfigError::ZeroRestartInterval)
+    );
+    let max_prefix = MaxPrefixConfig::new(10, 75, false, None).unwrap();
+    assert!(PeerConfigBuilder::new()
+        .max_prefix(AddressType::Ipv6Multicast, max_prefix)
+        .is_ok());
+    assert_eq!(
+        PeerConfigBuilder::new()
+            .max_prefix(AddressType::L2VpnBgpEvpn, max_prefix)
+            .err(),
+        Some(MaxPrefixConfigError::UnsupportedAddressType(
+            AddressType::L2VpnBgpEvpn
+        ))
+    );
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_established_max_prefix_warning_only() -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(PEER_BGP_ID),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open.clone()))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update.clone()));
+
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let config = PeerConfigBuilder::new()
+        .max_prefix(
+            AddressType::Ipv4Unicast,
+            MaxPrefixConfig::new(1, 75, true, None).unwrap(),
+        )
+        .unwrap()
+        .build();
+    let mut peer = Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect);
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::TcpConnectionRequestAcked(PEER_ADDR));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::BGPOpen(peer_open));
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::KeepAliveMsg);
+
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::UpdateMsg(update, UpdateTreatment::Normal));
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+    assert_eq!(peer.adj_rib_in().len(AddressType::Ipv4Unicast), 2);
+    assert!(peer.max_prefix_restart_timer().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_idle_hold_time_backoff() {
+    let config = PeerConfigBuilder::new()
+        .idle_hold_duration(5)
+        .idle_hold_max_duration(60)
+        .build();
+    assert_eq!(config.idle_hold_time(0), Duration::from_secs(5));
+    assert_eq!(config.idle_hold_time(1), Duration::from_secs(5));
+    assert_eq!(config.idle_hold_time(2), Duration::from_secs(10));
+    assert_eq!(config.idle_hold_time(3), Duration::from_secs(20));
+    assert_eq!(config.idle_hold_time(4), Duration::from_secs(40));
+    assert_eq!(config.idle_hold_time(5), Duration::from_secs(60));
+    assert_eq!(config.idle_hold_time(u32::MAX), Duration::from_secs(60));
+}
+
+/// Peer that gets established then receives a cease notification after
+/// `established_duration`
+fn flapping_peer(
+    established_duration: Duration,
+    idle_hold_decay_duration: u16,
+) -> Peer<
+    IpAddr,
+    SocketAddr,
+    SimStream,
+    BgpCodec,
+    MockActiveConnect<BgpSimScriptBuilder>,
+    EchoCapabilitiesPolicy<SocketAddr, SimStream, BgpCodec>,
+> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .wait(established_duration)
+        .read(BgpMessage::Notification(
+            BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown {
+                value: vec![],
+            }),
+        ));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let config = PeerConfigBuilder::new()
+        .damp_peer_oscillations(true)
+        .idle_hold_duration(1)
+        .idle_hold_max_duration(60)
+        .idle_hold_decay_duration(idle_hold_decay_duration)
+        .build();
+    Peer::new(PEER_KEY, PROPERTIES, config, SIM_POLICY, active_connect)
+}
+
+async fn run_till_established<C: ActiveConnect<SocketAddr, SimStream, BgpCodec>>(
+    peer: &mut Peer<
+        IpAddr,
+        SocketAddr,
+        SimStream,
+        BgpCodec,
+        C,
+        EchoCapabilitiesPolicy<SocketAddr, SimStream, BgpCodec>,
+    >,
+) -> Result<(), FsmStateError<SocketAddr>> {
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_damp_peer_oscillations() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut peer = flapping_peer(Duration::from_secs(1), 60);
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+
+    for penalty in 1..=3 {
+        run_till_established(&mut peer).await?;
+        let event = peer.run().await?;
+        assert!(matches!(event, BgpEvent::NotifMsg(_)));
+        assert_eq!(peer.fsm_state(), FsmState::Idle);
+        assert_eq!(peer.stats().damp_penalty(), penalty);
+        assert!(peer.idle_hold_timer().is_some());
+
+        // Automatic start events are ignored while the IdleHoldTimer is running
+        peer.add_admin_event(PeerAdminEvents::AutomaticStart);
+        let before = tokio::time::Instant::now();
+        let event = peer.run().await?;
+        assert_eq!(event, BgpEvent::IdleHoldTimerExpires);
+        assert_eq!(before.elapsed(), Duration::from_secs(1 << (penalty - 1)));
+        assert!(peer.idle_hold_timer().is_none());
+
+        let event = peer.run().await?;
+        assert_eq!(event, BgpEvent::AutomaticStartWithDampPeerOscillations);
+        assert_eq!(peer.fsm_state(), FsmState::Connect);
+    }
+
+    // Manual start resets the damping
+    run_till_established(&mut peer).await?;
+    peer.run().await?;
+    assert_eq!(peer.stats().damp_penalty(), 4);
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStart);
+    assert_eq!(peer.stats().damp_penalty(), 0);
+    assert!(peer.idle_hold_timer().is_none());
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_damp_peer_oscillations_decay() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut peer = flapping_peer(Duration::from_secs(3), 1);
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    peer.run().await?;
+
+    for _ in 0..3 {
+        run_till_established(&mut peer).await?;
+        let event = peer.run().await?;
+        assert!(matches!(event, BgpEvent::NotifMsg(_)));
+        // The penalty decays while the session is established
+        assert_eq!(peer.stats().damp_penalty(), 1);
+        let event = peer.run().await?;
+        assert_eq!(event, BgpEvent::IdleHoldTimerExpires);
+        let event = peer.run().await?;
+        assert_eq!(event, BgpEvent::AutomaticStartWithDampPeerOscillations);
+    }
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_stats() -> Result<(), FsmStateError<SocketAddr>> {
+    let peer_open = BgpOpenMessage::new(PEER_AS as u16, HOLD_TIME, PEER_BGP_ID, vec![]);
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![PEER_AS as u16]),
+            next_hop_attr(Ipv4Addr::new(192, 168, 0, 2)),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    let withdraw = BgpUpdateMessage::new(
+        vec![ipv4_unicast("10.0.1.0/24")],
+        vec![origin_attr(), as_path_attr(vec![PEER_AS as u16])],
+        vec![],
+    );
+    let cease =
+        BgpNotificationMessage::CeaseError(CeaseError::AdministrativeShutdown { value: vec![] });
+    let mut io_builder = BgpSimScriptBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(peer_open))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update))
+        .read(BgpMessage::Update(withdraw))
+        .wait(Duration::from_secs(10))
+        .read(BgpMessage::Notification(cease.clone()));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    run_till_established(&mut peer).await?;
+
+    let stats = peer.stats();
+    assert_eq!(stats.established_transitions(), 1);
+    assert!(stats.last_up().is_some());
+    assert_eq!(stats.last_down(), None);
+    assert!(stats.uptime().is_some());
+    let first = stats.fsm_history().front().unwrap();
+    assert_eq!(first.from(), FsmState::Idle);
+    assert_eq!(first.to(), FsmState::Connect);
+    assert_eq!(first.event(), Some(&BgpEvent::ManualStart));
+    assert_eq!(
+        stats.fsm_history().back().unwrap().to(),
+        FsmState::Established
+    );
+    assert_eq!(stats.fsm_transitions(), stats.fsm_history().len() as u64);
+
+    let event = peer.run().await?;
+    assert!(matches!(event, BgpEvent::UpdateMsg(_, _)));
+    let event = peer.run().await?;
+    assert!(matches!(event, BgpEvent::UpdateMsg(_, _)));
+    let counters = peer.stats().prefix_counters(AddressType::Ipv4Unicast);
+    assert_eq!(counters.received(), 2);
+    assert_eq!(counters.withdrawn(), 1);
+    assert_eq!(counters.accepted(), 1);
+    assert!(peer.stats().update_rate() > 0.0);
+    assert!(peer.stats().withdraw_rate() > 0.0);
+
+    let event = peer.run().await?;
+    assert!(matches!(event, BgpEvent::NotifMsg(_)));
+    let stats = peer.stats();
+    assert_eq!(peer.fsm_state(), FsmState::Idle);
+    assert!(stats.last_down().is_some());
+    assert_eq!(stats.uptime(), None);
+    assert_eq!(
+        stats.last_notification_received().map(|x| x.notification()),
+        Some(&cease)
+    );
+    assert_eq!(stats.last_notification_sent(), None);
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_update_config() -> Result<(), FsmStateError<SocketAddr>> {
+    let active_connect = MockFailedActiveConnect {
+        peer_addr: PEER_ADDR,
+        connect_delay: Duration::from_secs(0),
+    };
+    let config = PeerConfigBuilder::new()
+        .connect_retry_duration(10)
+        .passive_tcp_establishment(true)
+        .build();
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        config.clone(),
+        SIM_POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ManualStartWithPassiveTcp);
+    assert_eq!(peer.fsm_state(), FsmState::Active);
+
+    let new_config = PeerConfigBuilder::new()
+        .connect_retry_duration(20)
+        .passive_tcp_establishment(true)
+        .collision_detect_established_state(true)
+        .build();
+    peer.update_config(new_config.clone());
+    assert_eq!(peer.config(), &new_config);
+    // Running timer is not reset by the config change
+    assert_eq!(
+        peer.connect_retry_timer().map(|x| x.period()),
+        Some(Duration::from_secs(10))
+    );
+
+    let before = tokio::time::Instant::now();
+    let event = peer.run().await?;
+    assert_eq!(event, BgpEvent::ConnectRetryTimerExpires);
+    assert_eq!(before.elapsed(), Duration::from_secs(10));
+    // The new duration is picked up on the next timer cycle
+    assert_eq!(
+        peer.connect_retry_timer().map(|x| x.period()),
+        Some(Duration::from_secs(20))
+    );
+    Ok(())
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_update_policy() {
+    let active_connect = MockFailedActiveConnect {
+        peer_addr: PEER_ADDR,
+        connect_delay: Duration::from_secs(0),
+    };
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        SIM_POLICY,
+        active_connect,
+    );
+    let mut policy = SIM_POLICY;
+    policy.send_asn4_cap_by_default(true);
+    peer.update_policy(policy);
+    assert!(peer.policy().is_send_asn4_cap_by_default());
+}
@@ -17,10 +17,10 @@ use crate::{
     events::BgpEvent,
     fsm::FsmState,
     peer::*,
-    peer_controller::PeerController,
+    peer_controller::{PeerController, UpdatePolicyError},
     tests::{
         BgpIoMockBuilder, MockActiveConnect, HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS,
-        PEER_BGP_ID, PEER_KEY, POLICY, PROPERTIES,
+        PEER_BGP_ID, PEER_KEY, POLICY, PROPERTIES, SIM_POLICY,
     },
 };
 use netgauze_bgp_pkt::{
@@ -115,6 +115,63 @@ async fn test_start_stop_with_passive_tcp(
     Ok(())
 }
 
+#[test_log::test(tokio::test)]
+async fn test_get_state_and_ribs() {
+    let config = PeerConfigBuilder::default()
+        .passive_tcp_establishment(true)
+        .build();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder.wait(Duration::from_secs(1));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+
+    let controller = PeerController::new(PEER_KEY, PROPERTIES, config, tx, POLICY, active_connect);
+    let mut handle = controller.get_new_handle();
+    assert_eq!(
+        handle.peer_stats().await.unwrap().fsm_state(),
+        FsmState::Idle
+    );
+    handle.start().unwrap();
+    assert_eq!(
+        rx.recv().await,
+        Some(Ok((FsmState::Active, BgpEvent::ManualStartWithPassiveTcp)))
+    );
+    assert_eq!(
+        handle.peer_stats().await.unwrap().fsm_state(),
+        FsmState::Active
+    );
+    assert!(handle.adj_rib_in().await.unwrap().is_empty());
+    assert!(handle.adj_rib_out().await.unwrap().is_empty());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_policy_type_mismatch() {
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder: BgpIoMockBuilder::new(),
+        connect_delay: Duration::from_secs(0),
+    };
+    let controller = PeerController::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        tx,
+        POLICY,
+        active_connect,
+    );
+    let handle = controller.get_new_handle();
+    assert!(handle.update_policy(POLICY).is_ok());
+    assert!(matches!(
+        handle.update_policy(SIM_POLICY),
+        Err(UpdatePolicyError::PolicyTypeMismatch)
+    ));
+}
+
 #[test_log::test(tokio::test)]
 async fn test_get_exchanged_capabilities(
 ) -> Result<(), mpsc::error::SendError<PeerEvent<SocketAddr, tokio_test::io::Mock>>> {
@@ -0,0 +1,322 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    open::BgpOpenMessage,
+    path_attribute::{
+        ClusterId, ClusterList, LocalPreference, MultiExitDiscriminator, Originator, PathAttribute,
+        PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    events::{BgpEvent, UpdateTreatment},
+    export::*,
+    fsm::{FsmState, FsmStateError},
+    peer::*,
+    reflection::*,
+    rib::Route,
+    tests::{
+        rib::{as_path_attr, ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+const CLUSTER_ID: Ipv4Addr = Ipv4Addr::new(192, 168, 100, 1);
+const OTHER_BGP_ID: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 3);
+
+fn originator_attr(id: Ipv4Addr) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::Originator(Originator::new(id)),
+    )
+    .unwrap()
+}
+
+fn cluster_list_attr(ids: Vec<Ipv4Addr>) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::ClusterList(ClusterList::new(
+            ids.into_iter().map(ClusterId::new).collect(),
+        )),
+    )
+    .unwrap()
+}
+
+fn med_attr(metric: u32) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        false,
+        false,
+        false,
+        PathAttributeValue::MultiExitDiscriminator(MultiExitDiscriminator::new(metric)),
+    )
+    .unwrap()
+}
+
+fn ibgp_properties(client_role: ClientRole) -> PeerProperties<SocketAddr> {
+    PeerProperties::new(MY_AS, MY_AS, MY_BGP_ID, PEER_ADDR, false).with_client_role(client_role)
+}
+
+#[test]
+fn test_is_reflection_loop() {
+    let attributes = vec![origin_attr(), as_path_attr(vec![])];
+    assert!(!is_reflection_loop(&attributes, MY_BGP_ID, CLUSTER_ID));
+
+    let attributes = vec![
+        origin_attr(),
+        originator_attr(OTHER_BGP_ID),
+        cluster_list_attr(vec![OTHER_BGP_ID]),
+    ];
+    assert!(!is_reflection_loop(&attributes, MY_BGP_ID, CLUSTER_ID));
+
+    let attributes = vec![origin_attr(), originator_attr(MY_BGP_ID)];
+    assert!(is_reflection_loop(&attributes, MY_BGP_ID, CLUSTER_ID));
+
+    let attributes = vec![
+        origin_attr(),
+        originator_attr(OTHER_BGP_ID),
+        cluster_list_attr(vec![OTHER_BGP_ID, CLUSTER_ID]),
+    ];
+    assert!(is_reflection_loop(&attributes, MY_BGP_ID, CLUSTER_ID));
+}
+
+#[test]
+fn test_export_reflected_routes() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default();
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+        ],
+        None,
+    );
+    let from_non_client = RouteSource::Internal {
+        peer_bgp_id: OTHER_BGP_ID,
+        from_client: false,
+    };
+    let from_client = RouteSource::Internal {
+        peer_bgp_id: OTHER_BGP_ID,
+        from_client: true,
+    };
+    let local_pref = PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::LocalPreference(LocalPreference::new(DEFAULT_LOCAL_PREF)),
+    )
+    .unwrap();
+    let reflected = Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            local_pref.clone(),
+            originator_attr(OTHER_BGP_ID),
+            cluster_list_attr(vec![MY_BGP_ID]),
+        ],
+        None,
+    );
+
+    // Routes from non clients are only reflected to clients
+    let non_client = ibgp_properties(ClientRole::NonClient);
+    let client = ibgp_properties(ClientRole::RouteReflectorClient);
+    assert_eq!(
+        export_route(&non_client, &config, &key, &route, from_non_client),
+        None
+    );
+    assert_eq!(
+        export_route(&client, &config, &key, &route, from_non_client),
+        Some(reflected.clone())
+    );
+    // Routes from clients are reflected to all internal peers
+    assert_eq!(
+        export_route(&non_client, &config, &key, &route, from_client),
+        Some(reflected.clone())
+    );
+    assert_eq!(
+        export_route(&client, &config, &key, &route, from_client),
+        Some(reflected)
+    );
+
+    // Existing ORIGINATOR_ID is kept and the cluster ID is prepended
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            originator_attr(Ipv4Addr::new(192, 168, 0, 4)),
+            cluster_list_attr(vec![Ipv4Addr::new(192, 168, 100, 2)]),
+        ],
+        None,
+    );
+    let client = client.with_cluster_id(Some(CLUSTER_ID));
+    assert_eq!(
+        export_route(&client, &config, &key, &route, from_client),
+        Some(Route::new(
+            vec![
+                origin_attr(),
+                as_path_attr(vec![]),
+                next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+                local_pref,
+                originator_attr(Ipv4Addr::new(192, 168, 0, 4)),
+                cluster_list_attr(vec![CLUSTER_ID, Ipv4Addr::new(192, 168, 100, 2)]),
+            ],
+            None,
+        ))
+    );
+
+    // Reflection attributes are not advertised to external peers
+    assert_eq!(
+        export_route(&PROPERTIES, &config, &key, &route, from_client),
+        Some(Route::new(
+            vec![
+                origin_attr(),
+                as_path_attr(vec![MY_AS as u16]),
+                next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            ],
+            None,
+        ))
+    );
+}
+
+#[test]
+fn test_export_route_server() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default()
+        .with_as_prepend(2)
+        .with_next_hop_self_ipv4(Some(Ipv4Addr::new(192, 0, 2, 100)));
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![300]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            med_attr(10),
+        ],
+        None,
+    );
+    let properties = PROPERTIES.with_client_role(ClientRole::RouteServerClient);
+    assert_eq!(
+        export_route(&properties, &config, &key, &route, RouteSource::External),
+        Some(route.clone())
+    );
+
+    // Route server clients are only meaningful for external peers
+    let exported = export_route(
+        &ibgp_properties(ClientRole::RouteServerClient),
+        &config,
+        &key,
+        &route,
+        RouteSource::External,
+    )
+    .unwrap();
+    assert!(exported
+        .attributes()
+        .contains(&next_hop_attr(Ipv4Addr::new(192, 0, 2, 100))));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_ingress_reflection_loop() -> Result<(), FsmStateError<SocketAddr>> {
+    let looped = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![300]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            originator_attr(OTHER_BGP_ID),
+            cluster_list_attr(vec![CLUSTER_ID]),
+        ],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let reflected = BgpUpdateMessage::new(
+        vec![],
+        vec![
+            origin_attr(),
+            as_path_attr(vec![300]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            originator_attr(OTHER_BGP_ID),
+            cluster_list_attr(vec![Ipv4Addr::new(192, 168, 100, 2)]),
+        ],
+        vec![ipv4_unicast("10.0.1.0/24")],
+    );
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(looped.clone()))
+        .read(BgpMessage::Update(reflected.clone()));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let properties = ibgp_properties(ClientRole::NonClient).with_cluster_id(Some(CLUSTER_ID));
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(looped, UpdateTreatment::TreatAsWithdraw)
+    );
+    assert!(peer.adj_rib_in().is_empty());
+
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(reflected, UpdateTreatment::Normal)
+    );
+    assert_eq!(peer.adj_rib_in().len(AddressType::Ipv4Unicast), 1);
+    Ok(())
+}
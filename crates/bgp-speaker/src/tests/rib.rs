@@ -0,0 +1,320 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+
+use netgauze_bgp_pkt::{
+    nlri::{Ipv4Unicast, Ipv4UnicastAddress, Ipv6Unicast, Ipv6UnicastAddress},
+    path_attribute::{
+        As2PathSegment, AsPath, AsPathSegmentType, MpReach, MpUnreach, NextHop, Origin,
+        PathAttribute, PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+use netgauze_iana::address_family::AddressType;
+use netgauze_parse_utils::WritablePdu;
+
+use crate::rib::*;
+
+pub(crate) fn origin_attr() -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::Origin(Origin::IGP),
+    )
+    .unwrap()
+}
+
+pub(crate) fn as_path_attr(as_numbers: Vec<u16>) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(vec![As2PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            as_numbers,
+        )])),
+    )
+    .unwrap()
+}
+
+pub(crate) fn next_hop_attr(next_hop: Ipv4Addr) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::NextHop(NextHop::new(next_hop)),
+    )
+    .unwrap()
+}
+
+pub(crate) fn ipv4_unicast(prefix: &str) -> Ipv4UnicastAddress {
+    Ipv4UnicastAddress::new_no_path_id(
+        Ipv4Unicast::from_net(Ipv4Net::from_str(prefix).unwrap()).unwrap(),
+    )
+}
+
+fn ipv6_unicast(prefix: &str) -> Ipv6UnicastAddress {
+    Ipv6UnicastAddress::new(
+        None,
+        Ipv6Unicast::from_net(Ipv6Net::from_str(prefix).unwrap()).unwrap(),
+    )
+}
+
+pub(crate) fn ipv4_key(prefix: &str) -> RouteKey {
+    RouteKey::new(
+        AddressType::Ipv4Unicast,
+        None,
+        IpNet::from_str(prefix).unwrap(),
+    )
+}
+
+#[test]
+fn test_adj_rib_ipv4_announce_withdraw() {
+    let mut rib = AdjRib::new();
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 2))],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    rib.apply(UpdateRoutes::from(&update));
+    assert_eq!(rib.len(AddressType::Ipv4Unicast), 2);
+    assert!(rib.get(&ipv4_key("10.0.0.0/24")).is_some());
+    assert_eq!(
+        rib.updates(AddressType::Ipv4Unicast, BGP_MAX_MESSAGE_LENGTH)
+            .len(),
+        1
+    );
+
+    let withdraw = BgpUpdateMessage::new(vec![ipv4_unicast("10.0.0.0/24")], vec![], vec![]);
+    rib.apply(UpdateRoutes::from(&withdraw));
+    assert_eq!(rib.len(AddressType::Ipv4Unicast), 1);
+    assert!(rib.get(&ipv4_key("10.0.0.0/24")).is_none());
+    assert_eq!(
+        rib.updates(AddressType::Ipv4Unicast, BGP_MAX_MESSAGE_LENGTH),
+        vec![BgpUpdateMessage::new(
+            vec![],
+            vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 2))],
+            vec![ipv4_unicast("10.0.1.0/24")],
+        )]
+    );
+}
+
+#[test]
+fn test_updates_grouped_by_path() {
+    let mut rib = AdjRib::new();
+    for (next_hop, prefixes) in [
+        (
+            Ipv4Addr::new(192, 168, 0, 2),
+            ["10.0.0.0/24", "10.0.2.0/24"],
+        ),
+        (
+            Ipv4Addr::new(192, 168, 0, 3),
+            ["10.0.1.0/24", "10.0.3.0/24"],
+        ),
+    ] {
+        rib.apply(UpdateRoutes::from(&BgpUpdateMessage::new(
+            vec![],
+            vec![origin_attr(), next_hop_attr(next_hop)],
+            prefixes.into_iter().map(ipv4_unicast).collect(),
+        )));
+    }
+    let mut updates = rib.updates(AddressType::Ipv4Unicast, BGP_MAX_MESSAGE_LENGTH);
+    updates.sort_by_key(|update| update.nlri().first().map(|nlri| nlri.network().address()));
+    assert_eq!(
+        updates,
+        vec![
+            BgpUpdateMessage::new(
+                vec![],
+                vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 2))],
+                vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.2.0/24")],
+            ),
+            BgpUpdateMessage::new(
+                vec![],
+                vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 3))],
+                vec![ipv4_unicast("10.0.1.0/24"), ipv4_unicast("10.0.3.0/24")],
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_adj_rib_ipv6_mp_reach() {
+    let next_hop_global = Ipv6Addr::from_str("2001:db8::1").unwrap();
+    let next_hop_local = Some(Ipv6Addr::from_str("fe80::1").unwrap());
+    let mp_reach = PathAttribute::from(
+        true,
+        false,
+        false,
+        true,
+        PathAttributeValue::MpReach(MpReach::Ipv6Unicast {
+            next_hop_global,
+            next_hop_local,
+            nlri: vec![ipv6_unicast("2001:db8:1::/48")],
+        }),
+    )
+    .unwrap();
+    let update = BgpUpdateMessage::new(vec![], vec![origin_attr(), mp_reach], vec![]);
+    let mut rib = AdjRib::new();
+    rib.apply(UpdateRoutes::from(&update));
+
+    let key = RouteKey::new(
+        AddressType::Ipv6Unicast,
+        None,
+        IpNet::from_str("2001:db8:1::/48").unwrap(),
+    );
+    let route = rib.get(&key).unwrap();
+    assert_eq!(route.attributes(), &vec![origin_attr()]);
+    assert_eq!(
+        route.mp_next_hop(),
+        Some(MpNextHop::new(IpAddr::V6(next_hop_global), next_hop_local))
+    );
+    assert_eq!(
+        rib.updates(AddressType::Ipv6Unicast, BGP_MAX_MESSAGE_LENGTH),
+        vec![update]
+    );
+    assert!(rib
+        .updates(AddressType::Ipv4Unicast, BGP_MAX_MESSAGE_LENGTH)
+        .is_empty());
+
+    let mp_unreach = PathAttribute::from(
+        true,
+        false,
+        false,
+        true,
+        PathAttributeValue::MpUnreach(MpUnreach::Ipv6Unicast {
+            nlri: vec![ipv6_unicast("2001:db8:1::/48")],
+        }),
+    )
+    .unwrap();
+    let withdraw = BgpUpdateMessage::new(vec![], vec![mp_unreach], vec![]);
+    assert_eq!(
+        withdraw_updates(AddressType::Ipv6Unicast, &[key], BGP_MAX_MESSAGE_LENGTH),
+        vec![withdraw.clone()]
+    );
+    rib.apply(UpdateRoutes::from(&withdraw));
+    assert!(rib.is_empty());
+}
+
+#[test]
+fn test_adj_rib_stale_sweep() {
+    let mut rib = AdjRib::new();
+    let update = BgpUpdateMessage::new(
+        vec![],
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 2))],
+        vec![ipv4_unicast("10.0.0.0/24"), ipv4_unicast("10.0.1.0/24")],
+    );
+    rib.apply(UpdateRoutes::from(&update));
+    rib.mark_stale(AddressType::Ipv4Unicast);
+    assert!(rib.get(&ipv4_key("10.0.0.0/24")).unwrap().is_stale());
+
+    let refresh = BgpUpdateMessage::new(
+        vec![],
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 168, 0, 2))],
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    rib.apply(UpdateRoutes::from(&refresh));
+    assert!(!rib.get(&ipv4_key("10.0.0.0/24")).unwrap().is_stale());
+
+    let swept = rib.sweep_stale(AddressType::Ipv4Unicast);
+    assert_eq!(swept, vec![ipv4_key("10.0.1.0/24")]);
+    assert_eq!(rib.len(AddressType::Ipv4Unicast), 1);
+    assert_eq!(
+        withdraw_updates(AddressType::Ipv4Unicast, &swept, BGP_MAX_MESSAGE_LENGTH),
+        vec![BgpUpdateMessage::new(
+            vec![ipv4_unicast("10.0.1.0/24")],
+            vec![],
+            vec![]
+        )]
+    );
+}
+
+fn message_length(update: &BgpUpdateMessage) -> usize {
+    BgpMessage::Update(update.clone()).len()
+}
+
+#[test]
+fn test_withdraw_updates_packed_by_length() {
+    let keys: Vec<RouteKey> = (0..2000u32)
+        .map(|i| {
+            let net = Ipv4Net::new(Ipv4Addr::from(0x0a00_0000 + (i << 8)), 24).unwrap();
+            RouteKey::new(AddressType::Ipv4Unicast, None, IpNet::V4(net))
+        })
+        .collect();
+
+    let updates = withdraw_updates(AddressType::Ipv4Unicast, &keys, BGP_MAX_MESSAGE_LENGTH);
+    // 19 octets of header, 4 octets of lengths and 4 octets per /24 prefix
+    assert_eq!(updates.len(), 2);
+    assert_eq!(message_length(&updates[0]), 19 + 4 + 4 * 1018);
+    assert!(updates
+        .iter()
+        .all(|update| message_length(update) <= BGP_MAX_MESSAGE_LENGTH as usize));
+    let withdrawn: Vec<RouteKey> = updates
+        .iter()
+        .flat_map(|update| UpdateRoutes::from(update).withdrawn().clone())
+        .collect();
+    assert_eq!(withdrawn, keys);
+
+    let updates = withdraw_updates(
+        AddressType::Ipv4Unicast,
+        &keys,
+        BGP_EXTENDED_MAX_MESSAGE_LENGTH,
+    );
+    assert_eq!(updates.len(), 1);
+}
+
+#[test]
+fn test_updates_packed_by_length() {
+    let next_hop_global = Ipv6Addr::from_str("2001:db8::1").unwrap();
+    let route = Route::new(
+        vec![origin_attr(), as_path_attr(vec![100; 100])],
+        Some(MpNextHop::new(IpAddr::V6(next_hop_global), None)),
+    );
+    let keys: Vec<RouteKey> = (0..1000u128)
+        .map(|i| {
+            let net = Ipv6Net::new(Ipv6Addr::from((0x2001_0db8 << 96) + (i << 64)), 64).unwrap();
+            RouteKey::new(AddressType::Ipv6Unicast, Some(1), IpNet::V6(net))
+        })
+        .collect();
+    let mut rib = AdjRib::new();
+    for key in &keys {
+        rib.insert(*key, route.clone());
+    }
+
+    let updates = rib.updates(AddressType::Ipv6Unicast, BGP_MAX_MESSAGE_LENGTH);
+    assert!(updates.len() > 1);
+    assert!(updates
+        .iter()
+        .all(|update| message_length(update) <= BGP_MAX_MESSAGE_LENGTH as usize));
+    let mut announced = AdjRib::new();
+    for update in &updates {
+        announced.apply(UpdateRoutes::from(update));
+    }
+    assert_eq!(announced.len(AddressType::Ipv6Unicast), keys.len());
+    assert!(keys.iter().all(|key| announced.get(key) == Some(&route)));
+
+    let updates = rib.updates(AddressType::Ipv6Unicast, BGP_EXTENDED_MAX_MESSAGE_LENGTH);
+    assert_eq!(updates.len(), 1);
+}
@@ -0,0 +1,316 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, BgpRoleCapability},
+    iana::BgpRoleValue,
+    notification::{BgpNotificationMessage, OpenMessageError},
+    open::{BgpOpenMessage, BgpOpenMessageParameter},
+    path_attribute::{OnlyToCustomer, PathAttribute, PathAttributeValue},
+    update::BgpUpdateMessage,
+    BgpMessage,
+};
+
+use crate::{
+    events::{BgpEvent, UpdateTreatment},
+    export::*,
+    fsm::{FsmState, FsmStateError},
+    peer::*,
+    rib::Route,
+    role::*,
+    tests::{
+        rib::{as_path_attr, ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+fn otc_attr(asn: u32) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        true,
+        false,
+        false,
+        PathAttributeValue::OnlyToCustomer(OnlyToCustomer::new(asn)),
+    )
+    .unwrap()
+}
+
+fn role_open(my_as: u16, bgp_id: Ipv4Addr, roles: Vec<BgpRoleValue>) -> BgpOpenMessage {
+    BgpOpenMessage::new(
+        my_as,
+        HOLD_TIME,
+        bgp_id,
+        roles
+            .into_iter()
+            .map(|role| {
+                BgpOpenMessageParameter::Capabilities(vec![BgpCapability::BgpRole(
+                    BgpRoleCapability::new(role),
+                )])
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn test_check_open_role() {
+    let pairs = [
+        (BgpRoleValue::Provider, BgpRoleValue::Customer),
+        (BgpRoleValue::Customer, BgpRoleValue::Provider),
+        (BgpRoleValue::RS, BgpRoleValue::RsClient),
+        (BgpRoleValue::RsClient, BgpRoleValue::RS),
+        (BgpRoleValue::Peer, BgpRoleValue::Peer),
+    ];
+    for (role, peer_role) in pairs {
+        let open = role_open(PEER_AS as u16, PEER_BGP_ID, vec![peer_role]);
+        assert_eq!(check_open_role(role, true, &open), Ok(()));
+    }
+
+    let open = role_open(PEER_AS as u16, PEER_BGP_ID, vec![BgpRoleValue::Customer]);
+    assert_eq!(
+        check_open_role(BgpRoleValue::Customer, false, &open),
+        Err(OpenMessageError::RoleMismatch {
+            value: vec![BgpRoleValue::Customer.into()]
+        })
+    );
+
+    // Multiple role capabilities are accepted only if they carry the same value
+    let open = role_open(
+        PEER_AS as u16,
+        PEER_BGP_ID,
+        vec![BgpRoleValue::Peer, BgpRoleValue::Peer],
+    );
+    assert_eq!(check_open_role(BgpRoleValue::Peer, false, &open), Ok(()));
+    let open = role_open(
+        PEER_AS as u16,
+        PEER_BGP_ID,
+        vec![BgpRoleValue::Peer, BgpRoleValue::Customer],
+    );
+    assert!(check_open_role(BgpRoleValue::Peer, false, &open).is_err());
+
+    // Missing role is only rejected in strict mode
+    let open = role_open(PEER_AS as u16, PEER_BGP_ID, vec![]);
+    assert_eq!(check_open_role(BgpRoleValue::Peer, false, &open), Ok(()));
+    assert_eq!(
+        check_open_role(BgpRoleValue::Peer, true, &open),
+        Err(OpenMessageError::RoleMismatch { value: vec![] })
+    );
+}
+
+#[test]
+fn test_otc_ingress() {
+    let peer_asn = PEER_AS;
+    // OTC is added to routes received from providers, peers and route servers
+    for role in [
+        BgpRoleValue::Customer,
+        BgpRoleValue::Peer,
+        BgpRoleValue::RsClient,
+    ] {
+        let mut attributes = vec![origin_attr()];
+        assert!(otc_ingress(role, peer_asn, &mut attributes));
+        assert_eq!(attributes, vec![origin_attr(), otc_attr(peer_asn)]);
+    }
+    // Routes received from customers and route server clients are leaks
+    for role in [BgpRoleValue::Provider, BgpRoleValue::RS] {
+        let mut attributes = vec![origin_attr(), otc_attr(300)];
+        assert!(!otc_ingress(role, peer_asn, &mut attributes));
+        let mut attributes = vec![origin_attr()];
+        assert!(otc_ingress(role, peer_asn, &mut attributes));
+        assert_eq!(attributes, vec![origin_attr()]);
+    }
+    // Routes received from peers must carry the peer's ASN
+    let mut attributes = vec![origin_attr(), otc_attr(300)];
+    assert!(!otc_ingress(BgpRoleValue::Peer, peer_asn, &mut attributes));
+    let mut attributes = vec![origin_attr(), otc_attr(peer_asn)];
+    assert!(otc_ingress(BgpRoleValue::Peer, peer_asn, &mut attributes));
+    // Existing OTC is kept for routes received from providers
+    let mut attributes = vec![origin_attr(), otc_attr(300)];
+    assert!(otc_ingress(
+        BgpRoleValue::Customer,
+        peer_asn,
+        &mut attributes
+    ));
+    assert_eq!(attributes, vec![origin_attr(), otc_attr(300)]);
+}
+
+#[test]
+fn test_otc_egress() {
+    // Routes with OTC are not sent to providers, peers and route servers
+    for role in [
+        BgpRoleValue::Customer,
+        BgpRoleValue::Peer,
+        BgpRoleValue::RsClient,
+    ] {
+        let mut attributes = vec![origin_attr(), otc_attr(300)];
+        assert!(!otc_egress(role, MY_AS, &mut attributes));
+    }
+    // OTC is added to routes sent to customers, peers and route server clients
+    for role in [BgpRoleValue::Provider, BgpRoleValue::Peer, BgpRoleValue::RS] {
+        let mut attributes = vec![origin_attr()];
+        assert!(otc_egress(role, MY_AS, &mut attributes));
+        assert_eq!(attributes, vec![origin_attr(), otc_attr(MY_AS)]);
+    }
+    let mut attributes = vec![origin_attr(), otc_attr(300)];
+    assert!(otc_egress(BgpRoleValue::Provider, MY_AS, &mut attributes));
+    assert_eq!(attributes, vec![origin_attr(), otc_attr(300)]);
+}
+
+#[test]
+fn test_export_otc() {
+    let key = ipv4_key("10.0.0.0/24");
+    let config = ExportConfig::default();
+    let route = Route::new(
+        vec![
+            origin_attr(),
+            as_path_attr(vec![300]),
+            next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+            otc_attr(300),
+        ],
+        None,
+    );
+    let to_provider = PROPERTIES.with_bgp_role(Some(BgpRoleValue::Customer));
+    let to_customer = PROPERTIES.with_bgp_role(Some(BgpRoleValue::Provider));
+    assert_eq!(
+        export_route(&to_provider, &config, &key, &route, RouteSource::External),
+        None
+    );
+    assert!(export_route(&to_customer, &config, &key, &route, RouteSource::External).is_some());
+
+    // BGP Role is ignored for internal peers
+    let internal = PeerProperties::new(MY_AS, MY_AS, MY_BGP_ID, PEER_ADDR, false)
+        .with_bgp_role(Some(BgpRoleValue::Customer));
+    assert_eq!(internal.bgp_role(), None);
+    assert!(export_route(&internal, &config, &key, &route, RouteSource::External).is_some());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_open_role_mismatch() -> Result<(), FsmStateError<SocketAddr>> {
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(role_open(
+            MY_AS as u16,
+            MY_BGP_ID,
+            vec![BgpRoleValue::Customer],
+        )))
+        .read(BgpMessage::Open(role_open(
+            PEER_AS as u16,
+            PEER_BGP_ID,
+            vec![BgpRoleValue::Customer],
+        )))
+        .write(BgpMessage::Notification(
+            BgpNotificationMessage::OpenMessageError(OpenMessageError::RoleMismatch {
+                value: vec![BgpRoleValue::Customer.into()],
+            }),
+        ));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let properties = PROPERTIES.with_bgp_role(Some(BgpRoleValue::Customer));
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    assert_eq!(peer.run().await?, BgpEvent::ManualStart);
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::TcpConnectionRequestAcked(PEER_ADDR)
+    );
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::BGPOpenMsgErr(OpenMessageError::RoleMismatch {
+            value: vec![BgpRoleValue::Customer.into()],
+        })
+    );
+    assert_eq!(peer.fsm_state(), FsmState::Idle);
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_otc_ingress_update() -> Result<(), FsmStateError<SocketAddr>> {
+    let attributes = vec![
+        origin_attr(),
+        as_path_attr(vec![PEER_AS as u16]),
+        next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+    ];
+    let update = BgpUpdateMessage::new(
+        vec![],
+        attributes.clone(),
+        vec![ipv4_unicast("10.0.0.0/24")],
+    );
+    let mut with_otc = attributes;
+    with_otc.push(otc_attr(PEER_AS));
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(role_open(
+            MY_AS as u16,
+            MY_BGP_ID,
+            vec![BgpRoleValue::Customer],
+        )))
+        .read(BgpMessage::Open(role_open(
+            PEER_AS as u16,
+            PEER_BGP_ID,
+            vec![BgpRoleValue::Provider],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(update));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let properties = PROPERTIES
+        .with_bgp_role(Some(BgpRoleValue::Customer))
+        .with_strict_bgp_role(true);
+    let mut peer = Peer::new(
+        PEER_KEY,
+        properties,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    for _ in 0..4 {
+        peer.run().await?;
+    }
+    assert_eq!(peer.fsm_state(), FsmState::Established);
+
+    // Routes received from a provider are marked with the provider's ASN
+    let event = peer.run().await?;
+    assert_eq!(
+        event,
+        BgpEvent::UpdateMsg(
+            BgpUpdateMessage::new(vec![], with_otc.clone(), vec![ipv4_unicast("10.0.0.0/24")]),
+            UpdateTreatment::Normal
+        )
+    );
+    assert_eq!(
+        peer.adj_rib_in()
+            .get(&ipv4_key("10.0.0.0/24"))
+            .map(|route| route.attributes()),
+        Some(&with_otc)
+    );
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use tokio::sync::watch;
+
+use netgauze_bgp_pkt::{
+    iana::OriginValidationState, open::BgpOpenMessage, update::BgpUpdateMessage, BgpMessage,
+};
+use netgauze_rpki::{
+    aspa::{AspaDirection, AspaRecord, AspaTable},
+    client::RpkiData,
+    rov::{origin_validation_state, set_origin_validation_state},
+    vrp::{Vrp, VrpTable},
+};
+
+use crate::{
+    events::{BgpEvent, UpdateTreatment},
+    fsm::{FsmState, FsmStateError},
+    peer::{Peer, PeerAdminEvents, PeerConfig},
+    rib::{RouteKey, UpdateRoutes},
+    rpki::*,
+    tests::{
+        rib::{as_path_attr, ipv4_key, ipv4_unicast, next_hop_attr, origin_attr},
+        *,
+    },
+};
+
+fn table() -> VrpTable {
+    VrpTable::from_iter([
+        Vrp::new(IpNet::from_str("192.0.2.0/24").unwrap(), 24, PEER_AS),
+        Vrp::new(IpNet::from_str("198.51.100.0/24").unwrap(), 24, 300),
+    ])
+}
+
+fn update(prefixes: &[&str], state: Option<OriginValidationState>) -> BgpUpdateMessage {
+    update_with_path(prefixes, state, vec![PEER_AS as u16])
+}
+
+fn update_with_path(
+    prefixes: &[&str],
+    state: Option<OriginValidationState>,
+    as_path: Vec<u16>,
+) -> BgpUpdateMessage {
+    let mut attributes = vec![
+        origin_attr(),
+        as_path_attr(as_path),
+        next_hop_attr(Ipv4Addr::new(192, 0, 2, 1)),
+    ];
+    set_origin_validation_state(&mut attributes, state);
+    BgpUpdateMessage::new(
+        vec![],
+        attributes,
+        prefixes.iter().map(|prefix| ipv4_unicast(prefix)).collect(),
+    )
+}
+
+/// The announced and withdrawn routes of each update, along with the
+/// signaled validation state
+fn summary(
+    updates: &[BgpUpdateMessage],
+) -> Vec<(Vec<RouteKey>, Vec<RouteKey>, Option<OriginValidationState>)> {
+    updates
+        .iter()
+        .map(|update| {
+            let routes = UpdateRoutes::from(update);
+            (
+                routes.announced().iter().map(|(key, _)| *key).collect(),
+                routes.withdrawn().clone(),
+                origin_validation_state(update.path_attributes()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_validate_update_single_state() {
+    let validator = RouteOriginValidator::from_table(table());
+    let received = update(&["192.0.2.0/24"], None);
+    assert_eq!(
+        validator.validate_update(received.clone(), MY_AS, false, None, None),
+        vec![received.clone()]
+    );
+
+    let validator = validator.with_validation_state_community(true);
+    assert_eq!(
+        validator.validate_update(received, MY_AS, false, None, None),
+        vec![update(
+            &["192.0.2.0/24"],
+            Some(OriginValidationState::Valid)
+        )]
+    );
+}
+
+#[test]
+fn test_validate_update_split() {
+    let validator = RouteOriginValidator::from_table(table()).with_validation_state_community(true);
+    let received = update(&["192.0.2.0/24", "198.51.100.0/24", "203.0.113.0/24"], None);
+    assert_eq!(
+        summary(&validator.validate_update(received.clone(), MY_AS, false, None, None)),
+        vec![
+            (
+                vec![ipv4_key("192.0.2.0/24")],
+                vec![],
+                Some(OriginValidationState::Valid)
+            ),
+            (
+                vec![ipv4_key("198.51.100.0/24")],
+                vec![],
+                Some(OriginValidationState::Invalid)
+            ),
+            (
+                vec![ipv4_key("203.0.113.0/24")],
+                vec![],
+                Some(OriginValidationState::NotFound)
+            ),
+        ]
+    );
+
+    let validator = validator.with_reject_invalid(true);
+    assert_eq!(
+        summary(&validator.validate_update(received, MY_AS, false, None, None)),
+        vec![
+            (vec![], vec![ipv4_key("198.51.100.0/24")], None),
+            (
+                vec![ipv4_key("192.0.2.0/24")],
+                vec![],
+                Some(OriginValidationState::Valid)
+            ),
+            (
+                vec![ipv4_key("203.0.113.0/24")],
+                vec![],
+                Some(OriginValidationState::NotFound)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_update_signaled_state() {
+    let validator = RouteOriginValidator::from_table(table());
+    let received = update(&["192.0.2.0/24"], Some(OriginValidationState::Invalid));
+
+    // The state signaled by an external peer is removed
+    assert_eq!(
+        validator.validate_update(received.clone(), MY_AS, false, None, None),
+        vec![update(&["192.0.2.0/24"], None)]
+    );
+    // and trusted from an internal peer
+    assert_eq!(
+        validator.validate_update(received.clone(), MY_AS, true, None, None),
+        vec![received.clone()]
+    );
+    let validator = validator.with_reject_invalid(true);
+    assert_eq!(
+        summary(&validator.validate_update(received, MY_AS, true, None, None)),
+        vec![(vec![], vec![ipv4_key("192.0.2.0/24")], None)]
+    );
+}
+
+#[test]
+fn test_validate_update_aspa() {
+    // The origin 300 only authorizes 400 as its provider
+    let (_tx, rx) = watch::channel(Arc::new(RpkiData::from(AspaTable::from_iter([
+        AspaRecord::new(300, vec![400]),
+    ]))));
+    let validator = RouteOriginValidator::new(rx);
+    let leaked = update_with_path(&["192.0.2.0/24"], None, vec![PEER_AS as u16, 300]);
+    let upstream = Some(AspaDirection::Upstream);
+    assert_eq!(
+        validator.validate_update(leaked.clone(), MY_AS, false, upstream, Some(PEER_AS)),
+        vec![leaked.clone()]
+    );
+
+    let validator = validator.with_aspa_verification(true);
+    assert_eq!(
+        summary(&validator.validate_update(leaked.clone(), MY_AS, false, upstream, Some(PEER_AS))),
+        vec![(vec![], vec![ipv4_key("192.0.2.0/24")], None)]
+    );
+    // Not verified without the peer's role
+    assert_eq!(
+        validator.validate_update(leaked, MY_AS, false, None, Some(PEER_AS)),
+        vec![update_with_path(
+            &["192.0.2.0/24"],
+            None,
+            vec![PEER_AS as u16, 300]
+        )]
+    );
+
+    let valid = update_with_path(&["192.0.2.0/24"], None, vec![400, 300]);
+    assert_eq!(
+        validator.validate_update(valid.clone(), MY_AS, false, upstream, Some(400)),
+        vec![valid]
+    );
+}
+
+#[tokio::test]
+async fn test_validator_changed() {
+    let (tx, rx) = watch::channel(Arc::new(RpkiData::default()));
+    let mut validator = RouteOriginValidator::new(rx).with_validation_state_community(true);
+    let received = update(&["192.0.2.0/24"], None);
+    assert_eq!(
+        validator.validate_update(received.clone(), MY_AS, false, None, None),
+        vec![update(
+            &["192.0.2.0/24"],
+            Some(OriginValidationState::NotFound)
+        )]
+    );
+
+    tx.send(Arc::new(RpkiData::from(table()))).unwrap();
+    tokio::time::timeout(std::time::Duration::from_secs(1), validator.changed())
+        .await
+        .unwrap();
+    assert_eq!(
+        validator.validate_update(received, MY_AS, false, None, None),
+        vec![update(
+            &["192.0.2.0/24"],
+            Some(OriginValidationState::Valid)
+        )]
+    );
+}
+
+#[test_log::test(tokio::test(start_paused = true))]
+async fn test_peer_revalidate_routes() -> Result<(), FsmStateError<SocketAddr>> {
+    let received = update(&["198.51.100.0/24"], None);
+    let withdrawn = BgpUpdateMessage::new(vec![ipv4_unicast("198.51.100.0/24")], vec![], vec![]);
+    // No route refresh is sent to the peer when the data changes
+    let mut io_builder = BgpIoMockBuilder::new();
+    io_builder
+        .write(BgpMessage::Open(BgpOpenMessage::new(
+            MY_AS as u16,
+            HOLD_TIME,
+            MY_BGP_ID,
+            vec![],
+        )))
+        .read(BgpMessage::Open(BgpOpenMessage::new(
+            PEER_AS as u16,
+            HOLD_TIME,
+            PEER_BGP_ID,
+            vec![],
+        )))
+        .write(BgpMessage::KeepAlive)
+        .read(BgpMessage::KeepAlive)
+        .read(BgpMessage::Update(received.clone()))
+        .wait(Duration::from_secs(30));
+    let active_connect = MockActiveConnect {
+        peer_addr: PEER_ADDR,
+        io_builder,
+        connect_delay: Duration::from_secs(0),
+    };
+    let (tx, rx) = watch::channel(Arc::new(RpkiData::from(table())));
+    let mut peer = Peer::new(
+        PEER_KEY,
+        PROPERTIES,
+        PeerConfig::default(),
+        POLICY,
+        active_connect,
+    );
+    peer.set_route_origin_validator(Some(
+        RouteOriginValidator::new(rx).with_reject_invalid(true),
+    ));
+    peer.add_admin_event(PeerAdminEvents::ManualStart);
+    while peer.fsm_state() != FsmState::Established {
+        peer.run().await?;
+    }
+    // The route is originated by the wrong AS
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::UpdateMsg(withdrawn.clone(), UpdateTreatment::Normal)
+    );
+    assert!(peer.adj_rib_in().is_empty());
+
+    let authorized = VrpTable::from_iter([Vrp::new(
+        IpNet::from_str("198.51.100.0/24").unwrap(),
+        24,
+        PEER_AS,
+    )]);
+    tx.send(Arc::new(RpkiData::from(authorized))).unwrap();
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RouteOriginValidationDataChanged
+    );
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RevalidatedRoutesChanged(received.clone())
+    );
+    assert!(peer
+        .adj_rib_in()
+        .get(&ipv4_key("198.51.100.0/24"))
+        .is_some());
+
+    tx.send(Arc::new(RpkiData::from(table()))).unwrap();
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RouteOriginValidationDataChanged
+    );
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RevalidatedRoutesChanged(withdrawn)
+    );
+    assert!(peer.adj_rib_in().is_empty());
+
+    // Removing the validator restores the routes as received
+    peer.set_route_origin_validator(None);
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RouteOriginValidationDataChanged
+    );
+    assert_eq!(
+        peer.run().await?,
+        BgpEvent::RevalidatedRoutesChanged(received)
+    );
+    assert!(peer
+        .adj_rib_in()
+        .get(&ipv4_key("198.51.100.0/24"))
+        .is_some());
+    Ok(())
+}
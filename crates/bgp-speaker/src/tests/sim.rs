@@ -0,0 +1,325 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    fsm::FsmState,
+    nexthop::StaticNextHopResolver,
+    peer::PeerConfigBuilder,
+    rib::Route,
+    sim::*,
+    tests::rib::{ipv4_key, next_hop_attr, origin_attr},
+};
+
+const IP_A: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+const IP_B: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+const IP_C: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+
+/// A - B - C chain of external sessions
+fn chain() -> SimTopology {
+    SimTopologyBuilder::new()
+        .speaker(IP_A, 65001, Ipv4Addr::new(1, 1, 1, 1))
+        .speaker(IP_B, 65002, Ipv4Addr::new(2, 2, 2, 2))
+        .speaker(IP_C, 65003, Ipv4Addr::new(3, 3, 3, 3))
+        .link(SimLink::new(IP_A, IP_B))
+        .link(SimLink::new(IP_B, IP_C))
+        .build()
+        .unwrap()
+}
+
+fn local_route() -> Route {
+    route_via(Ipv4Addr::new(10, 0, 0, 1))
+}
+
+fn route_via(next_hop: Ipv4Addr) -> Route {
+    Route::new(vec![origin_attr(), next_hop_attr(next_hop)], None)
+}
+
+#[tokio::test]
+async fn test_sim_network() -> io::Result<()> {
+    let network = SimNetwork::new();
+    let addr = SocketAddr::new(IP_B, SIM_BGP_PORT);
+    let mut listener = network.bind(addr)?;
+    assert_eq!(
+        network.bind(addr).map(|_| ()).map_err(|err| err.kind()),
+        Err(io::ErrorKind::AddrInUse)
+    );
+    assert_eq!(
+        network
+            .connect(IP_A, SocketAddr::new(IP_C, SIM_BGP_PORT))
+            .map(|_| ())
+            .map_err(|err| err.kind()),
+        Err(io::ErrorKind::ConnectionRefused)
+    );
+
+    let mut client = network.connect(IP_A, addr)?;
+    let (mut server, peer_addr) = listener.accept().await?;
+    assert_eq!(peer_addr, client.local_addr());
+    assert_eq!(server.peer_addr(), client.local_addr());
+    assert_eq!(client.peer_addr(), addr);
+    client.write_all(b"hello").await?;
+    let mut buf = [0u8; 5];
+    server.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"hello");
+
+    // The traffic is dropped while the link is down
+    network.set_link_up(IP_B, IP_A, false);
+    assert!(!network.is_link_up(IP_A, IP_B));
+    client.write_all(b"lost").await?;
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), server.read(&mut buf))
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        network
+            .connect(IP_A, addr)
+            .map(|_| ())
+            .map_err(|err| err.kind()),
+        Err(io::ErrorKind::TimedOut)
+    );
+
+    network.set_link_up(IP_A, IP_B, true);
+    server.write_all(b"again").await?;
+    client.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"again");
+
+    // A dropped listener refuses the connections
+    drop(listener);
+    assert_eq!(
+        network
+            .connect(IP_A, addr)
+            .map(|_| ())
+            .map_err(|err| err.kind()),
+        Err(io::ErrorKind::ConnectionRefused)
+    );
+    assert!(network.bind(addr).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_sim_topology_errors() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let _guard = runtime.enter();
+    let bgp_id = Ipv4Addr::new(1, 1, 1, 1);
+    assert_eq!(
+        SimTopologyBuilder::new()
+            .speaker(IP_A, 65001, bgp_id)
+            .speaker(IP_A, 65002, bgp_id)
+            .build()
+            .map(|_| ()),
+        Err(SimTopologyError::DuplicateSpeaker(IP_A))
+    );
+    assert_eq!(
+        SimTopologyBuilder::new()
+            .speaker(IP_A, 65001, bgp_id)
+            .link(SimLink::new(IP_A, IP_B))
+            .build()
+            .map(|_| ()),
+        Err(SimTopologyError::UnknownSpeaker(IP_B))
+    );
+    assert_eq!(
+        SimTopologyBuilder::new()
+            .speaker(IP_A, 65001, bgp_id)
+            .speaker(IP_B, 65002, bgp_id)
+            .link(SimLink::new(IP_A, IP_B))
+            .link(SimLink::new(IP_B, IP_A))
+            .build()
+            .map(|_| ()),
+        Err(SimTopologyError::DuplicateLink(IP_B, IP_A))
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sim_route_propagation() {
+    let clock = SimClock::start();
+    let topology = chain();
+    let (a, c) = (
+        topology.speaker(IP_A).unwrap(),
+        topology.speaker(IP_C).unwrap(),
+    );
+    topology.start();
+    let key = ipv4_key("192.0.2.0/24");
+    a.announce_routes(vec![(key, local_route())]);
+
+    let state = c.wait_until(|state| state.best_route(&key).is_some()).await;
+    let best = state.best_route(&key).unwrap();
+    assert_eq!(best.peer_ip(), Some(IP_B));
+    assert_eq!(best.as_path_len(), 2);
+    // The route sent back to A is dropped by the AS_PATH loop check
+    let state = a.state();
+    assert_eq!(state.best_route(&key).unwrap().peer_ip(), None);
+    assert!(clock.elapsed() < Duration::from_secs(1));
+
+    a.withdraw_routes(vec![key]);
+    c.wait_until(|state| state.best_route(&key).is_none()).await;
+    topology
+        .speaker(IP_B)
+        .unwrap()
+        .wait_until(|state| state.best_routes().is_empty())
+        .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sim_collision() {
+    let clock = SimClock::start();
+    let topology = SimTopologyBuilder::new()
+        .speaker(IP_A, 65001, Ipv4Addr::new(1, 1, 1, 1))
+        .speaker(IP_B, 65001, Ipv4Addr::new(2, 2, 2, 2))
+        .link(SimLink::new(IP_A, IP_B))
+        .build()
+        .unwrap();
+    let (a, b) = (
+        topology.speaker(IP_A).unwrap(),
+        topology.speaker(IP_B).unwrap(),
+    );
+    // Both ends connect at the same time
+    topology.start();
+    a.wait_until(|state| state.is_established(IP_B)).await;
+    b.wait_until(|state| state.is_established(IP_A)).await;
+
+    // Keepalives keep the session up across many hold times
+    clock.run_for(Duration::from_secs(3600)).await;
+    assert!(a.state().is_established(IP_B));
+    assert!(b.state().is_established(IP_A));
+    for (speaker, peer_ip) in [(a, IP_B), (b, IP_A)] {
+        let stats = speaker
+            .peer_handle(peer_ip)
+            .unwrap()
+            .peer_stats()
+            .await
+            .unwrap();
+        assert_eq!(stats.established_transitions(), 1);
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sim_link_failure() {
+    let clock = SimClock::start();
+    // Peers are restarted after a session failure only when damping is enabled
+    let config = || {
+        PeerConfigBuilder::new()
+            .damp_peer_oscillations(true)
+            .idle_hold_duration(5)
+            .build()
+    };
+    let topology = SimTopologyBuilder::new()
+        .speaker(IP_A, 65001, Ipv4Addr::new(1, 1, 1, 1))
+        .speaker(IP_B, 65002, Ipv4Addr::new(2, 2, 2, 2))
+        .link(
+            SimLink::new(IP_A, IP_B)
+                .with_a_config(config())
+                .with_b_config(config()),
+        )
+        .build()
+        .unwrap();
+    let (a, b) = (
+        topology.speaker(IP_A).unwrap(),
+        topology.speaker(IP_B).unwrap(),
+    );
+    topology.start();
+    let key = ipv4_key("192.0.2.0/24");
+    a.announce_routes(vec![(key, local_route())]);
+    b.wait_until(|state| state.best_route(&key).is_some()).await;
+
+    // The hold timer expires after the link goes down
+    topology.network().set_link_up(IP_A, IP_B, false);
+    let down = clock.elapsed();
+    let state = b
+        .wait_until(|state| state.session_state(IP_A) != FsmState::Established)
+        .await;
+    assert!(state.best_route(&key).is_none());
+    assert!(clock.elapsed() - down >= Duration::from_secs(170));
+
+    // The session is established again once the link is restored
+    topology.network().set_link_up(IP_A, IP_B, true);
+    b.wait_until(|state| state.best_route(&key).is_some()).await;
+    assert!(a.state().is_established(IP_B));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sim_next_hop_tracking() {
+    let _clock = SimClock::start();
+    let ip_d = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4));
+    let resolver = Arc::new(
+        StaticNextHopResolver::new()
+            .with_route("198.51.100.0/24".parse().unwrap(), 20)
+            .with_route("203.0.113.0/24".parse().unwrap(), 10),
+    );
+    // C learns the prefix from B and D over paths of the same length
+    let topology = SimTopologyBuilder::new()
+        .speaker(IP_B, 65002, Ipv4Addr::new(2, 2, 2, 2))
+        .speaker(IP_C, 65003, Ipv4Addr::new(3, 3, 3, 3))
+        .speaker(ip_d, 65004, Ipv4Addr::new(4, 4, 4, 4))
+        .link(SimLink::new(IP_B, IP_C))
+        .link(SimLink::new(ip_d, IP_C))
+        .next_hop_resolver(IP_C, resolver.clone())
+        .build()
+        .unwrap();
+    let c = topology.speaker(IP_C).unwrap();
+    topology.start();
+    let key = ipv4_key("192.0.2.0/24");
+    topology
+        .speaker(IP_B)
+        .unwrap()
+        .announce_routes(vec![(key, route_via(Ipv4Addr::new(198, 51, 100, 1)))]);
+    topology
+        .speaker(ip_d)
+        .unwrap()
+        .announce_routes(vec![(key, route_via(Ipv4Addr::new(203, 0, 113, 1)))]);
+    let best_via = |peer_ip| {
+        move |state: &SimSpeakerState| {
+            state
+                .best_route(&key)
+                .is_some_and(|best| best.peer_ip() == Some(peer_ip))
+        }
+    };
+
+    // Lowest IGP metric to the next hop
+    let state = c
+        .wait_until(|state| {
+            state
+                .received_routes(IP_B)
+                .is_some_and(|routes| routes.contains_key(&key))
+                && best_via(ip_d)(state)
+        })
+        .await;
+    assert_eq!(state.best_route(&key).unwrap().igp_metric(), Some(10));
+
+    // The selection is run again when the metric changes
+    resolver.set_route("203.0.113.0/24".parse().unwrap(), 30);
+    let state = c.wait_until(best_via(IP_B)).await;
+    assert_eq!(state.best_route(&key).unwrap().igp_metric(), Some(20));
+
+    // Routes with an unreachable next hop aren't selected
+    resolver.remove_route(&"198.51.100.0/24".parse().unwrap());
+    let state = c.wait_until(best_via(ip_d)).await;
+    assert_eq!(state.best_route(&key).unwrap().igp_metric(), Some(30));
+    resolver.remove_route(&"203.0.113.0/24".parse().unwrap());
+    c.wait_until(|state| state.best_route(&key).is_none()).await;
+
+    resolver.set_route("198.51.100.0/24".parse().unwrap(), 5);
+    c.wait_until(best_via(IP_B)).await;
+}
@@ -0,0 +1,150 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+use crate::{
+    connection::{ActiveConnect, TcpActiveConnect},
+    socket::*,
+};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+fn listen(socket_config: &TcpSocketConfig) -> io::Result<TcpListener> {
+    let local_addr = SocketAddr::new(LOCALHOST, 0);
+    let socket = TcpSocket::new_v4()?;
+    socket_config.configure_listen(&socket, local_addr)?;
+    enable_save_syn(&socket)?;
+    socket.bind(local_addr)?;
+    socket.listen(16)
+}
+
+async fn connect(
+    listener: &TcpListener,
+    socket_config: TcpSocketConfig,
+) -> io::Result<(TcpStream, TcpStream)> {
+    let mut active_connect = TcpActiveConnect::new().with_socket_config(socket_config);
+    let client = active_connect.connect(listener.local_addr()?).await?;
+    let (server, _) = listener.accept().await?;
+    Ok((client, server))
+}
+
+#[test]
+fn test_socket_config_ttl() {
+    let config = TcpSocketConfig::new().with_ttl(10);
+    assert_eq!(config.min_ttl(), None);
+    assert_eq!(config.effective_ttl(), Some(10));
+
+    let config = config.with_ttl_security(1);
+    assert_eq!(config.min_ttl(), Some(255));
+    assert_eq!(config.effective_ttl(), Some(GTSM_TTL));
+
+    let config = TcpSocketConfig::new().with_ttl_security(0);
+    assert_eq!(config.ttl_security_hops(), Some(1));
+    let config = TcpSocketConfig::new().with_ttl_security(2);
+    assert_eq!(config.min_ttl(), Some(254));
+    let config = TcpSocketConfig::new().with_ttl_security(255);
+    assert_eq!(config.min_ttl(), Some(1));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_active_connect_socket_options() -> io::Result<()> {
+    let listener = listen(&TcpSocketConfig::new())?;
+    let config = TcpSocketConfig::new()
+        .with_local_addr(LOCALHOST)
+        .with_ttl(20)
+        .with_dscp(DSCP_CS6);
+    let (client, server) = connect(&listener, config.clone()).await?;
+    assert_eq!(client.local_addr()?.ip(), LOCALHOST);
+    assert_eq!(client.ttl()?, 20);
+
+    config.configure_accepted(&server)?;
+    assert_eq!(server.ttl()?, 20);
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_gtsm() -> io::Result<()> {
+    // The SYN-ACK must be sent with TTL 255 as well, otherwise it's dropped
+    // by the client
+    let listener = listen(&TcpSocketConfig::new().with_ttl(GTSM_TTL))?;
+    let gtsm = TcpSocketConfig::new().with_ttl_security(1);
+
+    // Loopback packets are sent with the default TTL of 64
+    let (_client, server) = connect(&listener, TcpSocketConfig::new()).await?;
+    let ret = gtsm.configure_accepted(&server);
+    assert_eq!(
+        ret.map_err(|err| err.kind()),
+        Err(io::ErrorKind::PermissionDenied)
+    );
+
+    let (client, server) = connect(&listener, gtsm.clone()).await?;
+    assert_eq!(client.ttl()?, u32::from(GTSM_TTL));
+    gtsm.configure_accepted(&server)?;
+    assert_eq!(server.ttl()?, u32::from(GTSM_TTL));
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_accepted_local_addr_mismatch() -> io::Result<()> {
+    let listener = listen(&TcpSocketConfig::new())?;
+    let (_client, server) = connect(&listener, TcpSocketConfig::new()).await?;
+    let config = TcpSocketConfig::new().with_local_addr(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)));
+    let ret = config.configure_accepted(&server);
+    assert_eq!(
+        ret.map_err(|err| err.kind()),
+        Err(io::ErrorKind::PermissionDenied)
+    );
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invalid_socket_config() -> io::Result<()> {
+    let listener = listen(&TcpSocketConfig::new())?;
+    let invalid_dscp = TcpSocketConfig::new().with_dscp(DSCP_MAX + 1);
+    let ret = connect(&listener, invalid_dscp).await;
+    assert_eq!(
+        ret.map_err(|err| err.kind()).err(),
+        Some(io::ErrorKind::InvalidInput)
+    );
+
+    let invalid_local_addr =
+        TcpSocketConfig::new().with_local_addr(IpAddr::V6(Ipv6Addr::LOCALHOST));
+    let ret = connect(&listener, invalid_local_addr).await;
+    assert_eq!(
+        ret.map_err(|err| err.kind()).err(),
+        Some(io::ErrorKind::InvalidInput)
+    );
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_bind_device() -> io::Result<()> {
+    let listener = listen(&TcpSocketConfig::new())?;
+    let config = TcpSocketConfig::new().with_bind_device("lo".to_string());
+    match connect(&listener, config).await {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            log::warn!("Not allowed to bind to device, skipping test");
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
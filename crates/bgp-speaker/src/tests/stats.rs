@@ -0,0 +1,47 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::stats::{RateMeter, RATE_WINDOW};
+
+#[test]
+fn test_rate_meter() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new(start);
+    assert_eq!(meter.rate(start), 0.0);
+
+    meter.record(start, 30);
+    meter.record(start + Duration::from_millis(500), 30);
+    meter.record(start + Duration::from_secs(30), 60);
+    assert_eq!(meter.rate(start + Duration::from_secs(30)), 2.0);
+
+    // Events recorded in the first second fall out of the window
+    assert_eq!(meter.rate(start + RATE_WINDOW), 1.0);
+    assert_eq!(
+        meter.rate(start + RATE_WINDOW + Duration::from_secs(30)),
+        0.0
+    );
+}
+
+#[test]
+fn test_rate_meter_ignores_zero() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new(start);
+    meter.record(start, 0);
+    assert_eq!(meter.rate(start), 0.0);
+}
@@ -14,13 +14,29 @@
 // limitations under the License.
 
 use crate::{
+    auth::TcpAuth,
     connection::TcpActiveConnect,
-    peer::{EchoCapabilitiesPolicy, PeerConfig},
-    supervisor::{PeersSupervisor, PeersSupervisorError},
-    tests::{HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PROPERTIES},
+    export::ExportRoute,
+    listener::SessionKey,
+    peer::{EchoCapabilitiesPolicy, PeerConfig, PeerConfigBuilder, PeerProperties},
+    rib::Route,
+    supervisor::{PeerDefinition, PeersSupervisor, PeersSupervisorError},
+    tests::{
+        rib::{ipv4_key, next_hop_attr, origin_attr},
+        HOLD_TIME, MY_AS, MY_BGP_ID, PEER_ADDR, PEER_AS, PROPERTIES,
+    },
 };
 use netgauze_bgp_pkt::codec::BgpCodec;
-use std::net::SocketAddr;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+type TcpPeerDefinition = PeerDefinition<
+    SocketAddr,
+    TcpActiveConnect,
+    EchoCapabilitiesPolicy<SocketAddr, tokio::net::TcpStream, BgpCodec>,
+>;
 
 const TCP_STREAM_POLICY: EchoCapabilitiesPolicy<SocketAddr, tokio::net::TcpStream, BgpCodec> =
     EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, HOLD_TIME, Vec::new(), Vec::new());
@@ -33,14 +49,14 @@ async fn test_add_remove_peers() -> Result<(), PeersSupervisorError> {
         PEER_ADDR.ip(),
         PROPERTIES,
         PeerConfig::default(),
-        TcpActiveConnect,
+        TcpActiveConnect::new(),
         TCP_STREAM_POLICY,
     )?;
     let second_create = supervisor.create_peer(
         PEER_ADDR.ip(),
         PROPERTIES,
         PeerConfig::default(),
-        TcpActiveConnect,
+        TcpActiveConnect::new(),
         TCP_STREAM_POLICY,
     );
     let removed_peer = supervisor.remove_peer(&PEER_ADDR.ip());
@@ -56,12 +72,12 @@ async fn test_add_remove_peers() -> Result<(), PeersSupervisorError> {
 async fn test_dynamic_peers() -> Result<(), PeersSupervisorError> {
     let mut supervisor = PeersSupervisor::new(MY_AS, MY_BGP_ID);
     let (_rx, _peer_handle) =
-        supervisor.dynamic_peer(PEER_ADDR.ip(), PEER_ADDR, TcpActiveConnect)?;
+        supervisor.dynamic_peer(PEER_ADDR.ip(), PEER_ADDR, TcpActiveConnect::new())?;
     let second_create = supervisor.create_peer(
         PEER_ADDR.ip(),
         PROPERTIES,
         PeerConfig::default(),
-        TcpActiveConnect,
+        TcpActiveConnect::new(),
         TCP_STREAM_POLICY,
     );
 
@@ -72,3 +88,244 @@ async fn test_dynamic_peers() -> Result<(), PeersSupervisorError> {
     assert!(non_existing_peer.is_none());
     Ok(())
 }
+
+#[test_log::test(tokio::test)]
+async fn test_set_tcp_auth() {
+    let mut supervisor: PeersSupervisor<IpAddr, SocketAddr, tokio::net::TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let mut rx = supervisor.tcp_auth_keys().subscribe();
+    let auth = TcpAuth::Md5(b"secret".to_vec());
+    let session_key = SessionKey::new(PEER_ADDR.ip());
+
+    assert_eq!(
+        supervisor.set_tcp_auth(session_key, Some(auth.clone())),
+        None
+    );
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(rx.borrow_and_update().get(&session_key), Some(&auth));
+    assert_eq!(supervisor.set_tcp_auth(session_key, None), Some(auth));
+    assert!(rx.borrow_and_update().is_empty());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_update_peers() {
+    let mut supervisor: PeersSupervisor<IpAddr, SocketAddr, tokio::net::TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let second_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3)), 179);
+    let second_properties = PeerProperties::new(MY_AS, PEER_AS, MY_BGP_ID, second_addr, false);
+    let auth = TcpAuth::Md5(b"secret".to_vec());
+    let session_key = SessionKey::new(PEER_ADDR.ip());
+    let definition = |properties, config| {
+        PeerDefinition::new(
+            properties,
+            config,
+            TcpActiveConnect::new(),
+            TCP_STREAM_POLICY,
+        )
+    };
+
+    let update = supervisor.update_peers(HashMap::from([
+        (
+            PEER_ADDR.ip(),
+            definition(PROPERTIES, PeerConfig::default()).with_tcp_auth(session_key, auth.clone()),
+        ),
+        (
+            second_addr.ip(),
+            definition(second_properties, PeerConfig::default()),
+        ),
+    ]));
+    let mut added: Vec<IpAddr> = update.added().iter().map(|(key, _, _)| *key).collect();
+    added.sort();
+    assert_eq!(added, vec![PEER_ADDR.ip(), second_addr.ip()]);
+    assert!(update.removed().is_empty());
+    assert!(update.updated().is_empty());
+    assert_eq!(
+        supervisor.tcp_auth_keys().get(&session_key),
+        Some(auth.clone())
+    );
+
+    // Change the config of the first peer, and the properties of the second
+    let new_config = PeerConfigBuilder::new().connect_retry_duration(60).build();
+    let new_second_properties = PeerProperties::new(MY_AS, 300, MY_BGP_ID, second_addr, false);
+    let update = supervisor.update_peers(HashMap::from([
+        (
+            PEER_ADDR.ip(),
+            definition(PROPERTIES, new_config.clone()).with_tcp_auth(session_key, auth.clone()),
+        ),
+        (
+            second_addr.ip(),
+            definition(new_second_properties, PeerConfig::default()),
+        ),
+    ]));
+    assert_eq!(
+        update
+            .added()
+            .iter()
+            .map(|(key, _, _)| *key)
+            .collect::<Vec<_>>(),
+        vec![second_addr.ip()]
+    );
+    assert_eq!(update.removed(), &vec![second_addr.ip()]);
+    assert_eq!(update.updated(), &vec![PEER_ADDR.ip()]);
+
+    // Unchanged definitions are not reported as updated
+    let update = supervisor.update_peers(HashMap::from([(
+        PEER_ADDR.ip(),
+        definition(PROPERTIES, new_config.clone()).with_tcp_auth(session_key, auth.clone()),
+    )]));
+    assert!(update.added().is_empty());
+    assert_eq!(update.removed(), &vec![second_addr.ip()]);
+    assert!(update.updated().is_empty());
+    assert_eq!(supervisor.peer_keys(), vec![PEER_ADDR.ip()]);
+
+    // A changed policy is pushed to the peer
+    let new_policy =
+        EchoCapabilitiesPolicy::new(MY_AS, false, MY_BGP_ID, 90, Vec::new(), Vec::new());
+    let update = supervisor.update_peers(HashMap::from([(
+        PEER_ADDR.ip(),
+        PeerDefinition::new(
+            PROPERTIES,
+            new_config.clone(),
+            TcpActiveConnect::new(),
+            new_policy,
+        )
+        .with_tcp_auth(session_key, auth.clone()),
+    )]));
+    assert_eq!(update.updated(), &vec![PEER_ADDR.ip()]);
+
+    // Dynamic peers are not part of the peer set
+    let dynamic_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 179);
+    supervisor
+        .dynamic_peer(dynamic_addr.ip(), dynamic_addr, TcpActiveConnect::new())
+        .unwrap();
+    assert!(supervisor.is_dynamic_peer(&dynamic_addr.ip()));
+    let no_peers: HashMap<IpAddr, TcpPeerDefinition> = HashMap::new();
+    let update = supervisor.update_peers(no_peers);
+    assert_eq!(update.removed(), &vec![PEER_ADDR.ip()]);
+    assert_eq!(supervisor.peer_keys(), vec![dynamic_addr.ip()]);
+    assert_eq!(supervisor.tcp_auth_keys().get(&session_key), None);
+
+    // A configured peer replaces the dynamic peer
+    let update = supervisor.update_peers(HashMap::from([(
+        dynamic_addr.ip(),
+        definition(PROPERTIES, PeerConfig::default()),
+    )]));
+    assert_eq!(update.removed(), &vec![dynamic_addr.ip()]);
+    assert_eq!(update.added().len(), 1);
+    assert!(!supervisor.is_dynamic_peer(&dynamic_addr.ip()));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_add_peer_definition() -> Result<(), PeersSupervisorError> {
+    let mut supervisor: PeersSupervisor<IpAddr, SocketAddr, tokio::net::TcpStream> =
+        PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let second_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3)), 179);
+    let second_properties = PeerProperties::new(MY_AS, PEER_AS, MY_BGP_ID, second_addr, false);
+    let auth = TcpAuth::Md5(b"secret".to_vec());
+    let session_key = SessionKey::new(second_addr.ip());
+    let (_rx, _handle) = supervisor.create_peer(
+        PEER_ADDR.ip(),
+        PROPERTIES,
+        PeerConfig::default(),
+        TcpActiveConnect::new(),
+        TCP_STREAM_POLICY,
+    )?;
+
+    let definition = || {
+        PeerDefinition::new(
+            second_properties,
+            PeerConfig::default(),
+            TcpActiveConnect::new(),
+            TCP_STREAM_POLICY,
+        )
+        .with_tcp_auth(session_key, auth.clone())
+    };
+    let (_rx, _handle) = supervisor.add_peer(second_addr.ip(), definition())?;
+    let mut peer_keys = supervisor.peer_keys();
+    peer_keys.sort();
+    assert_eq!(peer_keys, vec![PEER_ADDR.ip(), second_addr.ip()]);
+    assert_eq!(
+        supervisor.tcp_auth_keys().get(&session_key),
+        Some(auth.clone())
+    );
+    assert_eq!(
+        supervisor.add_peer(second_addr.ip(), definition()).err(),
+        Some(PeersSupervisorError::PeerExists)
+    );
+
+    assert!(supervisor.remove_peer(&second_addr.ip()).is_some());
+    assert_eq!(supervisor.peer_keys(), vec![PEER_ADDR.ip()]);
+    assert_eq!(supervisor.tcp_auth_keys().get(&session_key), None);
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_announce_withdraw_routes() -> Result<(), PeersSupervisorError> {
+    let mut supervisor = PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let (_rx, _peer_handle) = supervisor.create_peer(
+        PEER_ADDR.ip(),
+        PROPERTIES,
+        PeerConfig::default(),
+        TcpActiveConnect::new(),
+        TCP_STREAM_POLICY,
+    )?;
+    let route = Route::new(
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 0, 2, 1))],
+        None,
+    );
+    let first = ExportRoute::local(ipv4_key("10.0.0.0/24"), route.clone());
+    let second = ExportRoute::local(ipv4_key("10.0.1.0/24"), route);
+    supervisor.announce_routes(vec![first.clone(), second.clone()]);
+    assert_eq!(supervisor.exported_routes().len(), 2);
+
+    supervisor.withdraw_routes(vec![ipv4_key("10.0.0.0/24"), ipv4_key("10.0.2.0/24")]);
+    assert_eq!(supervisor.exported_routes(), vec![second]);
+
+    // Peers created later start with the current routes
+    let (_rx, _peer_handle) = supervisor.create_peer(
+        IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3)),
+        PROPERTIES,
+        PeerConfig::default(),
+        TcpActiveConnect::new(),
+        TCP_STREAM_POLICY,
+    )?;
+    assert!(!supervisor.exported_routes().contains(&first));
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_announce_withdraw_client_routes() -> Result<(), PeersSupervisorError> {
+    let mut supervisor = PeersSupervisor::new(MY_AS, MY_BGP_ID);
+    let (_rx, _peer_handle) = supervisor.create_peer(
+        PEER_ADDR.ip(),
+        PROPERTIES,
+        PeerConfig::default(),
+        TcpActiveConnect::new(),
+        TCP_STREAM_POLICY,
+    )?;
+    let route = Route::new(
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 0, 2, 1))],
+        None,
+    );
+    let client_route = Route::new(
+        vec![origin_attr(), next_hop_attr(Ipv4Addr::new(192, 0, 2, 2))],
+        None,
+    );
+    let shared = ExportRoute::local(ipv4_key("10.0.0.0/24"), route);
+    let first = ExportRoute::local(ipv4_key("10.0.0.0/24"), client_route.clone());
+    let second = ExportRoute::local(ipv4_key("10.0.1.0/24"), client_route);
+    supervisor.announce_routes(vec![shared.clone()]);
+    supervisor.announce_client_routes(PEER_ADDR.ip(), vec![first, second.clone()]);
+    assert_eq!(supervisor.client_routes(&PEER_ADDR.ip()).len(), 2);
+    assert_eq!(supervisor.exported_routes(), vec![shared.clone()]);
+
+    // Client routes are kept when the peer is removed
+    supervisor.remove_peer(&PEER_ADDR.ip());
+    supervisor.withdraw_client_routes(PEER_ADDR.ip(), vec![ipv4_key("10.0.0.0/24")]);
+    assert_eq!(supervisor.client_routes(&PEER_ADDR.ip()), vec![second]);
+    assert_eq!(supervisor.exported_routes(), vec![shared]);
+
+    supervisor.withdraw_client_routes(PEER_ADDR.ip(), vec![ipv4_key("10.0.1.0/24")]);
+    assert!(supervisor.client_routes(&PEER_ADDR.ip()).is_empty());
+    Ok(())
+}
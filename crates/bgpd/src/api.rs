@@ -0,0 +1,1256 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! REST/JSON management API of the daemon.
+//!
+//! | Method   | Path                               | Description                                  |
+//! |----------|------------------------------------|----------------------------------------------|
+//! | `GET`    | `/api/v1/peers`                    | List the configured peers                    |
+//! | `POST`   | `/api/v1/peers`                    | Add and start a peer                         |
+//! | `GET`    | `/api/v1/peers/{ip}`               | Peer stats and capabilities                  |
+//! | `DELETE` | `/api/v1/peers/{ip}`               | Remove a configured peer                     |
+//! | `POST`   | `/api/v1/peers/{ip}/enable`        | Start the peer                               |
+//! | `POST`   | `/api/v1/peers/{ip}/disable`       | Shutdown the peer                            |
+//! | `POST`   | `/api/v1/peers/{ip}/soft-reset`    | Send a route refresh request                 |
+//! | `GET`    | `/api/v1/peers/{ip}/adj-rib-in`    | Routes received from the peer                |
+//! | `GET`    | `/api/v1/peers/{ip}/adj-rib-out`   | Routes advertised to the peer                |
+//! | `POST`   | `/api/v1/peers/{ip}/routes`        | Announce prefixes to the peer                |
+//! | `DELETE` | `/api/v1/peers/{ip}/routes`        | Withdraw prefixes from the peer              |
+//! | `GET`    | `/api/v1/updates[?peer={ip}]`      | Stream the received updates as JSON lines    |
+//!
+//! Peers are identified by their [SessionKey], i.e., the peer address followed
+//! by `@<local address>` when the peer is configured with a local address.
+//!
+//! Peers added or removed over the API change the running config of the
+//! daemon only, they're overwritten by the next config reload. Announced
+//! routes are exported to the peer as its own routes, so the peer's export
+//! settings are applied to them and they're re-advertised on route refresh.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, RwLock},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, Limited, StreamBody};
+use hyper::{
+    body::{Frame, Incoming},
+    header,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc, oneshot},
+};
+
+use netgauze_bgp_pkt::{
+    capabilities::BgpCapability,
+    notification::BgpNotificationMessage,
+    path_attribute::{
+        As4PathSegment, AsPath, AsPathSegmentType, LocalPreference, MultiExitDiscriminator,
+        NextHop, Origin, PathAttribute, PathAttributeValue,
+    },
+};
+use netgauze_bgp_speaker::{
+    export::ExportRoute,
+    listener::SessionKey,
+    peer::PeerStats,
+    peer_controller::PeerHandle,
+    rib::{MpNextHop, Route, RouteKey},
+    stats::NotificationRecord,
+};
+use netgauze_iana::address_family::AddressType;
+
+use crate::{
+    config::{ConfigError, PeerEntry},
+    output::UpdateRecord,
+};
+
+pub const API_PREFIX: &str = "/api/v1";
+
+/// Content type of the updates stream
+pub const JSON_LINES_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Max size of a request body
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Updates buffered for each stream subscriber, slower subscribers miss
+/// updates
+const UPDATES_BUFFER: usize = 4096;
+
+type ApiBody = UnsyncBoxBody<Bytes, Infallible>;
+
+type ApiHandle = PeerHandle<SocketAddr, TcpStream>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ApiError {
+    NotFound(String),
+    MethodNotAllowed,
+    BadRequest(String),
+    /// The peer is already configured
+    Conflict(String),
+    /// The peer task is not reachable anymore
+    Unavailable(String),
+}
+
+impl ApiError {
+    const fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::NotFound(msg)
+            | Self::BadRequest(msg)
+            | Self::Conflict(msg)
+            | Self::Unavailable(msg) => msg.clone(),
+            Self::MethodNotAllowed => "method not allowed".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerSummary {
+    pub peer: SessionKey,
+    pub peer_addr: SocketAddr,
+    pub state: String,
+    pub established_transitions: u32,
+    pub uptime_secs: Option<u64>,
+}
+
+impl PeerSummary {
+    fn new(peer: SessionKey, peer_addr: SocketAddr, stats: &PeerStats<SocketAddr>) -> Self {
+        Self {
+            peer,
+            peer_addr,
+            state: stats.fsm_state().to_string(),
+            established_transitions: stats.established_transitions(),
+            uptime_secs: stats.uptime().map(|uptime| uptime.as_secs()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefixStats {
+    pub address_type: AddressType,
+    pub received: u64,
+    pub withdrawn: u64,
+    pub accepted: u64,
+    pub advertised: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationStats {
+    pub timestamp: DateTime<Utc>,
+    pub notification: BgpNotificationMessage,
+}
+
+impl From<&NotificationRecord> for NotificationStats {
+    fn from(record: &NotificationRecord) -> Self {
+        Self {
+            timestamp: record.timestamp(),
+            notification: record.notification().clone(),
+        }
+    }
+}
+
+/// Capabilities of the current session, empty when the session is not
+/// established
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionCapabilities {
+    pub sent: Vec<BgpCapability>,
+    pub received: Vec<BgpCapability>,
+    pub negotiated: Vec<BgpCapability>,
+}
+
+impl SessionCapabilities {
+    /// Capabilities received from the peer that were also advertised to it.
+    /// Multiprotocol capabilities are compared per address type, the others by
+    /// capability code only.
+    fn new(sent: Vec<BgpCapability>, received: Vec<BgpCapability>) -> Self {
+        let negotiated = received
+            .iter()
+            .filter(|cap| {
+                sent.iter().any(|other| match (cap, other) {
+                    (
+                        BgpCapability::MultiProtocolExtensions(_),
+                        BgpCapability::MultiProtocolExtensions(_),
+                    ) => cap == &other,
+                    _ => cap.code() == other.code(),
+                })
+            })
+            .cloned()
+            .collect();
+        Self {
+            sent,
+            received,
+            negotiated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerDetail {
+    #[serde(flatten)]
+    pub summary: PeerSummary,
+    pub last_up: Option<DateTime<Utc>>,
+    pub last_down: Option<DateTime<Utc>>,
+    pub connect_retry_counter: u32,
+    pub damp_penalty: u32,
+    pub fsm_transitions: u64,
+    pub update_rate: f64,
+    pub withdraw_rate: f64,
+    pub prefixes: Vec<PrefixStats>,
+    pub last_notification_sent: Option<NotificationStats>,
+    pub last_notification_received: Option<NotificationStats>,
+    pub capabilities: SessionCapabilities,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteEntry {
+    pub address_type: AddressType,
+    pub path_id: Option<u32>,
+    pub prefix: IpNet,
+    /// Next hop carried in the MP_REACH_NLRI attribute
+    pub mp_next_hop: Option<IpAddr>,
+    pub attributes: Vec<PathAttribute>,
+}
+
+fn default_origin() -> Origin {
+    Origin::IGP
+}
+
+/// Prefixes to announce with the given path attributes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnounceRequest {
+    pub prefixes: Vec<IpNet>,
+    pub next_hop: IpAddr,
+    #[serde(default = "default_origin")]
+    pub origin: Origin,
+    #[serde(default)]
+    pub as_path: Vec<u32>,
+    #[serde(default)]
+    pub med: Option<u32>,
+    #[serde(default)]
+    pub local_pref: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WithdrawRequest {
+    pub prefixes: Vec<IpNet>,
+}
+
+/// Route refresh request, all the address types advertised by the peer are
+/// refreshed when `address_type` is not set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SoftResetRequest {
+    #[serde(default)]
+    pub address_type: Option<AddressType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoftResetResponse {
+    pub address_types: Vec<AddressType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutesResponse {
+    /// Number of routes announced or withdrawn
+    pub routes: usize,
+}
+
+/// Changes requested over the API, applied by the daemon owning the peers
+/// supervisor
+#[derive(Debug)]
+pub(crate) enum ApiCommand {
+    AddPeer(Box<PeerEntry>, oneshot::Sender<Result<(), ConfigError>>),
+    /// Replied with `false` when the peer is not configured
+    RemovePeer(SessionKey, oneshot::Sender<bool>),
+    AnnounceRoutes(SessionKey, Vec<ExportRoute>, oneshot::Sender<()>),
+    WithdrawRoutes(SessionKey, Vec<RouteKey>, oneshot::Sender<()>),
+}
+
+/// Management API serving the registered peers. Clones share the same peers
+/// and updates stream.
+#[derive(Debug, Clone)]
+pub struct ApiServer {
+    peers: Arc<RwLock<HashMap<SessionKey, ApiHandle>>>,
+    updates_tx: broadcast::Sender<UpdateRecord>,
+    /// Not set when the API is not served by a daemon, then peers and routes
+    /// can't be changed
+    commands_tx: Option<mpsc::UnboundedSender<ApiCommand>>,
+}
+
+impl Default for ApiServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApiServer {
+    pub fn new() -> Self {
+        let (updates_tx, _) = broadcast::channel(UPDATES_BUFFER);
+        Self {
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            updates_tx,
+            commands_tx: None,
+        }
+    }
+
+    pub(crate) fn with_commands_tx(
+        mut self,
+        commands_tx: mpsc::UnboundedSender<ApiCommand>,
+    ) -> Self {
+        self.commands_tx = Some(commands_tx);
+        self
+    }
+
+    pub fn add_peer(&self, peer_key: SessionKey, handle: ApiHandle) {
+        self.peers
+            .write()
+            .expect("API peers lock is poisoned")
+            .insert(peer_key, handle);
+    }
+
+    pub fn remove_peer(&self, peer_key: &SessionKey) -> Option<ApiHandle> {
+        self.peers
+            .write()
+            .expect("API peers lock is poisoned")
+            .remove(peer_key)
+    }
+
+    pub fn peer_handle(&self, peer_key: &SessionKey) -> Option<ApiHandle> {
+        self.peers
+            .read()
+            .expect("API peers lock is poisoned")
+            .get(peer_key)
+            .cloned()
+    }
+
+    pub fn peer_keys(&self) -> Vec<SessionKey> {
+        let mut keys: Vec<SessionKey> = self
+            .peers
+            .read()
+            .expect("API peers lock is poisoned")
+            .keys()
+            .copied()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Send a received update to the subscribers of the updates stream
+    pub fn publish(&self, record: UpdateRecord) {
+        // Error means there are no subscribers at the moment
+        let _ = self.updates_tx.send(record);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UpdateRecord> {
+        self.updates_tx.subscribe()
+    }
+
+    /// Bind to the given address and serve the API until an error occurs on
+    /// the listening socket
+    pub async fn serve(self, local_addr: SocketAddr) -> io::Result<()> {
+        let listener = TcpListener::bind(local_addr).await?;
+        self.serve_listener(listener).await
+    }
+
+    /// Serve the API on an already bound listener
+    pub async fn serve_listener(self, listener: TcpListener) -> io::Result<()> {
+        log::info!(
+            "Serving management API on http://{}{API_PREFIX}",
+            listener.local_addr()?
+        );
+        loop {
+            let (stream, remote_addr) = listener.accept().await?;
+            let api = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| handle_request(api.clone(), request));
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    log::debug!("Error serving management API to {remote_addr}: {err}");
+                }
+            });
+        }
+    }
+
+    fn handle(&self, peer: &str) -> Result<(SessionKey, ApiHandle), ApiError> {
+        let peer_key: SessionKey = peer
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("invalid peer address: {peer}")))?;
+        match self.peer_handle(&peer_key) {
+            Some(handle) => Ok((peer_key, handle)),
+            None => Err(ApiError::NotFound(format!("peer {peer_key} is not found"))),
+        }
+    }
+
+    async fn list_peers(&self) -> Result<Vec<PeerSummary>, ApiError> {
+        let mut peers = vec![];
+        for peer_key in self.peer_keys() {
+            if let Some(mut handle) = self.peer_handle(&peer_key) {
+                let stats = handle.peer_stats().await.map_err(unavailable)?;
+                peers.push(PeerSummary::new(peer_key, *handle.peer_addr(), &stats));
+            }
+        }
+        Ok(peers)
+    }
+
+    async fn peer_detail(&self, peer: &str) -> Result<PeerDetail, ApiError> {
+        let (peer_key, mut handle) = self.handle(peer)?;
+        let stats = handle.peer_stats().await.map_err(unavailable)?;
+        let sent = handle
+            .connection_sent_capabilities()
+            .await
+            .map_err(unavailable)?;
+        let received = handle
+            .connection_received_capabilities()
+            .await
+            .map_err(unavailable)?;
+        let mut prefixes: Vec<PrefixStats> = stats
+            .prefixes()
+            .iter()
+            .map(|(address_type, counters)| PrefixStats {
+                address_type: *address_type,
+                received: counters.received(),
+                withdrawn: counters.withdrawn(),
+                accepted: counters.accepted(),
+                advertised: counters.advertised(),
+            })
+            .collect();
+        prefixes.sort_by_key(|prefix| format!("{:?}", prefix.address_type));
+        Ok(PeerDetail {
+            summary: PeerSummary::new(peer_key, *handle.peer_addr(), &stats),
+            last_up: stats.last_up(),
+            last_down: stats.last_down(),
+            connect_retry_counter: stats.connect_retry_counter(),
+            damp_penalty: stats.damp_penalty(),
+            fsm_transitions: stats.fsm_transitions(),
+            update_rate: stats.update_rate(),
+            withdraw_rate: stats.withdraw_rate(),
+            prefixes,
+            last_notification_sent: stats.last_notification_sent().map(NotificationStats::from),
+            last_notification_received: stats
+                .last_notification_received()
+                .map(NotificationStats::from),
+            capabilities: SessionCapabilities::new(
+                sent.unwrap_or_default(),
+                received.unwrap_or_default(),
+            ),
+        })
+    }
+
+    /// Send a command to the daemon and wait for its reply
+    async fn command<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> ApiCommand,
+    ) -> Result<T, ApiError> {
+        let commands_tx = self.commands_tx.as_ref().ok_or_else(|| {
+            ApiError::Unavailable("peers are not managed by a daemon".to_string())
+        })?;
+        let (tx, rx) = oneshot::channel();
+        commands_tx
+            .send(command(tx))
+            .map_err(|_| ApiError::Unavailable("daemon is not running".to_string()))?;
+        rx.await
+            .map_err(|_| ApiError::Unavailable("daemon is not running".to_string()))
+    }
+
+    async fn create_peer(&self, peer: PeerEntry) -> Result<PeerActionResponse, ApiError> {
+        self.command(|tx| ApiCommand::AddPeer(Box::new(peer), tx))
+            .await?
+            .map_err(|err| match err {
+                ConfigError::DuplicatePeer(_) => ApiError::Conflict(err.to_string()),
+                _ => ApiError::BadRequest(err.to_string()),
+            })?;
+        Ok(PeerActionResponse { accepted: true })
+    }
+
+    async fn delete_peer(&self, peer: &str) -> Result<PeerActionResponse, ApiError> {
+        let peer_key: SessionKey = peer
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("invalid peer address: {peer}")))?;
+        if !self
+            .command(|tx| ApiCommand::RemovePeer(peer_key, tx))
+            .await?
+        {
+            return Err(ApiError::NotFound(format!(
+                "peer {peer_key} is not configured"
+            )));
+        }
+        Ok(PeerActionResponse { accepted: true })
+    }
+
+    fn set_enabled(&self, peer: &str, enabled: bool) -> Result<PeerActionResponse, ApiError> {
+        let (_, handle) = self.handle(peer)?;
+        let result = if enabled {
+            handle.start()
+        } else {
+            handle.shutdown()
+        };
+        result.map_err(|err| unavailable(err.to_string()))?;
+        Ok(PeerActionResponse { accepted: true })
+    }
+
+    async fn soft_reset(
+        &self,
+        peer: &str,
+        request: SoftResetRequest,
+    ) -> Result<SoftResetResponse, ApiError> {
+        let (_, mut handle) = self.handle(peer)?;
+        let address_types = match request.address_type {
+            Some(address_type) => vec![address_type],
+            None => {
+                let received = handle
+                    .connection_received_capabilities()
+                    .await
+                    .map_err(unavailable)?
+                    .unwrap_or_default();
+                let mut address_types: Vec<AddressType> = received
+                    .iter()
+                    .filter_map(|cap| match cap {
+                        BgpCapability::MultiProtocolExtensions(mp) => Some(mp.address_type()),
+                        _ => None,
+                    })
+                    .collect();
+                if address_types.is_empty() {
+                    address_types.push(AddressType::Ipv4Unicast);
+                }
+                address_types
+            }
+        };
+        for address_type in &address_types {
+            handle
+                .request_route_refresh(*address_type)
+                .map_err(|err| unavailable(err.to_string()))?;
+        }
+        Ok(SoftResetResponse { address_types })
+    }
+
+    async fn adj_rib(&self, peer: &str, rib_in: bool) -> Result<Vec<RouteEntry>, ApiError> {
+        let (_, mut handle) = self.handle(peer)?;
+        let rib = if rib_in {
+            handle.adj_rib_in().await
+        } else {
+            handle.adj_rib_out().await
+        }
+        .map_err(unavailable)?;
+        let mut routes = vec![];
+        for address_type in rib.address_types() {
+            for (key, route) in rib.routes(address_type) {
+                routes.push(RouteEntry {
+                    address_type,
+                    path_id: key.path_id(),
+                    prefix: key.prefix(),
+                    mp_next_hop: route.mp_next_hop().map(|next_hop| next_hop.next_hop()),
+                    attributes: route.attributes().clone(),
+                });
+            }
+        }
+        routes.sort_by_key(|route| (route.prefix, route.path_id));
+        Ok(routes)
+    }
+
+    async fn announce(
+        &self,
+        peer: &str,
+        request: AnnounceRequest,
+    ) -> Result<RoutesResponse, ApiError> {
+        let (peer_key, _) = self.handle(peer)?;
+        let routes = announce_routes(&request)?;
+        let count = routes.len();
+        self.command(|tx| ApiCommand::AnnounceRoutes(peer_key, routes, tx))
+            .await?;
+        Ok(RoutesResponse { routes: count })
+    }
+
+    async fn withdraw(
+        &self,
+        peer: &str,
+        request: WithdrawRequest,
+    ) -> Result<RoutesResponse, ApiError> {
+        let (peer_key, _) = self.handle(peer)?;
+        let keys: Vec<RouteKey> = request
+            .prefixes
+            .iter()
+            .map(|prefix| RouteKey::new(unicast_address_type(prefix), None, *prefix))
+            .collect();
+        let count = keys.len();
+        self.command(|tx| ApiCommand::WithdrawRoutes(peer_key, keys, tx))
+            .await?;
+        Ok(RoutesResponse { routes: count })
+    }
+
+    fn updates_stream(&self, peer: Option<SessionKey>) -> Response<ApiBody> {
+        let rx = self.subscribe();
+        let stream = futures_util::stream::unfold(rx, move |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(record) => {
+                        if peer.is_some_and(|peer| peer != record.peer()) {
+                            continue;
+                        }
+                        let mut line = match serde_json::to_vec(&record) {
+                            Ok(line) => line,
+                            Err(err) => {
+                                log::error!("Couldn't serialize update: {err}");
+                                continue;
+                            }
+                        };
+                        line.push(b'\n');
+                        return Some((Ok(Frame::data(Bytes::from(line))), rx));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Updates stream subscriber lagged, skipped {skipped} updates");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, JSON_LINES_CONTENT_TYPE)
+            .body(StreamBody::new(stream).boxed_unsync())
+            .expect("valid response")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerActionResponse {
+    pub accepted: bool,
+}
+
+fn unavailable<E: ToString>(err: E) -> ApiError {
+    ApiError::Unavailable(err.to_string())
+}
+
+const fn unicast_address_type(prefix: &IpNet) -> AddressType {
+    match prefix {
+        IpNet::V4(_) => AddressType::Ipv4Unicast,
+        IpNet::V6(_) => AddressType::Ipv6Unicast,
+    }
+}
+
+fn well_known_attribute(value: PathAttributeValue) -> Result<PathAttribute, ApiError> {
+    PathAttribute::from(false, true, false, false, value)
+        .map_err(|err| ApiError::BadRequest(format!("invalid path attribute: {err:?}")))
+}
+
+/// Build the routes exporting the requested prefixes. IPv4 prefixes with an
+/// IPv4 next hop use the NEXT_HOP attribute and the others use MP_REACH_NLRI,
+/// i.e., IPv4 prefixes with an IPv6 next hop are advertised as in
+/// [RFC8950](https://datatracker.ietf.org/doc/html/rfc8950). IPv6 prefixes
+/// require an IPv6 next hop.
+fn announce_routes(request: &AnnounceRequest) -> Result<Vec<ExportRoute>, ApiError> {
+    let as_path = if request.as_path.is_empty() {
+        AsPath::As4PathSegments(vec![])
+    } else {
+        AsPath::As4PathSegments(vec![As4PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            request.as_path.clone(),
+        )])
+    };
+    let mut attributes = vec![
+        well_known_attribute(PathAttributeValue::Origin(request.origin))?,
+        well_known_attribute(PathAttributeValue::AsPath(as_path))?,
+    ];
+    let mut classic_attributes = attributes.clone();
+    if let IpAddr::V4(next_hop) = request.next_hop {
+        classic_attributes.push(well_known_attribute(PathAttributeValue::NextHop(
+            NextHop::new(next_hop),
+        ))?);
+    }
+    let mut optional = vec![];
+    if let Some(med) = request.med {
+        optional.push(
+            PathAttribute::from(
+                true,
+                false,
+                false,
+                false,
+                PathAttributeValue::MultiExitDiscriminator(MultiExitDiscriminator::new(med)),
+            )
+            .map_err(|err| ApiError::BadRequest(format!("invalid MED: {err:?}")))?,
+        );
+    }
+    if let Some(local_pref) = request.local_pref {
+        optional.push(well_known_attribute(PathAttributeValue::LocalPreference(
+            LocalPreference::new(local_pref),
+        ))?);
+    }
+    attributes.extend(optional.iter().cloned());
+    classic_attributes.extend(optional);
+
+    let mut routes = vec![];
+    for prefix in &request.prefixes {
+        let address_type = unicast_address_type(prefix);
+        let route = match (address_type, request.next_hop) {
+            (AddressType::Ipv4Unicast, IpAddr::V4(_)) => {
+                Route::new(classic_attributes.clone(), None)
+            }
+            (AddressType::Ipv4Unicast | AddressType::Ipv6Unicast, IpAddr::V6(_)) => Route::new(
+                attributes.clone(),
+                Some(MpNextHop::new(request.next_hop, None)),
+            ),
+            _ => {
+                let next_hop = request.next_hop;
+                return Err(ApiError::BadRequest(format!(
+                    "next hop {next_hop} can't be used for {prefix}, IPv6 prefixes require an IPv6 next hop"
+                )));
+            }
+        };
+        routes.push(ExportRoute::local(
+            RouteKey::new(address_type, None, *prefix),
+            route,
+        ));
+    }
+    Ok(routes)
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<ApiBody> {
+    let body = serde_json::to_vec(value).expect("API responses are serializable");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)).boxed_unsync())
+        .expect("valid response")
+}
+
+fn error_response(err: ApiError) -> Response<ApiBody> {
+    json_response(
+        err.status(),
+        &ErrorResponse {
+            error: err.message(),
+        },
+    )
+}
+
+fn result_response<T: Serialize>(result: Result<T, ApiError>) -> Response<ApiBody> {
+    match result {
+        Ok(value) => json_response(StatusCode::OK, &value),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Parse the request body, an empty body is parsed as the default value when
+/// one is given
+async fn parse_body<T: serde::de::DeserializeOwned>(
+    request: Request<Incoming>,
+    default: Option<T>,
+) -> Result<T, ApiError> {
+    let body = Limited::new(request.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|err| ApiError::BadRequest(format!("couldn't read request body: {err}")))?
+        .to_bytes();
+    match default {
+        Some(default) if body.iter().all(|x| x.is_ascii_whitespace()) => Ok(default),
+        _ => serde_json::from_slice(&body)
+            .map_err(|err| ApiError::BadRequest(format!("invalid request body: {err}"))),
+    }
+}
+
+fn query_peer(request: &Request<Incoming>) -> Result<Option<SessionKey>, ApiError> {
+    let Some(query) = request.uri().query() else {
+        return Ok(None);
+    };
+    for pair in query.split('&') {
+        if let Some(value) = pair.strip_prefix("peer=") {
+            return value
+                .parse()
+                .map(Some)
+                .map_err(|_| ApiError::BadRequest(format!("invalid peer address: {value}")));
+        }
+    }
+    Ok(None)
+}
+
+async fn handle_request(
+    api: ApiServer,
+    request: Request<Incoming>,
+) -> Result<Response<ApiBody>, Infallible> {
+    let path = request.uri().path().to_string();
+    let Some(path) = path.strip_prefix(API_PREFIX) else {
+        return Ok(error_response(ApiError::NotFound(format!(
+            "{path} is not found"
+        ))));
+    };
+    let segments: Vec<&str> = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .collect();
+    let method = request.method().clone();
+    let response = match (method, segments.as_slice()) {
+        (Method::GET, ["peers"]) => result_response(api.list_peers().await),
+        (Method::POST, ["peers"]) => match parse_body(request, None).await {
+            Ok(body) => result_response(api.create_peer(body).await),
+            Err(err) => error_response(err),
+        },
+        (Method::GET, ["peers", peer]) => result_response(api.peer_detail(peer).await),
+        (Method::DELETE, ["peers", peer]) => result_response(api.delete_peer(peer).await),
+        (Method::POST, ["peers", peer, "enable"]) => result_response(api.set_enabled(peer, true)),
+        (Method::POST, ["peers", peer, "disable"]) => result_response(api.set_enabled(peer, false)),
+        (Method::POST, ["peers", peer, "soft-reset"]) => {
+            match parse_body(request, Some(SoftResetRequest::default())).await {
+                Ok(body) => result_response(api.soft_reset(peer, body).await),
+                Err(err) => error_response(err),
+            }
+        }
+        (Method::GET, ["peers", peer, "adj-rib-in"]) => {
+            result_response(api.adj_rib(peer, true).await)
+        }
+        (Method::GET, ["peers", peer, "adj-rib-out"]) => {
+            result_response(api.adj_rib(peer, false).await)
+        }
+        (Method::POST, ["peers", peer, "routes"]) => match parse_body(request, None).await {
+            Ok(body) => result_response(api.announce(peer, body).await),
+            Err(err) => error_response(err),
+        },
+        (Method::DELETE, ["peers", peer, "routes"]) => match parse_body(request, None).await {
+            Ok(body) => result_response(api.withdraw(peer, body).await),
+            Err(err) => error_response(err),
+        },
+        (Method::GET, ["updates"]) => match query_peer(&request) {
+            Ok(peer) => api.updates_stream(peer),
+            Err(err) => error_response(err),
+        },
+        (
+            _,
+            ["peers"]
+            | ["peers", _]
+            | ["peers", _, "enable" | "disable" | "soft-reset" | "adj-rib-in" | "adj-rib-out" | "routes"]
+            | ["updates"],
+        ) => error_response(ApiError::MethodNotAllowed),
+        _ => error_response(ApiError::NotFound(format!(
+            "{API_PREFIX}{path} is not found"
+        ))),
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{BgpdConfig, PeerEntry, TimersConfig},
+        daemon::BgpDaemon,
+        output::UpdateWriter,
+    };
+    use std::{net::Ipv4Addr, time::Duration};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        sync::mpsc,
+    };
+
+    fn free_port(ip: Ipv4Addr) -> u16 {
+        std::net::TcpListener::bind((ip, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    async fn spawn_api(api: ApiServer) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        tokio::spawn(api.serve_listener(listener));
+        local_addr
+    }
+
+    async fn send_request(api_addr: SocketAddr, method: &str, path: &str, body: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(api_addr).await.unwrap();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream
+    }
+
+    async fn request(
+        api_addr: SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        let mut stream = send_request(api_addr, method, path, body).await;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    async fn wait_for_state(api_addr: SocketAddr, peer: SessionKey, state: &str) {
+        tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                let (_, peers) = request(api_addr, "GET", "/api/v1/peers", "").await;
+                let peers: Vec<PeerSummary> = serde_json::from_value(peers).unwrap();
+                if peers.iter().any(|x| x.peer == peer && x.state == state) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("peer {peer} didn't reach {state} state"));
+    }
+
+    async fn wait_for_routes(api_addr: SocketAddr, path: &str, count: usize) -> Vec<RouteEntry> {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let (status, routes) = request(api_addr, "GET", path, "").await;
+                assert_eq!(status, 200);
+                let routes: Vec<RouteEntry> = serde_json::from_value(routes).unwrap();
+                if routes.len() == count {
+                    return routes;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{path} didn't have {count} routes"))
+    }
+
+    async fn next_record<R: tokio::io::AsyncBufRead + Unpin>(
+        updates: &mut tokio::io::Lines<R>,
+    ) -> UpdateRecord {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let line = updates.next_line().await.unwrap().unwrap();
+                if line.starts_with('{') {
+                    return serde_json::from_str::<UpdateRecord>(&line).unwrap();
+                }
+            }
+        })
+        .await
+        .expect("no update is streamed")
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let api_addr = spawn_api(ApiServer::new()).await;
+        let (status, body) = request(api_addr, "GET", "/api/v1/peers", "").await;
+        assert_eq!(status, 200);
+        assert_eq!(body, serde_json::json!([]));
+        let (status, body) = request(api_addr, "GET", "/api/v1/peers/192.0.2.1", "").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["error"], "peer 192.0.2.1 is not found");
+        let (status, _) = request(api_addr, "GET", "/api/v1/peers/not-an-ip", "").await;
+        assert_eq!(status, 400);
+        let (status, _) = request(api_addr, "DELETE", "/api/v1/peers", "").await;
+        assert_eq!(status, 405);
+        let (status, _) = request(api_addr, "GET", "/metrics", "").await;
+        assert_eq!(status, 404);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_add_remove_peer() {
+        let (writer, _) = UpdateWriter::spawn(tokio::io::sink());
+        let daemon = BgpDaemon::new(
+            BgpdConfig::new(Ipv4Addr::new(192, 0, 2, 1), 65000, vec![]),
+            writer,
+        );
+        let api_addr = spawn_api(daemon.api()).await;
+        let (_reload_tx, reload_rx) = mpsc::unbounded_channel();
+        let daemon = tokio::spawn(daemon.run(reload_rx));
+
+        let peer = r#"{"address": "192.0.2.2", "asn": 65001, "passive": true}"#;
+        let (status, body) = request(api_addr, "POST", "/api/v1/peers", peer).await;
+        assert_eq!(status, 200, "{body}");
+        let (status, peers) = request(api_addr, "GET", "/api/v1/peers", "").await;
+        assert_eq!(status, 200);
+        let peers: Vec<PeerSummary> = serde_json::from_value(peers).unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].peer, "192.0.2.2".parse().unwrap());
+
+        let (status, body) = request(api_addr, "POST", "/api/v1/peers", peer).await;
+        assert_eq!(status, 409);
+        assert_eq!(body["error"], "peer 192.0.2.2 is configured more than once");
+        // BFD is not configured on the daemon
+        let (status, _) = request(
+            api_addr,
+            "POST",
+            "/api/v1/peers",
+            r#"{"address": "192.0.2.3", "asn": 65001, "bfd": true}"#,
+        )
+        .await;
+        assert_eq!(status, 400);
+        let (status, _) = request(api_addr, "POST", "/api/v1/peers", r#"{"asn": 1}"#).await;
+        assert_eq!(status, 400);
+
+        let (status, body) = request(
+            api_addr,
+            "POST",
+            "/api/v1/peers/192.0.2.2/routes",
+            r#"{"prefixes": ["10.0.0.0/24"], "next_hop": "192.0.2.1"}"#,
+        )
+        .await;
+        assert_eq!(status, 200, "{body}");
+        let (status, body) = request(api_addr, "DELETE", "/api/v1/peers/192.0.2.2", "").await;
+        assert_eq!(status, 200, "{body}");
+        let (_, peers) = request(api_addr, "GET", "/api/v1/peers", "").await;
+        assert_eq!(peers, serde_json::json!([]));
+        let (status, body) = request(api_addr, "DELETE", "/api/v1/peers/192.0.2.2", "").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["error"], "peer 192.0.2.2 is not configured");
+
+        // Routes announced to the removed peer are not kept
+        let (status, _) = request(api_addr, "POST", "/api/v1/peers", peer).await;
+        assert_eq!(status, 200);
+        let (status, routes) =
+            request(api_addr, "GET", "/api/v1/peers/192.0.2.2/adj-rib-out", "").await;
+        assert_eq!(status, 200);
+        assert_eq!(routes, serde_json::json!([]));
+
+        daemon.abort();
+    }
+
+    #[tokio::test]
+    async fn test_peers_without_daemon() {
+        let api_addr = spawn_api(ApiServer::new()).await;
+        let (status, _) = request(
+            api_addr,
+            "POST",
+            "/api/v1/peers",
+            r#"{"address": "192.0.2.2", "asn": 65001}"#,
+        )
+        .await;
+        assert_eq!(status, 503);
+        let (status, _) = request(api_addr, "DELETE", "/api/v1/peers/192.0.2.2", "").await;
+        assert_eq!(status, 503);
+    }
+
+    /// Two daemons peering over loopback and managed over their APIs
+    #[cfg(target_os = "linux")]
+    #[test_log::test(tokio::test)]
+    async fn test_manage_peers() {
+        let ip1 = Ipv4Addr::new(127, 0, 0, 4);
+        let ip2 = Ipv4Addr::new(127, 0, 0, 5);
+        let ip3 = Ipv4Addr::new(127, 0, 0, 6);
+        let (peer1, peer2) = (IpAddr::V4(ip1), IpAddr::V4(ip2));
+        // The session to peer2 is keyed by the local address as well
+        let (session1, session2) = (
+            SessionKey::new(peer1),
+            SessionKey::new(peer2).with_local_ip(Some(peer1)),
+        );
+        let (port1, port2) = (free_port(ip1), free_port(ip2));
+        let timers = TimersConfig {
+            connect_retry: Some(1),
+            ..Default::default()
+        };
+        let config1 = BgpdConfig::new(
+            Ipv4Addr::new(1, 1, 1, 1),
+            65001,
+            vec![SocketAddr::new(peer1, port1)],
+        )
+        .with_peer(
+            PeerEntry::new(peer2, 65002)
+                .with_port(port2)
+                .with_local_address(peer1)
+                .with_timers(timers),
+        );
+        let config2 = BgpdConfig::new(
+            Ipv4Addr::new(2, 2, 2, 2),
+            65002,
+            vec![SocketAddr::new(peer2, port2)],
+        )
+        .with_peer(
+            PeerEntry::new(peer1, 65001)
+                .with_port(port1)
+                .with_passive(true),
+        );
+
+        let (writer2, _) = UpdateWriter::spawn(tokio::io::sink());
+        let daemon2 = BgpDaemon::new(config2, writer2);
+        let api2 = spawn_api(daemon2.api()).await;
+        let (_reload2_tx, reload2_rx) = mpsc::unbounded_channel();
+        let daemon2 = tokio::spawn(daemon2.run(reload2_rx));
+        // A failed connection attempt is not retried, so wait for the passive
+        // side to listen
+        loop {
+            let probe = tokio::net::TcpSocket::new_v4().unwrap();
+            probe.bind(SocketAddr::new(IpAddr::V4(ip3), 0)).unwrap();
+            if probe.connect(SocketAddr::new(peer2, port2)).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let (writer1, _) = UpdateWriter::spawn(tokio::io::sink());
+        let daemon1 = BgpDaemon::new(config1, writer1);
+        let api1 = spawn_api(daemon1.api()).await;
+        let (_reload1_tx, reload1_rx) = mpsc::unbounded_channel();
+        let daemon1 = tokio::spawn(daemon1.run(reload1_rx));
+
+        wait_for_state(api1, session2, "Established").await;
+        wait_for_state(api2, session1, "Established").await;
+
+        let (status, detail) = request(api1, "GET", &format!("/api/v1/peers/{session2}"), "").await;
+        assert_eq!(status, 200);
+        let detail: PeerDetail = serde_json::from_value(detail).unwrap();
+        assert_eq!(detail.summary.peer_addr, SocketAddr::new(peer2, port2));
+        assert_eq!(detail.summary.established_transitions, 1);
+        assert!(detail.last_up.is_some());
+        assert!(!detail.capabilities.sent.is_empty());
+        assert!(detail
+            .capabilities
+            .negotiated
+            .contains(&BgpCapability::RouteRefresh));
+
+        // Subscribe before announcing, the response headers are sent once
+        // the stream is set up
+        let stream = send_request(api2, "GET", &format!("/api/v1/updates?peer={peer1}"), "").await;
+        let mut updates = BufReader::new(stream).lines();
+        let status_line = updates.next_line().await.unwrap().unwrap();
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+
+        let (status, body) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/routes"),
+            &format!(
+                r#"{{"prefixes": ["10.0.0.0/24", "10.0.1.0/24"], "next_hop": "{ip1}", "as_path": [65001], "med": 10}}"#
+            ),
+        )
+        .await;
+        assert_eq!(status, 200, "{body}");
+        assert_eq!(body, serde_json::json!({"routes": 2}));
+
+        let routes = wait_for_routes(api2, &format!("/api/v1/peers/{peer1}/adj-rib-in"), 2).await;
+        assert_eq!(routes[0].address_type, AddressType::Ipv4Unicast);
+        assert_eq!(routes[0].prefix, "10.0.0.0/24".parse::<IpNet>().unwrap());
+        assert_eq!(routes[1].prefix, "10.0.1.0/24".parse::<IpNet>().unwrap());
+        assert!(routes[0].attributes.iter().any(|attr| attr.value()
+            == &PathAttributeValue::MultiExitDiscriminator(MultiExitDiscriminator::new(10))));
+        // The routes are exported, so the local ASN is prepended for the
+        // external peer
+        assert!(routes[0].attributes.iter().any(|attr| attr.value()
+            == &PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![As4PathSegment::new(
+                AsPathSegmentType::AsSequence,
+                vec![65001, 65001]
+            )]))));
+        wait_for_routes(api1, &format!("/api/v1/peers/{session2}/adj-rib-out"), 2).await;
+
+        let record = next_record(&mut updates).await;
+        assert_eq!(record.peer(), session1);
+        assert_eq!(record.update().nlri().len(), 2);
+
+        let (status, _) = request(
+            api1,
+            "DELETE",
+            &format!("/api/v1/peers/{session2}/routes"),
+            r#"{"prefixes": ["10.0.0.0/24"]}"#,
+        )
+        .await;
+        assert_eq!(status, 200);
+        let routes = wait_for_routes(api2, &format!("/api/v1/peers/{peer1}/adj-rib-in"), 1).await;
+        assert_eq!(routes[0].prefix, "10.0.1.0/24".parse::<IpNet>().unwrap());
+        wait_for_routes(api1, &format!("/api/v1/peers/{session2}/adj-rib-out"), 1).await;
+        let record = next_record(&mut updates).await;
+        assert_eq!(record.update().withdraw_routes().len(), 1);
+
+        let (status, body) = request(
+            api2,
+            "POST",
+            &format!("/api/v1/peers/{peer1}/soft-reset"),
+            "",
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(body, serde_json::json!({"address_types": ["Ipv4Unicast"]}));
+        // The remaining route is advertised again in response to the refresh
+        let record = next_record(&mut updates).await;
+        assert_eq!(record.update().nlri().len(), 1);
+
+        let (status, body) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/routes"),
+            r#"{"prefixes": ["10.0.2.0/24"], "next_hop": "2001:db8::1"}"#,
+        )
+        .await;
+        assert_eq!(status, 200, "{body}");
+        let routes =
+            wait_for_routes(api1, &format!("/api/v1/peers/{session2}/adj-rib-out"), 2).await;
+        assert_eq!(routes[1].prefix, "10.0.2.0/24".parse::<IpNet>().unwrap());
+        assert_eq!(routes[1].mp_next_hop, Some("2001:db8::1".parse().unwrap()));
+
+        let (status, body) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/routes"),
+            r#"{"prefixes": ["2001:db8::/32"], "next_hop": "192.0.2.1"}"#,
+        )
+        .await;
+        assert_eq!(status, 400, "{body}");
+        let (status, _) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/routes"),
+            r#"{"unknown": 1}"#,
+        )
+        .await;
+        assert_eq!(status, 400);
+
+        let (status, _) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/disable"),
+            "",
+        )
+        .await;
+        assert_eq!(status, 200);
+        wait_for_state(api1, session2, "Idle").await;
+        let (status, _) = request(
+            api1,
+            "POST",
+            &format!("/api/v1/peers/{session2}/enable"),
+            "",
+        )
+        .await;
+        assert_eq!(status, 200);
+        wait_for_state(api1, session2, "Established").await;
+
+        daemon1.abort();
+        daemon2.abort();
+    }
+}
@@ -0,0 +1,1300 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Daemon configuration, loaded from TOML or YAML files.
+
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    hash::{DefaultHasher, Hash, Hasher},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use netgauze_bgp_pkt::{
+    capabilities::{BgpCapability, MultiProtocolExtensionsCapability},
+    iana::BgpRoleValue,
+};
+use netgauze_bgp_speaker::{
+    bfd::{BfdConfig, BFD_PORT},
+    dynamic::{DynamicPeerRange, DynamicPeerTemplate, RemoteAsns},
+    listener::SessionKey,
+    peer::{PeerConfig, PeerConfigBuilder},
+    reflection::ClientRole,
+};
+use netgauze_iana::address_family::AddressType;
+
+/// Well-known BGP port
+pub const BGP_PORT: u16 = 179;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(String),
+    Yaml(String),
+    /// The file extension is not one of `toml`, `yaml`, or `yml`
+    UnknownFormat(String),
+    /// The same peer address is configured more than once with the same local
+    /// address
+    DuplicatePeer(SessionKey),
+    /// BFD is enabled on a peer without a `bfd` section
+    BfdNotConfigured(SessionKey),
+    /// The same peer template name is configured more than once
+    DuplicatePeerTemplate(String),
+    /// A dynamic range refers to a peer template that is not configured
+    UnknownPeerTemplate(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "couldn't read config file: {err}"),
+            Self::Toml(err) => write!(f, "invalid TOML config: {err}"),
+            Self::Yaml(err) => write!(f, "invalid YAML config: {err}"),
+            Self::UnknownFormat(path) => write!(
+                f,
+                "unknown config format for {path}, expected a .toml, .yaml, or .yml file"
+            ),
+            Self::DuplicatePeer(peer) => write!(f, "peer {peer} is configured more than once"),
+            Self::BfdNotConfigured(peer) => {
+                write!(f, "BFD is enabled on peer {peer} without a bfd section")
+            }
+            Self::DuplicatePeerTemplate(name) => {
+                write!(f, "peer template {name} is configured more than once")
+            }
+            Self::UnknownPeerTemplate(name) => write!(f, "unknown peer template {name}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+fn default_listen() -> Vec<SocketAddr> {
+    vec![
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), BGP_PORT),
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), BGP_PORT),
+    ]
+}
+
+const fn default_port() -> u16 {
+    BGP_PORT
+}
+
+const fn default_bmp_stats_interval() -> u64 {
+    60
+}
+
+const fn default_mrt_rotate_interval() -> u64 {
+    900
+}
+
+const fn default_mrt_rib_dump_interval() -> u64 {
+    7200
+}
+
+fn default_bfd_listen() -> SocketAddr {
+    SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), BFD_PORT)
+}
+
+const fn default_bfd_interval() -> u32 {
+    300
+}
+
+const fn default_bfd_detect_mult() -> u8 {
+    3
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+fn default_address_families() -> Vec<AddressType> {
+    vec![AddressType::Ipv4Unicast]
+}
+
+/// Top level configuration of the daemon
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BgpdConfig {
+    router_id: Ipv4Addr,
+    asn: u32,
+    #[serde(default = "default_listen")]
+    listen: Vec<SocketAddr>,
+    /// Accept sessions from unconfigured peers within these prefixes
+    #[serde(default)]
+    dynamic_peer_ranges: Vec<IpNet>,
+    /// Settings the dynamic peers of the `dynamic_ranges` are created from
+    #[serde(default)]
+    peer_templates: Vec<PeerTemplateConfig>,
+    /// Accept sessions from unconfigured peers within these prefixes, with
+    /// the settings of a peer template
+    #[serde(default)]
+    dynamic_ranges: Vec<DynamicRangeConfig>,
+    /// RFC5065 confederation, `asn` is then the local member AS
+    #[serde(default)]
+    confederation: Option<ConfederationConfig>,
+    /// RFC7854 BMP collectors the peers are monitored by
+    #[serde(default)]
+    bmp: Option<BmpConfig>,
+    /// RFC6396 MRT files the sessions are recorded to
+    #[serde(default)]
+    mrt: Option<MrtConfig>,
+    /// RFC5881 single-hop BFD the peers with `bfd` enabled are monitored by
+    #[serde(default)]
+    bfd: Option<BfdSettings>,
+    #[serde(default)]
+    peers: Vec<PeerEntry>,
+}
+
+impl BgpdConfig {
+    pub const fn new(router_id: Ipv4Addr, asn: u32, listen: Vec<SocketAddr>) -> Self {
+        Self {
+            router_id,
+            asn,
+            listen,
+            dynamic_peer_ranges: Vec::new(),
+            peer_templates: Vec::new(),
+            dynamic_ranges: Vec::new(),
+            confederation: None,
+            bmp: None,
+            mrt: None,
+            bfd: None,
+            peers: Vec::new(),
+        }
+    }
+
+    pub fn with_dynamic_peer_ranges(mut self, ranges: Vec<IpNet>) -> Self {
+        self.dynamic_peer_ranges = ranges;
+        self
+    }
+
+    pub fn with_peer_template(mut self, template: PeerTemplateConfig) -> Self {
+        self.peer_templates.push(template);
+        self
+    }
+
+    pub fn with_dynamic_range(mut self, range: DynamicRangeConfig) -> Self {
+        self.dynamic_ranges.push(range);
+        self
+    }
+
+    pub fn with_confederation(mut self, confederation: Option<ConfederationConfig>) -> Self {
+        self.confederation = confederation;
+        self
+    }
+
+    pub fn with_bmp(mut self, bmp: Option<BmpConfig>) -> Self {
+        self.bmp = bmp;
+        self
+    }
+
+    pub fn with_mrt(mut self, mrt: Option<MrtConfig>) -> Self {
+        self.mrt = mrt;
+        self
+    }
+
+    pub const fn with_bfd(mut self, bfd: Option<BfdSettings>) -> Self {
+        self.bfd = bfd;
+        self
+    }
+
+    pub fn with_peer(mut self, peer: PeerEntry) -> Self {
+        self.peers.push(peer);
+        self
+    }
+
+    /// Remove the peer with the given session key, returns the removed peer
+    /// if it was configured
+    pub fn remove_peer(&mut self, peer_key: &SessionKey) -> Option<PeerEntry> {
+        let pos = self
+            .peers
+            .iter()
+            .position(|peer| &peer.session_key() == peer_key)?;
+        Some(self.peers.remove(pos))
+    }
+
+    /// Load the config from a file, the format is chosen based on the file
+    /// extension
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content)?,
+            Some("yaml") | Some("yml") => Self::from_yaml(&content)?,
+            _ => return Err(ConfigError::UnknownFormat(path.display().to_string())),
+        };
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            toml::from_str(content).map_err(|err| ConfigError::Toml(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_yaml_ng::from_str(content).map_err(|err| ConfigError::Yaml(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let mut templates = HashSet::new();
+        for template in &self.peer_templates {
+            if !templates.insert(template.name.as_str()) {
+                return Err(ConfigError::DuplicatePeerTemplate(template.name.clone()));
+            }
+        }
+        for range in &self.dynamic_ranges {
+            if let Some(name) = &range.template {
+                if !templates.contains(name.as_str()) {
+                    return Err(ConfigError::UnknownPeerTemplate(name.clone()));
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        for peer in &self.peers {
+            if !seen.insert(peer.session_key()) {
+                return Err(ConfigError::DuplicatePeer(peer.session_key()));
+            }
+            if peer.bfd && self.bfd.is_none() {
+                return Err(ConfigError::BfdNotConfigured(peer.session_key()));
+            }
+        }
+        Ok(())
+    }
+
+    pub const fn router_id(&self) -> Ipv4Addr {
+        self.router_id
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+
+    pub const fn listen(&self) -> &Vec<SocketAddr> {
+        &self.listen
+    }
+
+    pub const fn dynamic_peer_ranges(&self) -> &Vec<IpNet> {
+        &self.dynamic_peer_ranges
+    }
+
+    pub const fn peer_templates(&self) -> &Vec<PeerTemplateConfig> {
+        &self.peer_templates
+    }
+
+    pub const fn dynamic_ranges(&self) -> &Vec<DynamicRangeConfig> {
+        &self.dynamic_ranges
+    }
+
+    /// Sessions from unconfigured peers are accepted from at least one range
+    pub fn allows_dynamic_peers(&self) -> bool {
+        !self.dynamic_peer_ranges.is_empty() || !self.dynamic_ranges.is_empty()
+    }
+
+    /// Ranges of the dynamic peers, the `dynamic_peer_ranges` and the
+    /// `dynamic_ranges` without a template use the default template, i.e., a
+    /// [PeerTemplateConfig] with only an empty name. The same config always
+    /// gives the same ranges, so the dynamic peers of unchanged ranges are
+    /// kept on reload.
+    pub fn dynamic_peer_range_settings(&self) -> Vec<DynamicPeerRange> {
+        let default_template = PeerTemplateConfig::new(String::new()).dynamic_peer_template();
+        let defaults = self
+            .dynamic_peer_ranges
+            .iter()
+            .map(|prefix| DynamicPeerRange::new(*prefix, default_template.clone()));
+        let ranges = self.dynamic_ranges.iter().map(|range| {
+            let template = range
+                .template
+                .as_ref()
+                .and_then(|name| {
+                    self.peer_templates
+                        .iter()
+                        .find(|template| &template.name == name)
+                })
+                .map_or_else(
+                    || default_template.clone(),
+                    PeerTemplateConfig::dynamic_peer_template,
+                );
+            DynamicPeerRange::new(range.prefix, template)
+                .with_remote_asns(range.remote_asns())
+                .with_max_peers(range.max_peers)
+        });
+        defaults.chain(ranges).collect()
+    }
+
+    pub const fn confederation(&self) -> Option<&ConfederationConfig> {
+        self.confederation.as_ref()
+    }
+
+    pub const fn bmp(&self) -> Option<&BmpConfig> {
+        self.bmp.as_ref()
+    }
+
+    pub const fn mrt(&self) -> Option<&MrtConfig> {
+        self.mrt.as_ref()
+    }
+
+    pub const fn bfd(&self) -> Option<&BfdSettings> {
+        self.bfd.as_ref()
+    }
+
+    pub const fn peers(&self) -> &Vec<PeerEntry> {
+        &self.peers
+    }
+}
+
+/// Confederation identifier and the AS numbers of the other member ASes
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfederationConfig {
+    pub id: u32,
+    #[serde(default)]
+    pub members: Vec<u32>,
+}
+
+impl ConfederationConfig {
+    /// The peer is in another member AS of the confederation
+    pub fn is_member(&self, asn: u32) -> bool {
+        self.members.contains(&asn)
+    }
+}
+
+/// BMP collectors and the interval of the statistics reports sent to them
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BmpConfig {
+    pub collectors: Vec<SocketAddr>,
+    /// Seconds between statistics reports, 0 disables them
+    #[serde(default = "default_bmp_stats_interval")]
+    pub stats_interval: u64,
+}
+
+/// Directory the MRT files are written to, and how often they are rotated
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MrtConfig {
+    pub directory: PathBuf,
+    /// Seconds before starting a new updates file, 0 disables it
+    #[serde(default = "default_mrt_rotate_interval")]
+    pub rotate_interval: u64,
+    /// Bytes before starting a new updates file, 0 disables it
+    #[serde(default)]
+    pub rotate_size: u64,
+    /// Seconds between the RIB snapshots, 0 disables them
+    #[serde(default = "default_mrt_rib_dump_interval")]
+    pub rib_dump_interval: u64,
+}
+
+/// Local address the BFD control packets are received on and the session
+/// parameters, intervals are in milliseconds
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BfdSettings {
+    #[serde(default = "default_bfd_listen")]
+    pub listen: SocketAddr,
+    #[serde(default = "default_bfd_interval")]
+    pub desired_min_tx: u32,
+    #[serde(default = "default_bfd_interval")]
+    pub required_min_rx: u32,
+    #[serde(default = "default_bfd_detect_mult")]
+    pub detect_mult: u8,
+}
+
+impl BfdSettings {
+    pub fn bfd_config(&self) -> BfdConfig {
+        BfdConfig::new(self.listen)
+            .with_desired_min_tx_interval(Duration::from_millis(self.desired_min_tx as u64))
+            .with_required_min_rx_interval(Duration::from_millis(self.required_min_rx as u64))
+            .with_detect_mult(self.detect_mult)
+    }
+}
+
+/// A configured BGP neighbor
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerEntry {
+    address: IpAddr,
+    #[serde(default = "default_port")]
+    port: u16,
+    asn: u32,
+    /// Source address used when connecting to the peer
+    #[serde(default)]
+    local_address: Option<IpAddr>,
+    /// Wait for the peer to open the TCP connection
+    #[serde(default)]
+    passive: bool,
+    /// TCP MD5 Signature password
+    #[serde(default)]
+    md5_password: Option<String>,
+    #[serde(default)]
+    timers: TimersConfig,
+    #[serde(default)]
+    capabilities: CapabilitiesConfig,
+    /// RFC9234 local BGP Role on the session
+    #[serde(default)]
+    role: Option<BgpRoleValue>,
+    /// Reject the session if the peer doesn't advertise its BGP Role
+    #[serde(default)]
+    strict_role: bool,
+    /// Tear down the session when BFD detects the peer as down
+    #[serde(default)]
+    bfd: bool,
+    /// Advertise the BGP Multisession capability
+    #[serde(default)]
+    multisession: bool,
+}
+
+impl PeerEntry {
+    pub fn new(address: IpAddr, asn: u32) -> Self {
+        Self {
+            address,
+            port: BGP_PORT,
+            asn,
+            local_address: None,
+            passive: false,
+            md5_password: None,
+            timers: TimersConfig::default(),
+            capabilities: CapabilitiesConfig::default(),
+            role: None,
+            strict_role: false,
+            bfd: false,
+            multisession: false,
+        }
+    }
+
+    pub const fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub const fn with_local_address(mut self, local_address: IpAddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
+    pub const fn with_passive(mut self, passive: bool) -> Self {
+        self.passive = passive;
+        self
+    }
+
+    pub fn with_md5_password(mut self, password: String) -> Self {
+        self.md5_password = Some(password);
+        self
+    }
+
+    pub const fn with_timers(mut self, timers: TimersConfig) -> Self {
+        self.timers = timers;
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: CapabilitiesConfig) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub const fn with_role(mut self, role: Option<BgpRoleValue>, strict: bool) -> Self {
+        self.role = role;
+        self.strict_role = strict;
+        self
+    }
+
+    pub const fn with_bfd(mut self, bfd: bool) -> Self {
+        self.bfd = bfd;
+        self
+    }
+
+    pub const fn with_multisession(mut self, multisession: bool) -> Self {
+        self.multisession = multisession;
+        self
+    }
+
+    pub const fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub const fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Key of the peer's session, the same address can be configured for
+    /// multiple peers with different local addresses
+    pub const fn session_key(&self) -> SessionKey {
+        SessionKey::new(self.address).with_local_ip(self.local_address)
+    }
+
+    pub const fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+
+    pub const fn local_address(&self) -> Option<IpAddr> {
+        self.local_address
+    }
+
+    pub const fn passive(&self) -> bool {
+        self.passive
+    }
+
+    pub const fn md5_password(&self) -> Option<&String> {
+        self.md5_password.as_ref()
+    }
+
+    pub const fn timers(&self) -> &TimersConfig {
+        &self.timers
+    }
+
+    pub const fn capabilities(&self) -> &CapabilitiesConfig {
+        &self.capabilities
+    }
+
+    pub const fn role(&self) -> Option<BgpRoleValue> {
+        self.role
+    }
+
+    pub const fn strict_role(&self) -> bool {
+        self.strict_role
+    }
+
+    pub const fn bfd(&self) -> bool {
+        self.bfd
+    }
+
+    pub const fn multisession(&self) -> bool {
+        self.multisession
+    }
+
+    /// FSM configuration of the peer, timers that are not set keep the
+    /// [PeerConfig] defaults. The jitter seed is derived from the peer
+    /// address, so reloading the same config doesn't change it.
+    pub fn peer_config(&self) -> PeerConfig {
+        let mut hasher = DefaultHasher::new();
+        self.socket_addr().hash(&mut hasher);
+        let builder = PeerConfigBuilder::new()
+            .passive_tcp_establishment(self.passive)
+            .rng_seed(hasher.finish());
+        self.timers.apply(builder).build()
+    }
+}
+
+/// Settings of the dynamic peers created from a [DynamicRangeConfig]. The
+/// peers wait for the remote end to connect and echo back the capabilities
+/// it advertises.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerTemplateConfig {
+    name: String,
+    /// The OpenDelayTimer defaults to its maximum, so the peer's OPEN message
+    /// is received before sending ours
+    #[serde(default)]
+    timers: TimersConfig,
+    /// Capabilities sent in addition to the echoed ones, none by default
+    #[serde(default)]
+    capabilities: Option<CapabilitiesConfig>,
+    /// RFC4456 route reflector client, only applicable to internal peers
+    #[serde(default)]
+    route_reflector_client: bool,
+}
+
+impl PeerTemplateConfig {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            timers: TimersConfig::default(),
+            capabilities: None,
+            route_reflector_client: false,
+        }
+    }
+
+    pub const fn with_timers(mut self, timers: TimersConfig) -> Self {
+        self.timers = timers;
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Option<CapabilitiesConfig>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub const fn with_route_reflector_client(mut self, value: bool) -> Self {
+        self.route_reflector_client = value;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub const fn timers(&self) -> &TimersConfig {
+        &self.timers
+    }
+
+    pub const fn capabilities(&self) -> Option<&CapabilitiesConfig> {
+        self.capabilities.as_ref()
+    }
+
+    pub const fn route_reflector_client(&self) -> bool {
+        self.route_reflector_client
+    }
+
+    /// Template of the dynamic peers, the jitter seed is derived from the
+    /// template name
+    pub fn dynamic_peer_template(&self) -> DynamicPeerTemplate {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        let builder = PeerConfigBuilder::new()
+            .open_delay_timer_duration(u16::MAX)
+            .passive_tcp_establishment(true)
+            .rng_seed(hasher.finish());
+        let client_role = if self.route_reflector_client {
+            ClientRole::RouteReflectorClient
+        } else {
+            ClientRole::NonClient
+        };
+        DynamicPeerTemplate::new(self.timers.apply(builder).build())
+            .with_capabilities(
+                self.capabilities
+                    .as_ref()
+                    .map(CapabilitiesConfig::bgp_capabilities)
+                    .unwrap_or_default(),
+            )
+            .with_client_role(client_role)
+    }
+}
+
+/// Prefix the dynamic peers are accepted from. A dynamic peer is removed as
+/// soon as its session goes down, which frees its slot in `max_peers`; there
+/// is no idle timeout keeping it around.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DynamicRangeConfig {
+    prefix: IpNet,
+    /// Name of the peer template, the default template when not set
+    #[serde(default)]
+    template: Option<String>,
+    /// ASNs the peers can use, any ASN when not set
+    #[serde(default)]
+    remote_asns: Option<RemoteAsnsConfig>,
+    /// Maximum number of dynamic peers established at the same time from
+    /// the range, unlimited when not set
+    #[serde(default)]
+    max_peers: Option<usize>,
+}
+
+impl DynamicRangeConfig {
+    pub const fn new(prefix: IpNet) -> Self {
+        Self {
+            prefix,
+            template: None,
+            remote_asns: None,
+            max_peers: None,
+        }
+    }
+
+    pub fn with_template(mut self, template: String) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    pub fn with_remote_asns(mut self, remote_asns: Option<RemoteAsnsConfig>) -> Self {
+        self.remote_asns = remote_asns;
+        self
+    }
+
+    pub const fn with_max_peers(mut self, max_peers: Option<usize>) -> Self {
+        self.max_peers = max_peers;
+        self
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+
+    pub const fn template(&self) -> Option<&String> {
+        self.template.as_ref()
+    }
+
+    pub fn remote_asns(&self) -> RemoteAsns {
+        match &self.remote_asns {
+            None => RemoteAsns::Any,
+            Some(RemoteAsnsConfig::List(asns)) => RemoteAsns::List(asns.clone()),
+            Some(RemoteAsnsConfig::Range { first, last }) => RemoteAsns::Range(*first..=*last),
+        }
+    }
+
+    pub const fn max_peers(&self) -> Option<usize> {
+        self.max_peers
+    }
+}
+
+/// ASNs of a dynamic range, either a list `[65001, 65002]` or an inclusive
+/// range `{ first = 64512, last = 65534 }`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RemoteAsnsConfig {
+    List(Vec<u32>),
+    Range { first: u32, last: u32 },
+}
+
+/// Timers in seconds
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimersConfig {
+    #[serde(default)]
+    pub hold_time: Option<u16>,
+    #[serde(default)]
+    pub keepalive: Option<u16>,
+    #[serde(default)]
+    pub connect_retry: Option<u16>,
+    #[serde(default)]
+    pub open_delay: Option<u16>,
+    #[serde(default)]
+    pub idle_hold: Option<u16>,
+}
+
+impl TimersConfig {
+    /// Set the configured timers, the others keep the builder's values
+    fn apply(&self, mut builder: PeerConfigBuilder) -> PeerConfigBuilder {
+        if let Some(value) = self.hold_time {
+            builder = builder
+                .hold_timer_duration(value)
+                .hold_timer_duration_large_value(value);
+        }
+        if let Some(value) = self.keepalive {
+            builder = builder.keepalive_timer_duration(value);
+        }
+        if let Some(value) = self.connect_retry {
+            builder = builder.connect_retry_duration(value);
+        }
+        if let Some(value) = self.open_delay {
+            builder = builder.open_delay_timer_duration(value);
+        }
+        if let Some(value) = self.idle_hold {
+            builder = builder.idle_hold_duration(value);
+        }
+        builder
+    }
+}
+
+/// Capabilities advertised to the peer, the four-octet AS number capability
+/// is always sent
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapabilitiesConfig {
+    #[serde(default = "default_address_families")]
+    pub address_families: Vec<AddressType>,
+    #[serde(default = "default_true")]
+    pub route_refresh: bool,
+    #[serde(default)]
+    pub enhanced_route_refresh: bool,
+    #[serde(default)]
+    pub extended_message: bool,
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            address_families: default_address_families(),
+            route_refresh: true,
+            enhanced_route_refresh: false,
+            extended_message: false,
+        }
+    }
+}
+
+impl CapabilitiesConfig {
+    pub fn bgp_capabilities(&self) -> Vec<BgpCapability> {
+        let mut capabilities: Vec<BgpCapability> = self
+            .address_families
+            .iter()
+            .map(|address_type| {
+                BgpCapability::MultiProtocolExtensions(MultiProtocolExtensionsCapability::new(
+                    *address_type,
+                ))
+            })
+            .collect();
+        if self.route_refresh {
+            capabilities.push(BgpCapability::RouteRefresh);
+        }
+        if self.enhanced_route_refresh {
+            capabilities.push(BgpCapability::EnhancedRouteRefresh);
+        }
+        if self.extended_message {
+            capabilities.push(BgpCapability::ExtendedMessage);
+        }
+        capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TOML_CONFIG: &str = r#"
+router_id = "192.0.2.1"
+asn = 65000
+listen = ["127.0.0.1:1179"]
+dynamic_peer_ranges = ["10.0.0.0/8"]
+confederation = { id = 64512, members = [65001] }
+bmp = { collectors = ["192.0.2.10:1790"], stats_interval = 30 }
+mrt = { directory = "/var/lib/bgpd/mrt", rotate_interval = 300, rotate_size = 1048576, rib_dump_interval = 3600 }
+bfd = { listen = "0.0.0.0:3784", desired_min_tx = 100, required_min_rx = 200, detect_mult = 5 }
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+md5_password = "secret"
+bfd = true
+
+[peers.timers]
+hold_time = 90
+connect_retry = 10
+
+[peers.capabilities]
+address_families = ["Ipv4Unicast", "Ipv6Unicast"]
+enhanced_route_refresh = true
+
+[[peers]]
+address = "2001:db8::2"
+port = 1179
+asn = 65002
+passive = true
+role = "Peer"
+strict_role = true
+multisession = true
+
+[[peer_templates]]
+name = "customers"
+route_reflector_client = true
+
+[peer_templates.timers]
+hold_time = 30
+
+[peer_templates.capabilities]
+address_families = ["Ipv6Unicast"]
+route_refresh = false
+
+[[dynamic_ranges]]
+prefix = "172.16.0.0/12"
+template = "customers"
+remote_asns = { first = 64512, last = 65534 }
+max_peers = 10
+
+[[dynamic_ranges]]
+prefix = "2001:db8:1::/48"
+remote_asns = [65003, 65004]
+"#;
+
+    const YAML_CONFIG: &str = r#"
+router_id: 192.0.2.1
+asn: 65000
+listen: ["127.0.0.1:1179"]
+dynamic_peer_ranges: ["10.0.0.0/8"]
+confederation:
+  id: 64512
+  members: [65001]
+bmp:
+  collectors: ["192.0.2.10:1790"]
+  stats_interval: 30
+mrt:
+  directory: /var/lib/bgpd/mrt
+  rotate_interval: 300
+  rotate_size: 1048576
+  rib_dump_interval: 3600
+bfd:
+  listen: 0.0.0.0:3784
+  desired_min_tx: 100
+  required_min_rx: 200
+  detect_mult: 5
+peers:
+  - address: 192.0.2.2
+    asn: 65001
+    md5_password: secret
+    bfd: true
+    timers:
+      hold_time: 90
+      connect_retry: 10
+    capabilities:
+      address_families: [Ipv4Unicast, Ipv6Unicast]
+      enhanced_route_refresh: true
+  - address: "2001:db8::2"
+    port: 1179
+    asn: 65002
+    passive: true
+    role: Peer
+    strict_role: true
+    multisession: true
+peer_templates:
+  - name: customers
+    route_reflector_client: true
+    timers:
+      hold_time: 30
+    capabilities:
+      address_families: [Ipv6Unicast]
+      route_refresh: false
+dynamic_ranges:
+  - prefix: 172.16.0.0/12
+    template: customers
+    remote_asns:
+      first: 64512
+      last: 65534
+    max_peers: 10
+  - prefix: "2001:db8:1::/48"
+    remote_asns: [65003, 65004]
+"#;
+
+    fn expected() -> BgpdConfig {
+        BgpdConfig::new(
+            Ipv4Addr::new(192, 0, 2, 1),
+            65000,
+            vec!["127.0.0.1:1179".parse().unwrap()],
+        )
+        .with_dynamic_peer_ranges(vec!["10.0.0.0/8".parse().unwrap()])
+        .with_confederation(Some(ConfederationConfig {
+            id: 64512,
+            members: vec![65001],
+        }))
+        .with_bmp(Some(BmpConfig {
+            collectors: vec!["192.0.2.10:1790".parse().unwrap()],
+            stats_interval: 30,
+        }))
+        .with_mrt(Some(MrtConfig {
+            directory: PathBuf::from("/var/lib/bgpd/mrt"),
+            rotate_interval: 300,
+            rotate_size: 1048576,
+            rib_dump_interval: 3600,
+        }))
+        .with_bfd(Some(BfdSettings {
+            listen: "0.0.0.0:3784".parse().unwrap(),
+            desired_min_tx: 100,
+            required_min_rx: 200,
+            detect_mult: 5,
+        }))
+        .with_peer(
+            PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 65001)
+                .with_md5_password("secret".to_string())
+                .with_bfd(true)
+                .with_timers(TimersConfig {
+                    hold_time: Some(90),
+                    connect_retry: Some(10),
+                    ..Default::default()
+                })
+                .with_capabilities(CapabilitiesConfig {
+                    address_families: vec![AddressType::Ipv4Unicast, AddressType::Ipv6Unicast],
+                    enhanced_route_refresh: true,
+                    ..Default::default()
+                }),
+        )
+        .with_peer(
+            PeerEntry::new("2001:db8::2".parse().unwrap(), 65002)
+                .with_port(1179)
+                .with_passive(true)
+                .with_role(Some(BgpRoleValue::Peer), true)
+                .with_multisession(true),
+        )
+        .with_peer_template(
+            PeerTemplateConfig::new("customers".to_string())
+                .with_route_reflector_client(true)
+                .with_timers(TimersConfig {
+                    hold_time: Some(30),
+                    ..Default::default()
+                })
+                .with_capabilities(Some(CapabilitiesConfig {
+                    address_families: vec![AddressType::Ipv6Unicast],
+                    route_refresh: false,
+                    ..Default::default()
+                })),
+        )
+        .with_dynamic_range(
+            DynamicRangeConfig::new("172.16.0.0/12".parse().unwrap())
+                .with_template("customers".to_string())
+                .with_remote_asns(Some(RemoteAsnsConfig::Range {
+                    first: 64512,
+                    last: 65534,
+                }))
+                .with_max_peers(Some(10)),
+        )
+        .with_dynamic_range(
+            DynamicRangeConfig::new("2001:db8:1::/48".parse().unwrap())
+                .with_remote_asns(Some(RemoteAsnsConfig::List(vec![65003, 65004]))),
+        )
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(BgpdConfig::from_toml(TOML_CONFIG).unwrap(), expected());
+        assert_eq!(BgpdConfig::from_yaml(YAML_CONFIG).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = BgpdConfig::from_toml("router_id = \"192.0.2.1\"\nasn = 65000\n").unwrap();
+        assert_eq!(config.listen(), &default_listen());
+        assert!(config.peers().is_empty());
+        assert_eq!(config.confederation(), None);
+        assert_eq!(config.bmp(), None);
+        let config = BgpdConfig::from_toml(
+            "router_id = \"192.0.2.1\"\nasn = 65000\nbmp = { collectors = [\"192.0.2.10:1790\"] }\n",
+        )
+        .unwrap();
+        assert_eq!(config.bmp().map(|bmp| bmp.stats_interval), Some(60));
+        assert_eq!(config.mrt(), None);
+        assert_eq!(config.bfd(), None);
+        let config = BgpdConfig::from_toml(
+            "router_id = \"192.0.2.1\"\nasn = 65000\nmrt = { directory = \"/tmp\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.mrt(),
+            Some(&MrtConfig {
+                directory: PathBuf::from("/tmp"),
+                rotate_interval: 900,
+                rotate_size: 0,
+                rib_dump_interval: 7200,
+            })
+        );
+
+        let config =
+            BgpdConfig::from_toml("router_id = \"192.0.2.1\"\nasn = 65000\nbfd = {}\n").unwrap();
+        let bfd_config = config.bfd().unwrap().bfd_config();
+        assert_eq!(bfd_config.local_addr(), "[::]:3784".parse().unwrap());
+        assert_eq!(
+            bfd_config.desired_min_tx_interval(),
+            Duration::from_millis(300)
+        );
+        assert_eq!(
+            bfd_config.required_min_rx_interval(),
+            Duration::from_millis(300)
+        );
+        assert_eq!(bfd_config.detect_mult(), 3);
+
+        let peer = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 65001);
+        assert_eq!(peer.socket_addr().port(), BGP_PORT);
+        assert!(!peer.bfd());
+        assert_eq!(
+            peer.capabilities().bgp_capabilities(),
+            vec![
+                BgpCapability::MultiProtocolExtensions(MultiProtocolExtensionsCapability::new(
+                    AddressType::Ipv4Unicast
+                )),
+                BgpCapability::RouteRefresh,
+            ]
+        );
+        let peer_config = peer.peer_config();
+        let default_config = PeerConfigBuilder::new().build();
+        assert_eq!(peer_config, peer.peer_config());
+        assert_eq!(
+            peer_config.hold_timer_duration(),
+            default_config.hold_timer_duration()
+        );
+        assert_eq!(
+            peer_config.connect_retry_duration(),
+            default_config.connect_retry_duration()
+        );
+        assert!(!peer_config.passive_tcp_establishment());
+    }
+
+    #[test]
+    fn test_peer_config() {
+        let config = BgpdConfig::from_toml(TOML_CONFIG).unwrap();
+        let peer_config = config.peers()[0].peer_config();
+        assert_eq!(peer_config.hold_timer_duration(), Duration::from_secs(90));
+        assert_eq!(
+            peer_config.connect_retry_duration(),
+            Duration::from_secs(10)
+        );
+        assert!(!peer_config.passive_tcp_establishment());
+        assert!(config.peers()[1].peer_config().passive_tcp_establishment());
+    }
+
+    #[test]
+    fn test_dynamic_peer_ranges() {
+        let config = BgpdConfig::from_toml(TOML_CONFIG).unwrap();
+        assert!(config.allows_dynamic_peers());
+        let ranges = config.dynamic_peer_range_settings();
+        assert_eq!(
+            ranges
+                .iter()
+                .map(DynamicPeerRange::prefix)
+                .collect::<Vec<_>>(),
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "172.16.0.0/12".parse().unwrap(),
+                "2001:db8:1::/48".parse().unwrap(),
+            ]
+        );
+        assert_eq!(ranges[0].remote_asns(), &RemoteAsns::Any);
+        assert!(ranges[0].template().capabilities().is_empty());
+
+        let template = ranges[1].template();
+        assert_eq!(
+            template.config().hold_timer_duration(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            template.config().open_delay_timer_duration(),
+            Duration::from_secs(u16::MAX as u64)
+        );
+        assert!(template.config().passive_tcp_establishment());
+        assert_eq!(
+            template.capabilities(),
+            &vec![BgpCapability::MultiProtocolExtensions(
+                MultiProtocolExtensionsCapability::new(AddressType::Ipv6Unicast)
+            )]
+        );
+        assert_eq!(template.client_role(), ClientRole::RouteReflectorClient);
+        assert_eq!(ranges[1].remote_asns(), &RemoteAsns::Range(64512..=65534));
+        assert_eq!(ranges[1].max_peers(), Some(10));
+
+        assert!(ranges[2].template().capabilities().is_empty());
+        assert_eq!(ranges[2].template().client_role(), ClientRole::NonClient);
+        assert_eq!(
+            ranges[2].remote_asns(),
+            &RemoteAsns::List(vec![65003, 65004])
+        );
+        assert_eq!(ranges[2].max_peers(), None);
+
+        // Reloading the same config doesn't change the ranges
+        assert_eq!(
+            BgpdConfig::from_toml(TOML_CONFIG)
+                .unwrap()
+                .dynamic_peer_range_settings(),
+            ranges
+        );
+
+        let config = BgpdConfig::from_toml("router_id = \"192.0.2.1\"\nasn = 65000\n").unwrap();
+        assert!(!config.allows_dynamic_peers());
+        assert!(config.dynamic_peer_range_settings().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let duplicate = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65002
+"#;
+        assert!(matches!(
+            BgpdConfig::from_toml(duplicate),
+            Err(ConfigError::DuplicatePeer(_))
+        ));
+        let multi_session = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+local_address = "192.0.2.10"
+"#;
+        let config = BgpdConfig::from_toml(multi_session).unwrap();
+        assert_ne!(
+            config.peers()[0].session_key(),
+            config.peers()[1].session_key()
+        );
+        let duplicate_session = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+local_address = "192.0.2.10"
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65002
+local_address = "192.0.2.10"
+"#;
+        assert!(matches!(
+            BgpdConfig::from_toml(duplicate_session),
+            Err(ConfigError::DuplicatePeer(_))
+        ));
+        let bfd_not_configured = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[peers]]
+address = "192.0.2.2"
+asn = 65001
+bfd = true
+"#;
+        assert!(matches!(
+            BgpdConfig::from_toml(bfd_not_configured),
+            Err(ConfigError::BfdNotConfigured(_))
+        ));
+        let duplicate_template = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[peer_templates]]
+name = "customers"
+
+[[peer_templates]]
+name = "customers"
+"#;
+        assert!(matches!(
+            BgpdConfig::from_toml(duplicate_template),
+            Err(ConfigError::DuplicatePeerTemplate(name)) if name == "customers"
+        ));
+        let unknown_template = r#"
+router_id = "192.0.2.1"
+asn = 65000
+
+[[dynamic_ranges]]
+prefix = "172.16.0.0/12"
+template = "customers"
+"#;
+        assert!(matches!(
+            BgpdConfig::from_toml(unknown_template),
+            Err(ConfigError::UnknownPeerTemplate(name)) if name == "customers"
+        ));
+        assert!(matches!(
+            BgpdConfig::from_toml("router_id = \"192.0.2.1\"\nasn = 65000\nunknown = 1\n"),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            BgpdConfig::from_file(Path::new("bgpd.ini")),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,778 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Run the configured peers on top of [PeersSupervisor] and [BgpListener]
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, mpsc::UnboundedReceiver},
+};
+
+use netgauze_bgp_pkt::{codec::BgpCodec, update::BgpUpdateMessage};
+use netgauze_bgp_speaker::{
+    auth::TcpAuth,
+    bfd::Bfd,
+    bmp::{BmpExporter, BmpExporterConfig},
+    connection::TcpActiveConnect,
+    events::{BgpEvent, UpdateTreatment},
+    listener::{BgpListener, SessionKey},
+    liveness::LivenessDetector,
+    mrt::{MrtRecorder, MrtRecorderConfig},
+    peer::{EchoCapabilitiesPolicy, PeerProperties},
+    peer_controller::{PeerHandle, PeerStateResult},
+    socket::TcpSocketConfig,
+    supervisor::{PeerDefinition, PeersSupervisor},
+};
+
+use crate::{
+    api::{ApiCommand, ApiServer},
+    config::{BgpdConfig, ConfigError, PeerEntry},
+    output::{UpdateRecord, UpdateWriter},
+};
+
+pub type BgpdPolicy = EchoCapabilitiesPolicy<SocketAddr, TcpStream, BgpCodec>;
+
+type BgpdPeerDefinition = PeerDefinition<SocketAddr, TcpActiveConnect, BgpdPolicy>;
+
+/// Error returned when a new config can't be applied to a running daemon
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReloadError {
+    /// Changing the router ID requires a restart
+    RouterIdChanged { current: Ipv4Addr, new: Ipv4Addr },
+    /// Changing the ASN requires a restart
+    AsnChanged { current: u32, new: u32 },
+    /// Changing the BFD settings requires a restart
+    BfdChanged,
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RouterIdChanged { current, new } => write!(
+                f,
+                "router ID changed from {current} to {new}, restart is required"
+            ),
+            Self::AsnChanged { current, new } => {
+                write!(
+                    f,
+                    "ASN changed from {current} to {new}, restart is required"
+                )
+            }
+            Self::BfdChanged => write!(f, "BFD settings changed, restart is required"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+/// BGP daemon running the peers defined in a [BgpdConfig] and writing the
+/// updates received from them to an [UpdateWriter]
+#[derive(Debug)]
+pub struct BgpDaemon {
+    config: BgpdConfig,
+    supervisor: PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    listener: BgpListener<SocketAddr, TcpStream>,
+    writer: UpdateWriter,
+    api: ApiServer,
+    bfd: Option<Arc<Bfd>>,
+    api_commands_rx: UnboundedReceiver<ApiCommand>,
+}
+
+/// Input of the daemon while it's running
+enum DaemonEvent {
+    Reload(Option<Box<BgpdConfig>>),
+    Api(ApiCommand),
+}
+
+impl BgpDaemon {
+    /// Create and start the configured peers, must be called within a tokio
+    /// runtime.
+    pub fn new(config: BgpdConfig, writer: UpdateWriter) -> Self {
+        let (dynamic_peer_events_tx, mut dynamic_peer_events_rx) = mpsc::unbounded_channel();
+        let dynamic_writer = writer.clone();
+        let (api_commands_tx, api_commands_rx) = mpsc::unbounded_channel();
+        let api = ApiServer::new().with_commands_tx(api_commands_tx);
+        let dynamic_api = api.clone();
+        tokio::spawn(async move {
+            while let Some((peer_key, event)) = dynamic_peer_events_rx.recv().await {
+                handle_peer_event(peer_key, event, &dynamic_writer, &dynamic_api);
+            }
+        });
+        let bfd = config.bfd().and_then(|bfd| {
+            log::info!("Running BFD on {}", bfd.listen);
+            match Bfd::bind(bfd.bfd_config()) {
+                Ok(bfd) => Some(Arc::new(bfd)),
+                Err(err) => {
+                    log::error!("Couldn't bind BFD on {}: {err}", bfd.listen);
+                    None
+                }
+            }
+        });
+        let mut listener = BgpListener::new(vec![], false);
+        listener.set_dynamic_peer_events_tx(dynamic_peer_events_tx);
+        let mut daemon = Self {
+            supervisor: PeersSupervisor::new(config.asn(), config.router_id()),
+            listener,
+            config: BgpdConfig::new(config.router_id(), config.asn(), vec![])
+                .with_bfd(config.bfd().copied()),
+            writer,
+            api,
+            bfd,
+            api_commands_rx,
+        };
+        daemon.apply(config);
+        daemon
+    }
+
+    pub const fn config(&self) -> &BgpdConfig {
+        &self.config
+    }
+
+    pub fn peer_keys(&self) -> Vec<SessionKey> {
+        self.supervisor.peer_keys()
+    }
+
+    /// Management API serving the configured peers, it's kept in sync with
+    /// the config on reload
+    pub fn api(&self) -> ApiServer {
+        self.api.clone()
+    }
+
+    pub fn peer_handle(
+        &mut self,
+        peer_key: &SessionKey,
+    ) -> Option<PeerHandle<SocketAddr, TcpStream>> {
+        self.supervisor.peer_handler(peer_key)
+    }
+
+    /// Apply a new config, peers whose session settings are unchanged are
+    /// kept running. Dynamic peers are kept unless their range was removed or
+    /// changed.
+    pub fn reload(&mut self, config: BgpdConfig) -> Result<(), ReloadError> {
+        if config.router_id() != self.config.router_id() {
+            return Err(ReloadError::RouterIdChanged {
+                current: self.config.router_id(),
+                new: config.router_id(),
+            });
+        }
+        if config.asn() != self.config.asn() {
+            return Err(ReloadError::AsnChanged {
+                current: self.config.asn(),
+                new: config.asn(),
+            });
+        }
+        if config.bfd() != self.config.bfd() {
+            return Err(ReloadError::BfdChanged);
+        }
+        self.apply(config);
+        Ok(())
+    }
+
+    /// Add a peer to the running config and start it. The peer is not
+    /// written to the config file, so it's removed by the next reload unless
+    /// the file configures it as well.
+    pub fn add_peer(&mut self, peer: PeerEntry) -> Result<(), ConfigError> {
+        let config = self.config.clone().with_peer(peer.clone());
+        config.validate()?;
+        let peer_key = peer.session_key();
+        // Only a dynamic peer can use the key of a peer that is not configured
+        if self.supervisor.remove_peer(&peer_key).is_some() {
+            log::info!("[{peer_key}] Dynamic peer replaced by a configured peer");
+        }
+        let definition = self.peer_definition(&config, &peer);
+        let (rx, handle) = self
+            .supervisor
+            .add_peer(peer_key, definition)
+            .map_err(|_| ConfigError::DuplicatePeer(peer_key))?;
+        self.listener.reg_peer(peer_key, handle.clone());
+        self.start_peer(peer_key, rx, handle, peer.bfd());
+        self.config = config;
+        Ok(())
+    }
+
+    /// Remove a peer from the running config along with the routes announced
+    /// to it, returns `false` when the peer is not configured
+    pub fn remove_peer(&mut self, peer_key: &SessionKey) -> bool {
+        if self.config.remove_peer(peer_key).is_none() {
+            return false;
+        }
+        self.supervisor.remove_peer(peer_key);
+        self.listener.unreg_peer(peer_key);
+        self.api.remove_peer(peer_key);
+        log::info!("[{peer_key}] Peer removed");
+        let keys = self
+            .supervisor
+            .client_routes(peer_key)
+            .iter()
+            .map(|route| *route.key())
+            .collect();
+        self.supervisor.withdraw_client_routes(*peer_key, keys);
+        true
+    }
+
+    fn handle_api_command(&mut self, command: ApiCommand) {
+        // Errors mean the API request was dropped, the change is applied anyway
+        match command {
+            ApiCommand::AddPeer(peer, tx) => {
+                let _ = tx.send(self.add_peer(*peer));
+            }
+            ApiCommand::RemovePeer(peer_key, tx) => {
+                let _ = tx.send(self.remove_peer(&peer_key));
+            }
+            ApiCommand::AnnounceRoutes(peer_key, routes, tx) => {
+                self.supervisor.announce_client_routes(peer_key, routes);
+                let _ = tx.send(());
+            }
+            ApiCommand::WithdrawRoutes(peer_key, keys, tx) => {
+                self.supervisor.withdraw_client_routes(peer_key, keys);
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    fn apply(&mut self, config: BgpdConfig) {
+        // Set before the peers are added, so new peers are monitored too
+        if config.bmp() != self.config.bmp() {
+            let exporter = config.bmp().map(|bmp| {
+                log::info!("Exporting BMP to {:?}", bmp.collectors);
+                BmpExporter::start(
+                    BmpExporterConfig::new(
+                        bmp.collectors.clone(),
+                        config.asn(),
+                        config.router_id(),
+                    )
+                    .with_stats_interval(Duration::from_secs(bmp.stats_interval)),
+                )
+            });
+            self.supervisor.set_bmp_exporter(exporter);
+        }
+        if config.mrt() != self.config.mrt() {
+            let recorder = config.mrt().map(|mrt| {
+                log::info!("Recording MRT files to {}", mrt.directory.display());
+                MrtRecorder::start(
+                    MrtRecorderConfig::new(mrt.directory.clone(), config.router_id())
+                        .with_rotate_interval(Duration::from_secs(mrt.rotate_interval))
+                        .with_rotate_size(mrt.rotate_size)
+                        .with_rib_dump_interval(Duration::from_secs(mrt.rib_dump_interval)),
+                )
+            });
+            self.supervisor.set_mrt_recorder(recorder);
+        }
+        // The local address is part of the session key, so peers with a new
+        // source address are recreated
+        let definitions = config
+            .peers()
+            .iter()
+            .map(|peer| (peer.session_key(), self.peer_definition(&config, peer)))
+            .collect::<HashMap<_, _>>();
+        let bfd_peers = bfd_peers_of(&config);
+        let update = self.supervisor.update_peers(definitions);
+        for peer_key in update.removed() {
+            log::info!("[{peer_key}] Peer removed");
+            self.listener.unreg_peer(peer_key);
+            self.api.remove_peer(peer_key);
+        }
+        for peer_key in update.updated() {
+            log::info!("[{peer_key}] Peer updated");
+        }
+        let mut added = HashSet::new();
+        for (peer_key, rx, handle) in update.into_added() {
+            self.start_peer(peer_key, rx, handle, bfd_peers.contains(&peer_key));
+            added.insert(peer_key);
+        }
+        // Running peers on which BFD was enabled or disabled
+        for peer_key in bfd_peers.symmetric_difference(&bfd_peers_of(&self.config)) {
+            if added.contains(peer_key) {
+                continue;
+            }
+            if let Some(handle) = self.supervisor.peer_handler(peer_key) {
+                self.set_liveness_detector(*peer_key, &handle, bfd_peers.contains(peer_key));
+            }
+        }
+
+        // The running listener keeps its dynamic peers and listening sockets
+        self.listener.set_sockets(config.listen().clone());
+        self.listener
+            .set_allow_dynamic_peers(config.allows_dynamic_peers());
+        self.listener
+            .update_dynamic_peer_ranges(config.dynamic_peer_range_settings(), &mut self.supervisor);
+        for peer in config.peers() {
+            if let Some(handle) = self.supervisor.peer_handler(&peer.session_key()) {
+                self.listener.reg_peer(peer.session_key(), handle);
+            }
+        }
+        self.config = config;
+    }
+
+    /// Start a peer created by the supervisor, its events are written to the
+    /// output and it's served by the API
+    fn start_peer(
+        &self,
+        peer_key: SessionKey,
+        rx: UnboundedReceiver<PeerStateResult<SocketAddr>>,
+        handle: PeerHandle<SocketAddr, TcpStream>,
+        bfd: bool,
+    ) {
+        log::info!("[{peer_key}] Peer added");
+        spawn_peer_events(peer_key, rx, self.writer.clone(), self.api.clone());
+        self.api.add_peer(peer_key, handle.clone());
+        if bfd {
+            self.set_liveness_detector(peer_key, &handle, true);
+        }
+        if let Err(err) = handle.start() {
+            log::error!("[{peer_key}] Couldn't start peer: {err}");
+        }
+    }
+
+    fn set_liveness_detector(
+        &self,
+        peer_key: SessionKey,
+        handle: &PeerHandle<SocketAddr, TcpStream>,
+        enabled: bool,
+    ) {
+        let detector = self
+            .bfd
+            .clone()
+            .filter(|_| enabled)
+            .map(|bfd| bfd as Arc<dyn LivenessDetector<SocketAddr>>);
+        if enabled && detector.is_none() {
+            log::warn!("[{peer_key}] BFD is not running, the peer is not monitored");
+        }
+        if let Err(err) = handle.set_liveness_detector(detector) {
+            log::error!("[{peer_key}] Couldn't set BFD on peer: {err}");
+        }
+    }
+
+    fn peer_definition(&self, config: &BgpdConfig, peer: &PeerEntry) -> BgpdPeerDefinition {
+        let properties = PeerProperties::new(
+            config.asn(),
+            peer.asn(),
+            config.router_id(),
+            peer.socket_addr(),
+            false,
+        )
+        .with_bgp_role(peer.role())
+        .with_strict_bgp_role(peer.strict_role())
+        .with_multisession(peer.multisession())
+        .with_confederation_id(config.confederation().map(|confederation| confederation.id))
+        .with_confederation_member(
+            config
+                .confederation()
+                .is_some_and(|confederation| confederation.is_member(peer.asn())),
+        );
+        let peer_config = peer.peer_config();
+        let policy = EchoCapabilitiesPolicy::new(
+            config.asn(),
+            true,
+            config.router_id(),
+            peer_config.hold_timer_duration_large_value().as_secs() as u16,
+            peer.capabilities().bgp_capabilities(),
+            Vec::new(),
+        );
+        let mut socket_config = TcpSocketConfig::new();
+        if let Some(local_address) = peer.local_address() {
+            socket_config = socket_config.with_local_addr(local_address);
+        }
+        let active_connect = TcpActiveConnect::new()
+            .with_tcp_auth_keys(self.supervisor.tcp_auth_keys().clone())
+            .with_socket_config(socket_config);
+        let definition = PeerDefinition::new(properties, peer_config, active_connect, policy);
+        match peer.md5_password() {
+            Some(password) => definition.with_tcp_auth(
+                peer.session_key(),
+                TcpAuth::Md5(password.as_bytes().to_vec()),
+            ),
+            None => definition,
+        }
+    }
+
+    /// Accept BGP connections, and apply the configs received on `reload_rx`
+    /// and the changes requested over the API until an error occurs on the
+    /// listening sockets. The listening sockets are re-bound only when a
+    /// reload changes them.
+    pub async fn run(mut self, mut reload_rx: UnboundedReceiver<BgpdConfig>) -> io::Result<()> {
+        let mut reload_closed = false;
+        loop {
+            let event = tokio::select! {
+                result = listen(&mut self.listener, &mut self.supervisor, &self.config) => return result,
+                reload = reload_rx.recv(), if !reload_closed => DaemonEvent::Reload(reload.map(Box::new)),
+                Some(command) = self.api_commands_rx.recv() => DaemonEvent::Api(command),
+            };
+            match event {
+                DaemonEvent::Reload(Some(config)) => {
+                    log::info!("Reloading config");
+                    if let Err(err) = self.reload(*config) {
+                        log::error!("Config reload rejected: {err}");
+                    }
+                }
+                DaemonEvent::Reload(None) => reload_closed = true,
+                DaemonEvent::Api(command) => self.handle_api_command(command),
+            }
+        }
+    }
+}
+
+/// Keys of the peers with BFD enabled
+fn bfd_peers_of(config: &BgpdConfig) -> HashSet<SessionKey> {
+    config
+        .peers()
+        .iter()
+        .filter(|peer| peer.bfd())
+        .map(|peer| peer.session_key())
+        .collect()
+}
+
+async fn listen(
+    listener: &mut BgpListener<SocketAddr, TcpStream>,
+    supervisor: &mut PeersSupervisor<SessionKey, SocketAddr, TcpStream>,
+    config: &BgpdConfig,
+) -> io::Result<()> {
+    if config.listen().is_empty() {
+        log::info!("No listening sockets configured, only active peers are connected");
+        std::future::pending::<()>().await;
+    }
+    listener.run(supervisor).await
+}
+
+fn spawn_peer_events(
+    peer_key: SessionKey,
+    mut rx: UnboundedReceiver<PeerStateResult<SocketAddr>>,
+    writer: UpdateWriter,
+    api: ApiServer,
+) {
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            handle_peer_event(peer_key, event, &writer, &api);
+        }
+    });
+}
+
+fn handle_peer_event(
+    peer_key: SessionKey,
+    event: PeerStateResult<SocketAddr>,
+    writer: &UpdateWriter,
+    api: &ApiServer,
+) {
+    match event {
+        Ok((_, BgpEvent::UpdateMsg(update, treatment))) => {
+            publish_update(peer_key, treatment, update, writer, api);
+        }
+        // Routes released by the route flap damping are reported as received
+        Ok((_, BgpEvent::DampedRoutesReused(update))) => {
+            publish_update(peer_key, UpdateTreatment::Normal, update, writer, api);
+        }
+        // Routes whose next hop became reachable or unreachable
+        Ok((_, BgpEvent::NextHopRoutesChanged(update))) => {
+            publish_update(peer_key, UpdateTreatment::Normal, update, writer, api);
+        }
+        Ok((state, event)) => log::debug!("[{peer_key}][{state}] {event:?}"),
+        Err(err) => log::warn!("[{peer_key}] {err}"),
+    }
+}
+
+fn publish_update(
+    peer_key: SessionKey,
+    treatment: UpdateTreatment,
+    update: BgpUpdateMessage,
+    writer: &UpdateWriter,
+    api: &ApiServer,
+) {
+    let record = UpdateRecord::new(Utc::now(), peer_key, treatment, update);
+    api.publish(record.clone());
+    if !writer.write(record) {
+        log::error!("[{peer_key}] Output is closed, dropping update");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BfdSettings, TimersConfig};
+    use ipnet::IpNet;
+    use netgauze_bgp_pkt::{
+        codec::BgpCodec,
+        nlri::{Ipv4Unicast, Ipv4UnicastAddress},
+        path_attribute::{
+            As4PathSegment, AsPath, AsPathSegmentType, NextHop, Origin, PathAttribute,
+            PathAttributeValue,
+        },
+        update::BgpUpdateMessage,
+        BgpMessage,
+    };
+    use netgauze_bgp_speaker::{connection::TcpActiveConnect, dynamic::DynamicPeerRange};
+    use std::{
+        net::{IpAddr, TcpListener},
+        time::Duration,
+    };
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    fn free_port(ip: Ipv4Addr) -> u16 {
+        TcpListener::bind((ip, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn update(prefix: &str, as_number: u32, next_hop: Ipv4Addr) -> BgpUpdateMessage {
+        let attr = |value| PathAttribute::from(false, true, false, false, value).unwrap();
+        BgpUpdateMessage::new(
+            vec![],
+            vec![
+                attr(PathAttributeValue::Origin(Origin::IGP)),
+                attr(PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![
+                    As4PathSegment::new(AsPathSegmentType::AsSequence, vec![as_number]),
+                ]))),
+                attr(PathAttributeValue::NextHop(NextHop::new(next_hop))),
+            ],
+            vec![Ipv4UnicastAddress::new_no_path_id(
+                Ipv4Unicast::from_net(prefix.parse().unwrap()).unwrap(),
+            )],
+        )
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_reload() {
+        let (writer, _) = UpdateWriter::spawn(tokio::io::sink());
+        let router_id = Ipv4Addr::new(192, 0, 2, 1);
+        let peer1 = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 65001);
+        let peer2 = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)), 65002);
+        let config = BgpdConfig::new(router_id, 65000, vec![]).with_peer(peer1.clone());
+        let mut daemon = BgpDaemon::new(config.clone(), writer);
+        assert_eq!(daemon.peer_keys(), vec![peer1.session_key()]);
+
+        let new_config = BgpdConfig::new(router_id, 65000, vec![]).with_peer(peer2.clone());
+        assert_eq!(daemon.reload(new_config.clone()), Ok(()));
+        assert_eq!(daemon.peer_keys(), vec![peer2.session_key()]);
+        assert_eq!(daemon.config(), &new_config);
+
+        // Sessions to the same peer from different local addresses
+        let peer3 = peer2
+            .clone()
+            .with_local_address(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)));
+        let multi_config = new_config.clone().with_peer(peer3.clone());
+        assert_eq!(daemon.reload(multi_config), Ok(()));
+        let mut peer_keys = daemon.peer_keys();
+        peer_keys.sort();
+        assert_eq!(peer_keys, vec![peer2.session_key(), peer3.session_key()]);
+        assert_eq!(daemon.reload(new_config), Ok(()));
+
+        assert_eq!(
+            daemon.reload(BgpdConfig::new(
+                Ipv4Addr::new(192, 0, 2, 100),
+                65000,
+                vec![]
+            )),
+            Err(ReloadError::RouterIdChanged {
+                current: router_id,
+                new: Ipv4Addr::new(192, 0, 2, 100)
+            })
+        );
+        assert_eq!(
+            daemon.reload(BgpdConfig::new(router_id, 65100, vec![])),
+            Err(ReloadError::AsnChanged {
+                current: 65000,
+                new: 65100
+            })
+        );
+        assert_eq!(
+            daemon.reload(
+                BgpdConfig::new(router_id, 65000, vec![]).with_bfd(Some(BfdSettings {
+                    listen: "127.0.0.1:3784".parse().unwrap(),
+                    desired_min_tx: 300,
+                    required_min_rx: 300,
+                    detect_mult: 3,
+                }))
+            ),
+            Err(ReloadError::BfdChanged)
+        );
+        assert_eq!(daemon.peer_keys(), vec![peer2.session_key()]);
+    }
+
+    /// A dynamic peer accepted from a range, as created by the listener
+    fn add_dynamic_peer(daemon: &mut BgpDaemon, peer_addr: SocketAddr) -> SessionKey {
+        let peer_key = SessionKey::new(peer_addr.ip());
+        let range = DynamicPeerRange::new(IpNet::from(peer_addr.ip()), Default::default());
+        daemon
+            .supervisor
+            .dynamic_peer_from_range::<BgpCodec, _>(
+                peer_key,
+                peer_addr,
+                TcpActiveConnect::new(),
+                &range,
+            )
+            .unwrap();
+        peer_key
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_api_add_remove_peer() {
+        let (writer, _) = UpdateWriter::spawn(tokio::io::sink());
+        let router_id = Ipv4Addr::new(192, 0, 2, 1);
+        let local_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+        let peer1 = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 65001);
+        let peer2 = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)), 65002);
+        let config = BgpdConfig::new(router_id, 65000, vec![]).with_peer(peer1.clone());
+        let mut daemon = BgpDaemon::new(config, writer);
+        let dynamic_key = add_dynamic_peer(&mut daemon, "10.0.0.1:179".parse().unwrap());
+
+        // Only the added peer is started, the running peers are kept
+        assert!(daemon.add_peer(peer2.clone()).is_ok());
+        let mut peer_keys = daemon.peer_keys();
+        peer_keys.sort();
+        assert_eq!(
+            peer_keys,
+            vec![dynamic_key, peer1.session_key(), peer2.session_key()]
+        );
+        assert_eq!(
+            daemon.listener.session_key(local_ip, peer2.address()),
+            Some(peer2.session_key())
+        );
+        assert!(matches!(
+            daemon.add_peer(peer2.clone()),
+            Err(ConfigError::DuplicatePeer(_))
+        ));
+
+        assert!(daemon.remove_peer(&peer2.session_key()));
+        assert!(!daemon.remove_peer(&peer2.session_key()));
+        let mut peer_keys = daemon.peer_keys();
+        peer_keys.sort();
+        assert_eq!(peer_keys, vec![dynamic_key, peer1.session_key()]);
+        assert_eq!(daemon.listener.session_key(local_ip, peer2.address()), None);
+        assert_eq!(daemon.config().peers(), &vec![peer1]);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_reload_keeps_dynamic_peers() {
+        let (writer, _) = UpdateWriter::spawn(tokio::io::sink());
+        let router_id = Ipv4Addr::new(192, 0, 2, 1);
+        let peer1 = PeerEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 65001);
+        let config = BgpdConfig::new(router_id, 65000, vec![])
+            .with_dynamic_peer_ranges(vec!["10.0.0.0/8".parse().unwrap()])
+            .with_peer(peer1.clone());
+        let mut daemon = BgpDaemon::new(config.clone(), writer);
+        let dynamic_key = add_dynamic_peer(&mut daemon, "10.0.0.1:179".parse().unwrap());
+
+        assert_eq!(daemon.reload(config.clone()), Ok(()));
+        let mut peer_keys = daemon.peer_keys();
+        peer_keys.sort();
+        assert_eq!(peer_keys, vec![dynamic_key, peer1.session_key()]);
+
+        let new_config = BgpdConfig::new(router_id, 65000, vec![])
+            .with_dynamic_peer_ranges(vec!["10.0.0.0/8".parse().unwrap()]);
+        assert_eq!(daemon.reload(new_config), Ok(()));
+        assert_eq!(daemon.peer_keys(), vec![dynamic_key]);
+    }
+
+    /// Two daemons peering over loopback, one announces a prefix and the
+    /// other writes it to its output
+    #[cfg(target_os = "linux")]
+    #[test_log::test(tokio::test)]
+    async fn test_loopback_session() {
+        let ip1 = Ipv4Addr::new(127, 0, 0, 1);
+        let ip2 = Ipv4Addr::new(127, 0, 0, 2);
+        let ip3 = Ipv4Addr::new(127, 0, 0, 3);
+        let (port1, port2) = (free_port(ip1), free_port(ip2));
+        let timers = TimersConfig {
+            connect_retry: Some(1),
+            ..Default::default()
+        };
+        let config1 = BgpdConfig::new(
+            Ipv4Addr::new(1, 1, 1, 1),
+            65001,
+            vec![SocketAddr::new(IpAddr::V4(ip1), port1)],
+        )
+        .with_peer(
+            PeerEntry::new(IpAddr::V4(ip2), 65002)
+                .with_port(port2)
+                .with_local_address(IpAddr::V4(ip1))
+                .with_timers(timers),
+        );
+        let config2 = BgpdConfig::new(
+            Ipv4Addr::new(2, 2, 2, 2),
+            65002,
+            vec![SocketAddr::new(IpAddr::V4(ip2), port2)],
+        )
+        .with_peer(
+            PeerEntry::new(IpAddr::V4(ip1), 65001)
+                .with_port(port1)
+                .with_passive(true),
+        );
+
+        let (output_tx, output_rx) = tokio::io::duplex(64 * 1024);
+        let (writer2, _) = UpdateWriter::spawn(output_tx);
+        let daemon2 = BgpDaemon::new(config2, writer2);
+        let (_reload2_tx, reload2_rx) = mpsc::unbounded_channel();
+        let daemon2 = tokio::spawn(daemon2.run(reload2_rx));
+        // A failed connection attempt is not retried, so wait for the passive
+        // side to listen. The probe comes from an unconfigured address and is
+        // dropped by the listener.
+        loop {
+            let probe = tokio::net::TcpSocket::new_v4().unwrap();
+            probe.bind(SocketAddr::new(IpAddr::V4(ip3), 0)).unwrap();
+            if probe
+                .connect(SocketAddr::new(IpAddr::V4(ip2), port2))
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let (writer1, _) = UpdateWriter::spawn(tokio::io::sink());
+        let mut daemon1 = BgpDaemon::new(config1, writer1);
+        let mut handle = daemon1
+            .peer_handle(&SessionKey::new(IpAddr::V4(ip2)).with_local_ip(Some(IpAddr::V4(ip1))))
+            .unwrap();
+        let (_reload1_tx, reload1_rx) = mpsc::unbounded_channel();
+        let daemon1 = tokio::spawn(daemon1.run(reload1_rx));
+
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while handle.peer_stats().await.unwrap().last_up().is_none() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("session is not established");
+
+        let announced = update("10.0.0.0/24", 65001, ip1);
+        handle
+            .send_bgp_message(BgpMessage::Update(announced.clone()))
+            .unwrap();
+        let mut lines = BufReader::new(output_rx).lines();
+        let line = tokio::time::timeout(Duration::from_secs(10), lines.next_line())
+            .await
+            .expect("no update is written")
+            .unwrap()
+            .unwrap();
+        let record: UpdateRecord = serde_json::from_str(&line).unwrap();
+        assert_eq!(record.peer(), SessionKey::new(IpAddr::V4(ip1)));
+        assert_eq!(record.update(), &announced);
+
+        daemon1.abort();
+        daemon2.abort();
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration file driven BGP daemon. Peers defined in the config are run
+//! by a [netgauze_bgp_speaker::supervisor::PeersSupervisor], and the updates
+//! received from them are written as JSON lines.
+
+pub mod api;
+pub mod config;
+pub mod daemon;
+pub mod output;
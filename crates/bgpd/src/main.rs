@@ -0,0 +1,113 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::Parser;
+use tokio::{io::AsyncWrite, sync::mpsc};
+
+use netgauze_bgpd::{config::BgpdConfig, daemon::BgpDaemon, output::UpdateWriter};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Path to the config file, in TOML (.toml) or YAML (.yaml, .yml) format
+    #[arg(short, long)]
+    config: PathBuf,
+
+    /// Append the received updates to this file instead of writing them to
+    /// stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Serve the REST management API on this address, e.g. 127.0.0.1:8080
+    #[arg(long)]
+    api_listen: Option<SocketAddr>,
+}
+
+/// Re-read the config file on SIGHUP and send it to the daemon
+#[cfg(unix)]
+fn spawn_reload_on_sighup(
+    path: PathBuf,
+    reload_tx: mpsc::UnboundedSender<BgpdConfig>,
+) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while sighup.recv().await.is_some() {
+            log::info!("Received SIGHUP, reading config from {}", path.display());
+            match BgpdConfig::from_file(&path) {
+                Ok(config) => {
+                    if reload_tx.send(config).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => log::error!("Config reload failed: {err}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_reload_on_sighup(
+    _path: PathBuf,
+    _reload_tx: mpsc::UnboundedSender<BgpdConfig>,
+) -> std::io::Result<()> {
+    log::warn!("Config reload is only supported on unix platforms");
+    Ok(())
+}
+
+#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let env = env_logger::Env::default()
+        .filter_or("MY_LOG_LEVEL", "INFO")
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+
+    let args = Args::parse();
+    let config = BgpdConfig::from_file(&args.config)?;
+
+    let output: Box<dyn AsyncWrite + Send + Unpin> = match &args.output {
+        Some(path) => Box::new(
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?,
+        ),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let (writer, writer_handle) = UpdateWriter::spawn(output);
+
+    let (reload_tx, reload_rx) = mpsc::unbounded_channel();
+    spawn_reload_on_sighup(args.config.clone(), reload_tx)?;
+
+    let daemon = BgpDaemon::new(config, writer);
+    if let Some(api_listen) = args.api_listen {
+        let api = daemon.api();
+        tokio::spawn(async move {
+            if let Err(err) = api.serve(api_listen).await {
+                log::error!("Management API stopped: {err}");
+            }
+        });
+    }
+    tokio::select! {
+        result = daemon.run(reload_rx) => result?,
+        result = writer_handle => result??,
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Write the received BGP updates as JSON lines
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use netgauze_bgp_pkt::update::BgpUpdateMessage;
+use netgauze_bgp_speaker::{events::UpdateTreatment, listener::SessionKey};
+
+/// A single line of the output, `peer` is the session key of the peer the
+/// update is received from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateRecord {
+    timestamp: DateTime<Utc>,
+    peer: SessionKey,
+    treatment: UpdateTreatment,
+    update: BgpUpdateMessage,
+}
+
+impl UpdateRecord {
+    pub const fn new(
+        timestamp: DateTime<Utc>,
+        peer: SessionKey,
+        treatment: UpdateTreatment,
+        update: BgpUpdateMessage,
+    ) -> Self {
+        Self {
+            timestamp,
+            peer,
+            treatment,
+            update,
+        }
+    }
+
+    pub const fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub const fn peer(&self) -> SessionKey {
+        self.peer
+    }
+
+    pub const fn treatment(&self) -> &UpdateTreatment {
+        &self.treatment
+    }
+
+    pub const fn update(&self) -> &BgpUpdateMessage {
+        &self.update
+    }
+}
+
+/// Sending side of the output, records are serialized and written by a
+/// background task in the order they're received
+#[derive(Debug, Clone)]
+pub struct UpdateWriter {
+    tx: mpsc::UnboundedSender<UpdateRecord>,
+}
+
+impl UpdateWriter {
+    /// Spawn the task writing to `output`. The task stops on the first write
+    /// error, or when all the [UpdateWriter] clones are dropped.
+    pub fn spawn<W: AsyncWrite + Send + Unpin + 'static>(
+        mut output: W,
+    ) -> (Self, JoinHandle<std::io::Result<()>>) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<UpdateRecord>();
+        let handle = tokio::spawn(async move {
+            while let Some(record) = rx.recv().await {
+                let mut line = match serde_json::to_vec(&record) {
+                    Ok(line) => line,
+                    Err(err) => {
+                        log::error!("Couldn't serialize update from {}: {err}", record.peer);
+                        continue;
+                    }
+                };
+                line.push(b'\n');
+                output.write_all(&line).await?;
+                output.flush().await?;
+            }
+            Ok(())
+        });
+        (Self { tx }, handle)
+    }
+
+    /// Returns false if the writer task is stopped
+    pub fn write(&self, record: UpdateRecord) -> bool {
+        self.tx.send(record).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[tokio::test]
+    async fn test_write_json_lines() {
+        let (tx, rx) = tokio::io::duplex(1024);
+        let (writer, handle) = UpdateWriter::spawn(tx);
+        let peer = SessionKey::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)));
+        let record = UpdateRecord::new(
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            peer,
+            UpdateTreatment::Normal,
+            BgpUpdateMessage::new(vec![], vec![], vec![]),
+        );
+        assert!(writer.write(record.clone()));
+        assert!(writer.write(record.clone()));
+        drop(writer);
+        handle.await.unwrap().unwrap();
+
+        let mut lines = BufReader::new(rx).lines();
+        for _ in 0..2 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            assert!(
+                line.starts_with("{\"timestamp\":\"2024-01-01T00:00:00Z\",\"peer\":\"192.0.2.2\"")
+            );
+            let parsed: UpdateRecord = serde_json::from_str(&line).unwrap();
+            assert_eq!(parsed, record);
+        }
+        assert_eq!(lines.next_line().await.unwrap(), None);
+    }
+}
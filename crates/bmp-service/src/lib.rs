@@ -22,6 +22,8 @@ use std::{
 };
 
 pub mod handle;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod server;
 pub mod transport;
 
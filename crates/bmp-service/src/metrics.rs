@@ -0,0 +1,120 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus metrics of the BMP server, registered in the process wide
+//! [netgauze_metrics::registry] when the `metrics` feature is enabled.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+};
+
+use netgauze_bmp_pkt::{codec::BmpCodecDecoderError, BmpMessage};
+use netgauze_metrics::{Collect, Counter, Family, GaugeFn, MetricType};
+
+use crate::handle::BmpServerHandle;
+
+/// Number of active BMP connections, as reported by
+/// [BmpServerHandle::connection_count]
+pub const ACTIVE_CONNECTIONS: &str = "netgauze_bmp_active_connections";
+/// BMP messages received by message type
+pub const MESSAGES_RECEIVED: &str = "netgauze_bmp_messages_received_total";
+/// Errors decoding BMP messages by [BmpCodecDecoderError] variant
+pub const DECODE_ERRORS: &str = "netgauze_bmp_decode_errors_total";
+
+pub(crate) struct BmpServerMetrics {
+    messages_received: Arc<Family<Counter>>,
+    decode_errors: Arc<Family<Counter>>,
+}
+
+impl BmpServerMetrics {
+    fn new() -> Self {
+        let registry = netgauze_metrics::registry();
+        Self {
+            messages_received: registry
+                .counter_family(
+                    MESSAGES_RECEIVED,
+                    "BMP messages received",
+                    &["local_addr", "type"],
+                )
+                .expect("metric registered with a different type"),
+            decode_errors: registry
+                .counter_family(
+                    DECODE_ERRORS,
+                    "Errors decoding BMP messages",
+                    &["local_addr", "error"],
+                )
+                .expect("metric registered with a different type"),
+        }
+    }
+
+    pub(crate) fn message_received(&self, local_addr: SocketAddr, msg: &BmpMessage) {
+        let msg_type = format!("{:?}", msg.get_type());
+        self.messages_received
+            .with_labels(&[&local_addr.to_string(), &msg_type])
+            .inc();
+    }
+
+    pub(crate) fn decode_error(&self, local_addr: SocketAddr, error: &BmpCodecDecoderError) {
+        self.decode_errors
+            .with_labels(&[&local_addr.to_string(), decode_error_kind(error)])
+            .inc();
+    }
+}
+
+pub(crate) fn decode_error_kind(error: &BmpCodecDecoderError) -> &'static str {
+    match error {
+        BmpCodecDecoderError::IoError(_) => "IoError",
+        BmpCodecDecoderError::Incomplete(_) => "Incomplete",
+        BmpCodecDecoderError::BmpMessageParsingError(_) => "BmpMessageParsingError",
+    }
+}
+
+/// Metrics of the BMP server, registered on first use
+pub(crate) fn metrics() -> &'static BmpServerMetrics {
+    static METRICS: OnceLock<BmpServerMetrics> = OnceLock::new();
+    METRICS.get_or_init(BmpServerMetrics::new)
+}
+
+/// Report the active connections of a [crate::server::BmpServer] for as long
+/// as the guard is alive
+pub(crate) struct ActiveConnectionsGuard {
+    collector: Arc<dyn Collect>,
+}
+
+impl ActiveConnectionsGuard {
+    pub(crate) fn new(local_addr: SocketAddr, handle: BmpServerHandle) -> Self {
+        let local_addr = local_addr.to_string();
+        let collector: Arc<dyn Collect> = Arc::new(GaugeFn::new(
+            &[("local_addr", local_addr.as_str())],
+            move || handle.connection_count() as f64,
+        ));
+        netgauze_metrics::registry()
+            .register(
+                ACTIVE_CONNECTIONS,
+                "Number of active BMP connections",
+                MetricType::Gauge,
+                collector.clone(),
+            )
+            .expect("metric registered with a different type");
+        Self { collector }
+    }
+}
+
+impl Drop for ActiveConnectionsGuard {
+    fn drop(&mut self) {
+        netgauze_metrics::registry().unregister(ACTIVE_CONNECTIONS, &self.collector);
+    }
+}
@@ -67,6 +67,9 @@ impl BmpServer {
         tracing::info!("binding on socket");
         let listener = TcpListener::bind(local_addr).await?;
         let handle = self.handle;
+        #[cfg(feature = "metrics")]
+        let _active_connections =
+            crate::metrics::ActiveConnectionsGuard::new(local_addr, handle.clone());
         handle.notify_listening();
         tracing::info!("started listening");
         let accept_loop_future = async {
@@ -148,6 +151,10 @@ impl BmpServer {
             let result = StreamExt::try_next(&mut framed).await;
             match result {
                 Ok(msg) => {
+                    #[cfg(feature = "metrics")]
+                    if let Some(msg) = &msg {
+                        crate::metrics::metrics().message_received(addr_info.local_socket(), msg);
+                    }
                     let is_last = msg.is_none();
                     let tagged = Ok(TaggedData::new(addr_info, msg));
                     service.ready().await?;
@@ -157,6 +164,8 @@ impl BmpServer {
                     }
                 }
                 Err(err) => {
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::metrics().decode_error(addr_info.local_socket(), &err);
                     let tagged = Err(TaggedData::new(addr_info, err));
                     service.ready().await?;
                     service.call(tagged).await?;
@@ -210,6 +219,32 @@ mod tests {
         assert!(server.is_finished());
     }
 
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_metrics() {
+        let (handle, server, addr) = start_server().await;
+        let mut client = connect(addr).await;
+        let msg = BmpMessage::V3(BmpMessageValue::Initiation(InitiationMessage::new(vec![])));
+        client.send(msg).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let encoded = netgauze_metrics::registry().encode();
+        assert!(encoded.contains(&format!(
+            "netgauze_bmp_active_connections{{local_addr=\"{addr}\"}} 1\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "netgauze_bmp_messages_received_total{{local_addr=\"{addr}\",type=\"Initiation\"}} 1\n"
+        )));
+
+        handle.shutdown();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(server.is_finished());
+        let encoded = netgauze_metrics::registry().encode();
+        assert!(!encoded.contains(&format!(
+            "netgauze_bmp_active_connections{{local_addr=\"{addr}\"}}"
+        )));
+    }
+
     fn get_free_socket() -> SocketAddr {
         let mut rng = rand::thread_rng();
         let port: u16 = rng.gen_range(25000..50000);
@@ -66,6 +66,8 @@ pub struct FlowInfoCodec {
     in_message: bool,
     netflow_v9_templates_map: netflow::TemplatesMap,
     ipfix_templates_map: ipfix::TemplatesMap,
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::CodecMetrics,
 }
 
 impl FlowInfoCodec {
@@ -74,6 +76,18 @@ impl FlowInfoCodec {
             in_message: false,
             netflow_v9_templates_map: HashMap::new(),
             ipfix_templates_map: HashMap::new(),
+            #[cfg(feature = "metrics")]
+            metrics: crate::metrics::CodecMetrics::default(),
+        }
+    }
+
+    /// Report the codec metrics to the given metrics instead of the process
+    /// wide registry
+    #[cfg(all(test, feature = "metrics"))]
+    pub(crate) fn with_metrics(metrics: std::sync::Arc<crate::metrics::FlowMetrics>) -> Self {
+        Self {
+            metrics: crate::metrics::CodecMetrics::new(metrics),
+            ..Self::new()
         }
     }
 
@@ -212,7 +226,7 @@ impl Decoder for FlowInfoCodec {
         // We're using IPFIX_HEADER_LENGTH as criteria to start parsing since it's
         // smaller than NetFlow v9 header size.
         let header_length = IPFIX_HEADER_LENGTH as usize;
-        if self.in_message || buf.len() >= header_length {
+        let result = if self.in_message || buf.len() >= header_length {
             let version: u16 = NetworkEndian::read_u16(&buf[0..2]);
             // Read the length (ipfix) or count (NetFlow v9), starting form after the
             // version
@@ -236,6 +250,15 @@ impl Decoder for FlowInfoCodec {
         } else {
             // We don't have enough data yet to start processing
             Ok(None)
+        };
+        #[cfg(feature = "metrics")]
+        {
+            self.metrics.decoded(&result);
+            self.metrics.update_templates(
+                self.ipfix_templates_map.len(),
+                self.netflow_v9_templates_map.len(),
+            );
         }
+        result
     }
 }
@@ -17,6 +17,8 @@
 pub mod codec;
 pub mod ie;
 pub mod ipfix;
+#[cfg(feature = "metrics")]
+mod metrics;
 pub mod netflow;
 #[cfg(feature = "serde")]
 pub mod wire;
@@ -0,0 +1,236 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus metrics of the flow codec, registered in the process wide
+//! [netgauze_metrics::registry] when the `metrics` feature is enabled.
+
+use std::sync::{Arc, OnceLock};
+
+use netgauze_metrics::{Counter, Family, Gauge, Registry};
+
+use crate::{codec::FlowInfoCodecDecoderError, FlowInfo};
+
+/// Flow packets decoded by version
+pub const PACKETS_DECODED: &str = "netgauze_flow_packets_decoded_total";
+/// Errors decoding flow packets by [FlowInfoCodecDecoderError] variant
+pub const DECODE_ERRORS: &str = "netgauze_flow_decode_errors_total";
+/// Number of templates cached by all the codecs by version
+pub const TEMPLATES: &str = "netgauze_flow_templates";
+
+const IPFIX: &str = "IPFIX";
+const NETFLOW_V9: &str = "NetFlowV9";
+
+#[derive(Debug)]
+pub(crate) struct FlowMetrics {
+    packets_decoded: Arc<Family<Counter>>,
+    decode_errors: Arc<Family<Counter>>,
+    templates: Arc<Family<Gauge>>,
+}
+
+impl FlowMetrics {
+    pub(crate) fn new(registry: &Registry) -> Self {
+        Self {
+            packets_decoded: registry
+                .counter_family(PACKETS_DECODED, "Flow packets decoded", &["version"])
+                .expect("metric registered with a different type"),
+            decode_errors: registry
+                .counter_family(DECODE_ERRORS, "Errors decoding flow packets", &["error"])
+                .expect("metric registered with a different type"),
+            templates: registry
+                .gauge_family(TEMPLATES, "Number of cached templates", &["version"])
+                .expect("metric registered with a different type"),
+        }
+    }
+
+    fn packet_decoded(&self, info: &FlowInfo) {
+        let version = match info {
+            FlowInfo::IPFIX(_) => IPFIX,
+            FlowInfo::NetFlowV9(_) => NETFLOW_V9,
+        };
+        self.packets_decoded.with_labels(&[version]).inc();
+    }
+
+    fn decode_error(&self, error: &FlowInfoCodecDecoderError) {
+        self.decode_errors
+            .with_labels(&[decode_error_kind(error)])
+            .inc();
+    }
+}
+
+pub(crate) fn decode_error_kind(error: &FlowInfoCodecDecoderError) -> &'static str {
+    match error {
+        FlowInfoCodecDecoderError::IoError(_) => "IoError",
+        FlowInfoCodecDecoderError::Incomplete(_) => "Incomplete",
+        FlowInfoCodecDecoderError::UnsupportedVersion(_) => "UnsupportedVersion",
+        FlowInfoCodecDecoderError::IpfixParsingError(_) => "IpfixParsingError",
+        FlowInfoCodecDecoderError::NetFlowV9ParingError(_) => "NetFlowV9ParingError",
+    }
+}
+
+/// Metrics of the flow codec in the process wide registry, registered on
+/// first use
+pub(crate) fn metrics() -> Arc<FlowMetrics> {
+    static METRICS: OnceLock<Arc<FlowMetrics>> = OnceLock::new();
+    METRICS
+        .get_or_init(|| Arc::new(FlowMetrics::new(&netgauze_metrics::registry())))
+        .clone()
+}
+
+/// Metrics reported by a single codec. The codec's contribution to the
+/// [TEMPLATES] gauge is withdrawn when the codec is dropped.
+#[derive(Debug)]
+pub(crate) struct CodecMetrics {
+    metrics: Arc<FlowMetrics>,
+    ipfix_templates: usize,
+    netflow_v9_templates: usize,
+}
+
+impl Default for CodecMetrics {
+    fn default() -> Self {
+        Self::new(metrics())
+    }
+}
+
+impl CodecMetrics {
+    pub(crate) const fn new(metrics: Arc<FlowMetrics>) -> Self {
+        Self {
+            metrics,
+            ipfix_templates: 0,
+            netflow_v9_templates: 0,
+        }
+    }
+
+    /// Count the outcome of a decode call
+    pub(crate) fn decoded(&self, result: &Result<Option<FlowInfo>, FlowInfoCodecDecoderError>) {
+        match result {
+            Ok(Some(info)) => self.metrics.packet_decoded(info),
+            Ok(None) => {}
+            Err(err) => self.metrics.decode_error(err),
+        }
+    }
+
+    /// Update the number of templates cached by the codec
+    pub(crate) fn update_templates(&mut self, ipfix: usize, netflow_v9: usize) {
+        let templates = &self.metrics.templates;
+        if ipfix != self.ipfix_templates {
+            templates
+                .with_labels(&[IPFIX])
+                .add(ipfix as i64 - self.ipfix_templates as i64);
+            self.ipfix_templates = ipfix;
+        }
+        if netflow_v9 != self.netflow_v9_templates {
+            templates
+                .with_labels(&[NETFLOW_V9])
+                .add(netflow_v9 as i64 - self.netflow_v9_templates as i64);
+            self.netflow_v9_templates = netflow_v9;
+        }
+    }
+}
+
+impl Drop for CodecMetrics {
+    fn drop(&mut self) {
+        self.update_templates(0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::FlowInfoCodec;
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    /// IPFIX packet with a single template (ID 256) of one field
+    const IPFIX_TEMPLATE_PACKET: [u8; 28] = [
+        0x00, 0x0a, // Version
+        0x00, 0x1c, // Length
+        0x58, 0x3d, 0xe0, 0x59, // Export time
+        0x00, 0x00, 0x00, 0x00, // Seq number
+        0x00, 0x00, 0x00, 0x00, // Observation domain
+        0x00, 0x02, 0x00, 0x0c, // Template set
+        0x01, 0x00, 0x00, 0x01, // Template ID and field count
+        0x00, 0x08, 0x00, 0x04, // sourceIPv4Address
+    ];
+
+    /// Header of a NetFlow v5 packet
+    const UNSUPPORTED_VERSION_PACKET: [u8; 16] = [
+        0x00, 0x05, 0x00, 0x10, 0x58, 0x3d, 0xe0, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+
+    fn counter(family: &Family<Counter>, label: &str) -> u64 {
+        family.get(&[label]).map(|x| x.get()).unwrap_or_default()
+    }
+
+    fn gauge(family: &Family<Gauge>, label: &str) -> i64 {
+        family.get(&[label]).map(|x| x.get()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_codec_metrics() {
+        let registry = Registry::new();
+        let metrics = Arc::new(FlowMetrics::new(&registry));
+        let mut codec = FlowInfoCodec::with_metrics(metrics.clone());
+        let mut other_codec = FlowInfoCodec::with_metrics(metrics.clone());
+
+        let mut buf = BytesMut::from(&IPFIX_TEMPLATE_PACKET[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Ok(Some(FlowInfo::IPFIX(_)))
+        ));
+        // Not enough data to decode a packet
+        let mut buf = BytesMut::from(&IPFIX_TEMPLATE_PACKET[..8]);
+        assert_eq!(codec.decode(&mut buf), Ok(None));
+        let mut buf = BytesMut::from(&IPFIX_TEMPLATE_PACKET[..]);
+        assert!(other_codec.decode(&mut buf).is_ok());
+        let mut buf = BytesMut::from(&UNSUPPORTED_VERSION_PACKET[..]);
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(FlowInfoCodecDecoderError::UnsupportedVersion(5))
+        );
+
+        assert_eq!(counter(&metrics.packets_decoded, IPFIX), 2);
+        assert_eq!(counter(&metrics.packets_decoded, NETFLOW_V9), 0);
+        assert_eq!(counter(&metrics.decode_errors, "UnsupportedVersion"), 1);
+        assert_eq!(gauge(&metrics.templates, IPFIX), 2);
+        assert_eq!(gauge(&metrics.templates, NETFLOW_V9), 0);
+
+        // Same template again doesn't change the gauge
+        let mut buf = BytesMut::from(&IPFIX_TEMPLATE_PACKET[..]);
+        assert!(codec.decode(&mut buf).is_ok());
+        assert_eq!(gauge(&metrics.templates, IPFIX), 2);
+
+        // Dropped codecs withdraw their templates
+        drop(codec);
+        assert_eq!(gauge(&metrics.templates, IPFIX), 1);
+        drop(other_codec);
+        assert_eq!(gauge(&metrics.templates, IPFIX), 0);
+        assert!(registry
+            .encode()
+            .contains("netgauze_flow_packets_decoded_total{version=\"IPFIX\"} 3\n"));
+    }
+
+    #[test]
+    fn test_decode_error_kind() {
+        assert_eq!(
+            decode_error_kind(&FlowInfoCodecDecoderError::Incomplete(None)),
+            "Incomplete"
+        );
+        assert_eq!(
+            decode_error_kind(&FlowInfoCodecDecoderError::IoError("closed".to_string())),
+            "IoError"
+        );
+    }
+}
@@ -0,0 +1,529 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lightweight metrics registry used by NetGauze services.
+//!
+//! Metrics are organized in families of [Counter] or [Gauge] sharing the same
+//! name and label names. A [Registry] renders all the registered families in
+//! the [Prometheus text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+//! which can be served over HTTP using [server::MetricsServer].
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Write},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+pub mod server;
+
+/// Content type of the Prometheus text exposition format
+pub const TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl Display for MetricType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Counter => write!(f, "counter"),
+            Self::Gauge => write!(f, "gauge"),
+        }
+    }
+}
+
+/// A single metric value that can be part of a [Family]
+pub trait Metric: Default + Send + Sync + 'static {
+    const TYPE: MetricType;
+
+    fn value(&self) -> f64;
+}
+
+/// Monotonically increasing counter
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Metric for Counter {
+    const TYPE: MetricType = MetricType::Counter;
+
+    fn value(&self) -> f64 {
+        self.get() as f64
+    }
+}
+
+/// A value that can go up and down
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn dec(&self) {
+        self.sub(1);
+    }
+
+    pub fn add(&self, value: i64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, value: i64) {
+        self.0.fetch_sub(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Metric for Gauge {
+    const TYPE: MetricType = MetricType::Gauge;
+
+    fn value(&self) -> f64 {
+        self.get() as f64
+    }
+}
+
+/// One value of a metric family along with its labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    labels: Vec<(String, String)>,
+    value: f64,
+}
+
+impl Sample {
+    pub const fn new(labels: Vec<(String, String)>, value: f64) -> Self {
+        Self { labels, value }
+    }
+
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    pub const fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+/// Source of samples that is scraped every time the [Registry] is encoded
+pub trait Collect: Send + Sync {
+    fn collect(&self) -> Vec<Sample>;
+}
+
+/// A set of metrics of the same type distinguished by their label values
+#[derive(Debug)]
+pub struct Family<M> {
+    label_names: Vec<&'static str>,
+    metrics: Mutex<BTreeMap<Vec<String>, Arc<M>>>,
+}
+
+impl<M: Metric> Family<M> {
+    pub fn new(label_names: &[&'static str]) -> Self {
+        Self {
+            label_names: label_names.to_vec(),
+            metrics: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn label_names(&self) -> &[&'static str] {
+        &self.label_names
+    }
+
+    /// Get the metric for the given label values, creating it if it doesn't
+    /// exist yet. Label values must be in the same order as the label names.
+    pub fn with_labels(&self, values: &[&str]) -> Arc<M> {
+        assert_eq!(
+            values.len(),
+            self.label_names.len(),
+            "label values don't match the label names {:?}",
+            self.label_names
+        );
+        let key = values.iter().map(|x| x.to_string()).collect();
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.entry(key).or_default().clone()
+    }
+
+    pub fn get(&self, values: &[&str]) -> Option<Arc<M>> {
+        let key: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        self.metrics.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Stop reporting the metric with the given label values
+    pub fn remove(&self, values: &[&str]) -> Option<Arc<M>> {
+        let key: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        self.metrics.lock().unwrap().remove(&key)
+    }
+
+    /// Stop reporting all the metrics with the given value for a label,
+    /// returns the number of removed metrics
+    pub fn remove_matching(&self, label_name: &str, value: &str) -> usize {
+        let Some(index) = self.label_names.iter().position(|name| *name == label_name) else {
+            return 0;
+        };
+        let mut metrics = self.metrics.lock().unwrap();
+        let before = metrics.len();
+        metrics.retain(|values, _| values[index] != value);
+        before - metrics.len()
+    }
+}
+
+impl<M: Metric> Collect for Family<M> {
+    fn collect(&self) -> Vec<Sample> {
+        self.metrics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(values, metric)| {
+                let labels = self
+                    .label_names
+                    .iter()
+                    .zip(values)
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect();
+                Sample::new(labels, metric.value())
+            })
+            .collect()
+    }
+}
+
+/// Gauge computed on demand by calling a function at scrape time, useful to
+/// expose values that are already tracked elsewhere.
+pub struct GaugeFn<F> {
+    labels: Vec<(String, String)>,
+    func: F,
+}
+
+impl<F: Fn() -> f64 + Send + Sync> GaugeFn<F> {
+    pub fn new(labels: &[(&str, &str)], func: F) -> Self {
+        Self {
+            labels: labels
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            func,
+        }
+    }
+}
+
+impl<F: Fn() -> f64 + Send + Sync> Collect for GaugeFn<F> {
+    fn collect(&self) -> Vec<Sample> {
+        vec![Sample::new(self.labels.clone(), (self.func)())]
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RegistryError {
+    /// A metric with the same name is already registered with another type
+    TypeMismatch {
+        name: String,
+        registered: MetricType,
+        requested: MetricType,
+    },
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TypeMismatch {
+                name,
+                registered,
+                requested,
+            } => write!(
+                f,
+                "metric {name} is registered as {registered} and cannot be registered as {requested}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+struct RegistryEntry {
+    help: String,
+    metric_type: MetricType,
+    collectors: Vec<Arc<dyn Collect>>,
+}
+
+/// Collection of metrics that are exposed together
+#[derive(Default)]
+pub struct Registry {
+    entries: Mutex<BTreeMap<String, RegistryEntry>>,
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries = self.entries.lock().unwrap();
+        f.debug_struct("Registry")
+            .field("metrics", &entries.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a collector under the given metric name. Multiple collectors
+    /// can be registered under the same name as long as they have the same
+    /// type, their samples are reported together.
+    pub fn register(
+        &self,
+        name: &str,
+        help: &str,
+        metric_type: MetricType,
+        collector: Arc<dyn Collect>,
+    ) -> Result<(), RegistryError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(name.to_string())
+            .or_insert_with(|| RegistryEntry {
+                help: help.to_string(),
+                metric_type,
+                collectors: vec![],
+            });
+        if entry.metric_type != metric_type {
+            return Err(RegistryError::TypeMismatch {
+                name: name.to_string(),
+                registered: entry.metric_type,
+                requested: metric_type,
+            });
+        }
+        entry.collectors.push(collector);
+        Ok(())
+    }
+
+    /// Remove a collector previously added with [Registry::register]
+    pub fn unregister(&self, name: &str, collector: &Arc<dyn Collect>) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(name) else {
+            return false;
+        };
+        let len = entry.collectors.len();
+        entry.collectors.retain(|x| !Arc::ptr_eq(x, collector));
+        let removed = entry.collectors.len() != len;
+        if entry.collectors.is_empty() {
+            entries.remove(name);
+        }
+        removed
+    }
+
+    /// Create and register a new family of counters
+    pub fn counter_family(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&'static str],
+    ) -> Result<Arc<Family<Counter>>, RegistryError> {
+        self.family(name, help, label_names)
+    }
+
+    /// Create and register a new family of gauges
+    pub fn gauge_family(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&'static str],
+    ) -> Result<Arc<Family<Gauge>>, RegistryError> {
+        self.family(name, help, label_names)
+    }
+
+    fn family<M: Metric>(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&'static str],
+    ) -> Result<Arc<Family<M>>, RegistryError> {
+        let family = Arc::new(Family::<M>::new(label_names));
+        self.register(name, help, M::TYPE, family.clone())?;
+        Ok(family)
+    }
+
+    /// Render all the registered metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        let entries = self.entries.lock().unwrap();
+        for (name, entry) in entries.iter() {
+            let _ = writeln!(out, "# HELP {name} {}", escape_help(&entry.help));
+            let _ = writeln!(out, "# TYPE {name} {}", entry.metric_type);
+            for sample in entry.collectors.iter().flat_map(|x| x.collect()) {
+                out.push_str(name);
+                if !sample.labels.is_empty() {
+                    out.push('{');
+                    for (index, (label, value)) in sample.labels.iter().enumerate() {
+                        if index > 0 {
+                            out.push(',');
+                        }
+                        let _ = write!(out, "{label}=\"{}\"", escape_label_value(value));
+                    }
+                    out.push('}');
+                }
+                let _ = writeln!(out, " {}", format_value(sample.value));
+            }
+        }
+        out
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value.is_sign_positive() {
+            "+Inf".to_string()
+        } else {
+            "-Inf".to_string()
+        }
+    } else {
+        value.to_string()
+    }
+}
+
+/// The process wide registry used by the NetGauze crates when compiled with
+/// the `metrics` feature.
+pub fn registry() -> Arc<Registry> {
+    static REGISTRY: OnceLock<Arc<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Arc::new(Registry::new())).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let registry = Registry::new();
+        let counter = registry
+            .counter_family(
+                "test_messages_total",
+                "Messages received",
+                &["peer", "type"],
+            )
+            .unwrap();
+        let gauge = registry.gauge_family("test_up", "Is up", &[]).unwrap();
+        counter.with_labels(&["10.0.0.1", "Update"]).inc_by(3);
+        counter.with_labels(&["10.0.0.1", "Open"]).inc();
+        gauge.with_labels(&[]).set(1);
+        let func: Arc<dyn Collect> = Arc::new(GaugeFn::new(&[("addr", "a\"b")], || 2.0));
+        registry
+            .register("test_connections", "Connections", MetricType::Gauge, func)
+            .unwrap();
+
+        assert_eq!(
+            registry.encode(),
+            "# HELP test_connections Connections\n\
+             # TYPE test_connections gauge\n\
+             test_connections{addr=\"a\\\"b\"} 2\n\
+             # HELP test_messages_total Messages received\n\
+             # TYPE test_messages_total counter\n\
+             test_messages_total{peer=\"10.0.0.1\",type=\"Open\"} 1\n\
+             test_messages_total{peer=\"10.0.0.1\",type=\"Update\"} 3\n\
+             # HELP test_up Is up\n\
+             # TYPE test_up gauge\n\
+             test_up 1\n"
+        );
+    }
+
+    #[test]
+    fn test_register_type_mismatch() {
+        let registry = Registry::new();
+        registry.counter_family("test_total", "Test", &[]).unwrap();
+        assert_eq!(
+            registry.gauge_family("test_total", "Test", &[]).err(),
+            Some(RegistryError::TypeMismatch {
+                name: "test_total".to_string(),
+                registered: MetricType::Counter,
+                requested: MetricType::Gauge,
+            })
+        );
+    }
+
+    #[test]
+    fn test_unregister() {
+        let registry = Registry::new();
+        let first: Arc<dyn Collect> = Arc::new(GaugeFn::new(&[("id", "1")], || 1.0));
+        let second: Arc<dyn Collect> = Arc::new(GaugeFn::new(&[("id", "2")], || 2.0));
+        registry
+            .register("test", "Test", MetricType::Gauge, first.clone())
+            .unwrap();
+        registry
+            .register("test", "Test", MetricType::Gauge, second.clone())
+            .unwrap();
+        assert!(registry.unregister("test", &first));
+        assert!(!registry.unregister("test", &first));
+        assert_eq!(
+            registry.encode(),
+            "# HELP test Test\n# TYPE test gauge\ntest{id=\"2\"} 2\n"
+        );
+        assert!(registry.unregister("test", &second));
+        assert_eq!(registry.encode(), "");
+    }
+
+    #[test]
+    fn test_family_remove() {
+        let family = Family::<Gauge>::new(&["state"]);
+        family.with_labels(&["Idle"]).set(1);
+        assert_eq!(family.get(&["Idle"]).map(|x| x.get()), Some(1));
+        assert!(family.remove(&["Idle"]).is_some());
+        assert!(family.collect().is_empty());
+    }
+
+    #[test]
+    fn test_family_remove_matching() {
+        let family = Family::<Counter>::new(&["peer", "type"]);
+        family.with_labels(&["10.0.0.1", "Open"]).inc();
+        family.with_labels(&["10.0.0.1", "Update"]).inc();
+        family.with_labels(&["10.0.0.2", "Open"]).inc();
+        assert_eq!(family.remove_matching("peer", "10.0.0.1"), 2);
+        assert_eq!(family.remove_matching("other", "10.0.0.2"), 0);
+        assert!(family.get(&["10.0.0.2", "Open"]).is_some());
+        assert_eq!(family.collect().len(), 1);
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP endpoint serving the metrics of a [Registry] on `GET /metrics`
+
+use std::{convert::Infallible, io, net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{
+    body::Incoming, header, server::conn::http1, service::service_fn, Method, Request, Response,
+    StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use crate::{Registry, TEXT_CONTENT_TYPE};
+
+/// Path the metrics are served on
+pub const METRICS_PATH: &str = "/metrics";
+
+#[derive(Debug, Clone)]
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    registry: Arc<Registry>,
+}
+
+impl MetricsServer {
+    pub const fn new(local_addr: SocketAddr, registry: Arc<Registry>) -> Self {
+        Self {
+            local_addr,
+            registry,
+        }
+    }
+
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Bind to the configured address and serve metrics until the returned
+    /// future is dropped
+    pub async fn serve(self) -> io::Result<()> {
+        let listener = TcpListener::bind(self.local_addr).await?;
+        Self::serve_listener(listener, self.registry).await
+    }
+
+    /// Serve metrics on an already bound listener
+    pub async fn serve_listener(listener: TcpListener, registry: Arc<Registry>) -> io::Result<()> {
+        log::info!(
+            "Serving metrics on http://{}{METRICS_PATH}",
+            listener.local_addr()?
+        );
+        loop {
+            let (stream, remote_addr) = listener.accept().await?;
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let response = handle_request(&registry, &request);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    log::debug!("Error serving metrics to {remote_addr}: {err}");
+                }
+            });
+        }
+    }
+}
+
+fn handle_request(registry: &Registry, request: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let (status, content_type, body) = if request.uri().path() != METRICS_PATH {
+        (
+            StatusCode::NOT_FOUND,
+            "text/plain",
+            "Not Found\n".to_string(),
+        )
+    } else if request.method() != Method::GET {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "text/plain",
+            "Method Not Allowed\n".to_string(),
+        )
+    } else {
+        (StatusCode::OK, TEXT_CONTENT_TYPE, registry.encode())
+    };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(body)))
+        .expect("valid response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let registry = Arc::new(Registry::new());
+        registry
+            .counter_family("test_total", "Test", &[])
+            .unwrap()
+            .with_labels(&[])
+            .inc();
+        let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(MetricsServer::serve_listener(listener, registry));
+
+        let response = request(
+            addr,
+            "GET /metrics?name[]=x HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        let body = "# HELP test_total Test\n# TYPE test_total counter\ntest_total 1\n";
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(&format!("content-type: {TEXT_CONTENT_TYPE}\r\n")));
+        assert!(response.contains(&format!("content-length: {}\r\n", body.len())));
+        assert!(response.ends_with(&format!("\r\n\r\n{body}")));
+
+        let response = request(
+            addr,
+            "GET /other HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = request(
+            addr,
+            "POST /metrics HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        let response = request(addr, "GET\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        server.abort();
+    }
+
+    #[test]
+    fn test_server_debug() {
+        let registry = Arc::new(Registry::new());
+        registry.gauge_family("test", "Test", &[]).unwrap();
+        let server = MetricsServer::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9090),
+            registry,
+        );
+        assert_eq!(
+            format!("{server:?}"),
+            "MetricsServer { local_addr: 127.0.0.1:9090, registry: Registry { metrics: [\"test\"] } }"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains MRT codes that are registered at IANA [Multi-Threaded Routing Toolkit (MRT) Parameters](https://www.iana.org/assignments/mrt/mrt.xhtml)
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, FromRepr};
+
+/// Corresponds to the bit 0 of the Peer Type in a PEER_INDEX_TABLE entry. If
+/// set indicates that the Peer IP Address is an IPv6 address. See [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.1)
+pub const PEER_TYPE_IS_IPV6: u8 = 0b00000001;
+
+/// Corresponds to the bit 1 of the Peer Type in a PEER_INDEX_TABLE entry. If
+/// set indicates that the Peer AS is a 4-octet AS number. See [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396#section-4.3.1)
+pub const PEER_TYPE_IS_ASN4: u8 = 0b00000010;
+
+/// MRT Types as registered in IANA [MRT Types](https://www.iana.org/assignments/mrt/mrt.xhtml#type-codes)
+#[repr(u16)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MrtType {
+    OspfV2 = 11,
+    TableDump = 12,
+    TableDumpV2 = 13,
+    Bgp4mp = 16,
+    Bgp4mpEt = 17,
+    Isis = 32,
+    IsisEt = 33,
+    OspfV3 = 48,
+    OspfV3Et = 49,
+}
+
+/// MRT type is not one of [`MrtType`], the carried value is the undefined
+/// code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedMrtType(pub u16);
+
+impl From<MrtType> for u16 {
+    fn from(value: MrtType) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for MrtType {
+    type Error = UndefinedMrtType;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedMrtType(value)),
+        }
+    }
+}
+
+/// TABLE_DUMP_V2 Subtypes as registered in IANA [TABLE_DUMP_V2 Subtype Codes](https://www.iana.org/assignments/mrt/mrt.xhtml#table-dump-v2-subtype-codes)
+#[repr(u16)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TableDumpV2Subtype {
+    PeerIndexTable = 1,
+    RibIpv4Unicast = 2,
+    RibIpv4Multicast = 3,
+    RibIpv6Unicast = 4,
+    RibIpv6Multicast = 5,
+    RibGeneric = 6,
+    GeoPeerTable = 7,
+    RibIpv4UnicastAddPath = 8,
+    RibIpv4MulticastAddPath = 9,
+    RibIpv6UnicastAddPath = 10,
+    RibIpv6MulticastAddPath = 11,
+    RibGenericAddPath = 12,
+}
+
+/// TABLE_DUMP_V2 subtype is not one of [`TableDumpV2Subtype`], the carried
+/// value is the undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedTableDumpV2Subtype(pub u16);
+
+impl From<TableDumpV2Subtype> for u16 {
+    fn from(value: TableDumpV2Subtype) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for TableDumpV2Subtype {
+    type Error = UndefinedTableDumpV2Subtype;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedTableDumpV2Subtype(value)),
+        }
+    }
+}
+
+/// BGP4MP and BGP4MP_ET Subtypes as registered in IANA [BGP4MP Subtype Codes](https://www.iana.org/assignments/mrt/mrt.xhtml#BGP4MP-codes)
+#[repr(u16)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Bgp4mpSubtype {
+    StateChange = 0,
+    Message = 1,
+    MessageAs4 = 4,
+    StateChangeAs4 = 5,
+    MessageLocal = 6,
+    MessageAs4Local = 7,
+    MessageAddPath = 8,
+    MessageAs4AddPath = 9,
+    MessageLocalAddPath = 10,
+    MessageAs4LocalAddPath = 11,
+}
+
+/// BGP4MP subtype is not one of [`Bgp4mpSubtype`], the carried value is the
+/// undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedBgp4mpSubtype(pub u16);
+
+impl From<Bgp4mpSubtype> for u16 {
+    fn from(value: Bgp4mpSubtype) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for Bgp4mpSubtype {
+    type Error = UndefinedBgp4mpSubtype;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedBgp4mpSubtype(value)),
+        }
+    }
+}
+
+/// BGP FSM states used in BGP4MP_STATE_CHANGE, see [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396#section-4.4.1)
+#[repr(u16)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Bgp4mpState {
+    Idle = 1,
+    Connect = 2,
+    Active = 3,
+    OpenSent = 4,
+    OpenConfirm = 5,
+    Established = 6,
+}
+
+/// BGP FSM state is not one of [`Bgp4mpState`], the carried value is the
+/// undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedBgp4mpState(pub u16);
+
+impl From<Bgp4mpState> for u16 {
+    fn from(value: Bgp4mpState) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for Bgp4mpState {
+    type Error = UndefinedBgp4mpState;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedBgp4mpState(value)),
+        }
+    }
+}
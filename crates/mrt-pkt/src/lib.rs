@@ -0,0 +1,631 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! MRT routing information export format representation, see [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396)
+//! and the ADD-PATH extensions in [RFC8050](https://datatracker.ietf.org/doc/html/rfc8050).
+//!
+//! Only the BGP related types are decoded, records of the other types are
+//! carried as [MrtValue::Unknown].
+
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr};
+
+use netgauze_bgp_pkt::{path_attribute::PathAttribute, BgpMessage};
+use netgauze_iana::address_family::AddressType;
+use serde::{Deserialize, Serialize};
+
+use crate::iana::{Bgp4mpState, Bgp4mpSubtype, MrtType, TableDumpV2Subtype};
+
+pub mod iana;
+#[cfg(feature = "serde")]
+pub mod reader;
+#[cfg(feature = "serde")]
+pub mod wire;
+
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                           Timestamp                           |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |             Type              |            Subtype            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                             Length                            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                      Message... (variable)
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// The microseconds of the Extended Timestamp types are carried in
+/// `timestamp` and are dropped when writing the other types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MrtRecord {
+    timestamp: DateTime<Utc>,
+    value: MrtValue,
+}
+
+impl MrtRecord {
+    pub const fn new(timestamp: DateTime<Utc>, value: MrtValue) -> Self {
+        Self { timestamp, value }
+    }
+
+    pub const fn timestamp(&self) -> &DateTime<Utc> {
+        &self.timestamp
+    }
+
+    pub const fn value(&self) -> &MrtValue {
+        &self.value
+    }
+
+    pub fn into_value(self) -> MrtValue {
+        self.value
+    }
+
+    /// Returns the MRT type code of the record
+    pub const fn type_code(&self) -> u16 {
+        self.value.type_code()
+    }
+
+    /// Returns the MRT subtype code of the record
+    pub const fn subtype_code(&self) -> u16 {
+        self.value.subtype_code()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MrtValue {
+    TableDumpV2(TableDumpV2),
+    Bgp4mp(Bgp4mp),
+    /// BGP4MP with microsecond resolution timestamp
+    Bgp4mpEt(Bgp4mp),
+    /// Records of types that are not decoded, the carried value is the
+    /// message after the MRT common header.
+    Unknown {
+        mrt_type: u16,
+        subtype: u16,
+        value: Vec<u8>,
+    },
+}
+
+impl MrtValue {
+    /// Returns the [MrtType] of the record, or `Err(code)` for unknown types
+    pub const fn get_type(&self) -> Result<MrtType, u16> {
+        match self {
+            Self::TableDumpV2(_) => Ok(MrtType::TableDumpV2),
+            Self::Bgp4mp(_) => Ok(MrtType::Bgp4mp),
+            Self::Bgp4mpEt(_) => Ok(MrtType::Bgp4mpEt),
+            Self::Unknown { mrt_type, .. } => Err(*mrt_type),
+        }
+    }
+
+    pub const fn type_code(&self) -> u16 {
+        match self.get_type() {
+            Ok(mrt_type) => mrt_type as u16,
+            Err(code) => code,
+        }
+    }
+
+    pub const fn subtype_code(&self) -> u16 {
+        match self {
+            Self::TableDumpV2(value) => value.get_subtype() as u16,
+            Self::Bgp4mp(value) => value.get_subtype() as u16,
+            Self::Bgp4mpEt(value) => value.get_subtype() as u16,
+            Self::Unknown { subtype, .. } => *subtype,
+        }
+    }
+}
+
+/// TABLE_DUMP_V2 records, see [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396#section-4.3)
+/// and [RFC8050](https://datatracker.ietf.org/doc/html/rfc8050#section-4)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TableDumpV2 {
+    PeerIndexTable(PeerIndexTable),
+    RibIpv4Unicast(AfiSafiRib),
+    RibIpv4Multicast(AfiSafiRib),
+    RibIpv6Unicast(AfiSafiRib),
+    RibIpv6Multicast(AfiSafiRib),
+    RibGeneric(RibGeneric),
+    RibIpv4UnicastAddPath(AfiSafiRib),
+    RibIpv4MulticastAddPath(AfiSafiRib),
+    RibIpv6UnicastAddPath(AfiSafiRib),
+    RibIpv6MulticastAddPath(AfiSafiRib),
+    RibGenericAddPath(RibGeneric),
+}
+
+impl TableDumpV2 {
+    pub const fn get_subtype(&self) -> TableDumpV2Subtype {
+        match self {
+            Self::PeerIndexTable(_) => TableDumpV2Subtype::PeerIndexTable,
+            Self::RibIpv4Unicast(_) => TableDumpV2Subtype::RibIpv4Unicast,
+            Self::RibIpv4Multicast(_) => TableDumpV2Subtype::RibIpv4Multicast,
+            Self::RibIpv6Unicast(_) => TableDumpV2Subtype::RibIpv6Unicast,
+            Self::RibIpv6Multicast(_) => TableDumpV2Subtype::RibIpv6Multicast,
+            Self::RibGeneric(_) => TableDumpV2Subtype::RibGeneric,
+            Self::RibIpv4UnicastAddPath(_) => TableDumpV2Subtype::RibIpv4UnicastAddPath,
+            Self::RibIpv4MulticastAddPath(_) => TableDumpV2Subtype::RibIpv4MulticastAddPath,
+            Self::RibIpv6UnicastAddPath(_) => TableDumpV2Subtype::RibIpv6UnicastAddPath,
+            Self::RibIpv6MulticastAddPath(_) => TableDumpV2Subtype::RibIpv6MulticastAddPath,
+            Self::RibGenericAddPath(_) => TableDumpV2Subtype::RibGenericAddPath,
+        }
+    }
+
+    /// The RIB entries carry a Path Identifier
+    pub const fn is_add_path(&self) -> bool {
+        matches!(
+            self,
+            Self::RibIpv4UnicastAddPath(_)
+                | Self::RibIpv4MulticastAddPath(_)
+                | Self::RibIpv6UnicastAddPath(_)
+                | Self::RibIpv6MulticastAddPath(_)
+                | Self::RibGenericAddPath(_)
+        )
+    }
+}
+
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                      Collector BGP ID                         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |       View Name Length        |     View Name (variable)      |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |          Peer Count           |    Peer Entries (variable)
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PeerIndexTable {
+    collector_bgp_id: Ipv4Addr,
+    view_name: String,
+    peers: Vec<PeerEntry>,
+}
+
+impl PeerIndexTable {
+    pub const fn new(collector_bgp_id: Ipv4Addr, view_name: String, peers: Vec<PeerEntry>) -> Self {
+        Self {
+            collector_bgp_id,
+            view_name,
+            peers,
+        }
+    }
+
+    pub const fn collector_bgp_id(&self) -> Ipv4Addr {
+        self.collector_bgp_id
+    }
+
+    pub fn view_name(&self) -> &str {
+        &self.view_name
+    }
+
+    pub const fn peers(&self) -> &Vec<PeerEntry> {
+        &self.peers
+    }
+
+    /// Look up the peer referenced by [RibEntry::peer_index]
+    pub fn peer(&self, peer_index: u16) -> Option<&PeerEntry> {
+        self.peers.get(peer_index as usize)
+    }
+}
+
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |   Peer Type   |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         Peer BGP ID                           |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                   Peer IP Address (variable)                  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                        Peer AS (variable)                     |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PeerEntry {
+    bgp_id: Ipv4Addr,
+    address: IpAddr,
+    asn: u32,
+    asn4: bool,
+}
+
+impl PeerEntry {
+    pub const fn new(bgp_id: Ipv4Addr, address: IpAddr, asn: u32, asn4: bool) -> Self {
+        Self {
+            bgp_id,
+            address,
+            asn,
+            asn4,
+        }
+    }
+
+    pub const fn bgp_id(&self) -> Ipv4Addr {
+        self.bgp_id
+    }
+
+    pub const fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+
+    /// The Peer AS is encoded as a 4-octet AS number
+    pub const fn asn4(&self) -> bool {
+        self.asn4
+    }
+}
+
+/// RIB_IPV4_UNICAST, RIB_IPV4_MULTICAST, RIB_IPV6_UNICAST, RIB_IPV6_MULTICAST
+/// and their ADD-PATH variants
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         Sequence Number                       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// | Prefix Length |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                        Prefix (variable)                      |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |         Entry Count           |  RIB Entries (variable)
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AfiSafiRib {
+    sequence_number: u32,
+    prefix: IpNet,
+    entries: Vec<RibEntry>,
+}
+
+impl AfiSafiRib {
+    pub const fn new(sequence_number: u32, prefix: IpNet, entries: Vec<RibEntry>) -> Self {
+        Self {
+            sequence_number,
+            prefix,
+            entries,
+        }
+    }
+
+    pub const fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+
+    pub const fn entries(&self) -> &Vec<RibEntry> {
+        &self.entries
+    }
+}
+
+/// RIB_GENERIC and RIB_GENERIC_ADDPATH, the NLRI is carried as encoded in the
+/// BGP UPDATE message, without the Path Identifier.
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         Sequence Number                       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |    Address Family Identifier  |Subsequent AFI |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |     Network Layer Reachability Information (variable)         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |         Entry Count           |  RIB Entries (variable)
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RibGeneric {
+    sequence_number: u32,
+    address_type: AddressType,
+    nlri: Vec<u8>,
+    entries: Vec<RibEntry>,
+}
+
+impl RibGeneric {
+    pub const fn new(
+        sequence_number: u32,
+        address_type: AddressType,
+        nlri: Vec<u8>,
+        entries: Vec<RibEntry>,
+    ) -> Self {
+        Self {
+            sequence_number,
+            address_type,
+            nlri,
+            entries,
+        }
+    }
+
+    pub const fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    pub const fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub const fn nlri(&self) -> &Vec<u8> {
+        &self.nlri
+    }
+
+    pub const fn entries(&self) -> &Vec<RibEntry> {
+        &self.entries
+    }
+}
+
+/// A route of a peer in the TABLE_DUMP_V2 RIB records. The `AS_PATH` is always
+/// encoded with 4-octet AS numbers, and the abbreviated `MP_REACH_NLRI` of
+/// the MRT format is expanded to a full [PathAttribute] with no NLRI.
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |         Peer Index            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         Originated Time                       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                 Path Identifier (ADD-PATH only)               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |      Attribute Length         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                    BGP Attributes... (variable)
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RibEntry {
+    peer_index: u16,
+    originated_time: DateTime<Utc>,
+    path_id: Option<u32>,
+    path_attributes: Vec<PathAttribute>,
+}
+
+impl RibEntry {
+    pub const fn new(
+        peer_index: u16,
+        originated_time: DateTime<Utc>,
+        path_id: Option<u32>,
+        path_attributes: Vec<PathAttribute>,
+    ) -> Self {
+        Self {
+            peer_index,
+            originated_time,
+            path_id,
+            path_attributes,
+        }
+    }
+
+    pub const fn peer_index(&self) -> u16 {
+        self.peer_index
+    }
+
+    pub const fn originated_time(&self) -> &DateTime<Utc> {
+        &self.originated_time
+    }
+
+    pub const fn path_id(&self) -> Option<u32> {
+        self.path_id
+    }
+
+    pub const fn path_attributes(&self) -> &Vec<PathAttribute> {
+        &self.path_attributes
+    }
+}
+
+/// BGP4MP and BGP4MP_ET records, see [RFC6396](https://datatracker.ietf.org/doc/html/rfc6396#section-4.4)
+/// and [RFC8050](https://datatracker.ietf.org/doc/html/rfc8050#section-3)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Bgp4mp {
+    StateChange(Bgp4mpStateChange),
+    Message(Bgp4mpMessage),
+    MessageAs4(Bgp4mpMessage),
+    StateChangeAs4(Bgp4mpStateChange),
+    MessageLocal(Bgp4mpMessage),
+    MessageAs4Local(Bgp4mpMessage),
+    MessageAddPath(Bgp4mpMessage),
+    MessageAs4AddPath(Bgp4mpMessage),
+    MessageLocalAddPath(Bgp4mpMessage),
+    MessageAs4LocalAddPath(Bgp4mpMessage),
+}
+
+impl Bgp4mp {
+    pub const fn get_subtype(&self) -> Bgp4mpSubtype {
+        match self {
+            Self::StateChange(_) => Bgp4mpSubtype::StateChange,
+            Self::Message(_) => Bgp4mpSubtype::Message,
+            Self::MessageAs4(_) => Bgp4mpSubtype::MessageAs4,
+            Self::StateChangeAs4(_) => Bgp4mpSubtype::StateChangeAs4,
+            Self::MessageLocal(_) => Bgp4mpSubtype::MessageLocal,
+            Self::MessageAs4Local(_) => Bgp4mpSubtype::MessageAs4Local,
+            Self::MessageAddPath(_) => Bgp4mpSubtype::MessageAddPath,
+            Self::MessageAs4AddPath(_) => Bgp4mpSubtype::MessageAs4AddPath,
+            Self::MessageLocalAddPath(_) => Bgp4mpSubtype::MessageLocalAddPath,
+            Self::MessageAs4LocalAddPath(_) => Bgp4mpSubtype::MessageAs4LocalAddPath,
+        }
+    }
+
+    /// Peer and Local AS numbers are 4-octets and the BGP message is encoded
+    /// for a session that negotiated the 4-octet AS number capability
+    pub const fn is_asn4(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageAs4(_)
+                | Self::StateChangeAs4(_)
+                | Self::MessageAs4Local(_)
+                | Self::MessageAs4AddPath(_)
+                | Self::MessageAs4LocalAddPath(_)
+        )
+    }
+
+    /// The BGP message is encoded for a session that negotiated ADD-PATH
+    pub const fn is_add_path(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageAddPath(_)
+                | Self::MessageAs4AddPath(_)
+                | Self::MessageLocalAddPath(_)
+                | Self::MessageAs4LocalAddPath(_)
+        )
+    }
+
+    /// The BGP message was sent by the local system rather than received
+    pub const fn is_local(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageLocal(_)
+                | Self::MessageAs4Local(_)
+                | Self::MessageLocalAddPath(_)
+                | Self::MessageAs4LocalAddPath(_)
+        )
+    }
+
+    pub const fn peer(&self) -> &Bgp4mpPeer {
+        match self {
+            Self::StateChange(value) | Self::StateChangeAs4(value) => &value.peer,
+            Self::Message(value)
+            | Self::MessageAs4(value)
+            | Self::MessageLocal(value)
+            | Self::MessageAs4Local(value)
+            | Self::MessageAddPath(value)
+            | Self::MessageAs4AddPath(value)
+            | Self::MessageLocalAddPath(value)
+            | Self::MessageAs4LocalAddPath(value) => &value.peer,
+        }
+    }
+}
+
+/// The header shared by all the BGP4MP subtypes
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |               Peer AS Number (2 or 4 octets)                  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |               Local AS Number (2 or 4 octets)                 |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |        Interface Index        |        Address Family         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                      Peer IP Address (variable)               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                      Local IP Address (variable)              |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// The Address Family is derived from `peer_address`, `local_address` must be
+/// of the same family.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bgp4mpPeer {
+    peer_asn: u32,
+    local_asn: u32,
+    interface_index: u16,
+    peer_address: IpAddr,
+    local_address: IpAddr,
+}
+
+impl Bgp4mpPeer {
+    pub const fn new(
+        peer_asn: u32,
+        local_asn: u32,
+        interface_index: u16,
+        peer_address: IpAddr,
+        local_address: IpAddr,
+    ) -> Self {
+        Self {
+            peer_asn,
+            local_asn,
+            interface_index,
+            peer_address,
+            local_address,
+        }
+    }
+
+    pub const fn peer_asn(&self) -> u32 {
+        self.peer_asn
+    }
+
+    pub const fn local_asn(&self) -> u32 {
+        self.local_asn
+    }
+
+    pub const fn interface_index(&self) -> u16 {
+        self.interface_index
+    }
+
+    pub const fn peer_address(&self) -> IpAddr {
+        self.peer_address
+    }
+
+    pub const fn local_address(&self) -> IpAddr {
+        self.local_address
+    }
+}
+
+/// BGP4MP_STATE_CHANGE and BGP4MP_STATE_CHANGE_AS4
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bgp4mpStateChange {
+    peer: Bgp4mpPeer,
+    old_state: Bgp4mpState,
+    new_state: Bgp4mpState,
+}
+
+impl Bgp4mpStateChange {
+    pub const fn new(peer: Bgp4mpPeer, old_state: Bgp4mpState, new_state: Bgp4mpState) -> Self {
+        Self {
+            peer,
+            old_state,
+            new_state,
+        }
+    }
+
+    pub const fn peer(&self) -> &Bgp4mpPeer {
+        &self.peer
+    }
+
+    pub const fn old_state(&self) -> Bgp4mpState {
+        self.old_state
+    }
+
+    pub const fn new_state(&self) -> Bgp4mpState {
+        self.new_state
+    }
+}
+
+/// BGP4MP_MESSAGE and its AS4, LOCAL and ADD-PATH variants, carrying the whole
+/// BGP message including its header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bgp4mpMessage {
+    peer: Bgp4mpPeer,
+    message: BgpMessage,
+}
+
+impl Bgp4mpMessage {
+    pub const fn new(peer: Bgp4mpPeer, message: BgpMessage) -> Self {
+        Self { peer, message }
+    }
+
+    pub const fn peer(&self) -> &Bgp4mpPeer {
+        &self.peer
+    }
+
+    pub const fn message(&self) -> &BgpMessage {
+        &self.message
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Iterate over the records of MRT files.
+//!
+//! Example:
+//! ```rust,no_run
+//! use netgauze_mrt_pkt::{reader::MrtReader, MrtValue};
+//!
+//! let reader = MrtReader::open("updates.20240101.0000").unwrap();
+//! for record in reader {
+//!     match record {
+//!         Ok(record) => {
+//!             if let MrtValue::Bgp4mp(value) = record.value() {
+//!                 println!("{} {:?}", record.timestamp(), value.peer());
+//!             }
+//!         }
+//!         Err(err) => eprintln!("{err}"),
+//!     }
+//! }
+//! ```
+//!
+//! Files ending with `.gz` and `.bz2` are decompressed when the `gzip` and
+//! `bzip2` features are enabled.
+
+use std::{
+    fmt::{Display, Formatter},
+    fs::File,
+    io,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use netgauze_parse_utils::{LocatedParsingError, ReadablePdu, Span};
+
+use crate::{wire::deserializer::MrtRecordParsingError, MrtRecord};
+
+/// Length of the MRT common header
+const MRT_HEADER_LENGTH: usize = 12;
+
+/// Max length of a record accepted by the reader, longer records are
+/// rejected with [io::ErrorKind::InvalidData] instead of being buffered
+pub const MAX_MRT_RECORD_LENGTH: usize = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum MrtReaderError {
+    /// Reading the underlying file failed, no more records are returned
+    Io(io::Error),
+    /// The record at `offset` couldn't be parsed, the reader continues with
+    /// the next record
+    Parsing {
+        offset: u64,
+        error: MrtRecordParsingError,
+    },
+}
+
+impl Display for MrtReaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "error reading MRT records: {err}"),
+            Self::Parsing { offset, error } => {
+                write!(f, "error parsing MRT record at offset {offset}: {error:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MrtReaderError {}
+
+impl From<io::Error> for MrtReaderError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Iterator over the [MrtRecord] of an uncompressed MRT stream
+#[derive(Debug)]
+pub struct MrtReader<R> {
+    reader: R,
+    offset: u64,
+    done: bool,
+}
+
+impl<R: Read> MrtReader<R> {
+    pub const fn new(reader: R) -> Self {
+        Self {
+            reader,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Offset of the next record in the uncompressed stream
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next record, returns `Ok(None)` at the end of the stream
+    fn read_record(&mut self) -> Result<Option<Vec<u8>>, io::Error> {
+        let mut buf = vec![0; MRT_HEADER_LENGTH];
+        let mut read = 0;
+        while read < MRT_HEADER_LENGTH {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let length = u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]) as usize;
+        if length > MAX_MRT_RECORD_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("MRT record length {length} exceeds {MAX_MRT_RECORD_LENGTH} octets"),
+            ));
+        }
+        // The buffer grows with the data actually read rather than the
+        // advertised length
+        (&mut self.reader)
+            .take(length as u64)
+            .read_to_end(&mut buf)?;
+        if buf.len() < MRT_HEADER_LENGTH + length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Some(buf))
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: Read> MrtReader<flate2::read::MultiGzDecoder<R>> {
+    /// Read a gzip compressed MRT stream
+    pub fn gzip(reader: R) -> Self {
+        Self::new(flate2::read::MultiGzDecoder::new(reader))
+    }
+}
+
+#[cfg(feature = "bzip2")]
+impl<R: Read> MrtReader<bzip2::read::MultiBzDecoder<R>> {
+    /// Read a bzip2 compressed MRT stream
+    pub fn bzip2(reader: R) -> Self {
+        Self::new(bzip2::read::MultiBzDecoder::new(reader))
+    }
+}
+
+impl MrtReader<Box<dyn Read + Send>> {
+    /// Open an MRT file, files ending with `.gz` or `.bz2` are decompressed
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let file = BufReader::new(File::open(path)?);
+        let reader: Box<dyn Read + Send> =
+            match path.extension().and_then(|extension| extension.to_str()) {
+                #[cfg(feature = "gzip")]
+                Some("gz") => Box::new(flate2::read::MultiGzDecoder::new(file)),
+                #[cfg(feature = "bzip2")]
+                Some("bz2") => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+                #[cfg(not(feature = "gzip"))]
+                Some("gz") => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "reading gzip files requires the `gzip` feature",
+                    ))
+                }
+                #[cfg(not(feature = "bzip2"))]
+                Some("bz2") => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "reading bzip2 files requires the `bzip2` feature",
+                    ))
+                }
+                _ => Box::new(file),
+            };
+        Ok(Self::new(reader))
+    }
+}
+
+impl<R: Read> Iterator for MrtReader<R> {
+    type Item = Result<MrtRecord, MrtReaderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let buf = match self.read_record() {
+            Ok(Some(buf)) => buf,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err.into()));
+            }
+        };
+        let offset = self.offset;
+        self.offset += buf.len() as u64;
+        match MrtRecord::from_wire(Span::new(&buf)) {
+            Ok((_, record)) => Some(Ok(record)),
+            Err(nom::Err::Incomplete(_)) => Some(Err(MrtReaderError::Parsing {
+                offset,
+                error: MrtRecordParsingError::NomError(nom::error::ErrorKind::Eof),
+            })),
+            Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+                Some(Err(MrtReaderError::Parsing {
+                    offset,
+                    error: err.error().clone(),
+                }))
+            }
+        }
+    }
+}
@@ -0,0 +1,656 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deserializer library for MRT's wire format
+
+use chrono::{LocalResult, TimeZone, Utc};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    string::FromUtf8Error,
+};
+
+use netgauze_bgp_pkt::{
+    path_attribute::PathAttribute,
+    wire::deserializer::{
+        path_attribute::PathAttributeParsingError, BgpMessageParsingError, BgpParsingContext,
+        Ipv4PrefixParsingError, Ipv6PrefixParsingError,
+    },
+};
+use netgauze_iana::address_family::{
+    AddressFamily, AddressType, InvalidAddressType, SubsequentAddressFamily,
+    UndefinedAddressFamily, UndefinedSubsequentAddressFamily,
+};
+use netgauze_parse_utils::{
+    parse_into_located, parse_into_located_one_input, parse_into_located_two_inputs,
+    ErrorKindSerdeDeref, LocatedParsingError, ReadablePdu, ReadablePduWithOneInput,
+    ReadablePduWithTwoInputs, Span,
+};
+use netgauze_serde_macros::LocatedError;
+use nom::{
+    error::{ErrorKind, FromExternalError},
+    number::complete::{be_u128, be_u16, be_u32, be_u8},
+    IResult,
+};
+
+use crate::{
+    iana::*,
+    wire::{ADD_PATH_ADDRESS_TYPES, EXTENDED_LENGTH_MASK, MP_REACH_NLRI},
+    *,
+};
+
+/// Parse a timestamp, `micros` is set only for the Extended Timestamp types
+fn parse_time(secs: u32, micros: u32) -> Option<DateTime<Utc>> {
+    match Utc.timestamp_opt(secs.into(), micros.checked_mul(1_000)?) {
+        LocalResult::Single(time) => Some(time),
+        _ => None,
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum MrtRecordParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    InvalidTime(u32, u32),
+    TableDumpV2Error(#[from_located(module = "self")] TableDumpV2ParsingError),
+    Bgp4mpError(#[from_located(module = "self")] Bgp4mpParsingError),
+}
+
+impl<'a> ReadablePdu<'a, LocatedMrtRecordParsingError<'a>> for MrtRecord {
+    fn from_wire(buf: Span<'a>) -> IResult<Span<'a>, Self, LocatedMrtRecordParsingError<'a>> {
+        let input = buf;
+        let (buf, timestamp) = be_u32(buf)?;
+        let (buf, mrt_type) = be_u16(buf)?;
+        let (buf, subtype) = be_u16(buf)?;
+        let (buf, length) = be_u32(buf)?;
+        let (reminder, buf) = nom::bytes::complete::take(length)(buf)?;
+        let (buf, micros) = match MrtType::try_from(mrt_type) {
+            Ok(MrtType::Bgp4mpEt) => be_u32(buf)?,
+            _ => (buf, 0),
+        };
+        let timestamp = match parse_time(timestamp, micros) {
+            Some(timestamp) => timestamp,
+            None => {
+                return Err(nom::Err::Error(LocatedMrtRecordParsingError::new(
+                    input,
+                    MrtRecordParsingError::InvalidTime(timestamp, micros),
+                )))
+            }
+        };
+        let table_dump_v2_subtype = TableDumpV2Subtype::try_from(subtype)
+            .ok()
+            .filter(|subtype| *subtype != TableDumpV2Subtype::GeoPeerTable);
+        let bgp4mp_subtype = Bgp4mpSubtype::try_from(subtype).ok();
+        let (buf, value) = match (
+            MrtType::try_from(mrt_type),
+            table_dump_v2_subtype,
+            bgp4mp_subtype,
+        ) {
+            (Ok(MrtType::TableDumpV2), Some(subtype), _) => {
+                let (buf, value) = parse_into_located_one_input(buf, subtype)?;
+                (buf, MrtValue::TableDumpV2(value))
+            }
+            (Ok(MrtType::Bgp4mp), _, Some(subtype)) => {
+                let (buf, value) = parse_into_located_one_input(buf, subtype)?;
+                (buf, MrtValue::Bgp4mp(value))
+            }
+            (Ok(MrtType::Bgp4mpEt), _, Some(subtype)) => {
+                let (buf, value) = parse_into_located_one_input(buf, subtype)?;
+                (buf, MrtValue::Bgp4mpEt(value))
+            }
+            _ => {
+                let value = MrtValue::Unknown {
+                    mrt_type,
+                    subtype,
+                    value: buf.to_vec(),
+                };
+                let (buf, _) = nom::bytes::complete::take(buf.len())(buf)?;
+                (buf, value)
+            }
+        };
+        // Make sure the record is fully parsed according to its length
+        if !buf.is_empty() {
+            return Err(nom::Err::Error(LocatedMrtRecordParsingError::new(
+                buf,
+                MrtRecordParsingError::NomError(ErrorKind::NonEmpty),
+            )));
+        }
+        Ok((reminder, MrtRecord::new(timestamp, value)))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum TableDumpV2ParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    UnsupportedSubtype(TableDumpV2Subtype),
+    PeerIndexTableError(#[from_located(module = "self")] PeerIndexTableParsingError),
+    AfiSafiRibError(#[from_located(module = "self")] AfiSafiRibParsingError),
+    RibGenericError(#[from_located(module = "self")] RibGenericParsingError),
+}
+
+impl<'a> ReadablePduWithOneInput<'a, TableDumpV2Subtype, LocatedTableDumpV2ParsingError<'a>>
+    for TableDumpV2
+{
+    fn from_wire(
+        buf: Span<'a>,
+        subtype: TableDumpV2Subtype,
+    ) -> IResult<Span<'a>, Self, LocatedTableDumpV2ParsingError<'a>> {
+        let (buf, value) = match subtype {
+            TableDumpV2Subtype::PeerIndexTable => {
+                let (buf, value) = parse_into_located(buf)?;
+                (buf, TableDumpV2::PeerIndexTable(value))
+            }
+            TableDumpV2Subtype::RibIpv4Unicast => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv4Unicast, false)?;
+                (buf, TableDumpV2::RibIpv4Unicast(value))
+            }
+            TableDumpV2Subtype::RibIpv4Multicast => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv4Multicast, false)?;
+                (buf, TableDumpV2::RibIpv4Multicast(value))
+            }
+            TableDumpV2Subtype::RibIpv6Unicast => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv6Unicast, false)?;
+                (buf, TableDumpV2::RibIpv6Unicast(value))
+            }
+            TableDumpV2Subtype::RibIpv6Multicast => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv6Multicast, false)?;
+                (buf, TableDumpV2::RibIpv6Multicast(value))
+            }
+            TableDumpV2Subtype::RibGeneric => {
+                let (buf, value) = parse_into_located_one_input(buf, false)?;
+                (buf, TableDumpV2::RibGeneric(value))
+            }
+            TableDumpV2Subtype::RibIpv4UnicastAddPath => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv4Unicast, true)?;
+                (buf, TableDumpV2::RibIpv4UnicastAddPath(value))
+            }
+            TableDumpV2Subtype::RibIpv4MulticastAddPath => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv4Multicast, true)?;
+                (buf, TableDumpV2::RibIpv4MulticastAddPath(value))
+            }
+            TableDumpV2Subtype::RibIpv6UnicastAddPath => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv6Unicast, true)?;
+                (buf, TableDumpV2::RibIpv6UnicastAddPath(value))
+            }
+            TableDumpV2Subtype::RibIpv6MulticastAddPath => {
+                let (buf, value) =
+                    parse_into_located_two_inputs(buf, AddressType::Ipv6Multicast, true)?;
+                (buf, TableDumpV2::RibIpv6MulticastAddPath(value))
+            }
+            TableDumpV2Subtype::RibGenericAddPath => {
+                let (buf, value) = parse_into_located_one_input(buf, true)?;
+                (buf, TableDumpV2::RibGenericAddPath(value))
+            }
+            TableDumpV2Subtype::GeoPeerTable => {
+                return Err(nom::Err::Error(LocatedTableDumpV2ParsingError::new(
+                    buf,
+                    TableDumpV2ParsingError::UnsupportedSubtype(subtype),
+                )))
+            }
+        };
+        Ok((buf, value))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum PeerIndexTableParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    FromUtf8Error(String),
+    PeerEntryError(#[from_located(module = "self")] PeerEntryParsingError),
+}
+
+impl<'a> FromExternalError<Span<'a>, FromUtf8Error> for LocatedPeerIndexTableParsingError<'a> {
+    fn from_external_error(input: Span<'a>, _kind: ErrorKind, error: FromUtf8Error) -> Self {
+        LocatedPeerIndexTableParsingError::new(
+            input,
+            PeerIndexTableParsingError::FromUtf8Error(error.to_string()),
+        )
+    }
+}
+
+impl<'a> ReadablePdu<'a, LocatedPeerIndexTableParsingError<'a>> for PeerIndexTable {
+    fn from_wire(buf: Span<'a>) -> IResult<Span<'a>, Self, LocatedPeerIndexTableParsingError<'a>> {
+        let (buf, collector_bgp_id) = be_u32(buf)?;
+        let (buf, view_name_len) = be_u16(buf)?;
+        let (buf, view_name) = nom::combinator::map_res(
+            nom::bytes::complete::take(view_name_len),
+            |view_name: Span<'_>| String::from_utf8(view_name.to_vec()),
+        )(buf)?;
+        let (mut buf, peer_count) = be_u16(buf)?;
+        let mut peers = Vec::with_capacity(peer_count as usize);
+        for _ in 0..peer_count {
+            let (tmp, peer) = parse_into_located(buf)?;
+            peers.push(peer);
+            buf = tmp;
+        }
+        Ok((
+            buf,
+            PeerIndexTable::new(Ipv4Addr::from(collector_bgp_id), view_name, peers),
+        ))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum PeerEntryParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+}
+
+impl<'a> ReadablePdu<'a, LocatedPeerEntryParsingError<'a>> for PeerEntry {
+    fn from_wire(buf: Span<'a>) -> IResult<Span<'a>, Self, LocatedPeerEntryParsingError<'a>> {
+        let (buf, peer_type) = be_u8(buf)?;
+        let (buf, bgp_id) = be_u32(buf)?;
+        let (buf, address) = if peer_type & PEER_TYPE_IS_IPV6 == PEER_TYPE_IS_IPV6 {
+            let (buf, address) = be_u128(buf)?;
+            (buf, IpAddr::V6(Ipv6Addr::from(address)))
+        } else {
+            let (buf, address) = be_u32(buf)?;
+            (buf, IpAddr::V4(Ipv4Addr::from(address)))
+        };
+        let asn4 = peer_type & PEER_TYPE_IS_ASN4 == PEER_TYPE_IS_ASN4;
+        let (buf, asn) = if asn4 {
+            be_u32(buf)?
+        } else {
+            let (buf, asn) = be_u16(buf)?;
+            (buf, asn as u32)
+        };
+        Ok((
+            buf,
+            PeerEntry::new(Ipv4Addr::from(bgp_id), address, asn, asn4),
+        ))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum AfiSafiRibParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    Ipv4PrefixError(
+        #[from_located(module = "netgauze_bgp_pkt::wire::deserializer")] Ipv4PrefixParsingError,
+    ),
+    Ipv6PrefixError(
+        #[from_located(module = "netgauze_bgp_pkt::wire::deserializer")] Ipv6PrefixParsingError,
+    ),
+    RibEntryError(#[from_located(module = "self")] RibEntryParsingError),
+}
+
+impl<'a> ReadablePduWithTwoInputs<'a, AddressType, bool, LocatedAfiSafiRibParsingError<'a>>
+    for AfiSafiRib
+{
+    fn from_wire(
+        buf: Span<'a>,
+        address_type: AddressType,
+        add_path: bool,
+    ) -> IResult<Span<'a>, Self, LocatedAfiSafiRibParsingError<'a>> {
+        let (buf, sequence_number) = be_u32(buf)?;
+        let (buf, prefix) = if address_type.address_family() == AddressFamily::IPv6 {
+            let (buf, prefix): (Span<'_>, Ipv6Net) = parse_into_located(buf)?;
+            (buf, IpNet::V6(prefix))
+        } else {
+            let (buf, prefix): (Span<'_>, Ipv4Net) = parse_into_located(buf)?;
+            (buf, IpNet::V4(prefix))
+        };
+        let (buf, entries) = parse_rib_entries(buf, address_type, add_path)?;
+        Ok((buf, AfiSafiRib::new(sequence_number, prefix, entries)))
+    }
+}
+
+fn parse_rib_entries<
+    'a,
+    E: From<LocatedRibEntryParsingError<'a>> + nom::error::ParseError<Span<'a>>,
+>(
+    buf: Span<'a>,
+    address_type: AddressType,
+    add_path: bool,
+) -> IResult<Span<'a>, Vec<RibEntry>, E> {
+    let (mut buf, entry_count) = be_u16(buf)?;
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    for _ in 0..entry_count {
+        let (tmp, entry) = parse_into_located_two_inputs(buf, address_type, add_path)?;
+        entries.push(entry);
+        buf = tmp;
+    }
+    Ok((buf, entries))
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum RibGenericParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    UndefinedAddressFamily(#[from_external] UndefinedAddressFamily),
+    UndefinedSubsequentAddressFamily(#[from_external] UndefinedSubsequentAddressFamily),
+    InvalidAddressType(InvalidAddressType),
+    RibEntryError(#[from_located(module = "self")] RibEntryParsingError),
+}
+
+/// Length of a single NLRI as encoded in the BGP UPDATE message
+fn nlri_length(address_type: AddressType, buf: &[u8]) -> Option<usize> {
+    let first = *buf.first()? as usize;
+    let len = match address_type {
+        AddressType::L2VpnBgpEvpn => 2 + *buf.get(1)? as usize,
+        AddressType::Ipv4FlowSpec
+        | AddressType::Ipv4FlowSpecL3Vpn
+        | AddressType::Ipv6FlowSpec
+        | AddressType::Ipv6FlowSpecL3Vpn => {
+            if first >= 0xf0 {
+                2 + (((first & 0x0f) << 8) | *buf.get(1)? as usize)
+            } else {
+                1 + first
+            }
+        }
+        AddressType::L2VpnVpls => 2 + ((first << 8) | *buf.get(1)? as usize),
+        AddressType::BgpLs | AddressType::BgpLsVpn => {
+            4 + (((*buf.get(2)? as usize) << 8) | *buf.get(3)? as usize)
+        }
+        _ => 1 + first.div_ceil(8),
+    };
+    (len <= buf.len()).then_some(len)
+}
+
+impl<'a> ReadablePduWithOneInput<'a, bool, LocatedRibGenericParsingError<'a>> for RibGeneric {
+    fn from_wire(
+        buf: Span<'a>,
+        add_path: bool,
+    ) -> IResult<Span<'a>, Self, LocatedRibGenericParsingError<'a>> {
+        let (buf, sequence_number) = be_u32(buf)?;
+        let input = buf;
+        let (buf, afi) = nom::combinator::map_res(be_u16, AddressFamily::try_from)(buf)?;
+        let (buf, safi) = nom::combinator::map_res(be_u8, SubsequentAddressFamily::try_from)(buf)?;
+        let address_type = match AddressType::from_afi_safi(afi, safi) {
+            Ok(address_type) => address_type,
+            Err(err) => {
+                return Err(nom::Err::Error(LocatedRibGenericParsingError::new(
+                    input,
+                    RibGenericParsingError::InvalidAddressType(err),
+                )))
+            }
+        };
+        let nlri_len = match nlri_length(address_type, buf.fragment()) {
+            Some(len) => len,
+            None => {
+                return Err(nom::Err::Error(LocatedRibGenericParsingError::new(
+                    buf,
+                    RibGenericParsingError::NomError(ErrorKind::Eof),
+                )))
+            }
+        };
+        let (buf, nlri) = nom::bytes::complete::take(nlri_len)(buf)?;
+        let (buf, entries) = parse_rib_entries(buf, address_type, add_path)?;
+        Ok((
+            buf,
+            RibGeneric::new(sequence_number, address_type, nlri.to_vec(), entries),
+        ))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum RibEntryParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    InvalidTime(u32),
+    PathAttributeError(
+        #[from_located(module = "netgauze_bgp_pkt::wire::deserializer::path_attribute")]
+        PathAttributeParsingError,
+    ),
+}
+
+/// The RIB entries abbreviate `MP_REACH_NLRI` to only the next hop length and
+/// the next hop. Returns the attribute expanded to the BGP UPDATE encoding,
+/// with the AFI/SAFI of the RIB and no NLRI, along with the buffer after it.
+/// `None` is returned when the attribute is not an abbreviated
+/// `MP_REACH_NLRI`, some implementations write the full attribute instead.
+fn expand_mp_reach(buf: &[u8], address_type: AddressType) -> Option<(usize, Vec<u8>)> {
+    let flags = *buf.first()?;
+    if *buf.get(1)? != MP_REACH_NLRI {
+        return None;
+    }
+    let (header_len, len) = if flags & EXTENDED_LENGTH_MASK == EXTENDED_LENGTH_MASK {
+        (4, ((*buf.get(2)? as usize) << 8) | *buf.get(3)? as usize)
+    } else {
+        (3, *buf.get(2)? as usize)
+    };
+    let value = buf.get(header_len..header_len + len)?;
+    // The AFI of the full encoding starts with a zero octet, while the next hop
+    // length is never zero
+    if value.first().is_none_or(|first| *first == 0) {
+        return None;
+    }
+    // AFI, SAFI and the reserved octet
+    let expanded_len = len + 4;
+    let mut expanded = Vec::with_capacity(expanded_len + 4);
+    if expanded_len > u8::MAX as usize {
+        expanded.push(flags | EXTENDED_LENGTH_MASK);
+        expanded.push(MP_REACH_NLRI);
+        expanded.extend((expanded_len as u16).to_be_bytes());
+    } else {
+        expanded.push(flags & !EXTENDED_LENGTH_MASK);
+        expanded.push(MP_REACH_NLRI);
+        expanded.push(expanded_len as u8);
+    }
+    expanded.extend(u16::from(address_type.address_family()).to_be_bytes());
+    expanded.push(address_type.subsequent_address_family().into());
+    expanded.extend(value);
+    expanded.push(0);
+    Some((header_len + len, expanded))
+}
+
+impl<'a> ReadablePduWithTwoInputs<'a, AddressType, bool, LocatedRibEntryParsingError<'a>>
+    for RibEntry
+{
+    fn from_wire(
+        buf: Span<'a>,
+        address_type: AddressType,
+        add_path: bool,
+    ) -> IResult<Span<'a>, Self, LocatedRibEntryParsingError<'a>> {
+        let (buf, peer_index) = be_u16(buf)?;
+        let input = buf;
+        let (buf, originated_time) = be_u32(buf)?;
+        let originated_time = match parse_time(originated_time, 0) {
+            Some(time) => time,
+            None => {
+                return Err(nom::Err::Error(LocatedRibEntryParsingError::new(
+                    input,
+                    RibEntryParsingError::InvalidTime(originated_time),
+                )))
+            }
+        };
+        let (buf, path_id) = if add_path {
+            let (buf, path_id) = be_u32(buf)?;
+            (buf, Some(path_id))
+        } else {
+            (buf, None)
+        };
+        let (buf, attributes_len) = be_u16(buf)?;
+        let (buf, mut attributes_buf) = nom::bytes::complete::take(attributes_len)(buf)?;
+        // AS_PATH is always encoded with 4-octet AS numbers
+        let mut ctx = BgpParsingContext::default();
+        let mut path_attributes = Vec::new();
+        while !attributes_buf.is_empty() {
+            let (tmp, attribute) = match expand_mp_reach(attributes_buf.fragment(), address_type) {
+                Some((consumed, expanded)) => {
+                    let attribute = match PathAttribute::from_wire(Span::new(&expanded), &mut ctx) {
+                        Ok((_, attribute)) => attribute,
+                        Err(nom::Err::Incomplete(needed)) => {
+                            return Err(nom::Err::Incomplete(needed))
+                        }
+                        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+                            return Err(nom::Err::Error(LocatedRibEntryParsingError::new(
+                                attributes_buf,
+                                RibEntryParsingError::PathAttributeError(err.error().clone()),
+                            )))
+                        }
+                    };
+                    let (tmp, _) = nom::bytes::complete::take(consumed)(attributes_buf)?;
+                    (tmp, attribute)
+                }
+                None => parse_into_located_one_input(attributes_buf, &mut ctx)?,
+            };
+            path_attributes.push(attribute);
+            attributes_buf = tmp;
+        }
+        Ok((
+            buf,
+            RibEntry::new(peer_index, originated_time, path_id, path_attributes),
+        ))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum Bgp4mpParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    Bgp4mpPeerError(#[from_located(module = "self")] Bgp4mpPeerParsingError),
+    UndefinedBgp4mpState(#[from_external] UndefinedBgp4mpState),
+    BgpMessageError(
+        #[from_located(module = "netgauze_bgp_pkt::wire::deserializer")] BgpMessageParsingError,
+    ),
+}
+
+impl<'a> ReadablePduWithOneInput<'a, Bgp4mpSubtype, LocatedBgp4mpParsingError<'a>> for Bgp4mp {
+    fn from_wire(
+        buf: Span<'a>,
+        subtype: Bgp4mpSubtype,
+    ) -> IResult<Span<'a>, Self, LocatedBgp4mpParsingError<'a>> {
+        let asn4 = matches!(
+            subtype,
+            Bgp4mpSubtype::MessageAs4
+                | Bgp4mpSubtype::StateChangeAs4
+                | Bgp4mpSubtype::MessageAs4Local
+                | Bgp4mpSubtype::MessageAs4AddPath
+                | Bgp4mpSubtype::MessageAs4LocalAddPath
+        );
+        let add_path = matches!(
+            subtype,
+            Bgp4mpSubtype::MessageAddPath
+                | Bgp4mpSubtype::MessageAs4AddPath
+                | Bgp4mpSubtype::MessageLocalAddPath
+                | Bgp4mpSubtype::MessageAs4LocalAddPath
+        );
+        let (buf, peer) = parse_into_located_one_input(buf, asn4)?;
+        if matches!(
+            subtype,
+            Bgp4mpSubtype::StateChange | Bgp4mpSubtype::StateChangeAs4
+        ) {
+            let (buf, old_state) = nom::combinator::map_res(be_u16, Bgp4mpState::try_from)(buf)?;
+            let (buf, new_state) = nom::combinator::map_res(be_u16, Bgp4mpState::try_from)(buf)?;
+            let state_change = Bgp4mpStateChange::new(peer, old_state, new_state);
+            let value = if asn4 {
+                Bgp4mp::StateChangeAs4(state_change)
+            } else {
+                Bgp4mp::StateChange(state_change)
+            };
+            return Ok((buf, value));
+        }
+        let add_path_map = if add_path {
+            ADD_PATH_ADDRESS_TYPES
+                .iter()
+                .map(|address_type| (*address_type, true))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let mut ctx =
+            BgpParsingContext::new(asn4, HashMap::new(), add_path_map, true, true, true, true);
+        let (buf, message) = parse_into_located_one_input(buf, &mut ctx)?;
+        let message = Bgp4mpMessage::new(peer, message);
+        let value = match subtype {
+            Bgp4mpSubtype::Message => Bgp4mp::Message(message),
+            Bgp4mpSubtype::MessageAs4 => Bgp4mp::MessageAs4(message),
+            Bgp4mpSubtype::MessageLocal => Bgp4mp::MessageLocal(message),
+            Bgp4mpSubtype::MessageAs4Local => Bgp4mp::MessageAs4Local(message),
+            Bgp4mpSubtype::MessageAddPath => Bgp4mp::MessageAddPath(message),
+            Bgp4mpSubtype::MessageAs4AddPath => Bgp4mp::MessageAs4AddPath(message),
+            Bgp4mpSubtype::MessageLocalAddPath => Bgp4mp::MessageLocalAddPath(message),
+            Bgp4mpSubtype::MessageAs4LocalAddPath => Bgp4mp::MessageAs4LocalAddPath(message),
+            Bgp4mpSubtype::StateChange | Bgp4mpSubtype::StateChangeAs4 => unreachable!(),
+        };
+        Ok((buf, value))
+    }
+}
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum Bgp4mpPeerParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    UndefinedAddressFamily(#[from_external] UndefinedAddressFamily),
+    UnexpectedAddressFamily(AddressFamily),
+}
+
+impl<'a> ReadablePduWithOneInput<'a, bool, LocatedBgp4mpPeerParsingError<'a>> for Bgp4mpPeer {
+    fn from_wire(
+        buf: Span<'a>,
+        asn4: bool,
+    ) -> IResult<Span<'a>, Self, LocatedBgp4mpPeerParsingError<'a>> {
+        let (buf, peer_asn, local_asn) = if asn4 {
+            let (buf, peer_asn) = be_u32(buf)?;
+            let (buf, local_asn) = be_u32(buf)?;
+            (buf, peer_asn, local_asn)
+        } else {
+            let (buf, peer_asn) = be_u16(buf)?;
+            let (buf, local_asn) = be_u16(buf)?;
+            (buf, peer_asn as u32, local_asn as u32)
+        };
+        let (buf, interface_index) = be_u16(buf)?;
+        let input = buf;
+        let (buf, afi) = nom::combinator::map_res(be_u16, AddressFamily::try_from)(buf)?;
+        let (buf, peer_address, local_address) = match afi {
+            AddressFamily::IPv4 => {
+                let (buf, peer_address) = be_u32(buf)?;
+                let (buf, local_address) = be_u32(buf)?;
+                (
+                    buf,
+                    IpAddr::V4(Ipv4Addr::from(peer_address)),
+                    IpAddr::V4(Ipv4Addr::from(local_address)),
+                )
+            }
+            AddressFamily::IPv6 => {
+                let (buf, peer_address) = be_u128(buf)?;
+                let (buf, local_address) = be_u128(buf)?;
+                (
+                    buf,
+                    IpAddr::V6(Ipv6Addr::from(peer_address)),
+                    IpAddr::V6(Ipv6Addr::from(local_address)),
+                )
+            }
+            _ => {
+                return Err(nom::Err::Error(LocatedBgp4mpPeerParsingError::new(
+                    input,
+                    Bgp4mpPeerParsingError::UnexpectedAddressFamily(afi),
+                )))
+            }
+        };
+        Ok((
+            buf,
+            Bgp4mpPeer::new(
+                peer_asn,
+                local_asn,
+                interface_index,
+                peer_address,
+                local_address,
+            ),
+        ))
+    }
+}
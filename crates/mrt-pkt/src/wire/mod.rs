@@ -0,0 +1,55 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serialize/Deserialize MRT wire format
+
+pub mod deserializer;
+pub mod serializer;
+#[cfg(test)]
+mod tests;
+
+use netgauze_iana::address_family::AddressType;
+
+/// Path attribute type code of `MP_REACH_NLRI`
+pub(crate) const MP_REACH_NLRI: u8 = 14;
+
+/// Extended Length bit of the path attribute flags
+pub(crate) const EXTENDED_LENGTH_MASK: u8 = 0x10;
+
+/// In BGP4MP messages with ADD-PATH, the Path Identifier is present for every
+/// address family
+pub(crate) const ADD_PATH_ADDRESS_TYPES: [AddressType; 21] = [
+    AddressType::Ipv4Unicast,
+    AddressType::Ipv4Multicast,
+    AddressType::Ipv4MplsLabeledVpn,
+    AddressType::Ipv4MulticastBgpMplsVpn,
+    AddressType::Ipv4Bgp4over6,
+    AddressType::Ipv4FlowSpec,
+    AddressType::Ipv4FlowSpecL3Vpn,
+    AddressType::Ipv4NlriMplsLabels,
+    AddressType::Ipv6Unicast,
+    AddressType::Ipv6Multicast,
+    AddressType::Ipv6MplsLabeledVpn,
+    AddressType::Ipv6MulticastBgpMplsVpn,
+    AddressType::Ipv6Bgp6over4,
+    AddressType::Ipv6FlowSpec,
+    AddressType::Ipv6FlowSpecL3Vpn,
+    AddressType::Ipv6NlriMplsLabels,
+    AddressType::L2VpnBgpEvpn,
+    AddressType::L2VpnVpls,
+    AddressType::BgpLs,
+    AddressType::BgpLsVpn,
+    AddressType::RouteTargetConstrains,
+];
@@ -0,0 +1,430 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serializer library for MRT's wire format
+
+use byteorder::{NetworkEndian, WriteBytesExt};
+use ipnet::IpNet;
+use std::{io::Write, net::IpAddr};
+
+use netgauze_iana::address_family::AddressFamily;
+
+use netgauze_bgp_pkt::{
+    path_attribute::{PathAttribute, PathAttributeValue},
+    wire::serializer::{path_attribute::PathAttributeWritingError, BgpMessageWritingError},
+};
+use netgauze_parse_utils::{WritablePdu, WritablePduWithOneInput};
+use netgauze_serde_macros::WritingError;
+
+use crate::{
+    iana::*,
+    wire::{EXTENDED_LENGTH_MASK, MP_REACH_NLRI},
+    *,
+};
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum MrtRecordWritingError {
+    StdIOError(#[from_std_io_error] String),
+    TableDumpV2Error(#[from] TableDumpV2WritingError),
+    Bgp4mpError(#[from] Bgp4mpWritingError),
+}
+
+impl WritablePdu<MrtRecordWritingError> for MrtRecord {
+    /// 4-octets timestamp, 2-octets type, 2-octets subtype, 4-octets length
+    const BASE_LENGTH: usize = 12;
+
+    fn len(&self) -> usize {
+        let value_len = match &self.value {
+            MrtValue::TableDumpV2(value) => value.len(),
+            MrtValue::Bgp4mp(value) => value.len(),
+            // 4-octets microsecond timestamp
+            MrtValue::Bgp4mpEt(value) => 4 + value.len(),
+            MrtValue::Unknown { value, .. } => value.len(),
+        };
+        Self::BASE_LENGTH + value_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), MrtRecordWritingError> {
+        writer.write_u32::<NetworkEndian>(self.timestamp.timestamp() as u32)?;
+        writer.write_u16::<NetworkEndian>(self.type_code())?;
+        writer.write_u16::<NetworkEndian>(self.subtype_code())?;
+        writer.write_u32::<NetworkEndian>((self.len() - Self::BASE_LENGTH) as u32)?;
+        match &self.value {
+            MrtValue::TableDumpV2(value) => value.write(writer)?,
+            MrtValue::Bgp4mp(value) => value.write(writer)?,
+            MrtValue::Bgp4mpEt(value) => {
+                writer.write_u32::<NetworkEndian>(self.timestamp.timestamp_subsec_micros())?;
+                value.write(writer)?;
+            }
+            MrtValue::Unknown { value, .. } => writer.write_all(value)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum TableDumpV2WritingError {
+    StdIOError(#[from_std_io_error] String),
+    PeerIndexTableError(#[from] PeerIndexTableWritingError),
+    RibError(#[from] RibEntryWritingError),
+}
+
+impl WritablePdu<TableDumpV2WritingError> for TableDumpV2 {
+    const BASE_LENGTH: usize = 0;
+
+    fn len(&self) -> usize {
+        let add_path = self.is_add_path();
+        let value_len = match self {
+            Self::PeerIndexTable(value) => value.len(),
+            Self::RibIpv4Unicast(value)
+            | Self::RibIpv4Multicast(value)
+            | Self::RibIpv6Unicast(value)
+            | Self::RibIpv6Multicast(value)
+            | Self::RibIpv4UnicastAddPath(value)
+            | Self::RibIpv4MulticastAddPath(value)
+            | Self::RibIpv6UnicastAddPath(value)
+            | Self::RibIpv6MulticastAddPath(value) => value.len(add_path),
+            Self::RibGeneric(value) | Self::RibGenericAddPath(value) => value.len(add_path),
+        };
+        Self::BASE_LENGTH + value_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), TableDumpV2WritingError> {
+        let add_path = self.is_add_path();
+        match self {
+            Self::PeerIndexTable(value) => value.write(writer)?,
+            Self::RibIpv4Unicast(value)
+            | Self::RibIpv4Multicast(value)
+            | Self::RibIpv6Unicast(value)
+            | Self::RibIpv6Multicast(value)
+            | Self::RibIpv4UnicastAddPath(value)
+            | Self::RibIpv4MulticastAddPath(value)
+            | Self::RibIpv6UnicastAddPath(value)
+            | Self::RibIpv6MulticastAddPath(value) => value.write(writer, add_path)?,
+            Self::RibGeneric(value) | Self::RibGenericAddPath(value) => {
+                value.write(writer, add_path)?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum PeerIndexTableWritingError {
+    StdIOError(#[from_std_io_error] String),
+    PeerEntryError(#[from] PeerEntryWritingError),
+}
+
+impl WritablePdu<PeerIndexTableWritingError> for PeerIndexTable {
+    /// 4-octets collector BGP ID, 2-octets view name length, 2-octets peer
+    /// count
+    const BASE_LENGTH: usize = 8;
+
+    fn len(&self) -> usize {
+        Self::BASE_LENGTH
+            + self.view_name.len()
+            + self.peers.iter().map(|peer| peer.len()).sum::<usize>()
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), PeerIndexTableWritingError> {
+        writer.write_all(&self.collector_bgp_id.octets())?;
+        writer.write_u16::<NetworkEndian>(self.view_name.len() as u16)?;
+        writer.write_all(self.view_name.as_bytes())?;
+        writer.write_u16::<NetworkEndian>(self.peers.len() as u16)?;
+        for peer in &self.peers {
+            peer.write(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum PeerEntryWritingError {
+    StdIOError(#[from_std_io_error] String),
+}
+
+impl WritablePdu<PeerEntryWritingError> for PeerEntry {
+    /// 1-octet peer type, 4-octets peer BGP ID
+    const BASE_LENGTH: usize = 5;
+
+    fn len(&self) -> usize {
+        let address_len = match self.address {
+            IpAddr::V4(_) => 4,
+            IpAddr::V6(_) => 16,
+        };
+        let asn_len = if self.asn4 { 4 } else { 2 };
+        Self::BASE_LENGTH + address_len + asn_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), PeerEntryWritingError> {
+        let mut peer_type = 0;
+        if self.address.is_ipv6() {
+            peer_type |= PEER_TYPE_IS_IPV6;
+        }
+        if self.asn4 {
+            peer_type |= PEER_TYPE_IS_ASN4;
+        }
+        writer.write_u8(peer_type)?;
+        writer.write_all(&self.bgp_id.octets())?;
+        match self.address {
+            IpAddr::V4(address) => writer.write_all(&address.octets())?,
+            IpAddr::V6(address) => writer.write_all(&address.octets())?,
+        }
+        if self.asn4 {
+            writer.write_u32::<NetworkEndian>(self.asn)?;
+        } else {
+            writer.write_u16::<NetworkEndian>(self.asn as u16)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prefix length octet followed by the minimum number of octets of the prefix
+fn prefix_len(prefix: &IpNet) -> usize {
+    1 + (prefix.prefix_len() as usize).div_ceil(8)
+}
+
+fn write_prefix<T: Write>(writer: &mut T, prefix: &IpNet) -> Result<(), std::io::Error> {
+    let octets = match prefix.network() {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    };
+    writer.write_u8(prefix.prefix_len())?;
+    writer.write_all(&octets[..prefix_len(prefix) - 1])
+}
+
+impl WritablePduWithOneInput<bool, RibEntryWritingError> for AfiSafiRib {
+    /// 4-octets sequence number, 2-octets entry count
+    const BASE_LENGTH: usize = 6;
+
+    fn len(&self, add_path: bool) -> usize {
+        Self::BASE_LENGTH
+            + prefix_len(&self.prefix)
+            + self
+                .entries
+                .iter()
+                .map(|entry| entry.len(add_path))
+                .sum::<usize>()
+    }
+
+    fn write<T: Write>(&self, writer: &mut T, add_path: bool) -> Result<(), RibEntryWritingError> {
+        writer.write_u32::<NetworkEndian>(self.sequence_number)?;
+        write_prefix(writer, &self.prefix)?;
+        writer.write_u16::<NetworkEndian>(self.entries.len() as u16)?;
+        for entry in &self.entries {
+            entry.write(writer, add_path)?;
+        }
+        Ok(())
+    }
+}
+
+impl WritablePduWithOneInput<bool, RibEntryWritingError> for RibGeneric {
+    /// 4-octets sequence number, 2-octets AFI, 1-octet SAFI, 2-octets entry
+    /// count
+    const BASE_LENGTH: usize = 9;
+
+    fn len(&self, add_path: bool) -> usize {
+        Self::BASE_LENGTH
+            + self.nlri.len()
+            + self
+                .entries
+                .iter()
+                .map(|entry| entry.len(add_path))
+                .sum::<usize>()
+    }
+
+    fn write<T: Write>(&self, writer: &mut T, add_path: bool) -> Result<(), RibEntryWritingError> {
+        writer.write_u32::<NetworkEndian>(self.sequence_number)?;
+        writer.write_u16::<NetworkEndian>(self.address_type.address_family().into())?;
+        writer.write_u8(self.address_type.subsequent_address_family().into())?;
+        writer.write_all(&self.nlri)?;
+        writer.write_u16::<NetworkEndian>(self.entries.len() as u16)?;
+        for entry in &self.entries {
+            entry.write(writer, add_path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum RibEntryWritingError {
+    StdIOError(#[from_std_io_error] String),
+    PathAttributeError(#[from] PathAttributeWritingError),
+}
+
+/// Encode the path attribute for a RIB entry, `MP_REACH_NLRI` is abbreviated to
+/// the next hop length and the next hop
+fn rib_path_attribute(attribute: &PathAttribute) -> Result<Vec<u8>, PathAttributeWritingError> {
+    let mut buf = Vec::with_capacity(attribute.len());
+    attribute.write(&mut buf)?;
+    if !matches!(attribute.value(), PathAttributeValue::MpReach(_)) {
+        return Ok(buf);
+    }
+    let header_len = if attribute.extended_length() { 4 } else { 3 };
+    // Skip the AFI and SAFI
+    let value = &buf[header_len + 3..];
+    let next_hop_len = value[0] as usize;
+    let abbreviated = &value[..1 + next_hop_len];
+    let mut ret = Vec::with_capacity(header_len + abbreviated.len());
+    if attribute.extended_length() {
+        ret.push(buf[0] | EXTENDED_LENGTH_MASK);
+        ret.push(MP_REACH_NLRI);
+        ret.extend((abbreviated.len() as u16).to_be_bytes());
+    } else {
+        ret.push(buf[0]);
+        ret.push(MP_REACH_NLRI);
+        ret.push(abbreviated.len() as u8);
+    }
+    ret.extend(abbreviated);
+    Ok(ret)
+}
+
+impl WritablePduWithOneInput<bool, RibEntryWritingError> for RibEntry {
+    /// 2-octets peer index, 4-octets originated time, 2-octets attribute length
+    const BASE_LENGTH: usize = 8;
+
+    fn len(&self, add_path: bool) -> usize {
+        let path_id_len = if add_path { 4 } else { 0 };
+        let attributes_len = self
+            .path_attributes
+            .iter()
+            .map(|attribute| {
+                rib_path_attribute(attribute)
+                    .map(|buf| buf.len())
+                    .unwrap_or_else(|_| attribute.len())
+            })
+            .sum::<usize>();
+        Self::BASE_LENGTH + path_id_len + attributes_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T, add_path: bool) -> Result<(), RibEntryWritingError> {
+        writer.write_u16::<NetworkEndian>(self.peer_index)?;
+        writer.write_u32::<NetworkEndian>(self.originated_time.timestamp() as u32)?;
+        if add_path {
+            writer.write_u32::<NetworkEndian>(self.path_id.unwrap_or_default())?;
+        }
+        let mut attributes = Vec::new();
+        for attribute in &self.path_attributes {
+            attributes.extend(rib_path_attribute(attribute)?);
+        }
+        writer.write_u16::<NetworkEndian>(attributes.len() as u16)?;
+        writer.write_all(&attributes)?;
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum Bgp4mpWritingError {
+    StdIOError(#[from_std_io_error] String),
+    Bgp4mpPeerError(#[from] Bgp4mpPeerWritingError),
+    BgpMessageError(#[from] BgpMessageWritingError),
+}
+
+impl WritablePdu<Bgp4mpWritingError> for Bgp4mp {
+    const BASE_LENGTH: usize = 0;
+
+    fn len(&self) -> usize {
+        let value_len = match self {
+            Self::StateChange(value) | Self::StateChangeAs4(value) => {
+                // 2-octets old state, 2-octets new state
+                value.peer.len(self.is_asn4()) + 4
+            }
+            Self::Message(value)
+            | Self::MessageAs4(value)
+            | Self::MessageLocal(value)
+            | Self::MessageAs4Local(value)
+            | Self::MessageAddPath(value)
+            | Self::MessageAs4AddPath(value)
+            | Self::MessageLocalAddPath(value)
+            | Self::MessageAs4LocalAddPath(value) => {
+                value.peer.len(self.is_asn4()) + value.message.len()
+            }
+        };
+        Self::BASE_LENGTH + value_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), Bgp4mpWritingError> {
+        match self {
+            Self::StateChange(value) | Self::StateChangeAs4(value) => {
+                value.peer.write(writer, self.is_asn4())?;
+                writer.write_u16::<NetworkEndian>(value.old_state.into())?;
+                writer.write_u16::<NetworkEndian>(value.new_state.into())?;
+            }
+            Self::Message(value)
+            | Self::MessageAs4(value)
+            | Self::MessageLocal(value)
+            | Self::MessageAs4Local(value)
+            | Self::MessageAddPath(value)
+            | Self::MessageAs4AddPath(value)
+            | Self::MessageLocalAddPath(value)
+            | Self::MessageAs4LocalAddPath(value) => {
+                value.peer.write(writer, self.is_asn4())?;
+                value.message.write(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum Bgp4mpPeerWritingError {
+    StdIOError(#[from_std_io_error] String),
+    /// The local address is not of the same address family as the peer address
+    AddressFamilyMismatch(IpAddr, IpAddr),
+}
+
+impl WritablePduWithOneInput<bool, Bgp4mpPeerWritingError> for Bgp4mpPeer {
+    /// 2-octets interface index, 2-octets address family
+    const BASE_LENGTH: usize = 4;
+
+    fn len(&self, asn4: bool) -> usize {
+        let asn_len = if asn4 { 8 } else { 4 };
+        let address_len = match self.peer_address {
+            IpAddr::V4(_) => 8,
+            IpAddr::V6(_) => 32,
+        };
+        Self::BASE_LENGTH + asn_len + address_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T, asn4: bool) -> Result<(), Bgp4mpPeerWritingError> {
+        if asn4 {
+            writer.write_u32::<NetworkEndian>(self.peer_asn)?;
+            writer.write_u32::<NetworkEndian>(self.local_asn)?;
+        } else {
+            writer.write_u16::<NetworkEndian>(self.peer_asn as u16)?;
+            writer.write_u16::<NetworkEndian>(self.local_asn as u16)?;
+        }
+        writer.write_u16::<NetworkEndian>(self.interface_index)?;
+        match (self.peer_address, self.local_address) {
+            (IpAddr::V4(peer_address), IpAddr::V4(local_address)) => {
+                writer.write_u16::<NetworkEndian>(AddressFamily::IPv4.into())?;
+                writer.write_all(&peer_address.octets())?;
+                writer.write_all(&local_address.octets())?;
+            }
+            (IpAddr::V6(peer_address), IpAddr::V6(local_address)) => {
+                writer.write_u16::<NetworkEndian>(AddressFamily::IPv6.into())?;
+                writer.write_all(&peer_address.octets())?;
+                writer.write_all(&local_address.octets())?;
+            }
+            (peer_address, local_address) => {
+                return Err(Bgp4mpPeerWritingError::AddressFamilyMismatch(
+                    peer_address,
+                    local_address,
+                ))
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod mrt_files;
+
+use chrono::TimeZone;
+use netgauze_bgp_pkt::{
+    path_attribute::{
+        As4PathSegment, AsPath, AsPathSegmentType, MpReach, NextHop, Origin, PathAttribute,
+        PathAttributeValue,
+    },
+    wire::deserializer::Ipv4PrefixParsingError,
+    BgpMessage,
+};
+use netgauze_parse_utils::{
+    test_helpers::{
+        combine, test_parse_error, test_parse_error_with_one_input,
+        test_parse_error_with_two_inputs, test_parsed_completely,
+        test_parsed_completely_with_one_input, test_parsed_completely_with_two_inputs, test_write,
+        test_write_with_one_input,
+    },
+    Span, WritablePdu,
+};
+use nom::error::ErrorKind;
+use std::{
+    io::Cursor,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::{
+    iana::*,
+    reader::{MrtReader, MrtReaderError},
+    wire::{deserializer::*, serializer::*},
+    *,
+};
+
+fn origin() -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::Origin(Origin::IGP),
+    )
+    .unwrap()
+}
+
+fn as_path(as_numbers: Vec<u32>) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As4PathSegments(vec![As4PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            as_numbers,
+        )])),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_peer_entry() -> Result<(), PeerEntryWritingError> {
+    let good_ipv4_asn2_wire = [
+        0x00, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0x01, 0xfb, 0xf0,
+    ];
+    let good_ipv6_asn4_wire = [
+        0x03, 0xc6, 0x33, 0x64, 0x01, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xfa, 0x56, 0xea, 0x00,
+    ];
+    let bad_eof_wire = [0x00, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02];
+
+    let good_ipv4_asn2 = PeerEntry::new(
+        Ipv4Addr::new(192, 0, 2, 1),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        64496,
+        false,
+    );
+    let good_ipv6_asn4 = PeerEntry::new(
+        Ipv4Addr::new(198, 51, 100, 1),
+        IpAddr::V6(Ipv6Addr::from_str("2001:db8::1").unwrap()),
+        4200000000,
+        true,
+    );
+    let bad_eof = LocatedPeerEntryParsingError::new(
+        unsafe { Span::new_from_raw_offset(5, &bad_eof_wire[5..]) },
+        PeerEntryParsingError::NomError(ErrorKind::Eof),
+    );
+
+    test_parsed_completely(&good_ipv4_asn2_wire, &good_ipv4_asn2);
+    test_parsed_completely(&good_ipv6_asn4_wire, &good_ipv6_asn4);
+    test_parse_error::<PeerEntry, LocatedPeerEntryParsingError<'_>>(&bad_eof_wire, &bad_eof);
+
+    test_write(&good_ipv4_asn2, &good_ipv4_asn2_wire)?;
+    test_write(&good_ipv6_asn4, &good_ipv6_asn4_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_rib_entry_mp_reach() -> Result<(), RibEntryWritingError> {
+    let abbreviated_wire = combine(vec![
+        &[0x00, 0x01, 0x65, 0x92, 0x00, 0x00, 0x00, 0x18],
+        &[0x40, 0x01, 0x01, 0x00],
+        &[0x80, 0x0e, 0x11, 0x10],
+        &Ipv6Addr::from_str("2001:db8::1").unwrap().octets(),
+    ]);
+    let extended_length_wire = combine(vec![
+        &[0x00, 0x01, 0x65, 0x92, 0x00, 0x00, 0x00, 0x19],
+        &[0x40, 0x01, 0x01, 0x00],
+        &[0x90, 0x0e, 0x00, 0x11, 0x10],
+        &Ipv6Addr::from_str("2001:db8::1").unwrap().octets(),
+    ]);
+    // Some implementations write the full MP_REACH_NLRI attribute
+    let full_wire = combine(vec![
+        &[0x00, 0x01, 0x65, 0x92, 0x00, 0x00, 0x00, 0x1c],
+        &[0x40, 0x01, 0x01, 0x00],
+        &[0x80, 0x0e, 0x15, 0x00, 0x02, 0x01, 0x10],
+        &Ipv6Addr::from_str("2001:db8::1").unwrap().octets(),
+        &[0x00],
+    ]);
+    let add_path_wire = combine(vec![
+        &[0x00, 0x00, 0x65, 0x92, 0x00, 0x00],
+        &[0x00, 0x00, 0x00, 0x07],
+        &[0x00, 0x0b],
+        &[0x40, 0x01, 0x01, 0x00],
+        &[0x40, 0x03, 0x04, 0xc0, 0x00, 0x02, 0x01],
+    ]);
+
+    let good_mp_reach = RibEntry::new(
+        1,
+        Utc.timestamp_opt(1704067072, 0).unwrap(),
+        None,
+        vec![
+            origin(),
+            PathAttribute::from(
+                true,
+                false,
+                false,
+                false,
+                PathAttributeValue::MpReach(MpReach::Ipv6Unicast {
+                    next_hop_global: Ipv6Addr::from_str("2001:db8::1").unwrap(),
+                    next_hop_local: None,
+                    nlri: vec![],
+                }),
+            )
+            .unwrap(),
+        ],
+    );
+    let good_add_path = RibEntry::new(
+        0,
+        Utc.timestamp_opt(1704067072, 0).unwrap(),
+        Some(7),
+        vec![
+            origin(),
+            PathAttribute::from(
+                false,
+                true,
+                false,
+                false,
+                PathAttributeValue::NextHop(NextHop::new(Ipv4Addr::new(192, 0, 2, 1))),
+            )
+            .unwrap(),
+        ],
+    );
+
+    test_parsed_completely_with_two_inputs(
+        &abbreviated_wire,
+        AddressType::Ipv6Unicast,
+        false,
+        &good_mp_reach,
+    );
+    test_parsed_completely_with_two_inputs(
+        &extended_length_wire,
+        AddressType::Ipv6Unicast,
+        false,
+        &good_mp_reach,
+    );
+    test_parsed_completely_with_two_inputs(
+        &full_wire,
+        AddressType::Ipv6Unicast,
+        false,
+        &good_mp_reach,
+    );
+    test_parsed_completely_with_two_inputs(
+        &add_path_wire,
+        AddressType::Ipv4Unicast,
+        true,
+        &good_add_path,
+    );
+
+    // Always written in the abbreviated form
+    test_write_with_one_input(&good_mp_reach, false, &abbreviated_wire)?;
+    test_write_with_one_input(&good_add_path, true, &add_path_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_afi_safi_rib() -> Result<(), RibEntryWritingError> {
+    let good_wire = combine(vec![
+        &[0x00, 0x00, 0x00, 0x2a],
+        &[0x13, 0x0a, 0x20, 0x00],
+        &[0x00, 0x01],
+        &[0x00, 0x00, 0x65, 0x92, 0x00, 0x00, 0x00, 0x0d],
+        &[0x40, 0x01, 0x01, 0x00],
+        &[0x40, 0x02, 0x06, 0x02, 0x01, 0x00, 0x00, 0xfb, 0xf0],
+    ]);
+    let bad_wire = [
+        0x00, 0x00, 0x00, 0x2a, 0x21, 0x0a, 0x20, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    let good = AfiSafiRib::new(
+        42,
+        "10.32.0.0/19".parse().unwrap(),
+        vec![RibEntry::new(
+            0,
+            Utc.timestamp_opt(1704067072, 0).unwrap(),
+            None,
+            vec![origin(), as_path(vec![64496])],
+        )],
+    );
+
+    let bad = LocatedAfiSafiRibParsingError::new(
+        unsafe { Span::new_from_raw_offset(4, &bad_wire[4..]) },
+        AfiSafiRibParsingError::Ipv4PrefixError(Ipv4PrefixParsingError::InvalidIpv4PrefixLen(33)),
+    );
+
+    test_parsed_completely_with_two_inputs(&good_wire, AddressType::Ipv4Unicast, false, &good);
+    test_parse_error_with_two_inputs::<
+        AfiSafiRib,
+        AddressType,
+        bool,
+        LocatedAfiSafiRibParsingError<'_>,
+    >(
+        &bad_wire,
+        AddressType::Ipv4Unicast,
+        false,
+        nom::Err::Error(bad),
+    );
+
+    test_write_with_one_input(&good, false, &good_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_bgp4mp() -> Result<(), Bgp4mpWritingError> {
+    let good_state_change_wire = combine(vec![
+        &[0xfb, 0xf0, 0xfb, 0xf4, 0x00, 0x01, 0x00, 0x01],
+        &[0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0xfe],
+        &[0x00, 0x06, 0x00, 0x01],
+    ]);
+    let good_keepalive_as4_wire = combine(vec![
+        &[
+            0x00, 0x00, 0xfb, 0xf0, 0x00, 0x00, 0xfb, 0xf4, 0x00, 0x00, 0x00, 0x02,
+        ],
+        &Ipv6Addr::from_str("2001:db8::1").unwrap().octets(),
+        &Ipv6Addr::from_str("2001:db8::fe").unwrap().octets(),
+        &[0xff; 16],
+        &[0x00, 0x13, 0x04],
+    ]);
+    let bad_afi_wire = combine(vec![
+        &[0xfb, 0xf0, 0xfb, 0xf4, 0x00, 0x01, 0x40, 0x04],
+        &[0x00, 0x06, 0x00, 0x01],
+    ]);
+    let bad_state_wire = combine(vec![
+        &[0xfb, 0xf0, 0xfb, 0xf4, 0x00, 0x01, 0x00, 0x01],
+        &[0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0xfe],
+        &[0x00, 0x07, 0x00, 0x01],
+    ]);
+
+    let ipv4_peer = Bgp4mpPeer::new(
+        64496,
+        64500,
+        1,
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 254)),
+    );
+    let good_state_change = Bgp4mp::StateChange(Bgp4mpStateChange::new(
+        ipv4_peer,
+        Bgp4mpState::Established,
+        Bgp4mpState::Idle,
+    ));
+    let good_keepalive_as4 = Bgp4mp::MessageAs4(Bgp4mpMessage::new(
+        Bgp4mpPeer::new(
+            64496,
+            64500,
+            0,
+            IpAddr::V6(Ipv6Addr::from_str("2001:db8::1").unwrap()),
+            IpAddr::V6(Ipv6Addr::from_str("2001:db8::fe").unwrap()),
+        ),
+        BgpMessage::KeepAlive,
+    ));
+    let bad_afi = LocatedBgp4mpParsingError::new(
+        unsafe { Span::new_from_raw_offset(6, &bad_afi_wire[6..]) },
+        Bgp4mpParsingError::Bgp4mpPeerError(Bgp4mpPeerParsingError::UnexpectedAddressFamily(
+            netgauze_iana::address_family::AddressFamily::BgpLs,
+        )),
+    );
+    let bad_state = LocatedBgp4mpParsingError::new(
+        unsafe { Span::new_from_raw_offset(16, &bad_state_wire[16..]) },
+        Bgp4mpParsingError::UndefinedBgp4mpState(UndefinedBgp4mpState(7)),
+    );
+    let bad_mismatch = Bgp4mp::StateChange(Bgp4mpStateChange::new(
+        Bgp4mpPeer::new(
+            64496,
+            64500,
+            1,
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ),
+        Bgp4mpState::Established,
+        Bgp4mpState::Idle,
+    ));
+
+    test_parsed_completely_with_one_input(
+        &good_state_change_wire,
+        Bgp4mpSubtype::StateChange,
+        &good_state_change,
+    );
+    test_parsed_completely_with_one_input(
+        &good_keepalive_as4_wire,
+        Bgp4mpSubtype::MessageAs4,
+        &good_keepalive_as4,
+    );
+    test_parse_error_with_one_input::<Bgp4mp, Bgp4mpSubtype, LocatedBgp4mpParsingError<'_>>(
+        &bad_afi_wire,
+        Bgp4mpSubtype::StateChange,
+        &bad_afi,
+    );
+    test_parse_error_with_one_input::<Bgp4mp, Bgp4mpSubtype, LocatedBgp4mpParsingError<'_>>(
+        &bad_state_wire,
+        Bgp4mpSubtype::StateChange,
+        &bad_state,
+    );
+
+    test_write(&good_state_change, &good_state_change_wire)?;
+    test_write(&good_keepalive_as4, &good_keepalive_as4_wire)?;
+    assert_eq!(
+        bad_mismatch.write(&mut Cursor::new(vec![])),
+        Err(Bgp4mpWritingError::Bgp4mpPeerError(
+            Bgp4mpPeerWritingError::AddressFamilyMismatch(
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            )
+        ))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mrt_record() -> Result<(), MrtRecordWritingError> {
+    let good_et_wire = combine(vec![
+        &[
+            0x65, 0x92, 0x00, 0x07, 0x00, 0x11, 0x00, 0x04, 0x00, 0x00, 0x00, 0x2b,
+        ],
+        &[0x00, 0x01, 0xe2, 0x40],
+        &[
+            0x00, 0x00, 0xfb, 0xf0, 0x00, 0x00, 0xfb, 0xf4, 0x00, 0x00, 0x00, 0x01,
+        ],
+        &[0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0xfe],
+        &[0xff; 16],
+        &[0x00, 0x13, 0x04],
+    ]);
+    let good_unknown_wire = [
+        0x65, 0x92, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0xab, 0xcd,
+    ];
+    let bad_trailing_wire = [
+        0x65, 0x92, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0xfb, 0xf0, 0xfb,
+        0xf4, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0xfe, 0x00, 0x06,
+        0x00, 0x01, 0x00,
+    ];
+
+    let good_et = MrtRecord::new(
+        Utc.timestamp_opt(1704067079, 123_456_000).unwrap(),
+        MrtValue::Bgp4mpEt(Bgp4mp::MessageAs4(Bgp4mpMessage::new(
+            Bgp4mpPeer::new(
+                64496,
+                64500,
+                0,
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 254)),
+            ),
+            BgpMessage::KeepAlive,
+        ))),
+    );
+    let good_unknown = MrtRecord::new(
+        Utc.timestamp_opt(1704067072, 0).unwrap(),
+        MrtValue::Unknown {
+            mrt_type: 11,
+            subtype: 1,
+            value: vec![0xab, 0xcd],
+        },
+    );
+    let bad_trailing = LocatedMrtRecordParsingError::new(
+        unsafe { Span::new_from_raw_offset(32, &bad_trailing_wire[32..]) },
+        MrtRecordParsingError::NomError(ErrorKind::NonEmpty),
+    );
+
+    test_parsed_completely(&good_et_wire, &good_et);
+    test_parsed_completely(&good_unknown_wire, &good_unknown);
+    test_parse_error::<MrtRecord, LocatedMrtRecordParsingError<'_>>(
+        &bad_trailing_wire,
+        &bad_trailing,
+    );
+
+    test_write(&good_et, &good_et_wire)?;
+    test_write(&good_unknown, &good_unknown_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_reader_errors() {
+    let unknown = [
+        0x65, 0x92, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0xab, 0xcd,
+    ];
+    // BGP4MP_STATE_CHANGE with an undefined FSM state
+    let bad_state = combine(vec![
+        &[
+            0x65, 0x92, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14,
+        ],
+        &[0xfb, 0xf0, 0xfb, 0xf4, 0x00, 0x01, 0x00, 0x01],
+        &[0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00, 0x02, 0xfe],
+        &[0x00, 0x07, 0x00, 0x01],
+    ]);
+    let truncated = &unknown[..13];
+    let wire = combine(vec![&unknown, &bad_state, &unknown, truncated]);
+
+    let mut reader = MrtReader::new(Cursor::new(wire));
+    assert!(matches!(reader.next(), Some(Ok(_))));
+    // The reader continues after a record that can't be parsed
+    assert!(matches!(
+        reader.next(),
+        Some(Err(MrtReaderError::Parsing {
+            offset: 14,
+            error: MrtRecordParsingError::Bgp4mpError(Bgp4mpParsingError::UndefinedBgp4mpState(
+                UndefinedBgp4mpState(7)
+            )),
+        }))
+    ));
+    assert!(matches!(reader.next(), Some(Ok(_))));
+    assert_eq!(reader.offset(), 60);
+    assert!(matches!(
+        reader.next(),
+        Some(Err(MrtReaderError::Io(err))) if err.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_reader_record_too_long() {
+    let header = [
+        0x65, 0x92, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x01, 0xff, 0xff, 0xff, 0xff,
+    ];
+    let wire = combine(vec![&header, &[0xab, 0xcd]]);
+
+    let mut reader = MrtReader::new(Cursor::new(wire));
+    assert!(matches!(
+        reader.next(),
+        Some(Err(MrtReaderError::Io(err))) if err.kind() == std::io::ErrorKind::InvalidData
+    ));
+    assert!(reader.next().is_none());
+}
@@ -0,0 +1,120 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::reader::MrtReader;
+use netgauze_parse_utils::{ReadablePdu, Span, WritablePdu};
+use rstest::*;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use crate::MrtRecord;
+
+#[rstest]
+fn test_mrt_file(#[files("../../assets/mrt/*.mrt")] path: PathBuf) {
+    let overwrite = std::env::var("OVERWRITE")
+        .to_owned()
+        .unwrap_or_default()
+        .eq_ignore_ascii_case("true");
+    test_mrt(overwrite, path);
+}
+
+#[cfg(feature = "gzip")]
+#[rstest]
+fn test_mrt_gzip_file(#[files("../../assets/mrt/*.mrt.gz")] path: PathBuf) {
+    test_mrt(false, path);
+}
+
+#[cfg(feature = "bzip2")]
+#[rstest]
+fn test_mrt_bzip2_file(#[files("../../assets/mrt/*.mrt.bz2")] path: PathBuf) {
+    test_mrt(false, path);
+}
+
+// Rust is not smart enough to detect this method is used in rstest
+#[allow(dead_code)]
+fn test_mrt(overwrite: bool, mrt_path: PathBuf) {
+    let err_msg = format!("Couldn't extract parent directory name from path: {mrt_path:?}");
+    let parent = mrt_path.parent().expect(&err_msg);
+    let err_msg = format!("Couldn't extract filename from path: {mrt_path:?}");
+    let filename = mrt_path
+        .file_name()
+        .expect(&err_msg)
+        .to_str()
+        .expect("Couldn't convert filename to string");
+    let mut json_path = PathBuf::from(parent);
+    json_path.push(format!(
+        "{}-mrt.json",
+        filename.split(".mrt").next().unwrap()
+    ));
+    let (mut json_file, mut lines) = if overwrite {
+        let err_msg = format!("Couldn't create json file: {json_path:?}.\nDetailed Error");
+        (Some(File::create(json_path.clone()).expect(&err_msg)), None)
+    } else {
+        let err_msg = format!(
+            "Couldn't open json file: {json_path:?} for MRT file: {:?}.\
+            \nTry running with `OVERWRITE=true` to create new output.\
+            \nDetailed Error",
+            mrt_path.as_path(),
+        );
+        let reader = BufReader::new(File::open(json_path.clone()).expect(&err_msg));
+        let lines = reader.lines();
+        (None, Some(lines))
+    };
+    let reader = MrtReader::open(mrt_path.as_path()).unwrap();
+    let mut written = vec![];
+    for record in reader {
+        let serialized = match &record {
+            Ok(record) => {
+                serde_json::to_string(record).expect("Couldn't serialize MRT record to json")
+            }
+            Err(err) => format!("{err}"),
+        };
+        if let Ok(record) = record {
+            // Records are written back exactly as they were read
+            let mut buf = vec![];
+            record.write(&mut buf).expect("Couldn't write MRT record");
+            assert_eq!(buf.len(), record.len());
+            let (_, parsed) = MrtRecord::from_wire(Span::new(&buf)).unwrap();
+            assert_eq!(parsed, record);
+            written.extend(buf);
+        }
+        if let Some(file) = json_file.as_mut() {
+            file.write_all(serialized.as_bytes())
+                .expect("Couldn't write json message");
+            file.write_all(b"\n").expect("Couldn't write json message");
+        }
+        if let Some(lines) = lines.as_mut() {
+            let err_msg = format!(
+                "MRT record is not found in expected output file.\
+                \nMRT record {serialized}.\
+                \nExpected output file: {json_path:?}",
+            );
+            let expected = lines.next().expect(&err_msg).expect("Error reading");
+            assert_eq!(expected, serialized);
+        }
+    }
+    if let Some(lines) = lines.as_mut() {
+        assert!(
+            lines.next().is_none(),
+            "Missing MRT records in {mrt_path:?}"
+        );
+    }
+    if mrt_path.extension().and_then(|ext| ext.to_str()) == Some("mrt") {
+        assert_eq!(written, std::fs::read(mrt_path.as_path()).unwrap());
+    }
+}
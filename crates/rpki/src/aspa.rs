@@ -0,0 +1,326 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! AS path verification with Autonomous System Provider Authorization
+//! (ASPA) to detect route leaks, see [draft-ietf-sidrops-aspa-verification](https://datatracker.ietf.org/doc/html/draft-ietf-sidrops-aspa-verification).
+//!
+//! The [AspaTable] is synchronized from an RPKI cache with version 2 of the
+//! RTR protocol or loaded from the JSON output of a relying party software,
+//! such as rpki-client or Routinator.
+
+use std::{collections::HashMap, io};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use netgauze_bgp_pkt::{
+    iana::BgpRoleValue,
+    path_attribute::{AsPathSegmentType, PathAttribute},
+    update::BgpUpdateMessage,
+};
+
+use crate::rov::{announced_prefixes, effective_as_path};
+
+/// Set of the provider ASes authorized by a customer AS. A customer with no
+/// provider is attested with the provider AS0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AspaRecord {
+    #[serde(
+        rename = "customer_asid",
+        alias = "customer",
+        deserialize_with = "deserialize_asn"
+    )]
+    customer_asn: u32,
+    #[serde(deserialize_with = "deserialize_asns")]
+    providers: Vec<u32>,
+}
+
+impl AspaRecord {
+    pub fn new(customer_asn: u32, mut providers: Vec<u32>) -> Self {
+        providers.sort_unstable();
+        providers.dedup();
+        Self {
+            customer_asn,
+            providers,
+        }
+    }
+
+    pub const fn customer_asn(&self) -> u32 {
+        self.customer_asn
+    }
+
+    /// Provider ASes sorted in ascending order
+    pub const fn providers(&self) -> &Vec<u32> {
+        &self.providers
+    }
+}
+
+/// ASN in the JSON files, either a number or a string such as `AS65000`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonAsn {
+    Number(u32),
+    Text(String),
+}
+
+fn parse_asn<E: serde::de::Error>(asn: JsonAsn) -> Result<u32, E> {
+    match asn {
+        JsonAsn::Number(asn) => Ok(asn),
+        JsonAsn::Text(text) => text
+            .strip_prefix("AS")
+            .or_else(|| text.strip_prefix("as"))
+            .unwrap_or(&text)
+            .parse()
+            .map_err(|_| E::custom(format!("invalid AS number `{text}`"))),
+    }
+}
+
+fn deserialize_asn<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    parse_asn(JsonAsn::deserialize(deserializer)?)
+}
+
+fn deserialize_asns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+    Vec::<JsonAsn>::deserialize(deserializer)?
+        .into_iter()
+        .map(parse_asn)
+        .collect()
+}
+
+/// Result of the hop check between two adjacent ASes of a path
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AspaAuthorization {
+    /// The customer AS has no ASPA
+    NoAttestation,
+    /// The provider AS is authorized by the customer AS
+    ProviderPlus,
+    /// The provider AS is not authorized by the customer AS
+    NotProviderPlus,
+}
+
+/// Relation with the neighbor a route is received from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AspaDirection {
+    /// Received from a customer, a lateral peer, a route server or a route
+    /// server client
+    Upstream,
+    /// Received from a provider
+    Downstream,
+}
+
+impl From<BgpRoleValue> for AspaDirection {
+    /// Direction of the routes received from a peer given the local BGP role
+    /// ([RFC9234](https://datatracker.ietf.org/doc/html/rfc9234))
+    fn from(local_role: BgpRoleValue) -> Self {
+        match local_role {
+            BgpRoleValue::Customer => Self::Downstream,
+            BgpRoleValue::Provider
+            | BgpRoleValue::RS
+            | BgpRoleValue::RsClient
+            | BgpRoleValue::Peer => Self::Upstream,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AspaVerificationState {
+    Valid,
+    Invalid,
+    Unknown,
+}
+
+/// ASPA records indexed by customer AS
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AspaTable {
+    records: HashMap<u32, AspaRecord>,
+}
+
+#[derive(Deserialize)]
+struct AspaJson {
+    aspas: Vec<AspaRecord>,
+}
+
+impl AspaTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the `aspas` of a JSON document, the other members are ignored
+    ///
+    /// ```json
+    /// {"aspas": [{"customer_asid": 65000, "providers": [65001, 65002]}]}
+    /// ```
+    pub fn from_json<R: io::Read>(reader: R) -> Result<Self, serde_json::Error> {
+        let json: AspaJson = serde_json::from_reader(reader)?;
+        Ok(json.aspas.into_iter().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Insert the record of a customer AS, replacing and returning its
+    /// previous record if any
+    pub fn insert(&mut self, record: AspaRecord) -> Option<AspaRecord> {
+        let record = AspaRecord::new(record.customer_asn, record.providers);
+        self.records.insert(record.customer_asn, record)
+    }
+
+    pub fn remove(&mut self, customer_asn: u32) -> Option<AspaRecord> {
+        self.records.remove(&customer_asn)
+    }
+
+    pub fn get(&self, customer_asn: u32) -> Option<&AspaRecord> {
+        self.records.get(&customer_asn)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AspaRecord> {
+        self.records.values()
+    }
+
+    /// Check if `provider_asn` is authorized as a provider of `customer_asn`
+    pub fn authorized(&self, customer_asn: u32, provider_asn: u32) -> AspaAuthorization {
+        match self.records.get(&customer_asn) {
+            None => AspaAuthorization::NoAttestation,
+            Some(record) if record.providers.binary_search(&provider_asn).is_ok() => {
+                AspaAuthorization::ProviderPlus
+            }
+            Some(_) => AspaAuthorization::NotProviderPlus,
+        }
+    }
+
+    /// Verify an AS path ordered as in the `AS_PATH` attribute, i.e., the
+    /// neighbor AS first and the origin AS last. Prepended ASes are collapsed
+    /// and an empty path is `Invalid`.
+    pub fn verify(&self, path: &[u32], direction: AspaDirection) -> AspaVerificationState {
+        // From the origin AS(1) to the neighbor AS(N)
+        let mut path: Vec<u32> = path.iter().rev().copied().collect();
+        path.dedup();
+        let len = path.len();
+        if len == 0 {
+            return AspaVerificationState::Invalid;
+        }
+        let up: Vec<AspaAuthorization> = path
+            .windows(2)
+            .map(|pair| self.authorized(pair[0], pair[1]))
+            .collect();
+        let down: Vec<AspaAuthorization> = path
+            .windows(2)
+            .map(|pair| self.authorized(pair[1], pair[0]))
+            .collect();
+        let not_rejected = |hop: AspaAuthorization| hop != AspaAuthorization::NotProviderPlus;
+        let attested = |hop: AspaAuthorization| hop == AspaAuthorization::ProviderPlus;
+        let max_up_ramp = ramp_length(up.iter(), not_rejected);
+        let min_up_ramp = ramp_length(up.iter(), attested);
+        let (max_down_ramp, min_down_ramp) = match direction {
+            AspaDirection::Upstream => (0, 0),
+            AspaDirection::Downstream => (
+                ramp_length(down.iter().rev(), not_rejected),
+                ramp_length(down.iter().rev(), attested),
+            ),
+        };
+        if max_up_ramp + max_down_ramp < len {
+            AspaVerificationState::Invalid
+        } else if min_up_ramp + min_down_ramp < len {
+            AspaVerificationState::Unknown
+        } else {
+            AspaVerificationState::Valid
+        }
+    }
+}
+
+/// Number of ASes of a ramp made of the consecutive hops accepted by `accept`,
+/// the up ramp starts from the origin AS and the down ramp from the neighbor AS
+fn ramp_length<'a>(
+    hops: impl Iterator<Item = &'a AspaAuthorization>,
+    accept: impl Fn(AspaAuthorization) -> bool,
+) -> usize {
+    1 + hops.take_while(|hop| accept(**hop)).count()
+}
+
+impl FromIterator<AspaRecord> for AspaTable {
+    fn from_iter<T: IntoIterator<Item = AspaRecord>>(iter: T) -> Self {
+        let mut table = Self::new();
+        for record in iter {
+            table.insert(record);
+        }
+        table
+    }
+}
+
+/// Verify the AS path of a route with the given path attributes, computed
+/// from the `AS_PATH` and `AS4_PATH` attributes. The confederation segments
+/// are ignored, and a path with an `AS_SET` is `Invalid`. When `neighbor_as`
+/// is given, a path that doesn't start with it is `Invalid`, it's omitted for
+/// the routes received from a route server that doesn't add its AS to the
+/// path.
+pub fn verify_route(
+    table: &AspaTable,
+    attributes: &[PathAttribute],
+    direction: AspaDirection,
+    neighbor_as: Option<u32>,
+) -> AspaVerificationState {
+    let mut path = vec![];
+    for segment in effective_as_path(attributes)
+        .iter()
+        .filter(|segment| !segment.segment_type().is_confed())
+    {
+        match segment.segment_type() {
+            AsPathSegmentType::AsSequence => path.extend(segment.as_numbers()),
+            _ => return AspaVerificationState::Invalid,
+        }
+    }
+    if neighbor_as.is_some_and(|neighbor_as| path.first() != Some(&neighbor_as)) {
+        return AspaVerificationState::Invalid;
+    }
+    table.verify(&path, direction)
+}
+
+/// Verify the AS path of the routes announced by a BGP update, `None` is
+/// returned when no route is announced, see [announced_prefixes]
+pub fn verify_update(
+    table: &AspaTable,
+    update: &BgpUpdateMessage,
+    direction: AspaDirection,
+    neighbor_as: Option<u32>,
+) -> Option<AspaVerificationState> {
+    if announced_prefixes(update).is_empty() {
+        return None;
+    }
+    Some(verify_route(
+        table,
+        update.path_attributes(),
+        direction,
+        neighbor_as,
+    ))
+}
+
+/// Verify the AS path of the routes reported in a BMP Route Monitoring
+/// message, the monitored peer is expected to be the first AS of the path
+#[cfg(feature = "bmp")]
+pub fn verify_route_monitoring(
+    table: &AspaTable,
+    msg: &netgauze_bmp_pkt::RouteMonitoringMessage,
+    direction: AspaDirection,
+) -> Option<AspaVerificationState> {
+    match msg.update_message() {
+        netgauze_bgp_pkt::BgpMessage::Update(update) => {
+            verify_update(table, update, direction, Some(msg.peer_header().peer_as()))
+        }
+        _ => None,
+    }
+}
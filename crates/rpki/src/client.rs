@@ -0,0 +1,740 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RTR client that keeps the data of an RPKI cache synchronized over TCP, see
+//! [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210#section-8) for the
+//! protocol sequences.
+//!
+//! The client starts with a Reset Query and then asks for the incremental
+//! changes with Serial Queries every refresh interval or when the cache sends a
+//! Serial Notify. The changes of a Cache Response are applied atomically
+//! once its End of Data PDU is received. When the cache can't be reached, the
+//! client retries every retry interval, and drops the data after the expire
+//! interval.
+//!
+//! With version 2, the client also synchronizes the ASPA records of the cache.
+
+use futures::{SinkExt, StreamExt};
+use std::{collections::HashSet, fmt::Display, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::TcpStream,
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep_until, Instant},
+};
+use tokio_util::codec::Framed;
+
+use netgauze_parse_utils::WritablePdu;
+
+use crate::{
+    aspa::{AspaRecord, AspaTable},
+    codec::{RtrCodec, RtrCodecDecoderError},
+    iana::{RtrErrorCode, RtrVersion},
+    vrp::{Vrp, VrpTable},
+    wire::{deserializer::RtrPduParsingError, serializer::RtrPduWritingError},
+    ErrorReport, RouterKey, RtrAspa, RtrIntervals, RtrPdu, RtrPduValue, DEFAULT_EXPIRE_INTERVAL,
+    DEFAULT_REFRESH_INTERVAL, DEFAULT_RETRY_INTERVAL,
+};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Data synchronized from an RPKI cache
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpkiData {
+    vrps: VrpTable,
+    router_keys: HashSet<RouterKey>,
+    aspas: AspaTable,
+}
+
+impl RpkiData {
+    pub const fn new(vrps: VrpTable, router_keys: HashSet<RouterKey>, aspas: AspaTable) -> Self {
+        Self {
+            vrps,
+            router_keys,
+            aspas,
+        }
+    }
+
+    pub const fn vrps(&self) -> &VrpTable {
+        &self.vrps
+    }
+
+    /// BGPsec router keys, only synchronized with version 1
+    pub const fn router_keys(&self) -> &HashSet<RouterKey> {
+        &self.router_keys
+    }
+
+    /// ASPA records, only synchronized with version 2
+    pub const fn aspas(&self) -> &AspaTable {
+        &self.aspas
+    }
+}
+
+impl From<VrpTable> for RpkiData {
+    fn from(vrps: VrpTable) -> Self {
+        Self::new(vrps, HashSet::new(), AspaTable::new())
+    }
+}
+
+impl From<AspaTable> for RpkiData {
+    fn from(aspas: AspaTable) -> Self {
+        Self::new(VrpTable::new(), HashSet::new(), aspas)
+    }
+}
+
+/// State of the data last synchronized from the cache
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RtrSession {
+    version: RtrVersion,
+    session_id: u16,
+    serial: u32,
+}
+
+impl RtrSession {
+    pub const fn new(version: RtrVersion, session_id: u16, serial: u32) -> Self {
+        Self {
+            version,
+            session_id,
+            serial,
+        }
+    }
+
+    /// Protocol version negotiated with the cache
+    pub const fn version(&self) -> RtrVersion {
+        self.version
+    }
+
+    pub const fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    pub const fn serial(&self) -> u32 {
+        self.serial
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtrClientConfig {
+    cache: SocketAddr,
+    version: RtrVersion,
+    intervals: RtrIntervals,
+    connect_timeout: Duration,
+}
+
+impl RtrClientConfig {
+    pub const fn new(cache: SocketAddr) -> Self {
+        Self {
+            cache,
+            version: RtrVersion::V1,
+            intervals: RtrIntervals::new(
+                DEFAULT_REFRESH_INTERVAL,
+                DEFAULT_RETRY_INTERVAL,
+                DEFAULT_EXPIRE_INTERVAL,
+            ),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+
+    pub const fn cache(&self) -> SocketAddr {
+        self.cache
+    }
+
+    /// Highest protocol version offered to the cache, the client falls back to
+    /// the older version when the cache doesn't support it. Version 2 is
+    /// needed to synchronize the ASPA records.
+    pub const fn version(&self) -> RtrVersion {
+        self.version
+    }
+
+    /// Intervals used with version 0 and until the cache reports its own
+    /// intervals in an End of Data PDU
+    pub const fn intervals(&self) -> RtrIntervals {
+        self.intervals
+    }
+
+    pub const fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub const fn with_version(mut self, version: RtrVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub const fn with_intervals(mut self, intervals: RtrIntervals) -> Self {
+        self.intervals = intervals;
+        self
+    }
+
+    pub const fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+}
+
+/// Reasons an RTR session with the cache is closed
+#[derive(Debug, Clone, PartialEq)]
+pub enum RtrClientError {
+    IoError(String),
+    ConnectTimeout,
+    /// No End of Data is received from the cache within the retry interval
+    ResponseTimeout,
+    ConnectionClosed,
+    DecodeError(RtrCodecDecoderError),
+    EncodeError(RtrPduWritingError),
+    /// Error Report received from the cache
+    ErrorReportReceived(ErrorReport),
+    /// Error Report sent to the cache
+    ErrorReportSent(ErrorReport),
+    /// The cache doesn't support the protocol version, the client reconnects
+    /// with the given version
+    VersionDowngrade(RtrVersion),
+}
+
+impl Display for RtrClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(err) => write!(f, "IO error: {err}"),
+            Self::ConnectTimeout => write!(f, "timeout connecting to the cache"),
+            Self::ResponseTimeout => write!(f, "timeout waiting for the cache response"),
+            Self::ConnectionClosed => write!(f, "connection closed by the cache"),
+            Self::DecodeError(err) => write!(f, "error decoding PDU: {err:?}"),
+            Self::EncodeError(err) => write!(f, "error encoding PDU: {err:?}"),
+            Self::ErrorReportReceived(report) => write!(
+                f,
+                "error report received from the cache: {} {}",
+                report.error_code(),
+                report.text()
+            ),
+            Self::ErrorReportSent(report) => write!(
+                f,
+                "error report sent to the cache: {} {}",
+                report.error_code(),
+                report.text()
+            ),
+            Self::VersionDowngrade(version) => {
+                write!(
+                    f,
+                    "cache doesn't support the version, downgrading to {version}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RtrClientError {}
+
+impl From<std::io::Error> for RtrClientError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IoError(err.to_string())
+    }
+}
+
+impl From<RtrPduWritingError> for RtrClientError {
+    fn from(err: RtrPduWritingError) -> Self {
+        Self::EncodeError(err)
+    }
+}
+
+/// RTR client running in the background, the task is stopped when the client
+/// is dropped.
+#[derive(Debug)]
+pub struct RtrClient {
+    data: watch::Receiver<Arc<RpkiData>>,
+    session: watch::Receiver<Option<RtrSession>>,
+    handle: JoinHandle<()>,
+}
+
+impl RtrClient {
+    /// Start the client in the current tokio runtime
+    pub fn start(config: RtrClientConfig) -> Self {
+        let (data_tx, data) = watch::channel(Arc::new(RpkiData::default()));
+        let (session_tx, session) = watch::channel(None);
+        let task = RtrClientTask {
+            version: config.version,
+            intervals: config.intervals,
+            config,
+            session: None,
+            expire_at: None,
+            data: Arc::new(RpkiData::default()),
+            data_tx,
+            session_tx,
+        };
+        let handle = tokio::spawn(task.run());
+        Self {
+            data,
+            session,
+            handle,
+        }
+    }
+
+    /// Current data, empty until the first synchronization
+    pub fn data(&self) -> Arc<RpkiData> {
+        self.data.borrow().clone()
+    }
+
+    /// Receive the data every time a Cache Response is applied
+    pub fn subscribe(&self) -> watch::Receiver<Arc<RpkiData>> {
+        self.data.clone()
+    }
+
+    /// Session of the current data, `None` until the first synchronization
+    /// and after the data expired
+    pub fn session(&self) -> Option<RtrSession> {
+        *self.session.borrow()
+    }
+
+    pub fn subscribe_session(&self) -> watch::Receiver<Option<RtrSession>> {
+        self.session.clone()
+    }
+}
+
+impl Drop for RtrClient {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Changes of a Cache Response, applied when its End of Data is received
+struct PendingResponse {
+    session_id: u16,
+    data: RpkiData,
+}
+
+/// State of a single TCP connection with the cache
+struct Connection {
+    framed: Framed<TcpStream, RtrCodec>,
+    /// Set after the first PDU is received from the cache
+    negotiated: bool,
+    /// Waiting for the answer to a query
+    query_sent_at: Option<Instant>,
+    pending: Option<PendingResponse>,
+    refresh_at: Instant,
+}
+
+struct RtrClientTask {
+    config: RtrClientConfig,
+    version: RtrVersion,
+    intervals: RtrIntervals,
+    /// Session ID and serial of the current data
+    session: Option<(u16, u32)>,
+    expire_at: Option<Instant>,
+    data: Arc<RpkiData>,
+    data_tx: watch::Sender<Arc<RpkiData>>,
+    session_tx: watch::Sender<Option<RtrSession>>,
+}
+
+impl RtrClientTask {
+    async fn run(mut self) {
+        loop {
+            let err = match tokio::time::timeout(
+                self.config.connect_timeout,
+                TcpStream::connect(self.config.cache),
+            )
+            .await
+            {
+                Ok(Ok(stream)) => self.run_connection(stream).await,
+                Ok(Err(err)) => err.into(),
+                Err(_) => RtrClientError::ConnectTimeout,
+            };
+            if let RtrClientError::VersionDowngrade(version) = err {
+                log::info!("[RTR {}] {err}", self.config.cache);
+                self.version = version;
+                continue;
+            }
+            log::warn!("[RTR {}] {err}", self.config.cache);
+            let retry_at = Instant::now() + Duration::from_secs(self.intervals.retry() as u64);
+            match self.expire_at {
+                Some(expire_at) if expire_at < retry_at => {
+                    sleep_until(expire_at).await;
+                    self.expire();
+                    sleep_until(retry_at).await;
+                }
+                _ => sleep_until(retry_at).await,
+            }
+        }
+    }
+
+    /// Drop the data when it couldn't be refreshed within the expire interval
+    fn expire(&mut self) {
+        log::warn!(
+            "[RTR {}] data expired, dropping {} VRPs",
+            self.config.cache,
+            self.data.vrps().len()
+        );
+        self.expire_at = None;
+        self.session = None;
+        self.data = Arc::new(RpkiData::default());
+        self.data_tx.send_replace(self.data.clone());
+        self.session_tx.send_replace(None);
+    }
+
+    fn query(&self) -> RtrPdu {
+        let value = match self.session {
+            Some((session_id, serial)) => RtrPduValue::SerialQuery { session_id, serial },
+            None => RtrPduValue::ResetQuery,
+        };
+        RtrPdu::new(self.version, value)
+    }
+
+    async fn send_query(&self, connection: &mut Connection) -> Result<(), RtrClientError> {
+        connection.framed.send(self.query()).await?;
+        connection.query_sent_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Send an Error Report to the cache and return the error to close the
+    /// connection
+    async fn send_error(
+        &self,
+        connection: &mut Connection,
+        error_code: RtrErrorCode,
+        pdu: Option<&RtrPdu>,
+        text: &str,
+    ) -> RtrClientError {
+        let mut encapsulated = vec![];
+        if let Some(pdu) = pdu {
+            let _ = pdu.write(&mut encapsulated);
+        }
+        let report = ErrorReport::new(error_code, encapsulated, text.to_string());
+        let pdu = RtrPdu::new(self.version, RtrPduValue::ErrorReport(report.clone()));
+        if let Err(err) = connection.framed.send(pdu).await {
+            return err.into();
+        }
+        RtrClientError::ErrorReportSent(report)
+    }
+
+    async fn run_connection(&mut self, stream: TcpStream) -> RtrClientError {
+        let mut connection = Connection {
+            framed: Framed::new(stream, RtrCodec),
+            negotiated: false,
+            query_sent_at: None,
+            pending: None,
+            refresh_at: Instant::now(),
+        };
+        if let Err(err) = self.send_query(&mut connection).await {
+            return err;
+        }
+        loop {
+            let retry = Duration::from_secs(self.intervals.retry() as u64);
+            let response_deadline = connection.query_sent_at.map(|sent_at| sent_at + retry);
+            let result = tokio::select! {
+                pdu = connection.framed.next() => match pdu {
+                    None => Err(RtrClientError::ConnectionClosed),
+                    Some(Ok(pdu)) => self.handle_pdu(&mut connection, pdu).await,
+                    Some(Err(err)) => Err(self.handle_decode_error(&mut connection, err).await),
+                },
+                _ = sleep_until(connection.refresh_at), if connection.query_sent_at.is_none() => {
+                    self.send_query(&mut connection).await
+                }
+                _ = sleep_until(response_deadline.unwrap_or(connection.refresh_at)), if response_deadline.is_some() => {
+                    Err(RtrClientError::ResponseTimeout)
+                }
+                _ = sleep_until(self.expire_at.unwrap_or(connection.refresh_at)), if self.expire_at.is_some() => {
+                    self.expire();
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                return err;
+            }
+        }
+    }
+
+    async fn handle_decode_error(
+        &self,
+        connection: &mut Connection,
+        err: RtrCodecDecoderError,
+    ) -> RtrClientError {
+        let error_code = match &err {
+            RtrCodecDecoderError::IoError(_) => return RtrClientError::DecodeError(err),
+            RtrCodecDecoderError::RtrPduParsingError(RtrPduParsingError::UndefinedRtrVersion(
+                _,
+            )) => RtrErrorCode::UnsupportedProtocolVersion,
+            RtrCodecDecoderError::RtrPduParsingError(
+                RtrPduParsingError::UndefinedRtrPduType(_)
+                | RtrPduParsingError::UnsupportedPduType(_, _),
+            ) => RtrErrorCode::UnsupportedPduType,
+            _ => RtrErrorCode::CorruptData,
+        };
+        let text = format!("{err:?}");
+        match self.send_error(connection, error_code, None, &text).await {
+            RtrClientError::ErrorReportSent(_) => RtrClientError::DecodeError(err),
+            err => err,
+        }
+    }
+
+    async fn handle_pdu(
+        &mut self,
+        connection: &mut Connection,
+        pdu: RtrPdu,
+    ) -> Result<(), RtrClientError> {
+        if !connection.negotiated {
+            if let RtrPduValue::ErrorReport(report) = pdu.value() {
+                if report.error_code() == RtrErrorCode::UnsupportedProtocolVersion
+                    && self.version > RtrVersion::V0
+                {
+                    // Fallback to the version of the cache, or else to the previous version
+                    let version = if pdu.version() < self.version {
+                        pdu.version()
+                    } else {
+                        RtrVersion::try_from(u8::from(self.version) - 1).unwrap_or(RtrVersion::V0)
+                    };
+                    return Err(RtrClientError::VersionDowngrade(version));
+                }
+            }
+            if pdu.version() > self.version {
+                return Err(self
+                    .send_error(
+                        connection,
+                        RtrErrorCode::UnsupportedProtocolVersion,
+                        Some(&pdu),
+                        "unsupported protocol version",
+                    )
+                    .await);
+            }
+            if pdu.version() < self.version {
+                log::info!(
+                    "[RTR {}] cache answered with version {}",
+                    self.config.cache,
+                    pdu.version()
+                );
+                self.version = pdu.version();
+            }
+            connection.negotiated = true;
+        } else if pdu.version() != self.version {
+            return Err(self
+                .send_error(
+                    connection,
+                    RtrErrorCode::UnexpectedProtocolVersion,
+                    Some(&pdu),
+                    "unexpected protocol version",
+                )
+                .await);
+        }
+        match pdu.value() {
+            RtrPduValue::SerialNotify { .. } => {
+                if connection.query_sent_at.is_none() {
+                    self.send_query(connection).await?;
+                }
+            }
+            RtrPduValue::CacheResponse { session_id } => {
+                let expected_session = match self.session {
+                    Some((current, _)) if current != *session_id => {
+                        // The cache restarted with a new session, the data must be fetched again
+                        self.session = None;
+                        false
+                    }
+                    _ => true,
+                };
+                if connection.query_sent_at.is_none()
+                    || connection.pending.is_some()
+                    || !expected_session
+                {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::CorruptData,
+                            Some(&pdu),
+                            "unexpected cache response",
+                        )
+                        .await);
+                }
+                let data = match self.session {
+                    Some(_) => self.data.as_ref().clone(),
+                    None => RpkiData::default(),
+                };
+                connection.pending = Some(PendingResponse {
+                    session_id: *session_id,
+                    data,
+                });
+            }
+            RtrPduValue::Prefix(prefix) => {
+                let Some(pending) = connection.pending.as_mut() else {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::CorruptData,
+                            Some(&pdu),
+                            "prefix outside of a cache response",
+                        )
+                        .await);
+                };
+                let vrp = Vrp::from(prefix);
+                if prefix.announce() {
+                    if !pending.data.vrps.insert(vrp) {
+                        return Err(self
+                            .send_error(
+                                connection,
+                                RtrErrorCode::DuplicateAnnouncementReceived,
+                                Some(&pdu),
+                                "duplicate prefix announcement",
+                            )
+                            .await);
+                    }
+                } else if !pending.data.vrps.remove(&vrp) {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::WithdrawalOfUnknownRecord,
+                            Some(&pdu),
+                            "withdrawal of unknown prefix",
+                        )
+                        .await);
+                }
+            }
+            RtrPduValue::RouterKey(key) => {
+                let Some(pending) = connection.pending.as_mut() else {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::CorruptData,
+                            Some(&pdu),
+                            "router key outside of a cache response",
+                        )
+                        .await);
+                };
+                let record = RouterKey::new(true, *key.ski(), key.asn(), key.spki().clone());
+                if key.announce() {
+                    if !pending.data.router_keys.insert(record) {
+                        return Err(self
+                            .send_error(
+                                connection,
+                                RtrErrorCode::DuplicateAnnouncementReceived,
+                                Some(&pdu),
+                                "duplicate router key announcement",
+                            )
+                            .await);
+                    }
+                } else if !pending.data.router_keys.remove(&record) {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::WithdrawalOfUnknownRecord,
+                            Some(&pdu),
+                            "withdrawal of unknown router key",
+                        )
+                        .await);
+                }
+            }
+            RtrPduValue::Aspa(aspa) => {
+                let Some(pending) = connection.pending.as_mut() else {
+                    return Err(self
+                        .send_error(
+                            connection,
+                            RtrErrorCode::CorruptData,
+                            Some(&pdu),
+                            "ASPA outside of a cache response",
+                        )
+                        .await);
+                };
+                if let Err((error_code, text)) = apply_aspa(&mut pending.data.aspas, aspa) {
+                    return Err(self
+                        .send_error(connection, error_code, Some(&pdu), text)
+                        .await);
+                }
+            }
+            RtrPduValue::EndOfData(end_of_data) => {
+                let pending = match connection.pending.take() {
+                    Some(pending) if pending.session_id == end_of_data.session_id() => pending,
+                    _ => {
+                        return Err(self
+                            .send_error(
+                                connection,
+                                RtrErrorCode::CorruptData,
+                                Some(&pdu),
+                                "unexpected end of data",
+                            )
+                            .await);
+                    }
+                };
+                if let Some(intervals) = end_of_data.intervals() {
+                    self.intervals = intervals;
+                }
+                let now = Instant::now();
+                self.session = Some((pending.session_id, end_of_data.serial()));
+                self.expire_at = Some(now + Duration::from_secs(self.intervals.expire() as u64));
+                connection.refresh_at = now + Duration::from_secs(self.intervals.refresh() as u64);
+                connection.query_sent_at = None;
+                log::debug!(
+                    "[RTR {}] synchronized serial {} with {} VRPs and {} ASPAs",
+                    self.config.cache,
+                    end_of_data.serial(),
+                    pending.data.vrps().len(),
+                    pending.data.aspas().len()
+                );
+                self.data = Arc::new(pending.data);
+                self.data_tx.send_replace(self.data.clone());
+                self.session_tx.send_replace(Some(RtrSession::new(
+                    self.version,
+                    end_of_data.session_id(),
+                    end_of_data.serial(),
+                )));
+            }
+            RtrPduValue::CacheReset => {
+                // The cache can't provide the changes since our serial
+                self.session = None;
+                connection.pending = None;
+                self.send_query(connection).await?;
+            }
+            RtrPduValue::ErrorReport(report) => {
+                // Error Reports are never answered with an Error Report
+                return Err(RtrClientError::ErrorReportReceived(report.clone()));
+            }
+            RtrPduValue::SerialQuery { .. } | RtrPduValue::ResetQuery => {
+                return Err(self
+                    .send_error(
+                        connection,
+                        RtrErrorCode::UnsupportedPduType,
+                        Some(&pdu),
+                        "query PDUs are only sent by routers",
+                    )
+                    .await);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply an ASPA PDU, an announcement replaces the previous record of the
+/// customer AS
+fn apply_aspa(aspas: &mut AspaTable, aspa: &RtrAspa) -> Result<(), (RtrErrorCode, &'static str)> {
+    if aspa.announce() {
+        if aspa.providers().is_empty() {
+            return Err((
+                RtrErrorCode::AspaProviderListError,
+                "ASPA announcement without providers",
+            ));
+        }
+        aspas.insert(AspaRecord::new(
+            aspa.customer_asn(),
+            aspa.providers().clone(),
+        ));
+    } else {
+        if !aspa.providers().is_empty() {
+            return Err((
+                RtrErrorCode::AspaProviderListError,
+                "ASPA withdrawal with providers",
+            ));
+        }
+        if aspas.remove(aspa.customer_asn()).is_none() {
+            return Err((
+                RtrErrorCode::WithdrawalOfUnknownRecord,
+                "withdrawal of unknown ASPA",
+            ));
+        }
+    }
+    Ok(())
+}
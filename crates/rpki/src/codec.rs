@@ -0,0 +1,101 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Codecs to decode and encode RTR PDUs from byte streams
+
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::{Buf, BufMut, BytesMut};
+use netgauze_parse_utils::{LocatedParsingError, ReadablePdu, Span, WritablePdu};
+use nom::Needed;
+use serde::{Deserialize, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    wire::{deserializer::RtrPduParsingError, serializer::RtrPduWritingError, RTR_HEADER_LENGTH},
+    RtrPdu,
+};
+
+/// Upper bound for the length of a PDU, to avoid buffering an unbounded amount
+/// of data when the length field is corrupted. The largest PDUs are Error
+/// Reports that encapsulate another PDU.
+pub const RTR_PDU_MAX_LENGTH: usize = 65535;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RtrCodecDecoderError {
+    IoError(String),
+    Incomplete(Option<usize>),
+    InvalidPduLength(u32),
+    RtrPduParsingError(RtrPduParsingError),
+}
+
+impl From<std::io::Error> for RtrCodecDecoderError {
+    fn from(error: std::io::Error) -> Self {
+        Self::IoError(error.to_string())
+    }
+}
+
+/// Encoder and Decoder for [`RtrPdu`]
+#[derive(Debug, Default)]
+pub struct RtrCodec;
+
+impl Encoder<RtrPdu> for RtrCodec {
+    type Error = RtrPduWritingError;
+
+    fn encode(&mut self, pdu: RtrPdu, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(pdu.len());
+        let mut writer = dst.writer();
+        pdu.write(&mut writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for RtrCodec {
+    type Item = RtrPdu;
+    type Error = RtrCodecDecoderError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.len() < RTR_HEADER_LENGTH {
+            // We don't have enough data yet to start processing
+            return Ok(None);
+        }
+        let length = NetworkEndian::read_u32(&buf[4..RTR_HEADER_LENGTH]);
+        if (length as usize) < RTR_HEADER_LENGTH || length as usize > RTR_PDU_MAX_LENGTH {
+            // The stream can't be synchronized again after an invalid length
+            buf.clear();
+            return Err(RtrCodecDecoderError::InvalidPduLength(length));
+        }
+        let length = length as usize;
+        if buf.len() < length {
+            // We still didn't read all the bytes for the PDU yet
+            buf.reserve(length - buf.len());
+            return Ok(None);
+        }
+        let result = match RtrPdu::from_wire(Span::new(&buf[..length])) {
+            Ok((_, pdu)) => Ok(Some(pdu)),
+            Err(nom::Err::Incomplete(needed)) => {
+                let needed = match needed {
+                    Needed::Unknown => None,
+                    Needed::Size(size) => Some(size.get()),
+                };
+                Err(RtrCodecDecoderError::Incomplete(needed))
+            }
+            Err(nom::Err::Error(error)) | Err(nom::Err::Failure(error)) => Err(
+                RtrCodecDecoderError::RtrPduParsingError(error.error().clone()),
+            ),
+        };
+        buf.advance(length);
+        result
+    }
+}
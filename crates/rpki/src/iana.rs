@@ -0,0 +1,145 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains RTR codes that are registered at IANA [Resource Public Key Infrastructure (RPKI) Router Protocol](https://www.iana.org/assignments/rpki/rpki.xhtml)
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, FromRepr};
+
+/// Announce/Withdraw flag of the prefix, router key and ASPA PDUs, see [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210#section-5.6)
+pub const RTR_FLAG_ANNOUNCE: u8 = 0b00000001;
+
+/// RTR protocol versions, version 0 is defined in [RFC6810](https://datatracker.ietf.org/doc/html/rfc6810),
+/// version 1 in [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210) and
+/// version 2 in [draft-ietf-sidrops-8210bis](https://datatracker.ietf.org/doc/html/draft-ietf-sidrops-8210bis)
+#[repr(u8)]
+#[derive(
+    Display,
+    FromRepr,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    Serialize,
+    Deserialize,
+)]
+pub enum RtrVersion {
+    V0 = 0,
+    V1 = 1,
+    V2 = 2,
+}
+
+/// RTR version is not one of [`RtrVersion`], the carried value is the
+/// undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedRtrVersion(pub u8);
+
+impl From<RtrVersion> for u8 {
+    fn from(value: RtrVersion) -> Self {
+        value as u8
+    }
+}
+
+impl TryFrom<u8> for RtrVersion {
+    type Error = UndefinedRtrVersion;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedRtrVersion(value)),
+        }
+    }
+}
+
+/// RTR PDU Types as registered in IANA [rpki-rtr-pdu](https://www.iana.org/assignments/rpki/rpki.xhtml#rpki-rtr-pdu)
+#[repr(u8)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RtrPduType {
+    SerialNotify = 0,
+    SerialQuery = 1,
+    ResetQuery = 2,
+    CacheResponse = 3,
+    Ipv4Prefix = 4,
+    Ipv6Prefix = 6,
+    EndOfData = 7,
+    CacheReset = 8,
+    RouterKey = 9,
+    ErrorReport = 10,
+    Aspa = 11,
+}
+
+/// RTR PDU type is not one of [`RtrPduType`], the carried value is the
+/// undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedRtrPduType(pub u8);
+
+impl From<RtrPduType> for u8 {
+    fn from(value: RtrPduType) -> Self {
+        value as u8
+    }
+}
+
+impl TryFrom<u8> for RtrPduType {
+    type Error = UndefinedRtrPduType;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedRtrPduType(value)),
+        }
+    }
+}
+
+/// RTR Error Codes as registered in IANA [rpki-rtr-error](https://www.iana.org/assignments/rpki/rpki.xhtml#rpki-rtr-error)
+#[repr(u16)]
+#[derive(Display, FromRepr, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RtrErrorCode {
+    CorruptData = 0,
+    InternalError = 1,
+    NoDataAvailable = 2,
+    InvalidRequest = 3,
+    UnsupportedProtocolVersion = 4,
+    UnsupportedPduType = 5,
+    WithdrawalOfUnknownRecord = 6,
+    DuplicateAnnouncementReceived = 7,
+    UnexpectedProtocolVersion = 8,
+    AspaProviderListError = 9,
+}
+
+/// RTR error code is not one of [`RtrErrorCode`], the carried value is the
+/// undefined code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct UndefinedRtrErrorCode(pub u16);
+
+impl From<RtrErrorCode> for u16 {
+    fn from(value: RtrErrorCode) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for RtrErrorCode {
+    type Error = UndefinedRtrErrorCode;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match Self::from_repr(value) {
+            Some(val) => Ok(val),
+            None => Err(UndefinedRtrErrorCode(value)),
+        }
+    }
+}
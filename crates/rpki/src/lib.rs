@@ -0,0 +1,485 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RPKI to Router protocol (RTR) representation and client, see [RFC6810](https://datatracker.ietf.org/doc/html/rfc6810)
+//! and [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210), and BGP
+//! prefix origin validation as described in [RFC6811](https://datatracker.ietf.org/doc/html/rfc6811).
+//!
+//! The [client::RtrClient] keeps a [vrp::VrpTable] synchronized with an RPKI
+//! cache over TCP, which is then used with the helpers in [rov] to validate
+//! the routes received by a BGP speaker or reported by a BMP collector. With
+//! version 2 of the protocol, the client also synchronizes an
+//! [aspa::AspaTable] used to verify the AS paths of the routes.
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::iana::{RtrErrorCode, RtrPduType, RtrVersion};
+
+pub mod aspa;
+pub mod client;
+pub mod codec;
+pub mod iana;
+pub mod rov;
+pub mod vrp;
+pub mod wire;
+
+#[cfg(test)]
+mod tests;
+
+/// Refresh, Retry and Expire intervals in seconds as suggested by
+/// [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210#section-6)
+pub const DEFAULT_REFRESH_INTERVAL: u32 = 3600;
+pub const DEFAULT_RETRY_INTERVAL: u32 = 600;
+pub const DEFAULT_EXPIRE_INTERVAL: u32 = 7200;
+
+/// ```text
+///  0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |                     |
+/// | Version  |   Type   |    Session ID       |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                Length                     |
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RtrPdu {
+    version: RtrVersion,
+    value: RtrPduValue,
+}
+
+impl RtrPdu {
+    pub const fn new(version: RtrVersion, value: RtrPduValue) -> Self {
+        Self { version, value }
+    }
+
+    pub const fn version(&self) -> RtrVersion {
+        self.version
+    }
+
+    pub const fn value(&self) -> &RtrPduValue {
+        &self.value
+    }
+
+    pub fn into_value(self) -> RtrPduValue {
+        self.value
+    }
+
+    pub const fn pdu_type(&self) -> RtrPduType {
+        self.value.pdu_type()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RtrPduValue {
+    /// Sent by the cache to announce that it has new data
+    SerialNotify {
+        session_id: u16,
+        serial: u32,
+    },
+    /// Sent by the router to ask for the changes since the given serial
+    SerialQuery {
+        session_id: u16,
+        serial: u32,
+    },
+    /// Sent by the router to ask for the full data set
+    ResetQuery,
+    /// Starts the answer of the cache to a query
+    CacheResponse {
+        session_id: u16,
+    },
+    /// IPv4 or IPv6 Prefix PDU, the PDU type is derived from the prefix
+    Prefix(RtrPrefix),
+    EndOfData(EndOfData),
+    /// Sent by the cache when it can't provide the changes since the serial
+    /// in the router's Serial Query
+    CacheReset,
+    /// Only in version 1 and later
+    RouterKey(RouterKey),
+    ErrorReport(ErrorReport),
+    /// Only in version 2
+    Aspa(RtrAspa),
+}
+
+impl RtrPduValue {
+    pub const fn pdu_type(&self) -> RtrPduType {
+        match self {
+            Self::SerialNotify { .. } => RtrPduType::SerialNotify,
+            Self::SerialQuery { .. } => RtrPduType::SerialQuery,
+            Self::ResetQuery => RtrPduType::ResetQuery,
+            Self::CacheResponse { .. } => RtrPduType::CacheResponse,
+            Self::Prefix(prefix) => match prefix.prefix {
+                IpNet::V4(_) => RtrPduType::Ipv4Prefix,
+                IpNet::V6(_) => RtrPduType::Ipv6Prefix,
+            },
+            Self::EndOfData(_) => RtrPduType::EndOfData,
+            Self::CacheReset => RtrPduType::CacheReset,
+            Self::RouterKey(_) => RtrPduType::RouterKey,
+            Self::ErrorReport(_) => RtrPduType::ErrorReport,
+            Self::Aspa(_) => RtrPduType::Aspa,
+        }
+    }
+}
+
+/// Validated ROA Payload (VRP) carried in the IPv4 and IPv6 Prefix PDUs
+///
+/// ```text
+/// 0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |                     |
+/// | Version  |   Type   |         zero        |
+/// |          |          |                     |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                 Length                    |
+/// |                                           |
+/// +-------------------------------------------+
+/// |          |  Prefix  |   Max    |          |
+/// |  Flags   |  Length  |  Length  |   zero   |
+/// |          |          |          |          |
+/// +-------------------------------------------+
+/// |                                           |
+/// |         IPv4 or IPv6 Prefix               |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |         Autonomous System Number          |
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RtrPrefix {
+    announce: bool,
+    prefix: IpNet,
+    max_length: u8,
+    asn: u32,
+}
+
+impl RtrPrefix {
+    pub const fn new(announce: bool, prefix: IpNet, max_length: u8, asn: u32) -> Self {
+        Self {
+            announce,
+            prefix,
+            max_length,
+            asn,
+        }
+    }
+
+    /// `true` for an announcement and `false` for a withdrawal
+    pub const fn announce(&self) -> bool {
+        self.announce
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+
+    pub const fn max_length(&self) -> u8 {
+        self.max_length
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+}
+
+/// The intervals are only carried in version 1
+///
+/// ```text
+/// 0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |                     |
+/// | Version  |   Type   |     Session ID      |
+/// |    1     |    7     |                     |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                 Length=24                 |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |               Serial Number               |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |              Refresh Interval             |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |               Retry Interval              |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |              Expire Interval              |
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EndOfData {
+    session_id: u16,
+    serial: u32,
+    intervals: Option<RtrIntervals>,
+}
+
+impl EndOfData {
+    pub const fn new(session_id: u16, serial: u32, intervals: Option<RtrIntervals>) -> Self {
+        Self {
+            session_id,
+            serial,
+            intervals,
+        }
+    }
+
+    pub const fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    pub const fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    pub const fn intervals(&self) -> Option<RtrIntervals> {
+        self.intervals
+    }
+}
+
+/// Timing parameters of the RTR session in seconds, see [RFC8210](https://datatracker.ietf.org/doc/html/rfc8210#section-6)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RtrIntervals {
+    refresh: u32,
+    retry: u32,
+    expire: u32,
+}
+
+impl RtrIntervals {
+    pub const fn new(refresh: u32, retry: u32, expire: u32) -> Self {
+        Self {
+            refresh,
+            retry,
+            expire,
+        }
+    }
+
+    /// How long to wait before the next Serial Query
+    pub const fn refresh(&self) -> u32 {
+        self.refresh
+    }
+
+    /// How long to wait before retrying a failed query
+    pub const fn retry(&self) -> u32 {
+        self.retry
+    }
+
+    /// How long the data is kept when it can't be refreshed
+    pub const fn expire(&self) -> u32 {
+        self.expire
+    }
+}
+
+impl Default for RtrIntervals {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_REFRESH_INTERVAL,
+            DEFAULT_RETRY_INTERVAL,
+            DEFAULT_EXPIRE_INTERVAL,
+        )
+    }
+}
+
+/// BGPsec router key, only in version 1
+///
+/// ```text
+/// 0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |          |          |
+/// | Version  |   Type   |  Flags   |   zero   |
+/// |    1     |    9     |          |          |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                  Length                   |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// +---                                     ---+
+/// |          Subject Key Identifier           |
+/// +---                                     ---+
+/// |                                           |
+/// +---                                     ---+
+/// |                (20 octets)                |
+/// +---                                     ---+
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                 AS Number                 |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// ~       Subject Public Key Info             ~
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RouterKey {
+    announce: bool,
+    ski: [u8; 20],
+    asn: u32,
+    spki: Vec<u8>,
+}
+
+impl RouterKey {
+    pub const fn new(announce: bool, ski: [u8; 20], asn: u32, spki: Vec<u8>) -> Self {
+        Self {
+            announce,
+            ski,
+            asn,
+            spki,
+        }
+    }
+
+    /// `true` for an announcement and `false` for a withdrawal
+    pub const fn announce(&self) -> bool {
+        self.announce
+    }
+
+    pub const fn ski(&self) -> &[u8; 20] {
+        &self.ski
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+
+    pub const fn spki(&self) -> &Vec<u8> {
+        &self.spki
+    }
+}
+
+/// ```text
+/// 0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |                     |
+/// | Version  |   Type   |     Error Code      |
+/// |          |    10    |                     |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                  Length                   |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |       Length of Encapsulated PDU          |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// ~               Erroneous PDU               ~
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |           Length of Error Text            |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// |              Arbitrary Text               |
+/// |                    of                     |
+/// ~          Error Diagnostic Message         ~
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    error_code: RtrErrorCode,
+    pdu: Vec<u8>,
+    text: String,
+}
+
+impl ErrorReport {
+    pub const fn new(error_code: RtrErrorCode, pdu: Vec<u8>, text: String) -> Self {
+        Self {
+            error_code,
+            pdu,
+            text,
+        }
+    }
+
+    pub const fn error_code(&self) -> RtrErrorCode {
+        self.error_code
+    }
+
+    /// Copy of the PDU that caused the error, could be empty
+    pub const fn pdu(&self) -> &Vec<u8> {
+        &self.pdu
+    }
+
+    pub const fn text(&self) -> &String {
+        &self.text
+    }
+}
+
+/// Autonomous System Provider Authorization (ASPA) of a customer AS, a
+/// withdrawal carries no provider
+///
+/// ```text
+/// 0          8          16         24        31
+/// .-------------------------------------------.
+/// | Protocol |   PDU    |                     |
+/// | Version  |   Type   |        zero         |
+/// |    2     |    11    |                     |
+/// +-------------------------------------------+
+/// |                                           |
+/// |                 Length                    |
+/// |                                           |
+/// +-------------------------------------------+
+/// |          |          |                     |
+/// |  Flags   |   zero   |        zero         |
+/// |          |          |                     |
+/// +-------------------------------------------+
+/// |                                           |
+/// |    Customer Autonomous System Number      |
+/// |                                           |
+/// +-------------------------------------------+
+/// |                                           |
+/// ~    Provider Autonomous System Numbers     ~
+/// |                                           |
+/// `-------------------------------------------'
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RtrAspa {
+    announce: bool,
+    customer_asn: u32,
+    providers: Vec<u32>,
+}
+
+impl RtrAspa {
+    pub const fn new(announce: bool, customer_asn: u32, providers: Vec<u32>) -> Self {
+        Self {
+            announce,
+            customer_asn,
+            providers,
+        }
+    }
+
+    /// `true` for an announcement and `false` for a withdrawal
+    pub const fn announce(&self) -> bool {
+        self.announce
+    }
+
+    pub const fn customer_asn(&self) -> u32 {
+        self.customer_asn
+    }
+
+    pub const fn providers(&self) -> &Vec<u32> {
+        &self.providers
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BGP prefix origin validation of routes, see [RFC6811](https://datatracker.ietf.org/doc/html/rfc6811),
+//! and the BGP Prefix Origin Validation State Extended Community
+//! [RFC8097](https://datatracker.ietf.org/doc/html/rfc8097)
+
+use ipnet::IpNet;
+use netgauze_bgp_pkt::{
+    community::{ExtendedCommunity, NonTransitiveOpaqueExtendedCommunity},
+    iana::OriginValidationState,
+    path_attribute::{
+        As4PathSegment, AsPathSegmentType, ExtendedCommunities, MpReach, PathAttribute,
+        PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+};
+
+use crate::vrp::VrpTable;
+
+/// Four-octet AS path of a route computed from the `AS_PATH` and `AS4_PATH`
+/// attributes, see [netgauze_bgp_pkt::path_attribute::AsPath::effective_as4_path].
+/// An empty path is returned when there is no `AS_PATH`.
+pub fn effective_as_path(attributes: &[PathAttribute]) -> Vec<As4PathSegment> {
+    let as4_path = attributes.iter().find_map(|attr| match attr.value() {
+        PathAttributeValue::As4Path(as4_path) => Some(as4_path),
+        _ => None,
+    });
+    attributes
+        .iter()
+        .find_map(|attr| match attr.value() {
+            PathAttributeValue::AsPath(as_path) => Some(as_path.effective_as4_path(as4_path)),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Origin AS of a route as defined in [RFC6811](https://datatracker.ietf.org/doc/html/rfc6811#section-2):
+/// the right-most AS of the final `AS_SEQUENCE` segment. `None` is returned
+/// when the final segment is an `AS_SET`, and `local_as` when the path is
+/// empty, i.e., the route was originated by the local AS or its
+/// confederation.
+pub fn route_origin(attributes: &[PathAttribute], local_as: u32) -> Option<u32> {
+    let as_path = effective_as_path(attributes);
+    let last_segment = as_path
+        .iter()
+        .rev()
+        .find(|segment| !segment.segment_type().is_confed());
+    match last_segment {
+        None => Some(local_as),
+        Some(segment) => match segment.segment_type() {
+            AsPathSegmentType::AsSequence => segment.as_numbers().last().copied(),
+            _ => None,
+        },
+    }
+}
+
+/// Validate the origin of a route with the given path attributes
+pub fn validate_route(
+    table: &VrpTable,
+    prefix: &IpNet,
+    attributes: &[PathAttribute],
+    local_as: u32,
+) -> OriginValidationState {
+    table.validate(prefix, route_origin(attributes, local_as))
+}
+
+/// Prefixes announced by a BGP update for the unicast and multicast address
+/// families
+pub fn announced_prefixes(update: &BgpUpdateMessage) -> Vec<IpNet> {
+    let mut prefixes: Vec<IpNet> = update
+        .nlri()
+        .iter()
+        .map(|addr| IpNet::V4(addr.network().address()))
+        .collect();
+    for attr in update.path_attributes() {
+        if let PathAttributeValue::MpReach(mp_reach) = attr.value() {
+            match mp_reach {
+                MpReach::Ipv4Unicast { nlri, .. } => {
+                    prefixes.extend(nlri.iter().map(|x| IpNet::V4(x.network().address())))
+                }
+                MpReach::Ipv4Multicast { nlri, .. } => {
+                    prefixes.extend(nlri.iter().map(|x| IpNet::V4(x.network().address())))
+                }
+                MpReach::Ipv6Unicast { nlri, .. } => {
+                    prefixes.extend(nlri.iter().map(|x| IpNet::V6(x.network().address())))
+                }
+                MpReach::Ipv6Multicast { nlri, .. } => {
+                    prefixes.extend(nlri.iter().map(|x| IpNet::V6(x.network().address())))
+                }
+                _ => {}
+            }
+        }
+    }
+    prefixes
+}
+
+/// Validate every prefix announced by a BGP update, see
+/// [announced_prefixes]
+pub fn validate_update(
+    table: &VrpTable,
+    update: &BgpUpdateMessage,
+    local_as: u32,
+) -> Vec<(IpNet, OriginValidationState)> {
+    let origin = route_origin(update.path_attributes(), local_as);
+    announced_prefixes(update)
+        .into_iter()
+        .map(|prefix| (prefix, table.validate(&prefix, origin)))
+        .collect()
+}
+
+/// Validate the routes reported in a BMP Route Monitoring message. Routes
+/// with an empty AS path are considered to be originated by the monitored
+/// peer's AS.
+#[cfg(feature = "bmp")]
+pub fn validate_route_monitoring(
+    table: &VrpTable,
+    msg: &netgauze_bmp_pkt::RouteMonitoringMessage,
+) -> Vec<(IpNet, OriginValidationState)> {
+    match msg.update_message() {
+        netgauze_bgp_pkt::BgpMessage::Update(update) => {
+            validate_update(table, update, msg.peer_header().peer_as())
+        }
+        _ => vec![],
+    }
+}
+
+/// Validation state signaled by the BGP Prefix Origin Validation State
+/// Extended Community
+pub fn origin_validation_state(attributes: &[PathAttribute]) -> Option<OriginValidationState> {
+    attributes.iter().find_map(|attr| match attr.value() {
+        PathAttributeValue::ExtendedCommunities(communities) => communities
+            .communities()
+            .iter()
+            .find_map(|community| match community {
+                ExtendedCommunity::NonTransitiveOpaque(
+                    NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState {
+                        validation_state,
+                    },
+                ) => Some(*validation_state),
+                _ => None,
+            }),
+        _ => None,
+    })
+}
+
+const fn is_origin_validation_state(community: &ExtendedCommunity) -> bool {
+    matches!(
+        community,
+        ExtendedCommunity::NonTransitiveOpaque(
+            NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState { .. }
+        )
+    )
+}
+
+/// Replace the BGP Prefix Origin Validation State Extended Community of a
+/// route, the community is removed when `state` is `None`. The Extended
+/// Communities attribute is removed if no other community is left.
+pub fn set_origin_validation_state(
+    attributes: &mut Vec<PathAttribute>,
+    state: Option<OriginValidationState>,
+) {
+    let position = attributes
+        .iter()
+        .position(|attr| matches!(attr.value(), PathAttributeValue::ExtendedCommunities(_)));
+    let mut communities: Vec<ExtendedCommunity> = match position {
+        Some(position) => match attributes.remove(position).value() {
+            PathAttributeValue::ExtendedCommunities(communities) => communities
+                .communities()
+                .iter()
+                .filter(|community| !is_origin_validation_state(community))
+                .cloned()
+                .collect(),
+            _ => vec![],
+        },
+        None => vec![],
+    };
+    if let Some(validation_state) = state {
+        communities.push(ExtendedCommunity::NonTransitiveOpaque(
+            NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState { validation_state },
+        ));
+    }
+    if communities.is_empty() {
+        return;
+    }
+    // Each extended community is 8-octets long
+    let extended_length = communities.len() * 8 > u8::MAX as usize;
+    let attr = PathAttribute::from(
+        true,
+        true,
+        false,
+        extended_length,
+        PathAttributeValue::ExtendedCommunities(ExtendedCommunities::new(communities)),
+    );
+    if let Ok(attr) = attr {
+        // Keep the attributes ordered by type code
+        let position = position.unwrap_or_else(|| {
+            attributes
+                .iter()
+                .position(|other| attribute_code(other) > attribute_code(&attr))
+                .unwrap_or(attributes.len())
+        });
+        attributes.insert(position, attr);
+    }
+}
+
+fn attribute_code(attr: &PathAttribute) -> u8 {
+    match attr.path_attribute_type() {
+        Ok(code) => code as u8,
+        Err(code) => code,
+    }
+}
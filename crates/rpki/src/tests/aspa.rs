@@ -0,0 +1,249 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use netgauze_bgp_pkt::path_attribute::{
+    As2PathSegment, As4Path, As4PathSegment, AsPath, AsPathSegmentType, PathAttribute,
+    PathAttributeValue,
+};
+
+use crate::aspa::*;
+
+fn table() -> AspaTable {
+    AspaTable::from_iter([
+        AspaRecord::new(64496, vec![64497]),
+        AspaRecord::new(64497, vec![64498]),
+        AspaRecord::new(64498, vec![0]),
+        AspaRecord::new(64509, vec![64498]),
+        AspaRecord::new(64511, vec![64509, 64498]),
+    ])
+}
+
+fn as_path_attr(as_path: AsPath) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(as_path),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_aspa_authorized() {
+    let table = table();
+    assert_eq!(
+        table.authorized(64496, 64497),
+        AspaAuthorization::ProviderPlus
+    );
+    assert_eq!(
+        table.authorized(64496, 64498),
+        AspaAuthorization::NotProviderPlus
+    );
+    assert_eq!(
+        table.authorized(64520, 64496),
+        AspaAuthorization::NoAttestation
+    );
+    assert_eq!(
+        table.get(64511),
+        Some(&AspaRecord::new(64511, vec![64498, 64509]))
+    );
+}
+
+#[test]
+fn test_aspa_verify_upstream() {
+    let table = table();
+    let verify = |path: &[u32]| table.verify(path, AspaDirection::Upstream);
+    assert_eq!(verify(&[]), AspaVerificationState::Invalid);
+    assert_eq!(verify(&[64520]), AspaVerificationState::Valid);
+    assert_eq!(verify(&[64498, 64497, 64496]), AspaVerificationState::Valid);
+    // Prepended ASes are collapsed
+    assert_eq!(
+        verify(&[64498, 64498, 64497, 64496, 64496]),
+        AspaVerificationState::Valid
+    );
+    // 64499 is not a provider of 64497
+    assert_eq!(
+        verify(&[64499, 64497, 64496]),
+        AspaVerificationState::Invalid
+    );
+    // 64520 has no ASPA
+    assert_eq!(verify(&[64521, 64520]), AspaVerificationState::Unknown);
+}
+
+#[test]
+fn test_aspa_verify_downstream() {
+    let table = table();
+    let verify = |path: &[u32]| table.verify(path, AspaDirection::Downstream);
+    assert_eq!(verify(&[64520, 64496]), AspaVerificationState::Valid);
+    // Up from 64496 to 64498 then down to 64509
+    assert_eq!(
+        verify(&[64509, 64498, 64497, 64496]),
+        AspaVerificationState::Valid
+    );
+    // 64511 leaks the routes of its provider 64498 to its other provider 64509
+    assert_eq!(
+        verify(&[64509, 64511, 64498, 64497, 64496]),
+        AspaVerificationState::Invalid
+    );
+    assert_eq!(
+        verify(&[64521, 64520, 64496]),
+        AspaVerificationState::Unknown
+    );
+}
+
+#[test]
+fn test_aspa_verify_route() {
+    let table = table();
+    let sequence = vec![as_path_attr(AsPath::As4PathSegments(vec![
+        As4PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![64509, 64498, 64497, 64496],
+        ),
+    ]))];
+    assert_eq!(
+        verify_route(&table, &sequence, AspaDirection::Downstream, Some(64509)),
+        AspaVerificationState::Valid
+    );
+    // The neighbor must be the first AS of the path
+    assert_eq!(
+        verify_route(&table, &sequence, AspaDirection::Downstream, Some(64510)),
+        AspaVerificationState::Invalid
+    );
+
+    let set = as_path_attr(AsPath::As4PathSegments(vec![
+        As4PathSegment::new(AsPathSegmentType::AsSequence, vec![64497]),
+        As4PathSegment::new(AsPathSegmentType::AsSet, vec![64496]),
+    ]));
+    assert_eq!(
+        verify_route(&table, &[set], AspaDirection::Upstream, None),
+        AspaVerificationState::Invalid
+    );
+
+    // The four-octet path is computed from AS4_PATH
+    let table = AspaTable::from_iter([AspaRecord::new(4200000000, vec![4200000001])]);
+    let attributes = vec![
+        as_path_attr(AsPath::As2PathSegments(vec![As2PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![23456, 23456],
+        )])),
+        PathAttribute::from(
+            true,
+            true,
+            false,
+            false,
+            PathAttributeValue::As4Path(As4Path::new(vec![As4PathSegment::new(
+                AsPathSegmentType::AsSequence,
+                vec![4200000001, 4200000000],
+            )])),
+        )
+        .unwrap(),
+    ];
+    assert_eq!(
+        verify_route(
+            &table,
+            &attributes,
+            AspaDirection::Upstream,
+            Some(4200000001)
+        ),
+        AspaVerificationState::Valid
+    );
+}
+
+#[test]
+fn test_aspa_table_from_json() {
+    let rpki_client = r#"{
+        "metadata": {"buildtime": "2024-01-01T00:00:00Z"},
+        "roas": [],
+        "aspas": [
+            {"customer_asid": 64496, "expires": 1704067200, "providers": [64498, 64497]},
+            {"customer_asid": 64498, "expires": 1704067200, "providers": [0]}
+        ]
+    }"#;
+    let routinator = r#"{
+        "aspas": [
+            {"customer": "AS64496", "providers": ["AS64497", "AS64498"]},
+            {"customer": "AS64498", "providers": ["AS0"]}
+        ]
+    }"#;
+    let expected = AspaTable::from_iter([
+        AspaRecord::new(64496, vec![64497, 64498]),
+        AspaRecord::new(64498, vec![0]),
+    ]);
+    assert_eq!(
+        AspaTable::from_json(rpki_client.as_bytes()).unwrap(),
+        expected
+    );
+    assert_eq!(
+        AspaTable::from_json(routinator.as_bytes()).unwrap(),
+        expected
+    );
+    assert!(AspaTable::from_json(
+        r#"{"aspas": [{"customer": "ASX", "providers": []}]}"#.as_bytes()
+    )
+    .is_err());
+}
+
+#[cfg(feature = "bmp")]
+#[test]
+fn test_verify_route_monitoring() {
+    use ipnet::Ipv4Net;
+    use netgauze_bgp_pkt::{
+        nlri::{Ipv4Unicast, Ipv4UnicastAddress},
+        update::BgpUpdateMessage,
+        BgpMessage,
+    };
+    use netgauze_bmp_pkt::{BmpPeerType, PeerHeader, RouteMonitoringMessage};
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        str::FromStr,
+    };
+
+    let peer_header = PeerHeader::new(
+        BmpPeerType::GlobalInstancePeer {
+            ipv6: false,
+            post_policy: false,
+            asn2: false,
+            adj_rib_out: false,
+        },
+        None,
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+        64509,
+        Ipv4Addr::new(192, 0, 2, 1),
+        None,
+    );
+    let as_path = as_path_attr(AsPath::As4PathSegments(vec![As4PathSegment::new(
+        AsPathSegmentType::AsSequence,
+        vec![64509, 64511, 64498, 64497, 64496],
+    )]));
+    let nlri = vec![Ipv4UnicastAddress::new_no_path_id(
+        Ipv4Unicast::from_net(Ipv4Net::from_str("192.0.2.0/24").unwrap()).unwrap(),
+    )];
+    let update = BgpUpdateMessage::new(vec![], vec![as_path.clone()], nlri);
+    let msg =
+        RouteMonitoringMessage::build(peer_header.clone(), BgpMessage::Update(update)).unwrap();
+    assert_eq!(
+        verify_route_monitoring(&table(), &msg, AspaDirection::Downstream),
+        Some(AspaVerificationState::Invalid)
+    );
+
+    // Nothing to verify without announced routes
+    let update = BgpUpdateMessage::new(vec![], vec![as_path], vec![]);
+    let msg = RouteMonitoringMessage::build(peer_header, BgpMessage::Update(update)).unwrap();
+    assert_eq!(
+        verify_route_monitoring(&table(), &msg, AspaDirection::Downstream),
+        None
+    );
+}
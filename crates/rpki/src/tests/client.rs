@@ -0,0 +1,558 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::{SinkExt, StreamExt};
+use ipnet::IpNet;
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tokio_util::codec::Framed;
+
+use crate::{
+    aspa::{AspaRecord, AspaTable},
+    client::*,
+    codec::RtrCodec,
+    iana::*,
+    vrp::Vrp,
+    *,
+};
+
+/// Fails the test instead of hanging if the client doesn't act in time
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Local stand-in for an RPKI cache, the test drives the session PDU by PDU
+struct StandInCache {
+    listener: TcpListener,
+}
+
+impl StandInCache {
+    async fn new() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Self { listener }
+    }
+
+    fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    async fn accept(&self) -> CacheConnection {
+        let (stream, _) = tokio::time::timeout(TEST_TIMEOUT, self.listener.accept())
+            .await
+            .expect("client didn't connect")
+            .unwrap();
+        CacheConnection(Framed::new(stream, RtrCodec))
+    }
+}
+
+struct CacheConnection(Framed<TcpStream, RtrCodec>);
+
+impl CacheConnection {
+    async fn recv(&mut self) -> Option<RtrPdu> {
+        tokio::time::timeout(TEST_TIMEOUT, self.0.next())
+            .await
+            .expect("no PDU received from the client")
+            .map(|pdu| pdu.unwrap())
+    }
+
+    async fn send(&mut self, version: RtrVersion, values: Vec<RtrPduValue>) {
+        for value in values {
+            self.0.send(RtrPdu::new(version, value)).await.unwrap();
+        }
+    }
+}
+
+fn net(value: &str) -> IpNet {
+    IpNet::from_str(value).unwrap()
+}
+
+fn prefix(announce: bool, value: &str, max_length: u8, asn: u32) -> RtrPduValue {
+    RtrPduValue::Prefix(RtrPrefix::new(announce, net(value), max_length, asn))
+}
+
+fn end_of_data(session_id: u16, serial: u32, refresh: u32) -> RtrPduValue {
+    RtrPduValue::EndOfData(EndOfData::new(
+        session_id,
+        serial,
+        Some(RtrIntervals::new(refresh, 1, 7200)),
+    ))
+}
+
+fn aspa(announce: bool, customer_asn: u32, providers: Vec<u32>) -> RtrPduValue {
+    RtrPduValue::Aspa(RtrAspa::new(announce, customer_asn, providers))
+}
+
+async fn wait_session(
+    session: &mut watch::Receiver<Option<RtrSession>>,
+    serial: u32,
+) -> RtrSession {
+    tokio::time::timeout(
+        TEST_TIMEOUT,
+        session.wait_for(|session| session.is_some_and(|session| session.serial() == serial)),
+    )
+    .await
+    .expect("client didn't synchronize")
+    .unwrap()
+    .unwrap()
+}
+
+fn vrps(data: &Arc<RpkiData>) -> Vec<Vrp> {
+    let mut vrps: Vec<Vrp> = data.vrps().iter().copied().collect();
+    vrps.sort();
+    vrps
+}
+
+#[tokio::test]
+async fn test_client_reset_and_serial_query() {
+    let cache = StandInCache::new().await;
+    let client = RtrClient::start(RtrClientConfig::new(cache.addr()));
+    let mut session = client.subscribe_session();
+    let mut conn = cache.accept().await;
+
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 7 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            prefix(true, "2001:db8::/32", 48, 64497),
+            end_of_data(7, 1, 1),
+        ],
+    )
+    .await;
+    assert_eq!(
+        wait_session(&mut session, 1).await,
+        RtrSession::new(RtrVersion::V1, 7, 1)
+    );
+    assert_eq!(
+        vrps(&client.data()),
+        vec![
+            Vrp::new(net("192.0.2.0/24"), 24, 64496),
+            Vrp::new(net("2001:db8::/32"), 48, 64497),
+        ]
+    );
+
+    // Serial Query is sent after the refresh interval
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(
+            RtrVersion::V1,
+            RtrPduValue::SerialQuery {
+                session_id: 7,
+                serial: 1
+            }
+        ))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 7 },
+            prefix(false, "192.0.2.0/24", 24, 64496),
+            prefix(true, "198.51.100.0/22", 24, 64498),
+            end_of_data(7, 2, 3600),
+        ],
+    )
+    .await;
+    wait_session(&mut session, 2).await;
+    assert_eq!(
+        vrps(&client.data()),
+        vec![
+            Vrp::new(net("198.51.100.0/22"), 24, 64498),
+            Vrp::new(net("2001:db8::/32"), 48, 64497),
+        ]
+    );
+
+    // Serial Notify triggers a Serial Query before the refresh interval
+    conn.send(
+        RtrVersion::V1,
+        vec![RtrPduValue::SerialNotify {
+            session_id: 7,
+            serial: 3,
+        }],
+    )
+    .await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(
+            RtrVersion::V1,
+            RtrPduValue::SerialQuery {
+                session_id: 7,
+                serial: 2
+            }
+        ))
+    );
+
+    // Cache Reset makes the client start again with a Reset Query
+    conn.send(RtrVersion::V1, vec![RtrPduValue::CacheReset])
+        .await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 8 },
+            prefix(true, "203.0.113.0/24", 24, 64499),
+            end_of_data(8, 10, 3600),
+        ],
+    )
+    .await;
+    assert_eq!(
+        wait_session(&mut session, 10).await,
+        RtrSession::new(RtrVersion::V1, 8, 10)
+    );
+    assert_eq!(
+        vrps(&client.data()),
+        vec![Vrp::new(net("203.0.113.0/24"), 24, 64499)]
+    );
+}
+
+#[tokio::test]
+async fn test_client_version_downgrade() {
+    let cache = StandInCache::new().await;
+    let client = RtrClient::start(RtrClientConfig::new(cache.addr()));
+    let mut session = client.subscribe_session();
+
+    // Version 0 only cache
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V0,
+        vec![RtrPduValue::ErrorReport(ErrorReport::new(
+            RtrErrorCode::UnsupportedProtocolVersion,
+            vec![],
+            String::new(),
+        ))],
+    )
+    .await;
+    drop(conn);
+
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V0, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V0,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            RtrPduValue::EndOfData(EndOfData::new(1, 5, None)),
+        ],
+    )
+    .await;
+    assert_eq!(
+        wait_session(&mut session, 5).await,
+        RtrSession::new(RtrVersion::V0, 1, 5)
+    );
+    assert_eq!(client.data().vrps().len(), 1);
+}
+
+#[tokio::test]
+async fn test_client_aspa() {
+    let cache = StandInCache::new().await;
+    let config = RtrClientConfig::new(cache.addr()).with_version(RtrVersion::V2);
+    let client = RtrClient::start(config);
+    let mut session = client.subscribe_session();
+    let mut conn = cache.accept().await;
+
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V2, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V2,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 7 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            aspa(true, 64496, vec![64498, 64497]),
+            aspa(true, 64500, vec![0]),
+            end_of_data(7, 1, 1),
+        ],
+    )
+    .await;
+    assert_eq!(
+        wait_session(&mut session, 1).await,
+        RtrSession::new(RtrVersion::V2, 7, 1)
+    );
+    assert_eq!(client.data().vrps().len(), 1);
+    assert_eq!(
+        client.data().aspas(),
+        &AspaTable::from_iter([
+            AspaRecord::new(64496, vec![64497, 64498]),
+            AspaRecord::new(64500, vec![0]),
+        ])
+    );
+
+    // An announcement replaces the providers of the customer
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(
+            RtrVersion::V2,
+            RtrPduValue::SerialQuery {
+                session_id: 7,
+                serial: 1
+            }
+        ))
+    );
+    conn.send(
+        RtrVersion::V2,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 7 },
+            aspa(true, 64496, vec![64499]),
+            aspa(false, 64500, vec![]),
+            end_of_data(7, 2, 1),
+        ],
+    )
+    .await;
+    wait_session(&mut session, 2).await;
+    assert_eq!(
+        client.data().aspas(),
+        &AspaTable::from_iter([AspaRecord::new(64496, vec![64499])])
+    );
+
+    // Withdrawal of an unknown customer
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(
+            RtrVersion::V2,
+            RtrPduValue::SerialQuery {
+                session_id: 7,
+                serial: 2
+            }
+        ))
+    );
+    conn.send(
+        RtrVersion::V2,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 7 },
+            aspa(false, 64500, vec![]),
+        ],
+    )
+    .await;
+    let Some(RtrPdu {
+        value: RtrPduValue::ErrorReport(report),
+        ..
+    }) = conn.recv().await
+    else {
+        panic!("expected an error report");
+    };
+    assert_eq!(report.error_code(), RtrErrorCode::WithdrawalOfUnknownRecord);
+    assert_eq!(client.data().aspas().len(), 1);
+}
+
+#[tokio::test]
+async fn test_client_version_downgrade_from_v2() {
+    let cache = StandInCache::new().await;
+    let config = RtrClientConfig::new(cache.addr()).with_version(RtrVersion::V2);
+    let client = RtrClient::start(config);
+    let mut session = client.subscribe_session();
+
+    // The cache doesn't support version 2 and reports the error with the
+    // version of the query
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V2, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V2,
+        vec![RtrPduValue::ErrorReport(ErrorReport::new(
+            RtrErrorCode::UnsupportedProtocolVersion,
+            vec![],
+            String::new(),
+        ))],
+    )
+    .await;
+    drop(conn);
+
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            end_of_data(1, 5, 3600),
+        ],
+    )
+    .await;
+    assert_eq!(
+        wait_session(&mut session, 5).await,
+        RtrSession::new(RtrVersion::V1, 1, 5)
+    );
+    assert!(client.data().aspas().is_empty());
+}
+
+#[tokio::test]
+async fn test_client_protocol_errors() {
+    let cache = StandInCache::new().await;
+    let config =
+        RtrClientConfig::new(cache.addr()).with_intervals(RtrIntervals::new(3600, 1, 7200));
+    let client = RtrClient::start(config);
+    let mut session = client.subscribe_session();
+
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            prefix(true, "192.0.2.0/24", 24, 64496),
+        ],
+    )
+    .await;
+    let Some(RtrPdu {
+        value: RtrPduValue::ErrorReport(report),
+        ..
+    }) = conn.recv().await
+    else {
+        panic!("expected an error report");
+    };
+    assert_eq!(
+        report.error_code(),
+        RtrErrorCode::DuplicateAnnouncementReceived
+    );
+    assert_eq!(conn.recv().await, None);
+    // The partial response is discarded
+    assert!(client.data().vrps().is_empty());
+    assert_eq!(client.session(), None);
+
+    // Client retries after the retry interval
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            end_of_data(1, 1, 3600),
+        ],
+    )
+    .await;
+    wait_session(&mut session, 1).await;
+
+    // Withdrawal of an unknown record
+    conn.send(
+        RtrVersion::V1,
+        vec![RtrPduValue::SerialNotify {
+            session_id: 1,
+            serial: 2,
+        }],
+    )
+    .await;
+    assert!(matches!(
+        conn.recv().await.map(RtrPdu::into_value),
+        Some(RtrPduValue::SerialQuery { .. })
+    ));
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(false, "198.51.100.0/24", 24, 64496),
+        ],
+    )
+    .await;
+    let Some(RtrPdu {
+        value: RtrPduValue::ErrorReport(report),
+        ..
+    }) = conn.recv().await
+    else {
+        panic!("expected an error report");
+    };
+    assert_eq!(report.error_code(), RtrErrorCode::WithdrawalOfUnknownRecord);
+    // The data is kept until it expires
+    assert_eq!(client.data().vrps().len(), 1);
+    assert_eq!(
+        client.session(),
+        Some(RtrSession::new(RtrVersion::V1, 1, 1))
+    );
+
+    // The reconnected client continues with a Serial Query, and a PDU with
+    // another version is rejected
+    let mut conn = cache.accept().await;
+    assert_eq!(
+        conn.recv().await,
+        Some(RtrPdu::new(
+            RtrVersion::V1,
+            RtrPduValue::SerialQuery {
+                session_id: 1,
+                serial: 1
+            }
+        ))
+    );
+    conn.send(
+        RtrVersion::V1,
+        vec![RtrPduValue::CacheResponse { session_id: 1 }],
+    )
+    .await;
+    conn.send(RtrVersion::V0, vec![end_of_data(1, 2, 3600)])
+        .await;
+    let Some(RtrPdu {
+        value: RtrPduValue::ErrorReport(report),
+        ..
+    }) = conn.recv().await
+    else {
+        panic!("expected an error report");
+    };
+    assert_eq!(report.error_code(), RtrErrorCode::UnexpectedProtocolVersion);
+}
+
+#[tokio::test]
+async fn test_client_data_expires() {
+    let cache = StandInCache::new().await;
+    let client = RtrClient::start(RtrClientConfig::new(cache.addr()));
+    let mut data = client.subscribe();
+
+    let mut conn = cache.accept().await;
+    conn.recv().await;
+    conn.send(
+        RtrVersion::V1,
+        vec![
+            RtrPduValue::CacheResponse { session_id: 1 },
+            prefix(true, "192.0.2.0/24", 24, 64496),
+            RtrPduValue::EndOfData(EndOfData::new(1, 1, Some(RtrIntervals::new(3600, 3600, 1)))),
+        ],
+    )
+    .await;
+    tokio::time::timeout(TEST_TIMEOUT, data.wait_for(|data| !data.vrps().is_empty()))
+        .await
+        .unwrap()
+        .unwrap();
+    // The cache doesn't answer anymore
+    tokio::time::timeout(TEST_TIMEOUT, data.wait_for(|data| data.vrps().is_empty()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(client.session(), None);
+}
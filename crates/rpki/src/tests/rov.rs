@@ -0,0 +1,273 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use netgauze_bgp_pkt::{
+    community::{ExtendedCommunity, NonTransitiveOpaqueExtendedCommunity},
+    iana::OriginValidationState,
+    nlri::{Ipv4Unicast, Ipv4UnicastAddress, Ipv6Unicast, Ipv6UnicastAddress},
+    path_attribute::{
+        As2PathSegment, As4Path, As4PathSegment, AsPath, AsPathSegmentType, ExtendedCommunities,
+        MpReach, Origin, PathAttribute, PathAttributeValue,
+    },
+    update::BgpUpdateMessage,
+};
+use std::{net::Ipv6Addr, str::FromStr};
+
+use crate::{
+    rov::*,
+    vrp::{Vrp, VrpTable},
+};
+
+fn net(value: &str) -> IpNet {
+    IpNet::from_str(value).unwrap()
+}
+
+fn origin_attr() -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::Origin(Origin::IGP),
+    )
+    .unwrap()
+}
+
+fn as_path_attr(segments: Vec<As4PathSegment>) -> PathAttribute {
+    PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As4PathSegments(segments)),
+    )
+    .unwrap()
+}
+
+fn extended_communities_attr(communities: Vec<ExtendedCommunity>) -> PathAttribute {
+    PathAttribute::from(
+        true,
+        true,
+        false,
+        false,
+        PathAttributeValue::ExtendedCommunities(ExtendedCommunities::new(communities)),
+    )
+    .unwrap()
+}
+
+fn state_community(validation_state: OriginValidationState) -> ExtendedCommunity {
+    ExtendedCommunity::NonTransitiveOpaque(
+        NonTransitiveOpaqueExtendedCommunity::BgpOriginValidationState { validation_state },
+    )
+}
+
+#[test]
+fn test_route_origin() {
+    let sequence = vec![As4PathSegment::new(
+        AsPathSegmentType::AsSequence,
+        vec![64496, 64497],
+    )];
+    assert_eq!(route_origin(&[as_path_attr(sequence)], 64500), Some(64497));
+
+    let set = vec![
+        As4PathSegment::new(AsPathSegmentType::AsSequence, vec![64496]),
+        As4PathSegment::new(AsPathSegmentType::AsSet, vec![64497, 64498]),
+    ];
+    assert_eq!(route_origin(&[as_path_attr(set)], 64500), None);
+
+    // Confederation segments are skipped
+    let confed = vec![As4PathSegment::new(
+        AsPathSegmentType::AsConfedSequence,
+        vec![65001],
+    )];
+    assert_eq!(route_origin(&[as_path_attr(confed)], 64500), Some(64500));
+    assert_eq!(route_origin(&[as_path_attr(vec![])], 64500), Some(64500));
+    assert_eq!(route_origin(&[], 64500), Some(64500));
+
+    // The 4-octet origin is recovered from AS4_PATH
+    let as2_path = PathAttribute::from(
+        false,
+        true,
+        false,
+        false,
+        PathAttributeValue::AsPath(AsPath::As2PathSegments(vec![As2PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![64496, 23456],
+        )])),
+    )
+    .unwrap();
+    let as4_path = PathAttribute::from(
+        true,
+        true,
+        false,
+        false,
+        PathAttributeValue::As4Path(As4Path::new(vec![As4PathSegment::new(
+            AsPathSegmentType::AsSequence,
+            vec![4200000000],
+        )])),
+    )
+    .unwrap();
+    assert_eq!(
+        route_origin(&[as2_path.clone(), as4_path], 64500),
+        Some(4200000000)
+    );
+    assert_eq!(route_origin(&[as2_path], 64500), Some(23456));
+}
+
+#[test]
+fn test_validate_update() {
+    let table: VrpTable = vec![
+        Vrp::new(net("192.0.2.0/24"), 24, 64497),
+        Vrp::new(net("198.51.100.0/24"), 24, 64496),
+        Vrp::new(net("2001:db8::/32"), 48, 64497),
+    ]
+    .into_iter()
+    .collect();
+    let mp_reach = PathAttribute::from(
+        true,
+        false,
+        false,
+        true,
+        PathAttributeValue::MpReach(MpReach::Ipv6Unicast {
+            next_hop_global: Ipv6Addr::from_str("2001:db8::1").unwrap(),
+            next_hop_local: None,
+            nlri: vec![Ipv6UnicastAddress::new(
+                None,
+                Ipv6Unicast::from_net(Ipv6Net::from_str("2001:db8:1::/48").unwrap()).unwrap(),
+            )],
+        }),
+    )
+    .unwrap();
+    let as_path = as_path_attr(vec![As4PathSegment::new(
+        AsPathSegmentType::AsSequence,
+        vec![64496, 64497],
+    )]);
+    let nlri = ["192.0.2.0/24", "198.51.100.0/24", "203.0.113.0/24"]
+        .iter()
+        .map(|prefix| {
+            Ipv4UnicastAddress::new_no_path_id(
+                Ipv4Unicast::from_net(Ipv4Net::from_str(prefix).unwrap()).unwrap(),
+            )
+        })
+        .collect();
+    let update = BgpUpdateMessage::new(vec![], vec![origin_attr(), as_path, mp_reach], nlri);
+    assert_eq!(
+        validate_update(&table, &update, 64500),
+        vec![
+            (net("192.0.2.0/24"), OriginValidationState::Valid),
+            (net("198.51.100.0/24"), OriginValidationState::Invalid),
+            (net("203.0.113.0/24"), OriginValidationState::NotFound),
+            (net("2001:db8:1::/48"), OriginValidationState::Valid),
+        ]
+    );
+    assert_eq!(
+        validate_route(
+            &table,
+            &net("198.51.100.0/24"),
+            update.path_attributes(),
+            64500
+        ),
+        OriginValidationState::Invalid
+    );
+}
+
+#[test]
+fn test_origin_validation_state_community() {
+    let other =
+        ExtendedCommunity::NonTransitiveOpaque(NonTransitiveOpaqueExtendedCommunity::Unassigned {
+            sub_type: 0x05,
+            value: [0, 0, 0, 0, 0, 1],
+        });
+    let mut attributes = vec![origin_attr()];
+    assert_eq!(origin_validation_state(&attributes), None);
+
+    set_origin_validation_state(&mut attributes, Some(OriginValidationState::Invalid));
+    assert_eq!(
+        attributes,
+        vec![
+            origin_attr(),
+            extended_communities_attr(vec![state_community(OriginValidationState::Invalid)])
+        ]
+    );
+    assert_eq!(
+        origin_validation_state(&attributes),
+        Some(OriginValidationState::Invalid)
+    );
+
+    // Replace the state and keep the other communities
+    let mut attributes = vec![
+        origin_attr(),
+        extended_communities_attr(vec![
+            state_community(OriginValidationState::NotFound),
+            other,
+        ]),
+    ];
+    set_origin_validation_state(&mut attributes, Some(OriginValidationState::Valid));
+    assert_eq!(
+        attributes,
+        vec![
+            origin_attr(),
+            extended_communities_attr(vec![other, state_community(OriginValidationState::Valid)])
+        ]
+    );
+
+    set_origin_validation_state(&mut attributes, None);
+    assert_eq!(
+        attributes,
+        vec![origin_attr(), extended_communities_attr(vec![other])]
+    );
+    let mut attributes = vec![
+        origin_attr(),
+        extended_communities_attr(vec![state_community(OriginValidationState::Valid)]),
+    ];
+    set_origin_validation_state(&mut attributes, None);
+    assert_eq!(attributes, vec![origin_attr()]);
+}
+
+#[cfg(feature = "bmp")]
+#[test]
+fn test_validate_route_monitoring() {
+    use netgauze_bgp_pkt::BgpMessage;
+    use netgauze_bmp_pkt::{BmpPeerType, PeerHeader, RouteMonitoringMessage};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let table: VrpTable = vec![Vrp::new(net("192.0.2.0/24"), 24, 64496)]
+        .into_iter()
+        .collect();
+    let peer_header = PeerHeader::new(
+        BmpPeerType::GlobalInstancePeer {
+            ipv6: false,
+            post_policy: false,
+            asn2: false,
+            adj_rib_out: false,
+        },
+        None,
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+        64496,
+        Ipv4Addr::new(192, 0, 2, 1),
+        None,
+    );
+    let nlri = vec![Ipv4UnicastAddress::new_no_path_id(
+        Ipv4Unicast::from_net(Ipv4Net::from_str("192.0.2.0/24").unwrap()).unwrap(),
+    )];
+    // Empty AS path from an iBGP peer, the origin is the peer's AS
+    let update = BgpUpdateMessage::new(vec![], vec![origin_attr(), as_path_attr(vec![])], nlri);
+    let msg = RouteMonitoringMessage::build(peer_header, BgpMessage::Update(update)).unwrap();
+    assert_eq!(
+        validate_route_monitoring(&table, &msg),
+        vec![(net("192.0.2.0/24"), OriginValidationState::Valid)]
+    );
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ipnet::IpNet;
+use netgauze_bgp_pkt::iana::OriginValidationState;
+use std::str::FromStr;
+
+use crate::{vrp::*, RtrPrefix};
+
+fn net(value: &str) -> IpNet {
+    IpNet::from_str(value).unwrap()
+}
+
+#[test]
+fn test_vrp_table() {
+    let mut table = VrpTable::new();
+    let vrp = Vrp::new(net("192.0.2.1/24"), 24, 64496);
+    assert_eq!(vrp.prefix(), net("192.0.2.0/24"));
+    assert!(table.insert(vrp));
+    assert!(!table.insert(vrp));
+    assert!(table.insert(Vrp::new(net("192.0.2.0/24"), 25, 64496)));
+    assert!(table.insert(Vrp::new(net("192.0.0.0/16"), 24, 64497)));
+    assert_eq!(table.len(), 3);
+    assert!(table.contains(&vrp));
+    assert_eq!(table.covering(net("192.0.2.128/25")).count(), 3);
+    assert_eq!(table.covering(net("192.0.3.0/24")).count(), 1);
+    assert_eq!(table.covering(net("198.51.100.0/24")).count(), 0);
+
+    assert!(table.remove(&vrp));
+    assert!(!table.remove(&vrp));
+    assert!(!table.contains(&vrp));
+    assert_eq!(table.len(), 2);
+
+    let from_rtr = Vrp::from(&RtrPrefix::new(true, net("2001:db8::/32"), 48, 64496));
+    assert_eq!(from_rtr, Vrp::new(net("2001:db8::/32"), 48, 64496));
+    let table: VrpTable = vec![from_rtr, from_rtr].into_iter().collect();
+    assert_eq!(table.len(), 1);
+    assert_eq!(table.iter().collect::<Vec<_>>(), vec![&from_rtr]);
+}
+
+#[test]
+fn test_vrp_table_validate() {
+    let table: VrpTable = vec![
+        Vrp::new(net("192.0.2.0/24"), 24, 64496),
+        Vrp::new(net("198.51.100.0/22"), 24, 64497),
+        Vrp::new(net("203.0.113.0/24"), 24, 0),
+        Vrp::new(net("2001:db8::/32"), 48, 64496),
+    ]
+    .into_iter()
+    .collect();
+
+    assert_eq!(
+        table.validate(&net("192.0.2.0/24"), Some(64496)),
+        OriginValidationState::Valid
+    );
+    // Wrong origin
+    assert_eq!(
+        table.validate(&net("192.0.2.0/24"), Some(64497)),
+        OriginValidationState::Invalid
+    );
+    // Longer than the max length
+    assert_eq!(
+        table.validate(&net("192.0.2.0/25"), Some(64496)),
+        OriginValidationState::Invalid
+    );
+    assert_eq!(
+        table.validate(&net("198.51.101.0/24"), Some(64497)),
+        OriginValidationState::Valid
+    );
+    // AS_SET origin never matches
+    assert_eq!(
+        table.validate(&net("198.51.100.0/22"), None),
+        OriginValidationState::Invalid
+    );
+    // AS 0 VRPs never match
+    assert_eq!(
+        table.validate(&net("203.0.113.0/24"), Some(0)),
+        OriginValidationState::Invalid
+    );
+    assert_eq!(
+        table.validate(&net("2001:db8:1::/48"), Some(64496)),
+        OriginValidationState::Valid
+    );
+    assert_eq!(
+        table.validate(&net("192.0.0.0/16"), Some(64496)),
+        OriginValidationState::NotFound
+    );
+    assert_eq!(
+        table.validate(&net("2001:db9::/32"), Some(64496)),
+        OriginValidationState::NotFound
+    );
+}
@@ -0,0 +1,161 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validated ROA Payload (VRP) table and prefix origin validation as
+//! described in [RFC6811](https://datatracker.ietf.org/doc/html/rfc6811)
+
+use ipnet::IpNet;
+use netgauze_bgp_pkt::iana::OriginValidationState;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::RtrPrefix;
+
+/// Validated ROA Payload, the prefix is always truncated to its length
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Vrp {
+    prefix: IpNet,
+    max_length: u8,
+    asn: u32,
+}
+
+impl Vrp {
+    pub fn new(prefix: IpNet, max_length: u8, asn: u32) -> Self {
+        Self {
+            prefix: prefix.trunc(),
+            max_length,
+            asn,
+        }
+    }
+
+    pub const fn prefix(&self) -> IpNet {
+        self.prefix
+    }
+
+    pub const fn max_length(&self) -> u8 {
+        self.max_length
+    }
+
+    pub const fn asn(&self) -> u32 {
+        self.asn
+    }
+
+    /// A route matches the VRP when the VRP prefix covers the route's prefix,
+    /// the route's prefix is not longer than the max length and the origin
+    /// AS is the same. AS 0 never matches, see [RFC6483](https://datatracker.ietf.org/doc/html/rfc6483#section-4).
+    pub fn matches(&self, prefix: &IpNet, origin: Option<u32>) -> bool {
+        self.prefix.contains(prefix)
+            && prefix.prefix_len() <= self.max_length
+            && self.asn != 0
+            && origin == Some(self.asn)
+    }
+}
+
+impl From<&RtrPrefix> for Vrp {
+    fn from(value: &RtrPrefix) -> Self {
+        Self::new(value.prefix(), value.max_length(), value.asn())
+    }
+}
+
+/// Set of VRPs indexed by prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VrpTable {
+    vrps: HashMap<IpNet, HashSet<Vrp>>,
+    len: usize,
+}
+
+impl VrpTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `false` if the VRP is already in the table
+    pub fn insert(&mut self, vrp: Vrp) -> bool {
+        let inserted = self.vrps.entry(vrp.prefix).or_default().insert(vrp);
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    /// Returns `false` if the VRP is not in the table
+    pub fn remove(&mut self, vrp: &Vrp) -> bool {
+        let Some(vrps) = self.vrps.get_mut(&vrp.prefix) else {
+            return false;
+        };
+        let removed = vrps.remove(vrp);
+        if vrps.is_empty() {
+            self.vrps.remove(&vrp.prefix);
+        }
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn contains(&self, vrp: &Vrp) -> bool {
+        self.vrps
+            .get(&vrp.prefix)
+            .is_some_and(|vrps| vrps.contains(vrp))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vrp> {
+        self.vrps.values().flatten()
+    }
+
+    /// VRPs with a prefix that covers the given prefix, these are the
+    /// candidate VRPs in [RFC6811](https://datatracker.ietf.org/doc/html/rfc6811#section-2)
+    pub fn covering(&self, prefix: IpNet) -> impl Iterator<Item = &Vrp> {
+        (0..=prefix.prefix_len())
+            .filter_map(move |len| IpNet::new(prefix.addr(), len).ok())
+            .filter_map(|net| self.vrps.get(&net.trunc()))
+            .flatten()
+    }
+
+    /// Validate the origin AS of a route. The origin is `None` when the AS
+    /// path ends with an `AS_SET`, such routes are never valid.
+    pub fn validate(&self, prefix: &IpNet, origin: Option<u32>) -> OriginValidationState {
+        let mut covered = false;
+        for vrp in self.covering(*prefix) {
+            if vrp.matches(prefix, origin) {
+                return OriginValidationState::Valid;
+            }
+            covered = true;
+        }
+        if covered {
+            OriginValidationState::Invalid
+        } else {
+            OriginValidationState::NotFound
+        }
+    }
+}
+
+impl FromIterator<Vrp> for VrpTable {
+    fn from_iter<T: IntoIterator<Item = Vrp>>(iter: T) -> Self {
+        let mut table = Self::new();
+        for vrp in iter {
+            table.insert(vrp);
+        }
+        table
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deserializer library for RTR's wire format
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    string::FromUtf8Error,
+};
+
+use netgauze_parse_utils::{ErrorKindSerdeDeref, ReadablePdu, Span};
+use netgauze_serde_macros::LocatedError;
+use nom::{
+    error::{ErrorKind, FromExternalError},
+    number::complete::{be_u128, be_u16, be_u32, be_u8},
+    IResult,
+};
+
+use crate::{
+    iana::*,
+    wire::{RTR_HEADER_LENGTH, SKI_LENGTH},
+    *,
+};
+
+#[derive(LocatedError, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum RtrPduParsingError {
+    #[serde(with = "ErrorKindSerdeDeref")]
+    NomError(#[from_nom] ErrorKind),
+    UndefinedRtrVersion(#[from_external] UndefinedRtrVersion),
+    UndefinedRtrPduType(#[from_external] UndefinedRtrPduType),
+    UndefinedRtrErrorCode(#[from_external] UndefinedRtrErrorCode),
+    InvalidPduLength(RtrPduType, u32),
+    /// The PDU type is not defined in the PDU's protocol version
+    UnsupportedPduType(RtrVersion, RtrPduType),
+    InvalidPrefixLength(u8),
+    InvalidMaxLength(u8),
+    InvalidErrorText(String),
+}
+
+impl<'a> FromExternalError<Span<'a>, FromUtf8Error> for LocatedRtrPduParsingError<'a> {
+    fn from_external_error(input: Span<'a>, _kind: ErrorKind, error: FromUtf8Error) -> Self {
+        LocatedRtrPduParsingError::new(
+            input,
+            RtrPduParsingError::InvalidErrorText(error.to_string()),
+        )
+    }
+}
+
+/// Check the length of the PDU, the length of Router Key, Error Report and
+/// ASPA PDUs are variable and only the minimum length is checked
+const fn valid_length(version: RtrVersion, pdu_type: RtrPduType, length: u32) -> bool {
+    match pdu_type {
+        RtrPduType::SerialNotify | RtrPduType::SerialQuery => length == 12,
+        RtrPduType::ResetQuery | RtrPduType::CacheResponse | RtrPduType::CacheReset => length == 8,
+        RtrPduType::Ipv4Prefix => length == 20,
+        RtrPduType::Ipv6Prefix => length == 32,
+        RtrPduType::EndOfData => match version {
+            RtrVersion::V0 => length == 12,
+            RtrVersion::V1 | RtrVersion::V2 => length == 24,
+        },
+        RtrPduType::RouterKey => length >= 32,
+        RtrPduType::ErrorReport => length >= 16,
+        // Provider ASNs are four octets each
+        RtrPduType::Aspa => length >= 16 && length & 0x3 == 0,
+    }
+}
+
+impl<'a> ReadablePdu<'a, LocatedRtrPduParsingError<'a>> for RtrPdu {
+    fn from_wire(buf: Span<'a>) -> IResult<Span<'a>, Self, LocatedRtrPduParsingError<'a>> {
+        let input = buf;
+        let (buf, version) = nom::combinator::map_res(be_u8, RtrVersion::try_from)(buf)?;
+        let (buf, pdu_type) = nom::combinator::map_res(be_u8, RtrPduType::try_from)(buf)?;
+        let supported = match pdu_type {
+            RtrPduType::RouterKey => version >= RtrVersion::V1,
+            RtrPduType::Aspa => version >= RtrVersion::V2,
+            _ => true,
+        };
+        if !supported {
+            return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+                input,
+                RtrPduParsingError::UnsupportedPduType(version, pdu_type),
+            )));
+        }
+        let (buf, header_value) = be_u16(buf)?;
+        let (buf, length) = be_u32(buf)?;
+        if !valid_length(version, pdu_type, length) {
+            return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+                input,
+                RtrPduParsingError::InvalidPduLength(pdu_type, length),
+            )));
+        }
+        let (reminder, buf) = nom::bytes::complete::take(length as usize - RTR_HEADER_LENGTH)(buf)?;
+        let (buf, value) = match pdu_type {
+            RtrPduType::SerialNotify => {
+                let (buf, serial) = be_u32(buf)?;
+                let value = RtrPduValue::SerialNotify {
+                    session_id: header_value,
+                    serial,
+                };
+                (buf, value)
+            }
+            RtrPduType::SerialQuery => {
+                let (buf, serial) = be_u32(buf)?;
+                let value = RtrPduValue::SerialQuery {
+                    session_id: header_value,
+                    serial,
+                };
+                (buf, value)
+            }
+            RtrPduType::ResetQuery => (buf, RtrPduValue::ResetQuery),
+            RtrPduType::CacheResponse => (
+                buf,
+                RtrPduValue::CacheResponse {
+                    session_id: header_value,
+                },
+            ),
+            RtrPduType::Ipv4Prefix | RtrPduType::Ipv6Prefix => {
+                let (buf, prefix) = parse_prefix(buf, pdu_type == RtrPduType::Ipv6Prefix)?;
+                (buf, RtrPduValue::Prefix(prefix))
+            }
+            RtrPduType::EndOfData => {
+                let (buf, serial) = be_u32(buf)?;
+                let (buf, intervals) = match version {
+                    RtrVersion::V0 => (buf, None),
+                    RtrVersion::V1 | RtrVersion::V2 => {
+                        let (buf, refresh) = be_u32(buf)?;
+                        let (buf, retry) = be_u32(buf)?;
+                        let (buf, expire) = be_u32(buf)?;
+                        (buf, Some(RtrIntervals::new(refresh, retry, expire)))
+                    }
+                };
+                let value = RtrPduValue::EndOfData(EndOfData::new(header_value, serial, intervals));
+                (buf, value)
+            }
+            RtrPduType::CacheReset => (buf, RtrPduValue::CacheReset),
+            RtrPduType::RouterKey => {
+                let announce = (header_value >> 8) as u8 & RTR_FLAG_ANNOUNCE == RTR_FLAG_ANNOUNCE;
+                let (buf, ski) = nom::bytes::complete::take(SKI_LENGTH)(buf)?;
+                let mut ski_value = [0; SKI_LENGTH];
+                ski_value.copy_from_slice(ski.fragment());
+                let (buf, asn) = be_u32(buf)?;
+                let (buf, spki) = nom::bytes::complete::take(buf.len())(buf)?;
+                let value =
+                    RtrPduValue::RouterKey(RouterKey::new(announce, ski_value, asn, spki.to_vec()));
+                (buf, value)
+            }
+            RtrPduType::ErrorReport => {
+                let error_code = match RtrErrorCode::try_from(header_value) {
+                    Ok(error_code) => error_code,
+                    Err(err) => {
+                        return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+                            input,
+                            RtrPduParsingError::UndefinedRtrErrorCode(err),
+                        )))
+                    }
+                };
+                let (buf, pdu_length) = be_u32(buf)?;
+                let (buf, pdu) = nom::bytes::complete::take(pdu_length)(buf)?;
+                let (buf, text_length) = be_u32(buf)?;
+                let (buf, text) = nom::combinator::map_res(
+                    nom::bytes::complete::take(text_length),
+                    |x: Span<'_>| String::from_utf8(x.to_vec()),
+                )(buf)?;
+                let value =
+                    RtrPduValue::ErrorReport(ErrorReport::new(error_code, pdu.to_vec(), text));
+                (buf, value)
+            }
+            RtrPduType::Aspa => {
+                let (buf, flags) = be_u8(buf)?;
+                let announce = flags & RTR_FLAG_ANNOUNCE == RTR_FLAG_ANNOUNCE;
+                let (buf, _zero) = be_u8(buf)?;
+                let (buf, _zero) = be_u16(buf)?;
+                let (buf, customer_asn) = be_u32(buf)?;
+                let (buf, providers) = nom::multi::many0(be_u32)(buf)?;
+                let value = RtrPduValue::Aspa(RtrAspa::new(announce, customer_asn, providers));
+                (buf, value)
+            }
+        };
+        // Make sure the PDU is fully parsed according to its length
+        if !buf.is_empty() {
+            return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+                buf,
+                RtrPduParsingError::NomError(ErrorKind::NonEmpty),
+            )));
+        }
+        Ok((reminder, RtrPdu::new(version, value)))
+    }
+}
+
+fn parse_prefix(
+    buf: Span<'_>,
+    ipv6: bool,
+) -> IResult<Span<'_>, RtrPrefix, LocatedRtrPduParsingError<'_>> {
+    let (buf, flags) = be_u8(buf)?;
+    let announce = flags & RTR_FLAG_ANNOUNCE == RTR_FLAG_ANNOUNCE;
+    let input = buf;
+    let (buf, prefix_length) = be_u8(buf)?;
+    let max_prefix_length = if ipv6 { 128 } else { 32 };
+    if prefix_length > max_prefix_length {
+        return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+            input,
+            RtrPduParsingError::InvalidPrefixLength(prefix_length),
+        )));
+    }
+    let input = buf;
+    let (buf, max_length) = be_u8(buf)?;
+    if max_length < prefix_length || max_length > max_prefix_length {
+        return Err(nom::Err::Error(LocatedRtrPduParsingError::new(
+            input,
+            RtrPduParsingError::InvalidMaxLength(max_length),
+        )));
+    }
+    let (buf, _zero) = be_u8(buf)?;
+    let (buf, prefix) = if ipv6 {
+        let (buf, addr) = be_u128(buf)?;
+        // Prefix length is already checked, so the unwrap is safe
+        let prefix = Ipv6Net::new(Ipv6Addr::from(addr), prefix_length).unwrap();
+        (buf, IpNet::V6(prefix))
+    } else {
+        let (buf, addr) = be_u32(buf)?;
+        let prefix = Ipv4Net::new(Ipv4Addr::from(addr), prefix_length).unwrap();
+        (buf, IpNet::V4(prefix))
+    };
+    let (buf, asn) = be_u32(buf)?;
+    Ok((buf, RtrPrefix::new(announce, prefix, max_length, asn)))
+}
@@ -0,0 +1,27 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serialize/Deserialize RTR wire format
+
+pub mod deserializer;
+pub mod serializer;
+#[cfg(test)]
+mod tests;
+
+/// 1-octet version, 1-octet type, 2-octets session id and 4-octets length
+pub const RTR_HEADER_LENGTH: usize = 8;
+
+/// 20-octets Subject Key Identifier
+pub(crate) const SKI_LENGTH: usize = 20;
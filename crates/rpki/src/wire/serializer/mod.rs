@@ -0,0 +1,131 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serializer library for RTR's wire format
+
+use byteorder::{NetworkEndian, WriteBytesExt};
+use ipnet::IpNet;
+use std::io::Write;
+
+use netgauze_parse_utils::WritablePdu;
+use netgauze_serde_macros::WritingError;
+
+use crate::{iana::*, *};
+
+#[derive(WritingError, Eq, PartialEq, Clone, Debug)]
+pub enum RtrPduWritingError {
+    StdIOError(#[from_std_io_error] String),
+}
+
+impl WritablePdu<RtrPduWritingError> for RtrPdu {
+    /// 1-octet version, 1-octet type, 2-octets session id and 4-octets length
+    const BASE_LENGTH: usize = 8;
+
+    fn len(&self) -> usize {
+        let value_len = match &self.value {
+            RtrPduValue::SerialNotify { .. } | RtrPduValue::SerialQuery { .. } => 4,
+            RtrPduValue::ResetQuery
+            | RtrPduValue::CacheResponse { .. }
+            | RtrPduValue::CacheReset => 0,
+            RtrPduValue::Prefix(prefix) => match prefix.prefix {
+                IpNet::V4(_) => 12,
+                IpNet::V6(_) => 24,
+            },
+            RtrPduValue::EndOfData(_) => match self.version {
+                RtrVersion::V0 => 4,
+                RtrVersion::V1 | RtrVersion::V2 => 16,
+            },
+            RtrPduValue::RouterKey(key) => key.ski.len() + 4 + key.spki.len(),
+            RtrPduValue::ErrorReport(report) => 4 + report.pdu.len() + 4 + report.text.len(),
+            RtrPduValue::Aspa(aspa) => 4 + 4 + 4 * aspa.providers.len(),
+        };
+        Self::BASE_LENGTH + value_len
+    }
+
+    fn write<T: Write>(&self, writer: &mut T) -> Result<(), RtrPduWritingError> {
+        writer.write_u8(self.version.into())?;
+        writer.write_u8(self.pdu_type().into())?;
+        let header_value = match &self.value {
+            RtrPduValue::SerialNotify { session_id, .. }
+            | RtrPduValue::SerialQuery { session_id, .. }
+            | RtrPduValue::CacheResponse { session_id } => *session_id,
+            RtrPduValue::EndOfData(end_of_data) => end_of_data.session_id,
+            RtrPduValue::RouterKey(key) => {
+                let flags = if key.announce { RTR_FLAG_ANNOUNCE } else { 0 };
+                (flags as u16) << 8
+            }
+            RtrPduValue::ErrorReport(report) => report.error_code.into(),
+            RtrPduValue::ResetQuery
+            | RtrPduValue::Prefix(_)
+            | RtrPduValue::CacheReset
+            | RtrPduValue::Aspa(_) => 0,
+        };
+        writer.write_u16::<NetworkEndian>(header_value)?;
+        writer.write_u32::<NetworkEndian>(self.len() as u32)?;
+        match &self.value {
+            RtrPduValue::SerialNotify { serial, .. } | RtrPduValue::SerialQuery { serial, .. } => {
+                writer.write_u32::<NetworkEndian>(*serial)?;
+            }
+            RtrPduValue::ResetQuery
+            | RtrPduValue::CacheResponse { .. }
+            | RtrPduValue::CacheReset => {}
+            RtrPduValue::Prefix(prefix) => {
+                writer.write_u8(if prefix.announce {
+                    RTR_FLAG_ANNOUNCE
+                } else {
+                    0
+                })?;
+                writer.write_u8(prefix.prefix.prefix_len())?;
+                writer.write_u8(prefix.max_length)?;
+                writer.write_u8(0)?;
+                match prefix.prefix {
+                    IpNet::V4(net) => writer.write_all(&net.addr().octets())?,
+                    IpNet::V6(net) => writer.write_all(&net.addr().octets())?,
+                }
+                writer.write_u32::<NetworkEndian>(prefix.asn)?;
+            }
+            RtrPduValue::EndOfData(end_of_data) => {
+                writer.write_u32::<NetworkEndian>(end_of_data.serial)?;
+                if self.version != RtrVersion::V0 {
+                    let intervals = end_of_data.intervals.unwrap_or_default();
+                    writer.write_u32::<NetworkEndian>(intervals.refresh)?;
+                    writer.write_u32::<NetworkEndian>(intervals.retry)?;
+                    writer.write_u32::<NetworkEndian>(intervals.expire)?;
+                }
+            }
+            RtrPduValue::RouterKey(key) => {
+                writer.write_all(&key.ski)?;
+                writer.write_u32::<NetworkEndian>(key.asn)?;
+                writer.write_all(&key.spki)?;
+            }
+            RtrPduValue::ErrorReport(report) => {
+                writer.write_u32::<NetworkEndian>(report.pdu.len() as u32)?;
+                writer.write_all(&report.pdu)?;
+                writer.write_u32::<NetworkEndian>(report.text.len() as u32)?;
+                writer.write_all(report.text.as_bytes())?;
+            }
+            RtrPduValue::Aspa(aspa) => {
+                writer.write_u8(if aspa.announce { RTR_FLAG_ANNOUNCE } else { 0 })?;
+                writer.write_u8(0)?;
+                writer.write_u16::<NetworkEndian>(0)?;
+                writer.write_u32::<NetworkEndian>(aspa.customer_asn)?;
+                for provider in &aspa.providers {
+                    writer.write_u32::<NetworkEndian>(*provider)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright (C) 2024-present The NetGauze Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ipnet::IpNet;
+use netgauze_parse_utils::{
+    test_helpers::{test_parse_error, test_parsed_completely, test_write},
+    Span,
+};
+use nom::error::ErrorKind;
+use std::str::FromStr;
+
+use crate::{
+    iana::*,
+    wire::{deserializer::*, serializer::*},
+    *,
+};
+
+#[test]
+fn test_serial_notify_and_query() -> Result<(), RtrPduWritingError> {
+    let good_notify_wire = [
+        0x01, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x2a,
+    ];
+    let good_query_wire = [
+        0x00, 0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x2a,
+    ];
+    let bad_length_wire = [
+        0x01, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x2a, 0x00,
+    ];
+
+    let good_notify = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::SerialNotify {
+            session_id: 7,
+            serial: 42,
+        },
+    );
+    let good_query = RtrPdu::new(
+        RtrVersion::V0,
+        RtrPduValue::SerialQuery {
+            session_id: 7,
+            serial: 42,
+        },
+    );
+    let bad_length = LocatedRtrPduParsingError::new(
+        Span::new(&bad_length_wire),
+        RtrPduParsingError::InvalidPduLength(RtrPduType::SerialNotify, 13),
+    );
+
+    test_parsed_completely(&good_notify_wire, &good_notify);
+    test_parsed_completely(&good_query_wire, &good_query);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_length_wire, &bad_length);
+
+    test_write(&good_notify, &good_notify_wire)?;
+    test_write(&good_query, &good_query_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_reset_query_and_cache_response() -> Result<(), RtrPduWritingError> {
+    let good_reset_query_wire = [0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08];
+    let good_cache_response_wire = [0x01, 0x03, 0x12, 0x34, 0x00, 0x00, 0x00, 0x08];
+    let good_cache_reset_wire = [0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08];
+    let bad_version_wire = [0x05, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08];
+    let bad_type_wire = [0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08];
+
+    let good_reset_query = RtrPdu::new(RtrVersion::V1, RtrPduValue::ResetQuery);
+    let good_cache_response = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::CacheResponse { session_id: 0x1234 },
+    );
+    let good_cache_reset = RtrPdu::new(RtrVersion::V0, RtrPduValue::CacheReset);
+    let bad_version = LocatedRtrPduParsingError::new(
+        Span::new(&bad_version_wire),
+        RtrPduParsingError::UndefinedRtrVersion(UndefinedRtrVersion(5)),
+    );
+    let bad_type = LocatedRtrPduParsingError::new(
+        unsafe { Span::new_from_raw_offset(1, &bad_type_wire[1..]) },
+        RtrPduParsingError::UndefinedRtrPduType(UndefinedRtrPduType(5)),
+    );
+
+    test_parsed_completely(&good_reset_query_wire, &good_reset_query);
+    test_parsed_completely(&good_cache_response_wire, &good_cache_response);
+    test_parsed_completely(&good_cache_reset_wire, &good_cache_reset);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_version_wire, &bad_version);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_type_wire, &bad_type);
+
+    test_write(&good_reset_query, &good_reset_query_wire)?;
+    test_write(&good_cache_response, &good_cache_response_wire)?;
+    test_write(&good_cache_reset, &good_cache_reset_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_prefix() -> Result<(), RtrPduWritingError> {
+    let good_ipv4_wire = [
+        0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x01, 0x10, 0x18, 0x00, 0xc0, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xfb, 0xf0,
+    ];
+    let good_ipv6_wire = [
+        0x01, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x20, 0x30, 0x00, 0x20, 0x01, 0x0d,
+        0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfa, 0x56,
+        0xea, 0x00,
+    ];
+    let bad_prefix_length_wire = [
+        0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x01, 0x21, 0x21, 0x00, 0xc0, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xfb, 0xf0,
+    ];
+    let bad_max_length_wire = [
+        0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x01, 0x18, 0x10, 0x00, 0xc0, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xfb, 0xf0,
+    ];
+
+    let good_ipv4 = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::Prefix(RtrPrefix::new(
+            true,
+            IpNet::from_str("192.0.0.0/16").unwrap(),
+            24,
+            64496,
+        )),
+    );
+    let good_ipv6 = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::Prefix(RtrPrefix::new(
+            false,
+            IpNet::from_str("2001:db8::/32").unwrap(),
+            48,
+            4200000000,
+        )),
+    );
+    let bad_prefix_length = LocatedRtrPduParsingError::new(
+        unsafe { Span::new_from_raw_offset(9, &bad_prefix_length_wire[9..]) },
+        RtrPduParsingError::InvalidPrefixLength(33),
+    );
+    let bad_max_length = LocatedRtrPduParsingError::new(
+        unsafe { Span::new_from_raw_offset(10, &bad_max_length_wire[10..]) },
+        RtrPduParsingError::InvalidMaxLength(16),
+    );
+
+    test_parsed_completely(&good_ipv4_wire, &good_ipv4);
+    test_parsed_completely(&good_ipv6_wire, &good_ipv6);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(
+        &bad_prefix_length_wire,
+        &bad_prefix_length,
+    );
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(
+        &bad_max_length_wire,
+        &bad_max_length,
+    );
+
+    test_write(&good_ipv4, &good_ipv4_wire)?;
+    test_write(&good_ipv6, &good_ipv6_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_end_of_data() -> Result<(), RtrPduWritingError> {
+    let good_v0_wire = [
+        0x00, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x02,
+    ];
+    let good_v1_wire = [
+        0x01, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x0e,
+        0x10, 0x00, 0x00, 0x02, 0x58, 0x00, 0x00, 0x1c, 0x20,
+    ];
+    let bad_v1_length_wire = [
+        0x01, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x02,
+    ];
+
+    let good_v0 = RtrPdu::new(
+        RtrVersion::V0,
+        RtrPduValue::EndOfData(EndOfData::new(1, 2, None)),
+    );
+    let good_v1 = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::EndOfData(EndOfData::new(1, 2, Some(RtrIntervals::default()))),
+    );
+    let bad_v1_length = LocatedRtrPduParsingError::new(
+        Span::new(&bad_v1_length_wire),
+        RtrPduParsingError::InvalidPduLength(RtrPduType::EndOfData, 12),
+    );
+
+    test_parsed_completely(&good_v0_wire, &good_v0);
+    test_parsed_completely(&good_v1_wire, &good_v1);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_v1_length_wire, &bad_v1_length);
+
+    test_write(&good_v0, &good_v0_wire)?;
+    test_write(&good_v1, &good_v1_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_router_key() -> Result<(), RtrPduWritingError> {
+    let good_wire = [
+        0x01, 0x09, 0x01, 0x00, 0x00, 0x00, 0x00, 0x22, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x00, 0x00,
+        0xfb, 0xf0, 0x30, 0x59,
+    ];
+    let bad_v0_wire = [
+        0x00, 0x09, 0x01, 0x00, 0x00, 0x00, 0x00, 0x22, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x00, 0x00,
+        0xfb, 0xf0, 0x30, 0x59,
+    ];
+
+    let good = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::RouterKey(RouterKey::new(
+            true,
+            [
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+                0x0f, 0x10, 0x11, 0x12, 0x13, 0x14,
+            ],
+            64496,
+            vec![0x30, 0x59],
+        )),
+    );
+    let bad_v0 = LocatedRtrPduParsingError::new(
+        Span::new(&bad_v0_wire),
+        RtrPduParsingError::UnsupportedPduType(RtrVersion::V0, RtrPduType::RouterKey),
+    );
+
+    test_parsed_completely(&good_wire, &good);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_v0_wire, &bad_v0);
+
+    test_write(&good, &good_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_aspa() -> Result<(), RtrPduWritingError> {
+    let good_announce_wire = [
+        0x02, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
+        0xf0, 0x00, 0x00, 0xfb, 0xf1, 0x00, 0x00, 0xfb, 0xf2,
+    ];
+    let good_withdraw_wire = [
+        0x02, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
+        0xf0,
+    ];
+    let bad_v1_wire = [
+        0x01, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
+        0xf0,
+    ];
+    let bad_length_wire = [
+        0x02, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
+        0xf0, 0xfb, 0xf1,
+    ];
+
+    let good_announce = RtrPdu::new(
+        RtrVersion::V2,
+        RtrPduValue::Aspa(RtrAspa::new(true, 64496, vec![64497, 64498])),
+    );
+    let good_withdraw = RtrPdu::new(
+        RtrVersion::V2,
+        RtrPduValue::Aspa(RtrAspa::new(false, 64496, vec![])),
+    );
+    let bad_v1 = LocatedRtrPduParsingError::new(
+        Span::new(&bad_v1_wire),
+        RtrPduParsingError::UnsupportedPduType(RtrVersion::V1, RtrPduType::Aspa),
+    );
+    let bad_length = LocatedRtrPduParsingError::new(
+        Span::new(&bad_length_wire),
+        RtrPduParsingError::InvalidPduLength(RtrPduType::Aspa, 18),
+    );
+
+    test_parsed_completely(&good_announce_wire, &good_announce);
+    test_parsed_completely(&good_withdraw_wire, &good_withdraw);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_v1_wire, &bad_v1);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_length_wire, &bad_length);
+
+    test_write(&good_announce, &good_announce_wire)?;
+    test_write(&good_withdraw, &good_withdraw_wire)?;
+    Ok(())
+}
+
+#[test]
+fn test_error_report() -> Result<(), RtrPduWritingError> {
+    let good_wire = [
+        0x01, 0x0a, 0x00, 0x07, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0x08, 0x01, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x68, 0x69,
+    ];
+    let good_empty_wire = [
+        0x00, 0x0a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+    let bad_code_wire = [
+        0x00, 0x0a, 0x00, 0x63, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+    let bad_text_wire = [
+        0x00, 0x0a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0xff,
+    ];
+    let bad_trailing_wire = [
+        0x00, 0x0a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xff,
+    ];
+
+    let good = RtrPdu::new(
+        RtrVersion::V1,
+        RtrPduValue::ErrorReport(ErrorReport::new(
+            RtrErrorCode::DuplicateAnnouncementReceived,
+            vec![0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08],
+            "hi".to_string(),
+        )),
+    );
+    let good_empty = RtrPdu::new(
+        RtrVersion::V0,
+        RtrPduValue::ErrorReport(ErrorReport::new(
+            RtrErrorCode::NoDataAvailable,
+            vec![],
+            String::new(),
+        )),
+    );
+    let bad_code = LocatedRtrPduParsingError::new(
+        Span::new(&bad_code_wire),
+        RtrPduParsingError::UndefinedRtrErrorCode(UndefinedRtrErrorCode(99)),
+    );
+    let bad_text = LocatedRtrPduParsingError::new(
+        unsafe { Span::new_from_raw_offset(16, &bad_text_wire[16..]) },
+        RtrPduParsingError::InvalidErrorText(
+            "invalid utf-8 sequence of 1 bytes from index 0".to_string(),
+        ),
+    );
+    let bad_trailing = LocatedRtrPduParsingError::new(
+        unsafe { Span::new_from_raw_offset(16, &bad_trailing_wire[16..]) },
+        RtrPduParsingError::NomError(ErrorKind::NonEmpty),
+    );
+
+    test_parsed_completely(&good_wire, &good);
+    test_parsed_completely(&good_empty_wire, &good_empty);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_code_wire, &bad_code);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_text_wire, &bad_text);
+    test_parse_error::<RtrPdu, LocatedRtrPduParsingError<'_>>(&bad_trailing_wire, &bad_trailing);
+
+    test_write(&good, &good_wire)?;
+    test_write(&good_empty, &good_empty_wire)?;
+    Ok(())
+}